@@ -1,15 +1,21 @@
-use log::{debug, error};
+use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::constants::regex_black_list::REGEX_BLACK_LIST;
 use crate::interfaces::subconverter::{subconverter, SubconverterConfigBuilder, UploadStatus};
+use crate::models::extra_settings::{DEFAULT_PROBE_CONCURRENCY, DEFAULT_PROBE_TIMEOUT};
 use crate::models::ruleset::RulesetConfigs;
-use crate::models::{ProxyGroupConfigs, RegexMatchConfigs, SubconverterTarget};
+use crate::models::{
+    DedupKey, GroupTypeOverride, NameTemplate, ProxyGroupConfigs, ProxyType, RegexMatchConfigs,
+    SortKey, SubconverterTarget,
+};
 use crate::settings::external::ExternalSettings;
 use crate::settings::settings::init_settings;
 use crate::settings::{refresh_configuration, FromIni, FromIniWithDelimiter};
-use crate::utils::reg_valid;
+use crate::utils::file::load_content_async;
+use crate::utils::header::DEFAULT_OUTPUT_HEADER;
+use crate::utils::{acquire_conversion_slot, reg_valid};
 use crate::{RuleBases, Settings, TemplateArgs};
 
 #[cfg(target_arch = "wasm32")]
@@ -84,6 +90,9 @@ pub struct SubconverterQuery {
     pub groups: Option<String>,
     /// Ruleset contents
     pub ruleset: Option<String>,
+    /// Surge `[Rule]` section added to the rulesets, a URL or, for
+    /// authorized requests, a local file
+    pub surge_rules: Option<String>,
     /// External configuration file (optional)
     pub config: Option<String>,
 
@@ -109,6 +118,42 @@ pub struct SubconverterQuery {
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub append_type: Option<bool>,
+    /// Number nodes that share a name after renaming
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub append_index: Option<bool>,
+    /// Minimum number of digits of an appended index
+    pub index_digits: Option<usize>,
+    /// Text put between a name and its appended index
+    pub index_separator: Option<String>,
+    /// Name unnamed nodes and number duplicate names before renaming
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub normalize_names: Option<bool>,
+    /// Whether full-width punctuation in names becomes ASCII
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub normalize_punct: Option<bool>,
+    /// Whether known region names become their English name
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub transliterate: Option<bool>,
+    /// Template node names are replaced with, e.g. `{region} | {type}`
+    pub name_template: Option<String>,
+    /// Whether the name template runs after rename rules
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub name_template_after_rename: Option<bool>,
     /// Whether to remove old emoji and add new emoji
     #[serde(
         default,
@@ -121,6 +166,12 @@ pub struct SubconverterQuery {
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub add_emoji: Option<bool>,
+    /// Whether to pick emoji by GeoIP region of the server
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub emoji_geoip: Option<bool>,
     /// Whether to remove emoji
     #[serde(
         default,
@@ -133,6 +184,34 @@ pub struct SubconverterQuery {
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub list: Option<bool>,
+    /// Canonical SIP002 Shadowsocks links
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub sip002: Option<bool>,
+    /// Byte-identical output for identical input
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub deterministic: Option<bool>,
+    /// Reject Clash and sing-box output that does not parse back
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub validate: Option<bool>,
+    /// Give TLS nodes without a client fingerprint a random one
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub fp_random: Option<bool>,
+    /// Comma separated fingerprints random ones are picked from
+    pub fp_pool: Option<String>,
+    /// Seed of random fingerprint picks
+    pub fp_seed: Option<u64>,
     /// Sort nodes
     #[serde(
         default,
@@ -140,9 +219,108 @@ pub struct SubconverterQuery {
     )]
     pub sort: Option<bool>,
 
+    /// List the nodes of each group in node list order, defaults to `sort`
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub sort_group_members: Option<bool>,
+
     /// Sort Script
     pub sort_script: Option<String>,
 
+    /// Comma separated proxy types to drop, e.g. `ssr,http`
+    pub exclude_types: Option<String>,
+
+    /// Comma separated port rewrites, e.g. `443->8443,2000-2999->443`
+    pub port_rewrite: Option<String>,
+
+    /// Comma separated ports and ranges whose nodes are dropped
+    pub drop_ports: Option<String>,
+
+    /// Remove duplicate nodes
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub dedup: Option<bool>,
+
+    /// Dedup strategy: `endpoint`, `credential` or `transport`
+    pub dedup_key: Option<String>,
+
+    /// Drop nodes whose server does not accept a connection, honoured for
+    /// authorized requests only
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub probe: Option<bool>,
+
+    /// Maximum number of subscriptions fetched at once, capped at the
+    /// `max_concur_threads` setting
+    pub fetch_concurrency: Option<usize>,
+
+    /// Fail on malformed Clash proxy entries instead of skipping them
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub strict_parse: Option<bool>,
+
+    /// Keep the proxy groups of Clash inputs instead of regenerating them
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub keep_groups: Option<bool>,
+
+    /// Prefix nodes of `tag:LABEL,url` inputs with their label
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub prefix_labels: Option<bool>,
+
+    /// Clash core of Clash output, `meta` (default) or `vanilla`
+    pub clash_core: Option<String>,
+
+    /// Switch vanilla Clash output to Clash.Meta when nodes need it
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub upgrade_clash: Option<bool>,
+
+    /// Merge proxy groups sharing a name into the first one
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub collapse_groups: Option<bool>,
+
+    /// Fewest nodes a group filled from node matches keeps
+    pub min_group_size: Option<usize>,
+
+    /// Rewrite of the type of the configured proxy groups, `from->to`
+    pub group_type: Option<String>,
+
+    /// Comma separated groups keeping their type under `group_type`
+    pub group_type_exclude: Option<String>,
+
+    /// Maximum number of liveness probes running at once, at most
+    /// `max_probe_concurrency` of the settings
+    pub probe_concurrency: Option<usize>,
+
+    /// Liveness probe timeout in milliseconds, at most `max_probe_timeout`
+    /// of the settings
+    pub probe_timeout: Option<u32>,
+
+    /// Member of proxy groups that match no node, e.g. `DIRECT` or `REJECT`
+    pub group_fallback: Option<String>,
+
+    /// Built-in sort key, e.g. `name` or `port:desc`
+    pub sort_key: Option<String>,
+
     /// argFilterDeprecated
     #[serde(
         default,
@@ -152,6 +330,9 @@ pub struct SubconverterQuery {
 
     /// Information for filtering, rename, emoji addition
     pub rename: Option<String>,
+    /// Blocklist rejected by Clash and sing-box outputs, entries separated
+    /// by `|`, e.g. `QUIC|DOMAIN-SUFFIX,doubleclick.net`
+    pub block: Option<String>,
     /// Whether to enable TCP Fast Open
     #[serde(
         default,
@@ -164,12 +345,26 @@ pub struct SubconverterQuery {
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub udp: Option<bool>,
+    /// UDP over TCP version of nodes enabling it without naming one
+    pub uot_version: Option<u8>,
+    /// IP version preference of nodes declaring none, e.g. `ipv4-prefer`
+    pub ip_version: Option<String>,
+    /// sing-box and Clash.Meta multiplexing of nodes declaring none, e.g.
+    /// `h2mux,max_connections=4,brutal=50:100`
+    pub multiplex: Option<String>,
+    /// What happens to nodes the target cannot emit: skip, error or comment
+    pub on_unsupported: Option<String>,
+    /// Fallbacks for nodes the target lacks a feature of, e.g.
+    /// `trojan-go=trojan` or `all`
+    pub degrade: Option<String>,
     /// Whether to skip certificate verification
     #[serde(
         default,
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub scv: Option<bool>,
+    /// Which nodes `scv` applies to, `all` or `ip_only`
+    pub scv_policy: Option<String>,
     /// Whether to enable TLS 1.3
     #[serde(
         default,
@@ -190,6 +385,12 @@ pub struct SubconverterQuery {
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub strict: Option<bool>,
+    /// Whether Surge output keeps the managed config header of a Surge input
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub keep_managed: Option<bool>,
     /// Upload to gist
     #[serde(
         default,
@@ -207,12 +408,72 @@ pub struct SubconverterQuery {
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub script: Option<bool>,
+
+    /// Reference Clash nodes through `proxy-providers`
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub providers: Option<bool>,
+
+    /// Whether the Clash provider is health checked, `false` omits the block
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub provider_check: Option<bool>,
+
+    /// URL the Clash provider health check probes
+    pub provider_check_url: Option<String>,
+
+    /// Seconds between Clash provider health checks
+    pub provider_check_interval: Option<u32>,
+
+    /// Milliseconds a Clash.Meta provider health check may take
+    pub provider_check_timeout: Option<u32>,
+
+    /// Skip Clash provider health checks while the provider is unused
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub provider_check_lazy: Option<bool>,
+
+    /// Reference QuantumultX nodes through `[server_remote]`
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub server_remote: Option<bool>,
+
+    /// `block-quic` value of Surge 5 policies: `on`, `off` or `auto`
+    pub block_quic: Option<String>,
+
+    /// Tag of the generated QuantumultX server remote
+    pub remote_tag: Option<String>,
+
+    /// Update interval in seconds of generated QuantumultX remote resources
+    pub remote_interval: Option<u32>,
     #[serde(
         default,
         deserialize_with = "bool_deserializer::deserialize_option_bool"
     )]
     pub classic: Option<bool>,
 
+    /// Return what each stage did to the nodes as JSON instead of the config
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub explain: Option<bool>,
+
+    /// Prepend a comment with the generation time, counts and traffic
+    #[serde(
+        default,
+        deserialize_with = "bool_deserializer::deserialize_option_bool"
+    )]
+    pub header: Option<bool>,
+
     #[serde(
         default,
         deserialize_with = "bool_deserializer::deserialize_option_bool"
@@ -293,16 +554,16 @@ pub async fn sub_process(
     req_url: Option<String>,
     query: SubconverterQuery,
 ) -> Result<SubResponse, Box<dyn std::error::Error>> {
-    let mut global = Settings::current();
+    let mut global = Settings::snapshot();
 
     // not initialized, in wasm that's common for cold start.
     if global.pref_path.is_empty() {
         debug!("Global config not initialized, reloading");
         init_settings("").await?;
-        global = Settings::current();
+        global = Settings::snapshot();
     } else if global.reload_conf_on_request && !global.api_mode && !global.generator_mode {
         refresh_configuration().await;
-        global = Settings::current();
+        global = Settings::snapshot();
     }
 
     // Start building configuration
@@ -346,10 +607,10 @@ pub async fn sub_process(
         ));
     }
 
-    builder.update_interval(match query.interval {
-        Some(interval) => interval,
-        None => global.update_interval,
-    });
+    // Unset, the update policy of a Surge input or the settings applies
+    if let Some(interval) = query.interval {
+        builder.update_interval(interval);
+    }
     // Check if we should authorize the request, if we are in API mode
     #[cfg(not(feature = "js-runtime"))]
     let authorized = false;
@@ -358,7 +619,17 @@ pub async fn sub_process(
     let authorized =
         !global.api_mode || query.token.as_deref().unwrap_or_default() == global.api_access_token;
     builder.authorized(authorized);
-    builder.update_strict(query.strict.unwrap_or(global.update_strict));
+    builder.token(query.token.clone());
+    builder.request_query(
+        req_url
+            .as_deref()
+            .and_then(|url| url.split_once('?'))
+            .map(|(_, query)| query.to_string()),
+    );
+    if let Some(strict) = query.strict {
+        builder.update_strict(strict);
+    }
+    builder.keep_managed_config(query.keep_managed.unwrap_or_default());
 
     if query
         .include
@@ -407,6 +678,24 @@ pub async fn sub_process(
     template_args.request_params = query.clone();
 
     builder.append_proxy_type(query.append_type.unwrap_or(global.append_type));
+    builder.append_index(query.append_index.unwrap_or_default());
+    if let Some(digits) = query.index_digits {
+        builder.append_index_digits(digits);
+    }
+    if let Some(separator) = &query.index_separator {
+        builder.append_index_separator(separator);
+    }
+    builder.normalize_names(query.normalize_names.unwrap_or(global.normalize_names));
+    builder.normalize_punctuation(query.normalize_punct.unwrap_or_default());
+    builder.transliterate_regions(query.transliterate.unwrap_or_default());
+    builder.name_template(
+        query
+            .name_template
+            .as_deref()
+            .filter(|template| !template.is_empty())
+            .map(NameTemplate::new),
+    );
+    builder.name_template_after_rename(query.name_template_after_rename.unwrap_or_default());
 
     let mut arg_expand_rulesets = query.expand;
     if target.is_clash() && query.script.is_none() {
@@ -416,17 +705,215 @@ pub async fn sub_process(
     // flags
     builder.tfo(query.tfo.or(global.tfo_flag));
     builder.udp(query.udp.or(global.udp_flag));
+    builder.uot_version(query.uot_version);
+    if let Some(ip_version) = &query.ip_version {
+        match ip_version.parse() {
+            Ok(ip_version) => {
+                builder.ip_version(Some(ip_version));
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    if let Some(multiplex) = &query.multiplex {
+        match multiplex.parse() {
+            Ok(multiplex) => {
+                builder.multiplex(Some(multiplex));
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    if let Some(policy) = &query.on_unsupported {
+        match policy.parse() {
+            Ok(policy) => {
+                builder.on_unsupported(policy);
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    if let Some(degrade) = &query.degrade {
+        match degrade.parse() {
+            Ok(degrade) => {
+                builder.degrade(degrade);
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
     builder.skip_cert_verify(query.scv.or(global.skip_cert_verify));
+    match query.scv_policy.as_deref().map(str::parse) {
+        Some(Ok(policy)) => {
+            builder.skip_cert_verify_policy(policy);
+        }
+        Some(Err(e)) => return Ok(SubResponse::error(e, 400)),
+        None => {
+            builder.skip_cert_verify_policy(global.skip_cert_verify_policy);
+        }
+    }
     builder.tls13(query.tls13.or(global.tls13_flag));
     builder.sort(query.sort.unwrap_or(global.enable_sort));
+    builder.sort_group_members(query.sort_group_members);
     builder.sort_script(query.sort_script.unwrap_or(global.sort_script.clone()));
+    if let Some(types) = &query.exclude_types {
+        let types = types
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .filter_map(|name| match name.parse::<ProxyType>() {
+                Ok(proxy_type) => Some(proxy_type),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            })
+            .collect();
+        builder.exclude_types(types);
+    }
+    if let Some(rules) = &query.port_rewrite {
+        match rules.parse() {
+            Ok(rules) => {
+                builder.port_rewrite(rules);
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    if let Some(ports) = &query.drop_ports {
+        match ports.parse() {
+            Ok(ports) => {
+                builder.drop_ports(ports);
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    builder.dedup(query.dedup.unwrap_or_default());
+    if let Some(dedup_key) = query
+        .dedup_key
+        .as_deref()
+        .and_then(|key| key.parse::<DedupKey>().ok())
+    {
+        builder.dedup_key(dedup_key);
+    }
+    if let Some(concurrency) = query.fetch_concurrency {
+        builder.fetch_concurrency(concurrency);
+    }
+    builder.strict_parse(query.strict_parse.unwrap_or_default());
+    builder.keep_input_groups(query.keep_groups.unwrap_or_default());
+    builder.prefix_labels(query.prefix_labels.unwrap_or_default());
+    if let Some(core) = &query.clash_core {
+        match core.parse() {
+            Ok(core) => {
+                builder.clash_core(core);
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    builder.upgrade_clash_core(query.upgrade_clash.unwrap_or_default());
+    builder.collapse_groups(query.collapse_groups.unwrap_or_default());
+    builder.min_group_size(query.min_group_size.unwrap_or_default());
+    if let Some(group_type) = query.group_type.as_deref().filter(|s| !s.is_empty()) {
+        match group_type.parse::<GroupTypeOverride>() {
+            Ok(mut group_type) => {
+                group_type.exclude = query
+                    .group_type_exclude
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                builder.group_type_override(Some(group_type));
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
+    // Probing dials every node, only authorized requests may ask for it
+    builder.probe_liveness(authorized && query.probe.unwrap_or_default());
+    builder.explain(query.explain.unwrap_or_default());
+    if query.header.unwrap_or_default() {
+        builder.output_header(Some(DEFAULT_OUTPUT_HEADER.to_string()));
+    }
+    // Capped by the settings, defaults included
+    builder.probe_concurrency(
+        query
+            .probe_concurrency
+            .unwrap_or(DEFAULT_PROBE_CONCURRENCY)
+            .clamp(1, global.max_probe_concurrency.max(1)),
+    );
+    builder.probe_timeout(
+        query
+            .probe_timeout
+            .unwrap_or(DEFAULT_PROBE_TIMEOUT)
+            .min(global.max_probe_timeout),
+    );
+    if let Some(fallback) = &query.group_fallback {
+        builder.group_fallback(fallback);
+    }
+    if let Some(sort_key) = &query.sort_key {
+        match sort_key.parse::<SortKey>() {
+            Ok(sort_key) => {
+                builder.sort_key(Some(sort_key));
+            }
+            Err(e) => return Ok(SubResponse::error(e, 400)),
+        }
+    }
 
     builder.filter_deprecated(query.fdn.unwrap_or(global.filter_deprecated));
     builder.clash_new_field_name(query.new_name.unwrap_or(global.clash_use_new_field));
     builder.clash_script(query.script.unwrap_or_default());
+    let use_providers = query.providers.unwrap_or_default();
+    builder.clash_use_providers(use_providers);
+    let mut provider_check = global.provider_health_check.clone();
+    if let Some(enable) = query.provider_check {
+        provider_check.enable = enable;
+    }
+    if let Some(url) = query
+        .provider_check_url
+        .as_ref()
+        .filter(|url| !url.is_empty())
+    {
+        provider_check.url = url.clone();
+    }
+    if let Some(interval) = query
+        .provider_check_interval
+        .filter(|interval| *interval > 0)
+    {
+        provider_check.interval = interval;
+    }
+    if let Some(timeout) = query.provider_check_timeout {
+        provider_check.timeout = Some(timeout).filter(|timeout| *timeout > 0);
+    }
+    provider_check.lazy = query.provider_check_lazy.or(provider_check.lazy);
+    builder.provider_health_check(provider_check);
+    let use_server_remote = query.server_remote.unwrap_or_default();
+    builder.quanx_server_remote(use_server_remote);
+    if use_providers || use_server_remote {
+        builder.managed_config_prefix(global.managed_config_prefix.clone());
+    }
+    if let Some(block_quic) = &query.block_quic {
+        builder.surge_block_quic(block_quic);
+    }
+    if let Some(tag) = &query.remote_tag {
+        builder.quanx_remote_tag(tag);
+    }
+    if let Some(interval) = query.remote_interval {
+        builder.quanx_remote_interval(interval);
+    }
     builder.clash_classical_ruleset(query.classic.unwrap_or_default());
     let nodelist = query.list.unwrap_or_default();
     builder.nodelist(nodelist);
+    builder.ss_sip002(query.sip002.unwrap_or_default());
+    builder.group_health_check(global.group_health_check.clone());
+    builder.deterministic(query.deterministic.unwrap_or_default());
+    builder.validate_output(query.validate.unwrap_or_default());
+    builder.random_fingerprint(query.fp_random.unwrap_or_default());
+    if let Some(pool) = &query.fp_pool {
+        builder.fingerprint_pool(
+            pool.split(',')
+                .map(str::trim)
+                .filter(|fp| !fp.is_empty())
+                .map(str::to_string)
+                .collect(),
+        );
+    }
+    builder.fingerprint_seed(query.fp_seed);
 
     if arg_expand_rulesets != Some(true) {
         builder.clash_new_field_name(true);
@@ -545,9 +1032,22 @@ pub async fn sub_process(
     if let Some(add_emoji) = query.add_emoji {
         builder.add_emoji(add_emoji);
     }
+    if let Some(emoji_geoip) = query.emoji_geoip {
+        builder.emoji_by_geoip(emoji_geoip);
+    }
     if let Some(remove_emoji) = query.remove_emoji {
         builder.remove_emoji(remove_emoji);
     }
+    if let Some(block) = query.block.as_deref() {
+        builder.block_rules(
+            block
+                .split('|')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_string)
+                .collect(),
+        );
+    }
     if let Some(rename) = query.rename.as_deref() {
         if !rename.is_empty() {
             let v_array: Vec<String> = rename.split('`').map(|s| s.to_string()).collect();
@@ -584,6 +1084,35 @@ pub async fn sub_process(
     builder.proxy_groups(custom_group_configs);
     builder.ruleset_configs(ruleset_configs);
 
+    if !target.is_simple() && !nodelist {
+        let surge_rules = match query.surge_rules.as_deref().filter(|path| !path.is_empty()) {
+            Some(path)
+                if authorized || path.starts_with("http://") || path.starts_with("https://") =>
+            {
+                path
+            }
+            Some(path) => {
+                warn!(
+                    "Ignoring local Surge rule section '{}' of an unauthorized request",
+                    path
+                );
+                ""
+            }
+            None => global.surge_rule_section.as_str(),
+        };
+        if !surge_rules.is_empty() {
+            match load_content_async(surge_rules).await {
+                Ok(content) => {
+                    builder.surge_rule_section(&content);
+                }
+                Err(e) => error!(
+                    "Failed to load Surge rule section from {}: {}",
+                    surge_rules, e
+                ),
+            }
+        }
+    }
+
     // TODO: process with the script runtime
 
     // parse settings
@@ -599,10 +1128,11 @@ pub async fn sub_process(
         builder.filter_script(Some(filter));
     }
 
-    // // Process device ID
-    // if let Some(dev_id) = &query.dev_id {
-    //     builder.device_id(Some(dev_id.clone()));
-    // }
+    // Process device ID
+    let dev_id = query.dev_id.clone().unwrap_or(global.quanx_dev_id.clone());
+    if !dev_id.is_empty() {
+        builder.device_id(Some(dev_id));
+    }
 
     // // Set managed config prefix from global settings
     // if !global.managed_config_prefix.is_empty() {
@@ -625,6 +1155,16 @@ pub async fn sub_process(
         }
     };
 
+    // Bound simultaneous conversions, they share the CPU of hosted instances
+    let _slot = match acquire_conversion_slot().await {
+        Ok(slot) => slot,
+        Err(e) => {
+            warn!("Rejecting conversion: {}", e);
+            let headers = HashMap::from([("Retry-After".to_string(), "1".to_string())]);
+            return Ok(SubResponse::error(e.to_string(), 503).with_headers(headers));
+        }
+    };
+
     // Run subconverter directly instead of spawning a thread
     // This is necessary for WebAssembly compatibility
     debug!("Running subconverter with config: {:?}", config);
@@ -637,11 +1177,20 @@ pub async fn sub_process(
                 SubconverterTarget::Clash
                 | SubconverterTarget::ClashR
                 | SubconverterTarget::SingBox => "application/yaml",
-                SubconverterTarget::SSSub | SubconverterTarget::SSD => "application/json",
+                SubconverterTarget::SSSub
+                | SubconverterTarget::SSD
+                | SubconverterTarget::NodeJson
+                | SubconverterTarget::V2RayJson => "application/json",
                 _ => "text/plain",
             };
 
             debug!("Subconverter completed successfully");
+            if let Some(report) = result.explain {
+                return match serde_json::to_string_pretty(&report) {
+                    Ok(json) => Ok(SubResponse::ok(json, "application/json".to_string())),
+                    Err(e) => Ok(SubResponse::error(e.to_string(), 500)),
+                };
+            }
             Ok(SubResponse::ok(result.content, content_type.to_string())
                 .with_headers(result.headers)
                 .with_upload_status(result.upload_status))
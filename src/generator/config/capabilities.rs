@@ -0,0 +1,193 @@
+//! Options each target accepts, per protocol
+//!
+//! Clients reject configs carrying options they do not know, so emitters
+//! ask [`target_supports`] before writing an option a target may lack and
+//! drop it otherwise. Adding a target or an option means adding its row to
+//! the match below.
+
+use log::{debug, warn};
+
+use crate::models::{ClashCore, Proxy, ProxyType, SubconverterTarget};
+
+/// Options not every target accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// TCP Fast Open, `tfo`, `fast-open` or `tcp_fast_open`
+    TcpFastOpen,
+    /// VMess with a non-zero `alterId`, the MD5 authenticated header that
+    /// predates AEAD
+    LegacyVMess,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::TcpFastOpen => "tfo",
+            Capability::LegacyVMess => "alterId",
+        }
+    }
+}
+
+/// Protocols running over QUIC or plain UDP, TCP options do not apply
+fn is_udp_based(proxy_type: ProxyType) -> bool {
+    matches!(
+        proxy_type,
+        ProxyType::Hysteria | ProxyType::Hysteria2 | ProxyType::Tuic | ProxyType::WireGuard
+    )
+}
+
+/// Whether a target accepts an option on nodes of a protocol
+///
+/// Nodes of registered codecs are [`ProxyType::Unknown`] and count as TCP
+/// nodes. The `clash` rows describe Clash.Meta, see [`clash_core_supports`]
+/// for the vanilla core.
+pub fn target_supports(
+    target: &SubconverterTarget,
+    capability: Capability,
+    proxy_type: ProxyType,
+) -> bool {
+    match capability {
+        Capability::TcpFastOpen => match target {
+            // Clash.Meta takes `tfo` on TCP proxies and uses `fast-open` of
+            // Hysteria for its own handshake shortcut, the ClashR core has
+            // neither
+            SubconverterTarget::Clash => {
+                !is_udp_based(proxy_type)
+                    || matches!(proxy_type, ProxyType::Hysteria | ProxyType::Hysteria2)
+            }
+            SubconverterTarget::ClashR => false,
+            SubconverterTarget::Surge(_)
+            | SubconverterTarget::Surfboard
+            | SubconverterTarget::QuantumultX
+            | SubconverterTarget::Loon
+            | SubconverterTarget::Mellow
+            | SubconverterTarget::SingBox => !is_udp_based(proxy_type),
+            _ => false,
+        },
+        // Xray-core refuses the legacy header and V2Ray 5 only allows it
+        // behind an environment variable, every other client still speaks it
+        Capability::LegacyVMess => {
+            proxy_type == ProxyType::VMess && !matches!(target, SubconverterTarget::V2RayJson)
+        }
+    }
+}
+
+/// Whether a Clash core accepts an option on nodes of a protocol
+pub fn clash_core_supports(core: ClashCore, capability: Capability, proxy_type: ProxyType) -> bool {
+    match (core, capability) {
+        // `tfo` is a Clash.Meta addition, the vanilla core rejects it
+        (ClashCore::Vanilla, Capability::TcpFastOpen) => false,
+        _ => target_supports(&SubconverterTarget::Clash, capability, proxy_type),
+    }
+}
+
+/// The TCP Fast Open flag a node is emitted with for a target
+///
+/// `tfo` is the flag after defaults are applied, it is dropped with a debug
+/// message when the target does not accept it on the node.
+pub fn supported_tfo(target: &SubconverterTarget, node: &Proxy, tfo: Option<bool>) -> Option<bool> {
+    let supported = target_supports(target, Capability::TcpFastOpen, node.proxy_type);
+    keep_tfo(supported, &target.to_str(), node, tfo)
+}
+
+/// [`supported_tfo`] for a Clash export written for `core`
+pub fn supported_clash_tfo(core: ClashCore, node: &Proxy, tfo: Option<bool>) -> Option<bool> {
+    let supported = clash_core_supports(core, Capability::TcpFastOpen, node.proxy_type);
+    keep_tfo(supported, &format!("clash ({})", core.as_str()), node, tfo)
+}
+
+fn keep_tfo(supported: bool, target: &str, node: &Proxy, tfo: Option<bool>) -> Option<bool> {
+    if tfo.is_none() || supported {
+        return tfo;
+    }
+    debug!(
+        "Dropping {} of {} node '{}': {} does not support it",
+        Capability::TcpFastOpen.as_str(),
+        target,
+        node.remark,
+        node.proxy_type.to_string()
+    );
+    None
+}
+
+/// Warn when a VMess node relies on the legacy header a target dropped
+///
+/// The node is still emitted with its `alterId`, the server decides whether
+/// AEAD is accepted.
+pub fn warn_legacy_vmess(target: &SubconverterTarget, node: &Proxy) {
+    if node.proxy_type != ProxyType::VMess
+        || node.alter_id == 0
+        || target_supports(target, Capability::LegacyVMess, node.proxy_type)
+    {
+        return;
+    }
+    warn!(
+        "VMess node '{}' has {} {}, {} clients only connect with AEAD (alterId 0)",
+        node.remark,
+        Capability::LegacyVMess.as_str(),
+        node.alter_id,
+        target.to_str()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tfo_matrix() {
+        let tuic = Proxy {
+            proxy_type: ProxyType::Tuic,
+            ..Default::default()
+        };
+        let trojan = Proxy {
+            proxy_type: ProxyType::Trojan,
+            ..Default::default()
+        };
+        assert_eq!(
+            supported_tfo(&SubconverterTarget::Surge(4), &trojan, Some(true)),
+            Some(true)
+        );
+        assert_eq!(
+            supported_tfo(&SubconverterTarget::Surge(4), &tuic, Some(true)),
+            None
+        );
+        assert_eq!(
+            supported_tfo(&SubconverterTarget::ClashR, &trojan, Some(false)),
+            None
+        );
+        assert!(target_supports(
+            &SubconverterTarget::Clash,
+            Capability::TcpFastOpen,
+            ProxyType::Hysteria2
+        ));
+        assert!(!target_supports(
+            &SubconverterTarget::SingBox,
+            Capability::TcpFastOpen,
+            ProxyType::Hysteria2
+        ));
+        assert_eq!(
+            supported_tfo(&SubconverterTarget::ClashR, &trojan, None),
+            None
+        );
+        assert_eq!(
+            supported_clash_tfo(ClashCore::Meta, &trojan, Some(true)),
+            Some(true)
+        );
+        assert_eq!(
+            supported_clash_tfo(ClashCore::Vanilla, &trojan, Some(true)),
+            None
+        );
+
+        assert!(target_supports(
+            &SubconverterTarget::SingBox,
+            Capability::LegacyVMess,
+            ProxyType::VMess
+        ));
+        assert!(!target_supports(
+            &SubconverterTarget::V2RayJson,
+            Capability::LegacyVMess,
+            ProxyType::VMess
+        ));
+    }
+}
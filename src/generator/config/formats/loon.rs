@@ -1,14 +1,16 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::capabilities::supported_tfo;
+use crate::generator::config::group::group_generate_members;
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
-    BalanceStrategy, ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType,
-    RulesetContent,
+    trojan_go_skip_reason, BalanceStrategy, Degrade, ExtraSettings, Proxy, ProxyGroupConfigs,
+    ProxyGroupType, ProxyType, RulesetContent, SubconverterTarget, TrojanGoSupport,
 };
 use crate::utils::ini_reader::IniReader;
+use crate::utils::network::join_host_port;
 use crate::utils::string::join;
-use log::error;
-use std::collections::HashMap;
+use log::{error, warn};
+use std::collections::{HashMap, HashSet};
 
 /// Convert proxies to Loon format
 ///
@@ -65,6 +67,12 @@ pub async fn proxy_to_loon(
         let mut remark = node.remark.clone();
         process_remark(&mut remark, &remarks_list, false);
 
+        // Trojan lines carry no transport, a Trojan-Go node with one is
+        // emitted as plain Trojan when the fallback is enabled
+        if trojan_go_skip_reason(node, TrojanGoSupport::PLAIN).is_some() {
+            ext.degrade_node("Loon", node, Degrade::TrojanGo);
+        }
+
         // Extract node properties for easier access
         let hostname = &node.hostname;
         let port = node.port.to_string();
@@ -82,9 +90,16 @@ pub async fn proxy_to_loon(
         let plugin = node.plugin.as_deref().unwrap_or("");
         let pluginopts = node.plugin_option.as_deref().unwrap_or("");
         let tls_secure = node.tls_secure;
+        // Prefer an explicit SNI, the host header is only a fallback
+        let tls_name = node
+            .sni
+            .as_deref()
+            .or(node.server_name.as_deref())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(host);
 
         // Define tribool values with defaults from ext and override with node-specific values
-        let scv = ext.skip_cert_verify;
+        let scv = ext.default_skip_cert_verify(node);
         let scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
 
         let mut proxy;
@@ -105,6 +120,11 @@ pub async fn proxy_to_loon(
                         proxy.push_str(&format!(",{}", plugin_opts));
                     }
                 } else if !plugin.is_empty() {
+                    ext.skip_node(
+                        "Loon",
+                        &node.remark,
+                        format!("unsupported plugin '{}'", plugin),
+                    );
                     continue;
                 }
             }
@@ -124,8 +144,8 @@ pub async fn proxy_to_loon(
                     if tls_secure { "true" } else { "false" }
                 );
 
-                if tls_secure {
-                    proxy.push_str(&format!(",tls-name={}", host));
+                if tls_secure && !tls_name.is_empty() {
+                    proxy.push_str(&format!(",tls-name={}", tls_name));
                 }
 
                 match transproto {
@@ -177,8 +197,8 @@ pub async fn proxy_to_loon(
                     }
                 }
 
-                if !host.is_empty() {
-                    proxy.push_str(&format!(",tls-name={}", host));
+                if !tls_name.is_empty() {
+                    proxy.push_str(&format!(",tls-name={}", tls_name));
                 }
 
                 if scv.is_some() {
@@ -193,10 +213,14 @@ pub async fn proxy_to_loon(
                 }
             }
             ProxyType::Trojan => {
+                if let Some(reason) = trojan_go_skip_reason(node, TrojanGoSupport::PLAIN) {
+                    ext.skip_node("Loon", &node.remark, reason);
+                    continue;
+                }
                 proxy = format!("trojan,{},{},\"{}\"", hostname, port, password);
 
-                if !host.is_empty() {
-                    proxy.push_str(&format!(",tls-name={}", host));
+                if !tls_name.is_empty() {
+                    proxy.push_str(&format!(",tls-name={}", tls_name));
                 }
 
                 if scv.is_some() {
@@ -276,16 +300,27 @@ pub async fn proxy_to_loon(
                 // Add peer info
                 proxy.push_str(&format!(", peers=[{{{}}}]", generate_peer(node, true)));
             }
-            _ => continue,
+            _ => {
+                ext.skip_node(
+                    "Loon",
+                    &node.remark,
+                    format!("{} is not supported", node.proxy_type.to_string()),
+                );
+                continue;
+            }
         }
 
-        // Add fast-open option if enabled
-        if ext.tfo.unwrap_or(false) {
+        // Node values take precedence over the export defaults
+        let tfo = supported_tfo(
+            &SubconverterTarget::Loon,
+            node,
+            node.tcp_fast_open.or(ext.tfo),
+        );
+        if tfo.unwrap_or(false) {
             proxy.push_str(",fast-open=true");
         }
 
-        // Add UDP option if enabled
-        if ext.udp.unwrap_or(false) {
+        if node.udp.or(ext.udp).unwrap_or(false) {
             proxy.push_str(",udp=true");
         }
 
@@ -293,12 +328,19 @@ pub async fn proxy_to_loon(
         if ext.nodelist {
             output_nodelist.push_str(&format!("{} = {}\n", remark, proxy));
         } else {
-            ini.set("{NONAME}", &format!("{} = {}", remark, proxy), "")
+            ini.set_current("{NONAME}", &format!("{} = {}", remark, proxy))
                 .unwrap_or(());
             nodelist.push(node.clone());
             remarks_list.push(remark);
         }
     }
+    for line in ext.unsupported_comments("Loon", "#") {
+        if ext.nodelist {
+            output_nodelist.push_str(&format!("{}\n", line));
+        } else {
+            ini.set_current("{NONAME}", &line).unwrap_or(());
+        }
+    }
 
     if ext.nodelist {
         return output_nodelist;
@@ -309,8 +351,9 @@ pub async fn proxy_to_loon(
     ini.set_current_section("Proxy Group");
     ini.erase_section();
 
+    let (group_members, omitted_groups) = loon_group_members(&nodelist, extra_proxy_group, ext);
+
     for group in extra_proxy_group {
-        let mut filtered_nodelist = Vec::new();
         let mut group_str;
 
         match group.group_type {
@@ -326,21 +369,17 @@ pub async fn proxy_to_loon(
                 }
                 group_str = format!("{},default={},", group.type_str(), group.proxies[0]);
                 group_str.push_str(&join(&group.proxies[1..], ","));
-                ini.set("{NONAME}", &format!("{} = {}", group.name, group_str), "")
+                ini.set_current("{NONAME}", &format!("{} = {}", group.name, group_str))
                     .unwrap_or(());
                 continue;
             }
             _ => continue,
         }
 
-        // Generate filtered proxy list
-        for proxy_name in &group.proxies {
-            group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-        }
-
-        if filtered_nodelist.is_empty() {
-            filtered_nodelist.push("DIRECT".to_string());
-        }
+        // Loon rejects groups without members, so they are omitted
+        let Some(filtered_nodelist) = group_members.get(&group.name) else {
+            continue;
+        };
 
         // Update original_groups handling to extract image URLs
         // In C++ original_groups is loaded from the INI and checked for image-url values
@@ -358,7 +397,7 @@ pub async fn proxy_to_loon(
 
         // Build group string
         group_str = format!("{},", group.type_str());
-        group_str.push_str(&join(&filtered_nodelist, ","));
+        group_str.push_str(&join(filtered_nodelist, ","));
 
         // Ensure proper order for group fields to match C++ implementation
         if group.group_type != ProxyGroupType::Select {
@@ -392,12 +431,33 @@ pub async fn proxy_to_loon(
             group_str.push_str(&format!(",{}", img_url));
         }
 
-        ini.set("{NONAME}", &format!("{} = {}", group.name, group_str), "")
+        ini.set_current("{NONAME}", &format!("{} = {}", group.name, group_str))
             .unwrap_or(());
     }
 
     // Generate rules if enabled
     if ext.enable_rule_generator {
+        // Rules must not point at an omitted group
+        let mut kept_rulesets;
+        let ruleset_content_array = if omitted_groups.is_empty() {
+            ruleset_content_array
+        } else {
+            kept_rulesets = ruleset_content_array
+                .iter()
+                .filter(|ruleset| {
+                    let omitted = omitted_groups.contains(&ruleset.group);
+                    if omitted {
+                        warn!(
+                            "Dropping Loon ruleset '{}' of omitted group '{}'",
+                            ruleset.rule_path, ruleset.group
+                        );
+                    }
+                    !omitted
+                })
+                .cloned()
+                .collect();
+            &mut kept_rulesets
+        };
         ruleset_to_loon(
             &mut ini,
             ruleset_content_array,
@@ -410,6 +470,58 @@ pub async fn proxy_to_loon(
     ini.to_string()
 }
 
+/// Resolve the members of every selectable group, returning them with the
+/// names of the groups left out as empty
+///
+/// Groups matching no node get the group fallback. Only with an empty
+/// fallback can a group stay empty; a group whose members are all omitted
+/// groups is empty as well, so references to omitted groups are removed
+/// until nothing changes.
+fn loon_group_members(
+    nodelist: &[Proxy],
+    extra_proxy_group: &ProxyGroupConfigs,
+    ext: &ExtraSettings,
+) -> (HashMap<String, Vec<String>>, HashSet<String>) {
+    let mut members: HashMap<String, Vec<String>> = extra_proxy_group
+        .iter()
+        .filter(|group| {
+            matches!(
+                group.group_type,
+                ProxyGroupType::Select
+                    | ProxyGroupType::LoadBalance
+                    | ProxyGroupType::URLTest
+                    | ProxyGroupType::Fallback
+            )
+        })
+        .map(|group| {
+            let filtered_nodelist =
+                group_generate_members(group, nodelist, true, &ext.group_fallback, ext);
+            (group.name.clone(), filtered_nodelist)
+        })
+        .collect();
+
+    let mut omitted: HashSet<String> = HashSet::new();
+    loop {
+        let empty: Vec<String> = members
+            .iter()
+            .filter(|(_, list)| list.is_empty())
+            .map(|(name, _)| name.clone())
+            .collect();
+        if empty.is_empty() {
+            break;
+        }
+        for name in empty {
+            warn!("Loon proxy group '{}' has no members, omitting it", name);
+            members.remove(&name);
+            omitted.insert(name);
+        }
+        for list in members.values_mut() {
+            list.retain(|member| !omitted.contains(member));
+        }
+    }
+    (members, omitted)
+}
+
 /// Generate a WireGuard peer configuration string
 ///
 /// # Arguments
@@ -425,7 +537,10 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
         peer.push_str(&format!("public-key={}", public_key));
     }
 
-    peer.push_str(&format!(", endpoint={}:{}", node.hostname, node.port));
+    peer.push_str(&format!(
+        ", endpoint={}",
+        join_host_port(&node.hostname, node.port)
+    ));
 
     if !node.allowed_ips.is_empty() {
         peer.push_str(&format!(", allowed-ips={}", node.allowed_ips));
@@ -459,3 +574,90 @@ async fn ruleset_to_loon(
     )
     .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ruleset::parse_ruleset;
+    use crate::models::ProxyGroupConfig;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_loon_nodes_and_groups() {
+        let mut nodes = vec![
+            Proxy {
+                proxy_type: ProxyType::Trojan,
+                remark: "Trojan".to_string(),
+                hostname: "trojan.example.com".to_string(),
+                port: 443,
+                password: Some("secret".to_string()),
+                sni: Some("sni.example.com".to_string()),
+                udp: Some(false),
+                ..Default::default()
+            },
+            Proxy {
+                proxy_type: ProxyType::Shadowsocks,
+                remark: "SS".to_string(),
+                hostname: "1.2.3.4".to_string(),
+                port: 8388,
+                password: Some("pass".to_string()),
+                encrypt_method: Some("aes-128-gcm".to_string()),
+                ..Default::default()
+            },
+        ];
+        let mut empty = ProxyGroupConfig::new("Empty".to_string(), ProxyGroupType::Select);
+        empty.proxies = vec!["^Nothing$".to_string()];
+        let mut nested = ProxyGroupConfig::new("Nested".to_string(), ProxyGroupType::Select);
+        nested.proxies = vec!["[]Empty".to_string()];
+        let mut select = ProxyGroupConfig::new("Proxy".to_string(), ProxyGroupType::Select);
+        select.proxies = vec!["[]Nested".to_string(), ".*".to_string()];
+
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        ext.udp = Some(true);
+        ext.skip_cert_verify = Some(true);
+
+        let groups = vec![empty, nested, select];
+        let output = block_on(proxy_to_loon(
+            &mut nodes.clone(),
+            "[General]\nskip-proxy = 127.0.0.1\n",
+            &mut Vec::new(),
+            &groups,
+            &mut ext,
+        ));
+        assert!(output.contains("Empty = select,DIRECT\n"));
+        assert!(output.contains("Nested = select,Empty\n"));
+        assert!(output.contains("Proxy = select,Nested,Trojan,SS\n"));
+
+        // Without a fallback empty groups and their rules are left out
+        ext.group_fallback = String::new();
+        ext.enable_rule_generator = true;
+        let mut rulesets = vec![
+            parse_ruleset("[]DOMAIN,empty.example.com", "Empty"),
+            parse_ruleset("[]FINAL", "Proxy"),
+        ];
+        let output = block_on(proxy_to_loon(
+            &mut nodes,
+            "[General]\nskip-proxy = 127.0.0.1\n",
+            &mut rulesets,
+            &groups,
+            &mut ext,
+        ));
+
+        assert!(output.contains(
+            "Trojan = trojan,trojan.example.com,443,\"secret\",tls-name=sni.example.com,skip-cert-verify=true\n"
+        ));
+        assert!(output.contains("SS = Shadowsocks,1.2.3.4,8388,aes-128-gcm,\"pass\",udp=true\n"));
+        assert!(output.contains("Proxy = select,Trojan,SS\n"));
+        assert!(!output.contains("Empty ="));
+        assert!(!output.contains("Nested ="));
+        assert!(!output.contains("empty.example.com"));
+        assert!(output.contains("FINAL,Proxy"));
+    }
+}
@@ -1,11 +1,14 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::capabilities::supported_tfo;
+use crate::generator::config::group::group_generate_members;
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    is_ss2022_cipher, ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType,
+    RulesetContent, SubconverterTarget,
 };
 use crate::utils::base64::url_safe_base64_encode;
 use crate::utils::ini_reader::IniReader;
+use crate::utils::network::join_host_port;
 use crate::utils::string::{hash, join};
 use crate::utils::tribool::BoolTriboolExt;
 use crate::utils::url::url_encode;
@@ -111,10 +114,11 @@ async fn proxy_to_mellow_internal(
 
         // Get option values with defaults from ext
         let mut tfo = ext.tfo;
-        let mut scv = ext.skip_cert_verify;
+        let mut scv = ext.default_skip_cert_verify(node);
 
         // Override with node-specific values if present
         tfo = node.tcp_fast_open.as_ref().map_or(tfo, |val| Some(*val));
+        tfo = supported_tfo(&SubconverterTarget::Mellow, node, tfo);
         scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
 
         let mut _proxy_str: String = String::new();
@@ -122,23 +126,36 @@ async fn proxy_to_mellow_internal(
         // Format proxy string based on proxy type
         match node.proxy_type {
             ProxyType::Shadowsocks => {
-                // Skip if plugin is not empty
+                if is_ss2022_cipher(method) {
+                    ext.skip_node(
+                        "Mellow",
+                        &node.remark,
+                        format!("cipher {} is not supported", method),
+                    );
+                    continue;
+                }
                 if !plugin.is_empty() {
+                    ext.skip_node(
+                        "Mellow",
+                        &node.remark,
+                        format!("unsupported plugin '{}'", plugin),
+                    );
                     continue;
                 }
 
                 _proxy_str = format!(
-                    "{}, ss, ss://{}/{}:{}",
+                    "{}, ss, ss://{}/{}",
                     node.remark,
                     url_safe_base64_encode(&format!("{}:{}", method, password)),
-                    hostname,
-                    port
+                    join_host_port(hostname, node.port)
                 );
             }
             ProxyType::VMess => {
                 _proxy_str = format!(
-                    "{}, vmess1, vmess1://{}@{}:{}",
-                    node.remark, id, hostname, port
+                    "{}, vmess1, vmess1://{}@{}",
+                    node.remark,
+                    id,
+                    join_host_port(hostname, node.port)
                 );
 
                 // Add path if not empty
@@ -218,7 +235,14 @@ async fn proxy_to_mellow_internal(
                     node.remark, hostname, port, username, password
                 );
             }
-            _ => continue,
+            _ => {
+                ext.skip_node(
+                    "Mellow",
+                    &node.remark,
+                    format!("{} is not supported", node.proxy_type.to_string()),
+                );
+                continue;
+            }
         }
 
         // Add to INI
@@ -226,6 +250,9 @@ async fn proxy_to_mellow_internal(
         remarks_list.push(node.remark.clone());
         nodelist.push(node.clone());
     }
+    for line in ext.unsupported_comments("Mellow", "#") {
+        ini.set_current("{NONAME}", &line).unwrap_or(());
+    }
 
     // Process endpoint groups
     ini.set_current_section("EndpointGroup");
@@ -238,17 +265,15 @@ async fn proxy_to_mellow_internal(
             | ProxyGroupType::Fallback
             | ProxyGroupType::LoadBalance => {
                 // Generate node list
-                let mut filtered_nodelist = Vec::new();
-
-                // Process each proxy in the group
-                for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, false, ext);
-                }
+                let mut filtered_nodelist =
+                    group_generate_members(group, &nodelist, false, "", ext);
 
                 // Use default if filtered list is empty
                 if filtered_nodelist.is_empty() {
                     if remarks_list.is_empty() {
-                        filtered_nodelist.push("DIRECT".to_string());
+                        if !ext.group_fallback.is_empty() {
+                            filtered_nodelist.push(ext.group_fallback.clone());
+                        }
                     } else {
                         filtered_nodelist = remarks_list.clone();
                     }
@@ -1,5 +1,6 @@
 pub mod loon;
 pub mod mellow;
+pub mod node_json;
 pub mod quan;
 pub mod quanx;
 pub mod singbox;
@@ -7,3 +8,4 @@ pub mod single;
 pub mod ss_sub;
 pub mod ssd;
 pub mod surge;
+pub mod v2ray_json;
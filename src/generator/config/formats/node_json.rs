@@ -0,0 +1,310 @@
+//! Structured JSON node list output
+//!
+//! Serializes processed nodes to a stable schema for programmatic consumers.
+//! Field names are fixed per [`NODE_JSON_SCHEMA_VERSION`]: new fields may be
+//! added within a version, renames or removals bump it.
+
+use serde::Serialize;
+
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::{ExtraSettings, Proxy, ProxyType};
+use crate::utils::tribool::TriboolExt;
+
+/// Version of the node JSON schema
+pub const NODE_JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Top level document of the node JSON output
+#[derive(Debug, Serialize)]
+pub struct NodeJsonDocument {
+    /// Schema version, see [`NODE_JSON_SCHEMA_VERSION`]
+    pub version: u32,
+    /// Nodes in output order
+    pub nodes: Vec<NodeJson>,
+}
+
+/// One node with normalized field names
+///
+/// Fields that do not apply to a node are omitted.
+#[derive(Debug, Default, Serialize)]
+pub struct NodeJson {
+    pub name: String,
+    /// Lowercase protocol name, e.g. `ss`, `vmess`, `hysteria2`
+    #[serde(rename = "type")]
+    pub node_type: String,
+    pub group: String,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alter_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin_opts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_param: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs_param: Option<String>,
+    /// Transport network, e.g. `tcp`, `ws`, `grpc`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Path, or the service name for `grpc`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// gRPC stream mode, `gun` or `multi`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_mode: Option<String>,
+    pub tls: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alpn: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality_public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality_short_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_shared_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ports: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up_mbps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_mbps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+    /// Trojan-Go multiplexing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mux: Option<bool>,
+    /// Trojan-Go Shadowsocks layer as `ss;method:password`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<String>,
+}
+
+/// Lowercase schema name of a proxy type
+pub fn node_json_type_name(proxy_type: ProxyType) -> &'static str {
+    match proxy_type {
+        ProxyType::Shadowsocks => "ss",
+        ProxyType::ShadowsocksR => "ssr",
+        ProxyType::VMess => "vmess",
+        ProxyType::Trojan => "trojan",
+        ProxyType::Snell => "snell",
+        ProxyType::HTTP => "http",
+        ProxyType::HTTPS => "https",
+        ProxyType::Socks5 => "socks5",
+        ProxyType::WireGuard => "wireguard",
+        ProxyType::Hysteria => "hysteria",
+        ProxyType::Hysteria2 => "hysteria2",
+        ProxyType::Vless => "vless",
+        ProxyType::AnyTls => "anytls",
+        ProxyType::Tuic => "tuic",
+        ProxyType::Unknown => "unknown",
+    }
+}
+
+/// Keep a string only if it is not empty
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.clone().filter(|v| !v.is_empty())
+}
+
+impl NodeJson {
+    /// Normalize a node, using the global udp/tfo/scv values as defaults
+    pub fn from_proxy(node: &Proxy, ext: &ExtraSettings) -> Self {
+        let mut json = NodeJson {
+            name: node.remark.clone(),
+            node_type: node_json_type_name(node.proxy_type).to_string(),
+            group: node.group.clone(),
+            server: node.hostname.clone(),
+            port: node.port,
+            username: non_empty(&node.username),
+            password: non_empty(&node.password),
+            uuid: non_empty(&node.user_id),
+            cipher: non_empty(&node.encrypt_method),
+            plugin: non_empty(&node.plugin),
+            plugin_opts: non_empty(&node.plugin_option),
+            protocol: non_empty(&node.protocol),
+            protocol_param: non_empty(&node.protocol_param),
+            obfs: non_empty(&node.obfs),
+            obfs_param: non_empty(&node.obfs_param),
+            network: non_empty(&node.transfer_protocol),
+            host: non_empty(&node.host),
+            path: non_empty(&node.path),
+            grpc_mode: node.grpc_mode.map(|mode| mode.as_str().to_string()),
+            tls: node.tls_secure,
+            sni: non_empty(&node.sni).or_else(|| non_empty(&node.server_name)),
+            alpn: node.alpn.clone(),
+            fingerprint: non_empty(&node.fingerprint),
+            private_key: non_empty(&node.private_key),
+            public_key: non_empty(&node.public_key),
+            pre_shared_key: non_empty(&node.pre_shared_key),
+            ip: non_empty(&node.self_ip),
+            ipv6: non_empty(&node.self_ipv6),
+            ports: non_empty(&node.ports),
+            up_mbps: (node.up_speed > 0).then_some(node.up_speed),
+            down_mbps: (node.down_speed > 0).then_some(node.down_speed),
+            udp: node.udp.define(ext.udp),
+            tfo: node.tcp_fast_open.define(ext.tfo),
+            skip_cert_verify: node
+                .allow_insecure
+                .define(ext.default_skip_cert_verify(node)),
+            ..Default::default()
+        };
+        if node.proxy_type == ProxyType::VMess {
+            json.alter_id = Some(node.alter_id);
+        }
+        if matches!(node.proxy_type, ProxyType::Hysteria | ProxyType::Hysteria2) {
+            json.password = json.password.or_else(|| non_empty(&node.auth_str));
+        }
+
+        match &node.combined_proxy {
+            Some(CombinedProxy::Vless(vless)) => {
+                json.uuid = Some(vless.uuid.clone());
+                json.flow = non_empty(&vless.flow);
+                json.network = non_empty(&vless.network).or(json.network);
+                json.path = non_empty(&vless.ws_path)
+                    .or_else(|| non_empty(&vless.grpc_service_name))
+                    .or(json.path);
+                json.tls = vless.tls || vless.is_reality();
+                json.sni = non_empty(&vless.servername).or(json.sni);
+                if !vless.alpn.is_empty() {
+                    json.alpn = vless.alpn.clone();
+                }
+                json.grpc_mode = vless.grpc_mode.map(|mode| mode.as_str().to_string());
+                json.fingerprint = non_empty(&vless.client_fingerprint).or(json.fingerprint);
+                json.reality_public_key = non_empty(&vless.reality_public_key);
+                json.reality_short_id = non_empty(&vless.reality_short_id);
+            }
+            Some(CombinedProxy::Shadowsocks(ss)) => {
+                json.password = Some(ss.password.clone());
+                json.cipher = Some(ss.cipher.clone());
+                json.plugin = non_empty(&ss.plugin).or(json.plugin);
+                json.plugin_opts = non_empty(&ss.plugin_opts).or(json.plugin_opts);
+            }
+            Some(CombinedProxy::AnyTls(anytls)) => {
+                json.password = Some(anytls.password.clone());
+                json.tls = true;
+                json.sni = non_empty(&anytls.sni).or(json.sni);
+                if let Some(alpn) = anytls.alpn.as_ref().filter(|a| !a.is_empty()) {
+                    json.alpn = alpn.clone();
+                }
+            }
+            Some(CombinedProxy::Tuic(tuic)) => {
+                json.uuid = Some(tuic.uuid.clone());
+                json.password = Some(tuic.password.clone());
+                json.tls = true;
+                json.sni = non_empty(&tuic.sni).or(json.sni);
+                if let Some(alpn) = tuic.alpn.as_ref().filter(|a| !a.is_empty()) {
+                    json.alpn = alpn.clone();
+                }
+            }
+            Some(CombinedProxy::TrojanGo(trojan_go)) => {
+                json.mux = trojan_go.mux.then_some(true);
+                json.encryption = trojan_go
+                    .shadowsocks
+                    .as_ref()
+                    .map(|ss| format!("ss;{}:{}", ss.method, ss.password));
+            }
+            // Nodes of registered codecs are left out of the list
+            Some(CombinedProxy::Custom(_)) | None => {}
+        }
+        json
+    }
+}
+
+/// Convert nodes to the structured JSON node list
+///
+/// Nodes are expected to be preprocessed already (filters, rename, emoji,
+/// sort); no proxy groups or rules are generated.
+///
+/// # Arguments
+/// * `nodes` - Nodes to export
+/// * `ext` - Extra settings, for the udp/tfo/scv defaults
+pub fn proxy_to_node_json(nodes: &[Proxy], ext: &ExtraSettings) -> String {
+    let document = NodeJsonDocument {
+        version: NODE_JSON_SCHEMA_VERSION,
+        nodes: nodes
+            .iter()
+            .filter(|node| node.proxy_type != ProxyType::Unknown)
+            .map(|node| NodeJson::from_proxy(node, ext))
+            .collect(),
+    };
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_json_schema() {
+        let nodes = vec![
+            Proxy {
+                proxy_type: ProxyType::VMess,
+                remark: "HK 01".to_string(),
+                group: "Sub".to_string(),
+                hostname: "hk.example.com".to_string(),
+                port: 443,
+                user_id: Some("uuid".to_string()),
+                transfer_protocol: Some("ws".to_string()),
+                path: Some("/ws".to_string()),
+                tls_secure: true,
+                ..Default::default()
+            },
+            Proxy {
+                remark: "broken".to_string(),
+                ..Default::default()
+            },
+        ];
+        let ext = ExtraSettings::default();
+        let json: serde_json::Value =
+            serde_json::from_str(&proxy_to_node_json(&nodes, &ext)).unwrap();
+
+        assert_eq!(json["version"], NODE_JSON_SCHEMA_VERSION);
+        let list = json["nodes"].as_array().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            list[0],
+            serde_json::json!({
+                "name": "HK 01",
+                "type": "vmess",
+                "group": "Sub",
+                "server": "hk.example.com",
+                "port": 443,
+                "uuid": "uuid",
+                "alter_id": 0,
+                "network": "ws",
+                "path": "/ws",
+                "tls": true,
+            })
+        );
+    }
+}
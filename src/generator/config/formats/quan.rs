@@ -1,11 +1,13 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::group::{group_generate_members, quan_fallback};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    is_ss2022_cipher, ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType,
+    RulesetContent,
 };
 use crate::utils::base64::{base64_encode, url_safe_base64_encode};
 use crate::utils::ini_reader::IniReader;
+use crate::utils::network::join_host_port;
 use crate::utils::regexp::reg_get_match;
 use crate::utils::string::{join, replace_all_distinct, trim_of};
 use crate::utils::tribool::BoolTriboolExt;
@@ -131,7 +133,7 @@ async fn proxy_to_quan_internal(
         let group = node.group.as_ref();
 
         // Define skip_cert_verify with default from ext and override with node-specific value
-        let mut scv = ext.skip_cert_verify;
+        let mut scv = ext.default_skip_cert_verify(node);
         scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
 
         let mut proxy_str;
@@ -208,12 +210,19 @@ async fn proxy_to_quan_internal(
                 }
             }
             ProxyType::Shadowsocks => {
+                if is_ss2022_cipher(method) {
+                    ext.skip_node(
+                        "Quantumult",
+                        &node.remark,
+                        format!("cipher {} is not supported", method),
+                    );
+                    continue;
+                }
                 if ext.nodelist {
                     proxy_str = format!(
-                        "ss://{}@{}:{}",
+                        "ss://{}@{}",
                         url_safe_base64_encode(&format!("{}:{}", method, password)),
-                        hostname,
-                        port
+                        join_host_port(hostname, node.port)
                     );
 
                     if !plugin.is_empty() && !pluginopts.is_empty() {
@@ -310,7 +319,14 @@ async fn proxy_to_quan_internal(
                     proxy_str = format!("socks://{}", url_safe_base64_encode(&proxy_str));
                 }
             }
-            _ => continue,
+            _ => {
+                ext.skip_node(
+                    "Quantumult",
+                    &node.remark,
+                    format!("{} is not supported", node.proxy_type.to_string()),
+                );
+                continue;
+            }
         }
 
         // Add to INI
@@ -319,15 +335,19 @@ async fn proxy_to_quan_internal(
         nodelist.push(node.clone());
     }
 
-    // Stop here if nodelist mode is enabled
+    // Stop here if nodelist mode is enabled, its links have no comments
     if ext.nodelist {
         return;
     }
+    for line in ext.unsupported_comments("Quantumult", "#") {
+        ini.set_current("{NONAME}", &line).unwrap_or(());
+    }
 
     // Process proxy groups
     ini.set_current_section("POLICY");
     ini.erase_section();
 
+    let fallback = quan_fallback(&ext.group_fallback);
     for group in extra_proxy_group {
         let mut filtered_nodelist = Vec::new();
         let mut single_group;
@@ -336,13 +356,9 @@ async fn proxy_to_quan_internal(
         match group.group_type {
             ProxyGroupType::Select | ProxyGroupType::Fallback => {
                 // Process as static type
-                for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-                }
-
-                if filtered_nodelist.is_empty() {
-                    filtered_nodelist.push("direct".to_string());
-                }
+                filtered_nodelist.extend(group_generate_members(
+                    group, &nodelist, true, &fallback, ext,
+                ));
 
                 let proxies = join(&filtered_nodelist, "\n");
 
@@ -351,13 +367,9 @@ async fn proxy_to_quan_internal(
             }
             ProxyGroupType::URLTest => {
                 // Process as auto type
-                for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-                }
-
-                if filtered_nodelist.is_empty() {
-                    filtered_nodelist.push("direct".to_string());
-                }
+                filtered_nodelist.extend(group_generate_members(
+                    group, &nodelist, true, &fallback, ext,
+                ));
 
                 let proxies = join(&filtered_nodelist, "\n");
 
@@ -372,13 +384,9 @@ async fn proxy_to_quan_internal(
             }
             ProxyGroupType::LoadBalance => {
                 // Process as balance type
-                for proxy_name in &group.proxies {
-                    group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-                }
-
-                if filtered_nodelist.is_empty() {
-                    filtered_nodelist.push("direct".to_string());
-                }
+                filtered_nodelist.extend(group_generate_members(
+                    group, &nodelist, true, &fallback, ext,
+                ));
 
                 let proxies = join(&filtered_nodelist, "\n");
 
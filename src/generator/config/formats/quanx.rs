@@ -1,13 +1,17 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::capabilities::supported_tfo;
+use crate::generator::config::group::{group_generate_members, quan_fallback};
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    trojan_go_skip_reason, ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType,
+    RulesetContent, SubconverterTarget, TrojanGoSupport,
 };
 use crate::utils::ini_reader::IniReader;
+use crate::utils::network::bracket_ipv6;
 use crate::utils::string::{hash, join, replace_all_distinct, trim};
 use crate::utils::tribool::BoolTriboolExt;
 use crate::utils::url::get_url_arg;
+use crate::utils::url_encode;
 use log::error;
 
 /// Convert proxies to QuantumultX format (main entry point)
@@ -117,7 +121,8 @@ async fn proxy_to_quanx_internal(
         node.remark = remark;
 
         // Extract node properties for easier access
-        let hostname = &node.hostname;
+        // Servers are written as `host:port`, IPv6 literals need brackets
+        let hostname = bracket_ipv6(&node.hostname);
         let port = node.port.to_string();
         let username = node.username.as_deref().unwrap_or("");
         let password = node.password.as_deref().unwrap_or("");
@@ -137,12 +142,13 @@ async fn proxy_to_quanx_internal(
         // Get option values with defaults from ext
         let mut udp = ext.udp;
         let mut tfo = ext.tfo;
-        let mut scv = ext.skip_cert_verify;
+        let mut scv = ext.default_skip_cert_verify(node);
         let mut tls13 = ext.tls13;
 
         // Override with node-specific values if present
         udp = node.udp.as_ref().map_or(udp, |val| Some(*val));
         tfo = node.tcp_fast_open.as_ref().map_or(tfo, |val| Some(*val));
+        tfo = supported_tfo(&SubconverterTarget::QuantumultX, node, tfo);
         scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
         tls13 = node.tls13.as_ref().map_or(tls13, |val| Some(*val));
 
@@ -178,15 +184,28 @@ async fn proxy_to_quanx_internal(
                     ));
                 }
 
-                if transproto == "ws" {
-                    if tls_secure {
-                        _proxy_str.push_str(", obfs=wss");
-                    } else {
-                        _proxy_str.push_str(", obfs=ws");
+                match transproto {
+                    "ws" => {
+                        if tls_secure {
+                            _proxy_str.push_str(", obfs=wss");
+                        } else {
+                            _proxy_str.push_str(", obfs=ws");
+                        }
+                        _proxy_str.push_str(&format!(", obfs-host={}, obfs-uri={}", host, path));
+                    }
+                    "" | "tcp" => {
+                        if tls_secure {
+                            _proxy_str.push_str(&format!(", obfs=over-tls, obfs-host={}", host));
+                        }
+                    }
+                    _ => {
+                        ext.skip_node(
+                            "QuantumultX",
+                            &node.remark,
+                            format!("unsupported VMess transport '{}'", transproto),
+                        );
+                        continue;
                     }
-                    _proxy_str.push_str(&format!(", obfs-host={}, obfs-uri={}", host, path));
-                } else if tls_secure {
-                    _proxy_str.push_str(&format!(", obfs=over-tls, obfs-host={}", host));
                 }
             }
             ProxyType::Shadowsocks => {
@@ -239,7 +258,12 @@ async fn proxy_to_quanx_internal(
                             _proxy_str.push_str(&format!(", obfs-uri={}", plugin_path));
                         }
                     } else {
-                        continue; // Skip unsupported plugin
+                        ext.skip_node(
+                            "QuantumultX",
+                            &node.remark,
+                            format!("unsupported plugin '{}'", plugin),
+                        );
+                        continue;
                     }
                 }
             }
@@ -293,9 +317,27 @@ async fn proxy_to_quanx_internal(
                 }
             }
             ProxyType::Trojan => {
+                if let Some(reason) = trojan_go_skip_reason(node, TrojanGoSupport::WEBSOCKET) {
+                    ext.skip_node("QuantumultX", &node.remark, reason);
+                    continue;
+                }
                 _proxy_str = format!("trojan = {}:{}, password={}", hostname, port, password);
 
-                if tls_secure {
+                if transproto == "ws" {
+                    _proxy_str.push_str(&format!(
+                        ", obfs={}, obfs-host={}, obfs-uri={}",
+                        if tls_secure { "wss" } else { "ws" },
+                        host,
+                        path
+                    ));
+                } else if !matches!(transproto, "" | "tcp") {
+                    ext.skip_node(
+                        "QuantumultX",
+                        &node.remark,
+                        format!("unsupported Trojan transport '{}'", transproto),
+                    );
+                    continue;
+                } else if tls_secure {
                     _proxy_str.push_str(&format!(", over-tls=true, tls-host={}", host));
                     if !tls13.is_undef() {
                         _proxy_str.push_str(&format!(
@@ -334,7 +376,14 @@ async fn proxy_to_quanx_internal(
                     }
                 }
             }
-            _ => continue,
+            _ => {
+                ext.skip_node(
+                    "QuantumultX",
+                    &node.remark,
+                    format!("{} is not supported", node.proxy_type.to_string()),
+                );
+                continue;
+            }
         }
 
         // Add common options
@@ -380,10 +429,13 @@ async fn proxy_to_quanx_internal(
         _proxy_str.push_str(&format!(", tag={}", node.remark));
 
         // Add to INI
-        ini.set("{NONAME}", &_proxy_str, "").unwrap_or(());
+        ini.set_current("{NONAME}", &_proxy_str).unwrap_or(());
         remarks_list.push(node.remark.clone());
         nodelist.push(node.clone());
     }
+    for line in ext.unsupported_comments("QuantumultX", "#") {
+        ini.set_current("{NONAME}", &line).unwrap_or(());
+    }
 
     // Stop here if nodelist mode is enabled
     if ext.nodelist {
@@ -430,13 +482,13 @@ async fn proxy_to_quanx_internal(
 
         // Generate node list for non-SSID groups
         if group.group_type != ProxyGroupType::SSID {
-            for proxy_name in &group.proxies {
-                group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-            }
-
-            if filtered_nodelist.is_empty() {
-                filtered_nodelist.push("direct".to_string());
-            }
+            filtered_nodelist.extend(group_generate_members(
+                group,
+                &nodelist,
+                true,
+                &quan_fallback(&ext.group_fallback),
+                ext,
+            ));
 
             // Force groups with 1 node to be static
             if filtered_nodelist.len() < 2 {
@@ -477,11 +529,30 @@ async fn proxy_to_quanx_internal(
         }
 
         // Add to INI
-        ini.set("{NONAME}", &single_group, "").unwrap_or(());
+        ini.set_current("{NONAME}", &single_group).unwrap_or(());
     }
 
-    // Generate rules if enabled
+    // Generate remote resources and rules if enabled
     if ext.enable_rule_generator {
+        if ext.quanx_server_remote && !ext.quanx_server_remote_url.is_empty() {
+            // The remote lists the nodes, policies keep referring to their tags
+            ini.erase_section_by_name("server_local");
+            let server_remote = format!(
+                "{}, tag={}, update-interval={}, opt-parser=false, enabled=true",
+                with_dev_id(&ext.quanx_server_remote_url, &ext.quanx_dev_id),
+                ext.quanx_remote_tag,
+                ext.quanx_remote_interval
+            );
+            ini.set("server_remote", "{NONAME}", &server_remote)
+                .unwrap_or(());
+        }
+
+        let kept_remotes = if ext.overwrite_original_rules {
+            0
+        } else {
+            ini.get_items("filter_remote")
+                .map_or(0, |items| items.len())
+        };
         ruleset_to_surge(
             ini,
             ruleset_content_array,
@@ -490,5 +561,156 @@ async fn proxy_to_quanx_internal(
             &ext.managed_config_prefix,
         )
         .await;
+        finish_filter_remotes(ini, kept_remotes, ext);
+    }
+}
+
+/// Add the device ID to a managed URL as the `dev_id` argument, unless the
+/// URL already has one
+fn with_dev_id(url: &str, dev_id: &str) -> String {
+    let has_dev_id = url
+        .split_once('?')
+        .is_some_and(|(_, query)| query.split('&').any(|param| param.starts_with("dev_id=")));
+    if dev_id.is_empty() || has_dev_id {
+        return url.to_string();
+    }
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}dev_id={}", url, separator, url_encode(dev_id))
+}
+
+/// Complete the `[filter_remote]` entries generated from rulesets
+///
+/// Entries after the first `kept` ones get the remote update interval, and
+/// those fetched from the managed config prefix also get the device ID.
+fn finish_filter_remotes(ini: &mut IniReader, kept: usize, ext: &ExtraSettings) {
+    let Ok(items) = ini.get_items("filter_remote") else {
+        return;
+    };
+    if items.len() <= kept {
+        return;
+    }
+
+    ini.erase_section_by_name("filter_remote");
+    for (i, (key, value)) in items.into_iter().enumerate() {
+        let value = if i < kept {
+            value
+        } else {
+            let (url, options) = value.split_once(", ").unwrap_or((&value, ""));
+            let url = if !ext.managed_config_prefix.is_empty()
+                && url.starts_with(&ext.managed_config_prefix)
+            {
+                with_dev_id(url, &ext.quanx_dev_id)
+            } else {
+                url.to_string()
+            };
+            let mut line = url;
+            if !options.is_empty() {
+                line.push_str(", ");
+                line.push_str(options);
+            }
+            if !options.contains("update-interval=") {
+                line.push_str(&format!(", update-interval={}", ext.quanx_remote_interval));
+            }
+            line
+        };
+        ini.set("filter_remote", &key, &value).unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyGroupConfig;
+
+    fn vmess(remark: &str, transport: &str) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::VMess,
+            remark: remark.to_string(),
+            hostname: "vmess.example.com".to_string(),
+            port: 443,
+            user_id: Some("uuid".to_string()),
+            encrypt_method: Some("auto".to_string()),
+            transfer_protocol: Some(transport.to_string()),
+            host: Some("cdn.example.com".to_string()),
+            path: Some("/ws".to_string()),
+            tls_secure: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_quanx_server_remote_and_skipped_nodes() {
+        let mut nodes = vec![
+            vmess("WS", "ws"),
+            vmess("GRPC", "grpc"),
+            Proxy {
+                proxy_type: ProxyType::Shadowsocks,
+                remark: "Plugin".to_string(),
+                hostname: "ss.example.com".to_string(),
+                port: 8388,
+                password: Some("pass".to_string()),
+                encrypt_method: Some("aes-128-gcm".to_string()),
+                plugin: Some("kcptun".to_string()),
+                ..Default::default()
+            },
+        ];
+        let mut select = ProxyGroupConfig::new("Proxy".to_string(), ProxyGroupType::Select);
+        select.proxies = vec![".*".to_string()];
+        let groups = vec![select];
+        let base = "[general]\n[server_local]\n[server_remote]\n[filter_remote]\n[policy]\n";
+
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = true;
+        ext.quanx_dev_id = "device".to_string();
+        ext.quanx_server_remote = true;
+        ext.quanx_server_remote_url =
+            "https://sub.example.com/sub?target=quanx&list=true".to_string();
+        ext.quanx_remote_tag = "Nodes".to_string();
+        ext.quanx_remote_interval = 3600;
+
+        let output = futures::executor::block_on(proxy_to_quanx(
+            &mut nodes.clone(),
+            base,
+            &mut Vec::new(),
+            &groups,
+            &mut ext,
+        ));
+        assert!(output.contains(
+            "[server_remote]\nhttps://sub.example.com/sub?target=quanx&list=true&dev_id=device, \
+             tag=Nodes, update-interval=3600, opt-parser=false, enabled=true\n"
+        ));
+        assert!(!output.contains("vmess = "));
+        assert!(output.contains("static=Proxy, WS\n"));
+
+        // The node list keeps only the nodes QuantumultX can represent
+        ext.nodelist = true;
+        let list = futures::executor::block_on(proxy_to_quanx(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &groups,
+            &mut ext,
+        ));
+        assert_eq!(
+            list,
+            "vmess = vmess.example.com:443, method=chacha20-ietf-poly1305, password=uuid, \
+             obfs=wss, obfs-host=cdn.example.com, obfs-uri=/ws, tag=WS"
+        );
+    }
+
+    #[test]
+    fn test_with_dev_id() {
+        assert_eq!(
+            with_dev_id("https://sub.example.com/sub?target=quanx", "a b&c"),
+            "https://sub.example.com/sub?target=quanx&dev_id=a%20b%26c"
+        );
+        assert_eq!(
+            with_dev_id("https://sub.example.com/sub?dev_id=kept", "device"),
+            "https://sub.example.com/sub?dev_id=kept"
+        );
+        assert_eq!(
+            with_dev_id("https://a.example/list", ""),
+            "https://a.example/list"
+        );
     }
 }
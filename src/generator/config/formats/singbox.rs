@@ -1,13 +1,23 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::capabilities::supported_tfo;
+use crate::generator::config::group::group_generate_members;
 use crate::generator::config::remark::process_remark;
+use crate::generator::ruleconvert::block_rules::{blocks_quic, sing_box_quic_rule};
 use crate::generator::ruleconvert::ruleset_to_sing_box::ruleset_to_sing_box;
+use crate::models::proxy_codec::emit_custom_json;
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::multiplex::{singbox_multiplexes, takes_default_multiplex};
+use crate::models::proxy_node::shadowsocks::UDP_OVER_TCP_VERSIONS;
+use crate::models::proxy_node::shadowtls::ShadowTlsOptions;
+use crate::models::proxy_node::vless::VlessProxy;
+use crate::models::relay::upstream_of;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    auth_skip_reason, trojan_go_skip_reason, AuthRule, ExtraSettings, Proxy, ProxyGroupConfigs,
+    ProxyGroupType, ProxyType, RulesetContent, SubconverterTarget, TrojanGoSupport,
 };
-use crate::utils::base64::base64_encode;
 use crate::Settings;
 use log::error;
 use serde_json::{json, Map, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
 
 /// Format SingBox interval from seconds
 ///
@@ -103,6 +113,72 @@ fn build_singbox_transport(proxy: &Proxy) -> JsonValue {
     JsonValue::Object(transport)
 }
 
+/// Build SingBox transport configuration for a VLESS node
+///
+/// # Arguments
+/// * `vless` - VLESS specific options of the node
+///
+/// # Returns
+/// * Transport configuration as JSON
+fn build_singbox_vless_transport(vless: &VlessProxy) -> JsonValue {
+    let mut transport = Map::new();
+
+    match vless.network.as_deref().unwrap_or("tcp") {
+        "ws" | "httpupgrade" => {
+            let network = vless.network.as_deref().unwrap_or("ws");
+            transport.insert("type".to_string(), JsonValue::String(network.to_string()));
+            transport.insert(
+                "path".to_string(),
+                JsonValue::String(vless.ws_path.clone().unwrap_or_else(|| "/".to_string())),
+            );
+
+            if let Some(headers) = &vless.ws_headers {
+                if let Some(host) = headers.get("Host") {
+                    if network == "ws" {
+                        let mut header_obj = Map::new();
+                        header_obj.insert("Host".to_string(), JsonValue::String(host.clone()));
+                        transport.insert("headers".to_string(), JsonValue::Object(header_obj));
+                    } else {
+                        transport.insert("host".to_string(), JsonValue::String(host.clone()));
+                    }
+                }
+            }
+        }
+        "grpc" => {
+            transport.insert("type".to_string(), JsonValue::String("grpc".to_string()));
+
+            if let Some(service_name) = &vless.grpc_service_name {
+                if !service_name.is_empty() {
+                    transport.insert(
+                        "service_name".to_string(),
+                        JsonValue::String(service_name.clone()),
+                    );
+                }
+            }
+        }
+        "h2" | "http" => {
+            transport.insert("type".to_string(), JsonValue::String("http".to_string()));
+
+            if let Some(hosts) = &vless.h2_host {
+                if !hosts.is_empty() {
+                    let hosts = hosts
+                        .iter()
+                        .map(|h| JsonValue::String(h.clone()))
+                        .collect::<Vec<_>>();
+                    transport.insert("host".to_string(), JsonValue::Array(hosts));
+                }
+            }
+
+            if let Some(path) = &vless.h2_path {
+                transport.insert("path".to_string(), JsonValue::String(path.clone()));
+            }
+        }
+        _ => {} // Plain TCP needs no transport
+    }
+
+    JsonValue::Object(transport)
+}
+
 /// Add common members to a SingBox proxy configuration
 ///
 /// # Arguments
@@ -132,6 +208,72 @@ fn add_singbox_common_members(
     );
 }
 
+/// Add the credentials of an HTTP or SOCKS5 node, leaving out empty parts
+fn add_singbox_auth(proxy_obj: &mut Map<String, JsonValue>, proxy: &Proxy) {
+    if let Some(username) = proxy.auth_username() {
+        proxy_obj.insert(
+            "username".to_string(),
+            JsonValue::String(username.to_string()),
+        );
+    }
+    if let Some(password) = proxy.auth_password() {
+        proxy_obj.insert(
+            "password".to_string(),
+            JsonValue::String(password.to_string()),
+        );
+    }
+}
+
+/// Build the `shadowtls` outbound a Shadowsocks node dials through
+fn shadow_tls_outbound(
+    proxy: &Proxy,
+    tag: &str,
+    shadow_tls: &ShadowTlsOptions,
+    scv: Option<bool>,
+    tls13: Option<bool>,
+) -> JsonValue {
+    let mut obj = Map::new();
+    add_singbox_common_members(&mut obj, proxy, "shadowtls");
+    obj.insert("tag".to_string(), JsonValue::String(tag.to_string()));
+    obj.insert("version".to_string(), JsonValue::from(shadow_tls.version));
+    if let Some(password) = &shadow_tls.password {
+        obj.insert("password".to_string(), JsonValue::String(password.clone()));
+    }
+
+    let mut tls = Map::new();
+    tls.insert("enabled".to_string(), JsonValue::Bool(true));
+    tls.insert(
+        "server_name".to_string(),
+        JsonValue::String(shadow_tls.host.clone()),
+    );
+    if let Some(insecure) = scv {
+        tls.insert("insecure".to_string(), JsonValue::Bool(insecure));
+    }
+    if tls13.unwrap_or(false) {
+        tls.insert(
+            "min_version".to_string(),
+            JsonValue::String("1.3".to_string()),
+        );
+    }
+    obj.insert("tls".to_string(), JsonValue::Object(tls));
+    JsonValue::Object(obj)
+}
+
+/// Whether a Shadowsocks node is emitted with UDP over TCP
+///
+/// The node's own option wins when it was parsed with one, the global `udp`
+/// setting decides otherwise. Nodes without UDP never get it.
+fn udp_over_tcp(proxy: &Proxy, ext: &ExtraSettings, udp: Option<bool>) -> bool {
+    if proxy.proxy_type != ProxyType::Shadowsocks || udp == Some(false) {
+        return false;
+    }
+    let node_uot = match &proxy.combined_proxy {
+        Some(CombinedProxy::Shadowsocks(ss)) => ss.udp_over_tcp,
+        _ => None,
+    };
+    node_uot.unwrap_or(ext.udp == Some(true))
+}
+
 /// Convert string array to JSON array
 ///
 /// # Arguments
@@ -168,7 +310,8 @@ pub fn proxy_to_singbox(
     ext: &mut ExtraSettings,
 ) -> String {
     // Parse the base configuration
-    let mut json: JsonValue = if ext.nodelist {
+    // An empty base still yields a valid skeleton config
+    let mut json: JsonValue = if ext.nodelist || base_conf.trim().is_empty() {
         json!({})
     } else {
         match serde_json::from_str(base_conf) {
@@ -210,7 +353,15 @@ pub fn proxy_to_singbox(
     }
 
     // Process each proxy node
+    // Outbounds dialing through an upstream, with the upstream's node name
+    let mut detours: Vec<(usize, String)> = Vec::new();
+    let mut emitted_names: HashMap<String, String> = HashMap::new();
+    // ShadowTLS outbounds, tagged once every node name is known
+    let mut shadow_tls_outbounds: Vec<(usize, String)> = Vec::new();
+
     for node in nodes.iter_mut() {
+        let original_remark = node.remark.clone();
+
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
             let proxy_type = node.proxy_type.to_string();
@@ -225,11 +376,23 @@ pub fn proxy_to_singbox(
         // Define tribool values with defaults from ext and override with node-specific values
         let mut udp = ext.udp;
         let mut tfo = ext.tfo;
-        let mut scv = ext.skip_cert_verify;
+        let mut scv = ext.default_skip_cert_verify(node);
 
         udp = node.udp.as_ref().map_or(udp, |val| Some(*val));
         tfo = node.tcp_fast_open.as_ref().map_or(tfo, |val| Some(*val));
+        tfo = supported_tfo(&SubconverterTarget::SingBox, node, tfo);
         scv = node.allow_insecure.as_ref().map_or(scv, |val| Some(*val));
+        let tls13 = node.tls13.as_ref().map_or(ext.tls13, |val| Some(*val));
+        let shadow_tls = match node.proxy_type {
+            ProxyType::Shadowsocks => ShadowTlsOptions::from_proxy(node),
+            _ => None,
+        };
+
+        // sing-box sends HTTP and SOCKS5 credentials with a username
+        if let Some(reason) = auth_skip_reason(node, AuthRule::UsernameRequired) {
+            ext.skip_node("sing-box", &node.remark, reason);
+            continue;
+        }
 
         // Create proxy object based on type
         let mut proxy_obj = match node.proxy_type {
@@ -246,9 +409,9 @@ pub fn proxy_to_singbox(
                     obj.insert("password".to_string(), JsonValue::String(password.clone()));
                 }
 
-                // Handle plugin if present
+                // Handle plugin if present, ShadowTLS gets its own outbound
                 if let (Some(plugin), Some(plugin_opts)) = (&node.plugin, &node.plugin_option) {
-                    if !plugin.is_empty() && !plugin_opts.is_empty() {
+                    if !plugin.is_empty() && !plugin_opts.is_empty() && shadow_tls.is_none() {
                         let plugin_name = if plugin == "simple-obfs" {
                             "obfs-local"
                         } else {
@@ -266,6 +429,28 @@ pub fn proxy_to_singbox(
                     }
                 }
 
+                if udp_over_tcp(node, ext, udp) {
+                    let node_version = match &node.combined_proxy {
+                        Some(CombinedProxy::Shadowsocks(ss)) => ss.udp_over_tcp_version,
+                        _ => None,
+                    };
+                    let version = node_version.or_else(|| {
+                        ext.uot_version
+                            .filter(|version| UDP_OVER_TCP_VERSIONS.contains(version))
+                    });
+                    match version {
+                        Some(version) => {
+                            obj.insert(
+                                "udp_over_tcp".to_string(),
+                                json!({ "enabled": true, "version": version }),
+                            );
+                        }
+                        None => {
+                            obj.insert("udp_over_tcp".to_string(), JsonValue::Bool(true));
+                        }
+                    }
+                }
+
                 obj
             }
             ProxyType::ShadowsocksR => {
@@ -331,7 +516,96 @@ pub fn proxy_to_singbox(
 
                 obj
             }
+            ProxyType::Vless => {
+                let vless = match &node.combined_proxy {
+                    Some(CombinedProxy::Vless(vless)) => vless,
+                    _ => continue, // VLESS nodes always carry combined options
+                };
+
+                let mut obj = Map::new();
+                add_singbox_common_members(&mut obj, node, "vless");
+                obj.insert("uuid".to_string(), JsonValue::String(vless.uuid.clone()));
+
+                if let Some(flow) = &vless.flow {
+                    if !flow.is_empty() {
+                        obj.insert("flow".to_string(), JsonValue::String(flow.clone()));
+                    }
+                }
+
+                if let Some(packet_encoding) = &vless.packet_encoding {
+                    if !packet_encoding.is_empty() && packet_encoding != "none" {
+                        obj.insert(
+                            "packet_encoding".to_string(),
+                            JsonValue::String(packet_encoding.clone()),
+                        );
+                    }
+                }
+
+                if vless.tls {
+                    let mut tls = Map::new();
+                    tls.insert("enabled".to_string(), JsonValue::Bool(true));
+
+                    if let Some(server_name) = vless.servername.as_ref().or(node.sni.as_ref()) {
+                        if !server_name.is_empty() {
+                            tls.insert(
+                                "server_name".to_string(),
+                                JsonValue::String(server_name.clone()),
+                            );
+                        }
+                    }
+
+                    if let Some(allow_insecure) = vless.skip_cert_verify.or(scv) {
+                        tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
+                    }
+
+                    if !vless.alpn.is_empty() {
+                        tls.insert("alpn".to_string(), json!(vless.alpn));
+                    }
+
+                    if let Some(fingerprint) = vless
+                        .client_fingerprint
+                        .as_ref()
+                        .or(node.client_fingerprint.as_ref())
+                    {
+                        if !fingerprint.is_empty() {
+                            tls.insert(
+                                "utls".to_string(),
+                                json!({ "enabled": true, "fingerprint": fingerprint }),
+                            );
+                        }
+                    }
+
+                    if let Some(public_key) = &vless.reality_public_key {
+                        let mut reality = Map::new();
+                        reality.insert("enabled".to_string(), JsonValue::Bool(true));
+                        reality.insert(
+                            "public_key".to_string(),
+                            JsonValue::String(public_key.clone()),
+                        );
+                        if let Some(short_id) = &vless.reality_short_id {
+                            reality.insert(
+                                "short_id".to_string(),
+                                JsonValue::String(short_id.clone()),
+                            );
+                        }
+                        tls.insert("reality".to_string(), JsonValue::Object(reality));
+                    }
+
+                    obj.insert("tls".to_string(), JsonValue::Object(tls));
+                }
+
+                let transport = build_singbox_vless_transport(vless);
+                if !transport.as_object().unwrap().is_empty() {
+                    obj.insert("transport".to_string(), transport);
+                }
+
+                obj
+            }
             ProxyType::Trojan => {
+                if let Some(reason) = trojan_go_skip_reason(node, TrojanGoSupport::WEBSOCKET) {
+                    ext.skip_node("sing-box", &node.remark, reason);
+                    continue;
+                }
                 let mut obj = Map::new();
                 add_singbox_common_members(&mut obj, node, "trojan");
 
@@ -428,6 +702,19 @@ pub fn proxy_to_singbox(
                 obj
             }
             ProxyType::Hysteria => {
+                // sing-box only speaks plain UDP Hysteria
+                if let Some(protocol) = node
+                    .protocol
+                    .as_deref()
+                    .filter(|p| !p.is_empty() && *p != "udp")
+                {
+                    ext.skip_node(
+                        "sing-box",
+                        &node.remark,
+                        format!("Hysteria protocol '{}' is not supported", protocol),
+                    );
+                    continue;
+                }
                 let mut obj = Map::new();
                 add_singbox_common_members(&mut obj, node, "hysteria");
 
@@ -446,23 +733,22 @@ pub fn proxy_to_singbox(
                     );
                 }
 
-                if let Some(obfs) = &node.obfs {
+                // `obfs` takes the xplus password
+                if let Some(obfs) = &node.obfs_param {
                     if !obfs.is_empty() {
                         obj.insert("obfs".to_string(), JsonValue::String(obfs.clone()));
                     }
                 }
 
-                if let Some(auth_str) = &node.auth_str {
-                    if !auth_str.is_empty() {
+                // One of the two, base64 `auth` wins like in the client
+                match (&node.auth, &node.auth_str) {
+                    (Some(auth), _) if !auth.is_empty() => {
+                        obj.insert("auth".to_string(), JsonValue::String(auth.clone()));
+                    }
+                    (_, Some(auth_str)) if !auth_str.is_empty() => {
                         obj.insert("auth_str".to_string(), JsonValue::String(auth_str.clone()));
-
-                        // Create a temporary String
-                        let auth_str_value = auth_str.clone();
-                        obj.insert(
-                            "auth".to_string(),
-                            JsonValue::String(base64_encode(&auth_str_value)),
-                        );
                     }
+                    _ => {}
                 }
 
                 if node.recv_window_conn > 0 {
@@ -490,13 +776,18 @@ pub fn proxy_to_singbox(
                 let mut tls = Map::new();
                 tls.insert("enabled".to_string(), JsonValue::Bool(true));
 
+                if let Some(sni) = &node.sni {
+                    if !sni.is_empty() {
+                        tls.insert("server_name".to_string(), JsonValue::String(sni.clone()));
+                    }
+                }
+
                 if let Some(allow_insecure) = scv {
                     tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
                 }
 
-                if !node.alpn.is_empty() && node.alpn.len() > 0 {
-                    let alpn = vec![JsonValue::String(node.alpn.iter().next().unwrap().clone())];
-                    tls.insert("alpn".to_string(), JsonValue::Array(alpn));
+                if !node.alpn.is_empty() {
+                    tls.insert("alpn".to_string(), json!(node.alpn));
                 }
 
                 if let Some(ca) = &node.ca {
@@ -557,17 +848,41 @@ pub fn proxy_to_singbox(
                     }
                 }
 
+                // Port hopping, sing-box expects ranges as `start:end`
+                if let Some(ports) = &node.ports {
+                    if !ports.is_empty() {
+                        let server_ports: Vec<JsonValue> = ports
+                            .split(',')
+                            .map(|p| p.trim())
+                            .filter(|p| !p.is_empty())
+                            .map(|p| match p.split_once('-') {
+                                Some((start, end)) => format!("{}:{}", start, end),
+                                None => format!("{}:{}", p, p),
+                            })
+                            .map(JsonValue::String)
+                            .collect();
+                        if !server_ports.is_empty() {
+                            obj.insert("server_ports".to_string(), JsonValue::Array(server_ports));
+                        }
+                    }
+                }
+
                 // Add TLS settings
                 let mut tls = Map::new();
                 tls.insert("enabled".to_string(), JsonValue::Bool(true));
 
+                if let Some(sni) = &node.sni {
+                    if !sni.is_empty() {
+                        tls.insert("server_name".to_string(), JsonValue::String(sni.clone()));
+                    }
+                }
+
                 if let Some(allow_insecure) = scv {
                     tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
                 }
 
-                if !node.alpn.is_empty() && node.alpn.len() > 0 {
-                    let alpn = vec![JsonValue::String(node.alpn.iter().next().unwrap().clone())];
-                    tls.insert("alpn".to_string(), JsonValue::Array(alpn));
+                if !node.alpn.is_empty() {
+                    tls.insert("alpn".to_string(), json!(node.alpn));
                 }
 
                 if let Some(ca) = &node.ca {
@@ -585,43 +900,191 @@ pub fn proxy_to_singbox(
                 obj.insert("tls".to_string(), JsonValue::Object(tls));
                 obj
             }
-            ProxyType::HTTP | ProxyType::HTTPS => {
+            ProxyType::Tuic => {
+                let tuic = match &node.combined_proxy {
+                    Some(CombinedProxy::Tuic(tuic)) => tuic,
+                    _ => continue, // TUIC nodes always carry combined options
+                };
+
                 let mut obj = Map::new();
-                add_singbox_common_members(&mut obj, node, "http");
+                add_singbox_common_members(&mut obj, node, "tuic");
+                obj.insert("uuid".to_string(), JsonValue::String(tuic.uuid.clone()));
 
-                // Add HTTP/HTTPS specific fields
-                if let Some(username) = &node.username {
-                    obj.insert("username".to_string(), JsonValue::String(username.clone()));
+                if !tuic.password.is_empty() {
+                    obj.insert(
+                        "password".to_string(),
+                        JsonValue::String(tuic.password.clone()),
+                    );
                 }
 
-                if let Some(password) = &node.password {
-                    obj.insert("password".to_string(), JsonValue::String(password.clone()));
+                if let Some(congestion_control) = &tuic.congestion_control {
+                    obj.insert(
+                        "congestion_control".to_string(),
+                        JsonValue::String(congestion_control.clone()),
+                    );
                 }
 
+                if let Some(udp_relay_mode) = &tuic.udp_relay_mode {
+                    obj.insert(
+                        "udp_relay_mode".to_string(),
+                        JsonValue::String(udp_relay_mode.clone()),
+                    );
+                }
+
+                if let Some(reduce_rtt) = tuic.reduce_rtt {
+                    obj.insert(
+                        "zero_rtt_handshake".to_string(),
+                        JsonValue::Bool(reduce_rtt),
+                    );
+                }
+
+                if let Some(heartbeat) = tuic.heartbeat_interval {
+                    obj.insert(
+                        "heartbeat".to_string(),
+                        JsonValue::String(format!("{}ms", heartbeat)),
+                    );
+                }
+
+                // Add TLS settings, TUIC always runs over QUIC with TLS
+                let mut tls = Map::new();
+                tls.insert("enabled".to_string(), JsonValue::Bool(true));
+
+                if let Some(sni) = node.sni.as_ref().or(tuic.sni.as_ref()) {
+                    if !sni.is_empty() {
+                        tls.insert("server_name".to_string(), JsonValue::String(sni.clone()));
+                    }
+                }
+
+                if let Some(disable_sni) = tuic.disable_sni {
+                    tls.insert("disable_sni".to_string(), JsonValue::Bool(disable_sni));
+                }
+
+                if let Some(allow_insecure) = scv {
+                    tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
+                }
+
+                if let Some(alpn) = &tuic.alpn {
+                    if !alpn.is_empty() {
+                        tls.insert("alpn".to_string(), json!(alpn));
+                    }
+                }
+
+                if tls13.unwrap_or(false) {
+                    tls.insert(
+                        "min_version".to_string(),
+                        JsonValue::String("1.3".to_string()),
+                    );
+                }
+
+                obj.insert("tls".to_string(), JsonValue::Object(tls));
                 obj
             }
-            ProxyType::Socks5 => {
+            ProxyType::AnyTls => {
+                let anytls = match &node.combined_proxy {
+                    Some(CombinedProxy::AnyTls(anytls)) => anytls,
+                    _ => continue, // AnyTLS nodes always carry combined options
+                };
+
                 let mut obj = Map::new();
-                add_singbox_common_members(&mut obj, node, "socks");
+                add_singbox_common_members(&mut obj, node, "anytls");
+                obj.insert(
+                    "password".to_string(),
+                    JsonValue::String(anytls.password.clone()),
+                );
 
-                // Add Socks5 specific fields
-                obj.insert("version".to_string(), JsonValue::String("5".to_string()));
+                if let Some(interval) = anytls.idle_session_check_interval.filter(|v| *v > 0) {
+                    obj.insert(
+                        "idle_session_check_interval".to_string(),
+                        JsonValue::String(format_singbox_interval(interval as u32)),
+                    );
+                }
 
-                if let Some(username) = &node.username {
-                    obj.insert("username".to_string(), JsonValue::String(username.clone()));
+                if let Some(timeout) = anytls.idle_session_timeout.filter(|v| *v > 0) {
+                    obj.insert(
+                        "idle_session_timeout".to_string(),
+                        JsonValue::String(format_singbox_interval(timeout as u32)),
+                    );
                 }
 
-                if let Some(password) = &node.password {
-                    obj.insert("password".to_string(), JsonValue::String(password.clone()));
+                if let Some(min_idle_session) = anytls.min_idle_session {
+                    obj.insert(
+                        "min_idle_session".to_string(),
+                        JsonValue::Number(min_idle_session.into()),
+                    );
+                }
+
+                // Add TLS settings, AnyTLS always runs over TLS
+                let mut tls = Map::new();
+                tls.insert("enabled".to_string(), JsonValue::Bool(true));
+
+                if let Some(sni) = node.sni.as_ref().or(anytls.sni.as_ref()) {
+                    if !sni.is_empty() {
+                        tls.insert("server_name".to_string(), JsonValue::String(sni.clone()));
+                    }
+                }
+
+                if let Some(allow_insecure) = scv.or(anytls.skip_cert_verify) {
+                    tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
+                }
+
+                if let Some(alpn) = &anytls.alpn {
+                    if !alpn.is_empty() {
+                        tls.insert("alpn".to_string(), json!(alpn));
+                    }
                 }
 
+                if let Some(fingerprint) = &anytls.client_fingerprint {
+                    if !fingerprint.is_empty() {
+                        tls.insert(
+                            "utls".to_string(),
+                            json!({ "enabled": true, "fingerprint": fingerprint }),
+                        );
+                    }
+                }
+
+                if tls13.unwrap_or(false) {
+                    tls.insert(
+                        "min_version".to_string(),
+                        JsonValue::String("1.3".to_string()),
+                    );
+                }
+
+                obj.insert("tls".to_string(), JsonValue::Object(tls));
                 obj
             }
+            ProxyType::HTTP | ProxyType::HTTPS => {
+                let mut obj = Map::new();
+                add_singbox_common_members(&mut obj, node, "http");
+
+                // Add HTTP/HTTPS specific fields
+                add_singbox_auth(&mut obj, node);
+
+                obj
+            }
+            ProxyType::Socks5 => {
+                let mut obj = Map::new();
+                add_singbox_common_members(&mut obj, node, "socks");
+
+                // Add Socks5 specific fields
+                obj.insert("version".to_string(), JsonValue::String("5".to_string()));
+                add_singbox_auth(&mut obj, node);
+
+                obj
+            }
+            ProxyType::Unknown if node.custom_proxy().is_some() => {
+                match emit_custom_json(node, &SubconverterTarget::SingBox, ext) {
+                    Some(mut obj) => {
+                        obj.insert("tag".to_string(), JsonValue::String(node.remark.clone()));
+                        obj
+                    }
+                    None => continue,
+                }
+            }
             _ => continue, // Skip unsupported types
         };
 
-        // Add TLS settings for protocols that need it
-        if node.tls_secure {
+        // Add TLS settings for protocols that need it, unless already built above
+        if node.tls_secure && !proxy_obj.contains_key("tls") {
             let mut tls = Map::new();
             tls.insert("enabled".to_string(), JsonValue::Bool(true));
 
@@ -644,6 +1107,17 @@ pub fn proxy_to_singbox(
                 tls.insert("insecure".to_string(), JsonValue::Bool(allow_insecure));
             }
 
+            if !node.alpn.is_empty() {
+                tls.insert("alpn".to_string(), json!(node.alpn));
+            }
+
+            if let Some(fingerprint) = &node.client_fingerprint {
+                tls.insert(
+                    "utls".to_string(),
+                    json!({ "enabled": true, "fingerprint": fingerprint }),
+                );
+            }
+
             proxy_obj.insert("tls".to_string(), JsonValue::Object(tls));
         }
 
@@ -658,10 +1132,75 @@ pub fn proxy_to_singbox(
             proxy_obj.insert("tcp_fast_open".to_string(), JsonValue::Bool(tfo_enabled));
         }
 
+        if let Some(strategy) = node
+            .ip_version
+            .or(ext.ip_version)
+            .and_then(|version| version.singbox_domain_strategy())
+        {
+            proxy_obj.insert(
+                "domain_strategy".to_string(),
+                JsonValue::String(strategy.to_string()),
+            );
+        }
+
+        if let Some(multiplex) = node
+            .multiplex
+            .or_else(|| {
+                ext.multiplex
+                    .filter(|_| takes_default_multiplex(node, udp_over_tcp(node, ext, udp)))
+            })
+            .filter(|_| singbox_multiplexes(node.proxy_type))
+        {
+            proxy_obj.insert("multiplex".to_string(), multiplex.singbox_json());
+        }
+
+        // Chain through the ShadowTLS outbound, which is not a selectable node
+        let shadow_tls_obj = shadow_tls.map(|shadow_tls| {
+            shadow_tls_outbounds.push((outbounds.len(), node.remark.clone()));
+            shadow_tls_outbound(node, "", &shadow_tls, scv, tls13)
+        });
+
+        // A ShadowTLS node reaches its upstream from the ShadowTLS outbound
+        if let Some(upstream) = upstream_of(node) {
+            let index = outbounds.len() + usize::from(shadow_tls_obj.is_some());
+            detours.push((index, upstream.to_string()));
+        }
+        emitted_names
+            .entry(original_remark)
+            .or_insert_with(|| node.remark.clone());
+
         // Add to node list and outbounds
         nodelist.push(node.clone());
         remarks_list.push(node.remark.clone());
         outbounds.push(JsonValue::Object(proxy_obj));
+        outbounds.extend(shadow_tls_obj);
+    }
+
+    // ShadowTLS tags must not take the name of a node or group
+    let mut taken: HashSet<String> = remarks_list
+        .iter()
+        .cloned()
+        .chain(extra_proxy_group.iter().map(|group| group.name.clone()))
+        .collect();
+    for (index, remark) in shadow_tls_outbounds {
+        let base = format!("{}-shadowtls", remark);
+        let mut tag = base.clone();
+        let mut count = 2;
+        while taken.contains(&tag) {
+            tag = format!("{} {}", base, count);
+            count += 1;
+        }
+        outbounds[index]["detour"] = JsonValue::String(tag.clone());
+        outbounds[index + 1]["tag"] = JsonValue::String(tag.clone());
+        taken.insert(tag);
+    }
+
+    // Upstreams are named by their outbound tags
+    for (index, upstream) in detours {
+        let tag = emitted_names.get(&upstream).cloned().unwrap_or(upstream);
+        if let Some(outbound) = outbounds[index].as_object_mut() {
+            outbound.insert("detour".to_string(), JsonValue::String(tag));
+        }
     }
 
     // If nodelist mode, just return outbounds
@@ -687,14 +1226,13 @@ pub fn proxy_to_singbox(
         };
 
         // Generate filtered proxy list
-        for proxy_name in &group.proxies {
-            group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-        }
-
-        // Add DIRECT if empty
-        if filtered_nodelist.is_empty() {
-            filtered_nodelist.push("DIRECT".to_string());
-        }
+        filtered_nodelist.extend(group_generate_members(
+            group,
+            &nodelist,
+            true,
+            &ext.group_fallback,
+            ext,
+        ));
 
         // Create group object
         let mut group_obj = Map::new();
@@ -713,7 +1251,7 @@ pub fn proxy_to_singbox(
         group_obj.insert("outbounds".to_string(), JsonValue::Array(group_outbounds));
 
         // Add URL Test specific settings
-        if group.group_type == ProxyGroupType::URLTest {
+        if group_type == "urltest" {
             group_obj.insert("url".to_string(), JsonValue::String(group.url.clone()));
             group_obj.insert(
                 "interval".to_string(),
@@ -731,7 +1269,7 @@ pub fn proxy_to_singbox(
         outbounds.push(JsonValue::Object(group_obj));
     }
 
-    let global = Settings::current();
+    let global = Settings::snapshot();
 
     // Add global group if enabled
     if global.singbox_add_clash_modes {
@@ -769,17 +1307,490 @@ pub fn proxy_to_singbox(
         ruleset_content_array,
         ext.overwrite_original_rules,
     );
+    if blocks_quic(&ext.block_rules) {
+        if let Some(rules) = json["route"]["rules"].as_array_mut() {
+            rules.insert(0, sing_box_quic_rule());
+        }
+    }
 
     serde_json::to_string_pretty(&json).unwrap_or_default()
 }
 
-/// Generate a Sing-Box configuration
-pub fn generate_singbox(
-    _proxies: &[Proxy],
-    _group_config: &ProxyGroupConfigs,
-    _ruleset_content_array: &mut Vec<RulesetContent>,
-    _config: &str,
-    _extra_settings: &ExtraSettings,
-) -> String {
-    String::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{IpVersion, ProxyGroupConfig};
+
+    fn outbound_by_tag<'a>(json: &'a JsonValue, tag: &str) -> Option<&'a JsonValue> {
+        json["outbounds"]
+            .as_array()
+            .and_then(|outbounds| outbounds.iter().find(|o| o["tag"] == tag))
+    }
+
+    #[test]
+    fn test_empty_nodes_yield_skeleton() {
+        let mut nodes = Vec::new();
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+
+        let output = proxy_to_singbox(&mut nodes, "", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        assert!(outbound_by_tag(&json, "DIRECT").is_some());
+        assert!(outbound_by_tag(&json, "REJECT").is_some());
+    }
+
+    #[test]
+    fn test_block_rules() {
+        use crate::generator::ruleconvert::block_rules::block_ruleset;
+
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = true;
+        ext.block_rules = vec![
+            "QUIC".to_string(),
+            "DOMAIN-SUFFIX,ads.example.com".to_string(),
+        ];
+        let mut rulesets = vec![block_ruleset(&ext.block_rules).unwrap()];
+
+        let output = proxy_to_singbox(&mut Vec::new(), "", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        assert_eq!(outbound_by_tag(&json, "REJECT").unwrap()["type"], "block");
+        let rules = json["route"]["rules"].as_array().unwrap();
+        assert_eq!(
+            rules[0],
+            serde_json::json!({"network": "udp", "port": 443, "outbound": "REJECT"})
+        );
+        assert!(rules.iter().any(|rule| rule["outbound"] == "REJECT"
+            && rule["domain_suffix"] == serde_json::json!(["ads.example.com"])));
+    }
+
+    #[test]
+    fn test_vless_and_groups() {
+        let vless = VlessProxy {
+            uuid: "b831381d-6324-4d53-ad4f-8cda48b30811".to_string(),
+            tls: true,
+            servername: Some("example.com".to_string()),
+            network: Some("grpc".to_string()),
+            grpc_service_name: Some("svc".to_string()),
+            reality_public_key: Some("pbk".to_string()),
+            reality_short_id: Some("sid".to_string()),
+            ..Default::default()
+        };
+        let mut nodes = vec![Proxy {
+            proxy_type: ProxyType::Vless,
+            remark: "VLESS".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            combined_proxy: Some(CombinedProxy::Vless(vless)),
+            ..Default::default()
+        }];
+        let mut group = ProxyGroupConfig::new("Auto".to_string(), ProxyGroupType::URLTest);
+        group.proxies = vec![".*".to_string()];
+        group.url = "http://www.gstatic.com/generate_204".to_string();
+        group.interval = 300;
+
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        ext.tfo = Some(true);
+        ext.skip_cert_verify = Some(true);
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &vec![group], &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let node = outbound_by_tag(&json, "VLESS").unwrap();
+        assert_eq!(node["type"], "vless");
+        assert_eq!(node["tcp_fast_open"], true);
+        assert_eq!(node["tls"]["insecure"], true);
+        assert_eq!(node["tls"]["server_name"], "example.com");
+        assert_eq!(node["tls"]["reality"]["public_key"], "pbk");
+        assert_eq!(node["transport"]["service_name"], "svc");
+
+        let group = outbound_by_tag(&json, "Auto").unwrap();
+        assert_eq!(group["type"], "urltest");
+        assert_eq!(group["interval"], "5m");
+        assert_eq!(group["outbounds"][0], "VLESS");
+    }
+
+    #[test]
+    fn test_utls_fingerprint() {
+        let mut trojan = Proxy::default();
+        assert!(crate::parser::explodes::explode_trojan(
+            "trojan://secret@example.com:443?sni=real.example.com&fp=Firefox#Trojan",
+            &mut trojan
+        ));
+        let mut vmess = Proxy::default();
+        assert!(crate::parser::explodes::explode_std_vmess_new(
+            "vmess://uuid@example.com:443?type=ws&path=/ws&tls=true&fp=chrome_pq#VMess",
+            &mut vmess
+        ));
+        let mut nodes = vec![trojan, vmess];
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let trojan = outbound_by_tag(&json, "Trojan").unwrap();
+        assert_eq!(trojan["tls"]["utls"]["enabled"], true);
+        assert_eq!(trojan["tls"]["utls"]["fingerprint"], "firefox");
+        // Unknown fingerprints pass through
+        let vmess = outbound_by_tag(&json, "VMess").unwrap();
+        assert_eq!(vmess["tls"]["utls"]["fingerprint"], "chrome_pq");
+    }
+
+    #[test]
+    fn test_domain_strategy() {
+        let mut pinned = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "trojan://secret@example.com:443?ip-version=ipv4#Pinned",
+            &mut pinned
+        ));
+        let mut dual = pinned.clone();
+        dual.remark = "Dual".to_string();
+        dual.ip_version = Some(IpVersion::Dual);
+        let mut unset = pinned.clone();
+        unset.remark = "Unset".to_string();
+        unset.ip_version = None;
+        let mut nodes = vec![pinned, dual, unset];
+        let mut ext = ExtraSettings {
+            ip_version: Some(IpVersion::Ipv6Prefer),
+            ..Default::default()
+        };
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut Vec::new(), &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            outbound_by_tag(&json, "Pinned").unwrap()["domain_strategy"],
+            "ipv4_only"
+        );
+        assert!(outbound_by_tag(&json, "Dual")
+            .unwrap()
+            .get("domain_strategy")
+            .is_none());
+        assert_eq!(
+            outbound_by_tag(&json, "Unset").unwrap()["domain_strategy"],
+            "prefer_ipv6"
+        );
+    }
+
+    #[test]
+    fn test_multiplex() {
+        let mut linked = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "trojan://secret@example.com:443?multiplex=smux%2Cbrutal%3D20%3A80#Linked",
+            &mut linked
+        ));
+        let mut unset = linked.clone();
+        unset.remark = "Unset".to_string();
+        unset.multiplex = None;
+        let socks = Proxy {
+            proxy_type: ProxyType::Socks5,
+            remark: "Socks".to_string(),
+            hostname: "example.com".to_string(),
+            port: 1080,
+            ..Default::default()
+        };
+        let mut nodes = vec![linked, unset, socks];
+        let mut ext = ExtraSettings {
+            multiplex: Some("yamux,max_streams=16".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut Vec::new(), &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        let linked = &outbound_by_tag(&json, "Linked").unwrap()["multiplex"];
+        assert_eq!(linked["protocol"], "smux");
+        assert_eq!(linked["brutal"]["up_mbps"], 20);
+        assert_eq!(
+            outbound_by_tag(&json, "Unset").unwrap()["multiplex"],
+            json!({ "enabled": true, "protocol": "yamux", "max_streams": 16 })
+        );
+        // SOCKS has no sing-mux, the default is not applied
+        assert!(outbound_by_tag(&json, "Socks")
+            .unwrap()
+            .get("multiplex")
+            .is_none());
+    }
+
+    #[test]
+    fn test_default_multiplex_skips_unsupported_nodes() {
+        let content = "proxies:\n\
+            - {name: uot, type: ss, server: 1.2.3.4, port: 8388, cipher: aes-128-gcm, password: pw, udp-over-tcp: true}\n\
+            - {name: flow, type: vless, server: 1.2.3.4, port: 443, uuid: 26a1d547-b031-4139-9fc5-6671e1d0408a, tls: true, flow: xtls-rprx-vision}\n\
+            - {name: plain, type: vless, server: 1.2.3.4, port: 443, uuid: 26a1d547-b031-4139-9fc5-6671e1d0408a, tls: true}\n";
+        let mut nodes = Vec::new();
+        assert!(crate::parser::explodes::explode_clash(content, &mut nodes));
+        let mut ext = ExtraSettings {
+            multiplex: Some("yamux".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut Vec::new(), &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        for tag in ["uot", "flow"] {
+            assert!(outbound_by_tag(&json, tag)
+                .unwrap()
+                .get("multiplex")
+                .is_none());
+        }
+        assert_eq!(
+            outbound_by_tag(&json, "plain").unwrap()["multiplex"]["protocol"],
+            "yamux"
+        );
+    }
+
+    #[test]
+    fn test_anytls_outbound() {
+        let mut node = Proxy::default();
+        assert!(crate::parser::explodes::explode_anytls(
+            "anytls://secret@example.com:8443?sni=real.example.com&alpn=h2&fp=chrome&idle_session_check_interval=30&idle_session_timeout=90&min_idle_session=1#AnyTLS",
+            &mut node
+        ));
+        let mut nodes = vec![node];
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        ext.skip_cert_verify = Some(true);
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let node = outbound_by_tag(&json, "AnyTLS").unwrap();
+        assert_eq!(node["type"], "anytls");
+        assert_eq!(node["server_port"], 8443);
+        assert_eq!(node["password"], "secret");
+        assert_eq!(node["idle_session_check_interval"], "30s");
+        assert_eq!(node["idle_session_timeout"], "1m30s");
+        assert_eq!(node["min_idle_session"], 1);
+        assert_eq!(node["tls"]["server_name"], "real.example.com");
+        assert_eq!(node["tls"]["insecure"], true);
+        assert_eq!(node["tls"]["alpn"][0], "h2");
+        assert_eq!(node["tls"]["utls"]["fingerprint"], "chrome");
+    }
+
+    #[test]
+    fn test_proxy_credentials() {
+        let node = |proxy_type: ProxyType, remark: &str, username: &str, password: &str| Proxy {
+            proxy_type,
+            remark: remark.to_string(),
+            hostname: "example.com".to_string(),
+            port: 1080,
+            username: Some(username.to_string()),
+            password: Some(password.to_string()),
+            tls_secure: proxy_type == ProxyType::HTTPS,
+            ..Default::default()
+        };
+        let mut nodes = vec![
+            node(ProxyType::HTTPS, "HTTPS", "user", ""),
+            node(ProxyType::Socks5, "SOCKS", "user", "pass"),
+            node(ProxyType::Socks5, "NoUser", "", "pass"),
+        ];
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let https = outbound_by_tag(&json, "HTTPS").unwrap();
+        assert_eq!(https["type"], "http");
+        assert_eq!(https["username"], "user");
+        assert!(https.get("password").is_none());
+        assert_eq!(https["tls"]["enabled"], true);
+        let socks = outbound_by_tag(&json, "SOCKS").unwrap();
+        assert_eq!(socks["password"], "pass");
+        assert!(outbound_by_tag(&json, "NoUser").is_none());
+    }
+
+    #[test]
+    fn test_hysteria_outbound() {
+        let mut plain = Proxy::default();
+        assert!(crate::parser::explodes::explode_hysteria(
+            "hysteria://example.com:8443?auth=s3cret&peer=real.example.com&obfs=xplus&obfsParam=mask#Hy",
+            &mut plain
+        ));
+        let mut faketcp = Proxy::default();
+        assert!(crate::parser::explodes::explode_hysteria(
+            "hysteria://example.com:8443?protocol=faketcp&auth=s3cret#FakeTCP",
+            &mut faketcp
+        ));
+        let mut nodes = vec![plain, faketcp];
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let node = outbound_by_tag(&json, "Hy").unwrap();
+        assert_eq!(node["type"], "hysteria");
+        assert_eq!(node["auth_str"], "s3cret");
+        assert!(node.get("auth").is_none());
+        assert_eq!(node["obfs"], "mask");
+        assert_eq!(node["up_mbps"], 10);
+        assert_eq!(node["down_mbps"], 50);
+        assert_eq!(node["tls"]["server_name"], "real.example.com");
+        assert!(outbound_by_tag(&json, "FakeTCP").is_none());
+    }
+
+    #[test]
+    fn test_detour_to_upstream_node() {
+        let trojan = |remark: &str, upstream: Option<&str>| Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: remark.to_string(),
+            hostname: "example.com".to_string(),
+            port: 443,
+            password: Some("secret".to_string()),
+            underlying_proxy: upstream.map(str::to_string),
+            ..Default::default()
+        };
+        let mut nodes = vec![trojan("Exit", Some("Relay")), trojan("Relay", None)];
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        ext.append_proxy_type = true;
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let exit = outbound_by_tag(&json, "[Trojan] Exit").unwrap();
+        assert_eq!(exit["detour"], "[Trojan] Relay");
+        let relay = outbound_by_tag(&json, "[Trojan] Relay").unwrap();
+        assert!(relay.get("detour").is_none());
+    }
+
+    #[test]
+    fn test_shadowsocks_over_shadow_tls() {
+        let mut nodes = vec![Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: "SS".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            encrypt_method: Some("2022-blake3-aes-128-gcm".to_string()),
+            password: Some("key".to_string()),
+            plugin: Some("shadow-tls".to_string()),
+            plugin_option: Some("host=cloud.tencent.com;password=stls;version=3".to_string()),
+            ..Default::default()
+        }];
+        let mut rulesets = Vec::new();
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+        ext.skip_cert_verify = Some(true);
+        ext.tls13 = Some(true);
+
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+
+        let ss = outbound_by_tag(&json, "SS").unwrap();
+        assert_eq!(ss["detour"], "SS-shadowtls");
+        assert!(ss.get("plugin").is_none());
+
+        let shadow_tls = outbound_by_tag(&json, "SS-shadowtls").unwrap();
+        assert_eq!(shadow_tls["type"], "shadowtls");
+        assert_eq!(shadow_tls["server_port"], 443);
+        assert_eq!(shadow_tls["version"], 3);
+        assert_eq!(shadow_tls["password"], "stls");
+        assert_eq!(shadow_tls["tls"]["server_name"], "cloud.tencent.com");
+        assert_eq!(shadow_tls["tls"]["insecure"], true);
+        assert_eq!(shadow_tls["tls"]["min_version"], "1.3");
+
+        // A node already named like the ShadowTLS outbound keeps its name
+        let mut nodes = vec![
+            nodes[0].clone(),
+            Proxy {
+                proxy_type: ProxyType::Trojan,
+                remark: "SS-shadowtls".to_string(),
+                hostname: "5.6.7.8".to_string(),
+                port: 443,
+                password: Some("secret".to_string()),
+                ..Default::default()
+            },
+        ];
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut rulesets, &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            outbound_by_tag(&json, "SS-shadowtls").unwrap()["type"],
+            "trojan"
+        );
+        let ss = outbound_by_tag(&json, "SS").unwrap();
+        assert_eq!(ss["detour"], "SS-shadowtls 2");
+        let shadow_tls = outbound_by_tag(&json, "SS-shadowtls 2").unwrap();
+        assert_eq!(shadow_tls["type"], "shadowtls");
+    }
+
+    #[test]
+    fn test_shadowsocks_udp_over_tcp() {
+        let content = "proxies:\n\
+            - {name: v1, type: ss, server: 1.2.3.4, port: 8388, cipher: aes-128-gcm, password: pw, udp-over-tcp: true, udp-over-tcp-version: 1}\n\
+            - {name: default, type: ss, server: 1.2.3.4, port: 8389, cipher: aes-128-gcm, password: pw, udp-over-tcp: true}\n\
+            - {name: no-udp, type: ss, server: 1.2.3.4, port: 8390, cipher: aes-128-gcm, password: pw, udp: false, udp-over-tcp: true}\n";
+        let mut nodes = Vec::new();
+        assert!(crate::parser::explodes::explode_clash(content, &mut nodes));
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+
+        let render = |nodes: &mut Vec<Proxy>, ext: &mut ExtraSettings| {
+            let output = proxy_to_singbox(nodes, "{}", &mut Vec::new(), &Vec::new(), ext);
+            serde_json::from_str::<JsonValue>(&output).unwrap()
+        };
+        let json = render(&mut nodes.clone(), &mut ext);
+        assert_eq!(
+            outbound_by_tag(&json, "v1").unwrap()["udp_over_tcp"],
+            json!({ "enabled": true, "version": 1 })
+        );
+        assert_eq!(
+            outbound_by_tag(&json, "default").unwrap()["udp_over_tcp"],
+            true
+        );
+        assert!(outbound_by_tag(&json, "no-udp")
+            .unwrap()
+            .get("udp_over_tcp")
+            .is_none());
+
+        ext.uot_version = Some(2);
+        let json = render(&mut nodes, &mut ext);
+        assert_eq!(
+            outbound_by_tag(&json, "default").unwrap()["udp_over_tcp"],
+            json!({ "enabled": true, "version": 2 })
+        );
+    }
+
+    #[test]
+    fn test_udp_setting_enables_udp_over_tcp() {
+        let content = "proxies:\n\
+            - {name: plain, type: ss, server: 1.2.3.4, port: 8388, cipher: aes-128-gcm, password: pw}\n\
+            - {name: off, type: ss, server: 1.2.3.4, port: 8389, cipher: aes-128-gcm, password: pw, udp-over-tcp: false}\n";
+        let mut nodes = Vec::new();
+        assert!(crate::parser::explodes::explode_clash(content, &mut nodes));
+        let mut ext = ExtraSettings::default();
+        ext.enable_rule_generator = false;
+
+        let output = proxy_to_singbox(
+            &mut nodes.clone(),
+            "{}",
+            &mut Vec::new(),
+            &Vec::new(),
+            &mut ext,
+        );
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        assert!(outbound_by_tag(&json, "plain")
+            .unwrap()
+            .get("udp_over_tcp")
+            .is_none());
+
+        ext.udp = Some(true);
+        let output = proxy_to_singbox(&mut nodes, "{}", &mut Vec::new(), &Vec::new(), &mut ext);
+        let json: JsonValue = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            outbound_by_tag(&json, "plain").unwrap()["udp_over_tcp"],
+            true
+        );
+        assert!(outbound_by_tag(&json, "off")
+            .unwrap()
+            .get("udp_over_tcp")
+            .is_none());
+    }
 }
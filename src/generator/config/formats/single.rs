@@ -1,5 +1,10 @@
-use crate::models::{ExtraSettings, Proxy, ProxyType, SSR_CIPHERS, SS_CIPHERS};
+use crate::models::proxy_codec::emit_custom_line;
+use crate::models::{
+    is_ss2022_cipher, trojan_go_skip_reason, ExtraSettings, Proxy, ProxyType, SubconverterTarget,
+    TrojanGoSupport, SSR_CIPHERS, SS_CIPHERS,
+};
 use crate::utils::base64::{base64_encode, url_safe_base64_encode};
+use crate::utils::network::join_host_port;
 use crate::utils::url::url_encode;
 use log::error;
 // Bitflags for proxy types used in conversions
@@ -16,6 +21,48 @@ bitflags! {
     }
 }
 
+/// Generate the SIP002 URI of a Shadowsocks node
+///
+/// The userinfo is unpadded URL-safe base64 of `method:password`, except
+/// for Shadowsocks 2022 ciphers whose keys are percent-encoded as SIP002
+/// requires. The plugin and its options go percent-encoded into `plugin`,
+/// `simple-obfs` as `obfs-local`, the name SIP002 clients run it by.
+///
+/// # Returns
+/// * `None` if the node is not a Shadowsocks node or has no cipher
+pub fn ss_sip002_link(node: &Proxy) -> Option<String> {
+    if node.proxy_type != ProxyType::Shadowsocks {
+        return None;
+    }
+    let method = node.encrypt_method.as_deref().filter(|m| !m.is_empty())?;
+    let password = node.password.as_deref().unwrap_or("");
+    let userinfo = if is_ss2022_cipher(method) {
+        format!("{}:{}", url_encode(method), url_encode(password))
+    } else {
+        url_safe_base64_encode(&format!("{}:{}", method, password))
+    };
+    let mut link = format!(
+        "ss://{}@{}",
+        userinfo,
+        join_host_port(&node.hostname, node.port)
+    );
+
+    let plugin = match node.plugin.as_deref().unwrap_or("") {
+        "simple-obfs" => "obfs-local",
+        plugin => plugin,
+    };
+    if !plugin.is_empty() {
+        let plugin = match node.plugin_option.as_deref().unwrap_or("") {
+            "" => plugin.to_string(),
+            opts => format!("{};{}", plugin, opts),
+        };
+        link.push_str(&format!("/?plugin={}", url_encode(&plugin)));
+    }
+
+    link.push_str(&format!("#{}", url_encode(&node.remark)));
+    Some(link)
+}
+
 /// Generate a VMess link
 ///
 /// # Arguments
@@ -92,6 +139,8 @@ pub fn proxy_to_single(
         let remark = &node.remark;
         let hostname = &node.hostname;
         let port = node.port.to_string();
+        // `host:port` positions need brackets around IPv6 literals
+        let address = join_host_port(hostname, node.port);
 
         // Extract optional fields with safe defaults
         let password = node.password.as_deref().unwrap_or("");
@@ -106,7 +155,11 @@ pub fn proxy_to_single(
         let transfer_protocol = node.transfer_protocol.as_deref().unwrap_or("");
         let host = node.host.as_deref().unwrap_or("");
         let path = node.path.as_deref().unwrap_or("");
-        let fake_type = node.fake_type.as_deref();
+        // v2rayN keeps the gRPC mode in the header type field
+        let fake_type = match node.grpc_mode {
+            Some(mode) if transfer_protocol == "grpc" => Some(mode.as_str()),
+            _ => node.fake_type.as_deref(),
+        };
         let tls_secure = node.tls_secure;
         let alter_id = node.alter_id;
         let group = node.group.as_ref();
@@ -115,13 +168,20 @@ pub fn proxy_to_single(
 
         match node.proxy_type {
             ProxyType::Shadowsocks => {
-                if types.contains(ProxyUriTypes::SS) {
+                if types.contains(ProxyUriTypes::SS) && ext.ss_sip002 {
+                    match ss_sip002_link(node) {
+                        Some(link) => _proxy_str = link,
+                        None => {
+                            ext.skip_node("Shadowsocks", &node.remark, "missing cipher");
+                            continue;
+                        }
+                    }
+                } else if types.contains(ProxyUriTypes::SS) {
                     // SS format
                     _proxy_str = format!(
-                        "ss://{}@{}:{}",
+                        "ss://{}@{}",
                         url_safe_base64_encode(&format!("{}:{}", method, password)),
-                        hostname,
-                        port
+                        address
                     );
 
                     if !plugin.is_empty() && !plugin_opts.is_empty() {
@@ -177,10 +237,9 @@ pub fn proxy_to_single(
                     // Convert SSR to SS if compatible
                     if SS_CIPHERS.contains(&method) && protocol == "origin" && obfs == "plain" {
                         _proxy_str = format!(
-                            "ss://{}@{}:{}#{}",
+                            "ss://{}@{}#{}",
                             url_safe_base64_encode(&format!("{}:{}", method, password)),
-                            hostname,
-                            port,
+                            address,
                             url_encode(remark)
                         );
                     } else {
@@ -215,13 +274,16 @@ pub fn proxy_to_single(
                 if !types.contains(ProxyUriTypes::TROJAN) {
                     continue;
                 }
+                if let Some(reason) = trojan_go_skip_reason(node, TrojanGoSupport::WEBSOCKET) {
+                    ext.skip_node("Trojan", &node.remark, reason);
+                    continue;
+                }
 
                 // Trojan format
                 _proxy_str = format!(
-                    "trojan://{}@{}:{}?allowInsecure={}",
+                    "trojan://{}@{}?allowInsecure={}",
                     password,
-                    hostname,
-                    port,
+                    address,
                     if node.allow_insecure.unwrap_or(false) {
                         "1"
                     } else {
@@ -242,6 +304,15 @@ pub fn proxy_to_single(
 
                 _proxy_str.push_str(&format!("#{}", url_encode(remark)));
             }
+            // Only mixed links carry protocols of registered codecs
+            ProxyType::Unknown
+                if types == ProxyUriTypes::MIXED && node.custom_proxy().is_some() =>
+            {
+                match emit_custom_line(node, &SubconverterTarget::Mixed, ext) {
+                    Some(link) => _proxy_str = link,
+                    None => continue,
+                }
+            }
             _ => continue,
         }
 
@@ -256,3 +327,77 @@ pub fn proxy_to_single(
         base64_encode(&all_links)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_links_bracket_ipv6() {
+        let mut nodes = vec![
+            Proxy {
+                proxy_type: ProxyType::Shadowsocks,
+                remark: "SS".to_string(),
+                hostname: "2001:db8::1".to_string(),
+                port: 8388,
+                password: Some("secret".to_string()),
+                encrypt_method: Some("aes-128-gcm".to_string()),
+                ..Default::default()
+            },
+            Proxy {
+                proxy_type: ProxyType::Trojan,
+                remark: "Trojan".to_string(),
+                hostname: "2001:db8::3".to_string(),
+                port: 443,
+                password: Some("secret".to_string()),
+                ..Default::default()
+            },
+        ];
+        let output = proxy_to_single(
+            &mut nodes,
+            ProxyUriTypes::SS | ProxyUriTypes::TROJAN,
+            &mut ExtraSettings {
+                nodelist: true,
+                ..Default::default()
+            },
+        );
+        let links: Vec<&str> = output.lines().collect();
+
+        assert!(links[0].contains("@[2001:db8::1]:8388#SS"));
+        assert!(links[1].starts_with("trojan://secret@[2001:db8::3]:443?"));
+    }
+
+    fn round_trip(link: &str) -> String {
+        let mut node = Proxy::default();
+        assert!(crate::parser::explodes::explode_ss(link, &mut node));
+        ss_sip002_link(&node).unwrap()
+    }
+
+    #[test]
+    fn test_ss_sip002_link() {
+        let obfs = "ss://YWVzLTEyOC1nY206dGVzdA@192.168.100.1:8888/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dexample.com#Example%202";
+        assert_eq!(round_trip(obfs), obfs);
+
+        let v2ray = "ss://Y2hhY2hhMjAtaWV0Zi1wb2x5MTMwNTpwYXNz@[2001:db8::1]:443/?plugin=v2ray-plugin%3Bmode%3Dwebsocket%3Bhost%3Dcdn.example.com%3Bpath%3D%2Fws%3Btls#WS%20%E9%A6%99%E6%B8%AF";
+        assert_eq!(round_trip(v2ray), v2ray);
+
+        let ss2022 =
+            "ss://2022-blake3-aes-128-gcm:AAAAAAAAAAAAAAAAAAAAAA%3D%3D@example.com:8388#SS2022";
+        assert_eq!(round_trip(ss2022), ss2022);
+
+        let mut node = Proxy::default();
+        assert!(crate::parser::explodes::explode_ss(obfs, &mut node));
+        node.plugin = Some("simple-obfs".to_string());
+        node.plugin_option = None;
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ss_sip002: true,
+            ..Default::default()
+        };
+        let output = proxy_to_single(&mut vec![node], ProxyUriTypes::SS, &mut ext);
+        assert_eq!(
+            output.trim(),
+            "ss://YWVzLTEyOC1nY206dGVzdA@192.168.100.1:8888/?plugin=obfs-local#Example%202"
+        );
+    }
+}
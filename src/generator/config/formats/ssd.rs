@@ -1,3 +1,4 @@
+use crate::models::is_ss2022_cipher;
 use crate::models::Proxy;
 use crate::models::ProxyType;
 use crate::utils::base64::base64_encode;
@@ -16,7 +17,7 @@ pub fn proxy_to_ssd(
     nodes: &mut Vec<Proxy>,
     group: &str,
     userinfo: &str,
-    _ext: &crate::models::ExtraSettings,
+    ext: &crate::models::ExtraSettings,
 ) -> String {
     let mut index = 0;
     let group = if group.is_empty() { "SSD" } else { group };
@@ -78,6 +79,16 @@ pub fn proxy_to_ssd(
     for node in nodes.iter() {
         match node.proxy_type {
             ProxyType::Shadowsocks => {
+                let method = node.encrypt_method.as_deref().unwrap_or("");
+                if is_ss2022_cipher(method) {
+                    ext.skip_node(
+                        "SSD",
+                        &node.remark,
+                        format!("cipher {} is not supported", method),
+                    );
+                    continue;
+                }
+
                 // Handle plugin conversion
                 let plugin = node
                     .plugin
@@ -1,15 +1,22 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::capabilities::supported_tfo;
+use crate::generator::config::group::group_generate_members;
 use crate::generator::config::remark::process_remark;
 use crate::generator::ruleconvert::ruleset_to_surge::ruleset_to_surge;
+use crate::generator::ruleconvert::surge_rule_section::retain_known_groups;
+use crate::models::proxy_codec::emit_custom_line;
+use crate::models::proxy_node::ssr::normalize_ssr_params;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent,
+    auth_skip_reason, trojan_go_skip_reason, AuthRule, Degrade, ExtraSettings, Proxy,
+    ProxyGroupConfigs, ProxyGroupType, ProxyType, RulesetContent, SubconverterTarget,
+    TrojanGoSupport,
 };
 use crate::utils::ini_reader::IniReader;
-use crate::utils::network::{hostname_to_ip_addr, is_ipv4, is_ipv6};
+use crate::utils::network::{hostname_to_ip_addr, is_ipv4, is_ipv6, join_host_port};
 use crate::utils::string::{hash, join, to_lower};
 use crate::utils::tribool::{BoolTriboolExt, TriboolExt};
 use crate::Settings;
-use log::error;
+use log::{error, warn};
+use std::collections::HashMap;
 
 /// Generate a WireGuard peer configuration string
 ///
@@ -29,9 +36,7 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
     }
 
     peer.push_str("endpoint = ");
-    peer.push_str(&node.hostname);
-    peer.push_str(":");
-    peer.push_str(&node.port.to_string());
+    peer.push_str(&join_host_port(&node.hostname, node.port));
 
     if !node.allowed_ips.is_empty() {
         peer.push_str(", allowed-ips = ");
@@ -53,6 +58,22 @@ fn generate_peer(node: &Proxy, client_id_as_reserved: bool) -> String {
     peer
 }
 
+/// Check that a Snell node can be written for a Surge version
+///
+/// Snell v4 and v5 need Surge 5, and only the `http` and `tls` obfuscation
+/// modes exist; an empty, `none` or `off` mode disables obfuscation.
+fn check_snell(obfs: &str, version: u16, surge_ver: i32) -> Result<(), String> {
+    if !matches!(obfs, "" | "none" | "off" | "http" | "tls") {
+        return Err(format!("unsupported obfs mode '{}'", obfs));
+    }
+    match version {
+        0..=3 => Ok(()),
+        4 | 5 if surge_ver >= 5 => Ok(()),
+        4 | 5 => Err(format!("Snell v{} requires Surge 5", version)),
+        _ => Err(format!("unsupported Snell version {}", version)),
+    }
+}
+
 /// Convert proxies to Surge format
 ///
 /// This function converts a list of proxies to the Surge configuration format,
@@ -81,7 +102,12 @@ pub async fn proxy_to_surge(
     let mut nodelist = Vec::new();
     let mut local_port = 1080;
     let mut remarks_list = Vec::new();
-    let global = Settings::current();
+    let global = Settings::snapshot();
+    let target = if surge_ver == -3 {
+        SubconverterTarget::Surfboard
+    } else {
+        SubconverterTarget::Surge(surge_ver)
+    };
 
     // Configure INI reader
     ini.store_any_line = true;
@@ -115,8 +141,22 @@ pub async fn proxy_to_surge(
     ini.erase_section();
     ini.set("{NONAME}", "DIRECT", "direct").unwrap_or(());
 
+    let block_quic = match ext.surge_block_quic.as_str() {
+        "" | "on" | "off" | "auto" => ext.surge_block_quic.as_str(),
+        other => {
+            warn!("Ignoring invalid Surge block-quic value '{}'", other);
+            ""
+        }
+    };
+
+    // Lines are written once every node is named, upstreams are named by
+    // their emitted names
+    let mut proxy_lines = Vec::new();
+    let mut emitted_names: HashMap<String, String> = HashMap::new();
+
     // Process each proxy node
     for node in nodes.iter_mut() {
+        let original_remark = node.remark.clone();
         // Add proxy type prefix if enabled
         if ext.append_proxy_type {
             let proxy_type = node.proxy_type.to_string();
@@ -127,6 +167,12 @@ pub async fn proxy_to_surge(
         let mut remark = node.remark.clone();
         process_remark(&mut remark, &remarks_list, false);
 
+        // Trojan lines carry no transport, a Trojan-Go node with one is
+        // emitted as plain Trojan when the fallback is enabled
+        if trojan_go_skip_reason(node, TrojanGoSupport::PLAIN).is_some() {
+            ext.degrade_node("Surge", node, Degrade::TrojanGo);
+        }
+
         // Extract node properties for easier access
         let hostname = &node.hostname;
         let port = node.port.to_string();
@@ -144,17 +190,17 @@ pub async fn proxy_to_surge(
         let obfsparam = node.obfs_param.as_deref().unwrap_or("");
         let plugin = node.plugin.as_deref().unwrap_or("");
         let pluginopts = node.plugin_option.as_deref().unwrap_or("");
-        let underlying_proxy = node.underlying_proxy.as_deref().unwrap_or("");
+        let underlying_proxy = node.underlying_proxy.clone().unwrap_or_default();
         let tls_secure = node.tls_secure;
 
         // Define tribool values with defaults from ext and override with node-specific values
         let mut udp = ext.udp;
         let mut tfo = ext.tfo;
-        let mut scv = ext.skip_cert_verify;
+        let mut scv = ext.default_skip_cert_verify(node);
         let mut tls13 = ext.tls13;
 
         udp = node.udp.define(udp);
-        tfo = node.tcp_fast_open.define(tfo);
+        tfo = supported_tfo(&target, node, node.tcp_fast_open.define(tfo));
         scv = node.allow_insecure.define(scv);
         tls13 = node.tls13.define(tls13);
 
@@ -164,6 +210,12 @@ pub async fn proxy_to_surge(
         let mut _args = Vec::new();
         let mut headers = Vec::new();
 
+        // Surge sends HTTP and SOCKS5 credentials with a username
+        if let Some(reason) = auth_skip_reason(node, AuthRule::UsernameRequired) {
+            ext.skip_node("Surge", &remark, reason);
+            continue;
+        }
+
         // Build proxy string based on type
         match node.proxy_type {
             ProxyType::Shadowsocks => {
@@ -184,12 +236,20 @@ pub async fn proxy_to_surge(
                                 _proxy.push_str(&format!(",{}", pluginopts.replace(';', ",")));
                             }
                         }
-                        _ => continue,
+                        _ => {
+                            ext.skip_node(
+                                "Surge",
+                                &remark,
+                                format!("unsupported plugin '{}'", plugin),
+                            );
+                            continue;
+                        }
                     }
                 }
             }
             ProxyType::VMess => {
                 if surge_ver < 4 && surge_ver != -3 {
+                    ext.skip_node("Surge", &remark, "VMess needs Surge 4");
                     continue;
                 }
 
@@ -253,8 +313,16 @@ pub async fn proxy_to_surge(
             }
             ProxyType::ShadowsocksR => {
                 if ext.surge_ssr_path.is_empty() || surge_ver < 2 {
+                    ext.skip_node("Surge", &remark, "SSR needs Surge 2 and surge_ssr_path");
                     continue;
                 }
+                let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+                    Ok(params) => params,
+                    Err(e) => {
+                        ext.skip_node("Surge", &remark, e);
+                        continue;
+                    }
+                };
 
                 _proxy = format!("external, exec=\"{}\", args=\"", ext.surge_ssr_path);
                 _args = vec![
@@ -289,7 +357,8 @@ pub async fn proxy_to_surge(
 
                 if is_ipv4(hostname) || is_ipv6(hostname) {
                     _proxy.push_str(&format!(", addresses={}", hostname));
-                } else if global.surge_resolve_hostname {
+                } else if global.surge_resolve_hostname && !ext.deterministic {
+                    // Resolved addresses change between runs
                     if let Some(ip) = hostname_to_ip_addr(hostname) {
                         _proxy.push_str(&format!(", addresses={}", ip));
                     }
@@ -391,6 +460,11 @@ pub async fn proxy_to_surge(
             }
             ProxyType::Trojan => {
                 if surge_ver < 4 && surge_ver != -3 {
+                    ext.skip_node("Surge", &remark, "Trojan needs Surge 4");
+                    continue;
+                }
+                if let Some(reason) = trojan_go_skip_reason(node, TrojanGoSupport::PLAIN) {
+                    ext.skip_node("Surge", &remark, reason);
                     continue;
                 }
 
@@ -416,9 +490,14 @@ pub async fn proxy_to_surge(
                 }
             }
             ProxyType::Snell => {
+                if let Err(e) = check_snell(obfs, node.snell_version, surge_ver) {
+                    ext.skip_node("Surge", &remark, e);
+                    continue;
+                }
+
                 _proxy = format!("snell, {}, {}, psk={}", hostname, port, password);
 
-                if !obfs.is_empty() {
+                if !matches!(obfs, "" | "none" | "off") {
                     _proxy.push_str(&format!(", obfs={}", obfs));
 
                     if !host.is_empty() {
@@ -432,6 +511,7 @@ pub async fn proxy_to_surge(
             }
             ProxyType::WireGuard => {
                 if surge_ver < 4 && surge_ver != -3 {
+                    ext.skip_node("Surge", &remark, "WireGuard needs Surge 4");
                     continue;
                 }
 
@@ -440,12 +520,6 @@ pub async fn proxy_to_surge(
                 _real_section = format!("WireGuard {}", _section);
                 _proxy = format!("wireguard, section-name={}", _section);
 
-                if let Some(test_url) = &node.test_url {
-                    if !test_url.is_empty() {
-                        _proxy.push_str(&format!(", test-url={}", test_url));
-                    }
-                }
-
                 if let Some(private_key) = &node.private_key {
                     ini.set(&_real_section, "private-key", private_key)
                         .unwrap_or(());
@@ -494,6 +568,7 @@ pub async fn proxy_to_surge(
             }
             ProxyType::Hysteria2 => {
                 if surge_ver < 4 {
+                    ext.skip_node("Surge", &remark, "Hysteria2 needs Surge 4");
                     continue;
                 }
 
@@ -526,8 +601,25 @@ pub async fn proxy_to_surge(
                         _proxy.push_str(&format!(",sni={}", sni));
                     }
                 }
+
+                if !node.alpn.is_empty() {
+                    _proxy.push_str(&format!(",alpn={}", node.alpn.join(",")));
+                }
+            }
+            ProxyType::Unknown if node.custom_proxy().is_some() => {
+                match emit_custom_line(node, &target, ext) {
+                    Some(line) => _proxy = line,
+                    None => continue,
+                }
+            }
+            _ => {
+                ext.skip_node(
+                    "Surge",
+                    &node.remark,
+                    format!("{} is not supported", node.proxy_type.to_string()),
+                );
+                continue;
             }
-            _ => continue,
         }
 
         // Add common options
@@ -553,20 +645,45 @@ pub async fn proxy_to_surge(
             ));
         }
 
+        if let Some(test_url) = node.test_url.as_deref().filter(|url| !url.is_empty()) {
+            _proxy.push_str(&format!(", test-url={}", test_url));
+        }
+
+        if surge_ver >= 5 && !block_quic.is_empty() {
+            _proxy.push_str(&format!(", block-quic={}", block_quic));
+        }
+
+        if !ext.nodelist {
+            nodelist.push(node.clone());
+        }
+        emitted_names
+            .entry(original_remark)
+            .or_insert_with(|| remark.clone());
+        proxy_lines.push((remark.clone(), _proxy, underlying_proxy));
+        remarks_list.push(remark);
+    }
+    for (remark, mut proxy, underlying_proxy) in proxy_lines {
         if !underlying_proxy.is_empty() {
-            _proxy.push_str(&format!(", underlying-proxy={}", underlying_proxy));
+            let upstream = emitted_names
+                .get(&underlying_proxy)
+                .unwrap_or(&underlying_proxy);
+            proxy.push_str(&format!(", underlying-proxy={}", upstream));
         }
 
         // Add to nodelist or INI
         if ext.nodelist {
-            output_nodelist.push_str(&format!("{} = {}\n", remark, _proxy));
+            output_nodelist.push_str(&format!("{} = {}\n", remark, proxy));
         } else {
-            ini.set("{NONAME}", &format!("{} = {}", remark, _proxy), "")
+            ini.set("{NONAME}", &format!("{} = {}", remark, proxy), "")
                 .unwrap_or(());
-            nodelist.push(node.clone());
         }
-
-        remarks_list.push(remark);
+    }
+    for line in ext.unsupported_comments("Surge", "#") {
+        if ext.nodelist {
+            output_nodelist.push_str(&format!("{}\n", line));
+        } else {
+            ini.set_current("{NONAME}", &line).unwrap_or(());
+        }
     }
 
     if ext.nodelist {
@@ -604,13 +721,13 @@ pub async fn proxy_to_surge(
         }
 
         // Generate filtered proxy list
-        for proxy_name in &group.proxies {
-            group_generate(proxy_name, &nodelist, &mut filtered_nodelist, true, ext);
-        }
-
-        if filtered_nodelist.is_empty() {
-            filtered_nodelist.push("DIRECT".to_string());
-        }
+        filtered_nodelist.extend(group_generate_members(
+            group,
+            &nodelist,
+            true,
+            &ext.group_fallback,
+            ext,
+        ));
 
         if filtered_nodelist.len() == 1 {
             let proxy_name = to_lower(&filtered_nodelist[0]);
@@ -661,11 +778,12 @@ pub async fn proxy_to_surge(
             .unwrap_or(());
     }
 
-    // Generate rules if enabled
+    // Generate rules if enabled, dropping those aimed at missing groups
     if ext.enable_rule_generator {
+        let rulesets = retain_known_groups(ruleset_content_array, extra_proxy_group, &remarks_list);
         ruleset_to_surge(
             &mut ini,
-            ruleset_content_array,
+            &rulesets,
             surge_ver,
             ext.overwrite_original_rules,
             &ext.managed_config_prefix,
@@ -675,3 +793,160 @@ pub async fn proxy_to_surge(
 
     ini.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snell(remark: &str, obfs: &str, version: u16) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Snell,
+            remark: remark.to_string(),
+            hostname: "snell.example.com".to_string(),
+            port: 443,
+            password: Some("psk".to_string()),
+            obfs: Some(obfs.to_string()),
+            host: Some("bing.com".to_string()),
+            snell_version: version,
+            test_url: Some("http://cp.cloudflare.com".to_string()),
+            underlying_proxy: Some("Relay".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn nodelist(surge_ver: i32, ext: &mut ExtraSettings) -> String {
+        let mut nodes = vec![
+            snell("V3", "http", 3),
+            snell("V4", "none", 4),
+            snell("Bad", "salamander", 3),
+        ];
+        ext.nodelist = true;
+        futures::executor::block_on(proxy_to_surge(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &ProxyGroupConfigs::new(),
+            surge_ver,
+            ext,
+        ))
+    }
+
+    #[test]
+    fn test_surge_snell_by_version() {
+        let mut ext = ExtraSettings {
+            surge_block_quic: "on".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            nodelist(5, &mut ext),
+            "V3 = snell, snell.example.com, 443, psk=psk, obfs=http, obfs-host=bing.com, \
+             version=3, test-url=http://cp.cloudflare.com, block-quic=on, \
+             underlying-proxy=Relay\n\
+             V4 = snell, snell.example.com, 443, psk=psk, version=4, \
+             test-url=http://cp.cloudflare.com, block-quic=on, underlying-proxy=Relay\n"
+        );
+
+        // Surge 3 gets neither Snell v4 nor block-quic
+        assert_eq!(
+            nodelist(3, &mut ext),
+            "V3 = snell, snell.example.com, 443, psk=psk, obfs=http, obfs-host=bing.com, \
+             version=3, test-url=http://cp.cloudflare.com, underlying-proxy=Relay\n"
+        );
+    }
+
+    #[test]
+    fn test_underlying_proxy_follows_emitted_names() {
+        let mut relay = snell("Relay", "http", 3);
+        relay.underlying_proxy = None;
+        let mut nodes = vec![snell("Exit", "http", 3), relay];
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            append_proxy_type: true,
+            ..Default::default()
+        };
+        let output = futures::executor::block_on(proxy_to_surge(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &ProxyGroupConfigs::new(),
+            4,
+            &mut ext,
+        ));
+        let exit = output.lines().next().unwrap();
+        assert!(exit.starts_with("[Snell] Exit = "));
+        assert!(exit.ends_with(", underlying-proxy=[Snell] Relay"));
+    }
+
+    #[test]
+    fn test_surge_skips_invalid_ssr() {
+        let ssr = |remark: &str, obfs: &str| Proxy {
+            proxy_type: ProxyType::ShadowsocksR,
+            remark: remark.to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 8388,
+            password: Some("secret".to_string()),
+            encrypt_method: Some("aes-256-cfb".to_string()),
+            protocol: Some("auth_aes128_md5".to_string()),
+            obfs: Some(obfs.to_string()),
+            ..Default::default()
+        };
+        let mut nodes = vec![
+            ssr("Valid", "tls1.2_ticket_auth"),
+            ssr("Invalid", "obfs_tls"),
+        ];
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            surge_ssr_path: "/usr/bin/ssr-local".to_string(),
+            ..Default::default()
+        };
+        let output = futures::executor::block_on(proxy_to_surge(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &ProxyGroupConfigs::new(),
+            4,
+            &mut ext,
+        ));
+
+        assert!(output.starts_with("Valid = external, exec=\"/usr/bin/ssr-local\""));
+        assert!(!output.contains("Invalid"));
+    }
+
+    #[test]
+    fn test_check_snell() {
+        assert!(check_snell("tls", 2, 3).is_ok());
+        assert!(check_snell("", 5, 5).is_ok());
+        assert!(check_snell("", 4, 4).is_err());
+        assert!(check_snell("", 6, 5).is_err());
+        assert!(check_snell("shadow-tls", 4, 5).is_err());
+    }
+
+    #[test]
+    fn test_surge_degrades_trojan_go() {
+        let mut node = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "trojan-go://secret@example.com:443/?sni=real.example.com&type=ws&host=cdn.example.com&path=%2Fws#Go",
+            &mut node
+        ));
+        let render = |ext: &mut ExtraSettings| {
+            ext.nodelist = true;
+            futures::executor::block_on(proxy_to_surge(
+                &mut vec![node.clone()],
+                "",
+                &mut Vec::new(),
+                &ProxyGroupConfigs::new(),
+                4,
+                ext,
+            ))
+        };
+
+        assert!(!render(&mut ExtraSettings::default()).contains("Go = "));
+        let mut ext = ExtraSettings {
+            degrade: "trojan-go".parse().unwrap(),
+            ..Default::default()
+        };
+        let output = render(&mut ext);
+        assert!(output.contains("Go = trojan, example.com, 443, password=secret"));
+    }
+}
@@ -0,0 +1,443 @@
+//! V2Ray outbound JSON output
+//!
+//! Emits the nodes as a JSON array of V2Ray `outbounds` entries, each with
+//! `protocol`, `settings` and `streamSettings`, for Mellow and other clients
+//! built on V2Ray core. Protocols V2Ray has no outbound for are skipped.
+
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::generator::config::capabilities::warn_legacy_vmess;
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::vless::VlessProxy;
+use crate::models::{
+    trojan_go_skip_reason, ExtraSettings, Proxy, ProxyType, SubconverterTarget, Transport,
+    TrojanGoSupport,
+};
+use crate::utils::tribool::TriboolExt;
+
+/// Keep a string only if it is not empty
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+/// TLS options of an outbound
+#[derive(Debug, Default)]
+struct TlsOptions<'a> {
+    server_name: Option<&'a str>,
+    allow_insecure: Option<bool>,
+    alpn: &'a [String],
+    fingerprint: Option<&'a str>,
+    reality: Option<(&'a str, Option<&'a str>)>,
+}
+
+/// Transport options of an outbound
+#[derive(Debug, Default)]
+struct StreamOptions<'a> {
+    network: Option<&'a str>,
+    host: Option<&'a str>,
+    path: Option<&'a str>,
+    fake_type: Option<&'a str>,
+    multi_mode: bool,
+    tls: Option<TlsOptions<'a>>,
+}
+
+/// Build the `streamSettings` object of an outbound
+fn stream_settings(options: &StreamOptions) -> Result<JsonValue, String> {
+    let transport = match options.network {
+        Some(network) => network.parse::<Transport>()?,
+        None => Transport::Tcp,
+    };
+    let path = options.path.unwrap_or("");
+    let host = options.host.unwrap_or("");
+
+    let mut stream = Map::new();
+    let network = match transport {
+        Transport::Tcp | Transport::Http => "tcp",
+        Transport::Ws => "ws",
+        Transport::HttpUpgrade => "httpupgrade",
+        Transport::H2 => "http",
+        Transport::Grpc => "grpc",
+        Transport::Kcp => "kcp",
+        Transport::Quic => "quic",
+    };
+    stream.insert("network".to_string(), json!(network));
+
+    match transport {
+        // HTTP header obfuscation over TCP
+        Transport::Tcp | Transport::Http
+            if transport == Transport::Http || options.fake_type == Some("http") =>
+        {
+            let mut request = Map::new();
+            request.insert("path".to_string(), json!([path_or_root(path)]));
+            if !host.is_empty() {
+                request.insert("headers".to_string(), json!({ "Host": [host] }));
+            }
+            stream.insert(
+                "tcpSettings".to_string(),
+                json!({ "header": { "type": "http", "request": request } }),
+            );
+        }
+        Transport::Ws | Transport::HttpUpgrade => {
+            let key = if transport == Transport::Ws {
+                "wsSettings"
+            } else {
+                "httpupgradeSettings"
+            };
+            let mut settings = Map::new();
+            settings.insert("path".to_string(), json!(path_or_root(path)));
+            if !host.is_empty() {
+                if transport == Transport::Ws {
+                    settings.insert("headers".to_string(), json!({ "Host": host }));
+                } else {
+                    settings.insert("host".to_string(), json!(host));
+                }
+            }
+            stream.insert(key.to_string(), JsonValue::Object(settings));
+        }
+        Transport::H2 => {
+            let mut settings = Map::new();
+            settings.insert("path".to_string(), json!(path_or_root(path)));
+            if !host.is_empty() {
+                settings.insert("host".to_string(), json!([host]));
+            }
+            stream.insert("httpSettings".to_string(), JsonValue::Object(settings));
+        }
+        Transport::Grpc => {
+            stream.insert(
+                "grpcSettings".to_string(),
+                json!({ "serviceName": path, "multiMode": options.multi_mode }),
+            );
+        }
+        _ => {}
+    }
+
+    match &options.tls {
+        Some(tls) => {
+            let mut settings = Map::new();
+            if let Some(server_name) = tls.server_name {
+                settings.insert("serverName".to_string(), json!(server_name));
+            }
+            if let Some(fingerprint) = tls.fingerprint {
+                settings.insert("fingerprint".to_string(), json!(fingerprint));
+            }
+            if let Some((public_key, short_id)) = tls.reality {
+                settings.insert("publicKey".to_string(), json!(public_key));
+                settings.insert("shortId".to_string(), json!(short_id.unwrap_or("")));
+                stream.insert("security".to_string(), json!("reality"));
+                stream.insert("realitySettings".to_string(), JsonValue::Object(settings));
+            } else {
+                if let Some(allow_insecure) = tls.allow_insecure {
+                    settings.insert("allowInsecure".to_string(), json!(allow_insecure));
+                }
+                if !tls.alpn.is_empty() {
+                    settings.insert("alpn".to_string(), json!(tls.alpn));
+                }
+                stream.insert("security".to_string(), json!("tls"));
+                stream.insert("tlsSettings".to_string(), JsonValue::Object(settings));
+            }
+        }
+        None => {
+            stream.insert("security".to_string(), json!("none"));
+        }
+    }
+
+    Ok(JsonValue::Object(stream))
+}
+
+/// Path of a transport, V2Ray needs at least `/`
+fn path_or_root(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+/// Transport options of a node using the flat `Proxy` fields
+fn node_stream<'a>(node: &'a Proxy, scv: Option<bool>) -> StreamOptions<'a> {
+    StreamOptions {
+        network: non_empty(&node.transfer_protocol),
+        host: non_empty(&node.host),
+        path: non_empty(&node.path),
+        fake_type: non_empty(&node.fake_type),
+        multi_mode: node.grpc_mode.is_some_and(|mode| mode.as_str() == "multi"),
+        tls: node.tls_secure.then(|| TlsOptions {
+            server_name: non_empty(&node.sni)
+                .or_else(|| non_empty(&node.server_name))
+                .or_else(|| non_empty(&node.host)),
+            allow_insecure: scv,
+            alpn: &node.alpn,
+            fingerprint: non_empty(&node.client_fingerprint),
+            reality: None,
+        }),
+    }
+}
+
+/// Transport options of a VLESS node
+fn vless_stream<'a>(
+    node: &'a Proxy,
+    vless: &'a VlessProxy,
+    scv: Option<bool>,
+) -> StreamOptions<'a> {
+    let network = non_empty(&vless.network);
+    let (host, path) = match network {
+        Some("ws") => (
+            vless
+                .ws_headers
+                .as_ref()
+                .and_then(|headers| headers.get("Host"))
+                .map(String::as_str),
+            non_empty(&vless.ws_path),
+        ),
+        Some("h2") => (
+            vless
+                .h2_host
+                .as_ref()
+                .and_then(|hosts| hosts.first())
+                .map(String::as_str),
+            non_empty(&vless.h2_path),
+        ),
+        Some("grpc") => (None, non_empty(&vless.grpc_service_name)),
+        Some("http") => (None, non_empty(&vless.http_path)),
+        _ => (None, None),
+    };
+    StreamOptions {
+        network,
+        host,
+        path,
+        fake_type: None,
+        multi_mode: vless.grpc_mode.is_some_and(|mode| mode.as_str() == "multi"),
+        tls: (vless.tls || vless.is_reality()).then(|| TlsOptions {
+            server_name: non_empty(&vless.servername).or_else(|| non_empty(&node.sni)),
+            allow_insecure: vless.skip_cert_verify.or(scv),
+            alpn: &vless.alpn,
+            fingerprint: non_empty(&vless.client_fingerprint)
+                .or_else(|| non_empty(&node.client_fingerprint)),
+            reality: non_empty(&vless.reality_public_key)
+                .map(|key| (key, non_empty(&vless.reality_short_id))),
+        }),
+    }
+}
+
+/// Convert one node to a V2Ray outbound, `None` for protocols V2Ray lacks
+fn v2ray_outbound(node: &Proxy, ext: &ExtraSettings) -> Result<Option<JsonValue>, String> {
+    let scv = node
+        .allow_insecure
+        .define(ext.default_skip_cert_verify(node));
+    let server = node.hostname.as_str();
+    let port = node.port;
+
+    let (protocol, settings, stream) = match node.proxy_type {
+        ProxyType::Shadowsocks => {
+            let (method, password, plugin) = match &node.combined_proxy {
+                Some(CombinedProxy::Shadowsocks(ss)) => (
+                    ss.cipher.as_str(),
+                    ss.password.as_str(),
+                    non_empty(&ss.plugin).or_else(|| non_empty(&node.plugin)),
+                ),
+                _ => (
+                    node.encrypt_method.as_deref().unwrap_or(""),
+                    node.password.as_deref().unwrap_or(""),
+                    non_empty(&node.plugin),
+                ),
+            };
+            if let Some(plugin) = plugin {
+                return Err(format!("plugin '{}' is not supported", plugin));
+            }
+            let settings = json!({
+                "servers": [{
+                    "address": server,
+                    "port": port,
+                    "method": method,
+                    "password": password,
+                }]
+            });
+            ("shadowsocks", settings, None)
+        }
+        ProxyType::VMess => {
+            warn_legacy_vmess(&SubconverterTarget::V2RayJson, node);
+            let settings = json!({
+                "vnext": [{
+                    "address": server,
+                    "port": port,
+                    "users": [{
+                        "id": node.user_id.as_deref().unwrap_or(""),
+                        "alterId": node.alter_id,
+                        "security": non_empty(&node.encrypt_method).unwrap_or("auto"),
+                    }]
+                }]
+            });
+            ("vmess", settings, Some(node_stream(node, scv)))
+        }
+        ProxyType::Vless => {
+            let Some(CombinedProxy::Vless(vless)) = &node.combined_proxy else {
+                return Err("missing VLESS settings".to_string());
+            };
+            let mut user = Map::new();
+            user.insert("id".to_string(), json!(vless.uuid));
+            user.insert("encryption".to_string(), json!("none"));
+            if let Some(flow) = non_empty(&vless.flow) {
+                user.insert("flow".to_string(), json!(flow));
+            }
+            let settings = json!({
+                "vnext": [{ "address": server, "port": port, "users": [user] }]
+            });
+            ("vless", settings, Some(vless_stream(node, vless, scv)))
+        }
+        ProxyType::Trojan => {
+            if let Some(reason) = trojan_go_skip_reason(node, TrojanGoSupport::WEBSOCKET) {
+                return Err(reason);
+            }
+            let settings = json!({
+                "servers": [{
+                    "address": server,
+                    "port": port,
+                    "password": node.password.as_deref().unwrap_or(""),
+                }]
+            });
+            let mut stream = node_stream(node, scv);
+            // Trojan always runs over TLS
+            stream.tls.get_or_insert_with(|| TlsOptions {
+                server_name: non_empty(&node.sni).or_else(|| non_empty(&node.host)),
+                allow_insecure: scv,
+                alpn: &node.alpn,
+                fingerprint: non_empty(&node.client_fingerprint),
+                reality: None,
+            });
+            ("trojan", settings, Some(stream))
+        }
+        ProxyType::Socks5 | ProxyType::HTTP | ProxyType::HTTPS => {
+            let mut entry = Map::new();
+            entry.insert("address".to_string(), json!(server));
+            entry.insert("port".to_string(), json!(port));
+            if let Some(user) = non_empty(&node.username) {
+                entry.insert(
+                    "users".to_string(),
+                    json!([{ "user": user, "pass": node.password.as_deref().unwrap_or("") }]),
+                );
+            }
+            let protocol = if node.proxy_type == ProxyType::Socks5 {
+                "socks"
+            } else {
+                "http"
+            };
+            let stream =
+                (node.proxy_type == ProxyType::HTTPS || node.tls_secure).then(|| StreamOptions {
+                    tls: Some(TlsOptions {
+                        server_name: non_empty(&node.sni),
+                        allow_insecure: scv,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                });
+            (protocol, json!({ "servers": [entry] }), stream)
+        }
+        _ => return Ok(None),
+    };
+
+    let mut outbound = Map::new();
+    outbound.insert("tag".to_string(), json!(node.remark));
+    outbound.insert("protocol".to_string(), json!(protocol));
+    outbound.insert("settings".to_string(), settings);
+    if let Some(stream) = stream {
+        outbound.insert("streamSettings".to_string(), stream_settings(&stream)?);
+    }
+    Ok(Some(JsonValue::Object(outbound)))
+}
+
+/// Convert nodes to a JSON array of V2Ray outbounds
+///
+/// Nodes are expected to be preprocessed already; no routing rules are
+/// generated. Each outbound is tagged with the node name.
+///
+/// # Arguments
+/// * `nodes` - Nodes to export
+/// * `ext` - Extra settings, for the scv default
+pub fn proxy_to_v2ray_json(nodes: &[Proxy], ext: &ExtraSettings) -> String {
+    let mut outbounds = Vec::new();
+    for node in nodes {
+        match v2ray_outbound(node, ext) {
+            Ok(Some(outbound)) => outbounds.push(outbound),
+            Ok(None) => ext.skip_node(
+                "V2Ray",
+                &node.remark,
+                format!("{} is not supported", node.proxy_type.to_string()),
+            ),
+            Err(e) => ext.skip_node("V2Ray", &node.remark, e),
+        }
+    }
+    serde_json::to_string_pretty(&JsonValue::Array(outbounds)).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::explodes::explode;
+
+    fn outbounds(links: &[&str]) -> JsonValue {
+        let nodes: Vec<Proxy> = links
+            .iter()
+            .map(|link| {
+                let mut node = Proxy::default();
+                assert!(explode(link, &mut node), "failed to parse {}", link);
+                node
+            })
+            .collect();
+        let ext = ExtraSettings::default();
+        serde_json::from_str(&proxy_to_v2ray_json(&nodes, &ext)).unwrap()
+    }
+
+    #[test]
+    fn test_v2ray_outbounds() {
+        let json = outbounds(&[
+            "vmess://uuid@vmess.example.com:443?type=ws&path=/ws&host=cdn.example.com&tls=true&fp=chrome#VMess",
+            "trojan://secret@trojan.example.com:443?sni=real.example.com#Trojan",
+            "ss://YWVzLTI1Ni1nY206cGFzcw@ss.example.com:8388#SS",
+        ]);
+        let outbounds = json.as_array().unwrap();
+        assert_eq!(outbounds.len(), 3);
+
+        let vmess = &outbounds[0];
+        assert_eq!(vmess["tag"], "VMess");
+        assert_eq!(vmess["protocol"], "vmess");
+        assert_eq!(
+            vmess["settings"]["vnext"][0]["address"],
+            "vmess.example.com"
+        );
+        assert_eq!(vmess["settings"]["vnext"][0]["users"][0]["id"], "uuid");
+        assert_eq!(vmess["streamSettings"]["network"], "ws");
+        assert_eq!(vmess["streamSettings"]["wsSettings"]["path"], "/ws");
+        assert_eq!(
+            vmess["streamSettings"]["wsSettings"]["headers"]["Host"],
+            "cdn.example.com"
+        );
+        assert_eq!(vmess["streamSettings"]["security"], "tls");
+        assert_eq!(
+            vmess["streamSettings"]["tlsSettings"]["fingerprint"],
+            "chrome"
+        );
+
+        let trojan = &outbounds[1];
+        assert_eq!(trojan["protocol"], "trojan");
+        assert_eq!(trojan["settings"]["servers"][0]["password"], "secret");
+        assert_eq!(
+            trojan["streamSettings"]["tlsSettings"]["serverName"],
+            "real.example.com"
+        );
+
+        let ss = &outbounds[2];
+        assert_eq!(ss["protocol"], "shadowsocks");
+        assert_eq!(ss["settings"]["servers"][0]["method"], "aes-256-gcm");
+        assert_eq!(ss["settings"]["servers"][0]["password"], "pass");
+        assert!(ss.get("streamSettings").is_none());
+    }
+
+    #[test]
+    fn test_v2ray_skips_unsupported_nodes() {
+        let json = outbounds(&["snell://secret@snell.example.com:443?version=3#Snell"]);
+        assert_eq!(json, JsonValue::Array(Vec::new()));
+
+        let output = proxy_to_v2ray_json(&[], &ExtraSettings::default());
+        assert_eq!(output, "[]");
+    }
+}
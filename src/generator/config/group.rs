@@ -2,8 +2,10 @@
 //!
 //! This module provides functionality for generating proxy groups.
 
+use std::collections::HashMap;
+
 use crate::{
-    models::ExtraSettings,
+    models::{ExtraSettings, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType},
     utils::{
         matcher::{apply_matcher, reg_find},
         starts_with,
@@ -49,6 +51,167 @@ pub fn group_generate(
     }
 }
 
+/// Resolves the members of a proxy group against the node list
+///
+/// Member rules are expanded in order with [`group_generate`]: `[]NAME` adds
+/// a policy or group verbatim, anything else is matched against node remarks.
+/// Nodes whose remark matches the group's `filter` regex are appended
+/// afterwards, skipping any already listed. When
+/// [`ExtraSettings::sorts_group_members`] the nodes are then put in node
+/// list order, see [`sort_group_members`]. A group that ends up empty gets
+/// `fallback` as its only member so the generated config stays valid; an
+/// empty `fallback` leaves it empty.
+pub fn group_generate_members(
+    group: &ProxyGroupConfig,
+    nodelist: &[Proxy],
+    add_direct: bool,
+    fallback: &str,
+    ext: &ExtraSettings,
+) -> Vec<String> {
+    let mut members = Vec::new();
+    for rule in &group.proxies {
+        group_generate(rule, nodelist, &mut members, add_direct, ext);
+    }
+    if let Some(filter) = group.filter.as_deref().filter(|f| !f.is_empty()) {
+        group_filter_nodes(filter, nodelist, &mut members);
+    }
+    if ext.sorts_group_members() {
+        sort_group_members(&mut members, nodelist);
+    }
+    if members.is_empty() && !fallback.is_empty() {
+        members.push(fallback.to_string());
+    }
+    members
+}
+
+/// Puts the nodes among `members` in the order of `nodelist`
+///
+/// Each rule of a group lists its matches in node list order, but a group of
+/// several rules lists the matches of one rule after another. Policies and
+/// groups keep their places, the nodes are reordered in the places between
+/// them.
+pub fn sort_group_members(members: &mut [String], nodelist: &[Proxy]) {
+    let mut order: HashMap<&str, usize> = HashMap::new();
+    for (index, node) in nodelist.iter().enumerate() {
+        order.entry(node.remark.as_str()).or_insert(index);
+    }
+    let slots: Vec<usize> = (0..members.len())
+        .filter(|&slot| order.contains_key(members[slot].as_str()))
+        .collect();
+    let mut nodes: Vec<String> = slots.iter().map(|&slot| members[slot].clone()).collect();
+    nodes.sort_by_key(|remark| order[remark.as_str()]);
+    for (slot, remark) in slots.into_iter().zip(nodes) {
+        members[slot] = remark;
+    }
+}
+
+/// Appends the remarks of nodes matching a group filter regex
+///
+/// Only node remarks are matched, `!!GROUP=` style matchers and `[]` policies
+/// are not interpreted. Remarks already in `members` are not added again.
+pub fn group_filter_nodes(filter: &str, nodelist: &[Proxy], members: &mut Vec<String>) {
+    for node in nodelist {
+        if reg_find(&node.remark, filter) && !members.contains(&node.remark) {
+            members.push(node.remark.clone());
+        }
+    }
+}
+
+/// Drops the groups of fewer than `min_size` nodes, folding their nodes into
+/// the groups that listed them
+///
+/// Only groups filled from node matches alone are dropped: a group listing a
+/// `[]` policy or group, or pulling from a provider, is kept, and so is the
+/// main selector, the first `select` group. Each group that listed a dropped
+/// group as `[]NAME` gets its nodes there instead; when none did, they join
+/// the main selector. A group with nowhere to fold into is kept.
+///
+/// # Returns
+/// The name of each dropped group with the group its nodes went to
+pub fn fold_small_groups(
+    groups: &mut ProxyGroupConfigs,
+    nodelist: &[Proxy],
+    min_size: usize,
+    ext: &ExtraSettings,
+) -> Vec<(String, String)> {
+    if min_size == 0 {
+        return Vec::new();
+    }
+    let main = groups
+        .iter()
+        .position(|group| group.group_type == ProxyGroupType::Select);
+    // Decided up front, folding removes `[]` references from the parents
+    let eligible: Vec<bool> = groups
+        .iter()
+        .enumerate()
+        .map(|(index, group)| {
+            Some(index) != main
+                && group.using_provider.is_empty()
+                && !group.proxies.iter().any(|rule| starts_with(rule, "[]"))
+        })
+        .collect();
+    let mut folded = Vec::new();
+    let mut dropped = vec![false; groups.len()];
+    for index in 0..groups.len() {
+        if !eligible[index] {
+            continue;
+        }
+        let group = &groups[index];
+        let members = group_generate_members(group, nodelist, true, "", ext);
+        if members.len() >= min_size {
+            continue;
+        }
+        let reference = format!("[]{}", group.name);
+        let parents: Vec<usize> = (0..groups.len())
+            .filter(|&i| groups[i].proxies.contains(&reference))
+            .collect();
+        let targets = if parents.is_empty() {
+            main.into_iter().collect()
+        } else {
+            parents
+        };
+        let Some(&first) = targets.first() else {
+            continue;
+        };
+        let rules: Vec<String> = members
+            .iter()
+            .map(|remark| format!("^{}$", regex::escape(remark)))
+            .collect();
+        for &target in &targets {
+            let proxies = &mut groups[target].proxies;
+            let folded_rules: Vec<String> = rules
+                .iter()
+                .filter(|rule| !proxies.contains(rule))
+                .cloned()
+                .collect();
+            let at = proxies.iter().position(|rule| *rule == reference);
+            proxies.retain(|rule| *rule != reference);
+            let at = at.unwrap_or(proxies.len());
+            proxies.splice(at..at, folded_rules);
+        }
+        log::info!(
+            "Folded proxy group '{}' of {} node(s) into '{}'",
+            groups[index].name,
+            members.len(),
+            groups[first].name
+        );
+        folded.push((groups[index].name.clone(), groups[first].name.clone()));
+        dropped[index] = true;
+    }
+    let mut dropped = dropped.into_iter();
+    groups.retain(|_| !dropped.next().unwrap_or_default());
+    folded
+}
+
+/// Adapts a group fallback to Quantumult, which spells built-in policies in
+/// lowercase
+pub fn quan_fallback(fallback: &str) -> String {
+    match fallback.to_lowercase().as_str() {
+        policy @ ("direct" | "reject") => policy.to_string(),
+        _ => fallback.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -195,4 +358,149 @@ mod tests {
         assert!(filtered.contains(&"JP Node 1".to_string()));
         assert!(filtered.contains(&"US Node 1".to_string()));
     }
+
+    #[test]
+    fn test_group_generate_members_fallback() {
+        let nodes = create_test_nodes();
+        let ext = ExtraSettings::default();
+        let mut group = ProxyGroupConfig::default();
+        group.proxies = vec!["[]AUTO".to_string(), "^JP".to_string()];
+
+        let members = group_generate_members(&group, &nodes, true, "DIRECT", &ext);
+        assert_eq!(members, vec!["AUTO", "JP Node 1"]);
+
+        group.proxies = vec![".*SG.*".to_string()];
+        let members = group_generate_members(&group, &nodes, true, "REJECT", &ext);
+        assert_eq!(members, vec!["REJECT"]);
+        assert!(group_generate_members(&group, &nodes, true, "", &ext).is_empty());
+    }
+
+    #[test]
+    fn test_group_generate_members_filter() {
+        let nodes = create_test_nodes();
+        let ext = ExtraSettings::default();
+        let hk = ProxyGroupConfig {
+            proxies: vec!["[]DIRECT".to_string(), "HK Node 2".to_string()],
+            filter: Some("^(HK|US)".to_string()),
+            ..Default::default()
+        };
+        let all = ProxyGroupConfig {
+            filter: Some("Node".to_string()),
+            ..Default::default()
+        };
+
+        // Nodes may be members of several groups, each listed once per group
+        assert_eq!(
+            group_generate_members(&hk, &nodes, true, "REJECT", &ext),
+            vec!["DIRECT", "HK Node 2", "HK Node 1", "US Node 1"]
+        );
+        assert_eq!(
+            group_generate_members(&all, &nodes, true, "REJECT", &ext).len(),
+            4
+        );
+
+        let sg = ProxyGroupConfig {
+            filter: Some("^SG".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            group_generate_members(&sg, &nodes, true, "DIRECT", &ext),
+            vec!["DIRECT"]
+        );
+    }
+
+    #[test]
+    fn test_sort_group_members() {
+        let nodes = create_test_nodes();
+        let group = ProxyGroupConfig {
+            proxies: vec![
+                "^US".to_string(),
+                "[]DIRECT".to_string(),
+                "^JP".to_string(),
+                "HK Node 2".to_string(),
+                "^HK Node 1".to_string(),
+            ],
+            ..Default::default()
+        };
+        let members = |ext: &ExtraSettings| group_generate_members(&group, &nodes, true, "", ext);
+
+        let unsorted = ExtraSettings::default();
+        assert_eq!(
+            members(&unsorted),
+            vec!["US Node 1", "DIRECT", "JP Node 1", "HK Node 2", "HK Node 1"]
+        );
+
+        // Sorting nodes sorts group members too, unless told otherwise
+        let mut sorted = ExtraSettings {
+            sort_flag: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            members(&sorted),
+            vec!["HK Node 1", "DIRECT", "HK Node 2", "JP Node 1", "US Node 1"]
+        );
+        sorted.sort_group_members = Some(false);
+        assert_eq!(members(&sorted), members(&unsorted));
+        let members_only = ExtraSettings {
+            sort_group_members: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(members(&members_only)[0], "HK Node 1");
+    }
+
+    #[test]
+    fn test_fold_small_groups() {
+        let nodes = create_test_nodes();
+        let ext = ExtraSettings::default();
+        let group = |name: &str, group_type: ProxyGroupType, proxies: &[&str]| ProxyGroupConfig {
+            name: name.to_string(),
+            group_type,
+            proxies: proxies.iter().map(|rule| rule.to_string()).collect(),
+            ..Default::default()
+        };
+        let groups = vec![
+            group("Proxy", ProxyGroupType::Select, &["[]Asia", "[]US", ".*"]),
+            group(
+                "Asia",
+                ProxyGroupType::Select,
+                &["[]HK", "[]JP", "[]DIRECT"],
+            ),
+            group("HK", ProxyGroupType::URLTest, &["^HK"]),
+            group("JP", ProxyGroupType::URLTest, &["^JP"]),
+            group("US", ProxyGroupType::URLTest, &["^US"]),
+            group("Lone", ProxyGroupType::URLTest, &["^JP"]),
+        ];
+
+        let mut kept = groups.clone();
+        assert!(fold_small_groups(&mut kept, &nodes, 0, &ext).is_empty());
+        assert!(fold_small_groups(&mut kept, &nodes, 1, &ext).is_empty());
+        assert_eq!(kept.len(), groups.len());
+        assert_eq!(kept[0].proxies, groups[0].proxies);
+
+        // HK holds exactly 2 nodes and stays, the single node groups fold
+        let mut folded = groups.clone();
+        assert_eq!(
+            fold_small_groups(&mut folded, &nodes, 2, &ext),
+            vec![
+                ("JP".to_string(), "Asia".to_string()),
+                ("US".to_string(), "Proxy".to_string()),
+                ("Lone".to_string(), "Proxy".to_string()),
+            ]
+        );
+        let names: Vec<&str> = folded.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, vec!["Proxy", "Asia", "HK"]);
+        assert_eq!(
+            folded[0].proxies,
+            vec!["[]Asia", "^US Node 1$", ".*", "^JP Node 1$"]
+        );
+        assert_eq!(folded[1].proxies, vec!["[]HK", "^JP Node 1$", "[]DIRECT"]);
+
+        let mut folded = groups;
+        fold_small_groups(&mut folded, &nodes, 3, &ext);
+        assert_eq!(folded.len(), 2);
+        assert_eq!(
+            folded[1].proxies,
+            vec!["^HK Node 1$", "^HK Node 2$", "^JP Node 1$", "[]DIRECT"]
+        );
+    }
 }
@@ -2,6 +2,7 @@
 //!
 //! This module contains utility functions for configuration parsing and generation.
 
+pub mod capabilities;
 pub mod formats;
 pub mod group;
 pub mod peer;
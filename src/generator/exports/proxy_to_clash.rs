@@ -1,13 +1,28 @@
-use crate::generator::config::group::group_generate;
+use crate::generator::config::capabilities::{supported_clash_tfo, supported_tfo};
+use crate::generator::config::group::group_generate_members;
 use crate::generator::config::remark::process_remark;
-use crate::generator::ruleconvert::ruleset_to_clash_str;
+use crate::generator::ruleconvert::block_rules::blocks_quic;
+use crate::generator::ruleconvert::{ruleset_to_clash_rules, ClashRuleProviders};
 use crate::generator::yaml::clash::clash_output::ClashProxyOutput;
 use crate::generator::yaml::proxy_group_output::convert_proxy_groups;
-use crate::models::{ExtraSettings, Proxy, ProxyGroupConfigs, ProxyType, RulesetContent};
-use log::error;
+use crate::models::clash_core::{has_shadow_tls, is_meta_only, vanilla_skip_reason};
+use crate::models::proxy_codec::emit_custom_yaml;
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::multiplex::takes_default_multiplex;
+use crate::models::{
+    auth_skip_reason, needs_reality, node_ss2022_cipher, trojan_go_skip_reason, AuthRule,
+    ClashCore, ExtraSettings, Proxy, ProxyGroupConfigs, ProxyType, RulesetContent,
+    SubconverterTarget, TrojanGoSupport,
+};
+use log::{error, info, warn};
 use serde_yaml::{self, Mapping, Sequence, Value as YamlValue};
 use std::collections::{HashMap, HashSet};
 
+/// Name of the provider holding the nodes when `clash_use_providers` is set
+pub const CLASH_PROVIDER_NAME: &str = "subscription";
+/// Refresh interval of the provider in seconds
+const CLASH_PROVIDER_INTERVAL: u32 = 86400;
+
 // Lists of supported protocols and encryption methods for filtering in ClashR
 lazy_static::lazy_static! {
     static ref CLASH_SSR_CIPHERS: HashSet<&'static str> = {
@@ -94,6 +109,7 @@ pub fn proxy_to_clash(
     }
 
     // Apply conversion to the YAML node
+    let vanilla = !clash_r && vanilla_core(nodes, ext);
     proxy_to_clash_yaml(
         nodes,
         &mut yaml_node,
@@ -111,6 +127,8 @@ pub fn proxy_to_clash(
         };
     }
 
+    insert_clash_dns(&mut yaml_node, &ext.clash_dns, ext.clash_new_field_name);
+
     // Handle rule generation if enabled
     if !ext.enable_rule_generator {
         return match serde_yaml::to_string(&yaml_node) {
@@ -147,20 +165,36 @@ pub fn proxy_to_clash(
         }
 
         // TODO: Implement renderClashScript
-        // For now, just return the YAML
-        return match serde_yaml::to_string(&yaml_node) {
-            Ok(result) => result,
-            Err(_) => String::new(),
-        };
+        // For now, just return the YAML. Managed configs only get rules
+        // when they reference rulesets as rule-providers
+        if ext.clash_script || !ext.clash_classical_ruleset {
+            return match serde_yaml::to_string(&yaml_node) {
+                Ok(result) => result,
+                Err(_) => String::new(),
+            };
+        }
     }
 
     // Generate rules and return combined output
-    let rules_str = ruleset_to_clash_str(
+    let mut providers = ext
+        .clash_classical_ruleset
+        .then(|| ClashRuleProviders::new(&ext.managed_config_prefix).with_base(&yaml_node));
+    let rules_str = ruleset_to_clash_rules(
         &yaml_node,
         ruleset_content_array,
         ext.overwrite_original_rules,
         ext.clash_new_field_name,
+        providers.as_mut(),
+        (!clash_r).then_some(if vanilla {
+            ClashCore::Vanilla
+        } else {
+            ClashCore::Meta
+        }),
+        blocks_quic(&ext.block_rules),
     );
+    if let Some(providers) = providers {
+        providers.apply(&mut yaml_node);
+    }
 
     let yaml_output = match serde_yaml::to_string(&yaml_node) {
         Ok(result) => result,
@@ -170,6 +204,13 @@ pub fn proxy_to_clash(
     format!("{}{}", yaml_output, rules_str)
 }
 
+/// Whether a `clash` export of `nodes` is written for the vanilla core,
+/// `false` when nodes only Clash.Meta supports upgrade it
+fn vanilla_core(nodes: &[Proxy], ext: &ExtraSettings) -> bool {
+    ext.clash_core == ClashCore::Vanilla
+        && !(ext.upgrade_clash_core && nodes.iter().any(is_meta_only))
+}
+
 /// Convert proxies to Clash format with YAML node
 ///
 /// This function modifies a YAML node in place to add Clash configuration
@@ -200,6 +241,22 @@ pub fn proxy_to_clash_yaml(
     // Create JSON structure for the proxies
     let mut proxies_json = Vec::new();
     let mut remarks_list = Vec::new();
+    let mut emitted = Vec::new();
+    let mut emitted_names: HashMap<String, String> = HashMap::new();
+    let target = if clash_r {
+        SubconverterTarget::ClashR
+    } else {
+        SubconverterTarget::Clash
+    };
+    // Nodes only Clash.Meta supports decide whether a vanilla export upgrades
+    let vanilla = !clash_r && vanilla_core(nodes, ext);
+    if !clash_r && !vanilla && ext.clash_core == ClashCore::Vanilla {
+        info!(
+            "Emitting Clash.Meta instead of vanilla Clash for {} node(s) only it supports",
+            nodes.iter().filter(|node| is_meta_only(node)).count()
+        );
+    }
+    let mut meta_only_skipped = Vec::new();
 
     // Process each node
     for node in nodes.iter_mut() {
@@ -215,17 +272,78 @@ pub fn proxy_to_clash_yaml(
         process_remark(&mut remark, &remarks_list, false);
         remarks_list.push(remark.clone());
         // Check if this proxy type should be skipped
+        let core_name = if clash_r { "ClashR" } else { "Clash" };
         let should_skip = match node.proxy_type {
+            // The vanilla core refuses configs with protocols it lacks
+            _ if vanilla && is_meta_only(node) => {
+                let reason = vanilla_skip_reason(node).unwrap_or_default();
+                ext.skip_node("Clash", &node.remark, reason);
+                meta_only_skipped.push(node.remark.clone());
+                true
+            }
+
             // Skip Snell v4+ if exists - exactly matching C++ behavior
-            ProxyType::Snell if node.snell_version >= 4 => true,
+            ProxyType::Snell if node.snell_version >= 4 => {
+                ext.skip_node(
+                    core_name,
+                    &node.remark,
+                    format!("Snell v{} is not supported", node.snell_version),
+                );
+                true
+            }
 
             // Skip if not using ClashR or if using deprecated features with ShadowsocksR
-            ProxyType::ShadowsocksR if !clash_r && ext.filter_deprecated => true,
+            ProxyType::ShadowsocksR if !clash_r && ext.filter_deprecated => {
+                ext.skip_node(
+                    core_name,
+                    &node.remark,
+                    "ShadowsocksR is deprecated in Clash",
+                );
+                true
+            }
+
+            // ClashR cores have no ShadowTLS, which the server requires
+            ProxyType::Shadowsocks if clash_r && has_shadow_tls(node) => {
+                ext.skip_node("ClashR", &node.remark, "ShadowTLS is not supported");
+                true
+            }
+
+            // ClashR cores predate Shadowsocks 2022
+            ProxyType::Shadowsocks if clash_r && node_ss2022_cipher(node).is_some() => {
+                ext.skip_node(
+                    "ClashR",
+                    &node.remark,
+                    format!(
+                        "cipher {} is not supported",
+                        node.encrypt_method.as_deref().unwrap_or("")
+                    ),
+                );
+                true
+            }
+
+            // ClashR cores have no Trojan-Go Shadowsocks layer
+            ProxyType::Trojan if clash_r => {
+                match trojan_go_skip_reason(node, TrojanGoSupport::WEBSOCKET) {
+                    Some(reason) => {
+                        ext.skip_node("ClashR", &node.remark, reason);
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            // ClashR cores have no Reality either, and a Reality server has
+            // no certificate a plain TLS connection could verify
+            ProxyType::Vless if clash_r && needs_reality(node) => {
+                ext.skip_node("ClashR", &node.remark, "VLESS Reality is not supported");
+                true
+            }
 
             // Skip chacha20 encryption if filter_deprecated is enabled
             ProxyType::Shadowsocks
                 if ext.filter_deprecated && node.encrypt_method.as_deref() == Some("chacha20") =>
             {
+                ext.skip_node(core_name, &node.remark, "cipher chacha20 is deprecated");
                 true
             }
 
@@ -235,13 +353,43 @@ pub fn proxy_to_clash_yaml(
                 let protocol = node.protocol.as_deref().unwrap_or("");
                 let obfs = node.obfs.as_deref().unwrap_or("");
 
-                !CLASH_SSR_CIPHERS.contains(encrypt_method)
-                    || !CLASHR_PROTOCOLS.contains(protocol)
-                    || !CLASHR_OBFS.contains(obfs)
+                let reason = if !CLASH_SSR_CIPHERS.contains(encrypt_method) {
+                    Some(format!("cipher {} is not supported", encrypt_method))
+                } else if !CLASHR_PROTOCOLS.contains(protocol) {
+                    Some(format!("protocol {} is not supported", protocol))
+                } else if !CLASHR_OBFS.contains(obfs) {
+                    Some(format!("obfs {} is not supported", obfs))
+                } else {
+                    None
+                };
+                match reason {
+                    Some(reason) => {
+                        ext.skip_node(core_name, &node.remark, reason);
+                        true
+                    }
+                    None => false,
+                }
             }
 
-            // Skip unsupported proxy types
-            ProxyType::Unknown | ProxyType::HTTPS => true,
+            // Nodes of registered codecs are emitted by the codec
+            ProxyType::Unknown => node.custom_proxy().is_none(),
+
+            // Clash only sends HTTP basic auth with both parts set, SOCKS5
+            // auth with a username
+            ProxyType::HTTP | ProxyType::HTTPS | ProxyType::Socks5 => {
+                let rule = if node.proxy_type == ProxyType::Socks5 {
+                    AuthRule::UsernameRequired
+                } else {
+                    AuthRule::BothRequired
+                };
+                match auth_skip_reason(node, rule) {
+                    Some(reason) => {
+                        ext.skip_node(core_name, &node.remark, reason);
+                        true
+                    }
+                    None => false,
+                }
+            }
 
             // Process all other types
             _ => false,
@@ -252,17 +400,83 @@ pub fn proxy_to_clash_yaml(
         }
 
         // 创建代理副本，并应用所有必要的属性设置
-        let proxy_copy = node.clone().set_remark(remark).apply_default_values(
+        emitted_names
+            .entry(node.remark.clone())
+            .or_insert_with(|| remark.clone());
+        let mut proxy_copy = node.clone().set_remark(remark).apply_default_values(
             ext.udp,
             ext.tfo,
-            ext.skip_cert_verify,
+            ext.default_skip_cert_verify(node),
         );
+        proxy_copy.tcp_fast_open = if clash_r {
+            supported_tfo(&target, &proxy_copy, proxy_copy.tcp_fast_open)
+        } else {
+            let core = if vanilla {
+                ClashCore::Vanilla
+            } else {
+                ClashCore::Meta
+            };
+            supported_clash_tfo(core, &proxy_copy, proxy_copy.tcp_fast_open)
+        };
+        // `ip-version`, `smux`, `dialer-proxy`, `client-fingerprint` and
+        // Trojan `ss-opts` are Clash.Meta options the ClashR and vanilla
+        // cores do not know
+        if clash_r || vanilla {
+            proxy_copy.ip_version = None;
+            proxy_copy.multiplex = None;
+            proxy_copy.underlying_proxy = None;
+            proxy_copy.client_fingerprint = None;
+            match &mut proxy_copy.combined_proxy {
+                Some(CombinedProxy::Vless(vless)) => vless.client_fingerprint = None,
+                Some(CombinedProxy::TrojanGo(trojan_go)) => trojan_go.shadowsocks = None,
+                _ => {}
+            }
+        } else {
+            proxy_copy.ip_version = proxy_copy.ip_version.or(ext.ip_version);
+            let udp_over_tcp = match &proxy_copy.combined_proxy {
+                Some(CombinedProxy::Shadowsocks(ss)) => ss.udp_over_tcp.unwrap_or(false),
+                _ => false,
+            };
+            if takes_default_multiplex(&proxy_copy, udp_over_tcp) {
+                proxy_copy.multiplex = proxy_copy.multiplex.or(ext.multiplex);
+            }
+        }
+        emitted.push(proxy_copy);
+    }
+    if !meta_only_skipped.is_empty() {
+        warn!(
+            "{} node(s) were dropped because the target is vanilla Clash: {}. \
+             Use Clash.Meta (clash_core=meta) or upgrade_clash to keep them",
+            meta_only_skipped.len(),
+            meta_only_skipped.join(", ")
+        );
+    }
 
-        // 使用 From trait 自动转换为 ClashProxyOutput
-        let clash_proxy = ClashProxyOutput::from(proxy_copy);
+    for mut proxy in emitted {
+        if proxy.custom_proxy().is_some() {
+            if let Some(mut value) = emit_custom_yaml(&proxy, &target, ext) {
+                value["name"] = YamlValue::String(proxy.remark.clone());
+                proxies_json.push(value);
+            }
+            continue;
+        }
 
-        // 添加到代理列表
-        proxies_json.push(clash_proxy);
+        // Upstreams are named by their emitted names
+        if let Some(name) = proxy
+            .underlying_proxy
+            .as_ref()
+            .and_then(|upstream| emitted_names.get(upstream))
+        {
+            proxy.underlying_proxy = Some(name.clone());
+        }
+
+        // 使用 From trait 自动转换为 ClashProxyOutput
+        let remark = proxy.remark.clone();
+        match serde_yaml::to_value(ClashProxyOutput::from(proxy)) {
+            Ok(value) => proxies_json.push(value),
+            // A `null` entry would make the whole config invalid
+            Err(e) => ext.skip_node("Clash", &remark, format!("failed to serialize: {}", e)),
+        }
     }
 
     if ext.nodelist {
@@ -273,8 +487,29 @@ pub fn proxy_to_clash_yaml(
         return;
     }
 
-    // Update the YAML node with proxies
-    if let Some(ref mut map) = yaml_node.as_mapping_mut() {
+    let use_providers = ext.clash_use_providers && !ext.clash_provider_url.is_empty();
+    if ext.clash_use_providers && !use_providers {
+        warn!("Clash providers need a provider URL, falling back to inline proxies");
+    }
+
+    if use_providers {
+        if let Some(ref mut map) = yaml_node.as_mapping_mut() {
+            // Clash never had an old style name for providers
+            let key = "proxy-providers";
+            let mut providers = match map.remove(key) {
+                Some(YamlValue::Mapping(providers)) => providers,
+                _ => Mapping::new(),
+            };
+            providers.insert(
+                YamlValue::String(CLASH_PROVIDER_NAME.to_string()),
+                clash_provider(&ext.clash_provider_url, ext, vanilla),
+            );
+            map.insert(
+                YamlValue::String(key.to_string()),
+                YamlValue::Mapping(providers),
+            );
+        }
+    } else if let Some(ref mut map) = yaml_node.as_mapping_mut() {
         // Convert JSON proxies array to YAML
         let proxies_yaml_value =
             serde_yaml::to_value(&proxies_json).unwrap_or(YamlValue::Sequence(Vec::new()));
@@ -303,21 +538,48 @@ pub fn proxy_to_clash_yaml(
         // Build filtered nodes map for each group
         let mut filtered_nodes_map = HashMap::new();
         for group in extra_proxy_group {
-            let mut filtered_nodes = Vec::new();
-            for proxy_name in &group.proxies {
-                group_generate(proxy_name, nodes, &mut filtered_nodes, true, ext);
-            }
-
-            // Add DIRECT if empty
-            if filtered_nodes.is_empty() && group.using_provider.is_empty() {
-                filtered_nodes.push("DIRECT".to_string());
-            }
+            // Fall back to a placeholder if empty, unless a provider fills it
+            let fallback = if group.using_provider.is_empty() {
+                ext.group_fallback.as_str()
+            } else {
+                ""
+            };
+            let filtered_nodes = group_generate_members(group, nodes, true, fallback, ext);
 
             filtered_nodes_map.insert(group.name.clone(), filtered_nodes);
         }
 
         // Convert proxy groups using the new serialization
-        let clash_proxy_groups = convert_proxy_groups(extra_proxy_group, Some(&filtered_nodes_map));
+        let mut clash_proxy_groups =
+            convert_proxy_groups(extra_proxy_group, Some(&filtered_nodes_map));
+        if !ext.clash_new_field_name || vanilla {
+            for group in &mut clash_proxy_groups {
+                group.strip_meta_fields();
+            }
+        } else if clash_r {
+            for group in &mut clash_proxy_groups {
+                group.strip_gui_fields();
+            }
+        }
+
+        // Swap node members for a reference to the provider
+        if use_providers {
+            let node_names: HashSet<&str> = nodes.iter().map(|node| node.remark.as_str()).collect();
+            for group in &mut clash_proxy_groups {
+                let before = group.proxies.len();
+                group
+                    .proxies
+                    .retain(|name| !node_names.contains(name.as_str()));
+                if group.proxies.len() != before
+                    && !group
+                        .using_provider
+                        .iter()
+                        .any(|p| p == CLASH_PROVIDER_NAME)
+                {
+                    group.using_provider.push(CLASH_PROVIDER_NAME.to_string());
+                }
+            }
+        }
 
         // Merge with existing groups
         for group in clash_proxy_groups {
@@ -366,3 +628,1270 @@ pub fn proxy_to_clash_yaml(
         }
     }
 }
+
+/// Parse the `dns` section template, either the bare mapping or a document
+/// with a top-level `dns` key
+fn parse_clash_dns(template: &str) -> Option<Mapping> {
+    let value: YamlValue = match serde_yaml::from_str(template) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Ignoring invalid Clash dns section: {}", e);
+            return None;
+        }
+    };
+    let dns = match value {
+        YamlValue::Mapping(mut map) => match map.remove("dns") {
+            Some(YamlValue::Mapping(dns)) => dns,
+            Some(_) => {
+                warn!("Ignoring Clash dns section that is not a mapping");
+                return None;
+            }
+            None => map,
+        },
+        YamlValue::Null => return None,
+        _ => {
+            warn!("Ignoring Clash dns section that is not a mapping");
+            return None;
+        }
+    };
+    (!dns.is_empty()).then_some(dns)
+}
+
+/// Inject the `dns` section into a Clash config
+///
+/// A section already in the base config is replaced where it stands,
+/// otherwise it goes ahead of the proxy sections, which are looked up under
+/// the old or new field names following `new_field_name`. An empty template
+/// leaves the config untouched.
+fn insert_clash_dns(yaml_node: &mut YamlValue, template: &str, new_field_name: bool) {
+    if template.trim().is_empty() {
+        return;
+    }
+    let Some(dns) = parse_clash_dns(template) else {
+        return;
+    };
+    let Some(map) = yaml_node.as_mapping_mut() else {
+        return;
+    };
+
+    let key = YamlValue::String("dns".to_string());
+    if let Some(existing) = map.get_mut(&key) {
+        *existing = YamlValue::Mapping(dns);
+        return;
+    }
+
+    let proxy_sections: &[&str] = if new_field_name {
+        &["proxies", "proxy-providers", "proxy-groups"]
+    } else {
+        &["Proxy", "proxy-providers", "Proxy Group"]
+    };
+    let mut output = Mapping::new();
+    let mut dns = Some(dns);
+    for (k, v) in std::mem::take(map) {
+        if k.as_str()
+            .is_some_and(|name| proxy_sections.contains(&name))
+        {
+            if let Some(dns) = dns.take() {
+                output.insert(key.clone(), YamlValue::Mapping(dns));
+            }
+        }
+        output.insert(k, v);
+    }
+    if let Some(dns) = dns {
+        output.insert(key, YamlValue::Mapping(dns));
+    }
+    *map = output;
+}
+
+/// Build the `http` provider entry serving the node list
+///
+/// The health check keys are the same under both field name styles, only
+/// the section holding the provider is renamed. A disabled check is left
+/// out, `timeout` is only known to Clash.Meta.
+fn clash_provider(url: &str, ext: &ExtraSettings, vanilla: bool) -> YamlValue {
+    let check = &ext.provider_health_check;
+    let health_check = check.enable.then(|| {
+        let mut health_check = Mapping::new();
+        health_check.insert("enable".into(), YamlValue::Bool(true));
+        health_check.insert("url".into(), check.url.as_str().into());
+        health_check.insert("interval".into(), check.interval.into());
+        if let Some(timeout) = check.timeout.filter(|_| !vanilla) {
+            health_check.insert("timeout".into(), timeout.into());
+        }
+        if let Some(lazy) = check.lazy {
+            health_check.insert("lazy".into(), YamlValue::Bool(lazy));
+        }
+        health_check
+    });
+
+    let mut provider = Mapping::new();
+    provider.insert("type".into(), "http".into());
+    provider.insert("url".into(), url.into());
+    provider.insert("interval".into(), CLASH_PROVIDER_INTERVAL.into());
+    provider.insert(
+        "path".into(),
+        format!("./providers/{}.yaml", CLASH_PROVIDER_NAME).into(),
+    );
+    if let Some(health_check) = health_check {
+        provider.insert("health-check".into(), YamlValue::Mapping(health_check));
+    }
+    YamlValue::Mapping(provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::proxy_node::vless::VlessProxy;
+    use crate::models::{GroupHealthCheck, ProxyGroupConfig, ProxyGroupType, SkipCertVerifyPolicy};
+
+    fn node(remark: &str, udp: Option<bool>) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: remark.to_string(),
+            hostname: "example.com".to_string(),
+            port: 443,
+            password: Some("secret".to_string()),
+            udp,
+            ..Default::default()
+        }
+    }
+
+    fn emitted_udp(mut nodes: Vec<Proxy>, default_udp: Option<bool>) -> Vec<Option<bool>> {
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            udp: default_udp,
+            ..Default::default()
+        };
+        let mut yaml = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut nodes,
+            &mut yaml,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        yaml["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|proxy| proxy.get("udp").and_then(YamlValue::as_bool))
+            .collect()
+    }
+
+    #[test]
+    fn test_node_udp_overrides_default() {
+        let nodes = vec![node("off", Some(false)), node("unset", None)];
+        assert_eq!(
+            emitted_udp(nodes, Some(true)),
+            vec![Some(false), Some(true)]
+        );
+    }
+
+    #[test]
+    fn test_node_udp_without_default() {
+        let nodes = vec![node("on", Some(true)), node("unset", None)];
+        assert_eq!(emitted_udp(nodes, None), vec![Some(true), None]);
+    }
+
+    #[test]
+    fn test_vless_keeps_node_udp() {
+        let mut vless = node("vless", Some(false));
+        vless.proxy_type = ProxyType::Vless;
+        vless.combined_proxy = Some(CombinedProxy::Vless(VlessProxy {
+            uuid: "b831381d-6324-4d53-ad4f-8cda48b30811".to_string(),
+            udp: true,
+            ..Default::default()
+        }));
+        let mut unset = vless.clone();
+        unset.udp = None;
+        assert_eq!(
+            emitted_udp(vec![vless, unset], Some(false)),
+            vec![Some(false), Some(false)]
+        );
+    }
+
+    #[test]
+    fn test_client_fingerprint() {
+        let mut trojan = Proxy::default();
+        assert!(crate::parser::explodes::explode_trojan(
+            "trojan://secret@example.com:443?fp=safari#Trojan",
+            &mut trojan
+        ));
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut vec![trojan],
+            "",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        assert_eq!(output.matches("client-fingerprint: safari").count(), 1);
+    }
+
+    #[test]
+    fn test_ip_version() {
+        let mut pinned = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "trojan://secret@v6.example.com:443?ip-version=ipv6#Pinned",
+            &mut pinned
+        ));
+        let mut unset = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "trojan://secret@example.com:443#Unset",
+            &mut unset
+        ));
+        assert!(!crate::parser::explodes::explode(
+            "trojan://secret@example.com:443?ip-version=v4#Bad",
+            &mut Proxy::default()
+        ));
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ip_version: Some(crate::models::IpVersion::Ipv4Prefer),
+            ..Default::default()
+        };
+        let mut nodes = vec![pinned, unset];
+
+        let output = proxy_to_clash(
+            &mut nodes,
+            "",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert_eq!(output.matches("ip-version: ipv6\n").count(), 1);
+        assert_eq!(output.matches("ip-version: ipv4-prefer").count(), 1);
+
+        let output = proxy_to_clash(&mut nodes, "", &mut Vec::new(), &Vec::new(), true, &mut ext);
+        assert!(!output.contains("ip-version"));
+    }
+
+    #[test]
+    fn test_clash_smux() {
+        let mut nodes = Vec::new();
+        assert!(crate::parser::explodes::explode_clash(
+            r#"
+proxies:
+  - name: Muxed
+    type: trojan
+    server: a.example.com
+    port: 443
+    password: secret
+    smux:
+      enabled: true
+      protocol: smux
+      max-connections: 4
+      statistic: true
+      brutal-opts: { enabled: true, up: 50, down: 100 Mbps }
+  - { name: Off, type: trojan, server: b.example.com, port: 443, password: pw, smux: { enabled: false } }
+  - { name: Bad, type: trojan, server: c.example.com, port: 443, password: pw, smux: { enabled: true, protocol: mplex } }
+  - { name: Socks, type: socks5, server: d.example.com, port: 1080 }
+"#,
+            &mut nodes
+        ));
+        let remarks: Vec<&str> = nodes.iter().map(|node| node.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["Muxed", "Off", "Socks"]);
+        assert!(nodes[1].multiplex.is_none());
+
+        let emit = |nodes: &[Proxy], clash_r: bool, ext: &mut ExtraSettings| {
+            let mut output = YamlValue::Null;
+            proxy_to_clash_yaml(
+                &mut nodes.to_vec(),
+                &mut output,
+                &Vec::new(),
+                &Vec::new(),
+                clash_r,
+                ext,
+            );
+            output["proxies"].as_sequence().cloned().unwrap_or_default()
+        };
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            multiplex: Some("yamux,padding=true".parse().unwrap()),
+            ..Default::default()
+        };
+        let proxies = emit(&nodes, false, &mut ext);
+        assert_eq!(
+            proxies[0]["smux"],
+            serde_yaml::from_str::<YamlValue>(
+                "{ enabled: true, protocol: smux, max-connections: 4, \
+                 brutal-opts: { enabled: true, up: 50, down: 100 } }"
+            )
+            .unwrap()
+        );
+        assert_eq!(proxies[1]["smux"]["protocol"].as_str(), Some("yamux"));
+        assert_eq!(proxies[1]["smux"]["padding"].as_bool(), Some(true));
+        assert!(proxies[2].get("smux").is_none());
+
+        // Neither ClashR nor the vanilla core have sing-mux
+        assert!(emit(&nodes, true, &mut ext)
+            .iter()
+            .all(|proxy| proxy.get("smux").is_none()));
+        ext.clash_core = ClashCore::Vanilla;
+        assert!(emit(&nodes, false, &mut ext)
+            .iter()
+            .all(|proxy| proxy.get("smux").is_none()));
+    }
+
+    #[test]
+    fn test_anytls_uses_global_tls_defaults() {
+        let mut anytls = Proxy::default();
+        assert!(crate::parser::explodes::explode_anytls(
+            "anytls://secret@example.com:443?sni=real.example.com&fp=chrome#AnyTLS",
+            &mut anytls
+        ));
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            skip_cert_verify: Some(true),
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut vec![anytls],
+            "",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        assert!(output.contains("type: anytls"));
+        assert!(output.contains("client-fingerprint: chrome"));
+        assert_eq!(output.matches("skip-cert-verify: true").count(), 1);
+        assert_eq!(output.matches("sni: real.example.com").count(), 1);
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let mut ss = node("ss", None);
+        ss.proxy_type = ProxyType::Shadowsocks;
+        ss.encrypt_method = Some("aes-128-gcm".to_string());
+        ss.plugin = Some("obfs".to_string());
+        ss.plugin_option = Some("obfs=http;obfs-host=bing.com;mode=fast".to_string());
+
+        let render = || {
+            let mut ext = ExtraSettings {
+                nodelist: true,
+                deterministic: true,
+                ..Default::default()
+            };
+            proxy_to_clash(
+                &mut vec![ss.clone()],
+                "",
+                &mut Vec::new(),
+                &Vec::new(),
+                false,
+                &mut ext,
+            )
+        };
+        let output = render();
+        assert!(output
+            .contains("plugin-opts:\n    mode: fast\n    obfs: http\n    obfs-host: bing.com\n"));
+        assert_eq!(output, render());
+    }
+
+    #[test]
+    fn test_provider_replaces_inline_proxies() {
+        let mut nodes = vec![node("HK 01", None), node("JP 01", None)];
+        let groups = vec![
+            ProxyGroupConfig {
+                name: "Proxy".to_string(),
+                proxies: vec!["[]DIRECT".to_string(), ".*".to_string()],
+                ..Default::default()
+            },
+            ProxyGroupConfig {
+                name: "Final".to_string(),
+                proxies: vec!["[]Proxy".to_string()],
+                ..Default::default()
+            },
+        ];
+        let mut ext = ExtraSettings {
+            clash_use_providers: true,
+            clash_provider_url: "https://sub.example.com/list".to_string(),
+            ..Default::default()
+        };
+        let mut yaml = YamlValue::Mapping(Mapping::new());
+        proxy_to_clash_yaml(&mut nodes, &mut yaml, &Vec::new(), &groups, false, &mut ext);
+
+        assert!(yaml.get("proxies").is_none());
+        let provider = &yaml["proxy-providers"][CLASH_PROVIDER_NAME];
+        assert_eq!(
+            provider["url"].as_str(),
+            Some("https://sub.example.com/list")
+        );
+        assert_eq!(provider["health-check"]["enable"].as_bool(), Some(true));
+        assert_eq!(provider["health-check"]["interval"].as_u64(), Some(300));
+
+        let proxy = &yaml["proxy-groups"][0];
+        assert_eq!(proxy["use"][0].as_str(), Some(CLASH_PROVIDER_NAME));
+        assert_eq!(proxy["proxies"].as_sequence().unwrap().len(), 1);
+        let final_group = &yaml["proxy-groups"][1];
+        assert!(final_group.get("use").is_none());
+        assert_eq!(final_group["proxies"][0].as_str(), Some("Proxy"));
+    }
+
+    #[test]
+    fn test_provider_health_check() {
+        use crate::models::{ClashCore, ProviderHealthCheck};
+
+        let emit = |ext: &mut ExtraSettings| {
+            ext.clash_use_providers = true;
+            ext.clash_provider_url = "https://sub.example.com/list".to_string();
+            let mut yaml = YamlValue::Mapping(Mapping::new());
+            proxy_to_clash_yaml(
+                &mut vec![node("HK 01", None)],
+                &mut yaml,
+                &Vec::new(),
+                &Vec::new(),
+                false,
+                ext,
+            );
+            yaml
+        };
+
+        let mut ext = ExtraSettings {
+            provider_health_check: ProviderHealthCheck {
+                url: "https://cp.cloudflare.com".to_string(),
+                interval: 600,
+                timeout: Some(2000),
+                lazy: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let yaml = emit(&mut ext);
+        let check = &yaml["proxy-providers"][CLASH_PROVIDER_NAME]["health-check"];
+        assert_eq!(check["url"].as_str(), Some("https://cp.cloudflare.com"));
+        assert_eq!(check["interval"].as_u64(), Some(600));
+        assert_eq!(check["timeout"].as_u64(), Some(2000));
+        assert_eq!(check["lazy"].as_bool(), Some(true));
+
+        // Old field names keep the section name, vanilla has no timeout
+        ext.clash_new_field_name = false;
+        ext.clash_core = ClashCore::Vanilla;
+        let yaml = emit(&mut ext);
+        assert!(yaml.get("Proxy Provider").is_none());
+        let check = &yaml["proxy-providers"][CLASH_PROVIDER_NAME]["health-check"];
+        assert_eq!(check["interval"].as_u64(), Some(600));
+        assert!(check.get("timeout").is_none());
+
+        // A disabled check is left out instead of written as disabled
+        ext.provider_health_check.enable = false;
+        let yaml = emit(&mut ext);
+        let provider = &yaml["proxy-providers"][CLASH_PROVIDER_NAME];
+        assert_eq!(provider["type"].as_str(), Some("http"));
+        assert!(provider.get("health-check").is_none());
+    }
+
+    #[test]
+    fn test_alpn_round_trip() {
+        let yaml = r#"
+proxies:
+  - {name: a, type: trojan, server: a.com, port: 443, password: p, alpn: [h2, http/1.1]}
+  - {name: b, type: hysteria2, server: b.com, port: 443, password: p, alpn: "h3, h3"}
+  - {name: c, type: trojan, server: c.com, port: 443, password: p}
+"#;
+        let mut nodes = crate::parser::yaml::clash::parse_clash_yaml(yaml).unwrap();
+        assert_eq!(nodes[0].alpn, vec!["h2", "http/1.1"]);
+
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut nodes,
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        let proxies = output["proxies"].as_sequence().unwrap();
+        let alpn = |i: usize| -> Option<Vec<&str>> {
+            proxies[i].get("alpn").map(|alpn| {
+                alpn.as_sequence()
+                    .unwrap()
+                    .iter()
+                    .map(|v| v.as_str().unwrap())
+                    .collect()
+            })
+        };
+        assert_eq!(alpn(0), Some(vec!["h2", "http/1.1"]));
+        assert_eq!(alpn(1), Some(vec!["h3"]));
+        assert_eq!(alpn(2), None);
+    }
+
+    #[test]
+    fn test_dialer_proxy_follows_emitted_names() {
+        let yaml = r#"
+proxies:
+  - {name: Exit, type: trojan, server: a.com, port: 443, password: p, dialer-proxy: Relay}
+  - {name: Relay, type: trojan, server: b.com, port: 443, password: p}
+  - {name: Grouped, type: trojan, server: c.com, port: 443, password: p, dialer-proxy: Relays}
+"#;
+        let mut nodes = crate::parser::yaml::clash::parse_clash_yaml(yaml).unwrap();
+        assert_eq!(nodes[0].underlying_proxy.as_deref(), Some("Relay"));
+
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            append_proxy_type: true,
+            ..Default::default()
+        };
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut nodes,
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        let proxies = output["proxies"].as_sequence().unwrap();
+        let dialer = |i: usize| proxies[i].get("dialer-proxy").and_then(|v| v.as_str());
+        assert_eq!(dialer(0), Some("[Trojan] Relay"));
+        assert_eq!(dialer(1), None);
+        // Names matching no node are kept, they may be proxy groups
+        assert_eq!(dialer(2), Some("Relays"));
+    }
+
+    #[test]
+    fn test_vless_reality_opts() {
+        use crate::parser::explodes::explode_vless;
+
+        let mut node = Proxy::default();
+        assert!(explode_vless(
+            "vless://uuid@a.com:443?security=reality&pbk=PUBKEY&sid=6ba8&fp=safari\
+             &flow=xtls-rprx-vision&sni=www.apple.com&type=tcp#reality",
+            &mut node
+        ));
+
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let proxy = &output["proxies"][0];
+        assert_eq!(proxy["tls"], YamlValue::Bool(true));
+        assert_eq!(proxy["flow"].as_str(), Some("xtls-rprx-vision"));
+        assert_eq!(proxy["servername"].as_str(), Some("www.apple.com"));
+        assert_eq!(proxy["client-fingerprint"].as_str(), Some("safari"));
+        assert_eq!(proxy["reality-opts"]["public-key"].as_str(), Some("PUBKEY"));
+        assert_eq!(proxy["reality-opts"]["short-id"].as_str(), Some("6ba8"));
+
+        let mut missing_key = Proxy::default();
+        assert!(!explode_vless(
+            "vless://uuid@a.com:443?security=reality&sid=6ba8#broken",
+            &mut missing_key
+        ));
+    }
+
+    #[test]
+    fn test_clashr_skips_reality() {
+        use crate::models::DegradeMap;
+        use crate::parser::explodes::explode_vless;
+
+        let mut node = Proxy::default();
+        assert!(explode_vless(
+            "vless://uuid@a.com:443?security=reality&pbk=PUBKEY&sid=6ba8&fp=chrome\
+             &sni=www.example.com&type=tcp#reality",
+            &mut node
+        ));
+        let emit = |ext: &mut ExtraSettings| {
+            let mut output = YamlValue::Null;
+            proxy_to_clash_yaml(
+                &mut vec![node.clone()],
+                &mut output,
+                &Vec::new(),
+                &Vec::new(),
+                true,
+                ext,
+            );
+            output["proxies"].as_sequence().cloned().unwrap_or_default()
+        };
+
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+        assert!(emit(&mut ext).is_empty());
+
+        // No fallback turns it into a node that can not connect
+        ext.degrade = DegradeMap::all();
+        ext.on_unsupported = crate::models::UnsupportedPolicy::Error;
+        assert!(emit(&mut ext).is_empty());
+        let error = ext.unsupported_error().unwrap();
+        assert!(error.contains("'reality' for ClashR") && error.contains("Reality"));
+    }
+
+    #[test]
+    fn test_vanilla_clash_core() {
+        let nodes = vec![
+            Proxy {
+                proxy_type: ProxyType::Vless,
+                remark: "VLESS".to_string(),
+                hostname: "a.com".to_string(),
+                port: 443,
+                ..Default::default()
+            },
+            Proxy {
+                proxy_type: ProxyType::Trojan,
+                remark: "Trojan".to_string(),
+                hostname: "b.com".to_string(),
+                port: 443,
+                password: Some("secret".to_string()),
+                ip_version: Some(crate::models::IpVersion::Ipv4),
+                client_fingerprint: Some("chrome".to_string()),
+                underlying_proxy: Some("Relay".to_string()),
+                ..Default::default()
+            },
+        ];
+        let emit = |ext: &mut ExtraSettings| {
+            let mut output = YamlValue::Null;
+            proxy_to_clash_yaml(
+                &mut nodes.clone(),
+                &mut output,
+                &Vec::new(),
+                &Vec::new(),
+                false,
+                ext,
+            );
+            output["proxies"].as_sequence().cloned().unwrap_or_default()
+        };
+
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            clash_core: ClashCore::Vanilla,
+            on_unsupported: crate::models::UnsupportedPolicy::Error,
+            ..Default::default()
+        };
+        let proxies = emit(&mut ext);
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0]["name"].as_str(), Some("Trojan"));
+        for key in ["ip-version", "client-fingerprint", "dialer-proxy"] {
+            assert!(proxies[0].get(key).is_none(), "{}", key);
+        }
+        let error = ext.unsupported_error().unwrap();
+        assert!(error.contains("'VLESS' for Clash") && error.contains("clash_core=meta"));
+
+        // Upgrading emits everything for Clash.Meta
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            clash_core: ClashCore::Vanilla,
+            upgrade_clash_core: true,
+            ..Default::default()
+        };
+        let proxies = emit(&mut ext);
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[1]["ip-version"].as_str(), Some("ipv4"));
+        assert_eq!(proxies[1]["client-fingerprint"].as_str(), Some("chrome"));
+        assert_eq!(proxies[1]["dialer-proxy"].as_str(), Some("Relay"));
+    }
+
+    #[test]
+    fn test_client_fingerprint_is_meta_only() {
+        let nodes = vec![Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: "Trojan".to_string(),
+            hostname: "b.com".to_string(),
+            port: 443,
+            password: Some("secret".to_string()),
+            client_fingerprint: Some("firefox".to_string()),
+            ..Default::default()
+        }];
+        let fingerprint = |clash_r: bool, clash_core: ClashCore| {
+            let mut ext = ExtraSettings {
+                nodelist: true,
+                clash_core,
+                ..Default::default()
+            };
+            let mut output = YamlValue::Null;
+            proxy_to_clash_yaml(
+                &mut nodes.clone(),
+                &mut output,
+                &Vec::new(),
+                &Vec::new(),
+                clash_r,
+                &mut ext,
+            );
+            output["proxies"][0]["client-fingerprint"]
+                .as_str()
+                .map(str::to_string)
+        };
+
+        assert_eq!(
+            fingerprint(false, ClashCore::Meta).as_deref(),
+            Some("firefox")
+        );
+        assert_eq!(fingerprint(true, ClashCore::Meta), None);
+        assert_eq!(fingerprint(false, ClashCore::Vanilla), None);
+    }
+
+    #[test]
+    fn test_shadow_tls_needs_meta() {
+        let nodes = vec![Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: "SS".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            encrypt_method: Some("aes-128-gcm".to_string()),
+            password: Some("key".to_string()),
+            plugin: Some("shadow-tls".to_string()),
+            plugin_option: Some("host=cloud.tencent.com;password=stls".to_string()),
+            ..Default::default()
+        }];
+        let emitted = |clash_r: bool, clash_core: ClashCore| {
+            let mut ext = ExtraSettings {
+                nodelist: true,
+                clash_core,
+                ..Default::default()
+            };
+            let mut output = YamlValue::Null;
+            proxy_to_clash_yaml(
+                &mut nodes.clone(),
+                &mut output,
+                &Vec::new(),
+                &Vec::new(),
+                clash_r,
+                &mut ext,
+            );
+            output["proxies"].as_sequence().map_or(0, Vec::len)
+        };
+
+        assert_eq!(emitted(false, ClashCore::Meta), 1);
+        assert_eq!(emitted(true, ClashCore::Meta), 0);
+        assert_eq!(emitted(false, ClashCore::Vanilla), 0);
+    }
+
+    #[test]
+    fn test_unsupported_policy_covers_snell_v4() {
+        let mut nodes = vec![Proxy {
+            proxy_type: ProxyType::Snell,
+            remark: "Snell".to_string(),
+            hostname: "a.com".to_string(),
+            port: 443,
+            password: Some("psk".to_string()),
+            snell_version: 4,
+            ..Default::default()
+        }];
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            on_unsupported: crate::models::UnsupportedPolicy::Error,
+            ..Default::default()
+        };
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut nodes,
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert!(output["proxies"].as_sequence().is_none_or(|p| p.is_empty()));
+        let error = ext.unsupported_error().unwrap();
+        assert!(error.contains("'Snell' for Clash") && error.contains("Snell v4"));
+    }
+
+    #[test]
+    fn test_rulesets_as_rule_providers() {
+        let mut remote = RulesetContent::new("https://example.com/rules/Ads.list", "REJECT");
+        remote.set_rule_content("DOMAIN-SUFFIX,ads.example.com\n");
+        let mut local = RulesetContent::new("rules/Local.list", "DIRECT");
+        local.set_rule_content("DOMAIN-SUFFIX,lan.example.com\n");
+        let mut fallback = RulesetContent::new("[]FINAL", "Proxy");
+        fallback.set_rule_content("[]FINAL");
+
+        let mut ext = ExtraSettings {
+            enable_rule_generator: true,
+            clash_new_field_name: true,
+            clash_classical_ruleset: true,
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "",
+            &mut vec![remote, local, fallback],
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        let rules: Vec<&str> = yaml["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|rule| rule.as_str())
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                "RULE-SET,Ads,REJECT",
+                "DOMAIN-SUFFIX,lan.example.com,DIRECT",
+                "MATCH,Proxy"
+            ]
+        );
+        let provider = &yaml["rule-providers"]["Ads"];
+        assert_eq!(provider["behavior"].as_str(), Some("classical"));
+        assert_eq!(provider["format"].as_str(), Some("text"));
+    }
+
+    #[test]
+    fn test_managed_config_rules() {
+        let ruleset = || {
+            let mut remote = RulesetContent::new("https://example.com/rules/Ads.list", "REJECT");
+            remote.set_rule_content("DOMAIN-SUFFIX,ads.example.com\n");
+            vec![remote]
+        };
+        let mut ext = ExtraSettings {
+            enable_rule_generator: true,
+            clash_new_field_name: true,
+            managed_config_prefix: "https://sub.example.com".to_string(),
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "",
+            &mut ruleset(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert!(yaml.get("rules").is_none());
+
+        ext.clash_classical_ruleset = true;
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "",
+            &mut ruleset(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["rules"][0].as_str(), Some("RULE-SET,Ads,REJECT"));
+    }
+
+    #[test]
+    fn test_block_rules() {
+        use crate::generator::ruleconvert::block_rules::block_ruleset;
+
+        let block_rules = vec![
+            "QUIC".to_string(),
+            "ads".to_string(),
+            "DOMAIN-SUFFIX,doubleclick.net".to_string(),
+        ];
+        let rules = |clash_r: bool, clash_core: ClashCore| {
+            let mut fallback = RulesetContent::new("[]FINAL", "Proxy");
+            fallback.set_rule_content("[]FINAL");
+            let mut ext = ExtraSettings {
+                enable_rule_generator: true,
+                clash_new_field_name: true,
+                clash_classical_ruleset: true,
+                block_rules: block_rules.clone(),
+                clash_core,
+                ..Default::default()
+            };
+            let output = proxy_to_clash(
+                &mut Vec::new(),
+                "",
+                &mut vec![block_ruleset(&block_rules).unwrap(), fallback],
+                &Vec::new(),
+                clash_r,
+                &mut ext,
+            );
+            let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+            assert!(yaml.get("rule-providers").is_none());
+            yaml["rules"]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .filter_map(|rule| rule.as_str().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rules(false, ClashCore::Meta),
+            vec![
+                "AND,((NETWORK,UDP),(DST-PORT,443)),REJECT",
+                "DOMAIN-KEYWORD,ads,REJECT",
+                "DOMAIN-SUFFIX,doubleclick.net,REJECT",
+                "MATCH,Proxy"
+            ]
+        );
+        // Neither the ClashR nor the vanilla core has logical rules
+        assert_eq!(rules(true, ClashCore::Meta)[0], "DOMAIN-KEYWORD,ads,REJECT");
+        assert_eq!(
+            rules(false, ClashCore::Vanilla)[0],
+            "DOMAIN-KEYWORD,ads,REJECT"
+        );
+    }
+
+    #[test]
+    fn test_group_health_check_defaults() {
+        let defaults = GroupHealthCheck {
+            tolerance: 50,
+            lazy: Some(false),
+            ..Default::default()
+        };
+        let mut auto = ProxyGroupConfig::new("Auto".to_string(), ProxyGroupType::URLTest);
+        auto.proxies = vec!["DIRECT".to_string()];
+        auto.apply_health_check(&defaults);
+        let mut fallback = ProxyGroupConfig::new("Fallback".to_string(), ProxyGroupType::Fallback);
+        fallback.proxies = vec!["DIRECT".to_string()];
+        fallback.url = "https://cp.cloudflare.com".to_string();
+        fallback.interval = 60;
+        fallback.lazy = Some(true);
+        fallback.apply_health_check(&defaults);
+        let mut select = ProxyGroupConfig::new("Proxy".to_string(), ProxyGroupType::Select);
+        select.apply_health_check(&defaults);
+        assert!(select.url.is_empty());
+        let groups = vec![auto, fallback, select];
+
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            clash_new_field_name: true,
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "",
+            &mut Vec::new(),
+            &groups,
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        let auto = &yaml["proxy-groups"][0];
+        assert_eq!(
+            auto["url"].as_str(),
+            Some("http://www.gstatic.com/generate_204")
+        );
+        assert_eq!(auto["interval"], 300);
+        assert_eq!(auto["tolerance"], 50);
+        assert_eq!(auto["lazy"], YamlValue::Bool(false));
+        let fallback = &yaml["proxy-groups"][1];
+        assert_eq!(fallback["url"].as_str(), Some("https://cp.cloudflare.com"));
+        assert_eq!(fallback["interval"], 60);
+        assert_eq!(fallback["lazy"], YamlValue::Bool(true));
+
+        // Legacy cores get no lazy
+        ext.clash_new_field_name = false;
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "",
+            &mut Vec::new(),
+            &groups,
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        let auto = &yaml["Proxy Group"][0];
+        assert_eq!(auto["interval"], 300);
+        assert!(auto.get("lazy").is_none());
+
+        // Neither does the vanilla core
+        ext.clash_new_field_name = true;
+        ext.clash_core = ClashCore::Vanilla;
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "",
+            &mut Vec::new(),
+            &groups,
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        let fallback = &yaml["proxy-groups"][1];
+        assert_eq!(fallback["interval"], 60);
+        assert!(fallback.get("lazy").is_none());
+    }
+
+    #[test]
+    fn test_group_gui_fields() {
+        use crate::settings::FromIni;
+
+        let groups = ProxyGroupConfigs::from_ini(&[
+            "Proxy`select`!!ICON=https://example.com/proxy.png`[]DIRECT".to_string(),
+            "Backup`select`!!HIDDEN`[]DIRECT".to_string(),
+            "Plain`select`[]DIRECT".to_string(),
+        ]);
+        assert_eq!(
+            groups[0].icon.as_deref(),
+            Some("https://example.com/proxy.png")
+        );
+        assert!(groups[1].hidden);
+        assert_eq!(groups[0].proxies, vec!["[]DIRECT"]);
+
+        let generate = |ext: &mut ExtraSettings, clash_r: bool| {
+            let output =
+                proxy_to_clash(&mut Vec::new(), "", &mut Vec::new(), &groups, clash_r, ext);
+            let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+            let key = if ext.clash_new_field_name {
+                "proxy-groups"
+            } else {
+                "Proxy Group"
+            };
+            yaml[key].as_sequence().unwrap().clone()
+        };
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            clash_new_field_name: true,
+            ..Default::default()
+        };
+        let meta = generate(&mut ext, false);
+        assert_eq!(
+            meta[0]["icon"].as_str(),
+            Some("https://example.com/proxy.png")
+        );
+        assert!(meta[0].get("hidden").is_none());
+        assert_eq!(meta[1]["hidden"], YamlValue::Bool(true));
+        assert!(meta[2].get("icon").is_none() && meta[2].get("hidden").is_none());
+
+        // No other core gets the Meta only keys
+        let no_gui = |groups: &[YamlValue]| {
+            groups
+                .iter()
+                .all(|group| group.get("icon").is_none() && group.get("hidden").is_none())
+        };
+        assert!(no_gui(&generate(&mut ext, true)));
+        ext.clash_core = ClashCore::Vanilla;
+        assert!(no_gui(&generate(&mut ext, false)));
+        ext.clash_core = ClashCore::Meta;
+        ext.clash_new_field_name = false;
+        assert!(no_gui(&generate(&mut ext, false)));
+    }
+
+    #[test]
+    fn test_clash_dns_section() {
+        let base = "port: 7890\nproxies: []\n";
+        let mut ext = ExtraSettings {
+            enable_rule_generator: false,
+            ..Default::default()
+        };
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            base,
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert!(!output.contains("dns:"));
+
+        ext.clash_dns = "dns:\n  enable: true\n  enhanced-mode: fake-ip\n  \
+                         fake-ip-range: 198.18.0.1/16\n  nameserver: [223.5.5.5]\n  \
+                         fallback: [8.8.8.8]\n"
+            .to_string();
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            base,
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["dns"]["enhanced-mode"].as_str(), Some("fake-ip"));
+        assert_eq!(yaml["dns"]["fake-ip-range"].as_str(), Some("198.18.0.1/16"));
+        assert_eq!(yaml["dns"]["fallback"][0].as_str(), Some("8.8.8.8"));
+        assert!(output.find("dns:").unwrap() < output.find("proxies:").unwrap());
+
+        // A bare mapping replaces the section of the base config
+        ext.clash_dns = "enable: false\n".to_string();
+        let output = proxy_to_clash(
+            &mut Vec::new(),
+            "dns:\n  enable: true\n  ipv6: true\nProxy: []\n",
+            &mut Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let yaml: YamlValue = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["dns"]["enable"], YamlValue::Bool(false));
+        assert!(yaml["dns"].get("ipv6").is_none());
+    }
+
+    #[test]
+    fn test_ss2022_needs_clash_meta() {
+        let node = Proxy::ss_construct(
+            "group",
+            "SS2022",
+            "example.com",
+            8388,
+            "ZGVmZ2hpamtsbW5vcHFycw==",
+            "2022-blake3-aes-128-gcm",
+            "",
+            "",
+            None,
+            None,
+            None,
+            None,
+            "",
+        );
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node.clone()],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert_eq!(
+            output["proxies"][0]["cipher"].as_str(),
+            Some("2022-blake3-aes-128-gcm")
+        );
+
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node.clone()],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            true,
+            &mut ext,
+        );
+        assert_eq!(output["proxies"].as_sequence().map(Vec::len), Some(0));
+
+        // The vanilla core lacks the 2022 ciphers too
+        ext.clash_core = ClashCore::Vanilla;
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert_eq!(output["proxies"].as_sequence().map(Vec::len), Some(0));
+    }
+
+    #[test]
+    fn test_skip_cert_verify_ip_only() {
+        let mut ip = node("IP", None);
+        ip.hostname = "1.2.3.4".to_string();
+        let domain = node("Domain", None);
+        let mut pinned = node("Pinned", None);
+        pinned.hostname = "5.6.7.8".to_string();
+        pinned.allow_insecure = Some(false);
+
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            skip_cert_verify: Some(true),
+            skip_cert_verify_policy: SkipCertVerifyPolicy::IpOnly,
+            ..Default::default()
+        };
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![ip, domain, pinned],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+
+        let skip_cert_verify: Vec<Option<bool>> = output["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|proxy| proxy.get("skip-cert-verify").and_then(YamlValue::as_bool))
+            .collect();
+        assert_eq!(skip_cert_verify, vec![Some(true), None, Some(false)]);
+    }
+
+    #[test]
+    fn test_trojan_go_ss_opts() {
+        let mut node = Proxy::default();
+        assert!(crate::parser::explodes::explode(
+            "trojan-go://secret@example.com:443/?type=ws&path=%2Fws&encryption=ss%3Baes-256-gcm%3Ainner&mux=1#Go",
+            &mut node
+        ));
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node.clone()],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        let proxy = &output["proxies"][0];
+        assert_eq!(proxy["type"].as_str(), Some("trojan"));
+        assert_eq!(proxy["network"].as_str(), Some("ws"));
+        assert_eq!(proxy["ws-opts"]["path"].as_str(), Some("/ws"));
+        assert_eq!(proxy["ss-opts"]["enabled"].as_bool(), Some(true));
+        assert_eq!(proxy["ss-opts"]["method"].as_str(), Some("aes-256-gcm"));
+        assert_eq!(proxy["ss-opts"]["password"].as_str(), Some("inner"));
+        assert!(proxy.get("smux").is_none());
+
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node.clone()],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            true,
+            &mut ext,
+        );
+        assert_eq!(output["proxies"].as_sequence().map(Vec::len), Some(0));
+
+        // The server needs the layer, so vanilla Clash drops the node
+        let mut ext = ExtraSettings {
+            nodelist: true,
+            clash_core: ClashCore::Vanilla,
+            ..Default::default()
+        };
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node.clone()],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert_eq!(output["proxies"].as_sequence().map(Vec::len), Some(0));
+        ext.upgrade_clash_core = true;
+        let mut output = YamlValue::Null;
+        proxy_to_clash_yaml(
+            &mut vec![node],
+            &mut output,
+            &Vec::new(),
+            &Vec::new(),
+            false,
+            &mut ext,
+        );
+        assert_eq!(
+            output["proxies"][0]["ss-opts"]["method"].as_str(),
+            Some("aes-256-gcm")
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Rules rejecting traffic of a blocklist
+//!
+//! [`block_rules`](crate::models::ExtraSettings::block_rules) lists what
+//! Clash and sing-box outputs reject, as ruleset lines such as
+//! `DOMAIN-SUFFIX,doubleclick.net`. A bare entry is a `DOMAIN-KEYWORD` and
+//! the entry `QUIC` blocks QUIC, i.e. UDP to port 443, so clients fall back
+//! to TCP where the proxy handles them better.
+//!
+//! Domain and IP entries become a ruleset of the `REJECT` policy put ahead
+//! of the configured rulesets, converted like any other: `REJECT` in Clash
+//! and the `block` outbound tagged `REJECT` in sing-box. QUIC has no ruleset
+//! line every target reads and is emitted as the first rule by each
+//! generator, `AND,((NETWORK,UDP),(DST-PORT,443)),REJECT` in Clash.Meta and
+//! a `network`/`port` rule in sing-box. The ClashR core has no logical
+//! rules and gets no QUIC rule. Like every rule, none of this is generated
+//! without `enable_rule_generator`.
+
+use serde_json::{json, Value as JsonValue};
+
+use crate::models::{RulesetContent, RulesetType};
+
+/// Blocklist entry blocking QUIC
+pub const BLOCK_QUIC: &str = "QUIC";
+
+/// Policy of the blocklist rules, a sing-box `block` outbound of that tag is
+/// always emitted
+pub const BLOCK_POLICY: &str = "REJECT";
+
+/// Clash.Meta rule blocking QUIC
+pub const CLASH_QUIC_RULE: &str = "AND,((NETWORK,UDP),(DST-PORT,443)),REJECT";
+
+/// Whether the blocklist blocks QUIC
+pub fn blocks_quic(entries: &[String]) -> bool {
+    entries
+        .iter()
+        .any(|entry| entry.trim().eq_ignore_ascii_case(BLOCK_QUIC))
+}
+
+/// The ruleset of the domain and IP entries, `None` when there are none
+pub fn block_ruleset(entries: &[String]) -> Option<RulesetContent> {
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty() && !entry.eq_ignore_ascii_case(BLOCK_QUIC))
+        .map(|entry| {
+            if entry.contains(',') {
+                entry.to_string()
+            } else {
+                format!("DOMAIN-KEYWORD,{}", entry)
+            }
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    // No path, the rules are always inlined
+    let mut ruleset = RulesetContent::new("", BLOCK_POLICY);
+    ruleset.rule_type = RulesetType::Surge;
+    ruleset.set_rule_content(&lines.join("\n"));
+    Some(ruleset)
+}
+
+/// sing-box route rule blocking QUIC
+pub fn sing_box_quic_rule() -> JsonValue {
+    json!({
+        "network": "udp",
+        "port": 443,
+        "outbound": BLOCK_POLICY
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_ruleset() {
+        let entries: Vec<String> = ["quic", " ads ", "DOMAIN-SUFFIX,doubleclick.net", ""]
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect();
+        assert!(blocks_quic(&entries));
+        let ruleset = block_ruleset(&entries).unwrap();
+        assert_eq!(ruleset.group, "REJECT");
+        assert_eq!(
+            ruleset.get_rule_content(),
+            "DOMAIN-KEYWORD,ads\nDOMAIN-SUFFIX,doubleclick.net"
+        );
+
+        let quic_only = vec![BLOCK_QUIC.to_string()];
+        assert!(block_ruleset(&quic_only).is_none());
+        assert!(!blocks_quic(&entries[1..]));
+    }
+}
@@ -0,0 +1,273 @@
+//! Rulesets emitted as Clash `rule-providers`
+//!
+//! With `clash_classical_ruleset` set, external rulesets are not inlined but
+//! referenced with `RULE-SET,name,GROUP` and fetched by the client itself.
+//! The `behavior` of a provider follows the ruleset type (`clash-domain:`,
+//! `clash-ipcidr:` and `clash-classical:` prefixes), untyped rulesets are
+//! classical.
+//!
+//! With a managed config prefix every ruleset is served converted by the
+//! `getruleset` endpoint. Without one only remote rulesets the client can
+//! read as they are become providers: Clash payloads and Surge lists (as
+//! `text` providers). Anything else is inlined as before.
+
+use serde_yaml::{Mapping, Value as YamlValue};
+use std::collections::HashSet;
+
+use crate::models::{RulesetContent, RulesetType};
+use crate::utils::base64::url_safe_base64_encode;
+use crate::utils::network::is_link;
+
+/// Refresh interval of rule providers whose ruleset sets none, in seconds
+const RULE_PROVIDER_INTERVAL: u32 = 86400;
+
+/// `behavior` of a Clash rule provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleProviderBehavior {
+    Domain,
+    Ipcidr,
+    Classical,
+}
+
+impl RuleProviderBehavior {
+    /// Behavior of the provider serving a ruleset of this type
+    pub fn for_ruleset(rule_type: RulesetType) -> Self {
+        match rule_type {
+            RulesetType::ClashDomain => RuleProviderBehavior::Domain,
+            RulesetType::ClashIpcidr => RuleProviderBehavior::Ipcidr,
+            _ => RuleProviderBehavior::Classical,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleProviderBehavior::Domain => "domain",
+            RuleProviderBehavior::Ipcidr => "ipcidr",
+            RuleProviderBehavior::Classical => "classical",
+        }
+    }
+
+    /// `type` argument of the `getruleset` endpoint giving this behavior
+    fn getruleset_type(&self) -> u8 {
+        match self {
+            RuleProviderBehavior::Domain => 3,
+            RuleProviderBehavior::Ipcidr => 4,
+            RuleProviderBehavior::Classical => 6,
+        }
+    }
+}
+
+/// Rule providers collected while generating Clash rules
+#[derive(Debug, Clone, Default)]
+pub struct ClashRuleProviders {
+    /// Prefix of the `getruleset` endpoint, empty when there is none
+    managed_config_prefix: String,
+    providers: Mapping,
+    /// Names taken, including the providers of the base config
+    names: HashSet<String>,
+}
+
+impl ClashRuleProviders {
+    pub fn new(managed_config_prefix: &str) -> Self {
+        ClashRuleProviders {
+            managed_config_prefix: managed_config_prefix.trim_end_matches('/').to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Keep the names of the `rule-providers` of the base config, so no
+    /// ruleset is given a name that points at one of them
+    pub fn with_base(mut self, yaml_node: &YamlValue) -> Self {
+        if let Some(section) = yaml_node
+            .get("rule-providers")
+            .and_then(YamlValue::as_mapping)
+        {
+            self.names.extend(
+                section
+                    .keys()
+                    .filter_map(|name| name.as_str().map(str::to_string)),
+            );
+        }
+        self
+    }
+
+    /// Add a provider for the ruleset, returning its `RULE-SET` rule
+    ///
+    /// `None` for inline rules, generated rulesets and rulesets the client
+    /// could not read, they are inlined.
+    pub fn add(&mut self, ruleset: &RulesetContent) -> Option<String> {
+        // Generated rulesets such as the blocklist have no path
+        if ruleset.rule_path.is_empty() || ruleset.rule_path.starts_with("[]") {
+            return None;
+        }
+        let behavior = RuleProviderBehavior::for_ruleset(ruleset.rule_type);
+        let (url, text) = if !self.managed_config_prefix.is_empty() {
+            let url = format!(
+                "{}/getruleset?type={}&url={}",
+                self.managed_config_prefix,
+                behavior.getruleset_type(),
+                url_safe_base64_encode(&ruleset.rule_path_typed)
+            );
+            (url, false)
+        } else if is_link(&ruleset.rule_path) {
+            match ruleset.rule_type {
+                RulesetType::Surge => (ruleset.rule_path.clone(), true),
+                RulesetType::Quanx => return None,
+                _ => (ruleset.rule_path.clone(), false),
+            }
+        } else {
+            return None;
+        };
+
+        let name = self.unique_name(ruleset);
+        let mut provider = Mapping::new();
+        provider.insert("type".into(), "http".into());
+        provider.insert("behavior".into(), behavior.as_str().into());
+        if text {
+            provider.insert("format".into(), "text".into());
+        }
+        provider.insert("url".into(), url.into());
+        provider.insert(
+            "path".into(),
+            format!(
+                "./providers/rule-provider_{}.{}",
+                name,
+                if text { "txt" } else { "yaml" }
+            )
+            .into(),
+        );
+        let interval = if ruleset.update_interval > 0 {
+            ruleset.update_interval
+        } else {
+            RULE_PROVIDER_INTERVAL
+        };
+        provider.insert("interval".into(), YamlValue::Number(interval.into()));
+        self.providers
+            .insert(name.clone().into(), YamlValue::Mapping(provider));
+
+        Some(format!("RULE-SET,{},{}", name, ruleset.group))
+    }
+
+    /// Name of the provider, the ruleset's file name without extension,
+    /// numbered when several rulesets or a provider of the base config share
+    /// it
+    fn unique_name(&mut self, ruleset: &RulesetContent) -> String {
+        let path = ruleset
+            .rule_path
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default();
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        let stem = file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(stem, _)| stem);
+        let base: String = if stem.is_empty() {
+            &ruleset.group
+        } else {
+            stem
+        }
+        .chars()
+        .map(|c| match c {
+            ' ' | ',' | ':' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect();
+
+        let mut name = base.clone();
+        let mut index = 1;
+        while !self.names.insert(name.clone()) {
+            index += 1;
+            name = format!("{}_{}", base, index);
+        }
+        name
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Put the providers into the `rule-providers` section of a config,
+    /// providers of the base config keep their definition
+    pub fn apply(self, yaml_node: &mut YamlValue) {
+        if self.providers.is_empty() {
+            return;
+        }
+        let Some(root) = yaml_node.as_mapping_mut() else {
+            return;
+        };
+        let section = root
+            .entry("rule-providers".into())
+            .or_insert_with(|| YamlValue::Mapping(Mapping::new()));
+        if !section.is_mapping() {
+            *section = YamlValue::Mapping(Mapping::new());
+        }
+        if let Some(section) = section.as_mapping_mut() {
+            for (name, provider) in self.providers {
+                section.entry(name).or_insert(provider);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ruleset(path: &str, rule_type: RulesetType) -> RulesetContent {
+        let mut ruleset = RulesetContent::new(path, "Proxy");
+        ruleset.rule_type = rule_type;
+        ruleset
+    }
+
+    #[test]
+    fn test_clash_rule_providers() {
+        let mut yaml: YamlValue = serde_yaml::from_str("rule-providers:\n  Google: {}\n").unwrap();
+        let mut providers = ClashRuleProviders::new("").with_base(&yaml);
+        assert_eq!(
+            providers
+                .add(&ruleset(
+                    "https://example.com/rules/Google.list",
+                    RulesetType::Surge
+                ))
+                .as_deref(),
+            Some("RULE-SET,Google_2,Proxy")
+        );
+        assert_eq!(
+            providers
+                .add(&ruleset(
+                    "https://example.com/clash/Google.yaml",
+                    RulesetType::ClashDomain
+                ))
+                .as_deref(),
+            Some("RULE-SET,Google_3,Proxy")
+        );
+        // Neither a link nor convertible without the endpoint
+        assert!(providers
+            .add(&ruleset("rules/Local.list", RulesetType::Surge))
+            .is_none());
+        assert!(providers
+            .add(&ruleset("https://example.com/qx.list", RulesetType::Quanx))
+            .is_none());
+
+        providers.apply(&mut yaml);
+        let section = &yaml["rule-providers"];
+        assert_eq!(section["Google"], YamlValue::Mapping(Mapping::new()));
+        assert_eq!(section["Google_2"]["format"], "text");
+        assert_eq!(section["Google_3"]["behavior"], "domain");
+        assert_eq!(section["Google_3"]["interval"], 86400);
+
+        let mut managed = ClashRuleProviders::new("https://sub.example.com/");
+        let mut local = ruleset("rules/Local.list", RulesetType::Quanx);
+        local.update_interval = 3600;
+        assert_eq!(managed.add(&local).as_deref(), Some("RULE-SET,Local,Proxy"));
+        let mut yaml = YamlValue::Mapping(Mapping::new());
+        managed.apply(&mut yaml);
+        let provider = &yaml["rule-providers"]["Local"];
+        assert_eq!(provider["behavior"], "classical");
+        assert_eq!(provider["interval"], 3600);
+        assert!(provider["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://sub.example.com/getruleset?type=6&url="));
+    }
+}
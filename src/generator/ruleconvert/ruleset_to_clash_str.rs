@@ -2,7 +2,7 @@
 //!
 //! This module provides functionality to convert rulesets to Clash YAML string format.
 
-use crate::models::RulesetContent;
+use crate::models::{ClashCore, RulesetContent};
 use crate::utils::string::{find_str, starts_with, trim};
 use crate::Settings;
 use lazy_static::lazy_static;
@@ -10,8 +10,10 @@ use log::warn;
 use serde_yaml::Value as YamlValue;
 use std::collections::HashSet;
 
+use super::block_rules::CLASH_QUIC_RULE;
 use super::common::transform_rule_to_common;
 use super::convert_ruleset::convert_ruleset;
+use super::ruleset_to_clash_providers::ClashRuleProviders;
 
 lazy_static! {
     static ref CLASH_RULE_TYPES: HashSet<&'static str> = {
@@ -51,15 +53,47 @@ pub fn ruleset_to_clash_str(
     ruleset_content_array: &[RulesetContent],
     overwrite_original_rules: bool,
     new_field_name: bool,
+) -> String {
+    ruleset_to_clash_rules(
+        base_rule,
+        ruleset_content_array,
+        overwrite_original_rules,
+        new_field_name,
+        None,
+        Some(ClashCore::Meta),
+        false,
+    )
+}
+
+/// Converts rulesets to Clash rules like [`ruleset_to_clash_str`],
+/// referencing the rulesets `providers` can serve with `RULE-SET` instead of
+/// inlining them
+///
+/// `core` is the core the rules are written for, `None` for ClashR. With
+/// `block_quic` the rule rejecting QUIC goes first, for Clash.Meta only as
+/// the other cores have no logical rules.
+pub fn ruleset_to_clash_rules(
+    base_rule: &YamlValue,
+    ruleset_content_array: &[RulesetContent],
+    overwrite_original_rules: bool,
+    new_field_name: bool,
+    mut providers: Option<&mut ClashRuleProviders>,
+    core: Option<ClashCore>,
+    block_quic: bool,
 ) -> String {
     // Get global settings
-    let settings = Settings::current();
+    let settings = Settings::snapshot();
 
     // Set field name based on parameter
     let field_name = if new_field_name { "rules" } else { "Rule" };
     let mut output_content = format!("\n{}:\n", field_name);
     let mut total_rules = 0;
 
+    if block_quic && core == Some(ClashCore::Meta) {
+        output_content.push_str(&format!("  - {}\n", CLASH_QUIC_RULE));
+        total_rules += 1;
+    }
+
     // Include existing rules if not overwriting
     if !overwrite_original_rules {
         if let Some(rules) = base_rule.get(field_name) {
@@ -86,6 +120,12 @@ pub fn ruleset_to_clash_str(
         // Get group name from ruleset
         let rule_group = &ruleset.group;
 
+        if let Some(rule) = providers.as_deref_mut().and_then(|p| p.add(ruleset)) {
+            output_content.push_str(&format!("  - {}\n", rule));
+            total_rules += 1;
+            continue;
+        }
+
         // Get rule content from the shared future-like container
         let retrieved_rules = ruleset.get_rule_content();
 
@@ -25,7 +25,7 @@ pub fn ruleset_to_sing_box(
     overwrite_original_rules: bool,
 ) {
     // Get global settings
-    let settings = Settings::current();
+    let settings = Settings::snapshot();
 
     // Create rules array
     let mut rules = Value::Array(Vec::new());
@@ -125,17 +125,20 @@ pub async fn ruleset_to_surge(
     remote_path_prefix: &str,
 ) {
     // Get global settings
-    let settings = Settings::current();
+    let settings = Settings::snapshot();
 
     // Set the appropriate section based on surge_ver
-    match surge_ver {
-        0 => base_rule.set_current_section("RoutingRule"), // Mellow
-        -1 => base_rule.set_current_section("filter_local"), // Quantumult X
-        -2 => base_rule.set_current_section("TCP"),        // Quantumult
-        _ => base_rule.set_current_section("Rule"),
-    }
+    let section = match surge_ver {
+        0 => "RoutingRule",   // Mellow
+        -1 => "filter_local", // Quantumult X
+        -2 => "TCP",          // Quantumult
+        _ => "Rule",
+    };
+    base_rule.set_current_section(section);
 
-    // Handle overwriting original rules
+    // Handle overwriting original rules. When appending instead, the final
+    // rule of the base config is held back so it stays last.
+    let mut base_final_rules = Vec::new();
     if overwrite_original_rules {
         base_rule.erase_section();
         match surge_ver {
@@ -143,6 +146,17 @@ pub async fn ruleset_to_surge(
             -4 => base_rule.erase_section_by_name("Remote Rule"),
             _ => {}
         }
+    } else if let Ok(items) = base_rule.get_items(section) {
+        let (finals, others): (Vec<_>, Vec<_>) = items
+            .into_iter()
+            .partition(|(key, value)| key == "{NONAME}" && is_final_rule(value));
+        if !finals.is_empty() {
+            base_rule.erase_section();
+            for (key, value) in others {
+                let _ = base_rule.set_current(&key, &value);
+            }
+            base_final_rules = finals.into_iter().map(|(_, value)| value).collect();
+        }
     }
 
     // Keep track of all rules to add
@@ -383,8 +397,50 @@ pub async fn ruleset_to_surge(
         }
     }
 
+    // Keep the base final rule unless the rulesets brought their own
+    if !all_rules.iter().any(|rule| is_final_rule(rule)) {
+        all_rules.extend(base_final_rules);
+    }
+
     // Add all collected rules to the INI
     for rule in all_rules {
         let _ = base_rule.set_current("{NONAME}", &rule);
     }
 }
+
+/// Whether a rule is the catch-all `FINAL`/`MATCH` rule
+fn is_final_rule(rule: &str) -> bool {
+    let rule_type = rule.split(',').next().unwrap_or_default().trim();
+    rule_type.eq_ignore_ascii_case("FINAL") || rule_type.eq_ignore_ascii_case("MATCH")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ruleset::parse_ruleset;
+
+    #[test]
+    fn test_appended_rules_keep_base_final_last() {
+        let mut ini = IniReader::new();
+        ini.store_any_line = true;
+        ini.add_direct_save_section("Rule");
+        ini.parse("[Rule]\nGEOIP,CN,DIRECT\nFINAL,Proxy\n").unwrap();
+
+        let rulesets = vec![parse_ruleset("[]DOMAIN-SUFFIX,google.com", "Proxy")];
+        futures::executor::block_on(ruleset_to_surge(&mut ini, &rulesets, 4, false, ""));
+        assert_eq!(
+            ini.get_all("Rule", "{NONAME}").unwrap(),
+            vec![
+                "GEOIP,CN,DIRECT",
+                "DOMAIN-SUFFIX,google.com,Proxy",
+                "FINAL,Proxy"
+            ]
+        );
+
+        futures::executor::block_on(ruleset_to_surge(&mut ini, &rulesets, 4, true, ""));
+        assert_eq!(
+            ini.get_all("Rule", "{NONAME}").unwrap(),
+            vec!["DOMAIN-SUFFIX,google.com,Proxy"]
+        );
+    }
+}
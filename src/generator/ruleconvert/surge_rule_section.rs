@@ -0,0 +1,229 @@
+//! Surge `[Rule]` section parsing
+//!
+//! This module reads rules written in Surge's own syntax and turns them into
+//! ruleset configurations, so a Surge rule section can be used as the rule
+//! source of a conversion.
+
+use std::collections::HashSet;
+
+use log::warn;
+
+use crate::models::ruleset::RulesetConfigs;
+use crate::models::{ProxyGroupConfigs, RulesetConfig, RulesetContent};
+
+/// Rule types that are kept inline, with a value and a target group
+const INLINE_RULE_TYPES: &[&str] = &[
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "GEOIP",
+];
+
+/// Policies every Surge configuration provides
+const BUILTIN_POLICIES: &[&str] = &[
+    "DIRECT",
+    "REJECT",
+    "REJECT-TINYGIF",
+    "REJECT-DROP",
+    "REJECT-NO-DROP",
+];
+
+/// Parse the rules of a Surge `[Rule]` section
+///
+/// The content may be a whole Surge configuration, in which case only the
+/// `[Rule]` section is read, or bare rule lines. Supported directives are
+/// `RULE-SET`, `FINAL` (or `MATCH`) and the inline types `DOMAIN`,
+/// `DOMAIN-SUFFIX`, `DOMAIN-KEYWORD`, `IP-CIDR`, `IP-CIDR6` and `GEOIP`.
+///
+/// A `RULE-SET` path may be a local file or a remote URL; it is fetched like
+/// any other ruleset. Its `update-interval` option is kept.
+///
+/// # Arguments
+/// * `content` - Surge configuration or rule lines
+///
+/// # Returns
+/// The rules as ruleset configurations, in their original order
+pub fn parse_surge_rule_section(content: &str) -> RulesetConfigs {
+    let has_sections = content
+        .lines()
+        .any(|line| line.trim().starts_with('[') && line.trim().ends_with(']'));
+    let mut in_rule_section = !has_sections;
+    let mut rulesets = RulesetConfigs::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            in_rule_section = line[1..line.len() - 1].trim().eq_ignore_ascii_case("Rule");
+            continue;
+        }
+        if !in_rule_section
+            || line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with(';')
+            || line.starts_with("//")
+        {
+            continue;
+        }
+
+        match parse_surge_rule(line) {
+            Some(ruleset) => rulesets.push(ruleset),
+            None => warn!("Skipping unsupported Surge rule: '{}'", line),
+        }
+    }
+
+    rulesets
+}
+
+/// Parse a single Surge rule line
+fn parse_surge_rule(line: &str) -> Option<RulesetConfig> {
+    let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+    let rule_type = parts[0].to_uppercase();
+
+    match rule_type.as_str() {
+        "RULE-SET" => {
+            let (path, group) = (*parts.get(1)?, *parts.get(2)?);
+            if path.is_empty() || group.is_empty() {
+                return None;
+            }
+            let interval = parts[3..]
+                .iter()
+                .filter_map(|opt| opt.split_once('='))
+                .find(|(key, _)| key.trim() == "update-interval")
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .unwrap_or(0);
+            Some(RulesetConfig {
+                group: group.to_string(),
+                url: path.to_string(),
+                interval,
+            })
+        }
+        "FINAL" | "MATCH" => {
+            let group = *parts.get(1)?;
+            (!group.is_empty()).then(|| RulesetConfig {
+                group: group.to_string(),
+                url: "[]FINAL".to_string(),
+                interval: 0,
+            })
+        }
+        _ if INLINE_RULE_TYPES.contains(&rule_type.as_str()) => {
+            let (value, group) = (*parts.get(1)?, *parts.get(2)?);
+            if value.is_empty() || group.is_empty() {
+                return None;
+            }
+            let mut url = format!("[]{},{}", rule_type, value);
+            if parts[3..].contains(&"no-resolve") {
+                url.push_str(",no-resolve");
+            }
+            Some(RulesetConfig {
+                group: group.to_string(),
+                url,
+                interval: 0,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Drop rulesets whose target is neither a proxy group, a node nor a
+/// built-in policy
+///
+/// # Arguments
+/// * `rulesets` - Rulesets to filter
+/// * `groups` - Proxy groups of the generated configuration
+/// * `node_names` - Node names of the generated configuration
+///
+/// # Returns
+/// The rulesets that target a known policy
+pub fn retain_known_groups(
+    rulesets: &[RulesetContent],
+    groups: &ProxyGroupConfigs,
+    node_names: &[String],
+) -> Vec<RulesetContent> {
+    let known: HashSet<&str> = groups
+        .iter()
+        .map(|group| group.name.as_str())
+        .chain(node_names.iter().map(String::as_str))
+        .collect();
+
+    rulesets
+        .iter()
+        .filter(|ruleset| {
+            let group = ruleset.group.trim();
+            let is_known = known.contains(group)
+                || BUILTIN_POLICIES
+                    .iter()
+                    .any(|policy| policy.eq_ignore_ascii_case(group));
+            if !is_known {
+                warn!(
+                    "Dropping rule for unknown group '{}': '{}'",
+                    group,
+                    if ruleset.rule_path.is_empty() {
+                        ruleset.get_rule_content()
+                    } else {
+                        ruleset.rule_path.clone()
+                    }
+                );
+            }
+            is_known
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyGroupConfig;
+
+    #[test]
+    fn test_parse_surge_rule_section() {
+        let rules = parse_surge_rule_section(
+            "[General]\nloglevel = notify\n\n[Rule]\n# comment\n\
+             RULE-SET,https://example.com/ad.list,REJECT,update-interval=3600\n\
+             RULE-SET,rules/local.list,Proxy\n\
+             DOMAIN-SUFFIX,google.com,Proxy\n\
+             IP-CIDR,10.0.0.0/8,DIRECT,no-resolve\n\
+             GEOIP,CN,DIRECT\n\
+             PROCESS-NAME,curl,DIRECT\n\
+             FINAL,Proxy,dns-failed\n\n\
+             [MITM]\nhostname = example.com\n",
+        );
+        let urls: Vec<&str> = rules.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/ad.list",
+                "rules/local.list",
+                "[]DOMAIN-SUFFIX,google.com",
+                "[]IP-CIDR,10.0.0.0/8,no-resolve",
+                "[]GEOIP,CN",
+                "[]FINAL",
+            ]
+        );
+        assert_eq!(rules[0].interval, 3600);
+        assert_eq!(rules[0].group, "REJECT");
+        assert_eq!(rules[5].group, "Proxy");
+
+        let bare = parse_surge_rule_section("GEOIP,US,Proxy\nMATCH,DIRECT");
+        assert_eq!(bare.len(), 2);
+        assert_eq!(bare[1].url, "[]FINAL");
+    }
+
+    #[test]
+    fn test_retain_known_groups() {
+        let groups = vec![ProxyGroupConfig {
+            name: "Proxy".to_string(),
+            ..Default::default()
+        }];
+        let rulesets = vec![
+            RulesetContent::new("", "Proxy"),
+            RulesetContent::new("", "reject"),
+            RulesetContent::new("", "Streaming"),
+        ];
+        let kept = retain_known_groups(&rulesets, &groups, &[]);
+        let kept: Vec<&str> = kept.iter().map(|r| r.group.as_str()).collect();
+        assert_eq!(kept, vec!["Proxy", "reject"]);
+    }
+}
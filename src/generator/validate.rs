@@ -0,0 +1,340 @@
+//! Checks of generated output before it reaches a client
+//!
+//! With [`validate_output`](crate::models::ExtraSettings::validate_output)
+//! set, Clash and sing-box output is parsed back after generation and a
+//! conversion whose output a client would refuse to load fails instead of
+//! being returned. Clash proxies are read with the crate's own Clash parser;
+//! for both formats every name a group, rule, detour or `dialer-proxy`
+//! refers to must exist and names must be unique. Streamed node lists are
+//! checked one node at a time with [`validate_clash_node`]. Other targets are
+//! not checked.
+
+use std::collections::HashSet;
+
+use serde_json::Value as JsonValue;
+use serde_yaml::{Mapping, Value as YamlValue};
+
+use crate::models::SubconverterTarget;
+use crate::parser::yaml::clash::{
+    parse_clash_yaml_entries, ClashParseError, CLASH_BUILTIN_POLICIES,
+};
+
+/// Check that `output` generated for `target` is loadable
+///
+/// # Returns
+/// The first problem found
+pub fn validate_output(target: &SubconverterTarget, output: &str) -> Result<(), String> {
+    match target {
+        SubconverterTarget::Clash | SubconverterTarget::ClashR => validate_clash(output),
+        SubconverterTarget::SingBox => validate_singbox(output),
+        _ => Ok(()),
+    }
+}
+
+/// The value of the first key present, Clash accepts both field name styles
+fn section<'a>(map: &'a Mapping, keys: &[&str]) -> Option<&'a YamlValue> {
+    keys.iter().find_map(|key| map.get(*key))
+}
+
+/// Name of a proxy or group entry
+fn entry_name<'a>(entry: &'a YamlValue, kind: &str, index: usize) -> Result<&'a str, String> {
+    entry
+        .get("name")
+        .and_then(YamlValue::as_str)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| format!("{} #{} has no name", kind, index + 1))
+}
+
+fn validate_clash(output: &str) -> Result<(), String> {
+    let yaml: YamlValue =
+        serde_yaml::from_str(output).map_err(|e| format!("not a YAML document: {}", e))?;
+    let Some(map) = yaml.as_mapping() else {
+        return Err("not a YAML mapping".to_string());
+    };
+
+    let mut names: HashSet<&str> = CLASH_BUILTIN_POLICIES.iter().copied().collect();
+    let proxies = clash_proxies(map)?;
+    check_clash_proxies(proxies, &mut names)?;
+
+    let groups = match section(map, &["proxy-groups", "Proxy Group"]) {
+        Some(YamlValue::Sequence(groups)) => groups.as_slice(),
+        Some(YamlValue::Null) | None => &[],
+        Some(_) => return Err("the proxy group list is not a list".to_string()),
+    };
+    // Groups may refer to groups defined after them
+    for (index, group) in groups.iter().enumerate() {
+        let name = entry_name(group, "proxy group", index)?;
+        if !names.insert(name) {
+            return Err(format!("proxy group name '{}' is already taken", name));
+        }
+    }
+    for (index, group) in groups.iter().enumerate() {
+        let name = entry_name(group, "proxy group", index)?;
+        if group.get("type").and_then(YamlValue::as_str).is_none() {
+            return Err(format!("proxy group '{}' has no type", name));
+        }
+        let members = group
+            .get("proxies")
+            .and_then(YamlValue::as_sequence)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let uses_provider = group
+            .get("use")
+            .and_then(YamlValue::as_sequence)
+            .is_some_and(|providers| !providers.is_empty());
+        if members.is_empty() && !uses_provider {
+            return Err(format!("proxy group '{}' has no members", name));
+        }
+        for member in members {
+            let member = member.as_str().unwrap_or_default();
+            if !names.contains(member) {
+                return Err(format!(
+                    "proxy group '{}' refers to unknown proxy '{}'",
+                    name, member
+                ));
+            }
+        }
+    }
+
+    for (index, proxy) in proxies.iter().enumerate() {
+        if let Some(dialer) = dialer_proxy(proxy).filter(|dialer| !names.contains(dialer)) {
+            return Err(format!(
+                "proxy '{}' dials through unknown proxy '{}'",
+                entry_name(proxy, "proxy", index)?,
+                dialer
+            ));
+        }
+    }
+
+    let rules = match section(map, &["rules", "Rule"]) {
+        Some(YamlValue::Sequence(rules)) => rules.as_slice(),
+        _ => &[],
+    };
+    for rule in rules.iter().filter_map(YamlValue::as_str) {
+        if let Some(policy) = clash_rule_policy(rule) {
+            if !names.contains(policy) {
+                return Err(format!(
+                    "rule '{}' refers to unknown policy '{}'",
+                    rule, policy
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check a single node of a streamed Clash node list
+///
+/// `chunk` is the node's list item, as written after the `proxies:` line.
+///
+/// # Returns
+/// The `dialer-proxy` of the node, which the caller checks once every node
+/// is written
+pub fn validate_clash_node(chunk: &str) -> Result<Option<String>, String> {
+    let yaml: YamlValue = serde_yaml::from_str(&format!("proxies:\n{}", chunk))
+        .map_err(|e| format!("not a YAML document: {}", e))?;
+    let Some(map) = yaml.as_mapping() else {
+        return Err("not a YAML mapping".to_string());
+    };
+    let proxies = clash_proxies(map)?;
+    check_clash_proxies(proxies, &mut HashSet::new())?;
+    Ok(proxies.first().and_then(dialer_proxy).map(str::to_string))
+}
+
+/// The proxy list of a Clash config
+fn clash_proxies(map: &Mapping) -> Result<&[YamlValue], String> {
+    match section(map, &["proxies", "Proxy"]) {
+        Some(YamlValue::Sequence(proxies)) => Ok(proxies.as_slice()),
+        Some(YamlValue::Null) | None => Ok(&[]),
+        Some(_) => Err("the proxy list is not a list".to_string()),
+    }
+}
+
+/// Check that proxies parse and have unique names, adding them to `names`
+fn check_clash_proxies<'a>(
+    proxies: &'a [YamlValue],
+    names: &mut HashSet<&'a str>,
+) -> Result<(), String> {
+    for (index, proxy) in proxies.iter().enumerate() {
+        let name = entry_name(proxy, "proxy", index)?;
+        if !names.insert(name) {
+            return Err(format!("proxy name '{}' is used twice", name));
+        }
+    }
+    // The parser reads the new field name
+    let mut document = Mapping::new();
+    document.insert("proxies".into(), YamlValue::Sequence(proxies.to_vec()));
+    let document = serde_yaml::to_string(&document).map_err(|e| e.to_string())?;
+    match parse_clash_yaml_entries(&document, true) {
+        Ok(_) => Ok(()),
+        Err(ClashParseError::Entry(error)) => Err(format!("invalid proxy {}", error)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// The proxy or group a Clash.Meta proxy dials through
+fn dialer_proxy(proxy: &YamlValue) -> Option<&str> {
+    proxy.get("dialer-proxy").and_then(YamlValue::as_str)
+}
+
+/// Policy of a Clash rule, `None` for logical and sub-rules whose policy
+/// position depends on their payload
+fn clash_rule_policy(rule: &str) -> Option<&str> {
+    let parts: Vec<&str> = rule.split(',').map(str::trim).collect();
+    match parts[0] {
+        "AND" | "OR" | "NOT" | "SUB-RULE" => None,
+        "MATCH" | "FINAL" => parts.get(1).copied(),
+        _ => parts.get(2).copied(),
+    }
+}
+
+fn validate_singbox(output: &str) -> Result<(), String> {
+    let json: JsonValue =
+        serde_json::from_str(output).map_err(|e| format!("not a JSON document: {}", e))?;
+    let outbounds = match json.get("outbounds") {
+        Some(JsonValue::Array(outbounds)) => outbounds.as_slice(),
+        None => &[],
+        Some(_) => return Err("`outbounds` is not an array".to_string()),
+    };
+
+    let mut tags = HashSet::new();
+    for (index, outbound) in outbounds.iter().enumerate() {
+        let tag = outbound
+            .get("tag")
+            .and_then(JsonValue::as_str)
+            .filter(|tag| !tag.is_empty())
+            .ok_or_else(|| format!("outbound #{} has no tag", index + 1))?;
+        if outbound.get("type").and_then(JsonValue::as_str).is_none() {
+            return Err(format!("outbound '{}' has no type", tag));
+        }
+        if !tags.insert(tag) {
+            return Err(format!("outbound tag '{}' is used twice", tag));
+        }
+    }
+
+    let known = |tag: &str, what: &str| {
+        if tags.contains(tag) {
+            Ok(())
+        } else {
+            Err(format!("{} refers to unknown outbound '{}'", what, tag))
+        }
+    };
+    for outbound in outbounds {
+        let tag = outbound["tag"].as_str().unwrap_or_default();
+        if let Some(members) = outbound.get("outbounds").and_then(JsonValue::as_array) {
+            if members.is_empty() {
+                return Err(format!("outbound '{}' has no members", tag));
+            }
+            for member in members {
+                known(
+                    member.as_str().unwrap_or_default(),
+                    &format!("outbound '{}'", tag),
+                )?;
+            }
+        }
+        if let Some(detour) = outbound.get("detour").and_then(JsonValue::as_str) {
+            known(detour, &format!("the detour of outbound '{}'", tag))?;
+        }
+    }
+
+    let route = json.get("route");
+    let rules = route
+        .and_then(|route| route.get("rules"))
+        .and_then(JsonValue::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for (index, rule) in rules.iter().enumerate() {
+        if let Some(outbound) = rule.get("outbound").and_then(JsonValue::as_str) {
+            known(outbound, &format!("route rule #{}", index + 1))?;
+        }
+    }
+    // An empty final outbound means the first one
+    if let Some(fallback) = route
+        .and_then(|route| route.get("final"))
+        .and_then(JsonValue::as_str)
+        .filter(|fallback| !fallback.is_empty())
+    {
+        known(fallback, "the final route")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_clash() {
+        let valid = "proxies:\n\
+                     - {name: HK, type: trojan, server: hk.example.com, port: 443, password: pw}\n\
+                     proxy-groups:\n\
+                     - {name: Proxy, type: select, proxies: [Auto, HK, DIRECT]}\n\
+                     - {name: Auto, type: url-test, proxies: [HK]}\n\
+                     rules:\n\
+                     - DOMAIN-SUFFIX,example.com,Proxy,no-resolve\n\
+                     - AND,((NETWORK,UDP),(DST-PORT,443)),REJECT\n\
+                     - MATCH,Proxy\n";
+        assert_eq!(validate_output(&SubconverterTarget::Clash, valid), Ok(()));
+
+        let unknown_member = valid.replace("[Auto, HK, DIRECT]", "[Auto, JP]");
+        let err = validate_output(&SubconverterTarget::Clash, &unknown_member).unwrap_err();
+        assert_eq!(err, "proxy group 'Proxy' refers to unknown proxy 'JP'");
+        let unknown_policy = valid.replace("MATCH,Proxy", "MATCH,Final");
+        assert!(validate_output(&SubconverterTarget::Clash, &unknown_policy).is_err());
+        let bad_port = valid.replace("port: 443", "port: many");
+        let err = validate_output(&SubconverterTarget::ClashR, &bad_port).unwrap_err();
+        assert!(err.starts_with("invalid proxy"));
+        let twice = valid.replace("name: Auto", "name: HK");
+        assert!(validate_output(&SubconverterTarget::Clash, &twice).is_err());
+        let dialer = valid.replace("password: pw}", "password: pw, dialer-proxy: Auto}");
+        assert_eq!(validate_output(&SubconverterTarget::Clash, &dialer), Ok(()));
+        let err = validate_output(
+            &SubconverterTarget::Clash,
+            &valid.replace("password: pw}", "password: pw, dialer-proxy: JP}"),
+        )
+        .unwrap_err();
+        assert_eq!(err, "proxy 'HK' dials through unknown proxy 'JP'");
+
+        // Old field names and provider groups
+        let old = "Proxy: []\nProxy Group:\n- {name: P, type: select, use: [subscription]}\n";
+        assert_eq!(validate_output(&SubconverterTarget::Clash, old), Ok(()));
+        assert!(validate_output(&SubconverterTarget::Clash, "proxies: [").is_err());
+        // Unchecked targets
+        assert_eq!(
+            validate_output(&SubconverterTarget::Surfboard, "[Proxy"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_clash_node() {
+        let node = "- {name: HK, type: trojan, server: hk.example.com, port: 443, password: pw, dialer-proxy: JP}\n";
+        assert_eq!(validate_clash_node(node), Ok(Some("JP".to_string())));
+        let err = validate_clash_node(&node.replace("port: 443", "port: many")).unwrap_err();
+        assert!(err.starts_with("invalid proxy"));
+    }
+
+    #[test]
+    fn test_validate_singbox() {
+        let valid = r#"{
+            "outbounds": [
+                {"type": "selector", "tag": "Proxy", "outbounds": ["HK", "DIRECT"]},
+                {"type": "trojan", "tag": "HK", "detour": "DIRECT"},
+                {"type": "direct", "tag": "DIRECT"}
+            ],
+            "route": {"rules": [{"port": 443, "outbound": "Proxy"}], "final": "Proxy"}
+        }"#;
+        assert_eq!(validate_output(&SubconverterTarget::SingBox, valid), Ok(()));
+
+        let err = validate_output(
+            &SubconverterTarget::SingBox,
+            &valid.replace(r#""final": "Proxy""#, r#""final": "Final""#),
+        )
+        .unwrap_err();
+        assert_eq!(err, "the final route refers to unknown outbound 'Final'");
+        let untyped = valid.replace(r#""type": "trojan", "#, "");
+        assert!(validate_output(&SubconverterTarget::SingBox, &untyped).is_err());
+        let bad_detour = valid.replace(r#""detour": "DIRECT""#, r#""detour": "HK-tls""#);
+        assert!(validate_output(&SubconverterTarget::SingBox, &bad_detour).is_err());
+    }
+}
@@ -1,9 +1,11 @@
+use crate::models::proxy_node::multiplex::singbox_multiplexes;
 use crate::utils::is_empty_option_string;
 use crate::{generator::yaml::clash::output_proxy_types::*, Proxy, ProxyType};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use super::output_proxy_types::clash_output_anytls::ClashOutputAnyTLS;
+use super::output_proxy_types::clash_output_tuic::ClashOutputTuic;
 
 /// Represents a complete Clash configuration output
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,7 +55,7 @@ pub struct ClashYamlOutput {
     pub profile: Option<ClashProfile>,
 
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: BTreeMap<String, serde_yaml::Value>,
 }
 
 /// DNS configuration for Clash
@@ -73,7 +75,7 @@ pub struct ClashDns {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fallback_filter: Option<ClashDnsFallbackFilter>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: BTreeMap<String, serde_yaml::Value>,
 }
 
 /// DNS fallback filter configuration
@@ -87,7 +89,7 @@ pub struct ClashDnsFallbackFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub domain: Option<Vec<String>>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: BTreeMap<String, serde_yaml::Value>,
 }
 
 /// TUN configuration for Clash
@@ -107,7 +109,7 @@ pub struct ClashTun {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_detect_interface: Option<bool>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: BTreeMap<String, serde_yaml::Value>,
 }
 
 /// Profile settings for Clash
@@ -119,7 +121,7 @@ pub struct ClashProfile {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub store_fake_ip: Option<bool>,
     #[serde(flatten)]
-    pub extra_options: HashMap<String, serde_yaml::Value>,
+    pub extra_options: BTreeMap<String, serde_yaml::Value>,
 }
 
 /// Represents a single proxy in Clash configuration
@@ -150,6 +152,8 @@ pub enum ClashProxyOutput {
     VLess(VLessProxy),
     #[serde(rename = "anytls")]
     AnyTls(ClashOutputAnyTLS),
+    #[serde(rename = "tuic")]
+    Tuic(ClashOutputTuic),
 }
 
 /// Factory methods for creating various proxy types
@@ -212,6 +216,11 @@ impl ClashProxyOutput {
     pub fn new_anytls(common: CommonProxyOptions) -> Self {
         ClashProxyOutput::AnyTls(ClashOutputAnyTLS::new(common))
     }
+
+    /// Create a new TUIC proxy
+    pub fn new_tuic(common: CommonProxyOptions) -> Self {
+        ClashProxyOutput::Tuic(ClashOutputTuic::new(common))
+    }
 }
 
 /// Trait for common operations on all ClashProxy variants
@@ -268,6 +277,7 @@ impl ClashProxyCommon for ClashProxyOutput {
             ClashProxyOutput::Hysteria2(proxy) => &proxy.common,
             ClashProxyOutput::VLess(proxy) => &proxy.common,
             ClashProxyOutput::AnyTls(proxy) => &proxy.common,
+            ClashProxyOutput::Tuic(proxy) => &proxy.common,
         }
     }
 
@@ -285,6 +295,7 @@ impl ClashProxyCommon for ClashProxyOutput {
             ClashProxyOutput::Hysteria2(proxy) => &mut proxy.common,
             ClashProxyOutput::VLess(proxy) => &mut proxy.common,
             ClashProxyOutput::AnyTls(proxy) => &mut proxy.common,
+            ClashProxyOutput::Tuic(proxy) => &mut proxy.common,
         }
     }
 }
@@ -385,7 +396,7 @@ impl Default for ClashYamlOutput {
             rules: Vec::new(),
             tun: None,
             profile: None,
-            extra_options: HashMap::new(),
+            extra_options: BTreeMap::new(),
         }
     }
 }
@@ -393,7 +404,16 @@ impl Default for ClashYamlOutput {
 /// Implementation of From trait for ClashProxyOutput
 impl From<Proxy> for ClashProxyOutput {
     fn from(proxy: Proxy) -> Self {
-        match proxy.proxy_type {
+        let dialer_proxy = proxy
+            .underlying_proxy
+            .clone()
+            .filter(|name| !name.is_empty());
+        let ip_version = proxy.ip_version.map(|version| version.to_string());
+        let smux = proxy
+            .multiplex
+            .filter(|_| singbox_multiplexes(proxy.proxy_type))
+            .map(|multiplex| multiplex.clash_smux_yaml());
+        let mut output = match proxy.proxy_type {
             ProxyType::Shadowsocks => ClashProxyOutput::Shadowsocks(ShadowsocksProxy::from(proxy)),
             ProxyType::ShadowsocksR => {
                 ClashProxyOutput::ShadowsocksR(ShadowsocksRProxy::from(proxy))
@@ -423,6 +443,7 @@ impl From<Proxy> for ClashProxyOutput {
             ProxyType::Hysteria => ClashProxyOutput::Hysteria(HysteriaProxy::from(proxy)),
             ProxyType::Hysteria2 => ClashProxyOutput::Hysteria2(Hysteria2Proxy::from(proxy)),
             ProxyType::AnyTls => ClashProxyOutput::AnyTls(ClashOutputAnyTLS::from(proxy)),
+            ProxyType::Tuic => ClashProxyOutput::Tuic(ClashOutputTuic::from(proxy)),
             _ => {
                 // 遇到不支持的类型，返回一个默认的HTTP代理
                 // 实际使用时应该在转换前检查并筛选掉不支持的类型
@@ -434,6 +455,10 @@ impl From<Proxy> for ClashProxyOutput {
                 .build();
                 ClashProxyOutput::Http(HttpProxy::new(common))
             }
-        }
+        };
+        output.common_mut().dialer_proxy = dialer_proxy;
+        output.common_mut().ip_version = ip_version;
+        output.common_mut().smux = smux;
+        output
     }
 }
@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 
+use crate::models::proxy_node::combined::CombinedProxy;
 use crate::Proxy;
 
 use super::CommonProxyOptions;
 
 /// Represents an AnyTLS proxy for Clash output configuration (mihomo extension)
+///
+/// TLS options such as `sni` and `skip-cert-verify` live in the flattened
+/// [`CommonProxyOptions`] so the global defaults apply to them.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ClashOutputAnyTLS {
@@ -13,18 +16,7 @@ pub struct ClashOutputAnyTLS {
     pub common: CommonProxyOptions,
     password: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    alpn: Option<Vec<String>>, // Convert HashSet back to Vec for output
-    #[serde(skip_serializing_if = "Option::is_none")]
-    sni: Option<String>,
-    #[serde(rename = "skip-cert-verify", skip_serializing_if = "Option::is_none")]
-    skip_cert_verify: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    fingerprint: Option<String>,
-    #[serde(rename = "client-fingerprint", skip_serializing_if = "Option::is_none")]
-    client_fingerprint: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    // Assuming false is default, don't serialize if false
-    udp: Option<bool>,
+    alpn: Option<Vec<String>>,
     #[serde(
         rename = "idle-session-check-interval",
         skip_serializing_if = "Option::is_none"
@@ -45,11 +37,6 @@ impl ClashOutputAnyTLS {
             common,
             password: String::new(),
             alpn: None,
-            sni: None,
-            skip_cert_verify: None,
-            fingerprint: None,
-            client_fingerprint: None,
-            udp: None,
             idle_session_check_interval: None,
             idle_session_timeout: None,
             min_idle_session: None,
@@ -68,23 +55,22 @@ impl From<Proxy> for ClashOutputAnyTLS {
                 .build();
 
         let mut anytls = ClashOutputAnyTLS::new(common);
-        if let Some(ref combined) = proxy.combined_proxy {
-            if let crate::models::proxy_node::combined::CombinedProxy::AnyTls(ref anytls_proxy) =
-                combined
-            {
-                anytls.password = anytls_proxy.password.clone();
-                anytls.sni = anytls_proxy.sni.clone();
-                anytls.skip_cert_verify = anytls_proxy.skip_cert_verify;
-                anytls.fingerprint = anytls_proxy.fingerprint.clone();
-                anytls.client_fingerprint = anytls_proxy.client_fingerprint.clone();
-                anytls.idle_session_check_interval = anytls_proxy.idle_session_check_interval;
-                anytls.idle_session_timeout = anytls_proxy.idle_session_timeout;
-                anytls.min_idle_session = anytls_proxy.min_idle_session;
-                // 处理 ALPN
-                if let Some(alpn) = &anytls_proxy.alpn {
-                    if !alpn.is_empty() {
-                        anytls.alpn = Some(alpn.iter().cloned().collect());
-                    }
+        if let Some(CombinedProxy::AnyTls(anytls_proxy)) = &proxy.combined_proxy {
+            anytls.password = anytls_proxy.password.clone();
+            anytls.common.sni = anytls.common.sni.or_else(|| anytls_proxy.sni.clone());
+            anytls.common.udp = anytls.common.udp.or(anytls_proxy.udp);
+            anytls.common.skip_cert_verify = anytls
+                .common
+                .skip_cert_verify
+                .or(anytls_proxy.skip_cert_verify);
+            anytls.common.fingerprint = anytls_proxy.fingerprint.clone();
+            anytls.common.client_fingerprint = anytls_proxy.client_fingerprint.clone();
+            anytls.idle_session_check_interval = anytls_proxy.idle_session_check_interval;
+            anytls.idle_session_timeout = anytls_proxy.idle_session_timeout;
+            anytls.min_idle_session = anytls_proxy.min_idle_session;
+            if let Some(alpn) = &anytls_proxy.alpn {
+                if !alpn.is_empty() {
+                    anytls.alpn = Some(alpn.clone());
                 }
             }
         }
@@ -34,6 +34,7 @@ impl From<Proxy> for HttpProxy {
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
                 .tls(Some(proxy.proxy_type == crate::models::ProxyType::HTTPS))
+                .sni(proxy.sni.clone())
                 .build();
 
         let mut http = HttpProxy::new(common);
@@ -1,9 +1,10 @@
 use super::CommonProxyOptions;
+use crate::models::proxy_node::hysteria::Bandwidth;
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
 
-/// Hysteria proxy configuration
+/// Hysteria v1 proxy configuration of Clash.Meta
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct HysteriaProxy {
@@ -100,26 +101,25 @@ impl From<Proxy> for HysteriaProxy {
 
         hysteria.ports = proxy.ports;
         hysteria.protocol = proxy.protocol;
-        hysteria.obfs_protocol = proxy.obfs.clone();
 
+        // Mihomo requires both, the `up-speed` and `down-speed` aliases are
+        // left out
         if proxy.up_speed > 0 {
-            hysteria.up = Some(format!("{}Mbps", proxy.up_speed));
-            hysteria.up_speed = Some(proxy.up_speed);
+            hysteria.up = Some(Bandwidth::from_mbps(proxy.up_speed).to_clash());
         }
-
         if proxy.down_speed > 0 {
-            hysteria.down = Some(format!("{}Mbps", proxy.down_speed));
-            hysteria.down_speed = Some(proxy.down_speed);
+            hysteria.down = Some(Bandwidth::from_mbps(proxy.down_speed).to_clash());
         }
 
         hysteria.auth = proxy.auth;
         hysteria.auth_str = proxy.auth_str;
 
-        hysteria.obfs = proxy.obfs.clone();
+        // `obfs` takes the xplus password
+        hysteria.obfs = proxy.obfs_param;
         hysteria.fingerprint = proxy.fingerprint;
 
         if !proxy.alpn.is_empty() {
-            hysteria.alpn = Some(proxy.alpn.into_iter().collect());
+            hysteria.alpn = Some(proxy.alpn);
         }
 
         hysteria.ca = proxy.ca;
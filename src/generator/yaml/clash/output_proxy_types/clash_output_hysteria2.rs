@@ -1,4 +1,5 @@
 use super::CommonProxyOptions;
+use crate::models::proxy_node::hysteria::Bandwidth;
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
@@ -99,17 +100,17 @@ impl From<Proxy> for Hysteria2Proxy {
         hysteria2.ports = proxy.ports;
 
         if proxy.up_speed > 0 {
-            hysteria2.up = Some(format!("{}Mbps", proxy.up_speed));
+            hysteria2.up = Some(Bandwidth::from_mbps(proxy.up_speed).to_clash());
         }
 
         if proxy.down_speed > 0 {
-            hysteria2.down = Some(format!("{}Mbps", proxy.down_speed));
+            hysteria2.down = Some(Bandwidth::from_mbps(proxy.down_speed).to_clash());
         }
 
         hysteria2.fingerprint = proxy.fingerprint;
 
         if !proxy.alpn.is_empty() {
-            hysteria2.alpn = Some(proxy.alpn.into_iter().collect());
+            hysteria2.alpn = Some(proxy.alpn);
         }
 
         hysteria2.ca = proxy.ca;
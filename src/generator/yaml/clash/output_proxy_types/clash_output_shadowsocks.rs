@@ -1,8 +1,10 @@
 use super::CommonProxyOptions;
+use crate::models::proxy_node::shadowtls::{ShadowTlsOptions, SHADOW_TLS_PLUGIN};
 use crate::models::Proxy;
 use crate::utils::is_empty_option_string;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_yaml::Value as YamlValue;
+use std::collections::BTreeMap;
 
 /// Shadowsocks proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,7 +19,7 @@ pub struct ShadowsocksProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub plugin: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub plugin_opts: Option<HashMap<String, String>>,
+    pub plugin_opts: Option<BTreeMap<String, YamlValue>>,
     // Additional fields from the C++ implementation
     #[serde(skip_serializing_if = "Option::is_none")]
     pub udp_over_tcp: Option<bool>,
@@ -38,7 +40,8 @@ pub struct ShadowsocksProxy {
     // - interface (not implemented yet)
     // - routing_mark (not implemented yet)
     // - ip_version (not implemented yet)
-    // - dialer_proxy (not implemented yet)
+    // - dialer_proxy (set from underlying_proxy by ClashProxyOutput::from)
+    // - smux (set from multiplex by ClashProxyOutput::from)
 }
 
 impl ShadowsocksProxy {
@@ -66,23 +69,42 @@ impl From<Proxy> for ShadowsocksProxy {
                 .sni(proxy.sni.clone())
                 .build();
 
+        let shadow_tls = ShadowTlsOptions::from_proxy(&proxy);
         let mut ss = ShadowsocksProxy::new(common);
 
         ss.cipher = proxy.encrypt_method;
         ss.password = proxy.password;
-        ss.plugin = proxy.plugin;
+        if let Some(shadow_tls) = shadow_tls {
+            // Clash.Meta expects the version as a number
+            let mut opts = BTreeMap::new();
+            opts.insert("host".to_string(), YamlValue::from(shadow_tls.host));
+            if let Some(password) = shadow_tls.password {
+                opts.insert("password".to_string(), YamlValue::from(password));
+            }
+            opts.insert("version".to_string(), YamlValue::from(shadow_tls.version));
+            if let Some(skip_cert_verify) = proxy.allow_insecure {
+                opts.insert(
+                    "skip-cert-verify".to_string(),
+                    YamlValue::Bool(skip_cert_verify),
+                );
+            }
+            ss.plugin = Some(SHADOW_TLS_PLUGIN.to_string());
+            ss.plugin_opts = Some(opts);
+        } else {
+            ss.plugin = proxy.plugin;
 
-        if let Some(plugin_opts) = proxy.plugin_option {
-            let mut opts = HashMap::new();
+            if let Some(plugin_opts) = proxy.plugin_option {
+                let mut opts = BTreeMap::new();
 
-            for opt in plugin_opts.split(';') {
-                let parts: Vec<&str> = opt.split('=').collect();
-                if parts.len() == 2 {
-                    opts.insert(parts[0].to_string(), parts[1].to_string());
+                for opt in plugin_opts.split(';') {
+                    let parts: Vec<&str> = opt.split('=').collect();
+                    if parts.len() == 2 {
+                        opts.insert(parts[0].to_string(), YamlValue::from(parts[1]));
+                    }
                 }
-            }
 
-            ss.plugin_opts = Some(opts);
+                ss.plugin_opts = Some(opts);
+            }
         }
 
         // Map combined_proxy fields if available
@@ -100,3 +122,31 @@ impl From<Proxy> for ShadowsocksProxy {
         ss
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyType;
+
+    #[test]
+    fn test_shadow_tls_plugin_opts() {
+        let proxy = Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            remark: "SS".to_string(),
+            hostname: "1.2.3.4".to_string(),
+            port: 443,
+            allow_insecure: Some(true),
+            plugin: Some("shadowtls".to_string()),
+            plugin_option: Some("host=cloud.tencent.com;password=stls".to_string()),
+            ..Default::default()
+        };
+        let yaml = serde_yaml::to_value(ShadowsocksProxy::from(proxy)).unwrap();
+
+        assert_eq!(yaml["plugin"].as_str(), Some("shadow-tls"));
+        let opts = &yaml["plugin-opts"];
+        assert_eq!(opts["host"].as_str(), Some("cloud.tencent.com"));
+        assert_eq!(opts["password"].as_str(), Some("stls"));
+        assert_eq!(opts["version"].as_u64(), Some(3));
+        assert_eq!(opts["skip-cert-verify"].as_bool(), Some(true));
+    }
+}
@@ -2,7 +2,7 @@ use super::CommonProxyOptions;
 use crate::models::Proxy;
 use crate::utils::is_empty_option_string;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// Trojan proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,9 +15,14 @@ pub struct TrojanProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub network: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ws_opts: Option<WsOptions>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub grpc_opts: Option<GrpcOptions>,
+    /// Shadowsocks layer of Trojan-Go nodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ss_opts: Option<SsOptions>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,16 +31,28 @@ pub struct WsOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct GrpcOptions {
-    #[serde(skip_serializing_if = "is_empty_option_string")]
+    #[serde(
+        rename = "grpc-service-name",
+        skip_serializing_if = "is_empty_option_string"
+    )]
     pub service_name: Option<String>,
 }
 
+/// Trojan-Go Shadowsocks AEAD layer, Clash.Meta only
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SsOptions {
+    pub enabled: bool,
+    pub method: String,
+    pub password: String,
+}
+
 impl TrojanProxy {
     /// Create a new Trojan proxy
     pub fn new(common: CommonProxyOptions) -> Self {
@@ -43,8 +60,10 @@ impl TrojanProxy {
             common,
             password: None,
             network: None,
+            alpn: None,
             ws_opts: None,
             grpc_opts: None,
+            ss_opts: None,
         }
     }
 }
@@ -57,12 +76,24 @@ impl From<Proxy> for TrojanProxy {
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
                 .sni(proxy.sni.clone())
+                .client_fingerprint(proxy.client_fingerprint.clone())
                 .build();
 
         let mut trojan = TrojanProxy::new(common);
 
+        trojan.ss_opts = proxy
+            .trojan_go()
+            .and_then(|trojan_go| trojan_go.shadowsocks.as_ref())
+            .map(|ss| SsOptions {
+                enabled: true,
+                method: ss.method.clone(),
+                password: ss.password.clone(),
+            });
         trojan.password = proxy.password;
         trojan.network = proxy.transfer_protocol.clone();
+        if !proxy.alpn.is_empty() {
+            trojan.alpn = Some(proxy.alpn.clone());
+        }
 
         if let Some(network) = &proxy.transfer_protocol {
             match network.as_str() {
@@ -77,7 +108,7 @@ impl From<Proxy> for TrojanProxy {
                     }
 
                     if let Some(host) = &proxy.host {
-                        let mut headers = HashMap::new();
+                        let mut headers = BTreeMap::new();
                         headers.insert("Host".to_string(), host.clone());
                         ws_opts.headers = Some(headers);
                     }
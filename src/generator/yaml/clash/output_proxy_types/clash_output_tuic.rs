@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::Proxy;
+
+use super::CommonProxyOptions;
+
+/// Represents a TUIC v5 proxy for Clash output configuration (mihomo extension)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClashOutputTuic {
+    #[serde(flatten)]
+    pub common: CommonProxyOptions,
+    pub uuid: String,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub congestion_controller: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp_relay_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_sni: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_rtt: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval: Option<u32>,
+}
+
+impl ClashOutputTuic {
+    pub fn new(common: CommonProxyOptions) -> Self {
+        Self {
+            common,
+            uuid: String::new(),
+            password: String::new(),
+            congestion_controller: None,
+            udp_relay_mode: None,
+            alpn: None,
+            disable_sni: None,
+            reduce_rtt: None,
+            heartbeat_interval: None,
+        }
+    }
+}
+
+impl From<Proxy> for ClashOutputTuic {
+    fn from(proxy: Proxy) -> Self {
+        let common =
+            CommonProxyOptions::builder(proxy.remark.clone(), proxy.hostname.clone(), proxy.port)
+                .udp(proxy.udp)
+                .tfo(proxy.tcp_fast_open)
+                .skip_cert_verify(proxy.allow_insecure)
+                .sni(proxy.sni.clone())
+                .build();
+
+        let mut tuic = ClashOutputTuic::new(common);
+        if let Some(CombinedProxy::Tuic(ref tuic_proxy)) = proxy.combined_proxy {
+            tuic.uuid = tuic_proxy.uuid.clone();
+            tuic.password = tuic_proxy.password.clone();
+            tuic.congestion_controller = tuic_proxy.congestion_control.clone();
+            tuic.udp_relay_mode = tuic_proxy.udp_relay_mode.clone();
+            tuic.disable_sni = tuic_proxy.disable_sni;
+            tuic.reduce_rtt = tuic_proxy.reduce_rtt;
+            tuic.heartbeat_interval = tuic_proxy.heartbeat_interval;
+            if tuic.common.sni.is_none() {
+                tuic.common.sni = tuic_proxy.sni.clone();
+            }
+            // 处理 ALPN
+            if let Some(alpn) = &tuic_proxy.alpn {
+                if !alpn.is_empty() {
+                    tuic.alpn = Some(alpn.clone());
+                }
+            }
+        }
+        tuic
+    }
+}
@@ -2,7 +2,7 @@ use super::CommonProxyOptions;
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// Reality options for VLESS proxy
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,7 +10,7 @@ use std::collections::HashMap;
 pub struct RealityOptions {
     #[serde(rename = "public-key")]
     pub public_key: String,
-    #[serde(rename = "short-id")]
+    #[serde(rename = "short-id", skip_serializing_if = "String::is_empty")]
     pub short_id: String,
 }
 
@@ -23,7 +23,7 @@ pub struct HTTPOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, Vec<String>>>,
+    pub headers: Option<BTreeMap<String, Vec<String>>>,
 }
 
 /// HTTP2 options for VLESS proxy
@@ -54,7 +54,7 @@ pub struct WSOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<BTreeMap<String, String>>,
     #[serde(rename = "max-early-data", skip_serializing_if = "is_u32_option_zero")]
     pub max_early_data: Option<u32>,
     #[serde(
@@ -105,7 +105,7 @@ pub struct VLessProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub ws_path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ws_headers: Option<HashMap<String, String>>,
+    pub ws_headers: Option<BTreeMap<String, String>>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub servername: Option<String>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
@@ -166,27 +166,31 @@ impl From<Proxy> for VLessProxy {
                 vless.tls = Some(vless_proxy.tls);
                 vless.network = vless_proxy.network.clone();
                 vless.packet_addr = vless_proxy.packet_addr;
-                // vless should force udp to true
-                vless.common.udp = Some(vless_proxy.udp);
+                // an explicit node flag wins, otherwise vless defaults to udp
+                vless.common.udp = proxy.udp.or(Some(vless_proxy.udp));
                 vless.xudp = vless_proxy.xudp;
                 vless.packet_encoding = vless_proxy.packet_encoding.clone();
                 vless.fingerprint = vless_proxy.fingerprint.clone();
-                vless.client_fingerprint = vless_proxy.client_fingerprint.clone();
+                vless.client_fingerprint = vless_proxy
+                    .client_fingerprint
+                    .clone()
+                    .or_else(|| proxy.client_fingerprint.clone());
 
                 // 处理 ALPN
                 if !vless_proxy.alpn.is_empty() {
-                    vless.alpn = Some(vless_proxy.alpn.iter().cloned().collect());
+                    vless.alpn = Some(vless_proxy.alpn.clone());
                 }
 
-                // 处理 Reality 配置
-                if let (Some(public_key), Some(short_id)) = (
-                    &vless_proxy.reality_public_key,
-                    &vless_proxy.reality_short_id,
-                ) {
+                // 处理 Reality 配置，Clash.Meta 要求同时开启 tls 和 client-fingerprint
+                if vless_proxy.is_reality() {
                     vless.reality_opts = Some(RealityOptions {
-                        public_key: public_key.clone(),
-                        short_id: short_id.clone(),
+                        public_key: vless_proxy.reality_public_key.clone().unwrap_or_default(),
+                        short_id: vless_proxy.reality_short_id.clone().unwrap_or_default(),
                     });
+                    vless.tls = Some(true);
+                    if vless.client_fingerprint.as_deref().unwrap_or("").is_empty() {
+                        vless.client_fingerprint = Some("chrome".to_string());
+                    }
                 }
 
                 // 处理不同网络类型的特殊配置
@@ -195,7 +199,10 @@ impl From<Proxy> for VLessProxy {
                         "ws" => {
                             let ws_opts = WSOptions {
                                 path: vless_proxy.ws_path.clone(),
-                                headers: vless_proxy.ws_headers.clone(),
+                                headers: vless_proxy
+                                    .ws_headers
+                                    .clone()
+                                    .map(|h| h.into_iter().collect()),
                                 max_early_data: None,
                                 early_data_header_name: None,
                                 v2ray_http_upgrade: None,
@@ -207,7 +214,10 @@ impl From<Proxy> for VLessProxy {
                             let http_opts = HTTPOptions {
                                 method: vless_proxy.http_method.clone(),
                                 path: vless_proxy.http_path.as_ref().map(|p| vec![p.clone()]),
-                                headers: vless_proxy.http_headers.clone(),
+                                headers: vless_proxy
+                                    .http_headers
+                                    .clone()
+                                    .map(|h| h.into_iter().collect()),
                             };
                             vless.http_opts = Some(http_opts);
                         }
@@ -229,7 +239,7 @@ impl From<Proxy> for VLessProxy {
                 }
 
                 // 设置 servername
-                vless.servername = vless_proxy.servername.clone();
+                vless.servername = vless_proxy.servername.clone().or(proxy.sni.clone());
             }
         } else {
             // 如果没有 combined_proxy，则使用默认字段
@@ -253,7 +263,7 @@ impl From<Proxy> for VLessProxy {
                         }
 
                         if let Some(host) = &proxy.host {
-                            let mut headers = HashMap::new();
+                            let mut headers = BTreeMap::new();
                             headers.insert("Host".to_string(), host.clone());
                             vless.ws_headers = Some(headers);
                         }
@@ -2,7 +2,7 @@ use super::CommonProxyOptions;
 use crate::models::Proxy;
 use crate::utils::{is_empty_option_string, is_u32_option_zero};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// Vmess proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +20,8 @@ pub struct VmessProxy {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub network: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ws_opts: Option<VmessWsOptions>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub http_opts: Option<VmessHttpOptions>,
@@ -35,7 +37,7 @@ pub struct VmessWsOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,7 +46,7 @@ pub struct VmessHttpOptions {
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub headers: Option<HashMap<String, String>>,
+    pub headers: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,7 +61,10 @@ pub struct VmessH2Options {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct VmessGrpcOptions {
-    #[serde(skip_serializing_if = "is_empty_option_string")]
+    #[serde(
+        rename = "grpc-service-name",
+        skip_serializing_if = "is_empty_option_string"
+    )]
     pub service_name: Option<String>,
 }
 
@@ -72,6 +77,7 @@ impl VmessProxy {
             alter_id: 0,
             cipher: None,
             network: None,
+            alpn: None,
             ws_opts: None,
             http_opts: None,
             h2_opts: None,
@@ -88,6 +94,7 @@ impl From<Proxy> for VmessProxy {
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
                 .sni(proxy.sni.clone())
+                .client_fingerprint(proxy.client_fingerprint.clone())
                 .build();
 
         let mut vmess = VmessProxy::new(common);
@@ -96,6 +103,9 @@ impl From<Proxy> for VmessProxy {
         vmess.alter_id = proxy.alter_id as u32;
         vmess.cipher = proxy.encrypt_method.clone();
         vmess.network = proxy.transfer_protocol.clone();
+        if !proxy.alpn.is_empty() {
+            vmess.alpn = Some(proxy.alpn.clone());
+        }
 
         if let Some(network) = &proxy.transfer_protocol {
             match network.as_str() {
@@ -110,7 +120,7 @@ impl From<Proxy> for VmessProxy {
                     }
 
                     if let Some(host) = &proxy.host {
-                        let mut headers = HashMap::new();
+                        let mut headers = BTreeMap::new();
                         headers.insert("Host".to_string(), host.clone());
                         ws_opts.headers = Some(headers);
                     }
@@ -128,7 +138,7 @@ impl From<Proxy> for VmessProxy {
                     }
 
                     if let Some(host) = &proxy.host {
-                        let mut headers = HashMap::new();
+                        let mut headers = BTreeMap::new();
                         headers.insert("Host".to_string(), host.clone());
                         http_opts.headers = Some(headers);
                     }
@@ -17,9 +17,14 @@ pub struct WireGuardProxy {
     pub ip: Option<String>,
     #[serde(skip_serializing_if = "is_empty_option_string")]
     pub ipv6: Option<String>,
-    #[serde(skip_serializing_if = "is_empty_option_string")]
+    #[serde(
+        rename = "pre-shared-key",
+        skip_serializing_if = "is_empty_option_string"
+    )]
     pub preshared_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dns: Option<Vec<String>>,
     #[serde(skip_serializing_if = "is_u32_option_zero")]
     pub mtu: Option<u32>,
@@ -39,6 +44,7 @@ impl WireGuardProxy {
             ip: None,
             ipv6: None,
             preshared_key: None,
+            reserved: None,
             dns: None,
             mtu: None,
             allowed_ips: None,
@@ -51,7 +57,8 @@ impl From<Proxy> for WireGuardProxy {
     fn from(proxy: Proxy) -> Self {
         let common =
             CommonProxyOptions::builder(proxy.remark.clone(), proxy.hostname.clone(), proxy.port)
-                .udp(proxy.udp)
+                // WireGuard only carries UDP, keep it enabled unless disabled
+                .udp(proxy.udp.or(Some(true)))
                 .tfo(proxy.tcp_fast_open)
                 .skip_cert_verify(proxy.allow_insecure)
                 .sni(proxy.sni.clone())
@@ -64,6 +71,16 @@ impl From<Proxy> for WireGuardProxy {
         wg.ip = proxy.self_ip.clone();
         wg.ipv6 = proxy.self_ipv6.clone();
         wg.preshared_key = proxy.pre_shared_key.clone();
+        wg.reserved = proxy
+            .client_id
+            .as_deref()
+            .map(|reserved| {
+                reserved
+                    .split(',')
+                    .filter_map(|b| b.trim().parse::<u8>().ok())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|bytes| !bytes.is_empty());
 
         if !proxy.dns_servers.is_empty() {
             wg.dns = Some(proxy.dns_servers.iter().cloned().collect());
@@ -1,5 +1,6 @@
 use crate::utils::is_empty_option_string;
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value as YamlValue;
 /// Common proxy options that can be used across different proxy types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -32,6 +33,8 @@ pub struct CommonProxyOptions {
     pub ip_version: Option<String>, // ip-version in ClashMeta
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dialer_proxy: Option<String>, // dialer-proxy in ClashMeta
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smux: Option<YamlValue>, // smux in ClashMeta
 }
 
 impl CommonProxyOptions {
@@ -53,6 +56,7 @@ impl CommonProxyOptions {
             routing_mark: None,
             ip_version: None,
             dialer_proxy: None,
+            smux: None,
         }
     }
 
@@ -142,6 +146,12 @@ impl CommonProxyOptionsBuilder {
         self
     }
 
+    /// Set smux option
+    pub fn smux(mut self, value: Option<YamlValue>) -> Self {
+        self.common.smux = value;
+        self
+    }
+
     /// Build the final CommonProxyOptions
     pub fn build(self) -> CommonProxyOptions {
         self.common
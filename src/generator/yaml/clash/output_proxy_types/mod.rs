@@ -7,6 +7,7 @@ pub mod clash_output_shadowsocksr;
 pub mod clash_output_snell;
 pub mod clash_output_socks5;
 pub mod clash_output_trojan;
+pub mod clash_output_tuic;
 pub mod clash_output_vless;
 pub mod clash_output_vmess;
 pub mod clash_output_wireguard;
@@ -44,9 +44,9 @@ where
             // Add strategy for load balancing
             map.serialize_entry("strategy", &group.strategy_str())?;
 
-            // If not lazy, include the flag (false is default, so omit if true)
-            if !group.lazy {
-                map.serialize_entry("lazy", &group.lazy)?;
+            // Only include the flag when the group sets it
+            if let Some(lazy) = group.lazy {
+                map.serialize_entry("lazy", &lazy)?;
             }
 
             // Add URL test fields
@@ -61,9 +61,9 @@ where
             }
         }
         ProxyGroupType::Smart | ProxyGroupType::URLTest => {
-            // If not lazy, include the flag (true is default, so only include if false)
-            if !group.lazy {
-                map.serialize_entry("lazy", &group.lazy)?;
+            // Only include the flag when the group sets it
+            if let Some(lazy) = group.lazy {
+                map.serialize_entry("lazy", &lazy)?;
             }
 
             // Add URL test fields
@@ -167,9 +167,9 @@ pub struct ClashProxyGroup {
     #[serde(skip_serializing_if = "String::is_empty")]
     pub strategy: String,
 
-    /// Whether to use lazy loading
-    #[serde(skip_serializing_if = "is_true")]
-    pub lazy: bool,
+    /// Whether to skip health checks while the group is not in use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lazy: Option<bool>,
 
     /// Whether to disable UDP support
     #[serde(rename = "disable-udp", skip_serializing_if = "is_false")]
@@ -182,6 +182,14 @@ pub struct ClashProxyGroup {
     /// Whether to evaluate before use
     #[serde(rename = "evaluate-before-use", skip_serializing_if = "is_false")]
     pub evaluate_before_use: bool,
+
+    /// Icon URL shown by Clash.Meta GUIs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
+    /// Whether Clash.Meta GUIs hide the group
+    #[serde(skip_serializing_if = "is_false")]
+    pub hidden: bool,
 }
 
 // Helper functions for serde skip conditions
@@ -189,10 +197,6 @@ fn is_zero_u32(val: &u32) -> bool {
     *val == 0
 }
 
-fn is_true(val: &bool) -> bool {
-    *val
-}
-
 fn is_false(val: &bool) -> bool {
     !*val
 }
@@ -217,10 +221,12 @@ impl From<&ProxyGroupConfig> for ClashProxyGroup {
             timeout: 0,
             tolerance: 0,
             strategy: String::new(),
-            lazy: true, // Default to true
+            lazy: None,
             disable_udp: config.disable_udp,
             persistent: config.persistent,
             evaluate_before_use: config.evaluate_before_use,
+            icon: config.icon.clone(),
+            hidden: config.hidden,
         };
 
         // Add type-specific fields
@@ -233,17 +239,10 @@ impl From<&ProxyGroupConfig> for ClashProxyGroup {
                 clash_group.tolerance = config.tolerance;
             }
             ProxyGroupType::URLTest | ProxyGroupType::Smart | ProxyGroupType::Fallback => {
+                clash_group.lazy = config.lazy;
                 clash_group.url = config.url.clone();
                 clash_group.interval = config.interval;
                 clash_group.tolerance = config.tolerance;
-
-                // Only URLTest and Smart use lazy loading
-                if matches!(
-                    config.group_type,
-                    ProxyGroupType::URLTest | ProxyGroupType::Smart
-                ) {
-                    clash_group.lazy = config.lazy;
-                }
             }
             _ => {} // No special fields for other types
         }
@@ -257,6 +256,24 @@ impl From<&ProxyGroupConfig> for ClashProxyGroup {
     }
 }
 
+impl ClashProxyGroup {
+    /// Drop the health check options the vanilla core and legacy cores,
+    /// those reading `Proxy Group`, do not know. They only take `url`,
+    /// `interval` and `tolerance`.
+    pub fn strip_meta_fields(&mut self) {
+        self.lazy = None;
+        self.timeout = 0;
+        self.strip_gui_fields();
+    }
+
+    /// Drop the display options only Clash.Meta reads, the other cores
+    /// reject groups carrying them
+    pub fn strip_gui_fields(&mut self) {
+        self.icon = None;
+        self.hidden = false;
+    }
+}
+
 /// Converts ProxyGroupConfigs to a vector of ClashProxyGroup objects
 pub fn convert_proxy_groups(
     group_configs: &[ProxyGroupConfig],
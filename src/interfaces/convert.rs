@@ -0,0 +1,754 @@
+//! High-level conversion entry point
+//!
+//! [`convert`] runs the whole pipeline on a list of inputs: fetch, parse,
+//! preprocess (type exclusion, filters, rename, emoji, sort, duplicate name
+//! numbering, the node transform hook), group and emit. Proxy groups,
+//! rulesets and base configurations come from the global [`Settings`],
+//! everything else from the given [`ExtraSettings`]. [`convert_split`]
+//! fetches once and emits several configurations, each with its own
+//! settings, [`convert_bundle`] fetches and processes once and emits the
+//! same nodes in several formats.
+
+use std::collections::HashMap;
+
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::generator::validate::validate_output;
+use crate::models::{ExtraSettings, Proxy, RulesetContent, SubconverterTarget};
+use crate::parser::explodes::explode_conf_content;
+use crate::parser::yaml::clash::validate_clash_entries;
+use crate::utils::http::parse_proxy;
+use crate::utils::node_manip::preprocess_parsed_nodes;
+use crate::utils::{
+    decoded_body_size, fetch_subscription, filter_nodes_by_remark, is_link, FetchError,
+    FetchOptions, LimitError, ParseLimits,
+};
+use crate::{RuleBases, Settings};
+
+use super::subconverter::{
+    fetch_limit, generate_output, load_ruleset_content, parse_subscription, ParseOptions,
+    SubconverterConfig, SubconverterConfigBuilder,
+};
+
+/// One input of a conversion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubInput {
+    /// Subscription URL, local file (a path or `file://` URL), `-` for
+    /// standard input, or a single proxy link
+    Url(String),
+    /// Subscription content that was already fetched
+    Content(String),
+}
+
+impl SubInput {
+    /// Short description of the input for error messages
+    fn describe(&self) -> String {
+        match self {
+            SubInput::Url(url) => url.clone(),
+            SubInput::Content(content) => format!("inline content ({} bytes)", content.len()),
+        }
+    }
+}
+
+/// Error of a [`convert`] call, by the stage that failed
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    /// A subscription could not be downloaded
+    #[error("Failed to fetch '{input}': {source}")]
+    Fetch {
+        input: String,
+        #[source]
+        source: FetchError,
+    },
+    /// An input did not contain any recognizable node
+    #[error("Failed to parse '{input}': {message}")]
+    Parse { input: String, message: String },
+    /// No input produced a node
+    #[error("No nodes were found")]
+    NoNodes,
+    /// An input exceeded a size or node count limit
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    /// Filtering, renaming or sorting the nodes failed
+    #[error("Failed to process nodes: {0}")]
+    Process(String),
+    /// The target configuration could not be generated
+    #[error("Failed to generate {target} output: {message}")]
+    Emit { target: String, message: String },
+}
+
+/// Conversion options taken from the global settings
+struct GlobalOptions {
+    skip_failed_links: bool,
+    prepend_proxy_direct: bool,
+    fetch_options: FetchOptions,
+    limits: ParseLimits,
+}
+
+/// Conversion config for `target` built from the global settings, along with
+/// the options the pipeline needs besides the config
+fn global_config(
+    inputs: &[SubInput],
+    target: &SubconverterTarget,
+) -> (SubconverterConfig, GlobalOptions) {
+    let mut builder = SubconverterConfigBuilder::new();
+    let options = {
+        let global = Settings::snapshot();
+        builder
+            .target(target.clone())
+            .proxy_groups(global.custom_proxy_groups.clone())
+            .ruleset_configs(global.custom_rulesets.clone())
+            .rule_bases(RuleBases {
+                clash_rule_base: global.clash_base.clone(),
+                surge_rule_base: global.surge_base.clone(),
+                surfboard_rule_base: global.surfboard_base.clone(),
+                mellow_rule_base: global.mellow_base.clone(),
+                quan_rule_base: global.quan_base.clone(),
+                quanx_rule_base: global.quanx_base.clone(),
+                loon_rule_base: global.loon_base.clone(),
+                sssub_rule_base: global.ssub_base.clone(),
+                singbox_rule_base: global.singbox_base.clone(),
+            });
+        let limits = ParseLimits::from_settings(&global);
+        GlobalOptions {
+            skip_failed_links: global.skip_failed_links,
+            prepend_proxy_direct: global.prepend_proxy_direct_ruleset,
+            fetch_options: FetchOptions {
+                proxy: parse_proxy(&global.proxy_subscription),
+                max_size: limits.max_download_size,
+                ..Default::default()
+            },
+            limits,
+        }
+    };
+    let mut config = builder.build_unchecked();
+    config.urls = inputs
+        .iter()
+        .filter_map(|input| match input {
+            SubInput::Url(url) => Some(url.clone()),
+            SubInput::Content(_) => None,
+        })
+        .collect();
+    (config, options)
+}
+
+/// Convert subscriptions to a target format
+///
+/// Inputs are fetched concurrently, at most `fetch_concurrency` of them at
+/// once, and merged in input order, each one getting its index as group id.
+/// A failing input aborts the conversion unless `skip_failed_links` is set in
+/// the global settings, in which case it is logged and skipped.
+///
+/// `settings` is used as the extra settings of the conversion and keeps the
+/// state the pipeline leaves in it, e.g. the JavaScript context.
+///
+/// # Arguments
+/// * `inputs` - Subscriptions to convert
+/// * `target` - Output format
+/// * `settings` - Extra settings of the conversion
+///
+/// # Returns
+/// The generated configuration
+pub async fn convert(
+    inputs: &[SubInput],
+    target: SubconverterTarget,
+    settings: &mut ExtraSettings,
+) -> Result<String, ConvertError> {
+    let (mut config, options) = global_config(inputs, &target);
+    config.extra = std::mem::take(settings);
+
+    let result = async {
+        let nodes = fetch_nodes(inputs, &options, &config.extra).await?;
+        emit(&mut config, nodes, &options).await
+    }
+    .await;
+
+    *settings = config.extra;
+    result
+}
+
+/// Convert subscriptions into several configurations at once, e.g. one per
+/// region
+///
+/// The inputs are fetched and parsed a single time with the `authorized`,
+/// `strict_parse` and `fetch_concurrency` options of `settings`. Every output
+/// then runs the rest of the pipeline on its own copy of the nodes with its own
+/// settings, so the `include_remarks` and `exclude_remarks` of each output pick
+/// its nodes and emoji or rename rules may differ between outputs. Outputs can
+/// be derived from a common base with [`ExtraSettings::clone_settings`].
+///
+/// # Arguments
+/// * `inputs` - Subscriptions to convert
+/// * `target` - Output format of every output
+/// * `settings` - Settings the inputs are fetched and parsed with
+/// * `outputs` - Extra settings of each output, by output name
+///
+/// # Returns
+/// The generated configuration of each output, by output name
+pub async fn convert_split(
+    inputs: &[SubInput],
+    target: SubconverterTarget,
+    settings: &ExtraSettings,
+    outputs: HashMap<String, ExtraSettings>,
+) -> Result<HashMap<String, String>, ConvertError> {
+    let (_, options) = global_config(inputs, &target);
+    let nodes = fetch_nodes(inputs, &options, settings).await?;
+
+    let mut results = HashMap::with_capacity(outputs.len());
+    for (name, extra) in outputs {
+        let (mut config, _) = global_config(inputs, &target);
+        config.extra = extra;
+        let mut nodes = nodes.clone();
+        select_nodes(&mut nodes, &config.extra)?;
+        let output = emit(&mut config, nodes, &options).await?;
+        results.insert(name, output);
+    }
+    Ok(results)
+}
+
+/// Convert subscriptions into several target formats at once
+///
+/// The inputs are fetched, parsed, filtered, renamed and sorted a single time
+/// with `settings`. Each target then only generates its output from its own
+/// copy of the processed nodes, with the extra settings given for it: output
+/// options such as `clash_new_field_name`, `surge_ssr_path`, `udp` or `tfo`
+/// apply to that target alone, while node selection options are ignored.
+/// Rulesets are fetched once and shared by all targets generating rules.
+///
+/// # Arguments
+/// * `inputs` - Subscriptions to convert
+/// * `settings` - Settings the nodes are fetched and processed with
+/// * `targets` - Extra settings of each output format
+///
+/// # Returns
+/// The generated configuration of each target
+pub async fn convert_bundle(
+    inputs: &[SubInput],
+    settings: &mut ExtraSettings,
+    targets: HashMap<SubconverterTarget, ExtraSettings>,
+) -> Result<HashMap<SubconverterTarget, String>, ConvertError> {
+    let (_, options) = global_config(inputs, &SubconverterTarget::Auto);
+    let mut nodes = fetch_nodes(inputs, &options, settings).await?;
+    preprocess_parsed_nodes(&mut nodes, settings)
+        .await
+        .map_err(|e| ConvertError::Process(e.to_string()))?;
+
+    let mut rulesets: Option<Vec<RulesetContent>> = None;
+    let mut results = HashMap::with_capacity(targets.len());
+    for (target, extra) in targets {
+        let (mut config, _) = global_config(inputs, &target);
+        config.extra = extra;
+        let mut ruleset_content = if !generates_rules(&config) {
+            Vec::new()
+        } else if let Some(content) = &rulesets {
+            content.clone()
+        } else {
+            let content = load_ruleset_content(&config, &nodes, options.prepend_proxy_direct).await;
+            rulesets = Some(content.clone());
+            content
+        };
+        let output = generate_output(&mut config, &mut nodes.clone(), &mut ruleset_content).await;
+        check_output(&config, &output)?;
+        results.insert(target, output);
+    }
+    Ok(results)
+}
+
+/// Whether [`load_ruleset_content`] fetches rulesets for `config`
+fn generates_rules(config: &SubconverterConfig) -> bool {
+    config.extra.enable_rule_generator
+        && !matches!(
+            config.target,
+            SubconverterTarget::NodeJson | SubconverterTarget::V2RayJson
+        )
+}
+
+/// Fetch and parse all inputs into one node list
+async fn fetch_nodes(
+    inputs: &[SubInput],
+    options: &GlobalOptions,
+    extra: &ExtraSettings,
+) -> Result<Vec<Proxy>, ConvertError> {
+    let results: Vec<_> = stream::iter(inputs.iter().enumerate())
+        .map(|(group_id, input)| {
+            parse_input(
+                input,
+                group_id as i32,
+                &options.fetch_options,
+                &options.limits,
+                extra.authorized,
+                extra.strict_parse,
+            )
+        })
+        .buffered(fetch_limit(extra.fetch_concurrency))
+        .collect()
+        .await;
+
+    let mut nodes = Vec::new();
+    for (input, parsed) in inputs.iter().zip(results) {
+        match parsed {
+            Ok(mut parsed) => {
+                info!("Found {} nodes in {}", parsed.len(), input.describe());
+                nodes.append(&mut parsed);
+            }
+            Err(e) if options.skip_failed_links => warn!("{}, skipping it", e),
+            Err(e) => return Err(e),
+        }
+    }
+    select_nodes(&mut nodes, extra)?;
+    if nodes.is_empty() {
+        return Err(ConvertError::NoNodes);
+    }
+    options.limits.check_node_count(nodes.len())?;
+    Ok(nodes)
+}
+
+/// Apply the remark patterns of `extra`, the filter subscriptions are parsed
+/// with
+fn select_nodes(nodes: &mut Vec<Proxy>, extra: &ExtraSettings) -> Result<(), ConvertError> {
+    let removed = filter_nodes_by_remark(
+        nodes,
+        &extra.include_remarks,
+        &extra.exclude_remarks,
+        extra.explain.as_ref(),
+    )
+    .map_err(ConvertError::Process)?;
+    if removed > 0 {
+        info!(
+            "Filtered out {} node(s) by remark, {} left",
+            removed,
+            nodes.len()
+        );
+    }
+    Ok(())
+}
+
+/// Run the node pipeline of `config` and generate its output
+async fn emit(
+    config: &mut SubconverterConfig,
+    mut nodes: Vec<Proxy>,
+    options: &GlobalOptions,
+) -> Result<String, ConvertError> {
+    // `select_nodes` applied the remark patterns already
+    preprocess_parsed_nodes(&mut nodes, &mut config.extra)
+        .await
+        .map_err(|e| ConvertError::Process(e.to_string()))?;
+
+    let mut ruleset_content =
+        load_ruleset_content(config, &nodes, options.prepend_proxy_direct).await;
+    let output = generate_output(config, &mut nodes, &mut ruleset_content).await;
+    check_output(config, &output)?;
+    Ok(output)
+}
+
+/// Fail on generated output a client could not use
+fn check_output(config: &SubconverterConfig, output: &str) -> Result<(), ConvertError> {
+    let emit_error = |message: String| ConvertError::Emit {
+        target: config.target.to_str(),
+        message,
+    };
+    if let Some(message) = config.extra.unsupported_error() {
+        return Err(emit_error(message));
+    }
+    if output.trim().is_empty() {
+        return Err(emit_error("the generator produced no output".to_string()));
+    }
+    if config.extra.validate_output {
+        validate_output(&config.target, output)
+            .map_err(|e| emit_error(format!("generated output is invalid: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Fetch and parse a single input
+async fn parse_input(
+    input: &SubInput,
+    group_id: i32,
+    fetch_options: &FetchOptions,
+    limits: &ParseLimits,
+    authorized: bool,
+    strict: bool,
+) -> Result<Vec<Proxy>, ConvertError> {
+    let content = match input {
+        SubInput::Url(url) if is_link(url) => {
+            fetch_subscription(url, fetch_options)
+                .await
+                .map_err(|source| ConvertError::Fetch {
+                    input: url.clone(),
+                    source,
+                })?
+                .body
+        }
+        SubInput::Url(link) => {
+            // Local files and single proxy links
+            let opts = ParseOptions {
+                authorized,
+                strict,
+                ..Default::default()
+            };
+            return parse_subscription(link, opts, group_id, &None)
+                .await
+                .map_err(|message| ConvertError::Parse {
+                    input: link.clone(),
+                    message,
+                });
+        }
+        SubInput::Content(content) => content.clone(),
+    };
+    if strict {
+        validate_clash_entries(&content).map_err(|e| ConvertError::Parse {
+            input: input.describe(),
+            message: format!("invalid Clash proxy {}", e),
+        })?;
+    }
+
+    let mut nodes = Vec::new();
+    let parsed = explode_conf_content(&content, &mut nodes);
+    limits.check_decoded_size(decoded_body_size(&content))?;
+    if parsed <= 0 || nodes.is_empty() {
+        return Err(ConvertError::Parse {
+            input: input.describe(),
+            message: "no valid node found".to_string(),
+        });
+    }
+    limits.check_node_count(nodes.len())?;
+    for node in &mut nodes {
+        node.group_id = group_id;
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RegexMatchConfig;
+
+    #[test]
+    fn test_convert_content_input() {
+        let inputs = vec![SubInput::Content(
+            "trojan://secret@hk.example.com:443#HK%2001\n\
+             trojan://secret@jp.example.com:443#JP%2001"
+                .to_string(),
+        )];
+        let mut settings = ExtraSettings {
+            nodelist: true,
+            exclude_remarks: vec!["JP".to_string()],
+            ..Default::default()
+        };
+
+        let output =
+            futures::executor::block_on(convert(&inputs, SubconverterTarget::Clash, &mut settings))
+                .unwrap();
+        assert!(output.contains("HK 01"));
+        assert!(!output.contains("JP 01"));
+        assert!(settings.nodelist);
+    }
+
+    #[tokio::test]
+    async fn test_convert_mixed_file_and_content_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sub.txt");
+        std::fs::write(&path, "trojan://secret@hk.example.com:443#HK%2001\n").unwrap();
+
+        let inputs = vec![
+            SubInput::Url(format!("file://{}", path.display())),
+            SubInput::Content("trojan://secret@jp.example.com:443#JP%2001".to_string()),
+        ];
+        let mut settings = ExtraSettings {
+            nodelist: true,
+            authorized: true,
+            ..Default::default()
+        };
+
+        let output = convert(&inputs, SubconverterTarget::Clash, &mut settings)
+            .await
+            .unwrap();
+        assert!(output.contains("HK 01"));
+        assert!(output.contains("JP 01"));
+
+        // Local files need an authorized conversion
+        settings.authorized = false;
+        let err = convert(&inputs, SubconverterTarget::Clash, &mut settings)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ConvertError::Parse { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_convert_split() {
+        let inputs = vec![SubInput::Content(
+            "trojan://secret@hk.example.com:443#HK%2001\n\
+             trojan://secret@hk.example.com:444#HK%2002\n\
+             trojan://secret@jp.example.com:443#JP%2001"
+                .to_string(),
+        )];
+        let base = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+        let mut hk = base.clone_settings();
+        hk.include_remarks = vec!["^HK".to_string()];
+        let mut jp = base.clone_settings();
+        jp.include_remarks = vec!["^JP".to_string()];
+        jp.rename_array = vec![RegexMatchConfig::new(
+            "JP".to_string(),
+            "Japan".to_string(),
+            String::new(),
+        )];
+        let outputs = HashMap::from([("hk".to_string(), hk), ("jp".to_string(), jp)]);
+
+        let results = convert_split(&inputs, SubconverterTarget::Clash, &base, outputs)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results["hk"].contains("HK 01") && results["hk"].contains("HK 02"));
+        assert!(!results["hk"].contains("JP 01"));
+        assert!(results["jp"].contains("Japan 01"));
+        assert!(!results["jp"].contains("HK"));
+    }
+
+    #[tokio::test]
+    async fn test_convert_bundle() {
+        let inputs = vec![SubInput::Content(
+            "trojan://secret@hk.example.com:443#HK%2001\n\
+             trojan://secret@jp.example.com:443#JP%2001"
+                .to_string(),
+        )];
+        let mut settings = ExtraSettings {
+            exclude_remarks: vec!["JP".to_string()],
+            ..Default::default()
+        };
+        let clash = ExtraSettings {
+            nodelist: true,
+            udp: Some(true),
+            ..Default::default()
+        };
+        let surge = ExtraSettings {
+            nodelist: true,
+            // Ignored, nodes were already selected
+            exclude_remarks: vec!["HK".to_string()],
+            ..Default::default()
+        };
+        let targets = HashMap::from([
+            (SubconverterTarget::Clash, clash),
+            (SubconverterTarget::Surge(4), surge),
+        ]);
+
+        let results = convert_bundle(&inputs, &mut settings, targets)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        let clash = &results[&SubconverterTarget::Clash];
+        assert!(clash.contains("udp: true") && clash.contains("HK 01"));
+        assert!(!clash.contains("JP 01"));
+        let surge = &results[&SubconverterTarget::Surge(4)];
+        assert!(surge.contains("HK 01 = trojan") && !surge.contains("udp-relay"));
+        assert!(!surge.contains("JP 01"));
+    }
+
+    #[tokio::test]
+    async fn test_convert_bundle_validates_output() {
+        let inputs = vec![SubInput::Content(
+            "proxies:\n\
+             - {name: HK, type: trojan, server: hk.example.com, port: 443, password: pw, dialer-proxy: JP}\n\
+             - {name: JP, type: trojan, server: jp.example.com, port: 443, password: pw}\n"
+                .to_string(),
+        )];
+        let mut settings = ExtraSettings {
+            exclude_remarks: vec!["JP".to_string()],
+            ..Default::default()
+        };
+        let clash = ExtraSettings {
+            nodelist: true,
+            validate_output: true,
+            ..Default::default()
+        };
+        let targets = HashMap::from([(SubconverterTarget::Clash, clash)]);
+
+        let err = convert_bundle(&inputs, &mut settings, targets)
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("proxy 'HK' dials through unknown proxy 'JP'"));
+    }
+
+    #[tokio::test]
+    async fn test_convert_explain() {
+        use crate::utils::explain::DropStage;
+
+        let inputs = vec![SubInput::Content(
+            "trojan://secret@hk.example.com:443#HK%2001\n\
+             trojan://secret@jp.example.com:443#JP%20Expire\n\
+             hysteria://us.example.com:443?auth=secret&upmbps=10&downmbps=50#US%2001"
+                .to_string(),
+        )];
+        let mut settings = ExtraSettings::builder()
+            .nodelist(true)
+            .add_emoji(true)
+            .exclude_remarks(vec!["Expire".to_string()])
+            .explain(true)
+            .build();
+        settings.rename_array = vec![RegexMatchConfig::new(
+            "HK".to_string(),
+            "Hong Kong".to_string(),
+            String::new(),
+        )];
+        settings.emoji_array = vec![RegexMatchConfig::new(
+            "Hong Kong".to_string(),
+            "🇭🇰".to_string(),
+            String::new(),
+        )];
+
+        convert(&inputs, SubconverterTarget::Surge(4), &mut settings)
+            .await
+            .unwrap();
+        let report = settings.explain.unwrap().snapshot();
+        assert_eq!(report.dropped.len(), 1);
+        assert_eq!(report.dropped[0].remark, "JP Expire");
+        assert_eq!(report.dropped[0].stage, DropStage::RemarkFilter);
+        assert!(report.dropped[0].reason.contains("Expire"));
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].original, "HK 01");
+        assert_eq!(report.renamed[0].remark, "Hong Kong 01");
+        assert_eq!(report.renamed[0].rules, vec!["rename 'HK' -> 'Hong Kong'"]);
+        assert_eq!(report.emoji[0].remark, "Hong Kong 01");
+        assert_eq!(report.emoji[0].emoji, "🇭🇰");
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].remark, "US 01");
+        assert_eq!(report.skipped[0].target, "Surge");
+    }
+
+    #[tokio::test]
+    async fn test_convert_unsupported_policy() {
+        use crate::models::UnsupportedPolicy;
+
+        let inputs = vec![SubInput::Content(
+            "trojan://secret@hk.example.com:443#HK%2001\n\
+             hysteria://us.example.com:443?auth=secret&upmbps=10&downmbps=50#US%2001"
+                .to_string(),
+        )];
+        let settings = |policy| {
+            ExtraSettings::builder()
+                .nodelist(true)
+                .on_unsupported(policy)
+                .build()
+        };
+
+        let output = convert(
+            &inputs,
+            SubconverterTarget::Surge(4),
+            &mut settings(UnsupportedPolicy::Skip),
+        )
+        .await
+        .unwrap();
+        assert!(output.contains("HK 01 = trojan") && !output.contains("US 01"));
+
+        let output = convert(
+            &inputs,
+            SubconverterTarget::Surge(4),
+            &mut settings(UnsupportedPolicy::Comment),
+        )
+        .await
+        .unwrap();
+        assert!(output.contains("HK 01 = trojan"));
+        assert!(output.contains("# US 01: unsupported, Hysteria is not supported"));
+
+        let err = convert(
+            &inputs,
+            SubconverterTarget::Surge(4),
+            &mut settings(UnsupportedPolicy::Error),
+        )
+        .await
+        .unwrap_err();
+        match err {
+            ConvertError::Emit { target, message } => {
+                assert_eq!(target, "surge4");
+                assert!(message.contains("'US 01' for Surge"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_strict_parse() {
+        let inputs = vec![SubInput::Content(
+            "proxies:\n\
+             \x20 - {name: HK 01, type: trojan, server: hk.example.com, port: 443, password: secret}\n\
+             \x20 - {name: JP 01, type: trojan, server: jp.example.com, port: 443}\n"
+                .to_string(),
+        )];
+        let mut settings = ExtraSettings {
+            nodelist: true,
+            ..Default::default()
+        };
+
+        let output =
+            futures::executor::block_on(convert(&inputs, SubconverterTarget::Clash, &mut settings))
+                .unwrap();
+        assert!(output.contains("HK 01"));
+
+        settings.strict_parse = true;
+        let err =
+            futures::executor::block_on(convert(&inputs, SubconverterTarget::Clash, &mut settings))
+                .unwrap_err();
+        assert!(err.to_string().contains("'JP 01' (line 3)"));
+    }
+
+    #[test]
+    fn test_parse_input_limits() {
+        let input = SubInput::Content(
+            "trojan://secret@a.example.com:443#A\ntrojan://secret@b.example.com:443#B".to_string(),
+        );
+        let parse = |limits: ParseLimits| {
+            futures::executor::block_on(parse_input(
+                &input,
+                0,
+                &FetchOptions::default(),
+                &limits,
+                false,
+                false,
+            ))
+        };
+
+        assert_eq!(parse(ParseLimits::default()).unwrap().len(), 2);
+        let err = parse(ParseLimits {
+            max_nodes: 1,
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ConvertError::Limit(LimitError::NodeCount { count: 2, limit: 1 })
+        ));
+        let err = parse(ParseLimits {
+            max_decoded_size: 16,
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ConvertError::Limit(LimitError::DecodedSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_convert_error_stages() {
+        let mut settings = ExtraSettings::default();
+        let garbage = vec![SubInput::Content("not a subscription".to_string())];
+        let err = futures::executor::block_on(convert(
+            &garbage,
+            SubconverterTarget::Clash,
+            &mut settings,
+        ))
+        .unwrap_err();
+        assert!(matches!(err, ConvertError::Parse { .. }));
+
+        let err =
+            futures::executor::block_on(convert(&[], SubconverterTarget::Clash, &mut settings))
+                .unwrap_err();
+        assert!(matches!(err, ConvertError::NoNodes));
+    }
+}
@@ -1,3 +1,7 @@
+pub mod convert;
+pub mod stream;
 pub mod subconverter;
 
+pub use convert::{convert, convert_bundle, convert_split, ConvertError, SubInput};
+pub use stream::{convert_to_writer, NodeStreamWriter};
 pub use subconverter::*;
@@ -0,0 +1,459 @@
+//! Streaming node list output
+//!
+//! [`NodeStreamWriter`] emits a Clash or Surge node list one node at a time,
+//! so neither the whole node list nor the whole output has to be held in
+//! memory. [`convert_to_writer`] drives it from a [`SubconverterConfig`],
+//! parsing and emitting one subscription at a time.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use log::{info, warn};
+
+use crate::generator::config::formats::surge::proxy_to_surge;
+use crate::generator::exports::proxy_to_clash::proxy_to_clash;
+use crate::generator::validate::validate_clash_node;
+use crate::models::{ExtraSettings, Proxy, ProxyGroupConfigs, SubconverterTarget};
+use crate::parser::yaml::clash::CLASH_BUILTIN_POLICIES;
+use crate::utils::node_manip::{preprocess_node, RemarkFilter};
+use crate::Settings;
+
+use super::subconverter::{
+    load_filter_scripts, parse_subscription, ParseOptions, SubconverterConfig,
+};
+
+/// Writes a node list incrementally
+///
+/// Each node goes through the same stages as [`preprocess_nodes`], in the
+/// same order: type exclusion, port drops and rewrites, deduplication, remark
+/// filters, emoji removal, rename and emoji. Sorting and the node transform
+/// hook need the whole list and are not applied. With
+/// [`validate_output`](ExtraSettings::validate_output) set, each Clash node
+/// is checked as it is written and the `dialer-proxy` references once the
+/// list is finished.
+///
+/// [`preprocess_nodes`]: crate::utils::preprocess_nodes
+pub struct NodeStreamWriter<'a> {
+    target: SubconverterTarget,
+    extra: &'a mut ExtraSettings,
+    filter: RemarkFilter,
+    seen_nodes: HashSet<String>,
+    seen_remarks: HashSet<String>,
+    written: usize,
+    /// Names of the validated nodes written so far
+    validated: HashSet<String>,
+    /// Nodes written with a `dialer-proxy`, and the proxy they dial through
+    dialers: Vec<(String, String)>,
+}
+
+impl<'a> NodeStreamWriter<'a> {
+    /// Create a writer for a Clash, ClashR, Surge or Surfboard node list
+    ///
+    /// # Errors
+    /// Returns an error for other targets or invalid remark filters
+    pub fn new(target: SubconverterTarget, extra: &'a mut ExtraSettings) -> Result<Self, String> {
+        if !matches!(
+            target,
+            SubconverterTarget::Clash
+                | SubconverterTarget::ClashR
+                | SubconverterTarget::Surge(_)
+                | SubconverterTarget::Surfboard
+        ) {
+            return Err(format!(
+                "Streaming output is not supported for target {}",
+                target.to_str()
+            ));
+        }
+        if extra.sort_flag {
+            warn!("Sorting is not applied to streamed node lists");
+        }
+        if extra.append_index {
+            warn!("Duplicate names are not numbered in streamed node lists");
+        }
+        if extra.node_transform.is_some() {
+            warn!("The node transform hook is not applied to streamed node lists");
+        }
+        let filter = RemarkFilter::new(&extra.include_remarks, &extra.exclude_remarks)?;
+
+        Ok(NodeStreamWriter {
+            target,
+            extra,
+            filter,
+            seen_nodes: HashSet::new(),
+            seen_remarks: HashSet::new(),
+            written: 0,
+            validated: HashSet::new(),
+            dialers: Vec::new(),
+        })
+    }
+
+    /// Number of nodes written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Process and write one node
+    ///
+    /// # Returns
+    /// Whether the node was written, `false` if a stage dropped it
+    pub async fn write_node(
+        &mut self,
+        mut node: Proxy,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<bool> {
+        if self.extra.exclude_types.contains(&node.proxy_type)
+            || self.extra.drop_ports.contains(node.port)
+        {
+            return Ok(false);
+        }
+        if let Some(port) = self.extra.port_rewrite.rewrite(node.port) {
+            node.port = port;
+        }
+        if self.extra.dedup && !self.seen_nodes.insert(self.extra.dedup_key.identity(&node)) {
+            return Ok(false);
+        }
+        if !self.filter.matches(&node) {
+            return Ok(false);
+        }
+
+        preprocess_node(&mut node, self.extra).await;
+        node.remark = self.unique_remark(&node.remark);
+        let remark = node.remark.clone();
+
+        let chunk = self.render(node).await;
+        if let Some(e) = self.extra.unsupported_error() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+        }
+        if self.extra.validate_output && self.is_clash() && !chunk.is_empty() {
+            if let Some(dialer) = validate_clash_node(&chunk).map_err(invalid_output)? {
+                self.dialers.push((remark.clone(), dialer));
+            }
+            self.validated.insert(remark);
+        }
+        if chunk.is_empty() {
+            if !self.is_clash() {
+                for line in self.extra.unsupported_comments("Surge", "#") {
+                    writeln!(writer, "{}", line)?;
+                }
+            }
+            return Ok(false);
+        }
+        if self.written == 0 && self.is_clash() {
+            writer.write_all(b"proxies:\n")?;
+        }
+        writer.write_all(chunk.as_bytes())?;
+        self.written += 1;
+        Ok(true)
+    }
+
+    /// Process and write a batch of nodes
+    ///
+    /// # Returns
+    /// The number of nodes written
+    pub async fn write_nodes(
+        &mut self,
+        nodes: Vec<Proxy>,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<usize> {
+        let mut count = 0;
+        for node in nodes {
+            if self.write_node(node, writer).await? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Finish the output, writing an empty list if no node was written
+    ///
+    /// # Errors
+    /// Also fails when validating and a node dials through a proxy that was
+    /// not written
+    pub fn finish(self, writer: &mut dyn Write) -> std::io::Result<usize> {
+        if let Some((name, dialer)) = self.dialers.iter().find(|(_, dialer)| {
+            !self.validated.contains(dialer) && !CLASH_BUILTIN_POLICIES.contains(&dialer.as_str())
+        }) {
+            return Err(invalid_output(format!(
+                "proxy '{}' dials through unknown proxy '{}'",
+                name, dialer
+            )));
+        }
+        if self.written == 0 && self.is_clash() {
+            writer.write_all(b"proxies: []\n")?;
+        }
+        writer.flush()?;
+        Ok(self.written)
+    }
+
+    fn is_clash(&self) -> bool {
+        matches!(
+            self.target,
+            SubconverterTarget::Clash | SubconverterTarget::ClashR
+        )
+    }
+
+    /// Make a remark unique among the written ones, like `process_remark`
+    fn unique_remark(&mut self, remark: &str) -> String {
+        let remark = remark.replace('=', "-");
+        let mut unique = remark.clone();
+        let mut cnt = 2;
+        while self.seen_remarks.contains(&unique) {
+            unique = format!("{} {}", remark, cnt);
+            cnt += 1;
+        }
+        self.seen_remarks.insert(unique.clone());
+        unique
+    }
+
+    /// Render a single node with the regular generator in node list mode
+    async fn render(&mut self, node: Proxy) -> String {
+        let nodelist = std::mem::replace(&mut self.extra.nodelist, true);
+        let groups = ProxyGroupConfigs::new();
+        let mut nodes = vec![node];
+        let output = match self.target {
+            SubconverterTarget::Clash | SubconverterTarget::ClashR => {
+                let output = proxy_to_clash(
+                    &mut nodes,
+                    "",
+                    &mut Vec::new(),
+                    &groups,
+                    self.target == SubconverterTarget::ClashR,
+                    self.extra,
+                );
+                // Keep the list items, the header is written once
+                match output.split_once('\n') {
+                    Some((header, items)) if header.trim() == "proxies:" => items.to_string(),
+                    _ => String::new(),
+                }
+            }
+            SubconverterTarget::Surge(ver) => {
+                proxy_to_surge(&mut nodes, "", &mut Vec::new(), &groups, ver, self.extra).await
+            }
+            SubconverterTarget::Surfboard => {
+                proxy_to_surge(&mut nodes, "", &mut Vec::new(), &groups, -3, self.extra).await
+            }
+            _ => String::new(),
+        };
+        self.extra.nodelist = nodelist;
+        output
+    }
+}
+
+fn invalid_output(e: String) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Generated output is invalid: {}", e),
+    )
+}
+
+/// Convert subscriptions straight into a writer
+///
+/// Subscriptions are parsed one at a time and their nodes streamed through a
+/// [`NodeStreamWriter`], so only one subscription is held in memory. Only the
+/// node list is produced, groups and rules need every node up front. Insert
+/// URLs are written before or after the main URLs following
+/// `prepend_insert`, and the filter script runs on each subscription. Extra
+/// nodes come first or last following `prepend_extra_nodes` and are always
+/// processed, as every streamed node is.
+///
+/// # Returns
+/// The number of nodes written
+pub async fn convert_to_writer(
+    mut config: SubconverterConfig,
+    writer: &mut dyn Write,
+) -> Result<usize, String> {
+    let (enable_filter, skip_failed_links) = {
+        let global = Settings::snapshot();
+        (global.enable_filter, global.skip_failed_links)
+    };
+    let opts = ParseOptions {
+        include_remarks: config.include_remarks.clone(),
+        exclude_remarks: config.exclude_remarks.clone(),
+        authorized: config.authorized,
+        strict: config.extra.strict_parse,
+        prefix_labels: config.extra.prefix_labels,
+        ..Default::default()
+    };
+
+    // Insert URLs get negative group ids like in `subconverter`
+    let mut sources: Vec<(String, i32)> = config
+        .urls
+        .iter()
+        .enumerate()
+        .map(|(i, url)| (url.clone(), i as i32))
+        .collect();
+    let inserts = config
+        .insert_urls
+        .iter()
+        .enumerate()
+        .map(|(i, url)| (url.clone(), i as i32 - 1));
+    if config.prepend_insert {
+        sources.splice(0..0, inserts);
+    } else {
+        sources.extend(inserts);
+    }
+
+    let filter_scripts = if enable_filter && config.extra.authorized {
+        load_filter_scripts(&config).await?
+    } else {
+        Vec::new()
+    };
+    let function_name = config
+        .filter_function
+        .clone()
+        .unwrap_or_else(|| crate::models::DEFAULT_FILTER_FUNCTION.to_string());
+
+    // Extra nodes are a source of their own, without a URL
+    let extra_nodes = std::mem::take(&mut config.extra_nodes);
+    let mut sources: Vec<Option<(String, i32)>> = sources.into_iter().map(Some).collect();
+    if !extra_nodes.is_empty() {
+        if config.prepend_extra_nodes {
+            sources.insert(0, None);
+        } else {
+            sources.push(None);
+        }
+    }
+    let mut extra_nodes = Some(extra_nodes);
+
+    let mut stream = NodeStreamWriter::new(config.target.clone(), &mut config.extra)?;
+    for source in sources {
+        let mut nodes = match source {
+            None => {
+                let nodes = extra_nodes.take().unwrap_or_default();
+                info!("Streaming {} extra nodes", nodes.len());
+                nodes
+            }
+            Some((url, group_id)) => {
+                match parse_subscription(&url, opts.clone(), group_id, &config.request_headers)
+                    .await
+                {
+                    Ok(nodes) => {
+                        info!("Streaming {} nodes from '{}'", nodes.len(), url);
+                        nodes
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse URL '{}': {}", url, e);
+                        if !skip_failed_links {
+                            return Err(format!("Failed to parse URL '{}': {}", url, e));
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if let Some(group_name) = &config.group_name {
+            for node in &mut nodes {
+                node.group = group_name.clone();
+            }
+        }
+        if !filter_scripts.is_empty() {
+            stream
+                .extra
+                .eval_filter_chain(&mut nodes, &filter_scripts, &function_name)
+                .map_err(|e| e.to_string())?;
+        }
+        stream
+            .write_nodes(nodes, writer)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    stream.finish(writer).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ProxyType, RegexMatchConfig};
+
+    fn node(remark: &str, host: &str) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: remark.to_string(),
+            hostname: host.to_string(),
+            port: 443,
+            password: Some("secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn stream(target: SubconverterTarget, extra: &mut ExtraSettings, expected: usize) -> String {
+        let mut out = Vec::new();
+        let mut stream = NodeStreamWriter::new(target, extra).unwrap();
+        let nodes = vec![
+            node("HK 01", "a.example.com"),
+            node("HK 01", "b.example.com"),
+            node("JP 01", "c.example.com"),
+            node("HK 01", "a.example.com"),
+        ];
+        futures::executor::block_on(stream.write_nodes(nodes, &mut out)).unwrap();
+        assert_eq!(stream.finish(&mut out).unwrap(), expected);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_stream_applies_stages_in_order() {
+        let mut extra = ExtraSettings {
+            dedup: true,
+            exclude_remarks: vec!["Japan".to_string()],
+            rename_array: vec![RegexMatchConfig::new(
+                "JP".to_string(),
+                "Japan".to_string(),
+                String::new(),
+            )],
+            ..Default::default()
+        };
+
+        // The exclude pattern runs before rename, so the JP node is kept
+        let yaml = stream(SubconverterTarget::Clash, &mut extra, 3);
+        assert!(yaml.starts_with("proxies:\n"));
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let names: Vec<&str> = parsed["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["HK 01", "HK 01 2", "Japan 01"]);
+        assert!(!extra.nodelist);
+
+        extra.dedup = false;
+        extra.exclude_remarks = vec!["JP".to_string()];
+        let surge = stream(SubconverterTarget::Surge(4), &mut extra, 3);
+        let names: Vec<&str> = surge
+            .lines()
+            .map(|line| line.split(" = ").next().unwrap())
+            .collect();
+        assert_eq!(names, vec!["HK 01", "HK 01 2", "HK 01 3"]);
+    }
+
+    #[test]
+    fn test_stream_validates_dialer_proxy() {
+        let mut extra = ExtraSettings {
+            validate_output: true,
+            ..Default::default()
+        };
+        let mut chained = node("HK 01", "a.example.com");
+        chained.underlying_proxy = Some("JP 01".to_string());
+        let write = |extra: &mut ExtraSettings, nodes: Vec<Proxy>| {
+            let mut out = Vec::new();
+            let mut stream = NodeStreamWriter::new(SubconverterTarget::Clash, extra).unwrap();
+            futures::executor::block_on(stream.write_nodes(nodes, &mut out)).unwrap();
+            stream.finish(&mut out)
+        };
+
+        let nodes = vec![chained.clone(), node("JP 01", "b.example.com")];
+        assert_eq!(write(&mut extra, nodes).unwrap(), 2);
+        let err = write(&mut extra, vec![chained]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("proxy 'HK 01' dials through unknown proxy 'JP 01'"));
+    }
+
+    #[test]
+    fn test_stream_rejects_unsupported_target() {
+        let mut extra = ExtraSettings::default();
+        assert!(NodeStreamWriter::new(SubconverterTarget::SingBox, &mut extra).is_err());
+    }
+}
@@ -1,25 +1,55 @@
 use crate::generator::config::formats::single::{proxy_to_single, ProxyUriTypes};
 use crate::generator::config::formats::ssd::proxy_to_ssd;
 use crate::generator::config::formats::{
-    loon::proxy_to_loon, mellow::proxy_to_mellow, quan::proxy_to_quan, quanx::proxy_to_quanx,
-    singbox::proxy_to_singbox, ss_sub::proxy_to_ss_sub, surge::proxy_to_surge,
+    loon::proxy_to_loon, mellow::proxy_to_mellow, node_json::proxy_to_node_json,
+    quan::proxy_to_quan, quanx::proxy_to_quanx, singbox::proxy_to_singbox, ss_sub::proxy_to_ss_sub,
+    surge::proxy_to_surge, v2ray_json::proxy_to_v2ray_json,
 };
+use crate::generator::config::group::fold_small_groups;
 use crate::generator::exports::proxy_to_clash::proxy_to_clash;
+use crate::generator::ruleconvert::block_rules::block_ruleset;
+use crate::generator::ruleconvert::parse_surge_rule_section;
+use crate::generator::validate::validate_output;
+use crate::models::proxy_node::utls::assign_random_fingerprints;
 use crate::models::ruleset::RulesetConfigs;
 use crate::models::{
-    ExtraSettings, Proxy, ProxyGroupConfigs, RegexMatchConfig, RulesetContent, SubconverterTarget,
+    collapse_duplicate_groups, ClashCore, DedupKey, DegradeMap, ExtraSettings, GroupHealthCheck,
+    GroupTypeOverride, IpVersion, Multiplex, NameTemplate, PortRewriteMap, PortSet,
+    ProviderHealthCheck, Proxy, ProxyGroupConfig, ProxyGroupConfigs, ProxyType, RegexMatchConfig,
+    RulesetContent, SkipCertVerifyPolicy, SortKey, SubconverterTarget, UnsupportedPolicy,
+    DEFAULT_FILTER_FUNCTION,
 };
+use crate::parser::explodes::explode;
 use crate::parser::parse_settings::ParseSettings;
 use crate::parser::subparser::add_nodes;
+use crate::parser::yaml::clash::CLASH_BUILTIN_POLICIES;
 use crate::rulesets::ruleset::refresh_rulesets;
+use crate::utils::explain::{DropStage, DropTracker, ExplainRecorder, ExplainReport};
+use crate::utils::file::load_content_async;
 use crate::utils::file_get_async;
+use crate::utils::header::{
+    header_comment_prefix, prepend_output_header, render_output_header, HeaderInfo,
+};
 use crate::utils::http::parse_proxy;
 use crate::utils::http::web_get_async;
+use crate::utils::limits::ParseLimits;
+use crate::utils::managed_config::{set_managed_config, ManagedConfig};
+use crate::utils::node_manip::preprocess_parsed_nodes;
+use crate::utils::system::{safe_system_time, safe_unix_timestamp};
+use crate::utils::timing::{ConvertStage, ConvertStats, Stopwatch};
+use crate::utils::url_encode;
+use crate::utils::userinfo::SubscriptionUserInfo;
+use crate::utils::{
+    excluded_type_reason, filter_nodes_by_remark, filter_nodes_by_type, normalize_node_names,
+    RemarkFilter,
+};
 use crate::{Settings, TemplateArgs};
 use case_insensitive_string::CaseInsensitiveString;
+use futures::stream::{self, StreamExt};
 use log::{debug, error, info, warn};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::UNIX_EPOCH;
 
 #[derive(Debug, Clone, Default)]
 pub struct RuleBases {
@@ -46,6 +76,14 @@ pub struct SubconverterConfig {
     pub insert_urls: Vec<String>,
     /// Whether to prepend inserted nodes
     pub prepend_insert: bool,
+    /// Fixed nodes merged with the fetched ones, e.g. a personal relay
+    pub extra_nodes: Vec<Proxy>,
+    /// Whether to put extra nodes ahead of the fetched ones
+    pub prepend_extra_nodes: bool,
+    /// Whether extra nodes go through the remark filters, type exclusion, the
+    /// filter script, dedup, rename and emoji like fetched nodes; otherwise
+    /// they are added as given after node processing, before grouping
+    pub process_extra_nodes: bool,
     /// Custom group name
     pub group_name: Option<String>,
     /// Ruleset configs
@@ -62,14 +100,24 @@ pub struct SubconverterConfig {
     pub device_id: Option<String>,
     /// Filename for download
     pub filename: Option<String>,
-    /// Update interval in seconds
-    pub update_interval: u32,
+    /// Update interval in seconds, `None` takes the interval of a Surge
+    /// input's managed config header, then the one of the settings
+    pub update_interval: Option<u32>,
     /// Filter script
     pub filter_script: Option<String>,
-    /// Whether update is strict
-    pub update_strict: bool,
+    /// Filter scripts chained after `filter_script`, each seeing only the
+    /// nodes kept by the previous ones
+    pub filter_scripts: Vec<String>,
+    /// Name of the filter function defined by the script, `filter` if unset
+    pub filter_function: Option<String>,
+    /// Whether update is strict, `None` falls back like `update_interval`
+    pub update_strict: Option<bool>,
     /// Managed config prefix
     pub managed_config_prefix: String,
+    /// Whether Surge and Surfboard output repeats the `#!MANAGED-CONFIG`
+    /// header of a Surge input as it was, instead of generating one from
+    /// `managed_config_prefix` with the interval and strict of the input
+    pub keep_managed_config: bool,
     /// Upload path
     pub upload_path: Option<String>,
     /// Whether to upload the result
@@ -88,6 +136,9 @@ pub struct SubconverterConfig {
     pub template_args: Option<TemplateArgs>,
     /// Request headers
     pub request_headers: Option<HashMap<String, String>>,
+    /// Query string of the API request, forwarded by the links generated
+    /// configs use to fetch the nodes of this conversion again
+    pub request_query: Option<String>,
 }
 
 /// Builder for SubconverterConfig
@@ -111,6 +162,9 @@ impl SubconverterConfigBuilder {
                 urls: Vec::new(),
                 insert_urls: Vec::new(),
                 prepend_insert: false,
+                extra_nodes: Vec::new(),
+                prepend_extra_nodes: false,
+                process_extra_nodes: true,
                 group_name: None,
                 ruleset_configs: RulesetConfigs::default(),
                 proxy_groups: Vec::new(),
@@ -119,10 +173,13 @@ impl SubconverterConfigBuilder {
                 extra: ExtraSettings::default(),
                 device_id: None,
                 filename: None,
-                update_interval: 86400, // 24 hours
+                update_interval: None,
                 filter_script: None,
-                update_strict: false,
+                filter_scripts: Vec::new(),
+                filter_function: None,
+                update_strict: None,
                 managed_config_prefix: String::new(),
+                keep_managed_config: false,
                 upload_path: None,
                 upload: false,
                 proxy: None,
@@ -132,6 +189,7 @@ impl SubconverterConfigBuilder {
                 rule_bases: RuleBases::default(),
                 template_args: None,
                 request_headers: None,
+                request_query: None,
             },
         }
     }
@@ -206,6 +264,44 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the extra nodes merged with the fetched ones
+    pub fn extra_nodes(&mut self, nodes: Vec<Proxy>) -> &mut Self {
+        self.config.extra_nodes = nodes;
+        self
+    }
+
+    /// Add an extra node from a proxy link, links that do not parse are
+    /// logged and ignored
+    pub fn add_extra_node_link(&mut self, link: &str) -> &mut Self {
+        let mut node = Proxy::default();
+        // Nodes of a registered codec stay of unknown type
+        if explode(link.trim(), &mut node)
+            && (node.proxy_type != ProxyType::Unknown || node.custom_proxy().is_some())
+        {
+            // Grouped like insert nodes
+            node.group_id = -1;
+            self.config.extra_nodes.push(node);
+        } else {
+            warn!(
+                "Ignoring extra node link that could not be parsed: {}",
+                link
+            );
+        }
+        self
+    }
+
+    /// Set whether to put extra nodes ahead of the fetched ones
+    pub fn prepend_extra_nodes(&mut self, prepend: bool) -> &mut Self {
+        self.config.prepend_extra_nodes = prepend;
+        self
+    }
+
+    /// Set whether extra nodes are processed like fetched nodes
+    pub fn process_extra_nodes(&mut self, process: bool) -> &mut Self {
+        self.config.process_extra_nodes = process;
+        self
+    }
+
     /// Set custom group name
     pub fn group_name(&mut self, name: Option<String>) -> &mut Self {
         self.config.group_name = name;
@@ -223,6 +319,16 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Add the rules of a Surge `[Rule]` section to the ruleset configurations
+    ///
+    /// `RULE-SET` entries may point at a local file or a remote URL.
+    pub fn surge_rule_section(&mut self, content: &str) -> &mut Self {
+        self.config
+            .ruleset_configs
+            .extend(parse_surge_rule_section(content));
+        self
+    }
+
     /// Add an include remark pattern
     pub fn add_include_remark(&mut self, pattern: &str) -> &mut Self {
         self.config.include_remarks.push(pattern.to_string());
@@ -262,6 +368,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Pick emoji from the GeoIP region of each node's server
+    pub fn emoji_by_geoip(&mut self, enable: bool) -> &mut Self {
+        self.config.extra.emoji_by_geoip = enable;
+        self
+    }
+
     pub fn remove_emoji(&mut self, remove: bool) -> &mut Self {
         self.config.extra.remove_emoji = remove;
         self
@@ -279,6 +391,60 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether to number nodes that share a name after renaming
+    pub fn append_index(&mut self, append: bool) -> &mut Self {
+        self.config.extra.append_index = append;
+        self
+    }
+
+    /// Set the minimum number of digits of an appended index
+    pub fn append_index_digits(&mut self, digits: usize) -> &mut Self {
+        self.config.extra.append_index_digits = digits;
+        self
+    }
+
+    /// Set the text put between a name and its appended index
+    pub fn append_index_separator(&mut self, separator: &str) -> &mut Self {
+        self.config.extra.append_index_separator = separator.to_string();
+        self
+    }
+
+    /// Set whether unnamed and duplicate names are fixed right after parsing
+    pub fn normalize_names(&mut self, normalize: bool) -> &mut Self {
+        self.config.extra.normalize_names = normalize;
+        self
+    }
+
+    /// Set whether full-width punctuation in names becomes ASCII
+    pub fn normalize_punctuation(&mut self, normalize: bool) -> &mut Self {
+        self.config.extra.normalize_punctuation = normalize;
+        self
+    }
+
+    /// Set whether known region names become their English name
+    pub fn transliterate_regions(&mut self, transliterate: bool) -> &mut Self {
+        self.config.extra.transliterate_regions = transliterate;
+        self
+    }
+
+    /// Set region name replacements tried before the built-in ones
+    pub fn region_names(&mut self, names: Vec<(String, String)>) -> &mut Self {
+        self.config.extra.region_names = names;
+        self
+    }
+
+    /// Set the template node names are replaced with, `None` keeps them
+    pub fn name_template(&mut self, template: Option<NameTemplate>) -> &mut Self {
+        self.config.extra.name_template = template;
+        self
+    }
+
+    /// Set whether the name template runs after rename rules
+    pub fn name_template_after_rename(&mut self, after: bool) -> &mut Self {
+        self.config.extra.name_template_after_rename = after;
+        self
+    }
+
     /// Set whether to enable TCP Fast Open
     pub fn tfo(&mut self, tfo: Option<bool>) -> &mut Self {
         self.config.extra.tfo = tfo;
@@ -291,12 +457,50 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the UDP over TCP version of nodes that name none
+    pub fn uot_version(&mut self, version: Option<u8>) -> &mut Self {
+        self.config.extra.uot_version = version;
+        self
+    }
+
+    /// Set the IP version preference of nodes that declare none
+    pub fn ip_version(&mut self, ip_version: Option<IpVersion>) -> &mut Self {
+        self.config.extra.ip_version = ip_version;
+        self
+    }
+
+    /// Set the multiplexing of nodes that declare none, emitted as sing-box
+    /// `multiplex` and Clash.Meta `smux`
+    pub fn multiplex(&mut self, multiplex: Option<Multiplex>) -> &mut Self {
+        self.config.extra.multiplex = multiplex;
+        self
+    }
+
     /// Set whether to skip certificate verification
     pub fn skip_cert_verify(&mut self, skip: Option<bool>) -> &mut Self {
         self.config.extra.skip_cert_verify = skip;
         self
     }
 
+    /// Set which nodes the default skip certificate verification flag
+    /// applies to
+    pub fn skip_cert_verify_policy(&mut self, policy: SkipCertVerifyPolicy) -> &mut Self {
+        self.config.extra.skip_cert_verify_policy = policy;
+        self
+    }
+
+    /// Set what happens to nodes the target cannot emit
+    pub fn on_unsupported(&mut self, policy: UnsupportedPolicy) -> &mut Self {
+        self.config.extra.on_unsupported = policy;
+        self
+    }
+
+    /// Set the fallbacks for nodes the target can only emit degraded
+    pub fn degrade(&mut self, degrade: DegradeMap) -> &mut Self {
+        self.config.extra.degrade = degrade;
+        self
+    }
+
     /// Set whether to enable TLS 1.3
     pub fn tls13(&mut self, tls13: Option<bool>) -> &mut Self {
         self.config.extra.tls13 = tls13;
@@ -309,6 +513,98 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether group members are listed in node list order, `None` to
+    /// follow node sorting
+    pub fn sort_group_members(&mut self, sort: Option<bool>) -> &mut Self {
+        self.config.extra.sort_group_members = sort;
+        self
+    }
+
+    /// Drop every node of these proxy types before any other processing
+    pub fn exclude_types(&mut self, types: Vec<ProxyType>) -> &mut Self {
+        self.config.extra.exclude_types = types;
+        self
+    }
+
+    /// Set the rules moving nodes on matching ports to other ports
+    pub fn port_rewrite(&mut self, rules: PortRewriteMap) -> &mut Self {
+        self.config.extra.port_rewrite = rules;
+        self
+    }
+
+    /// Drop every node on these ports
+    pub fn drop_ports(&mut self, ports: PortSet) -> &mut Self {
+        self.config.extra.drop_ports = ports;
+        self
+    }
+
+    /// Set whether to remove duplicate nodes
+    pub fn dedup(&mut self, dedup: bool) -> &mut Self {
+        self.config.extra.dedup = dedup;
+        self
+    }
+
+    /// Set the properties that make two nodes duplicates
+    pub fn dedup_key(&mut self, dedup_key: DedupKey) -> &mut Self {
+        self.config.extra.dedup_key = dedup_key;
+        self
+    }
+
+    /// Set whether to drop nodes that fail a liveness probe
+    pub fn probe_liveness(&mut self, enable: bool) -> &mut Self {
+        self.config.extra.probe_liveness = enable;
+        self
+    }
+
+    /// Set the maximum number of subscriptions fetched at once, capped at
+    /// the `max_concur_threads` setting
+    pub fn fetch_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.config.extra.fetch_concurrency = concurrency;
+        self
+    }
+
+    /// Set whether malformed Clash proxy entries fail the subscription
+    pub fn strict_parse(&mut self, strict: bool) -> &mut Self {
+        self.config.extra.strict_parse = strict;
+        self
+    }
+
+    /// Set whether the proxy groups of Clash inputs are kept
+    pub fn keep_input_groups(&mut self, keep: bool) -> &mut Self {
+        self.config.extra.keep_input_groups = keep;
+        self
+    }
+
+    /// Set whether nodes from labelled inputs are prefixed with the label
+    pub fn prefix_labels(&mut self, prefix: bool) -> &mut Self {
+        self.config.extra.prefix_labels = prefix;
+        self
+    }
+
+    /// Set the maximum number of liveness probes running at once
+    pub fn probe_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.config.extra.probe_concurrency = concurrency;
+        self
+    }
+
+    /// Set the liveness probe timeout in milliseconds
+    pub fn probe_timeout(&mut self, timeout: u32) -> &mut Self {
+        self.config.extra.probe_timeout = timeout;
+        self
+    }
+
+    /// Set the member used for proxy groups that match no node
+    pub fn group_fallback(&mut self, fallback: &str) -> &mut Self {
+        self.config.extra.group_fallback = fallback.to_string();
+        self
+    }
+
+    /// Set the built-in sort order, used instead of the sort script
+    pub fn sort_key(&mut self, sort_key: Option<SortKey>) -> &mut Self {
+        self.config.extra.sort_key = sort_key;
+        self
+    }
+
     /// Set sort script
     pub fn sort_script(&mut self, script: String) -> &mut Self {
         self.config.extra.sort_script = script;
@@ -327,12 +623,84 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the core Clash output is written for
+    pub fn clash_core(&mut self, core: ClashCore) -> &mut Self {
+        self.config.extra.clash_core = core;
+        self
+    }
+
+    /// Set whether vanilla Clash exports switch to Clash.Meta when needed
+    pub fn upgrade_clash_core(&mut self, upgrade: bool) -> &mut Self {
+        self.config.extra.upgrade_clash_core = upgrade;
+        self
+    }
+
     /// Set whether to enable Clash script
     pub fn clash_script(&mut self, enable: bool) -> &mut Self {
         self.config.extra.clash_script = enable;
         self
     }
 
+    /// Set whether Clash output references nodes through `proxy-providers`
+    pub fn clash_use_providers(&mut self, enable: bool) -> &mut Self {
+        self.config.extra.clash_use_providers = enable;
+        self
+    }
+
+    /// Set the URL the Clash provider fetches its node list from, derived
+    /// from the managed config prefix when left empty
+    pub fn clash_provider_url(&mut self, url: &str) -> &mut Self {
+        self.config.extra.clash_provider_url = url.to_string();
+        self
+    }
+
+    /// Set the health check of the Clash provider
+    pub fn provider_health_check(&mut self, check: ProviderHealthCheck) -> &mut Self {
+        self.config.extra.provider_health_check = check;
+        self
+    }
+
+    /// Set the YAML of the `dns` section injected into Clash configs, loaded
+    /// from the `clash_dns` setting when left empty
+    pub fn clash_dns(&mut self, dns: &str) -> &mut Self {
+        self.config.extra.clash_dns = dns.to_string();
+        self
+    }
+
+    /// Set whether QuantumultX output references nodes through
+    /// `[server_remote]`
+    pub fn quanx_server_remote(&mut self, enable: bool) -> &mut Self {
+        self.config.extra.quanx_server_remote = enable;
+        self
+    }
+
+    /// Set the URL the QuantumultX server remote fetches its node list from,
+    /// derived from the managed config prefix when left empty
+    pub fn quanx_server_remote_url(&mut self, url: &str) -> &mut Self {
+        self.config.extra.quanx_server_remote_url = url.to_string();
+        self
+    }
+
+    /// Set the `block-quic` value of Surge 5 policies
+    pub fn surge_block_quic(&mut self, value: &str) -> &mut Self {
+        self.config.extra.surge_block_quic = value.to_string();
+        self
+    }
+
+    /// Set the tag of the generated QuantumultX server remote
+    pub fn quanx_remote_tag(&mut self, tag: &str) -> &mut Self {
+        self.config.extra.quanx_remote_tag = tag.to_string();
+        self
+    }
+
+    /// Set the update interval in seconds of generated QuantumultX remote
+    /// resources
+    pub fn quanx_remote_interval(&mut self, interval: u32) -> &mut Self {
+        self.config.extra.quanx_remote_interval = interval;
+        self
+    }
+
+    /// Set whether Clash output references rulesets as `rule-providers`
     pub fn clash_classical_ruleset(&mut self, enable: bool) -> &mut Self {
         self.config.extra.clash_classical_ruleset = enable;
         self
@@ -344,6 +712,73 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether Shadowsocks links are canonical SIP002 URIs
+    pub fn ss_sip002(&mut self, sip002: bool) -> &mut Self {
+        self.config.extra.ss_sip002 = sip002;
+        self
+    }
+
+    /// Set the health check of proxy groups that set none
+    pub fn group_health_check(&mut self, check: GroupHealthCheck) -> &mut Self {
+        self.config.extra.group_health_check = check;
+        self
+    }
+
+    /// Set whether proxy groups sharing a name are merged
+    pub fn collapse_groups(&mut self, collapse: bool) -> &mut Self {
+        self.config.extra.collapse_groups = collapse;
+        self
+    }
+
+    /// Set the fewest nodes a group filled from node matches keeps, 0 for
+    /// no minimum
+    pub fn min_group_size(&mut self, size: usize) -> &mut Self {
+        self.config.extra.min_group_size = size;
+        self
+    }
+
+    /// Set the rewrite of the type of the configured proxy groups
+    pub fn group_type_override(&mut self, group_type: Option<GroupTypeOverride>) -> &mut Self {
+        self.config.extra.group_type_override = group_type;
+        self
+    }
+
+    /// Set the template of the comment block put ahead of the output
+    pub fn output_header(&mut self, template: Option<String>) -> &mut Self {
+        self.config.extra.output_header = template;
+        self
+    }
+
+    /// Set whether identical input must give byte-identical output
+    pub fn deterministic(&mut self, deterministic: bool) -> &mut Self {
+        self.config.extra.deterministic = deterministic;
+        self
+    }
+
+    /// Set whether generated Clash and sing-box output is validated
+    pub fn validate_output(&mut self, validate: bool) -> &mut Self {
+        self.config.extra.validate_output = validate;
+        self
+    }
+
+    /// Set whether TLS nodes without a client fingerprint get a random one
+    pub fn random_fingerprint(&mut self, enable: bool) -> &mut Self {
+        self.config.extra.random_fingerprint = enable;
+        self
+    }
+
+    /// Set the fingerprints random ones are picked from
+    pub fn fingerprint_pool(&mut self, pool: Vec<String>) -> &mut Self {
+        self.config.extra.fingerprint_pool = pool;
+        self
+    }
+
+    /// Set the seed of random fingerprint picks
+    pub fn fingerprint_seed(&mut self, seed: Option<u64>) -> &mut Self {
+        self.config.extra.fingerprint_seed = seed;
+        self
+    }
+
     /// Set whether to enable rule generator
     pub fn enable_rule_generator(&mut self, enable: bool) -> &mut Self {
         self.config.extra.enable_rule_generator = enable;
@@ -356,8 +791,16 @@ impl SubconverterConfigBuilder {
         self
     }
 
-    /// Set device ID
+    /// Set the blocklist Clash and sing-box outputs reject, e.g. `QUIC` or
+    /// `DOMAIN-SUFFIX,doubleclick.net`
+    pub fn block_rules(&mut self, rules: Vec<String>) -> &mut Self {
+        self.config.extra.block_rules = rules;
+        self
+    }
+
+    /// Set device ID, also used as the QuantumultX device ID
     pub fn device_id(&mut self, device_id: Option<String>) -> &mut Self {
+        self.config.extra.quanx_dev_id = device_id.clone().unwrap_or_default();
         self.config.device_id = device_id;
         self
     }
@@ -370,7 +813,7 @@ impl SubconverterConfigBuilder {
 
     /// Set update interval
     pub fn update_interval(&mut self, interval: u32) -> &mut Self {
-        self.config.update_interval = interval;
+        self.config.update_interval = Some(interval);
         self
     }
 
@@ -380,9 +823,45 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set filter scripts to chain after the filter script, e.g. a personal
+    /// filter after a shared base one
+    pub fn filter_scripts(&mut self, scripts: Vec<String>) -> &mut Self {
+        self.config.filter_scripts = scripts;
+        self
+    }
+
+    /// Set the name of the filter function defined by the filter script
+    pub fn filter_function(&mut self, name: Option<String>) -> &mut Self {
+        self.config.filter_function = name;
+        self
+    }
+
+    /// Set whether a failure of the filter script on any node aborts the
+    /// conversion instead of keeping the nodes it failed on
+    pub fn filter_script_strict(&mut self, strict: bool) -> &mut Self {
+        self.config.extra.filter_script_strict = strict;
+        self
+    }
+
+    /// Set whether to record what each stage did to the nodes, returned as
+    /// [`SubconverterResult::explain`]
+    pub fn explain(&mut self, enabled: bool) -> &mut Self {
+        self.config.extra.explain = enabled.then(ExplainRecorder::default);
+        self
+    }
+
+    /// Set a hook run on the node list once preprocessing is done
+    pub fn node_transform(
+        &mut self,
+        transform: impl FnMut(&mut Vec<Proxy>) + Send + 'static,
+    ) -> &mut Self {
+        self.config.extra.node_transform = Some(Box::new(transform));
+        self
+    }
+
     /// Set whether update is strict
     pub fn update_strict(&mut self, strict: bool) -> &mut Self {
-        self.config.update_strict = strict;
+        self.config.update_strict = Some(strict);
         self
     }
 
@@ -392,6 +871,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set whether the managed config header of a Surge input is kept
+    pub fn keep_managed_config(&mut self, keep: bool) -> &mut Self {
+        self.config.keep_managed_config = keep;
+        self
+    }
+
     /// Set upload path
     pub fn upload_path(&mut self, path: Option<String>) -> &mut Self {
         self.config.upload_path = path;
@@ -410,6 +895,12 @@ impl SubconverterConfigBuilder {
         self
     }
 
+    /// Set the query string of the API request, without the `?`
+    pub fn request_query(&mut self, query: Option<String>) -> &mut Self {
+        self.config.request_query = query;
+        self
+    }
+
     /// Set authentication token
     pub fn token(&mut self, token: Option<String>) -> &mut Self {
         self.config.token = token;
@@ -504,12 +995,19 @@ impl SubconverterConfigBuilder {
         let config = self.config;
 
         // Basic validation
-        if config.urls.is_empty() && config.insert_urls.is_empty() {
+        if config.urls.is_empty() && config.insert_urls.is_empty() && config.extra_nodes.is_empty()
+        {
             return Err("No URLs provided".to_string());
         }
 
         Ok(config)
     }
+
+    /// Build the configuration without requiring any URL, for callers that
+    /// provide the nodes themselves
+    pub(crate) fn build_unchecked(&mut self) -> SubconverterConfig {
+        std::mem::take(self).config
+    }
 }
 
 /// Represents the status of the Gist upload operation
@@ -533,6 +1031,13 @@ pub struct SubconverterResult {
     pub headers: HashMap<String, String>,
     /// Status of the Gist upload
     pub upload_status: UploadStatus,
+    /// Time spent in each stage of the conversion
+    pub stats: ConvertStats,
+    /// Traffic and expiry reported by the first main subscription sending
+    /// them, also forwarded as the `Subscription-UserInfo` header
+    pub userinfo: Option<SubscriptionUserInfo>,
+    /// What each stage did to the nodes, when explain mode was on
+    pub explain: Option<ExplainReport>,
 }
 
 /// Options for parsing subscriptions
@@ -546,6 +1051,34 @@ pub struct ParseOptions {
 
     /// Whether the request is authorized
     pub authorized: bool,
+
+    /// Whether a malformed Clash proxy entry fails the subscription
+    pub strict: bool,
+
+    /// Whether the proxy groups of Clash inputs are collected
+    pub keep_groups: bool,
+
+    /// Whether nodes of a `tag:LABEL,url` input are prefixed with the label
+    pub prefix_labels: bool,
+
+    /// Where nodes dropped by the remark filters are recorded, in explain
+    /// mode
+    pub explain: Option<ExplainRecorder>,
+}
+
+/// Nodes and proxy groups parsed from one subscription
+#[derive(Debug, Clone, Default)]
+pub struct ParsedSubscription {
+    pub nodes: Vec<Proxy>,
+    /// Proxy groups of a Clash input, only collected with
+    /// [`ParseOptions::keep_groups`]
+    pub proxy_groups: ProxyGroupConfigs,
+    /// Fetch and parse times of the subscription
+    pub stats: ConvertStats,
+    /// Traffic and expiry the subscription reported, if any
+    pub userinfo: Option<SubscriptionUserInfo>,
+    /// `#!MANAGED-CONFIG` header of a Surge input, if any
+    pub managed_config: Option<ManagedConfig>,
 }
 
 impl Default for ParseOptions {
@@ -554,6 +1087,10 @@ impl Default for ParseOptions {
             include_remarks: Vec::new(),
             exclude_remarks: Vec::new(),
             authorized: false,
+            strict: false,
+            keep_groups: false,
+            prefix_labels: false,
+            explain: None,
         }
     }
 }
@@ -573,6 +1110,19 @@ pub async fn parse_subscription(
     group_id: i32,
     request_headers: &Option<HashMap<String, String>>,
 ) -> Result<Vec<Proxy>, String> {
+    parse_subscription_with_groups(url, options, group_id, request_headers)
+        .await
+        .map(|parsed| parsed.nodes)
+}
+
+/// Parse a subscription URL like [`parse_subscription`], also returning the
+/// proxy groups of a Clash input
+pub async fn parse_subscription_with_groups(
+    url: &str,
+    options: ParseOptions,
+    group_id: i32,
+    request_headers: &Option<HashMap<String, String>>,
+) -> Result<ParsedSubscription, String> {
     // Create a new parse settings instance
     let mut parse_settings = ParseSettings::default();
 
@@ -594,6 +1144,10 @@ pub async fn parse_subscription(
     }
 
     parse_settings.authorized = options.authorized;
+    parse_settings.strict = options.strict;
+    parse_settings.keep_groups = options.keep_groups;
+    parse_settings.prefix_labels = options.prefix_labels;
+    parse_settings.explain = options.explain.clone();
 
     // Create a vector to hold the nodes
     let mut nodes = Vec::new();
@@ -602,59 +1156,271 @@ pub async fn parse_subscription(
     // We use group_id = 0 since we don't care about it in this context
     add_nodes(url.to_string(), &mut nodes, group_id, &mut parse_settings).await?;
 
-    Ok(nodes)
+    Ok(ParsedSubscription {
+        nodes,
+        proxy_groups: parse_settings.proxy_groups,
+        stats: parse_settings.stats.snapshot(),
+        userinfo: parse_settings.userinfo,
+        managed_config: parse_settings.managed_config,
+    })
 }
 
-/// Process a subscription conversion request
-pub async fn subconverter(mut config: SubconverterConfig) -> Result<SubconverterResult, String> {
-    let mut response_headers = HashMap::new();
-    let mut nodes = Vec::new();
-    let global = Settings::current();
+/// Parse several subscriptions concurrently
+///
+/// At most `concurrency` subscriptions are fetched at once. Results come back
+/// in input order whatever order the fetches finish in, the n-th subscription
+/// getting `first_group_id + n` as group id.
+pub async fn parse_subscriptions(
+    urls: &[String],
+    options: &ParseOptions,
+    first_group_id: i32,
+    request_headers: &Option<HashMap<String, String>>,
+    concurrency: usize,
+) -> Vec<Result<Vec<Proxy>, String>> {
+    parse_subscriptions_with_groups(urls, options, first_group_id, request_headers, concurrency)
+        .await
+        .into_iter()
+        .map(|result| result.map(|parsed| parsed.nodes))
+        .collect()
+}
 
-    info!(
-        "Processing subscription conversion request to {}",
-        config.target.to_str()
-    );
+/// Parse several subscriptions concurrently like [`parse_subscriptions`],
+/// also returning the proxy groups of Clash inputs
+pub async fn parse_subscriptions_with_groups(
+    urls: &[String],
+    options: &ParseOptions,
+    first_group_id: i32,
+    request_headers: &Option<HashMap<String, String>>,
+    concurrency: usize,
+) -> Vec<Result<ParsedSubscription, String>> {
+    stream::iter(urls.iter().enumerate())
+        .map(|(index, url)| {
+            debug!("Parsing URL: {}", url);
+            parse_subscription_with_groups(
+                url,
+                options.clone(),
+                first_group_id + index as i32,
+                request_headers,
+            )
+        })
+        .buffered(fetch_limit(concurrency))
+        .collect()
+        .await
+}
 
-    // Parse subscription URLs
-    let opts = ParseOptions {
-        include_remarks: config.include_remarks.clone(),
-        exclude_remarks: config.exclude_remarks.clone(),
-        authorized: config.authorized,
-    };
+/// Number of subscriptions fetched at once for a requested `concurrency`,
+/// at least one and at most the server's `max_concur_threads`
+pub(crate) fn fetch_limit(concurrency: usize) -> usize {
+    let max_threads = Settings::snapshot().max_concur_threads.max(1) as usize;
+    concurrency.clamp(1, max_threads)
+}
 
-    // Parse insert URLs first if needed
-    let mut insert_nodes = Vec::new();
-    if !config.insert_urls.is_empty() {
-        let mut group_id = -1;
-        info!("Fetching node data from insert URLs");
-        for url in &config.insert_urls {
-            debug!("Parsing insert URL: {}", url);
-            match parse_subscription(url, opts.clone(), group_id, &config.request_headers).await {
-                Ok(mut parsed_nodes) => {
-                    info!("Found {} nodes from insert URL", parsed_nodes.len());
-                    insert_nodes.append(&mut parsed_nodes);
+/// Turn the proxy groups of Clash inputs into group configs for the output
+///
+/// Each group comes with the group id of its subscription. Members naming a
+/// group of the input or a built-in policy are kept verbatim, members naming
+/// a node follow it through renaming and match it by its final name, so nodes
+/// dropped at any later stage fall out of the group. Members naming nothing
+/// left are pruned.
+///
+/// `input_names` maps the group id and input name of each node to its id.
+pub fn resolve_input_groups(
+    groups: Vec<(i32, ProxyGroupConfig)>,
+    input_names: &HashMap<(i32, String), u32>,
+    nodes: &[Proxy],
+) -> ProxyGroupConfigs {
+    let current_names: HashMap<(i32, u32), &str> = nodes
+        .iter()
+        .map(|node| ((node.group_id, node.id), node.remark.as_str()))
+        .collect();
+    let group_names: HashSet<String> = groups.iter().map(|(_, g)| g.name.clone()).collect();
+
+    groups
+        .into_iter()
+        .map(|(group_id, mut group)| {
+            let members = std::mem::take(&mut group.proxies);
+            for member in members {
+                if group_names.contains(&member)
+                    || CLASH_BUILTIN_POLICIES.contains(&member.as_str())
+                {
+                    group.proxies.push(format!("[]{}", member));
+                    continue;
                 }
-                Err(e) => {
-                    warn!("Failed to parse insert URL '{}': {}", url, e);
-                    if !global.skip_failed_links {
+                let node_name = input_names
+                    .get(&(group_id, member.clone()))
+                    .and_then(|id| current_names.get(&(group_id, *id)));
+                match node_name {
+                    Some(name) => group
+                        .proxies
+                        .push(format!("(?-i)^{}$", regex::escape(name))),
+                    None => debug!("Pruning '{}' from proxy group '{}'", member, group.name),
+                }
+            }
+            group
+        })
+        .collect()
+}
+
+/// Put the groups of the input ahead of the configured ones, a configured
+/// group sharing the name of an input group is dropped
+fn merge_input_groups(
+    mut input_groups: ProxyGroupConfigs,
+    configured: ProxyGroupConfigs,
+) -> ProxyGroupConfigs {
+    let names: HashSet<String> = input_groups.iter().map(|g| g.name.clone()).collect();
+    input_groups.extend(
+        configured
+            .into_iter()
+            .filter(|group| !names.contains(&group.name)),
+    );
+    input_groups
+}
+
+/// URL of this conversion again with `params` in place of the request's own
+///
+/// The request's other parameters are forwarded as they were, the token and
+/// the rename, emoji and filter options included, so the nodes fetched from
+/// the link carry the names the generated config refers to. Without a
+/// request query, as when converting through the library, the link names the
+/// source URLs and the token. Values in `params` must be URL-encoded.
+pub fn conversion_link(config: &SubconverterConfig, params: &[(&str, &str)]) -> String {
+    let mut query: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    match &config.request_query {
+        Some(request) => query.extend(
+            request
+                .split('&')
+                .filter(|param| {
+                    let key = param.split('=').next().unwrap_or(param);
+                    !key.is_empty() && !params.iter().any(|(name, _)| *name == key)
+                })
+                .map(str::to_string),
+        ),
+        None => {
+            query.push(format!("url={}", url_encode(&config.urls.join("|"))));
+            if let Some(token) = config.token.as_deref().filter(|t| !t.is_empty()) {
+                query.push(format!("token={}", url_encode(token)));
+            }
+        }
+    }
+    format!("{}sub?{}", config.managed_config_prefix, query.join("&"))
+}
+
+/// `#!MANAGED-CONFIG` header of a generated Surge or Surfboard profile,
+/// pointing at this conversion again
+fn managed_header(config: &SubconverterConfig, params: &[(&str, &str)]) -> ManagedConfig {
+    let global = Settings::snapshot();
+    ManagedConfig {
+        url: conversion_link(config, params),
+        interval: Some(config.update_interval.unwrap_or(global.update_interval)),
+        strict: Some(config.update_strict.unwrap_or(global.update_strict)),
+    }
+}
+
+/// Put `extra` ahead of or after `nodes`
+pub(crate) fn merge_extra_nodes(nodes: &mut Vec<Proxy>, mut extra: Vec<Proxy>, prepend: bool) {
+    info!(
+        "{} {} extra nodes to {} fetched nodes",
+        if prepend { "Prepending" } else { "Appending" },
+        extra.len(),
+        nodes.len()
+    );
+    if prepend {
+        extra.append(nodes);
+        *nodes = extra;
+    } else {
+        nodes.append(&mut extra);
+    }
+}
+
+/// Process a subscription conversion request
+pub async fn subconverter(mut config: SubconverterConfig) -> Result<SubconverterResult, String> {
+    let stopwatch = Stopwatch::start();
+    let mut response_headers = HashMap::new();
+    let mut nodes = Vec::new();
+    let global = Settings::snapshot();
+
+    info!(
+        "Processing subscription conversion request to {}",
+        config.target.to_str()
+    );
+
+    // Parse subscription URLs
+    let opts = ParseOptions {
+        include_remarks: config.include_remarks.clone(),
+        exclude_remarks: config.exclude_remarks.clone(),
+        authorized: config.authorized,
+        strict: config.extra.strict_parse,
+        keep_groups: config.extra.keep_input_groups,
+        prefix_labels: config.extra.prefix_labels,
+        explain: config.extra.explain.clone(),
+    };
+
+    // The remark patterns of the extra settings then narrow what the request
+    // patterns kept, compiled up front so an invalid one fails before fetching
+    let extra_filter =
+        RemarkFilter::new(&config.extra.include_remarks, &config.extra.exclude_remarks)?;
+
+    // Parse insert URLs first if needed
+    let mut insert_nodes = Vec::new();
+    if !config.insert_urls.is_empty() {
+        info!("Fetching node data from insert URLs");
+        let results = parse_subscriptions_with_groups(
+            &config.insert_urls,
+            &opts,
+            -1,
+            &config.request_headers,
+            config.extra.fetch_concurrency,
+        )
+        .await;
+        for (url, result) in config.insert_urls.iter().zip(results) {
+            match result {
+                Ok(mut parsed) => {
+                    extra_filter.apply(&mut parsed.nodes, config.extra.explain.as_ref());
+                    info!("Found {} nodes from insert URL", parsed.nodes.len());
+                    insert_nodes.append(&mut parsed.nodes);
+                    config.extra.stats.merge(&parsed.stats);
+                }
+                Err(e) => {
+                    warn!("Failed to parse insert URL '{}': {}", url, e);
+                    if !global.skip_failed_links {
                         return Err(format!("Failed to parse insert URL '{}': {}", url, e));
                     }
                 }
             }
-            group_id += 1;
         }
     }
 
-    let mut group_id = 0;
     // Parse main URLs
     info!("Fetching node data from main URLs");
-    for url in &config.urls {
-        debug!("Parsing URL: {}", url);
-        match parse_subscription(url, opts.clone(), group_id, &config.request_headers).await {
-            Ok(mut parsed_nodes) => {
-                info!("Found {} nodes from URL", parsed_nodes.len());
-                nodes.append(&mut parsed_nodes);
+    let results = parse_subscriptions_with_groups(
+        &config.urls,
+        &opts,
+        0,
+        &config.request_headers,
+        config.extra.fetch_concurrency,
+    )
+    .await;
+    let mut input_groups = Vec::new();
+    let mut userinfo = None;
+    let mut managed_config = None;
+    for (index, (url, result)) in config.urls.iter().zip(results).enumerate() {
+        match result {
+            Ok(mut parsed) => {
+                extra_filter.apply(&mut parsed.nodes, config.extra.explain.as_ref());
+                info!("Found {} nodes from URL", parsed.nodes.len());
+                nodes.append(&mut parsed.nodes);
+                config.extra.stats.merge(&parsed.stats);
+                userinfo = userinfo.or(parsed.userinfo);
+                managed_config = managed_config.or(parsed.managed_config);
+                input_groups.extend(
+                    parsed
+                        .proxy_groups
+                        .into_iter()
+                        .map(|group| (index as i32, group)),
+                );
             }
             Err(e) => {
                 error!("Failed to parse URL '{}': {}", url, e);
@@ -663,14 +1429,24 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 }
             }
         }
-        group_id += 1;
     }
 
     // Exit if found nothing
-    if nodes.is_empty() && insert_nodes.is_empty() {
+    if nodes.is_empty() && insert_nodes.is_empty() && config.extra_nodes.is_empty() {
         return Err("No nodes were found!".to_string());
     }
 
+    // Input groups name nodes as the input did, remember which node each
+    // name stood for so renamed nodes can still be found
+    let mut input_names: HashMap<(i32, String), u32> = HashMap::new();
+    if !input_groups.is_empty() {
+        for node in &nodes {
+            input_names
+                .entry((node.group_id, node.remark.clone()))
+                .or_insert(node.id);
+        }
+    }
+
     // Merge insert nodes and main nodes
     if config.prepend_insert {
         // Prepend insert nodes
@@ -691,6 +1467,42 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
         );
         nodes.append(&mut insert_nodes);
     }
+    let mut extra_nodes = std::mem::take(&mut config.extra_nodes);
+    let extra_nodes = if config.process_extra_nodes {
+        // Remark patterns apply as if the nodes were parsed
+        filter_nodes_by_remark(
+            &mut extra_nodes,
+            &opts.include_remarks,
+            &opts.exclude_remarks,
+            config.extra.explain.as_ref(),
+        )?;
+        extra_filter.apply(&mut extra_nodes, config.extra.explain.as_ref());
+        merge_extra_nodes(&mut nodes, extra_nodes, config.prepend_extra_nodes);
+        Vec::new()
+    } else {
+        extra_nodes
+    };
+
+    // Give every node a unique name before filters and rename rules see them
+    if config.extra.normalize_names {
+        let renamed = normalize_node_names(&mut nodes);
+        if renamed > 0 {
+            info!("Fixed the name of {} unnamed or duplicate node(s)", renamed);
+        }
+    }
+
+    ParseLimits::from_settings(&global)
+        .check_node_count(nodes.len())
+        .map_err(|e| e.to_string())?;
+
+    // Drop excluded protocols before the filter script sees them
+    let filter_timer = config.extra.stats.time(ConvertStage::Filter);
+    let tracker = DropTracker::start(config.extra.explain.as_ref(), &nodes);
+    let excluded = filter_nodes_by_type(&mut nodes, &config.extra.exclude_types);
+    tracker.finish(DropStage::ExcludeType, &nodes, excluded_type_reason);
+    if excluded > 0 {
+        info!("Removed {} node(s) of excluded types", excluded);
+    }
 
     // Apply group name if specified
     if let Some(group_name) = &config.group_name {
@@ -702,60 +1514,359 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
 
     // Apply filter script if available
     if global.enable_filter && config.extra.authorized {
-        if let Some(_script) = &config.filter_script {
-            if !_script.is_empty() {
-                info!("Applying filter script");
-                if _script.starts_with("path:") {
-                    let import_script = file_get_async(&_script[5..], None)
-                        .await
-                        .map_err(|e| e.to_string())?;
-                    config
-                        .extra
-                        .eval_filter_function(&mut nodes, &import_script)
-                        .map_err(|e| e.to_string())?;
-                } else {
-                    config
-                        .extra
-                        .eval_filter_function(&mut nodes, &_script)
-                        .map_err(|e| e.to_string())?;
-                }
-                info!("Filter script applied successfully");
+        let scripts = load_filter_scripts(&config).await?;
+        if !scripts.is_empty() {
+            info!("Applying {} filter script(s)", scripts.len());
+            let tracker = DropTracker::start(config.extra.explain.as_ref(), &nodes);
+            let function_name = config
+                .filter_function
+                .clone()
+                .unwrap_or_else(|| DEFAULT_FILTER_FUNCTION.to_string());
+            let summary = config
+                .extra
+                .eval_filter_chain(&mut nodes, &scripts, &function_name)
+                .map_err(|e| e.to_string())?;
+            tracker.finish(DropStage::FilterScript, &nodes, |_| {
+                format!("rejected by filter function '{}'", function_name)
+            });
+            if summary.failed > 0 {
+                warn!(
+                    "Filter script failed on {} node(s), they were kept",
+                    summary.failed
+                );
             }
+            info!("Filter script applied successfully");
         }
     }
+    drop(filter_timer);
 
-    // Process nodes (rename, emoji, sort, etc.)
-    preprocess_nodes(&mut nodes, &mut config.extra)
+    // Process nodes (rename, emoji, sort, etc.), the remark patterns
+    // already applied
+    preprocess_parsed_nodes(&mut nodes, &mut config.extra)
         .await
         .map_err(|e| e.to_string())?;
+    if !extra_nodes.is_empty() {
+        merge_extra_nodes(&mut nodes, extra_nodes, config.prepend_extra_nodes);
+    }
+
+    // Before the groups of the input join, they keep their type
+    if let Some(group_type) = &config.extra.group_type_override {
+        let rewritten = group_type.apply(&mut config.proxy_groups);
+        info!(
+            "Rewrote {} proxy group(s) from {} to {}",
+            rewritten,
+            group_type.from.as_str(),
+            group_type.to.as_str()
+        );
+    }
+    if !input_groups.is_empty() {
+        let groups = resolve_input_groups(input_groups, &input_names, &nodes);
+        info!("Keeping {} proxy group(s) of the input", groups.len());
+        config.proxy_groups = merge_input_groups(groups, std::mem::take(&mut config.proxy_groups));
+    }
+    for group in &mut config.proxy_groups {
+        group.apply_health_check(&config.extra.group_health_check);
+    }
+    // Rules of a dropped group follow its nodes
+    for (dropped, target) in fold_small_groups(
+        &mut config.proxy_groups,
+        &nodes,
+        config.extra.min_group_size,
+        &config.extra,
+    ) {
+        for ruleset in &mut config.ruleset_configs {
+            if ruleset.group == dropped {
+                ruleset.group = target.clone();
+            }
+        }
+    }
 
-    // Pass subscription info if provided
-    if let Some(sub_info) = &config.sub_info {
-        response_headers.insert("Subscription-UserInfo".to_string(), sub_info.clone());
+    // Pass subscription info if provided, else what the subscriptions sent
+    if let Some(sub_info) = config
+        .sub_info
+        .clone()
+        .or_else(|| userinfo.map(|info| info.to_string()))
+    {
+        response_headers.insert("Subscription-UserInfo".to_string(), sub_info);
     }
 
     // Refresh rulesets if needed
+    let mut ruleset_content =
+        load_ruleset_content(&config, &nodes, global.prepend_proxy_direct_ruleset).await;
+
+    // Point the Clash provider at the node list of this conversion
+    if config.extra.clash_use_providers
+        && config.extra.clash_provider_url.is_empty()
+        && !config.managed_config_prefix.is_empty()
+    {
+        config.extra.clash_provider_url =
+            conversion_link(&config, &[("target", "clash"), ("list", "true")]);
+    }
+
+    // Load the dns section injected into Clash configs
+    if matches!(
+        config.target,
+        SubconverterTarget::Clash | SubconverterTarget::ClashR
+    ) && config.extra.clash_dns.is_empty()
+        && !global.clash_dns.is_empty()
+    {
+        match load_content_async(&global.clash_dns).await {
+            Ok(content) => config.extra.clash_dns = content,
+            Err(e) => warn!(
+                "Failed to load Clash dns section {}: {}",
+                global.clash_dns, e
+            ),
+        }
+    }
+
+    // Point the QuantumultX server remote at the node list of this conversion
+    if config.extra.quanx_server_remote
+        && config.extra.quanx_server_remote_url.is_empty()
+        && !config.managed_config_prefix.is_empty()
+    {
+        config.extra.quanx_server_remote_url =
+            conversion_link(&config, &[("target", "quanx"), ("list", "true")]);
+    }
+
+    // Generate output based on target
+    let emit_timer = config.extra.stats.time(ConvertStage::Emit);
+    if config.extra.random_fingerprint {
+        let seed = config.extra.fingerprint_seed.unwrap_or_else(|| {
+            if config.extra.deterministic {
+                0
+            } else {
+                safe_system_time()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or_default()
+            }
+        });
+        let assigned = assign_random_fingerprints(&mut nodes, &config.extra.fingerprint_pool, seed);
+        info!(
+            "Assigned a random client fingerprint to {} node(s)",
+            assigned
+        );
+    }
+    let mut output_content = generate_output(&mut config, &mut nodes, &mut ruleset_content).await;
+    drop(emit_timer);
+    if let Some(e) = config.extra.unsupported_error() {
+        return Err(e);
+    }
+    if config.extra.validate_output {
+        if let Err(e) = validate_output(&config.target, &output_content) {
+            error!(
+                "Generated {} output is invalid: {}",
+                config.target.to_str(),
+                e
+            );
+            return Err(format!("Generated output is invalid: {}", e));
+        }
+    }
+
+    // Repeat the managed config header of a Surge input, or keep its update
+    // policy in the header regenerated for this conversion unless the
+    // conversion sets its own
+    if let Some(managed_config) = managed_config.filter(|_| {
+        matches!(
+            config.target,
+            SubconverterTarget::Surge(_) | SubconverterTarget::Surfboard
+        )
+    }) {
+        if config.keep_managed_config {
+            output_content = set_managed_config(&output_content, &managed_config);
+        } else if let Some(mut header) = ManagedConfig::parse(&output_content) {
+            // An interval or strict given for the conversion wins
+            if config.update_interval.is_none() {
+                header.interval = managed_config.interval.or(header.interval);
+            }
+            if config.update_strict.is_none() {
+                header.strict = managed_config.strict.or(header.strict);
+            }
+            output_content = set_managed_config(&output_content, &header);
+        }
+    }
+
+    // Prepend the header comment to formats that have comments
+    if let (Some(template), Some(prefix)) = (
+        &config.extra.output_header,
+        header_comment_prefix(&config.target),
+    ) {
+        let info = HeaderInfo {
+            // The time would make identical input give different output
+            time: (!config.extra.deterministic).then(safe_unix_timestamp),
+            subscriptions: config.urls.len() + config.insert_urls.len(),
+            nodes: nodes.len(),
+            userinfo,
+        };
+        let header = render_output_header(template, &info, prefix);
+        output_content = prepend_output_header(&output_content, &header);
+    }
+
+    // Set filename header if provided
+    if let Some(filename) = &config.filename {
+        response_headers.insert(
+            "Content-Disposition".to_string(),
+            format!("attachment; filename=\"{}\"; filename*=utf-8''", filename),
+        );
+    }
+
+    let mut upload_status: UploadStatus = UploadStatus::NotAttempted;
+
+    // Upload result if needed
+    if config.upload {
+        // Determine arguments for upload_gist based on C++ logic
+        let (gist_name, write_manage_url) = match &config.target {
+            SubconverterTarget::Clash => ("clash".to_string(), false),
+            SubconverterTarget::ClashR => ("clashr".to_string(), false),
+            SubconverterTarget::Surge(ver) => {
+                let name = format!("surge{}", ver);
+                if config.extra.nodelist {
+                    (format!("{}list", name), true)
+                } else {
+                    (name, true)
+                }
+            }
+            SubconverterTarget::Surfboard => ("surfboard".to_string(), !config.extra.nodelist), /* Only true for config, not list */
+            SubconverterTarget::Mellow => ("mellow".to_string(), !config.extra.nodelist), /* Only true for config, not list */
+            SubconverterTarget::SSSub => ("sssub".to_string(), false),
+            SubconverterTarget::SS => ("ss".to_string(), false),
+            SubconverterTarget::SSR => ("ssr".to_string(), false),
+            SubconverterTarget::V2Ray => ("v2ray".to_string(), false),
+            SubconverterTarget::Trojan => ("trojan".to_string(), false),
+            SubconverterTarget::Mixed => ("sub".to_string(), false), // Corresponds to "sub" in C++
+            SubconverterTarget::Quantumult => ("quan".to_string(), false),
+            SubconverterTarget::QuantumultX => ("quanx".to_string(), false),
+            SubconverterTarget::Loon => ("loon".to_string(), false),
+            SubconverterTarget::SSD => ("ssd".to_string(), false),
+            SubconverterTarget::SingBox => ("singbox".to_string(), false),
+            SubconverterTarget::NodeJson => ("json".to_string(), false),
+            SubconverterTarget::V2RayJson => ("v2rayjson".to_string(), false),
+            SubconverterTarget::Auto => ("clash".to_string(), false), /* Defaulting to clash like
+                                                                       * the main logic */
+        };
+
+        // Use filename as path if provided, otherwise use the derived gist_name
+        let gist_path = config.filename.clone().unwrap_or_else(|| gist_name.clone());
+
+        info!(
+            "Attempting to upload result to Gist: name='{}', path='{}', write_manage_url={}",
+            gist_name, gist_path, write_manage_url
+        );
+
+        match crate::upload::gist::upload_gist(
+            &gist_name,
+            gist_path,
+            output_content.clone(), // Clone content for upload
+            write_manage_url,
+        )
+        .await
+        {
+            Ok(url) => {
+                info!("Successfully uploaded result to Gist: {}", url);
+                upload_status = UploadStatus::Success(url);
+            }
+            Err(e) => {
+                warn!("Failed to upload result to Gist: {}", e);
+                upload_status = UploadStatus::Failure(e);
+            }
+        }
+    }
+
+    let mut stats = config.extra.stats.snapshot();
+    stats.total = stopwatch.elapsed();
+    info!("Conversion completed");
+    debug!(
+        "Conversion took {:?}: fetch {:?}, parse {:?}, filter {:?}, rename {:?}, sort {:?}, emit {:?}, script {:?}",
+        stats.total,
+        stats.fetch,
+        stats.parse,
+        stats.filter,
+        stats.rename,
+        stats.sort,
+        stats.emit,
+        stats.script
+    );
+    Ok(SubconverterResult {
+        content: output_content,
+        headers: response_headers,
+        upload_status: upload_status,
+        stats,
+        userinfo,
+        explain: config
+            .extra
+            .explain
+            .as_ref()
+            .map(|explain| explain.snapshot()),
+    })
+}
+
+/// Sources of the filter scripts of `config` in the order they run, with
+/// `path:` scripts read
+pub(crate) async fn load_filter_scripts(
+    config: &SubconverterConfig,
+) -> Result<Vec<String>, String> {
+    let mut scripts = Vec::new();
+    for script in config.filter_script.iter().chain(&config.filter_scripts) {
+        if script.is_empty() {
+            continue;
+        }
+        scripts.push(match script.strip_prefix("path:") {
+            Some(path) => file_get_async(path, None)
+                .await
+                .map_err(|e| e.to_string())?,
+            None => script.clone(),
+        });
+    }
+    Ok(scripts)
+}
+
+/// Fetch the rulesets of a conversion, if the rule generator is enabled
+///
+/// The node JSON target has no rules, nothing is fetched for it.
+pub(crate) async fn load_ruleset_content(
+    config: &SubconverterConfig,
+    nodes: &[Proxy],
+    prepend_proxy_direct: bool,
+) -> Vec<RulesetContent> {
     let mut ruleset_content = Vec::new();
-    if config.extra.enable_rule_generator {
-        // TODO: Check if we're using custom rulesets or global rulesets
-        // if config.ruleset_configs == global.custom_rulesets {
-        //     refresh_rulesets(&config.ruleset_configs, &mut
-        // global.rulesets_content).await;     debug!("Using global ruleset
-        // content");     // Use global ruleset content if it's the same
-        // configuration     ruleset_content = global.rulesets_content.clone();
-
-        // Refresh rulesets with custom configuration
+    if config.extra.enable_rule_generator
+        && !matches!(
+            config.target,
+            SubconverterTarget::NodeJson | SubconverterTarget::V2RayJson
+        )
+    {
         info!("Refreshing rulesets with custom configuration");
         refresh_rulesets(&config.ruleset_configs, &mut ruleset_content).await;
 
+        // The blocklist goes ahead of the rulesets, behind the proxy direct
+        // rules
+        if matches!(
+            config.target,
+            SubconverterTarget::Clash | SubconverterTarget::ClashR | SubconverterTarget::SingBox
+        ) {
+            if let Some(block) = block_ruleset(&config.extra.block_rules) {
+                ruleset_content.insert(0, block);
+            }
+        }
+
         // Prepend proxy direct ruleset if needed
-        if global.prepend_proxy_direct_ruleset {
-            prepend_proxy_direct_ruleset(&mut ruleset_content, &nodes);
+        if prepend_proxy_direct {
+            prepend_proxy_direct_ruleset(&mut ruleset_content, nodes);
         }
     }
+    ruleset_content
+}
 
-    // Generate output based on target
-    let output_content = match &config.target {
+/// Render processed nodes and rulesets into the configured target format
+pub(crate) async fn generate_output(
+    config: &mut SubconverterConfig,
+    nodes: &mut Vec<Proxy>,
+    ruleset_content: &mut Vec<RulesetContent>,
+) -> String {
+    if config.extra.collapse_groups {
+        config.proxy_groups = collapse_duplicate_groups(std::mem::take(&mut config.proxy_groups));
+    }
+    match &config.target {
         SubconverterTarget::Clash => {
             info!("Generate target: Clash");
             let base = config
@@ -763,9 +1874,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&SubconverterTarget::Clash, config.template_args.as_ref())
                 .await;
             proxy_to_clash(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 false,
                 &mut config.extra,
@@ -778,9 +1889,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&SubconverterTarget::ClashR, config.template_args.as_ref())
                 .await;
             proxy_to_clash(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 true,
                 &mut config.extra,
@@ -793,9 +1904,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             let output = proxy_to_surge(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 *ver,
                 &mut config.extra,
@@ -804,25 +1915,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
 
             // Add managed configuration header if needed
             if !config.managed_config_prefix.is_empty() && config.extra.enable_rule_generator {
-                let managed_url = format!(
-                    "{}sub?target=surge&ver={}&url={}",
-                    config.managed_config_prefix,
-                    ver,
-                    // URL would need to be properly encoded
-                    config.urls.join("|")
-                );
-
-                format!(
-                    "#!MANAGED-CONFIG {} interval={} strict={}\n\n{}",
-                    managed_url,
-                    config.update_interval,
-                    if config.update_strict {
-                        "true"
-                    } else {
-                        "false"
-                    },
-                    output
-                )
+                let ver = ver.to_string();
+                let header = managed_header(config, &[("target", "surge"), ("ver", &ver)]);
+                set_managed_config(&output, &header)
             } else {
                 output
             }
@@ -834,9 +1929,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             let output = proxy_to_surge(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 -3, // Special version for Surfboard
                 &mut config.extra,
@@ -845,24 +1940,8 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
 
             // Add managed configuration header if needed
             if !config.managed_config_prefix.is_empty() && config.extra.enable_rule_generator {
-                let managed_url = format!(
-                    "{}sub?target=surfboard&url={}",
-                    config.managed_config_prefix,
-                    // URL would need to be properly encoded
-                    config.urls.join("|")
-                );
-
-                format!(
-                    "#!MANAGED-CONFIG {} interval={} strict={}\n\n{}",
-                    managed_url,
-                    config.update_interval,
-                    if config.update_strict {
-                        "true"
-                    } else {
-                        "false"
-                    },
-                    output
-                )
+                let header = managed_header(config, &[("target", "surfboard")]);
+                set_managed_config(&output, &header)
             } else {
                 output
             }
@@ -874,9 +1953,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             proxy_to_mellow(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 &mut config.extra,
             )
@@ -888,31 +1967,31 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .rule_bases
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
-            proxy_to_ss_sub(&base, &mut nodes, &mut config.extra)
+            proxy_to_ss_sub(&base, nodes, &mut config.extra)
         }
         SubconverterTarget::SS => {
             info!("Generate target: SS");
-            proxy_to_single(&mut nodes, ProxyUriTypes::SS, &mut config.extra)
+            proxy_to_single(nodes, ProxyUriTypes::SS, &mut config.extra)
         }
         SubconverterTarget::SSR => {
             info!("Generate target: SSR");
             proxy_to_single(
-                &mut nodes,
+                nodes,
                 ProxyUriTypes::SSR | ProxyUriTypes::SS,
                 &mut config.extra,
             )
         }
         SubconverterTarget::V2Ray => {
             info!("Generate target: V2Ray");
-            proxy_to_single(&mut nodes, ProxyUriTypes::VMESS, &mut config.extra)
+            proxy_to_single(nodes, ProxyUriTypes::VMESS, &mut config.extra)
         }
         SubconverterTarget::Trojan => {
             info!("Generate target: Trojan");
-            proxy_to_single(&mut nodes, ProxyUriTypes::TROJAN, &mut config.extra)
+            proxy_to_single(nodes, ProxyUriTypes::TROJAN, &mut config.extra)
         }
         SubconverterTarget::Mixed => {
             info!("Generate target: Mixed");
-            proxy_to_single(&mut nodes, ProxyUriTypes::MIXED, &mut config.extra)
+            proxy_to_single(nodes, ProxyUriTypes::MIXED, &mut config.extra)
         }
         SubconverterTarget::Quantumult => {
             info!("Generate target: Quantumult");
@@ -921,9 +2000,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             proxy_to_quan(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 &mut config.extra,
             )
@@ -936,9 +2015,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             proxy_to_quanx(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 &mut config.extra,
             )
@@ -951,9 +2030,9 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             proxy_to_loon(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 &mut config.extra,
             )
@@ -962,7 +2041,7 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
         SubconverterTarget::SSD => {
             info!("Generate target: SSD");
             proxy_to_ssd(
-                &mut nodes,
+                nodes,
                 &config.group_name.as_deref().unwrap_or(""),
                 &config.sub_info.as_deref().unwrap_or(""),
                 &mut config.extra,
@@ -975,13 +2054,21 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&config.target, config.template_args.as_ref())
                 .await;
             proxy_to_singbox(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 &mut config.extra,
             )
         }
+        SubconverterTarget::NodeJson => {
+            info!("Generate target: Node JSON");
+            proxy_to_node_json(nodes, &config.extra)
+        }
+        SubconverterTarget::V2RayJson => {
+            info!("Generate target: V2Ray outbound JSON");
+            proxy_to_v2ray_json(nodes, &config.extra)
+        }
         SubconverterTarget::Auto => {
             // When target is Auto, we should have decided on a specific target earlier
             // based on user agent If we still have Auto at this point, default
@@ -992,90 +2079,15 @@ pub async fn subconverter(mut config: SubconverterConfig) -> Result<Subconverter
                 .get_base_content(&SubconverterTarget::Clash, config.template_args.as_ref())
                 .await;
             proxy_to_clash(
-                &mut nodes,
+                nodes,
                 &base,
-                &mut ruleset_content,
+                ruleset_content,
                 &config.proxy_groups,
                 false,
                 &mut config.extra,
             )
         }
-    };
-
-    // Set filename header if provided
-    if let Some(filename) = &config.filename {
-        response_headers.insert(
-            "Content-Disposition".to_string(),
-            format!("attachment; filename=\"{}\"; filename*=utf-8''", filename),
-        );
     }
-
-    let mut upload_status: UploadStatus = UploadStatus::NotAttempted;
-
-    // Upload result if needed
-    if config.upload {
-        // Determine arguments for upload_gist based on C++ logic
-        let (gist_name, write_manage_url) = match &config.target {
-            SubconverterTarget::Clash => ("clash".to_string(), false),
-            SubconverterTarget::ClashR => ("clashr".to_string(), false),
-            SubconverterTarget::Surge(ver) => {
-                let name = format!("surge{}", ver);
-                if config.extra.nodelist {
-                    (format!("{}list", name), true)
-                } else {
-                    (name, true)
-                }
-            }
-            SubconverterTarget::Surfboard => ("surfboard".to_string(), !config.extra.nodelist), /* Only true for config, not list */
-            SubconverterTarget::Mellow => ("mellow".to_string(), !config.extra.nodelist), /* Only true for config, not list */
-            SubconverterTarget::SSSub => ("sssub".to_string(), false),
-            SubconverterTarget::SS => ("ss".to_string(), false),
-            SubconverterTarget::SSR => ("ssr".to_string(), false),
-            SubconverterTarget::V2Ray => ("v2ray".to_string(), false),
-            SubconverterTarget::Trojan => ("trojan".to_string(), false),
-            SubconverterTarget::Mixed => ("sub".to_string(), false), // Corresponds to "sub" in C++
-            SubconverterTarget::Quantumult => ("quan".to_string(), false),
-            SubconverterTarget::QuantumultX => ("quanx".to_string(), false),
-            SubconverterTarget::Loon => ("loon".to_string(), false),
-            SubconverterTarget::SSD => ("ssd".to_string(), false),
-            SubconverterTarget::SingBox => ("singbox".to_string(), false),
-            SubconverterTarget::Auto => ("clash".to_string(), false), /* Defaulting to clash like
-                                                                       * the main logic */
-        };
-
-        // Use filename as path if provided, otherwise use the derived gist_name
-        let gist_path = config.filename.clone().unwrap_or_else(|| gist_name.clone());
-
-        info!(
-            "Attempting to upload result to Gist: name='{}', path='{}', write_manage_url={}",
-            gist_name, gist_path, write_manage_url
-        );
-
-        match crate::upload::gist::upload_gist(
-            &gist_name,
-            gist_path,
-            output_content.clone(), // Clone content for upload
-            write_manage_url,
-        )
-        .await
-        {
-            Ok(url) => {
-                info!("Successfully uploaded result to Gist: {}", url);
-                upload_status = UploadStatus::Success(url);
-            }
-            Err(e) => {
-                warn!("Failed to upload result to Gist: {}", e);
-                upload_status = UploadStatus::Failure(e);
-            }
-        }
-    }
-
-    info!("Conversion completed");
-    Ok(SubconverterResult {
-        content: output_content,
-        headers: response_headers,
-        upload_status: upload_status,
-    })
 }
 
 /// Preprocess nodes before conversion
@@ -1119,7 +2131,7 @@ impl RuleBases {
     pub async fn load_content(&self) -> HashMap<SubconverterTarget, String> {
         let mut base_content = HashMap::new();
 
-        let global = Settings::current();
+        let global = Settings::snapshot();
         let proxy_config = parse_proxy(&global.proxy_config);
 
         // Helper function to load content from file or URL
@@ -1208,7 +2220,7 @@ impl RuleBases {
         target: &SubconverterTarget,
         template_args: Option<&TemplateArgs>,
     ) -> String {
-        let global = Settings::current();
+        let global = Settings::snapshot();
         let proxy_config = parse_proxy(&global.proxy_config);
 
         // Helper function to load content from file or URL
@@ -1367,3 +2379,504 @@ impl RuleBases {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_subscriptions_keeps_input_order() {
+        let urls: Vec<String> = ["HK", "JP", "broken", "US", "SG"]
+            .iter()
+            .map(|name| match *name {
+                "broken" => "not a link".to_string(),
+                name => format!("trojan://secret@{}.example.com:443#{}", name, name),
+            })
+            .collect();
+
+        let results = parse_subscriptions(&urls, &ParseOptions::default(), 0, &None, 2).await;
+        assert_eq!(results.len(), urls.len());
+        assert!(results[2].is_err());
+
+        let parsed: Vec<(String, i32)> = results
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|node| (node.remark, node.group_id))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("HK".to_string(), 0),
+                ("JP".to_string(), 1),
+                ("US".to_string(), 3),
+                ("SG".to_string(), 4)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extra_nodes() {
+        let convert = |prepend: bool, process: bool| async move {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Clash)
+                .add_url("trojan://secret@hk.example.com:443#HK%2001")
+                .add_extra_node_link("trojan://secret@relay.example.com:443#Relay")
+                .add_extra_node_link("not a link")
+                .prepend_extra_nodes(prepend)
+                .process_extra_nodes(process)
+                .extra(ExtraSettings {
+                    nodelist: true,
+                    exclude_remarks: vec!["Relay".to_string()],
+                    ..Default::default()
+                });
+            subconverter(builder.build().unwrap())
+                .await
+                .unwrap()
+                .content
+        };
+
+        // Processed extra nodes are filtered like fetched ones
+        let output = convert(false, true).await;
+        assert!(output.contains("HK 01") && !output.contains("Relay"));
+
+        let output = convert(true, false).await;
+        let relay = output.find("Relay").unwrap();
+        assert!(relay < output.find("HK 01").unwrap());
+        let output = convert(false, false).await;
+        assert!(output.find("Relay").unwrap() > output.find("HK 01").unwrap());
+
+        let mut builder = SubconverterConfigBuilder::new();
+        builder.extra_nodes(vec![Proxy::default()]);
+        assert!(builder.build().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_random_fingerprint_is_meta_only() {
+        let convert = |target: SubconverterTarget, clash_core: ClashCore| async move {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(target)
+                .add_url("trojan://secret@hk.example.com:443#HK%2001")
+                .extra(ExtraSettings {
+                    nodelist: true,
+                    clash_core,
+                    ..Default::default()
+                })
+                .random_fingerprint(true)
+                .fingerprint_seed(Some(1));
+            subconverter(builder.build().unwrap())
+                .await
+                .unwrap()
+                .content
+        };
+
+        let output = convert(SubconverterTarget::Clash, ClashCore::Meta).await;
+        assert!(output.contains("client-fingerprint"));
+        let output = convert(SubconverterTarget::ClashR, ClashCore::Meta).await;
+        assert!(!output.contains("client-fingerprint"));
+        let output = convert(SubconverterTarget::Clash, ClashCore::Vanilla).await;
+        assert!(!output.contains("client-fingerprint"));
+    }
+
+    #[tokio::test]
+    async fn test_output_header() {
+        let convert = |target: SubconverterTarget| async move {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(target)
+                .add_url("trojan://secret@hk.example.com:443#HK%2001")
+                .extra(ExtraSettings {
+                    nodelist: true,
+                    deterministic: true,
+                    ..Default::default()
+                })
+                .output_header(Some("nodes: {nodes}\ngenerated at {time}".to_string()));
+            subconverter(builder.build().unwrap())
+                .await
+                .unwrap()
+                .content
+        };
+
+        let clash = convert(SubconverterTarget::Clash).await;
+        assert!(clash.starts_with("# nodes: 1\n"));
+        assert!(!clash.contains("generated at"));
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&clash).unwrap();
+        assert_eq!(yaml["proxies"][0]["name"], "HK 01");
+
+        let singbox = convert(SubconverterTarget::SingBox).await;
+        assert!(!singbox.contains("nodes: 1"));
+    }
+
+    #[tokio::test]
+    async fn test_prefix_labels() {
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url("tag:Alpha,trojan://secret@hk.example.com:443#HK%2001")
+            .add_url("tag:,trojan://secret@jp.example.com:443#JP%2001")
+            .extra(ExtraSettings {
+                nodelist: true,
+                // Rename rules see the prefixed names
+                rename_array: vec![RegexMatchConfig::new(
+                    r"^\[Alpha\] ".to_string(),
+                    "A/".to_string(),
+                    String::new(),
+                )],
+                ..Default::default()
+            })
+            .prefix_labels(true);
+        let output = subconverter(builder.build().unwrap())
+            .await
+            .unwrap()
+            .content;
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(yaml["proxies"][0]["name"], "A/HK 01");
+        // An empty label changes nothing
+        assert_eq!(yaml["proxies"][1]["name"], "JP 01");
+
+        let options = ParseOptions {
+            prefix_labels: true,
+            ..Default::default()
+        };
+        let nodes = parse_subscription(
+            "tag:Alpha,trojan://secret@hk.example.com:443#HK%2001",
+            options,
+            0,
+            &None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(nodes[0].remark, "[Alpha] HK 01");
+        assert_eq!(nodes[0].group, "Alpha");
+    }
+
+    #[tokio::test]
+    async fn test_validate_output() {
+        use crate::models::ProxyGroupType;
+
+        let groups = |name: &str| {
+            let mut group = ProxyGroupConfig::new(name.to_string(), ProxyGroupType::Select);
+            group.proxies = vec![".*".to_string(), "[]DIRECT".to_string()];
+            vec![group]
+        };
+        let convert = |target, group: &str| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(target)
+                .add_url("trojan://secret@hk.example.com:443#HK%2001")
+                .proxy_groups(groups(group))
+                .validate_output(true);
+            subconverter(builder.build().unwrap())
+        };
+        assert!(convert(SubconverterTarget::Clash, "Proxy").await.is_ok());
+        assert!(convert(SubconverterTarget::SingBox, "Proxy").await.is_ok());
+        // A group named like a node does not load
+        let err = convert(SubconverterTarget::Clash, "HK 01")
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("Generated output is invalid"));
+        assert!(convert(SubconverterTarget::SingBox, "HK 01").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_group_type_override_keeps_input_groups() {
+        use crate::models::ProxyGroupType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.yaml");
+        std::fs::write(
+            &input,
+            "proxies:\n\
+             - {name: HK, type: trojan, server: hk.example.com, port: 443, password: pw}\n\
+             proxy-groups:\n\
+             - {name: Input, type: select, proxies: [HK]}\n",
+        )
+        .unwrap();
+        let select = |name: &str| {
+            let mut group = ProxyGroupConfig::new(name.to_string(), ProxyGroupType::Select);
+            group.proxies = vec![".*".to_string()];
+            group
+        };
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(input.to_str().unwrap())
+            .authorized(true)
+            .proxy_groups(vec![select("Proxy"), select("Auto")])
+            .keep_input_groups(true)
+            .group_type_override(Some(GroupTypeOverride {
+                from: ProxyGroupType::Select,
+                to: ProxyGroupType::URLTest,
+                exclude: vec!["Proxy".to_string()],
+            }));
+        let output = subconverter(builder.build().unwrap())
+            .await
+            .unwrap()
+            .content;
+        let yaml: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let groups = yaml["proxy-groups"].as_sequence().unwrap();
+        let types: Vec<(&str, &str)> = groups
+            .iter()
+            .map(|g| (g["name"].as_str().unwrap(), g["type"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("Input", "select"),
+                ("Proxy", "select"),
+                ("Auto", "url-test")
+            ]
+        );
+        // The rewritten group gets the default health check
+        assert_eq!(groups[2]["interval"].as_u64(), Some(300));
+    }
+
+    #[tokio::test]
+    async fn test_keep_managed_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.conf");
+        std::fs::write(
+            &input,
+            "#!MANAGED-CONFIG https://example.com/surge.conf interval=3600 strict=true\n\n\
+             [Proxy]\n\
+             HK = trojan, hk.example.com, 443, password=pw\n",
+        )
+        .unwrap();
+        let convert = |keep: bool| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Surge(4))
+                .add_url(input.to_str().unwrap())
+                .authorized(true)
+                .extra(ExtraSettings::builder().nodelist(true).build())
+                .keep_managed_config(keep);
+            async move {
+                subconverter(builder.build().unwrap())
+                    .await
+                    .unwrap()
+                    .content
+            }
+        };
+
+        let output = convert(true).await;
+        assert!(output.starts_with(
+            "#!MANAGED-CONFIG https://example.com/surge.conf interval=3600 strict=true\n\n"
+        ));
+        assert!(output.contains("hk.example.com"));
+        assert!(!convert(false).await.starts_with("#!MANAGED-CONFIG"));
+
+        // Regenerated headers point here and keep the update policy the
+        // conversion does not set
+        let regenerate = |interval: Option<u32>| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .target(SubconverterTarget::Surge(4))
+                .add_url(input.to_str().unwrap())
+                .authorized(true)
+                .managed_config_prefix("https://sub.example.org/".to_string())
+                .request_query(Some("target=surge&url=input&token=s3cret".to_string()))
+                .update_strict(false);
+            if let Some(interval) = interval {
+                builder.update_interval(interval);
+            }
+            async move {
+                subconverter(builder.build().unwrap())
+                    .await
+                    .unwrap()
+                    .content
+            }
+        };
+        assert!(regenerate(None).await.starts_with(
+            "#!MANAGED-CONFIG https://sub.example.org/sub?target=surge&ver=4&url=input&token=s3cret \
+             interval=3600 strict=false\n\n"
+        ));
+        assert!(regenerate(Some(86400))
+            .await
+            .contains(" interval=86400 strict=false\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_during_conversion() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sub", listener.local_addr().unwrap());
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(&url)
+            .extra(ExtraSettings::builder().nodelist(true).build());
+        let serve = async move {
+            // The conversion is waiting for its subscription when the
+            // settings are reloaded
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let reload = tokio::task::spawn_blocking(|| {
+                Settings::reload((*Settings::snapshot()).clone());
+            });
+            tokio::time::timeout(std::time::Duration::from_secs(5), reload)
+                .await
+                .expect("reload blocked by a running conversion")
+                .unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+            let body = "trojan://pw@hk.example.com:443#HK\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        };
+
+        // The HTTP client runs on a local task set, as under actix
+        let (output, _) = tokio::task::LocalSet::new()
+            .run_until(async { tokio::join!(subconverter(builder.build().unwrap()), serve) })
+            .await;
+        assert!(output.unwrap().content.contains("hk.example.com"));
+    }
+
+    #[test]
+    fn test_conversion_link() {
+        let config = |request_query: Option<&str>| {
+            let mut builder = SubconverterConfigBuilder::new();
+            builder
+                .add_url("https://example.com/sub?a=1")
+                .managed_config_prefix("https://sub.example.org/".to_string())
+                .token(Some("s3cret".to_string()))
+                .request_query(request_query.map(str::to_string));
+            builder.build().unwrap()
+        };
+        assert_eq!(
+            conversion_link(&config(None), &[("target", "quanx"), ("list", "true")]),
+            "https://sub.example.org/sub?target=quanx&list=true\
+             &url=https%3A%2F%2Fexample.com%2Fsub%3Fa%3D1&token=s3cret"
+        );
+
+        // The request's own rename and emoji options go along
+        let request = config(Some(
+            "target=quanx&url=https%3A%2F%2Fa.example&token=s3cret\
+             &rename=HK%40%E9%A6%99%E6%B8%AF&emoji=true&list=false",
+        ));
+        assert_eq!(
+            conversion_link(&request, &[("target", "quanx"), ("list", "true")]),
+            "https://sub.example.org/sub?target=quanx&list=true&url=https%3A%2F%2Fa.example\
+             &token=s3cret&rename=HK%40%E9%A6%99%E6%B8%AF&emoji=true"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clash_provider_url_forwards_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("nodes.txt");
+        std::fs::write(&input, "trojan://pw@hk.example.com:443#HK\n").unwrap();
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(input.to_str().unwrap())
+            .authorized(true)
+            .managed_config_prefix("https://sub.example.org/".to_string())
+            .request_query(Some(
+                "target=clash&url=nodes&token=s3cret&rename=HK%40Hong%20Kong".to_string(),
+            ))
+            .clash_use_providers(true);
+        let output = subconverter(builder.build().unwrap()).await.unwrap();
+        assert!(output.content.contains(
+            "https://sub.example.org/sub?target=clash&list=true&url=nodes&token=s3cret\
+             &rename=HK%40Hong%20Kong"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_explain_parse_filters() {
+        use crate::utils::explain::DropStage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("nodes.txt");
+        std::fs::write(
+            &input,
+            "trojan://pw@hk.example.com:443#HK\ntrojan://pw@jp.example.com:443#JP%20Expire\n",
+        )
+        .unwrap();
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(input.to_str().unwrap())
+            .authorized(true)
+            .exclude_remarks(vec!["Expire".to_string()])
+            .explain(true);
+        let output = subconverter(builder.build().unwrap()).await.unwrap();
+        let dropped = output.explain.unwrap().dropped;
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].remark, "JP Expire");
+        assert_eq!(dropped[0].stage, DropStage::RemarkFilter);
+        assert_eq!(dropped[0].reason, "matches exclude_remarks 'Expire'");
+    }
+
+    #[tokio::test]
+    async fn test_request_and_extra_remark_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("nodes.txt");
+        std::fs::write(
+            &input,
+            "trojan://pw@a.example.com:443#HK%2001\n\
+             trojan://pw@b.example.com:443#HK%2002\n\
+             trojan://pw@c.example.com:443#JP%2001\n",
+        )
+        .unwrap();
+        let mut builder = SubconverterConfigBuilder::new();
+        builder
+            .target(SubconverterTarget::Clash)
+            .add_url(input.to_str().unwrap())
+            .authorized(true)
+            .include_remarks(vec!["HK".to_string()])
+            .extra(ExtraSettings {
+                nodelist: true,
+                include_remarks: vec!["01".to_string()],
+                ..Default::default()
+            });
+        // A node has to pass both, the patterns do not add up
+        let output = subconverter(builder.build().unwrap()).await.unwrap();
+        assert!(output.content.contains("HK 01"));
+        assert!(!output.content.contains("HK 02") && !output.content.contains("JP 01"));
+    }
+
+    #[test]
+    fn test_resolve_input_groups() {
+        use crate::generator::config::group::group_generate_members;
+        use crate::models::ProxyGroupType;
+
+        let node = |id: u32, remark: &str| Proxy {
+            id,
+            remark: remark.to_string(),
+            ..Default::default()
+        };
+        let input_names: HashMap<(i32, String), u32> = [("HK", 0), ("JP", 1), ("US", 2)]
+            .iter()
+            .map(|(name, id)| ((0, name.to_string()), *id))
+            .collect();
+        // HK was renamed and US was filtered out
+        let nodes = vec![node(0, "🇭🇰 HK"), node(1, "JP")];
+
+        let mut auto = ProxyGroupConfig::new("Auto".to_string(), ProxyGroupType::URLTest);
+        auto.proxies = vec!["HK".into(), "US".into()];
+        let mut proxy = ProxyGroupConfig::new("Proxy".to_string(), ProxyGroupType::Select);
+        proxy.proxies = vec!["Auto".into(), "JP".into(), "Gone".into(), "DIRECT".into()];
+
+        let groups = resolve_input_groups(vec![(0, auto), (0, proxy)], &input_names, &nodes);
+        let ext = ExtraSettings::default();
+        let members =
+            |group: &ProxyGroupConfig| group_generate_members(group, &nodes, true, "", &ext);
+        assert_eq!(members(&groups[0]), vec!["🇭🇰 HK"]);
+        assert_eq!(members(&groups[1]), vec!["Auto", "JP", "DIRECT"]);
+
+        let mut configured = ProxyGroupConfig::new("Proxy".to_string(), ProxyGroupType::Select);
+        configured.proxies = vec![".*".into()];
+        let extra = ProxyGroupConfig::new("Extra".to_string(), ProxyGroupType::Select);
+        let merged = merge_input_groups(groups, vec![configured, extra]);
+        let names: Vec<&str> = merged.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["Auto", "Proxy", "Extra"]);
+        assert_eq!(members(&merged[1]), vec!["Auto", "JP", "DIRECT"]);
+    }
+}
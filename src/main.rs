@@ -117,7 +117,7 @@ async fn main() -> std::io::Result<()> {
             }
         };
 
-        let max_concur_threads = Settings::current().max_concur_threads;
+        let max_concur_threads = Settings::snapshot().max_concur_threads;
 
         info!("Subconverter starting on {}", listen_address);
 
@@ -1,3 +1,6 @@
+use crate::models::proxy_node::hysteria::{
+    HysteriaProtocol, HYSTERIA_DEFAULT_DOWN_MBPS, HYSTERIA_DEFAULT_UP_MBPS,
+};
 use crate::{Proxy, ProxyType};
 
 impl Proxy {
@@ -96,9 +99,21 @@ impl Proxy {
                     host.trim().to_owned()
                 },
             );
-            proxy.path = Some(if path.is_empty() { "/" } else { path.trim() }.to_owned());
+            // A gRPC service name has no default, unlike a path
+            proxy.path = Some(
+                if path.is_empty() && net != "grpc" {
+                    "/"
+                } else {
+                    path.trim()
+                }
+                .to_owned(),
+            );
         }
         proxy.fake_type = Some(typ.to_owned());
+        if net == "grpc" {
+            // v2rayN keeps the gRPC mode in the header type field
+            proxy.grpc_mode = typ.parse().ok();
+        }
 
         proxy
     }
@@ -359,7 +374,7 @@ impl Proxy {
         udp: Option<bool>,
         underlying_proxy: Option<String>,
     ) -> Self {
-        let mut dns_set = std::collections::HashSet::new();
+        let mut dns_set = std::collections::BTreeSet::new();
         for dns in dns_servers {
             dns_set.insert(dns);
         }
@@ -407,11 +422,6 @@ impl Proxy {
         allow_insecure: Option<bool>,
         underlying_proxy: Option<String>,
     ) -> Self {
-        let mut alpn_set = std::collections::HashSet::new();
-        for proto in alpn {
-            alpn_set.insert(proto);
-        }
-
         Proxy {
             proxy_type: ProxyType::Hysteria2,
             group,
@@ -426,7 +436,7 @@ impl Proxy {
             obfs_param: obfs_param,
             sni: sni,
             fingerprint: fingerprint,
-            alpn: alpn_set,
+            alpn,
             ca: ca,
             ca_str: ca_str,
             cwnd: cwnd.unwrap_or(0),
@@ -437,6 +447,8 @@ impl Proxy {
         }
     }
 
+    /// Hysteria v1 node, see [`hysteria`](super::proxy_node::hysteria) for
+    /// what the fields mean. Missing bandwidth gets the defaults.
     pub fn hysteria_construct(
         group: String,
         remark: String,
@@ -462,11 +474,6 @@ impl Proxy {
         allow_insecure: Option<bool>,
         underlying_proxy: Option<String>,
     ) -> Self {
-        let mut alpn_set = std::collections::HashSet::new();
-        for proto in alpn {
-            alpn_set.insert(proto);
-        }
-
         Proxy {
             proxy_type: ProxyType::Hysteria,
             group,
@@ -474,10 +481,14 @@ impl Proxy {
             hostname,
             port,
             ports: Some(ports),
-            protocol: Some(protocol),
+            protocol: Some(HysteriaProtocol::parse_lossy(&protocol).to_string()),
             obfs_param: Some(obfs_param),
-            up_speed: up_speed.unwrap_or(0),
-            down_speed: down_speed.unwrap_or(0),
+            up_speed: up_speed
+                .filter(|speed| *speed > 0)
+                .unwrap_or(HYSTERIA_DEFAULT_UP_MBPS),
+            down_speed: down_speed
+                .filter(|speed| *speed > 0)
+                .unwrap_or(HYSTERIA_DEFAULT_DOWN_MBPS),
             auth_str: Some(auth_str),
             obfs: Some(obfs),
             sni: Some(sni),
@@ -488,7 +499,7 @@ impl Proxy {
             recv_window: recv_window.unwrap_or(0),
             disable_mtu_discovery,
             hop_interval: hop_interval.unwrap_or(0),
-            alpn: alpn_set,
+            alpn,
             tcp_fast_open,
             allow_insecure,
             underlying_proxy,
@@ -0,0 +1,92 @@
+//! Which nodes the default skip-cert-verify flag applies to
+//!
+//! Servers given as an IP literal rarely present a certificate matching the
+//! SNI, so verification often fails for them while domain servers verify
+//! fine. [`SkipCertVerifyPolicy::IpOnly`] skips verification for the former
+//! only, instead of disabling it for every node.
+
+use std::str::FromStr;
+
+use super::Proxy;
+use crate::utils::{is_ipv4, is_ipv6};
+
+/// How the default skip-cert-verify flag is applied to nodes leaving it unset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SkipCertVerifyPolicy {
+    /// The flag applies to every node
+    #[default]
+    All,
+    /// Verification is skipped for nodes whose server is an IP literal,
+    /// domain servers keep verifying unless the flag says otherwise
+    IpOnly,
+}
+
+impl SkipCertVerifyPolicy {
+    /// Default skip-cert-verify value of a node under this policy
+    ///
+    /// Nodes setting the value themselves keep it, callers only use this for
+    /// nodes that leave it unset.
+    pub fn default_for(&self, flag: Option<bool>, node: &Proxy) -> Option<bool> {
+        match self {
+            SkipCertVerifyPolicy::All => flag,
+            SkipCertVerifyPolicy::IpOnly => {
+                let host = node.hostname.trim_start_matches('[').trim_end_matches(']');
+                if is_ipv4(host) || is_ipv6(host) {
+                    Some(true)
+                } else {
+                    flag.filter(|skip| !skip)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for SkipCertVerifyPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "all" | "always" => Ok(SkipCertVerifyPolicy::All),
+            "ip" | "ip-only" => Ok(SkipCertVerifyPolicy::IpOnly),
+            _ => Err(format!("Unknown skip-cert-verify policy: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(hostname: &str) -> Proxy {
+        Proxy {
+            hostname: hostname.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_skip_cert_verify_policy() {
+        let ip_only: SkipCertVerifyPolicy = "ip_only".parse().unwrap();
+        assert_eq!(ip_only, SkipCertVerifyPolicy::IpOnly);
+
+        assert_eq!(ip_only.default_for(None, &node("1.2.3.4")), Some(true));
+        assert_eq!(
+            ip_only.default_for(Some(false), &node("2001:db8::1")),
+            Some(true)
+        );
+        assert_eq!(ip_only.default_for(None, &node("example.com")), None);
+        assert_eq!(ip_only.default_for(Some(true), &node("example.com")), None);
+        assert_eq!(
+            ip_only.default_for(Some(false), &node("example.com")),
+            Some(false)
+        );
+
+        let all = SkipCertVerifyPolicy::default();
+        assert_eq!(
+            all.default_for(Some(true), &node("example.com")),
+            Some(true)
+        );
+        assert_eq!(all.default_for(None, &node("1.2.3.4")), None);
+        assert!("sometimes".parse::<SkipCertVerifyPolicy>().is_err());
+    }
+}
@@ -1,3 +1,16 @@
+//! Cipher sets of Shadowsocks and ShadowsocksR
+//!
+//! Shadowsocks 2022 ciphers (SIP022) take a base64 pre-shared key of a fixed
+//! length instead of a password, optionally prefixed with the identity keys of
+//! relays as `iPSK:uPSK`. Parsers check the keys with [`validate_ss2022_psk`]
+//! since clients refuse to start with a key of the wrong length.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use thiserror::Error;
+
+use super::{Proxy, ProxyType};
+
 pub static SS_CIPHERS: &[&str] = &[
     "rc4-md5",
     "aes-128-gcm",
@@ -49,3 +62,120 @@ pub static SSR_CIPHERS: &[&str] = &[
     "chacha20",
     "chacha20-ietf",
 ];
+
+/// Shadowsocks 2022 ciphers with the length of their keys in bytes
+const SS2022_KEY_LENGTHS: &[(&str, usize)] = &[
+    ("2022-blake3-aes-128-gcm", 16),
+    ("2022-blake3-aes-256-gcm", 32),
+    ("2022-blake3-chacha20-poly1305", 32),
+    ("2022-blake3-chacha12-poly1305", 32),
+    ("2022-blake3-chacha8-poly1305", 32),
+];
+
+/// A pre-shared key that does not fit its Shadowsocks 2022 cipher
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Ss2022KeyError {
+    #[error("key {0} is not valid base64")]
+    InvalidBase64(usize),
+    #[error("key {index} is {actual} bytes, {cipher} needs {expected}")]
+    WrongLength {
+        index: usize,
+        cipher: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// Whether `method` is a Shadowsocks 2022 cipher
+pub fn is_ss2022_cipher(method: &str) -> bool {
+    SS2022_KEY_LENGTHS.iter().any(|(name, _)| *name == method)
+}
+
+/// The Shadowsocks 2022 cipher of `node`, if it has one
+///
+/// Targets whose clients predate Shadowsocks 2022 skip such nodes.
+pub fn node_ss2022_cipher(node: &Proxy) -> Option<&str> {
+    node.encrypt_method
+        .as_deref()
+        .filter(|method| node.proxy_type == ProxyType::Shadowsocks && is_ss2022_cipher(method))
+}
+
+/// Split a Shadowsocks 2022 password into its keys, identity keys first
+pub fn split_ss2022_psk(password: &str) -> Vec<&str> {
+    password.split(':').collect()
+}
+
+/// Check the pre-shared keys of a Shadowsocks 2022 node
+///
+/// Every key of an `iPSK:uPSK` chain must decode to the key length of the
+/// cipher. Other ciphers take any password and always pass.
+pub fn validate_ss2022_psk(method: &str, password: &str) -> Result<(), Ss2022KeyError> {
+    let Some((_, expected)) = SS2022_KEY_LENGTHS.iter().find(|(name, _)| *name == method) else {
+        return Ok(());
+    };
+    for (index, key) in split_ss2022_psk(password).into_iter().enumerate() {
+        let decoded = STANDARD
+            .decode(key.trim())
+            .map_err(|_| Ss2022KeyError::InvalidBase64(index))?;
+        if decoded.len() != *expected {
+            return Err(Ss2022KeyError::WrongLength {
+                index,
+                cipher: method.to_string(),
+                expected: *expected,
+                actual: decoded.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_ss2022_cipher() {
+        let mut node = Proxy {
+            proxy_type: ProxyType::Shadowsocks,
+            encrypt_method: Some("2022-blake3-aes-128-gcm".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(node_ss2022_cipher(&node), Some("2022-blake3-aes-128-gcm"));
+        node.encrypt_method = Some("aes-128-gcm".to_string());
+        assert_eq!(node_ss2022_cipher(&node), None);
+        node.proxy_type = ProxyType::ShadowsocksR;
+        node.encrypt_method = Some("2022-blake3-aes-128-gcm".to_string());
+        assert_eq!(node_ss2022_cipher(&node), None);
+    }
+
+    #[test]
+    fn test_validate_ss2022_psk() {
+        let key16 = "ZGVmZ2hpamtsbW5vcHFycw==";
+        let key32 = "AQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRobHB0eHyA=";
+        assert!(is_ss2022_cipher("2022-blake3-aes-128-gcm"));
+        assert!(!is_ss2022_cipher("aes-128-gcm"));
+
+        assert_eq!(
+            validate_ss2022_psk("2022-blake3-aes-128-gcm", key16),
+            Ok(())
+        );
+        assert_eq!(
+            validate_ss2022_psk("2022-blake3-aes-256-gcm", &format!("{}:{}", key32, key32)),
+            Ok(())
+        );
+        assert_eq!(
+            validate_ss2022_psk("2022-blake3-aes-256-gcm", key16),
+            Err(Ss2022KeyError::WrongLength {
+                index: 0,
+                cipher: "2022-blake3-aes-256-gcm".to_string(),
+                expected: 32,
+                actual: 16,
+            })
+        );
+        assert_eq!(
+            validate_ss2022_psk("2022-blake3-aes-128-gcm", &format!("{}:not base64", key16)),
+            Err(Ss2022KeyError::InvalidBase64(1))
+        );
+        assert_eq!(validate_ss2022_psk("aes-128-gcm", "any password"), Ok(()));
+    }
+}
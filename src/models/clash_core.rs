@@ -0,0 +1,143 @@
+//! Which Clash core the `clash` target is emitted for
+//!
+//! The `clash` target is written for Clash.Meta (mihomo), which also reads
+//! every configuration of the original core. Users still running the
+//! original Clash or Clash Premium set
+//! [`ExtraSettings::clash_core`](super::ExtraSettings::clash_core) to
+//! [`ClashCore::Vanilla`]. Nodes of protocols only Clash.Meta implements,
+//! Shadowsocks nodes with a 2022 cipher or ShadowTLS and Trojan-Go nodes
+//! with a Shadowsocks layer are then skipped with a warning naming them and
+//! pointing at Clash.Meta. Meta only options such
+//! as `ip-version` and logical rules are left out.
+//!
+//! A vanilla core rejects the whole configuration on an unknown proxy
+//! type, so emitting the nodes anyway is not an option. With
+//! [`ExtraSettings::upgrade_clash_core`](super::ExtraSettings::upgrade_clash_core)
+//! the export switches to Clash.Meta instead when such nodes are present.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::ciphers::node_ss2022_cipher;
+use super::proxy_node::shadowtls::ShadowTlsOptions;
+use super::{Proxy, ProxyType};
+
+/// Clash core a configuration is written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ClashCore {
+    /// Clash.Meta, also known as mihomo
+    #[default]
+    Meta,
+    /// The original Clash core and Clash Premium
+    Vanilla,
+}
+
+impl ClashCore {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClashCore::Meta => "meta",
+            ClashCore::Vanilla => "vanilla",
+        }
+    }
+}
+
+impl FromStr for ClashCore {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "meta" | "clash.meta" | "mihomo" => Ok(ClashCore::Meta),
+            "vanilla" | "premium" | "clash" => Ok(ClashCore::Vanilla),
+            _ => Err(format!(
+                "Unknown Clash core '{}', expected meta or vanilla",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ClashCore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether only Clash.Meta implements the protocol of `node`, its
+/// Shadowsocks 2022 cipher, its ShadowTLS layer or its Trojan-Go
+/// Shadowsocks layer
+pub fn is_meta_only(node: &Proxy) -> bool {
+    matches!(
+        node.proxy_type,
+        ProxyType::Vless
+            | ProxyType::Hysteria
+            | ProxyType::Hysteria2
+            | ProxyType::Tuic
+            | ProxyType::AnyTls
+    ) || node_ss2022_cipher(node).is_some()
+        || has_shadow_tls(node)
+        || trojan_go_ss_method(node).is_some()
+}
+
+/// Whether `node` is a Shadowsocks node wrapped in ShadowTLS
+pub fn has_shadow_tls(node: &Proxy) -> bool {
+    node.proxy_type == ProxyType::Shadowsocks && ShadowTlsOptions::from_proxy(node).is_some()
+}
+
+/// Cipher of the Shadowsocks layer of a Trojan-Go node
+fn trojan_go_ss_method(node: &Proxy) -> Option<&str> {
+    let ss = node.trojan_go()?.shadowsocks.as_ref()?;
+    Some(ss.method.as_str())
+}
+
+/// Why a vanilla Clash core cannot take `node`, `None` when it can
+pub fn vanilla_skip_reason(node: &Proxy) -> Option<String> {
+    if let Some(method) = node_ss2022_cipher(node) {
+        return Some(format!(
+            "Shadowsocks cipher {} needs Clash.Meta, set clash_core=meta",
+            method
+        ));
+    }
+    if has_shadow_tls(node) {
+        return Some("ShadowTLS needs Clash.Meta, set clash_core=meta".to_string());
+    }
+    if let Some(method) = trojan_go_ss_method(node) {
+        return Some(format!(
+            "Trojan-Go Shadowsocks encryption ({}) needs Clash.Meta, set clash_core=meta",
+            method
+        ));
+    }
+    is_meta_only(node).then(|| {
+        format!(
+            "{} needs Clash.Meta, set clash_core=meta",
+            node.proxy_type.to_string()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clash_core() {
+        assert_eq!("Premium".parse(), Ok(ClashCore::Vanilla));
+        assert_eq!("clash.meta".parse(), Ok(ClashCore::Meta));
+        assert!("stash".parse::<ClashCore>().is_err());
+
+        let node = |proxy_type| Proxy {
+            proxy_type,
+            ..Default::default()
+        };
+        assert!(vanilla_skip_reason(&node(ProxyType::VMess)).is_none());
+        let reason = vanilla_skip_reason(&node(ProxyType::Hysteria2)).unwrap();
+        assert!(reason.starts_with("Hysteria2 needs Clash.Meta"));
+
+        let mut ss = node(ProxyType::Shadowsocks);
+        ss.encrypt_method = Some("aes-128-gcm".to_string());
+        assert!(vanilla_skip_reason(&ss).is_none());
+        ss.encrypt_method = Some("2022-blake3-aes-128-gcm".to_string());
+        assert!(is_meta_only(&ss));
+        let reason = vanilla_skip_reason(&ss).unwrap();
+        assert!(reason.starts_with("Shadowsocks cipher 2022-blake3-aes-128-gcm needs Clash.Meta"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Removal of duplicate nodes merged from several subscriptions
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use super::proxy_node::combined::CombinedProxy;
+use super::Proxy;
+
+/// The properties that make two nodes duplicates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DedupKey {
+    /// Type, server and port
+    Endpoint,
+    /// Type, server, port and the protocol secret (password, UUID, keys)
+    #[default]
+    Credential,
+    /// Like `Credential`, also comparing transport path, host and SNI
+    Transport,
+}
+
+impl DedupKey {
+    /// Build the identity of a node under this key
+    ///
+    /// Server names are compared case-insensitively; the remark is never
+    /// part of the identity.
+    pub fn identity(&self, node: &Proxy) -> String {
+        let mut parts = vec![
+            node.proxy_type.to_string().to_string(),
+            node.hostname.to_lowercase(),
+            node.port.to_string(),
+        ];
+        if *self == DedupKey::Endpoint {
+            return parts.join("\u{1}");
+        }
+
+        parts.extend(node_secrets(node));
+        if *self == DedupKey::Transport {
+            parts.push(node.transfer_protocol.clone().unwrap_or_default());
+            parts.push(node.path.clone().unwrap_or_default());
+            parts.push(node.host.clone().unwrap_or_default());
+            parts.push(
+                node.sni
+                    .clone()
+                    .or_else(|| node.server_name.clone())
+                    .unwrap_or_default(),
+            );
+            if let Some(CombinedProxy::Vless(vless)) = &node.combined_proxy {
+                parts.push(vless.network.clone().unwrap_or_default());
+                parts.push(vless.ws_path.clone().unwrap_or_default());
+                parts.push(vless.grpc_service_name.clone().unwrap_or_default());
+                parts.push(vless.servername.clone().unwrap_or_default());
+            }
+        }
+        parts.join("\u{1}")
+    }
+
+    /// Remove duplicate nodes, keeping the first one seen
+    ///
+    /// # Returns
+    /// The number of removed nodes
+    pub fn dedup_nodes(&self, nodes: &mut Vec<Proxy>) -> usize {
+        let before = nodes.len();
+        let mut seen = HashSet::new();
+        nodes.retain(|node| seen.insert(self.identity(node)));
+        before - nodes.len()
+    }
+}
+
+/// The protocol specific secrets of a node
+fn node_secrets(node: &Proxy) -> Vec<String> {
+    let mut secrets: Vec<String> = [
+        &node.username,
+        &node.password,
+        &node.user_id,
+        &node.auth,
+        &node.auth_str,
+        &node.private_key,
+        &node.public_key,
+        &node.pre_shared_key,
+        &node.obfs_param,
+    ]
+    .iter()
+    .map(|value| value.as_deref().unwrap_or_default().to_string())
+    .collect();
+
+    match &node.combined_proxy {
+        Some(CombinedProxy::Vless(vless)) => secrets.push(vless.uuid.clone()),
+        Some(CombinedProxy::Shadowsocks(ss)) => {
+            secrets.push(ss.password.clone());
+            secrets.push(ss.cipher.clone());
+        }
+        Some(CombinedProxy::AnyTls(anytls)) => secrets.push(anytls.password.clone()),
+        Some(CombinedProxy::Tuic(tuic)) => {
+            secrets.push(tuic.uuid.clone());
+            secrets.push(tuic.password.clone());
+        }
+        Some(CombinedProxy::TrojanGo(trojan_go)) => {
+            if let Some(ss) = &trojan_go.shadowsocks {
+                secrets.push(ss.password.clone());
+                secrets.push(ss.method.clone());
+            }
+        }
+        Some(CombinedProxy::Custom(custom)) => {
+            secrets.push(custom.codec.clone());
+            secrets.push(custom.data.to_string());
+        }
+        None => {}
+    }
+    secrets
+}
+
+/// Parse a dedup key: `endpoint`, `credential` or `transport`
+impl FromStr for DedupKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "endpoint" | "server" => Ok(DedupKey::Endpoint),
+            "credential" | "secret" => Ok(DedupKey::Credential),
+            "transport" | "full" => Ok(DedupKey::Transport),
+            _ => Err(format!("Unknown dedup key: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyType;
+
+    fn node(remark: &str, host: &str, password: &str) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: remark.to_string(),
+            hostname: host.to_string(),
+            port: 443,
+            password: Some(password.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dedup_keeps_first_seen() {
+        let mut nodes = vec![
+            node("HK 01", "hk.example.com", "a"),
+            node("🇭🇰 HK 01 copy", "HK.example.com", "a"),
+            node("HK 02", "hk.example.com", "b"),
+        ];
+        assert_eq!(DedupKey::Credential.dedup_nodes(&mut nodes), 1);
+        let remarks: Vec<&str> = nodes.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["HK 01", "HK 02"]);
+
+        assert_eq!(DedupKey::Endpoint.dedup_nodes(&mut nodes), 1);
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_dedup_key_from_str() {
+        assert_eq!("Transport".parse(), Ok(DedupKey::Transport));
+        assert!("remark".parse::<DedupKey>().is_err());
+    }
+}
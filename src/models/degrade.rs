@@ -0,0 +1,227 @@
+//! Fallbacks for nodes a target can only emit without a feature
+//!
+//! A target lacking a feature of a node normally skips it, see
+//! [`unsupported`](super::unsupported). With a [`DegradeMap`] in
+//! [`ExtraSettings::degrade`](super::ExtraSettings::degrade) the node is
+//! emitted as the simpler protocol it falls back to instead:
+//!
+//! | Rule | Fallback | Dropped |
+//! |---|---|---|
+//! | `trojan-go=trojan` | Trojan | websocket transport and mux |
+//!
+//! Degrading is opt-in and only happens where the target lacks the richer
+//! feature; a target that has it emits the node unchanged. Every degraded
+//! node is logged and stripped with [`Degrade::apply`] before it is
+//! emitted. A Trojan-Go node keeping its Shadowsocks layer is never degraded
+//! since its server requires the layer. VLESS Reality has no fallback: a
+//! Reality server has no certificate of its own, so the node can not
+//! connect over plain TLS and is skipped.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use super::proxy_node::combined::CombinedProxy;
+use super::{Proxy, ProxyType};
+
+/// A fallback from a protocol to a simpler one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Degrade {
+    /// Trojan-Go to plain Trojan
+    TrojanGo,
+}
+
+impl Degrade {
+    pub const ALL: [Degrade; 1] = [Degrade::TrojanGo];
+
+    /// Name of the richer protocol, the key of the rule
+    pub fn from_name(&self) -> &'static str {
+        match self {
+            Degrade::TrojanGo => "trojan-go",
+        }
+    }
+
+    /// Name of the protocol the node falls back to
+    pub fn to_name(&self) -> &'static str {
+        match self {
+            Degrade::TrojanGo => "trojan",
+        }
+    }
+
+    /// What the fallback leaves out
+    pub fn dropped(&self) -> &'static str {
+        match self {
+            Degrade::TrojanGo => "websocket transport and mux",
+        }
+    }
+
+    /// Why `node` cannot fall back, `None` when it can
+    pub fn refusal(&self, node: &Proxy) -> Option<String> {
+        match (self, &node.combined_proxy) {
+            (Degrade::TrojanGo, Some(CombinedProxy::TrojanGo(trojan_go))) => trojan_go
+                .shadowsocks
+                .as_ref()
+                .map(|_| "its server requires the Shadowsocks layer".to_string()),
+            _ => Some(format!("it is not a {} node", self.from_name())),
+        }
+    }
+
+    /// Remove the dropped feature from `node`
+    pub fn apply(&self, node: &mut Proxy) {
+        match self {
+            Degrade::TrojanGo => {
+                node.combined_proxy = None;
+                node.transfer_protocol = Some("tcp".to_string());
+                node.path = None;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Degrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.from_name(), self.to_name())
+    }
+}
+
+impl FromStr for Degrade {
+    type Err = String;
+
+    /// Parse `from=to` or just `from`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = match s.split_once('=') {
+            Some((from, to)) => (from.trim(), Some(to.trim())),
+            None => (s.trim(), None),
+        };
+        let from = from.to_lowercase();
+        let Some(degrade) = Degrade::ALL
+            .into_iter()
+            .find(|degrade| degrade.from_name() == from)
+        else {
+            return Err(format!("Unknown degrade rule '{}', expected trojan-go", s));
+        };
+        match to {
+            Some(to) if !to.eq_ignore_ascii_case(degrade.to_name()) => Err(format!(
+                "{} cannot degrade to '{}', only to {}",
+                degrade.from_name(),
+                to,
+                degrade.to_name()
+            )),
+            _ => Ok(degrade),
+        }
+    }
+}
+
+/// The fallbacks enabled for an export, none by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DegradeMap(BTreeSet<Degrade>);
+
+impl DegradeMap {
+    /// Every known fallback
+    pub fn all() -> Self {
+        DegradeMap(Degrade::ALL.into_iter().collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn allows(&self, degrade: Degrade) -> bool {
+        self.0.contains(&degrade)
+    }
+
+    pub fn insert(&mut self, degrade: Degrade) {
+        self.0.insert(degrade);
+    }
+}
+
+impl FromIterator<Degrade> for DegradeMap {
+    fn from_iter<I: IntoIterator<Item = Degrade>>(iter: I) -> Self {
+        DegradeMap(iter.into_iter().collect())
+    }
+}
+
+impl FromStr for DegradeMap {
+    type Err = String;
+
+    /// Parse a comma separated list of rules, `all` enables every one
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("all") {
+            return Ok(DegradeMap::all());
+        }
+        s.split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl fmt::Display for DegradeMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules: Vec<String> = self.0.iter().map(Degrade::to_string).collect();
+        f.write_str(&rules.join(","))
+    }
+}
+
+/// Whether a target lacking Reality would have to skip `node`
+pub fn needs_reality(node: &Proxy) -> bool {
+    node.proxy_type == ProxyType::Vless
+        && matches!(&node.combined_proxy, Some(CombinedProxy::Vless(vless)) if vless.is_reality())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::proxy_node::trojan_go::{TrojanGoProxy, TrojanGoShadowsocks};
+    use crate::models::proxy_node::vless::VlessProxy;
+
+    #[test]
+    fn test_degrade_map() {
+        assert_eq!("".parse(), Ok(DegradeMap::default()));
+        assert_eq!("ALL".parse(), Ok(DegradeMap::all()));
+        let map: DegradeMap = "trojan-go=TROJAN".parse().unwrap();
+        assert_eq!(map, DegradeMap::all());
+        assert_eq!(map.to_string(), "trojan-go=trojan");
+        assert!("trojan-go=vmess".parse::<DegradeMap>().is_err());
+        assert!("vless-reality".parse::<DegradeMap>().is_err());
+        assert!("hysteria".parse::<DegradeMap>().is_err());
+    }
+
+    #[test]
+    fn test_degrade_refusal() {
+        let mut trojan_go = Proxy {
+            proxy_type: ProxyType::Trojan,
+            transfer_protocol: Some("ws".to_string()),
+            path: Some("/ws".to_string()),
+            combined_proxy: Some(CombinedProxy::TrojanGo(TrojanGoProxy {
+                mux: true,
+                shadowsocks: None,
+            })),
+            ..Default::default()
+        };
+        assert_eq!(Degrade::TrojanGo.refusal(&trojan_go), None);
+        let mut degraded = trojan_go.clone();
+        Degrade::TrojanGo.apply(&mut degraded);
+        assert!(degraded.trojan_go().is_none());
+        assert_eq!(degraded.transfer_protocol.as_deref(), Some("tcp"));
+
+        trojan_go.combined_proxy = Some(CombinedProxy::TrojanGo(TrojanGoProxy {
+            mux: false,
+            shadowsocks: TrojanGoShadowsocks::parse("ss;aes-128-gcm:secret").unwrap(),
+        }));
+        assert!(Degrade::TrojanGo.refusal(&trojan_go).is_some());
+
+        let reality = Proxy {
+            proxy_type: ProxyType::Vless,
+            combined_proxy: Some(CombinedProxy::Vless(VlessProxy {
+                reality_public_key: Some("key".to_string()),
+                reality_short_id: Some("6ba8".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert!(needs_reality(&reality));
+        assert!(Degrade::TrojanGo.refusal(&reality).is_some());
+    }
+}
@@ -1,8 +1,87 @@
 use std::{cmp::Ordering, str::FromStr};
+#[cfg(feature = "js-runtime")]
+use std::{
+    sync::atomic::{AtomicBool, Ordering as AtomicOrdering},
+    sync::Arc,
+    time::Duration,
+};
 
+#[cfg(feature = "js-runtime")]
+use crate::utils::Stopwatch;
+use crate::utils::{ExplainRecorder, StatsRecorder};
 use crate::{utils::file_get_async, Settings};
 
-use super::{Proxy, ProxyType, RegexMatchConfig, RegexMatchConfigs};
+use super::{
+    ClashCore, DedupKey, Degrade, DegradeMap, GroupHealthCheck, GroupTypeOverride, IpVersion,
+    Multiplex, NameTemplate, PortRewriteMap, PortSet, ProviderHealthCheck, Proxy, ProxyType,
+    RegexMatchConfig, RegexMatchConfigs, SkipCertVerifyPolicy, SortKey, UnsupportedNodes,
+    UnsupportedPolicy,
+};
+
+/// Liveness probes running at once unless set otherwise
+pub const DEFAULT_PROBE_CONCURRENCY: usize = 16;
+/// Milliseconds a liveness probe waits unless set otherwise
+pub const DEFAULT_PROBE_TIMEOUT: u32 = 3000;
+
+/// Name of the function looked up by `eval_filter_function`
+pub const DEFAULT_FILTER_FUNCTION: &str = "filter";
+
+/// Errors raised while running user scripts
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScriptError {
+    /// The script ran but did not define the requested function
+    #[error("JavaScript function '{name}' is not defined by the script")]
+    FunctionNotFound { name: String },
+    /// A strict filter failed on some nodes, the first messages are kept
+    #[error(
+        "JavaScript function '{name}' failed on {failed} of {total} node(s): {}",
+        errors.join("; ")
+    )]
+    FilterFailed {
+        name: String,
+        failed: usize,
+        total: usize,
+        errors: Vec<String>,
+    },
+    /// A script of a filter chain failed, `index` counts from 0
+    #[error("Filter script {index} of the chain failed: {message}")]
+    ChainFailed { index: usize, message: String },
+    /// A script ran past `script_timeout` and was interrupted
+    #[error("JavaScript function '{name}' did not finish within {timeout} ms")]
+    TimedOut { name: String, timeout: u32 },
+    /// A script allocated more than `script_memory_limit` bytes
+    #[error("JavaScript function '{name}' exceeded the memory limit of {limit} bytes")]
+    OutOfMemory { name: String, limit: usize },
+}
+
+impl ScriptError {
+    /// Whether the script was stopped for exceeding a resource limit
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self,
+            ScriptError::TimedOut { .. } | ScriptError::OutOfMemory { .. }
+        )
+    }
+}
+
+/// Number of per-node messages kept in [`ScriptError::FilterFailed`]
+#[cfg(feature = "js-runtime")]
+const MAX_REPORTED_SCRIPT_ERRORS: usize = 5;
+
+/// Outcome of running a filter script over the nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterSummary {
+    /// Nodes the filter kept, including nodes it failed on
+    pub kept: usize,
+    /// Nodes the filter rejected
+    pub removed: usize,
+    /// Nodes the filter threw on or returned a non-boolean for
+    pub failed: usize,
+}
+
+/// Caller supplied hook run on the processed node list, see
+/// [`ExtraSettings::node_transform`]
+pub type NodeTransform = Box<dyn FnMut(&mut Vec<Proxy>) + Send>;
 
 /// Settings for subscription export operations
 pub struct ExtraSettings {
@@ -10,42 +89,200 @@ pub struct ExtraSettings {
     pub enable_rule_generator: bool,
     /// Whether to overwrite original rules
     pub overwrite_original_rules: bool,
+    /// What Clash and sing-box outputs reject, see
+    /// [`block_rules`](crate::generator::ruleconvert::block_rules)
+    pub block_rules: Vec<String>,
     /// Rename operations to apply
     pub rename_array: RegexMatchConfigs,
     /// Emoji operations to apply
     pub emoji_array: RegexMatchConfigs,
     /// Whether to add emoji
     pub add_emoji: bool,
+    /// Whether to pick the emoji from the GeoIP region of the server,
+    /// falling back to `emoji_array` when the lookup fails
+    pub emoji_by_geoip: bool,
     /// Whether to remove emoji
     pub remove_emoji: bool,
     /// Whether to append proxy type
     pub append_proxy_type: bool,
+    /// Whether to number nodes that still share a name after renaming
+    pub append_index: bool,
+    /// Minimum number of digits of an appended index, padded with zeros
+    pub append_index_digits: usize,
+    /// Text put between a name and its appended index
+    pub append_index_separator: String,
+    /// Whether unnamed nodes get a `{type}_{server}_{port}` name and
+    /// duplicate names a number right after parsing, before rename rules
+    pub normalize_names: bool,
+    /// Whether full-width and CJK punctuation in names becomes ASCII before
+    /// rename rules
+    pub normalize_punctuation: bool,
+    /// Whether known region names become their English name before rename
+    /// rules
+    pub transliterate_regions: bool,
+    /// Region names tried before the built-in
+    /// [`REGION_NAMES`](crate::utils::transliterate::REGION_NAMES), as
+    /// `(name, replacement)` pairs
+    pub region_names: Vec<(String, String)>,
+    /// Template every node name is replaced with, see
+    /// [`name_template`](super::name_template)
+    pub name_template: Option<NameTemplate>,
+    /// Whether the name template runs after rename rules instead of before
+    /// them
+    pub name_template_after_rename: bool,
     /// Whether to output as node list
     pub nodelist: bool,
+    /// Whether Shadowsocks links are canonical SIP002 URIs, with plugins
+    /// lacking options and Shadowsocks 2022 keys kept as SIP002 asks
+    pub ss_sip002: bool,
+    /// Health check filled into url-test, fallback and load-balance groups
+    /// that set none
+    pub group_health_check: GroupHealthCheck,
+    /// Whether proxy groups sharing a name are merged into the first one
+    /// before emission
+    pub collapse_groups: bool,
+    /// Groups filled from node matches with fewer nodes than this are
+    /// dropped and their nodes folded into the groups listing them, see
+    /// [`fold_small_groups`](crate::generator::config::group::fold_small_groups),
+    /// 0 keeps every group
+    pub min_group_size: usize,
+    /// Rewrite of the type of the configured proxy groups, `None` keeps
+    /// every type
+    pub group_type_override: Option<GroupTypeOverride>,
+    /// Template of the comment block put ahead of outputs with comments,
+    /// see [`crate::utils::header`]; `None` adds no header
+    pub output_header: Option<String>,
+    /// Whether identical input must give byte-identical output, skipping
+    /// steps that depend on the network at generation time
+    pub deterministic: bool,
+    /// Whether Clash and sing-box output is parsed back after generation and
+    /// rejected when malformed, see [`crate::generator::validate`]
+    pub validate_output: bool,
+    /// Whether TLS nodes without a client fingerprint get one picked from
+    /// `fingerprint_pool`
+    pub random_fingerprint: bool,
+    /// Fingerprints picked from, empty for
+    /// [`DEFAULT_FINGERPRINT_POOL`](super::proxy_node::utls::DEFAULT_FINGERPRINT_POOL)
+    pub fingerprint_pool: Vec<String>,
+    /// Seed of the fingerprint picks, deterministic output uses 0 when unset
+    /// and other output a new seed per export
+    pub fingerprint_seed: Option<u64>,
     /// Whether to sort nodes
     pub sort_flag: bool,
+    /// Whether the nodes in each proxy group are listed in node list order,
+    /// `None` follows `sort_flag`, see [`ExtraSettings::sorts_group_members`]
+    pub sort_group_members: Option<bool>,
+    /// Regex patterns of remarks to keep, all nodes are kept when empty.
+    /// Applied after the patterns of the request, see
+    /// [`RemarkFilter`](crate::utils::RemarkFilter)
+    pub include_remarks: Vec<String>,
+    /// Regex patterns of remarks to drop, applied like `include_remarks`
+    pub exclude_remarks: Vec<String>,
+    /// Proxy types dropped before any other processing
+    pub exclude_types: Vec<ProxyType>,
+    /// Ports nodes are moved to, see [`port_rewrite`](super::port_rewrite)
+    pub port_rewrite: PortRewriteMap,
+    /// Ports whose nodes are dropped, matched before `port_rewrite`
+    pub drop_ports: PortSet,
+    /// Whether to remove duplicate nodes before renaming
+    pub dedup: bool,
+    /// The properties that make two nodes duplicates
+    pub dedup_key: DedupKey,
+    /// Maximum number of subscriptions fetched at once, capped at the
+    /// `max_concur_threads` setting
+    pub fetch_concurrency: usize,
+    /// Whether a malformed proxy entry of a Clash subscription fails the
+    /// subscription instead of being skipped
+    pub strict_parse: bool,
+    /// Whether the proxy groups of Clash inputs are kept instead of being
+    /// regenerated, members pointing at dropped nodes are pruned
+    pub keep_input_groups: bool,
+    /// Whether the names of nodes from a labelled input (`tag:LABEL,url`)
+    /// start with `[LABEL] `, applied before rename rules
+    pub prefix_labels: bool,
+    /// Whether to drop nodes whose server does not accept a connection
+    pub probe_liveness: bool,
+    /// Maximum number of liveness probes running at once
+    pub probe_concurrency: usize,
+    /// Timeout of a single liveness probe in milliseconds
+    pub probe_timeout: u32,
+    /// Member used for proxy groups whose rules match no node, empty to
+    /// leave such groups empty
+    pub group_fallback: String,
     /// Whether to filter deprecated nodes
     pub filter_deprecated: bool,
     /// Whether to use new field names in Clash
     pub clash_new_field_name: bool,
+    /// Core Clash output is written for, see [`clash_core`](super::clash_core)
+    pub clash_core: ClashCore,
+    /// Whether an export for the vanilla core switches to Clash.Meta when
+    /// nodes only Clash.Meta supports are present
+    pub upgrade_clash_core: bool,
     /// Whether to use scripts in Clash
     pub clash_script: bool,
+    /// Whether Clash output lists nodes through a `proxy-providers` entry
+    /// instead of inline proxies
+    pub clash_use_providers: bool,
+    /// URL the Clash provider fetches its node list from, normally the
+    /// node list endpoint under the managed config prefix
+    pub clash_provider_url: String,
+    /// Health check of the Clash provider
+    pub provider_health_check: ProviderHealthCheck,
+    /// YAML of the `dns` section injected into Clash configs, either the
+    /// bare mapping or a document with a top-level `dns` key; empty leaves
+    /// the section of the base config untouched
+    pub clash_dns: String,
     /// Path to Surge SSR binary
     pub surge_ssr_path: String,
+    /// `block-quic` value of Surge 5 policies, `on`, `off` or `auto`; empty
+    /// leaves it to Surge
+    pub surge_block_quic: String,
     /// Prefix for managed configs
     pub managed_config_prefix: String,
-    /// QuantumultX device ID
+    /// QuantumultX device ID, added to the managed URLs QuantumultX fetches
     pub quanx_dev_id: String,
+    /// Whether QuantumultX output lists nodes through a `[server_remote]`
+    /// entry instead of inline servers
+    pub quanx_server_remote: bool,
+    /// URL the QuantumultX server remote fetches its node list from,
+    /// normally the node list endpoint under the managed config prefix
+    pub quanx_server_remote_url: String,
+    /// Tag of the generated QuantumultX server remote
+    pub quanx_remote_tag: String,
+    /// Update interval in seconds of generated QuantumultX remote resources
+    pub quanx_remote_interval: u32,
     /// UDP support flag
     pub udp: Option<bool>,
+    /// UDP over TCP version of Shadowsocks nodes enabling it without naming
+    /// one, `None` leaves it to the client (version 2 for sing-box)
+    pub uot_version: Option<u8>,
     /// TCP Fast Open support flag
     pub tfo: Option<bool>,
+    /// IP version preference of nodes that declare none, for Clash.Meta and
+    /// sing-box
+    pub ip_version: Option<IpVersion>,
+    /// Multiplexing of nodes that declare none, for sing-box and Clash.Meta
+    pub multiplex: Option<Multiplex>,
     /// Skip certificate verification flag
     pub skip_cert_verify: Option<bool>,
+    /// Which nodes `skip_cert_verify` applies to
+    pub skip_cert_verify_policy: SkipCertVerifyPolicy,
+    /// What happens to nodes the target cannot emit, see
+    /// [`unsupported`](super::unsupported)
+    pub on_unsupported: UnsupportedPolicy,
+    /// Nodes the target could not emit, kept unless they are skipped
+    pub unsupported_nodes: UnsupportedNodes,
+    /// Fallbacks for nodes the target can only emit without a feature, see
+    /// [`degrade`](super::degrade)
+    pub degrade: DegradeMap,
     /// TLS 1.3 support flag
     pub tls13: Option<bool>,
-    /// Whether to use classical ruleset in Clash
+    /// Whether Clash output references external rulesets as
+    /// `rule-providers` instead of inlining their rules, see
+    /// [`ClashRuleProviders`](crate::generator::ruleconvert::ClashRuleProviders)
     pub clash_classical_ruleset: bool,
+    /// Built-in sort order, takes precedence over `sort_script` when set
+    pub sort_key: Option<SortKey>,
     /// Script for sorting nodes
     pub sort_script: String,
     /// Style for Clash proxies output
@@ -54,10 +291,38 @@ pub struct ExtraSettings {
     pub clash_proxy_groups_style: String,
     /// Whether the export is authorized
     pub authorized: bool,
-    /// JavaScript runtime context (not implemented in Rust version)
+    /// Abort filtering when the filter script fails on any node, instead of
+    /// keeping the nodes it failed on
+    pub filter_script_strict: bool,
+    /// Memory the JavaScript runtime may allocate in bytes, 0 for no limit
+    pub script_memory_limit: usize,
+    /// Milliseconds a filter script may run for, 0 for no limit
+    pub script_timeout: u32,
+    /// Evaluate each script in a fresh JavaScript context, so no globals
+    /// survive from an earlier script
+    pub script_clean_context: bool,
+    /// Hook run on the node list once preprocessing is done
+    ///
+    /// It runs after the filter script, type exclusion, deduplication, remark
+    /// filters, liveness probing, emoji removal, rename, emoji, sorting and
+    /// duplicate name numbering, right before proxy groups and rules are
+    /// generated. Nodes it adds are emitted as they are, without any of
+    /// those stages applied.
+    pub node_transform: Option<NodeTransform>,
+    /// Stage times of the exports using these settings, see
+    /// [`ConvertStats`](crate::utils::ConvertStats)
+    pub stats: StatsRecorder,
+    /// Explain mode, `Some` records what each stage did to the nodes, see
+    /// [`explain`](crate::utils::explain)
+    pub explain: Option<ExplainRecorder>,
+    /// JavaScript context, created lazily on `js_runtime`
     #[cfg(feature = "js-runtime")]
     pub js_context: Option<rquickjs::Context>,
-    /// JavaScript runtime
+    /// JavaScript runtime, may be shared across exports
+    ///
+    /// `rquickjs::Runtime` is reference counted but not `Send`, so a shared
+    /// runtime must only be used from the thread that created it, e.g. one
+    /// runtime per worker thread kept in a `thread_local!`.
     #[cfg(feature = "js-runtime")]
     pub js_runtime: Option<rquickjs::Runtime>,
 }
@@ -67,57 +332,184 @@ impl std::fmt::Debug for ExtraSettings {
         f.debug_struct("ExtraSettings")
             .field("enable_rule_generator", &self.enable_rule_generator)
             .field("overwrite_original_rules", &self.overwrite_original_rules)
+            .field("block_rules", &self.block_rules)
             .field("rename_array", &self.rename_array)
             .field("emoji_array", &self.emoji_array)
             .field("add_emoji", &self.add_emoji)
+            .field("emoji_by_geoip", &self.emoji_by_geoip)
             .field("remove_emoji", &self.remove_emoji)
             .field("append_proxy_type", &self.append_proxy_type)
+            .field("append_index", &self.append_index)
+            .field("append_index_digits", &self.append_index_digits)
+            .field("append_index_separator", &self.append_index_separator)
+            .field("normalize_names", &self.normalize_names)
+            .field("normalize_punctuation", &self.normalize_punctuation)
+            .field("transliterate_regions", &self.transliterate_regions)
+            .field("region_names", &self.region_names)
+            .field("name_template", &self.name_template)
+            .field(
+                "name_template_after_rename",
+                &self.name_template_after_rename,
+            )
             .field("nodelist", &self.nodelist)
+            .field("ss_sip002", &self.ss_sip002)
+            .field("group_health_check", &self.group_health_check)
+            .field("collapse_groups", &self.collapse_groups)
+            .field("min_group_size", &self.min_group_size)
+            .field("group_type_override", &self.group_type_override)
+            .field("output_header", &self.output_header)
+            .field("deterministic", &self.deterministic)
+            .field("validate_output", &self.validate_output)
+            .field("random_fingerprint", &self.random_fingerprint)
+            .field("fingerprint_pool", &self.fingerprint_pool)
+            .field("fingerprint_seed", &self.fingerprint_seed)
             .field("sort_flag", &self.sort_flag)
+            .field("sort_group_members", &self.sort_group_members)
+            .field("include_remarks", &self.include_remarks)
+            .field("exclude_remarks", &self.exclude_remarks)
+            .field("exclude_types", &self.exclude_types)
+            .field("port_rewrite", &self.port_rewrite)
+            .field("drop_ports", &self.drop_ports)
+            .field("dedup", &self.dedup)
+            .field("dedup_key", &self.dedup_key)
+            .field("fetch_concurrency", &self.fetch_concurrency)
+            .field("strict_parse", &self.strict_parse)
+            .field("keep_input_groups", &self.keep_input_groups)
+            .field("prefix_labels", &self.prefix_labels)
+            .field("probe_liveness", &self.probe_liveness)
+            .field("probe_concurrency", &self.probe_concurrency)
+            .field("probe_timeout", &self.probe_timeout)
+            .field("group_fallback", &self.group_fallback)
             .field("filter_deprecated", &self.filter_deprecated)
             .field("clash_new_field_name", &self.clash_new_field_name)
+            .field("clash_core", &self.clash_core)
+            .field("upgrade_clash_core", &self.upgrade_clash_core)
             .field("clash_script", &self.clash_script)
+            .field("clash_use_providers", &self.clash_use_providers)
+            .field("clash_provider_url", &self.clash_provider_url)
+            .field("provider_health_check", &self.provider_health_check)
+            .field("clash_dns", &self.clash_dns)
             .field("surge_ssr_path", &self.surge_ssr_path)
+            .field("surge_block_quic", &self.surge_block_quic)
             .field("managed_config_prefix", &self.managed_config_prefix)
             .field("quanx_dev_id", &self.quanx_dev_id)
+            .field("quanx_server_remote", &self.quanx_server_remote)
+            .field("quanx_server_remote_url", &self.quanx_server_remote_url)
+            .field("quanx_remote_tag", &self.quanx_remote_tag)
+            .field("quanx_remote_interval", &self.quanx_remote_interval)
             .field("udp", &self.udp)
+            .field("uot_version", &self.uot_version)
+            .field("ip_version", &self.ip_version)
+            .field("multiplex", &self.multiplex)
             .field("tfo", &self.tfo)
             .field("skip_cert_verify", &self.skip_cert_verify)
+            .field("skip_cert_verify_policy", &self.skip_cert_verify_policy)
+            .field("on_unsupported", &self.on_unsupported)
+            .field("degrade", &self.degrade)
             .field("tls13", &self.tls13)
             .field("clash_classical_ruleset", &self.clash_classical_ruleset)
+            .field("sort_key", &self.sort_key)
             .field("sort_script", &self.sort_script)
             .field("clash_proxies_style", &self.clash_proxies_style)
             .field("clash_proxy_groups_style", &self.clash_proxy_groups_style)
             .field("authorized", &self.authorized)
+            .field("filter_script_strict", &self.filter_script_strict)
+            .field("script_memory_limit", &self.script_memory_limit)
+            .field("script_timeout", &self.script_timeout)
+            .field("script_clean_context", &self.script_clean_context)
+            .field("node_transform", &self.node_transform.is_some())
+            .field("stats", &self.stats.snapshot())
+            .field("explain", &self.explain.is_some())
             .finish()
     }
 }
 
+/// Starts from the global settings as they are now
+///
+/// The values taken from [`Settings`] are copied, a conversion keeps them
+/// when the settings are [reloaded](Settings::reload) while it runs.
 impl Default for ExtraSettings {
     fn default() -> Self {
-        let global = Settings::current();
+        let global = Settings::snapshot();
 
         ExtraSettings {
             enable_rule_generator: global.enable_rule_gen,
             overwrite_original_rules: global.overwrite_original_rules,
+            block_rules: Vec::new(),
             rename_array: Vec::new(),
             emoji_array: Vec::new(),
             add_emoji: false,
+            emoji_by_geoip: false,
             remove_emoji: false,
             append_proxy_type: false,
+            append_index: false,
+            append_index_digits: 2,
+            append_index_separator: " ".to_string(),
+            normalize_names: false,
+            normalize_punctuation: false,
+            transliterate_regions: false,
+            region_names: Vec::new(),
+            name_template: None,
+            name_template_after_rename: false,
             nodelist: false,
+            ss_sip002: false,
+            group_health_check: GroupHealthCheck::default(),
+            collapse_groups: false,
+            min_group_size: 0,
+            group_type_override: None,
+            output_header: None,
+            deterministic: false,
+            validate_output: false,
+            random_fingerprint: false,
+            fingerprint_pool: Vec::new(),
+            fingerprint_seed: None,
             sort_flag: false,
+            sort_group_members: None,
+            include_remarks: Vec::new(),
+            exclude_remarks: Vec::new(),
+            exclude_types: Vec::new(),
+            port_rewrite: PortRewriteMap::default(),
+            drop_ports: PortSet::default(),
+            dedup: false,
+            dedup_key: DedupKey::default(),
+            fetch_concurrency: 8,
+            strict_parse: false,
+            keep_input_groups: false,
+            prefix_labels: false,
+            probe_liveness: false,
+            probe_concurrency: DEFAULT_PROBE_CONCURRENCY,
+            probe_timeout: DEFAULT_PROBE_TIMEOUT,
+            group_fallback: "DIRECT".to_string(),
             filter_deprecated: false,
             clash_new_field_name: true,
+            clash_core: ClashCore::Meta,
+            upgrade_clash_core: false,
             clash_script: false,
+            clash_use_providers: false,
+            clash_provider_url: String::new(),
+            provider_health_check: ProviderHealthCheck::default(),
+            clash_dns: String::new(),
             surge_ssr_path: global.surge_ssr_path.clone(),
+            surge_block_quic: String::new(),
             managed_config_prefix: String::new(),
             quanx_dev_id: String::new(),
+            quanx_server_remote: false,
+            quanx_server_remote_url: String::new(),
+            quanx_remote_tag: "subconverter".to_string(),
+            quanx_remote_interval: 86400,
             udp: None,
+            uot_version: None,
+            ip_version: None,
+            multiplex: None,
             tfo: None,
             skip_cert_verify: None,
+            skip_cert_verify_policy: SkipCertVerifyPolicy::default(),
+            on_unsupported: UnsupportedPolicy::default(),
+            unsupported_nodes: UnsupportedNodes::default(),
+            degrade: DegradeMap::default(),
             tls13: None,
             clash_classical_ruleset: false,
+            sort_key: None,
             sort_script: String::new(),
             clash_proxies_style: if global.clash_proxies_style.is_empty() {
                 "flow".to_string()
@@ -130,6 +522,13 @@ impl Default for ExtraSettings {
                 global.clash_proxy_groups_style.clone()
             },
             authorized: false,
+            filter_script_strict: false,
+            script_memory_limit: global.script_memory_limit,
+            script_timeout: global.script_timeout,
+            script_clean_context: global.script_clean_context,
+            node_transform: None,
+            stats: StatsRecorder::default(),
+            explain: None,
             #[cfg(feature = "js-runtime")]
             js_context: None,
             #[cfg(feature = "js-runtime")]
@@ -138,39 +537,1100 @@ impl Default for ExtraSettings {
     }
 }
 
+impl ExtraSettings {
+    /// Create a builder starting from [`ExtraSettings::default()`], so global
+    /// `Settings` defaults still apply to every field that is not set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libsubconverter::models::{ExtraSettings, RegexMatchConfig};
+    ///
+    /// let ext = ExtraSettings::builder()
+    ///     .add_emoji(true)
+    ///     .remove_emoji(true)
+    ///     .rename(vec![RegexMatchConfig::new(
+    ///         "香港".to_string(),
+    ///         "HK".to_string(),
+    ///         String::new(),
+    ///     )])
+    ///     .sort(true)
+    ///     .clash_new_field_name(false)
+    ///     .udp(Some(true))
+    ///     .build();
+    ///
+    /// assert!(ext.add_emoji);
+    /// assert!(ext.sort_flag);
+    /// assert!(!ext.clash_new_field_name);
+    /// assert_eq!(ext.rename_array.len(), 1);
+    /// assert_eq!(ext.udp, Some(true));
+    /// ```
+    pub fn builder() -> ExtraSettingsBuilder {
+        ExtraSettingsBuilder::new()
+    }
+
+    /// Copy of the settings for another conversion
+    ///
+    /// The node transform hook cannot be shared and is left unset in the
+    /// copy. The copy shares the JavaScript runtime but gets a context of its
+    /// own. Stage times and the explain report start empty.
+    pub fn clone_settings(&self) -> Self {
+        ExtraSettings {
+            enable_rule_generator: self.enable_rule_generator,
+            overwrite_original_rules: self.overwrite_original_rules,
+            block_rules: self.block_rules.clone(),
+            rename_array: self.rename_array.clone(),
+            emoji_array: self.emoji_array.clone(),
+            add_emoji: self.add_emoji,
+            emoji_by_geoip: self.emoji_by_geoip,
+            remove_emoji: self.remove_emoji,
+            append_proxy_type: self.append_proxy_type,
+            append_index: self.append_index,
+            append_index_digits: self.append_index_digits,
+            append_index_separator: self.append_index_separator.clone(),
+            normalize_names: self.normalize_names,
+            normalize_punctuation: self.normalize_punctuation,
+            transliterate_regions: self.transliterate_regions,
+            region_names: self.region_names.clone(),
+            name_template: self.name_template.clone(),
+            name_template_after_rename: self.name_template_after_rename,
+            nodelist: self.nodelist,
+            ss_sip002: self.ss_sip002,
+            group_health_check: self.group_health_check.clone(),
+            collapse_groups: self.collapse_groups,
+            min_group_size: self.min_group_size,
+            group_type_override: self.group_type_override.clone(),
+            output_header: self.output_header.clone(),
+            deterministic: self.deterministic,
+            validate_output: self.validate_output,
+            random_fingerprint: self.random_fingerprint,
+            fingerprint_pool: self.fingerprint_pool.clone(),
+            fingerprint_seed: self.fingerprint_seed,
+            sort_flag: self.sort_flag,
+            sort_group_members: self.sort_group_members,
+            include_remarks: self.include_remarks.clone(),
+            exclude_remarks: self.exclude_remarks.clone(),
+            exclude_types: self.exclude_types.clone(),
+            port_rewrite: self.port_rewrite.clone(),
+            drop_ports: self.drop_ports.clone(),
+            dedup: self.dedup,
+            dedup_key: self.dedup_key,
+            fetch_concurrency: self.fetch_concurrency,
+            strict_parse: self.strict_parse,
+            keep_input_groups: self.keep_input_groups,
+            prefix_labels: self.prefix_labels,
+            probe_liveness: self.probe_liveness,
+            probe_concurrency: self.probe_concurrency,
+            probe_timeout: self.probe_timeout,
+            group_fallback: self.group_fallback.clone(),
+            filter_deprecated: self.filter_deprecated,
+            clash_new_field_name: self.clash_new_field_name,
+            clash_core: self.clash_core,
+            upgrade_clash_core: self.upgrade_clash_core,
+            clash_script: self.clash_script,
+            clash_use_providers: self.clash_use_providers,
+            clash_provider_url: self.clash_provider_url.clone(),
+            provider_health_check: self.provider_health_check.clone(),
+            clash_dns: self.clash_dns.clone(),
+            surge_ssr_path: self.surge_ssr_path.clone(),
+            surge_block_quic: self.surge_block_quic.clone(),
+            managed_config_prefix: self.managed_config_prefix.clone(),
+            quanx_dev_id: self.quanx_dev_id.clone(),
+            quanx_server_remote: self.quanx_server_remote,
+            quanx_server_remote_url: self.quanx_server_remote_url.clone(),
+            quanx_remote_tag: self.quanx_remote_tag.clone(),
+            quanx_remote_interval: self.quanx_remote_interval,
+            udp: self.udp,
+            uot_version: self.uot_version,
+            ip_version: self.ip_version,
+            multiplex: self.multiplex,
+            tfo: self.tfo,
+            skip_cert_verify: self.skip_cert_verify,
+            skip_cert_verify_policy: self.skip_cert_verify_policy,
+            on_unsupported: self.on_unsupported,
+            unsupported_nodes: UnsupportedNodes::default(),
+            degrade: self.degrade.clone(),
+            tls13: self.tls13,
+            clash_classical_ruleset: self.clash_classical_ruleset,
+            sort_key: self.sort_key,
+            sort_script: self.sort_script.clone(),
+            clash_proxies_style: self.clash_proxies_style.clone(),
+            clash_proxy_groups_style: self.clash_proxy_groups_style.clone(),
+            authorized: self.authorized,
+            filter_script_strict: self.filter_script_strict,
+            script_memory_limit: self.script_memory_limit,
+            script_timeout: self.script_timeout,
+            script_clean_context: self.script_clean_context,
+            node_transform: None,
+            stats: StatsRecorder::default(),
+            explain: self.explain.as_ref().map(|_| ExplainRecorder::default()),
+            #[cfg(feature = "js-runtime")]
+            js_context: None,
+            #[cfg(feature = "js-runtime")]
+            js_runtime: self.js_runtime.clone(),
+        }
+    }
+
+    /// Default skip certificate verification flag of a node leaving it unset
+    pub fn default_skip_cert_verify(&self, node: &Proxy) -> Option<bool> {
+        self.skip_cert_verify_policy
+            .default_for(self.skip_cert_verify, node)
+    }
+
+    /// Whether group members are put in node list order, by default when
+    /// nodes are sorted
+    pub fn sorts_group_members(&self) -> bool {
+        self.sort_group_members.unwrap_or(self.sort_flag)
+    }
+
+    /// Leave out a node `target` cannot emit as `on_unsupported` says
+    ///
+    /// The node is logged and recorded in explain mode. Under
+    /// [`UnsupportedPolicy::Error`] the caller of the generator fails the
+    /// conversion with [`unsupported_error`](Self::unsupported_error), under
+    /// [`UnsupportedPolicy::Comment`] line based generators list the node
+    /// with [`UnsupportedNodes::take_comments`].
+    pub fn skip_node(&self, target: &str, remark: &str, reason: impl std::fmt::Display) {
+        log::warn!("Skipping {} node '{}': {}", target, remark, reason);
+        if let Some(explain) = &self.explain {
+            explain.skipped(target, remark, reason.to_string());
+        }
+        if self.on_unsupported != UnsupportedPolicy::Skip {
+            self.unsupported_nodes
+                .record(target, remark, reason.to_string());
+        }
+    }
+
+    /// Degrade `node` with the fallback `degrade` so `target` can emit it
+    /// instead of skipping it
+    ///
+    /// Returns `false` and leaves the node alone when the fallback is not
+    /// enabled or does not fit the node. Degraded nodes are logged.
+    pub fn degrade_node(&self, target: &str, node: &mut Proxy, degrade: Degrade) -> bool {
+        if !self.degrade.allows(degrade) {
+            return false;
+        }
+        if let Some(refusal) = degrade.refusal(node) {
+            log::info!(
+                "Not degrading {} node '{}' to {}: {}",
+                target,
+                node.remark,
+                degrade.to_name(),
+                refusal
+            );
+            return false;
+        }
+        log::info!(
+            "Degrading {} node '{}' from {} to {}: {} dropped",
+            target,
+            node.remark,
+            degrade.from_name(),
+            degrade.to_name(),
+            degrade.dropped()
+        );
+        degrade.apply(node);
+        true
+    }
+
+    /// Comment lines listing the nodes `target` left out, under
+    /// [`UnsupportedPolicy::Comment`]
+    pub fn unsupported_comments(&self, target: &str, prefix: &str) -> Vec<String> {
+        if self.on_unsupported != UnsupportedPolicy::Comment {
+            return Vec::new();
+        }
+        self.unsupported_nodes.take_comments(target, prefix)
+    }
+
+    /// Error naming the nodes left out under [`UnsupportedPolicy::Error`]
+    pub fn unsupported_error(&self) -> Option<String> {
+        if self.on_unsupported != UnsupportedPolicy::Error {
+            return None;
+        }
+        self.unsupported_nodes.error_message()
+    }
+}
+
+/// Builder for ExtraSettings
+///
+/// Lets library consumers configure an export without touching the public
+/// fields or the JavaScript runtime handles directly.
+///
+/// # Examples
+///
+/// ```
+/// use libsubconverter::models::extra_settings::ExtraSettingsBuilder;
+///
+/// // A node list export with default tribool values for every node
+/// let ext = ExtraSettingsBuilder::new()
+///     .nodelist(true)
+///     .enable_rule_generator(false)
+///     .tfo(Some(false))
+///     .skip_cert_verify(Some(true))
+///     .build();
+///
+/// assert!(ext.nodelist);
+/// assert!(!ext.enable_rule_generator);
+/// assert_eq!(ext.skip_cert_verify, Some(true));
+/// ```
+#[derive(Debug, Default)]
+pub struct ExtraSettingsBuilder {
+    settings: ExtraSettings,
+}
+
+impl ExtraSettingsBuilder {
+    /// Create a new builder with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable the rule generator
+    pub fn enable_rule_generator(mut self, value: bool) -> Self {
+        self.settings.enable_rule_generator = value;
+        self
+    }
+
+    /// Overwrite the rules of the base configuration
+    pub fn overwrite_original_rules(mut self, value: bool) -> Self {
+        self.settings.overwrite_original_rules = value;
+        self
+    }
+
+    /// Set the blocklist Clash and sing-box outputs reject
+    pub fn block_rules(mut self, value: Vec<String>) -> Self {
+        self.settings.block_rules = value;
+        self
+    }
+
+    /// Set the rename operations applied to node remarks
+    pub fn rename(mut self, value: RegexMatchConfigs) -> Self {
+        self.settings.rename_array = value;
+        self
+    }
+
+    /// Set the emoji operations applied to node remarks
+    pub fn emoji(mut self, value: RegexMatchConfigs) -> Self {
+        self.settings.emoji_array = value;
+        self
+    }
+
+    /// Add emoji to node remarks
+    pub fn add_emoji(mut self, value: bool) -> Self {
+        self.settings.add_emoji = value;
+        self
+    }
+
+    /// Pick emoji from the GeoIP region of each node's server
+    pub fn emoji_by_geoip(mut self, value: bool) -> Self {
+        self.settings.emoji_by_geoip = value;
+        self
+    }
+
+    /// Remove existing emoji from node remarks
+    pub fn remove_emoji(mut self, value: bool) -> Self {
+        self.settings.remove_emoji = value;
+        self
+    }
+
+    /// Prefix node remarks with the proxy type
+    pub fn append_proxy_type(mut self, value: bool) -> Self {
+        self.settings.append_proxy_type = value;
+        self
+    }
+
+    /// Number nodes that share a name after renaming
+    pub fn append_index(mut self, value: bool) -> Self {
+        self.settings.append_index = value;
+        self
+    }
+
+    /// Minimum number of digits of an appended index
+    pub fn append_index_digits(mut self, value: usize) -> Self {
+        self.settings.append_index_digits = value;
+        self
+    }
+
+    /// Text put between a name and its appended index
+    pub fn append_index_separator(mut self, value: impl Into<String>) -> Self {
+        self.settings.append_index_separator = value.into();
+        self
+    }
+
+    /// Name unnamed nodes and number duplicate names right after parsing
+    pub fn normalize_names(mut self, value: bool) -> Self {
+        self.settings.normalize_names = value;
+        self
+    }
+
+    /// Turn full-width and CJK punctuation in names into ASCII
+    pub fn normalize_punctuation(mut self, value: bool) -> Self {
+        self.settings.normalize_punctuation = value;
+        self
+    }
+
+    /// Replace known region names with their English name
+    pub fn transliterate_regions(mut self, value: bool) -> Self {
+        self.settings.transliterate_regions = value;
+        self
+    }
+
+    /// Add a region name replacement, tried before the built-in ones
+    pub fn region_name(mut self, name: impl Into<String>, replacement: impl Into<String>) -> Self {
+        self.settings
+            .region_names
+            .push((name.into(), replacement.into()));
+        self
+    }
+
+    /// Replace node names with a template such as `{region} | {type}`
+    pub fn name_template(mut self, template: &str) -> Self {
+        self.settings.name_template = Some(NameTemplate::new(template));
+        self
+    }
+
+    /// Run the name template after rename rules instead of before them
+    pub fn name_template_after_rename(mut self, value: bool) -> Self {
+        self.settings.name_template_after_rename = value;
+        self
+    }
+
+    /// Output only the node list instead of a full config
+    pub fn nodelist(mut self, value: bool) -> Self {
+        self.settings.nodelist = value;
+        self
+    }
+
+    /// Write Shadowsocks links as canonical SIP002 URIs
+    pub fn ss_sip002(mut self, value: bool) -> Self {
+        self.settings.ss_sip002 = value;
+        self
+    }
+
+    /// Health check of proxy groups that set none
+    pub fn group_health_check(mut self, value: GroupHealthCheck) -> Self {
+        self.settings.group_health_check = value;
+        self
+    }
+
+    /// Merge proxy groups sharing a name
+    pub fn collapse_groups(mut self, value: bool) -> Self {
+        self.settings.collapse_groups = value;
+        self
+    }
+
+    /// Drop node groups with fewer nodes than `value`, folding their nodes
+    /// into the groups listing them
+    pub fn min_group_size(mut self, value: usize) -> Self {
+        self.settings.min_group_size = value;
+        self
+    }
+
+    /// Rewrite the type of the configured proxy groups
+    pub fn group_type_override(mut self, value: Option<GroupTypeOverride>) -> Self {
+        self.settings.group_type_override = value;
+        self
+    }
+
+    /// Template of the comment block put ahead of the output
+    pub fn output_header(mut self, template: Option<String>) -> Self {
+        self.settings.output_header = template;
+        self
+    }
+
+    /// Produce byte-identical output for identical input
+    pub fn deterministic(mut self, value: bool) -> Self {
+        self.settings.deterministic = value;
+        self
+    }
+
+    /// Reject Clash and sing-box output that does not parse back
+    pub fn validate_output(mut self, value: bool) -> Self {
+        self.settings.validate_output = value;
+        self
+    }
+
+    /// Give TLS nodes without a client fingerprint a random one
+    pub fn random_fingerprint(mut self, value: bool) -> Self {
+        self.settings.random_fingerprint = value;
+        self
+    }
+
+    /// Set the fingerprints random ones are picked from
+    pub fn fingerprint_pool(mut self, value: Vec<String>) -> Self {
+        self.settings.fingerprint_pool = value;
+        self
+    }
+
+    /// Set the seed of random fingerprint picks
+    pub fn fingerprint_seed(mut self, value: Option<u64>) -> Self {
+        self.settings.fingerprint_seed = value;
+        self
+    }
+
+    /// Sort nodes before export
+    pub fn sort(mut self, value: bool) -> Self {
+        self.settings.sort_flag = value;
+        self
+    }
+
+    /// List the nodes of each group in node list order, `None` to do so
+    /// when sorting nodes
+    pub fn sort_group_members(mut self, value: Option<bool>) -> Self {
+        self.settings.sort_group_members = value;
+        self
+    }
+
+    /// Keep only nodes whose remark matches one of these regex patterns
+    pub fn include_remarks(mut self, value: Vec<String>) -> Self {
+        self.settings.include_remarks = value;
+        self
+    }
+
+    /// Drop nodes whose remark matches one of these regex patterns
+    pub fn exclude_remarks(mut self, value: Vec<String>) -> Self {
+        self.settings.exclude_remarks = value;
+        self
+    }
+
+    /// Drop every node of these proxy types
+    pub fn exclude_types(mut self, value: Vec<ProxyType>) -> Self {
+        self.settings.exclude_types = value;
+        self
+    }
+
+    /// Move nodes on matching ports to other ports
+    pub fn port_rewrite(mut self, value: PortRewriteMap) -> Self {
+        self.settings.port_rewrite = value;
+        self
+    }
+
+    /// Drop every node on these ports
+    pub fn drop_ports(mut self, value: PortSet) -> Self {
+        self.settings.drop_ports = value;
+        self
+    }
+
+    /// Remove duplicate nodes before renaming
+    pub fn dedup(mut self, value: bool) -> Self {
+        self.settings.dedup = value;
+        self
+    }
+
+    /// Set the properties that make two nodes duplicates
+    pub fn dedup_key(mut self, value: DedupKey) -> Self {
+        self.settings.dedup_key = value;
+        self
+    }
+
+    /// Drop nodes that fail a liveness probe
+    pub fn probe_liveness(mut self, value: bool) -> Self {
+        self.settings.probe_liveness = value;
+        self
+    }
+
+    /// Set the maximum number of subscriptions fetched at once
+    pub fn fetch_concurrency(mut self, value: usize) -> Self {
+        self.settings.fetch_concurrency = value;
+        self
+    }
+
+    /// Fail on malformed Clash proxy entries instead of skipping them
+    pub fn strict_parse(mut self, value: bool) -> Self {
+        self.settings.strict_parse = value;
+        self
+    }
+
+    /// Keep the proxy groups of Clash inputs instead of regenerating them
+    pub fn keep_input_groups(mut self, value: bool) -> Self {
+        self.settings.keep_input_groups = value;
+        self
+    }
+
+    /// Prefix the names of nodes from labelled inputs with the label
+    pub fn prefix_labels(mut self, value: bool) -> Self {
+        self.settings.prefix_labels = value;
+        self
+    }
+
+    /// Set the maximum number of liveness probes running at once
+    pub fn probe_concurrency(mut self, value: usize) -> Self {
+        self.settings.probe_concurrency = value;
+        self
+    }
+
+    /// Set the liveness probe timeout in milliseconds
+    pub fn probe_timeout(mut self, value: u32) -> Self {
+        self.settings.probe_timeout = value;
+        self
+    }
+
+    /// Set the member used for proxy groups that match no node
+    pub fn group_fallback(mut self, value: impl Into<String>) -> Self {
+        self.settings.group_fallback = value.into();
+        self
+    }
+
+    /// Filter out nodes using deprecated ciphers
+    pub fn filter_deprecated(mut self, value: bool) -> Self {
+        self.settings.filter_deprecated = value;
+        self
+    }
+
+    /// Use the new field names in Clash output
+    pub fn clash_new_field_name(mut self, value: bool) -> Self {
+        self.settings.clash_new_field_name = value;
+        self
+    }
+
+    /// Set the core Clash output is written for
+    pub fn clash_core(mut self, value: ClashCore) -> Self {
+        self.settings.clash_core = value;
+        self
+    }
+
+    /// Switch vanilla Clash exports to Clash.Meta when they need it
+    pub fn upgrade_clash_core(mut self, value: bool) -> Self {
+        self.settings.upgrade_clash_core = value;
+        self
+    }
+
+    /// Use scripts in Clash output
+    pub fn clash_script(mut self, value: bool) -> Self {
+        self.settings.clash_script = value;
+        self
+    }
+
+    /// Reference nodes through a Clash `proxy-providers` entry
+    pub fn clash_use_providers(mut self, value: bool) -> Self {
+        self.settings.clash_use_providers = value;
+        self
+    }
+
+    /// Set the URL the Clash provider fetches its node list from
+    pub fn clash_provider_url(mut self, value: impl Into<String>) -> Self {
+        self.settings.clash_provider_url = value.into();
+        self
+    }
+
+    /// Set the health check of the Clash provider
+    pub fn provider_health_check(mut self, value: ProviderHealthCheck) -> Self {
+        self.settings.provider_health_check = value;
+        self
+    }
+
+    /// Set the YAML of the `dns` section injected into Clash configs
+    pub fn clash_dns(mut self, value: impl Into<String>) -> Self {
+        self.settings.clash_dns = value.into();
+        self
+    }
+
+    /// Set the path to the Surge SSR binary
+    pub fn surge_ssr_path(mut self, value: impl Into<String>) -> Self {
+        self.settings.surge_ssr_path = value.into();
+        self
+    }
+
+    /// Set the `block-quic` value of Surge 5 policies
+    pub fn surge_block_quic(mut self, value: impl Into<String>) -> Self {
+        self.settings.surge_block_quic = value.into();
+        self
+    }
+
+    /// Set the prefix for managed configs
+    pub fn managed_config_prefix(mut self, value: impl Into<String>) -> Self {
+        self.settings.managed_config_prefix = value.into();
+        self
+    }
+
+    /// Set the QuantumultX device ID
+    pub fn quanx_dev_id(mut self, value: impl Into<String>) -> Self {
+        self.settings.quanx_dev_id = value.into();
+        self
+    }
+
+    /// List QuantumultX nodes through a `[server_remote]` entry
+    pub fn quanx_server_remote(mut self, value: bool) -> Self {
+        self.settings.quanx_server_remote = value;
+        self
+    }
+
+    /// Set the URL the QuantumultX server remote fetches its node list from
+    pub fn quanx_server_remote_url(mut self, value: impl Into<String>) -> Self {
+        self.settings.quanx_server_remote_url = value.into();
+        self
+    }
+
+    /// Set the tag of the generated QuantumultX server remote
+    pub fn quanx_remote_tag(mut self, value: impl Into<String>) -> Self {
+        self.settings.quanx_remote_tag = value.into();
+        self
+    }
+
+    /// Set the update interval of generated QuantumultX remote resources
+    pub fn quanx_remote_interval(mut self, value: u32) -> Self {
+        self.settings.quanx_remote_interval = value;
+        self
+    }
+
+    /// Set the default UDP flag for nodes
+    pub fn udp(mut self, value: Option<bool>) -> Self {
+        self.settings.udp = value;
+        self
+    }
+
+    /// Set the UDP over TCP version of nodes that name none
+    pub fn uot_version(mut self, value: Option<u8>) -> Self {
+        self.settings.uot_version = value;
+        self
+    }
+
+    /// Set the IP version preference of nodes that declare none
+    pub fn ip_version(mut self, value: Option<IpVersion>) -> Self {
+        self.settings.ip_version = value;
+        self
+    }
+
+    /// Set the multiplexing of nodes that declare none, emitted as sing-box
+    /// `multiplex` and Clash.Meta `smux`
+    pub fn multiplex(mut self, value: Option<Multiplex>) -> Self {
+        self.settings.multiplex = value;
+        self
+    }
+
+    /// Set the default TCP Fast Open flag for nodes
+    pub fn tfo(mut self, value: Option<bool>) -> Self {
+        self.settings.tfo = value;
+        self
+    }
+
+    /// Set the default skip certificate verification flag for nodes
+    pub fn skip_cert_verify(mut self, value: Option<bool>) -> Self {
+        self.settings.skip_cert_verify = value;
+        self
+    }
+
+    /// Set which nodes the default skip certificate verification flag
+    /// applies to
+    pub fn skip_cert_verify_policy(mut self, value: SkipCertVerifyPolicy) -> Self {
+        self.settings.skip_cert_verify_policy = value;
+        self
+    }
+
+    /// Set what happens to nodes the target cannot emit
+    pub fn on_unsupported(mut self, value: UnsupportedPolicy) -> Self {
+        self.settings.on_unsupported = value;
+        self
+    }
+
+    /// Set the fallbacks for nodes the target can only emit degraded
+    pub fn degrade(mut self, value: DegradeMap) -> Self {
+        self.settings.degrade = value;
+        self
+    }
+
+    /// Set the default TLS 1.3 flag for nodes
+    pub fn tls13(mut self, value: Option<bool>) -> Self {
+        self.settings.tls13 = value;
+        self
+    }
+
+    /// Reference external rulesets as `rule-providers` in Clash output
+    pub fn clash_classical_ruleset(mut self, value: bool) -> Self {
+        self.settings.clash_classical_ruleset = value;
+        self
+    }
+
+    /// Set the built-in sort order used instead of the sort script
+    pub fn sort_key(mut self, value: Option<SortKey>) -> Self {
+        self.settings.sort_key = value;
+        self
+    }
+
+    /// Set the script used to sort nodes
+    pub fn sort_script(mut self, value: impl Into<String>) -> Self {
+        self.settings.sort_script = value.into();
+        self
+    }
+
+    /// Set the style for Clash proxies output
+    pub fn clash_proxies_style(mut self, value: impl Into<String>) -> Self {
+        self.settings.clash_proxies_style = value.into();
+        self
+    }
+
+    /// Set the style for Clash proxy groups output
+    pub fn clash_proxy_groups_style(mut self, value: impl Into<String>) -> Self {
+        self.settings.clash_proxy_groups_style = value.into();
+        self
+    }
+
+    /// Mark the export as authorized
+    pub fn authorized(mut self, value: bool) -> Self {
+        self.settings.authorized = value;
+        self
+    }
+
+    /// Abort filtering when the filter script fails on any node
+    pub fn filter_script_strict(mut self, value: bool) -> Self {
+        self.settings.filter_script_strict = value;
+        self
+    }
+
+    /// Set the memory the JavaScript runtime may allocate in bytes
+    pub fn script_memory_limit(mut self, bytes: usize) -> Self {
+        self.settings.script_memory_limit = bytes;
+        self
+    }
+
+    /// Set the milliseconds a filter script may run for
+    pub fn script_timeout(mut self, millis: u32) -> Self {
+        self.settings.script_timeout = millis;
+        self
+    }
+
+    /// Set whether each script is evaluated in a fresh JavaScript context
+    pub fn script_clean_context(mut self, value: bool) -> Self {
+        self.settings.script_clean_context = value;
+        self
+    }
+
+    /// Set a hook run on the node list once preprocessing is done
+    pub fn node_transform(
+        mut self,
+        transform: impl FnMut(&mut Vec<Proxy>) + Send + 'static,
+    ) -> Self {
+        self.settings.node_transform = Some(Box::new(transform));
+        self
+    }
+
+    /// Record stage times into `recorder`, e.g. to sum them over several
+    /// exports
+    pub fn stats(mut self, recorder: StatsRecorder) -> Self {
+        self.settings.stats = recorder;
+        self
+    }
+
+    /// Record what each stage does to the nodes, see
+    /// [`explain`](crate::utils::explain)
+    pub fn explain(mut self, enabled: bool) -> Self {
+        self.settings.explain = enabled.then(ExplainRecorder::default);
+        self
+    }
+
+    /// Reuse a long-lived JavaScript runtime instead of creating one per export
+    ///
+    /// The runtime is cloned by reference, so every `ExtraSettings` built with
+    /// the same runtime shares its memory and compiled state. See
+    /// [`ExtraSettings::js_runtime`] for the thread-safety expectations.
+    #[cfg(feature = "js-runtime")]
+    pub fn js_runtime(mut self, runtime: rquickjs::Runtime) -> Self {
+        self.settings.js_runtime = Some(runtime);
+        self.settings.js_context = None;
+        self
+    }
+
+    /// Reuse a long-lived JavaScript context together with its runtime
+    ///
+    /// Scripts evaluated in a shared context see each other's globals when
+    /// `script_clean_context` is disabled. The context needs the eval
+    /// intrinsic, so create it with [`rquickjs::Context::full`].
+    #[cfg(feature = "js-runtime")]
+    pub fn js_context(mut self, runtime: rquickjs::Runtime, context: rquickjs::Context) -> Self {
+        self.settings.js_runtime = Some(runtime);
+        self.settings.js_context = Some(context);
+        self
+    }
+
+    /// Build the ExtraSettings
+    pub fn build(self) -> ExtraSettings {
+        self.settings
+    }
+}
+
+/// The order nodes are sorted in without a sort script: by the bytes of
+/// their remarks, nodes of unknown type last
+fn sort_nodes_by_remark(nodes: &mut [Proxy]) {
+    nodes.sort_by(|a, b| {
+        if a.proxy_type == ProxyType::Unknown {
+            return Ordering::Greater;
+        }
+        if b.proxy_type == ProxyType::Unknown {
+            return Ordering::Less;
+        }
+        a.remark.cmp(&b.remark)
+    });
+}
+
+/// The pending exception of a context
+#[cfg(feature = "js-runtime")]
+enum JsException {
+    /// The memory limit was reached
+    OutOfMemory,
+    /// Anything else, as a readable message
+    Thrown(String),
+}
+
+/// Take the pending exception of a context
+///
+/// QuickJS throws `null` when the memory limit leaves no room for an error
+/// object, which is what tells running out of memory apart from an error a
+/// script throws: a message can be forged, and the uncatchable errors are
+/// the interrupts of [`arm_script_timeout`](ExtraSettings::arm_script_timeout).
+#[cfg(feature = "js-runtime")]
+fn take_js_exception(ctx: &rquickjs::Ctx) -> JsException {
+    let value = ctx.catch();
+    if value.is_null() {
+        return JsException::OutOfMemory;
+    }
+    if let Some(exception) = value.as_exception() {
+        return JsException::Thrown(exception.message().unwrap_or_default());
+    }
+    JsException::Thrown(
+        value
+            .as_string()
+            .and_then(|s| s.to_string().ok())
+            .unwrap_or_else(|| format!("{:?}", value)),
+    )
+}
+
 #[cfg(feature = "js-runtime")]
 impl ExtraSettings {
+    /// Make sure a JavaScript context exists, reusing the runtime if one was
+    /// provided and creating both otherwise
     pub fn init_js_context(&mut self) {
         if self.js_runtime.is_none() {
             self.js_runtime = Some(rquickjs::Runtime::new().unwrap());
+            self.js_context = None;
+        }
+        // A shared runtime takes the limit of the export using it last
+        if let Some(runtime) = &self.js_runtime {
+            runtime.set_memory_limit(self.script_memory_limit);
+        }
+        if self.js_context.is_none() {
+            // `Context::base` lacks the eval intrinsic needed to run scripts
             self.js_context =
-                Some(rquickjs::Context::base(&self.js_runtime.as_ref().unwrap()).unwrap());
+                Some(rquickjs::Context::full(self.js_runtime.as_ref().unwrap()).unwrap());
+        }
+    }
+
+    /// Replace the JavaScript context with a fresh one on the same runtime, so
+    /// no globals from earlier scripts survive
+    pub fn reset_js_context(&mut self) {
+        self.js_context = None;
+        self.init_js_context();
+    }
+
+    /// Replace the context after `error` when it is a script that ran past
+    /// its limits, so the following scripts start from a clean one
+    pub fn reset_js_context_after(&mut self, error: &(dyn std::error::Error + 'static)) {
+        if error
+            .downcast_ref::<ScriptError>()
+            .is_some_and(ScriptError::is_limit_exceeded)
+        {
+            self.reset_js_context();
+        }
+    }
+
+    /// Prepare the context for evaluating a script that defines `entry`
+    ///
+    /// A fresh context is used when `script_clean_context` is enabled.
+    /// Otherwise the previous definition of `entry` is removed so a script
+    /// that fails to define it can not silently run a stale function left
+    /// behind by an earlier export.
+    fn prepare_js_context(&mut self, entry: &str) {
+        if self.script_clean_context {
+            self.reset_js_context();
+        } else {
+            self.init_js_context();
+        }
+        self.remove_js_global(entry);
+    }
+
+    /// Unset a global. Functions declared by a script are not deletable, so
+    /// the binding is overwritten with `undefined`.
+    fn remove_js_global(&self, name: &str) {
+        if let Some(context) = &self.js_context {
+            context.with(|ctx| {
+                let _ = ctx.globals().set(name, rquickjs::Undefined);
+            });
         }
     }
 
+    /// Filter nodes with the `filter` function defined by a script
     pub fn eval_filter_function(
         &mut self,
         nodes: &mut Vec<Proxy>,
         source_str: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.init_js_context();
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        self.eval_named_filter_function(nodes, source_str, DEFAULT_FILTER_FUNCTION)
+    }
+
+    /// Filter nodes with a function of the given name defined by a script
+    ///
+    /// The function is called with each node and keeps it when it returns
+    /// `true`. Returns [`ScriptError::FunctionNotFound`] if the script does
+    /// not define `function_name`, leaving the nodes untouched.
+    ///
+    /// Nodes the function throws on are kept and counted in
+    /// [`FilterSummary::failed`]. With `filter_script_strict` any failure
+    /// aborts with [`ScriptError::FilterFailed`] and no node is removed.
+    ///
+    /// A script running longer than `script_timeout` or allocating beyond
+    /// `script_memory_limit` fails with [`ScriptError::TimedOut`] or
+    /// [`ScriptError::OutOfMemory`], also leaving the nodes untouched.
+    pub fn eval_named_filter_function(
+        &mut self,
+        nodes: &mut Vec<Proxy>,
+        source_str: &str,
+        function_name: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        self.prepare_js_context(function_name);
+        self.run_filter_function(nodes, source_str, function_name)
+    }
+
+    /// Filter nodes with several scripts in turn, each defining its own
+    /// `function_name` and seeing only the nodes the previous ones kept
+    ///
+    /// The scripts share one context, so helpers defined by an earlier script
+    /// are visible to later ones. A script that fails as
+    /// [`eval_named_filter_function`](Self::eval_named_filter_function) would
+    /// aborts the chain with [`ScriptError::ChainFailed`] naming it, and the
+    /// nodes are left as they were before the first script. A script
+    /// exceeding a resource limit aborts it with that limit's error instead.
+    /// A chain of one script reports its failure the same way, as index 0.
+    pub fn eval_filter_chain(
+        &mut self,
+        nodes: &mut Vec<Proxy>,
+        sources: &[String],
+        function_name: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        self.prepare_js_context(function_name);
+        let original = nodes.clone();
+        let mut summary = FilterSummary {
+            kept: nodes.len(),
+            ..Default::default()
+        };
+        for (index, source) in sources.iter().enumerate() {
+            // A script must define the function itself, not reuse the last one
+            self.remove_js_global(function_name);
+            match self.run_filter_function(nodes, source, function_name) {
+                Ok(step) => {
+                    summary.kept = step.kept;
+                    summary.removed += step.removed;
+                    summary.failed += step.failed;
+                }
+                Err(e) => {
+                    *nodes = original;
+                    if e.downcast_ref::<ScriptError>()
+                        .is_some_and(ScriptError::is_limit_exceeded)
+                    {
+                        return Err(e);
+                    }
+                    return Err(ScriptError::ChainFailed {
+                        index,
+                        message: e.to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Interrupt scripts of the runtime once `script_timeout` has passed
+    ///
+    /// The returned flag is raised when a script was interrupted.
+    fn arm_script_timeout(&self) -> Arc<AtomicBool> {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        if let (Some(runtime), true) = (&self.js_runtime, self.script_timeout > 0) {
+            let timeout = Duration::from_millis(self.script_timeout.into());
+            let stopwatch = Stopwatch::start();
+            let flag = interrupted.clone();
+            runtime.set_interrupt_handler(Some(Box::new(move || {
+                let expired = stopwatch.elapsed() >= timeout;
+                if expired {
+                    flag.store(true, AtomicOrdering::Relaxed);
+                }
+                expired
+            })));
+        }
+        interrupted
+    }
+
+    /// Remove the interrupt set by [`arm_script_timeout`](Self::arm_script_timeout),
+    /// failing the script `name` when it was interrupted
+    fn disarm_script_timeout(
+        &self,
+        interrupted: &AtomicBool,
+        name: &str,
+    ) -> Result<(), ScriptError> {
+        if let Some(runtime) = &self.js_runtime {
+            runtime.set_interrupt_handler(None);
+        }
+        if interrupted.load(AtomicOrdering::Relaxed) {
+            return Err(ScriptError::TimedOut {
+                name: name.to_string(),
+                timeout: self.script_timeout,
+            });
+        }
+        Ok(())
+    }
+
+    /// Run a filter script within `script_timeout` and `script_memory_limit`
+    ///
+    /// A script exceeding either fails with [`ScriptError::TimedOut`] or
+    /// [`ScriptError::OutOfMemory`] before any node is removed, and its
+    /// context is dropped so the next script starts from a clean one.
+    fn run_filter_function(
+        &mut self,
+        nodes: &mut Vec<Proxy>,
+        source_str: &str,
+        function_name: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        let _timer = self.stats.time(crate::utils::ConvertStage::Script);
+        let interrupted = self.arm_script_timeout();
+        let result = self.eval_filter_script(nodes, source_str, function_name);
+        let result = match self.disarm_script_timeout(&interrupted, function_name) {
+            Ok(()) => result,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = &result {
+            if let Some(e) = e
+                .downcast_ref::<ScriptError>()
+                .filter(|e| e.is_limit_exceeded())
+            {
+                log::error!("{}", e);
+                self.js_context = None;
+            }
+        }
+        result
+    }
+
+    fn eval_filter_script(
+        &mut self,
+        nodes: &mut Vec<Proxy>,
+        source_str: &str,
+        function_name: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        let out_of_memory = ScriptError::OutOfMemory {
+            name: function_name.to_string(),
+            limit: self.script_memory_limit,
+        };
         if let Some(context) = &mut self.js_context {
             let mut error_thrown = None;
+            let mut exhausted = false;
+            let mut missing_function = false;
+            let mut decisions: Vec<Result<bool, String>> = Vec::with_capacity(nodes.len());
             context.with(|ctx| {
                 match ctx.eval::<(), &str>(source_str) {
                     Ok(_) => (),
                     Err(e) => {
                         match e {
-                            rquickjs::Error::Exception => {
-                                log::error!(
-                                    "JavaScript eval throw exception: {}",
-                                    ctx.catch()
-                                        .try_into_string()
-                                        .unwrap()
-                                        .to_string()
-                                        .unwrap_or_default()
-                                );
-                            }
+                            rquickjs::Error::Exception => match take_js_exception(&ctx) {
+                                JsException::OutOfMemory => exhausted = true,
+                                JsException::Thrown(message) => {
+                                    log::error!("JavaScript eval throw exception: {}", message);
+                                }
+                            },
                             _ => {
                                 log::error!("JavaScript eval error: {}", e);
                             }
@@ -180,31 +1640,78 @@ impl ExtraSettings {
                     }
                 };
                 let filter_evaluated: rquickjs::Function =
-                    match ctx.globals().get::<_, rquickjs::Function>("filter") {
+                    match ctx.globals().get::<_, rquickjs::Function>(function_name) {
                         Ok(value) => value,
-                        Err(e) => {
-                            log::error!("JavaScript eval get function error: {}", e);
+                        Err(_) => {
+                            missing_function = true;
                             return;
                         }
                     };
 
-                nodes.retain_mut(|node| {
-                    match filter_evaluated.call::<(Proxy,), bool>((node.clone(),)) {
-                        Ok(value) => value,
-                        Err(e) => {
-                            log::error!("JavaScript eval call function error: {}", e);
-                            false
-                        }
-                    }
-                });
+                for node in nodes.iter() {
+                    let decision = match filter_evaluated.call::<(Proxy,), bool>((node.clone(),)) {
+                        Ok(value) => Ok(value),
+                        Err(rquickjs::Error::Exception) => match take_js_exception(&ctx) {
+                            JsException::OutOfMemory => {
+                                exhausted = true;
+                                return;
+                            }
+                            JsException::Thrown(message) => Err(message),
+                        },
+                        Err(e) => Err(e.to_string()),
+                    };
+                    decisions.push(decision.map_err(|e| format!("'{}': {}", node.remark, e)));
+                }
             });
-            match error_thrown {
-                Some(e) => Err(e.into()),
-                None => {
-                    log::info!("Filter function evaluated successfully");
-                    Ok(())
+            if exhausted {
+                return Err(out_of_memory.into());
+            }
+            if missing_function {
+                return Err(ScriptError::FunctionNotFound {
+                    name: function_name.to_string(),
+                }
+                .into());
+            }
+            if let Some(e) = error_thrown {
+                return Err(e.into());
+            }
+
+            let errors: Vec<String> = decisions
+                .iter()
+                .filter_map(|d| d.as_ref().err().cloned())
+                .collect();
+            for error in &errors {
+                log::error!("JavaScript filter function failed on node {}", error);
+            }
+            if self.filter_script_strict && !errors.is_empty() {
+                return Err(ScriptError::FilterFailed {
+                    name: function_name.to_string(),
+                    failed: errors.len(),
+                    total: nodes.len(),
+                    errors: errors
+                        .into_iter()
+                        .take(MAX_REPORTED_SCRIPT_ERRORS)
+                        .collect(),
                 }
+                .into());
             }
+
+            let total = nodes.len();
+            let mut decisions = decisions.into_iter();
+            // A node that failed evaluation is kept rather than dropped
+            nodes.retain(|_| decisions.next().is_none_or(|d| d.unwrap_or(true)));
+            let summary = FilterSummary {
+                kept: nodes.len(),
+                removed: total - nodes.len(),
+                failed: errors.len(),
+            };
+            log::info!(
+                "Filter function evaluated: {} kept, {} removed, {} failed",
+                summary.kept,
+                summary.removed,
+                summary.failed
+            );
+            Ok(summary)
         } else {
             Err("JavaScript context not initialized".into())
         }
@@ -213,7 +1720,7 @@ impl ExtraSettings {
     /// Sorts nodes by a specified criterion
     pub async fn eval_sort_nodes(
         &mut self,
-        nodes: &mut Vec<Proxy>,
+        nodes: &mut [Proxy],
     ) -> Result<(), Box<dyn std::error::Error>> {
         if !self.sort_script.is_empty() {
             let sort_script;
@@ -222,7 +1729,9 @@ impl ExtraSettings {
             } else {
                 sort_script = self.sort_script.clone();
             }
-            self.init_js_context();
+            self.prepare_js_context("compare");
+            let _timer = self.stats.time(crate::utils::ConvertStage::Script);
+            let interrupted = self.arm_script_timeout();
             let mut error_thrown = None;
             if let Some(context) = &mut self.js_context {
                 context.with(|ctx| {
@@ -265,20 +1774,16 @@ impl ExtraSettings {
                     });
                 });
             }
+            if let Err(e) = self.disarm_script_timeout(&interrupted, "compare") {
+                log::error!("{}", e);
+                self.js_context = None;
+                return Err(e.into());
+            }
             if let Some(e) = error_thrown {
                 return Err(e.into());
             }
         } else {
-            // Default sort by remark
-            nodes.sort_by(|a, b| {
-                if a.proxy_type == ProxyType::Unknown {
-                    return Ordering::Greater;
-                }
-                if b.proxy_type == ProxyType::Unknown {
-                    return Ordering::Less;
-                }
-                a.remark.cmp(&b.remark)
-            });
+            sort_nodes_by_remark(nodes);
         }
         Ok(())
     }
@@ -290,6 +1795,8 @@ impl ExtraSettings {
     ) -> Result<String, Box<dyn std::error::Error>> {
         let mut node_name = String::new();
         if !match_script.is_empty() {
+            let _timer = self.stats.time(crate::utils::ConvertStage::Script);
+            let interrupted = self.arm_script_timeout();
             let mut error_thrown = None;
             if let Some(context) = &self.js_context {
                 context.with(|ctx| {
@@ -322,6 +1829,7 @@ impl ExtraSettings {
                     }
                 })
             }
+            self.disarm_script_timeout(&interrupted, "rename")?;
             if let Some(e) = error_thrown {
                 return Err(e.into());
             }
@@ -336,6 +1844,8 @@ impl ExtraSettings {
     ) -> Result<String, Box<dyn std::error::Error>> {
         let mut node_emoji = String::new();
         if !match_script.is_empty() {
+            let _timer = self.stats.time(crate::utils::ConvertStage::Script);
+            let interrupted = self.arm_script_timeout();
             let mut error_thrown = None;
             if let Some(context) = &self.js_context {
                 context.with(|ctx| {
@@ -368,6 +1878,7 @@ impl ExtraSettings {
                     }
                 })
             }
+            self.disarm_script_timeout(&interrupted, "getEmoji")?;
             if let Some(e) = error_thrown {
                 return Err(e.into());
             }
@@ -379,20 +1890,45 @@ impl ExtraSettings {
 #[cfg(not(feature = "js-runtime"))]
 impl ExtraSettings {
     pub fn init_js_context(&mut self) {}
+    pub fn reset_js_context_after(&mut self, _error: &(dyn std::error::Error + 'static)) {}
     pub fn eval_filter_function(
+        &mut self,
+        nodes: &mut Vec<Proxy>,
+        source_str: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        self.eval_named_filter_function(nodes, source_str, DEFAULT_FILTER_FUNCTION)
+    }
+    pub fn eval_named_filter_function(
         &mut self,
         _nodes: &mut Vec<Proxy>,
         _source_str: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        _function_name: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
         Err(
             "JavaScript is not supported in this build, please enable js-runtime feature in cargo build"
                 .into(),
         )
     }
-    pub async fn eval_sort_nodes(
+    pub fn eval_filter_chain(
         &mut self,
         _nodes: &mut Vec<Proxy>,
+        _sources: &[String],
+        _function_name: &str,
+    ) -> Result<FilterSummary, Box<dyn std::error::Error>> {
+        Err(
+            "JavaScript is not supported in this build, please enable js-runtime feature in cargo build"
+                .into(),
+        )
+    }
+    pub async fn eval_sort_nodes(
+        &mut self,
+        nodes: &mut [Proxy],
     ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.sort_script.is_empty() {
+            // Default sort by remark does not need the JavaScript runtime
+            sort_nodes_by_remark(nodes);
+            return Ok(());
+        }
         Err(
             "JavaScript is not supported in this build, please enable js-runtime feature in cargo build"
                 .into(),
@@ -419,3 +1955,273 @@ impl ExtraSettings {
         )
     }
 }
+
+#[cfg(all(test, feature = "js-runtime"))]
+mod tests {
+    use super::*;
+
+    fn nodes() -> Vec<Proxy> {
+        ["HK 01", "JP 01"]
+            .iter()
+            .map(|remark| Proxy {
+                proxy_type: ProxyType::Trojan,
+                remark: remark.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_eval_named_filter_function() {
+        let mut extra = ExtraSettings::default();
+        let mut list = nodes();
+        extra
+            .eval_named_filter_function(
+                &mut list,
+                "function onlyHk(node) { return node.Remark.startsWith('HK'); }",
+                "onlyHk",
+            )
+            .unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].remark, "HK 01");
+    }
+
+    #[test]
+    fn test_eval_named_filter_function_missing() {
+        let mut extra = ExtraSettings::default();
+        let mut list = nodes();
+        let err = extra
+            .eval_named_filter_function(
+                &mut list,
+                "function filter(node) { return false; }",
+                "keep",
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ScriptError>(),
+            Some(&ScriptError::FunctionNotFound {
+                name: "keep".to_string()
+            })
+        );
+        assert_eq!(list.len(), 2);
+    }
+
+    const THROWING_FILTER: &str = "function filter(node) { \
+        if (node.Remark.startsWith('JP')) { throw new Error('boom'); } \
+        return false; }";
+
+    #[test]
+    fn test_eval_filter_function_keeps_failed_nodes() {
+        let mut extra = ExtraSettings::default();
+        let mut list = nodes();
+        let summary = extra
+            .eval_filter_function(&mut list, THROWING_FILTER)
+            .unwrap();
+        assert_eq!(
+            summary,
+            FilterSummary {
+                kept: 1,
+                removed: 1,
+                failed: 1
+            }
+        );
+        assert_eq!(list[0].remark, "JP 01");
+    }
+
+    #[test]
+    fn test_eval_filter_function_strict() {
+        let mut extra = ExtraSettings::builder().filter_script_strict(true).build();
+        let mut list = nodes();
+        let err = extra
+            .eval_filter_function(&mut list, THROWING_FILTER)
+            .unwrap_err();
+        match err.downcast_ref::<ScriptError>() {
+            Some(ScriptError::FilterFailed {
+                failed,
+                total,
+                errors,
+                ..
+            }) => {
+                assert_eq!((*failed, *total), (1, 2));
+                assert!(errors[0].contains("JP 01") && errors[0].contains("boom"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_eval_filter_function_limits() {
+        let mut extra = ExtraSettings::builder().script_timeout(100).build();
+        let mut list = nodes();
+        let err = extra
+            .eval_filter_function(&mut list, "function filter(node) { for (;;) {} }")
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ScriptError>(),
+            Some(&ScriptError::TimedOut {
+                name: "filter".to_string(),
+                timeout: 100
+            })
+        );
+        assert_eq!(list.len(), 2);
+
+        let mut extra = ExtraSettings::builder()
+            .script_memory_limit(8 * 1024 * 1024)
+            .build();
+        let err = extra
+            .eval_filter_function(
+                &mut list,
+                "function filter(node) { let a = []; for (;;) { a.push('x'.repeat(1024)); } }",
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ScriptError>(),
+            Some(ScriptError::OutOfMemory { .. })
+        ));
+        assert_eq!(list.len(), 2);
+        // The runtime stays usable once the script is gone
+        let summary = extra
+            .eval_filter_function(&mut list, "function filter(node) { return false; }")
+            .unwrap();
+        assert_eq!(summary.removed, 2);
+    }
+
+    #[test]
+    fn test_script_clean_context() {
+        const DEFINE: &str = "var leaked = 1; function filter(node) { return true; }";
+        const PROBE: &str = "function filter(node) { return typeof leaked === 'undefined'; }";
+        let run = |clean: bool| {
+            let mut extra = ExtraSettings::builder().script_clean_context(clean).build();
+            let mut list = nodes();
+            extra.eval_filter_function(&mut list, DEFINE).unwrap();
+            extra
+                .eval_filter_function(&mut list, PROBE)
+                .unwrap()
+                .removed
+        };
+
+        assert_eq!(run(true), 0);
+        // A shared context keeps the globals of the first script
+        assert_eq!(run(false), 2);
+        assert!(Settings::default().script_clean_context);
+    }
+
+    #[test]
+    fn test_shared_js_runtime() {
+        let runtime = rquickjs::Runtime::new().unwrap();
+        for _ in 0..2 {
+            let mut extra = ExtraSettings::builder().js_runtime(runtime.clone()).build();
+            let mut list = nodes();
+            let summary = extra
+                .eval_filter_function(&mut list, "function filter(node) { return false; }")
+                .unwrap();
+            assert_eq!(summary.removed, 2);
+        }
+
+        // Copies share the runtime, not the context
+        let mut extra = ExtraSettings::builder().js_runtime(runtime.clone()).build();
+        extra.init_js_context();
+        let copy = extra.clone_settings();
+        assert!(copy.js_runtime.is_some() && copy.js_context.is_none());
+
+        // A context sharing the runtime is used as given
+        let context = rquickjs::Context::full(&runtime).unwrap();
+        let mut extra = ExtraSettings::builder()
+            .js_context(runtime, context)
+            .script_clean_context(false)
+            .build();
+        let mut list = nodes();
+        extra
+            .eval_filter_function(&mut list, "function filter(node) { return true; }")
+            .unwrap();
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_eval_sort_and_rename_time_out() {
+        let timed_out = |name: &str| ScriptError::TimedOut {
+            name: name.to_string(),
+            timeout: 100,
+        };
+        let mut extra = ExtraSettings::builder()
+            .script_timeout(100)
+            .sort_script("function compare(a, b) { for (;;) {} }")
+            .build();
+        let mut list = nodes();
+        let err = futures::executor::block_on(extra.eval_sort_nodes(&mut list)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ScriptError>(),
+            Some(&timed_out("compare"))
+        );
+
+        extra.init_js_context();
+        let script = "function rename(node) { for (;;) {} }".to_string();
+        let err = futures::executor::block_on(extra.eval_get_rename_node_remark(&list[0], script))
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ScriptError>(),
+            Some(&timed_out("rename"))
+        );
+    }
+
+    #[test]
+    fn test_eval_filter_chain() {
+        let mut extra = ExtraSettings::default();
+        let mut list = nodes();
+        list.push(Proxy {
+            remark: "HK 02 Expire".to_string(),
+            ..Default::default()
+        });
+        let scripts = vec![
+            "function isHk(node) { return node.Remark.startsWith('HK'); } \
+             function filter(node) { return isHk(node); }"
+                .to_string(),
+            // Helpers of earlier scripts are still defined
+            "function filter(node) { return isHk(node) && !node.Remark.includes('Expire'); }"
+                .to_string(),
+        ];
+        let summary = extra
+            .eval_filter_chain(&mut list, &scripts, DEFAULT_FILTER_FUNCTION)
+            .unwrap();
+        assert_eq!(
+            summary,
+            FilterSummary {
+                kept: 1,
+                removed: 2,
+                failed: 0
+            }
+        );
+        assert_eq!(list[0].remark, "HK 01");
+
+        // The second script does not define `filter`, the first one's must not run
+        let mut list = nodes();
+        let scripts = vec![scripts[0].clone(), "var unrelated = 1;".to_string()];
+        let err = extra
+            .eval_filter_chain(&mut list, &scripts, DEFAULT_FILTER_FUNCTION)
+            .unwrap_err();
+        match err.downcast_ref::<ScriptError>() {
+            Some(ScriptError::ChainFailed { index, message }) => {
+                assert_eq!(*index, 1);
+                assert!(message.contains("'filter' is not defined"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_eval_filter_chain_of_one_script() {
+        let mut extra = ExtraSettings::default();
+        let mut list = nodes();
+        let scripts = vec!["var unrelated = 1;".to_string()];
+        let err = extra
+            .eval_filter_chain(&mut list, &scripts, DEFAULT_FILTER_FUNCTION)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ScriptError>(),
+            Some(ScriptError::ChainFailed { index: 0, .. })
+        ));
+        assert_eq!(list.len(), 2);
+    }
+}
@@ -0,0 +1,226 @@
+//! Stable JSON form of parsed nodes
+//!
+//! Lets a caller parse subscriptions once, cache the nodes and emit them to
+//! any target later without parsing again. A document is
+//!
+//! ```json
+//! {"version": 1, "proxies": [{"ProxyType": "Trojan", "Remark": "HK 01", ...}]}
+//! ```
+//!
+//! or `{"version": 1, "proxy": {...}}` for a single node. A node is the serde
+//! form of [`Proxy`], the one filter scripts see: fields in PascalCase,
+//! [`ProxyType`](super::ProxyType) by variant name, and the protocol specific
+//! part of combined nodes flattened next to them, tagged by `combined_type`
+//! and keeping the field names of its protocol.
+//!
+//! Within a version fields are only added. Fields missing from a document
+//! take their default, so documents written by older releases still read;
+//! unknown fields are ignored. A change that breaks this bumps
+//! [`INTERCHANGE_VERSION`], documents of a newer version are refused.
+
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::Proxy;
+
+/// Version written into documents, the newest one this build reads
+pub const INTERCHANGE_VERSION: u32 = 1;
+
+/// A document that cannot be read
+#[derive(Debug, Error)]
+pub enum InterchangeError {
+    #[error("Invalid interchange document: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Interchange document has no version")]
+    MissingVersion,
+    #[error(
+        "Unsupported interchange version {found}, this build reads up to {INTERCHANGE_VERSION}"
+    )]
+    UnsupportedVersion { found: u64 },
+    #[error("Interchange document has no '{0}' entry")]
+    MissingEntry(&'static str),
+    /// The protocol specific part of a node did not match its `combined_type`
+    #[error("Invalid '{combined_type}' data of interchange node {index}")]
+    InvalidCombined { index: usize, combined_type: String },
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy: Option<&'a Proxy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxies: Option<&'a [Proxy]>,
+}
+
+/// Serialize nodes into an interchange document
+pub fn nodes_to_interchange(nodes: &[Proxy]) -> String {
+    to_document(Document {
+        version: INTERCHANGE_VERSION,
+        proxy: None,
+        proxies: Some(nodes),
+    })
+}
+
+/// Read the nodes of an interchange document
+pub fn nodes_from_interchange(document: &str) -> Result<Vec<Proxy>, InterchangeError> {
+    match read_document(document, "proxies")? {
+        Value::Array(entries) => entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| read_node(index, entry))
+            .collect(),
+        _ => Err(InterchangeError::MissingEntry("proxies")),
+    }
+}
+
+impl Proxy {
+    /// Serialize the node into an interchange document
+    pub fn to_interchange(&self) -> String {
+        to_document(Document {
+            version: INTERCHANGE_VERSION,
+            proxy: Some(self),
+            proxies: None,
+        })
+    }
+
+    /// Read the node of a single node interchange document
+    pub fn from_interchange(document: &str) -> Result<Proxy, InterchangeError> {
+        read_node(0, read_document(document, "proxy")?)
+    }
+}
+
+fn to_document(document: Document) -> String {
+    // Proxy has no map keys or values that JSON cannot represent
+    serde_json::to_string(&document).expect("nodes always serialize to JSON")
+}
+
+/// Check the version of a document and take its `entry`
+fn read_document(document: &str, entry: &'static str) -> Result<Value, InterchangeError> {
+    let mut document: Value = serde_json::from_str(document)?;
+    let version = document
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(InterchangeError::MissingVersion)?;
+    if version == 0 || version > INTERCHANGE_VERSION as u64 {
+        return Err(InterchangeError::UnsupportedVersion { found: version });
+    }
+    document
+        .get_mut(entry)
+        .map(Value::take)
+        .ok_or(InterchangeError::MissingEntry(entry))
+}
+
+fn read_node(index: usize, entry: Value) -> Result<Proxy, InterchangeError> {
+    let combined_type = entry
+        .get("combined_type")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let node: Proxy = serde_json::from_value(entry)?;
+    // A flattened part that fails to deserialize silently becomes `None`
+    match combined_type {
+        Some(combined_type) if node.combined_proxy.is_none() => {
+            Err(InterchangeError::InvalidCombined {
+                index,
+                combined_type,
+            })
+        }
+        _ => Ok(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::proxy_node::combined::CombinedProxy;
+    use crate::models::proxy_node::vless::VlessProxy;
+    use crate::models::{GrpcMode, IpVersion, ProxyType};
+
+    fn json(node: &Proxy) -> Value {
+        serde_json::to_value(node).unwrap()
+    }
+
+    #[test]
+    fn test_interchange_round_trip() {
+        let nodes = vec![
+            Proxy {
+                proxy_type: ProxyType::Vless,
+                remark: "HK 01".to_string(),
+                hostname: "hk.example.com".to_string(),
+                port: 443,
+                grpc_mode: Some(GrpcMode::Multi),
+                ip_version: Some(IpVersion::Ipv4Prefer),
+                alpn: vec!["h2".to_string()],
+                dns_servers: ["1.1.1.1".to_string()].into(),
+                combined_proxy: Some(CombinedProxy::Vless(VlessProxy {
+                    uuid: "b831381d-6324-4d53-ad4f-8cda48b30811".to_string(),
+                    flow: Some("xtls-rprx-vision".to_string()),
+                    reality_public_key: Some("key".to_string()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+            Proxy {
+                proxy_type: ProxyType::Hysteria,
+                remark: "JP 01".to_string(),
+                auth_str: Some("secret".to_string()),
+                up_speed: 10,
+                down_speed: 50,
+                recv_window: 1 << 20,
+                ..Default::default()
+            },
+        ];
+
+        let document = nodes_to_interchange(&nodes);
+        let parsed = nodes_from_interchange(&document).unwrap();
+        assert_eq!(parsed.len(), 2);
+        for (node, parsed) in nodes.iter().zip(&parsed) {
+            assert_eq!(json(node), json(parsed));
+        }
+
+        let parsed = Proxy::from_interchange(&nodes[0].to_interchange()).unwrap();
+        assert_eq!(json(&nodes[0]), json(&parsed));
+        assert!(matches!(
+            nodes_from_interchange(&nodes[0].to_interchange()),
+            Err(InterchangeError::MissingEntry("proxies"))
+        ));
+    }
+
+    #[test]
+    fn test_interchange_compatibility() {
+        // Written before the GrpcMode, IpVersion and ClientFingerprint fields
+        let v1 = r#"{"version":1,"proxies":[{"ProxyType":"VMess","Id":0,"GroupId":0,
+            "Group":"","Remark":"US 01","Hostname":"us.example.com","Port":443,
+            "UserId":"b831381d-6324-4d53-ad4f-8cda48b30811","AlterId":0,
+            "TransferProtocol":"ws","Path":"/ws","TlsSecure":true},
+            {"ProxyType":"Shadowsocks","Remark":"SG 01","Hostname":"sg.example.com",
+            "Port":8388,"combined_type":"Shadowsocks","server":"sg.example.com",
+            "port":8388,"password":"pw","cipher":"aes-128-gcm"}]}"#;
+        let nodes = nodes_from_interchange(v1).unwrap();
+        assert_eq!(nodes[0].proxy_type, ProxyType::VMess);
+        assert_eq!(nodes[0].path.as_deref(), Some("/ws"));
+        assert!(nodes[0].tls_secure);
+        assert_eq!(nodes[0].ip_version, None);
+        assert_eq!(nodes[0].allowed_ips, Proxy::default().allowed_ips);
+        match &nodes[1].combined_proxy {
+            Some(CombinedProxy::Shadowsocks(ss)) => assert_eq!(ss.cipher, "aes-128-gcm"),
+            other => panic!("unexpected combined proxy: {:?}", other),
+        }
+
+        assert!(matches!(
+            nodes_from_interchange(r#"{"version":2,"proxies":[]}"#),
+            Err(InterchangeError::UnsupportedVersion { found: 2 })
+        ));
+        assert!(matches!(
+            nodes_from_interchange(r#"{"proxies":[]}"#),
+            Err(InterchangeError::MissingVersion)
+        ));
+        assert!(matches!(
+            nodes_from_interchange(
+                r#"{"version":1,"proxies":[{"ProxyType":"Vless","combined_type":"Vless","tls":"yes"}]}"#
+            ),
+            Err(InterchangeError::InvalidCombined { index: 0, .. })
+        ));
+    }
+}
@@ -49,33 +49,70 @@
 //! See the examples directory for more detailed usage examples.
 
 pub mod builder;
+pub mod cert_verify;
 pub mod ciphers;
+pub mod clash_core;
 pub mod configs;
 pub mod cron;
+pub mod dedup;
+pub mod degrade;
 pub mod extra_settings;
 pub mod ini_bindings;
+pub mod interchange;
+pub mod name_template;
+pub mod port_rewrite;
 pub mod proxy;
+pub mod proxy_codec;
 pub mod proxy_group_config;
 pub mod proxy_node;
 pub mod regex_match_config;
+pub mod relay;
 pub mod ruleset;
+pub mod sort_key;
 pub mod subconverter_target;
+pub mod unsupported;
 
-pub use extra_settings::ExtraSettings;
+pub use cert_verify::SkipCertVerifyPolicy;
+pub use dedup::DedupKey;
+pub use degrade::{needs_reality, Degrade, DegradeMap};
+pub use extra_settings::{
+    ExtraSettings, ExtraSettingsBuilder, FilterSummary, NodeTransform, ScriptError,
+    DEFAULT_FILTER_FUNCTION,
+};
+pub use name_template::NameTemplate;
+pub use port_rewrite::{PortRange, PortRewrite, PortRewriteMap, PortSet};
 pub use proxy_group_config::{
-    BalanceStrategy, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType,
+    collapse_duplicate_groups, BalanceStrategy, GroupHealthCheck, GroupTypeOverride,
+    ProviderHealthCheck, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType,
 };
 pub use regex_match_config::{RegexMatchConfig, RegexMatchConfigs};
+pub use relay::RelayChainError;
+pub use sort_key::{SortField, SortKey};
 pub use subconverter_target::SubconverterTarget;
+pub use unsupported::{UnsupportedNodes, UnsupportedPolicy};
 
+pub use clash_core::ClashCore;
+pub use interchange::{
+    nodes_from_interchange, nodes_to_interchange, InterchangeError, INTERCHANGE_VERSION,
+};
 pub use proxy::{Proxy, ProxyType};
+pub use proxy_codec::{register_codec, CodecOutput, FnCodec, ProxyCodec};
+pub use proxy_node::auth::{auth_skip_reason, AuthRule};
+pub use proxy_node::custom::CustomProxy;
+pub use proxy_node::ip_version::IpVersion;
+pub use proxy_node::multiplex::{Brutal, Multiplex, MultiplexProtocol};
+pub use proxy_node::transport::{GrpcMode, Transport, TransportOptions};
+pub use proxy_node::trojan_go::{trojan_go_skip_reason, TrojanGoSupport};
 pub use ruleset::{RulesetConfig, RulesetContent, RulesetType};
 
 // Re-export constants to module scope for use by other modules
 // Default proxy group names
-pub use ciphers::{SSR_CIPHERS, SS_CIPHERS};
+pub use ciphers::{
+    is_ss2022_cipher, node_ss2022_cipher, validate_ss2022_psk, Ss2022KeyError, SSR_CIPHERS,
+    SS_CIPHERS,
+};
 pub use proxy::{
-    HTTP_DEFAULT_GROUP, HYSTERIA2_DEFAULT_GROUP, HYSTERIA_DEFAULT_GROUP, SNELL_DEFAULT_GROUP,
-    SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_DEFAULT_GROUP, TROJAN_DEFAULT_GROUP,
-    V2RAY_DEFAULT_GROUP, WG_DEFAULT_GROUP,
+    ANYTLS_DEFAULT_GROUP, HTTP_DEFAULT_GROUP, HYSTERIA2_DEFAULT_GROUP, HYSTERIA_DEFAULT_GROUP,
+    SNELL_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_DEFAULT_GROUP,
+    TROJAN_DEFAULT_GROUP, TUIC_DEFAULT_GROUP, V2RAY_DEFAULT_GROUP, WG_DEFAULT_GROUP,
 };
@@ -0,0 +1,169 @@
+//! Node names computed from the fields of the node
+//!
+//! A template such as `{region} | {type} | {port}` replaces each node's name
+//! with its placeholders filled in. Known placeholders are:
+//!
+//! - `{name}`, the name the node has when the template runs;
+//! - `{type}`, the protocol, e.g. `Trojan`;
+//! - `{server}` and `{port}`;
+//! - `{region}`, the English name of the region named in the node's name,
+//!   see [`find_region`], or else of the GeoIP country of the server, see
+//!   [`region_of_code`]. It is empty when neither is known.
+//!
+//! Unknown placeholders are kept as written.
+
+use log::warn;
+
+use super::Proxy;
+use crate::utils::geoip::geoip_country;
+use crate::utils::transliterate::{find_region, region_of_code};
+
+/// A placeholder of a name template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Name,
+    Type,
+    Server,
+    Port,
+    Region,
+}
+
+impl Placeholder {
+    fn from_key(key: &str) -> Option<Self> {
+        match key.trim().to_lowercase().as_str() {
+            "name" | "remark" => Some(Placeholder::Name),
+            "type" => Some(Placeholder::Type),
+            "server" | "host" => Some(Placeholder::Server),
+            "port" => Some(Placeholder::Port),
+            "region" => Some(Placeholder::Region),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Placeholder),
+}
+
+/// A parsed node name template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    /// Parse a template, warning about each unknown placeholder
+    pub fn new(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let key = &rest[start + 1..start + len];
+            literal.push_str(&rest[..start]);
+            match Placeholder::from_key(key) {
+                Some(placeholder) => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(placeholder));
+                }
+                None => {
+                    warn!(
+                        "Unknown placeholder '{{{}}}' in name template '{}' is kept as is",
+                        key, template
+                    );
+                    literal.push_str(&rest[start..=start + len]);
+                }
+            }
+            rest = &rest[start + len + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        NameTemplate { parts }
+    }
+
+    /// Whether the template uses no placeholder
+    pub fn is_literal(&self) -> bool {
+        self.parts
+            .iter()
+            .all(|part| matches!(part, Part::Literal(_)))
+    }
+
+    /// Name of `node` under the template
+    ///
+    /// `region_names` are tried before the built-in region names, as in
+    /// [`transliterate_regions`](crate::utils::transliterate::transliterate_regions).
+    pub async fn render(&self, node: &Proxy, region_names: &[(String, String)]) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => name.push_str(text),
+                Part::Field(Placeholder::Name) => name.push_str(&node.remark),
+                Part::Field(Placeholder::Type) => name.push_str(node.proxy_type.to_string()),
+                Part::Field(Placeholder::Server) => name.push_str(&node.hostname),
+                Part::Field(Placeholder::Port) => name.push_str(&node.port.to_string()),
+                Part::Field(Placeholder::Region) => {
+                    let region = match find_region(&node.remark, region_names) {
+                        Some(region) => Some(region),
+                        None => geoip_country(&node.hostname)
+                            .await
+                            .map(|code| region_of_code(&code, region_names)),
+                    };
+                    if let Some(region) = region {
+                        name.push_str(&region);
+                    }
+                }
+            }
+        }
+        name.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyType;
+
+    #[tokio::test]
+    async fn test_name_template() {
+        let node = Proxy {
+            proxy_type: ProxyType::Trojan,
+            remark: "香港 IEPL 01".to_string(),
+            hostname: "hk.example.com".to_string(),
+            port: 443,
+            ..Default::default()
+        };
+        let template = NameTemplate::new("{region} | {type} | {port}");
+        assert_eq!(
+            template.render(&node, &[]).await,
+            "Hong Kong | Trojan | 443"
+        );
+        assert!(!template.is_literal());
+
+        let template = NameTemplate::new("{server}:{PORT} {unknown} {name");
+        assert_eq!(
+            template.render(&node, &[]).await,
+            "hk.example.com:443 {unknown} {name"
+        );
+        assert!(NameTemplate::new("{nope}").is_literal());
+
+        // No region in the name and no GeoIP database
+        let node = Proxy {
+            remark: "Premium".to_string(),
+            ..node
+        };
+        assert_eq!(
+            NameTemplate::new("{region} {name}")
+                .render(&node, &[])
+                .await,
+            "Premium"
+        );
+    }
+}
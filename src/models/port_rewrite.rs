@@ -0,0 +1,278 @@
+//! Rewriting and dropping nodes by server port
+//!
+//! Some networks block the ports a provider hands out. In
+//! [`ExtraSettings::port_rewrite`](super::ExtraSettings::port_rewrite) a rule
+//! such as `443->8443` moves nodes to a port the provider also listens on,
+//! and a range maps onto a single port (`2000-2999->443`) or onto a range of
+//! the same size by offset (`2000-2999->12000-12999`). The first matching
+//! rule wins. Nodes on a port of
+//! [`ExtraSettings::drop_ports`](super::ExtraSettings::drop_ports) are left
+//! out. Both apply to the parsed nodes of an export, after type exclusion and
+//! before deduplication, so the drop list sees the original ports and
+//! nodes rewritten onto the same endpoint are merged.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::Proxy;
+
+/// An inclusive range of ports, a single port when both ends are equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+
+    /// Number of ports after the first
+    fn span(&self) -> u16 {
+        self.end - self.start
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    /// Parse `443` or `2000-2999`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let port = |value: &str| {
+            value
+                .trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port > 0)
+                .ok_or_else(|| format!("Invalid port '{}'", value.trim()))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (port(start)?, port(end)?),
+            None => {
+                let port = port(s)?;
+                (port, port)
+            }
+        };
+        if start > end {
+            return Err(format!("Port range '{}' ends before it starts", s.trim()));
+        }
+        Ok(PortRange { start, end })
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// A rule moving the ports of `from` to `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRewrite {
+    pub from: PortRange,
+    /// A single port, or a range as large as `from`
+    pub to: PortRange,
+}
+
+impl PortRewrite {
+    /// The port `port` is moved to, `None` when the rule does not match
+    pub fn apply(&self, port: u16) -> Option<u16> {
+        if !self.from.contains(port) {
+            return None;
+        }
+        if self.to.start == self.to.end {
+            return Some(self.to.start);
+        }
+        Some(self.to.start + (port - self.from.start))
+    }
+}
+
+impl FromStr for PortRewrite {
+    type Err = String;
+
+    /// Parse `from->to`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once("->")
+            .ok_or_else(|| format!("Invalid port rewrite '{}', expected from->to", s.trim()))?;
+        let (from, to): (PortRange, PortRange) = (from.parse()?, to.parse()?);
+        if to.start != to.end && to.span() != from.span() {
+            return Err(format!(
+                "Port rewrite '{}' maps {} ports onto {}",
+                s.trim(),
+                u32::from(from.span()) + 1,
+                u32::from(to.span()) + 1
+            ));
+        }
+        Ok(PortRewrite { from, to })
+    }
+}
+
+impl fmt::Display for PortRewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}->{}", self.from, self.to)
+    }
+}
+
+/// Port rewrite rules of an export, none by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortRewriteMap(Vec<PortRewrite>);
+
+impl PortRewriteMap {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The port `port` is moved to by the first matching rule
+    pub fn rewrite(&self, port: u16) -> Option<u16> {
+        self.0.iter().find_map(|rule| rule.apply(port))
+    }
+
+    /// Move the nodes on a matching port
+    ///
+    /// # Returns
+    /// The number of nodes whose port changed
+    pub fn rewrite_nodes(&self, nodes: &mut [Proxy]) -> usize {
+        let mut rewritten = 0;
+        for node in nodes {
+            if let Some(port) = self.rewrite(node.port).filter(|port| *port != node.port) {
+                node.port = port;
+                rewritten += 1;
+            }
+        }
+        rewritten
+    }
+}
+
+impl FromIterator<PortRewrite> for PortRewriteMap {
+    fn from_iter<I: IntoIterator<Item = PortRewrite>>(iter: I) -> Self {
+        PortRewriteMap(iter.into_iter().collect())
+    }
+}
+
+impl FromStr for PortRewriteMap {
+    type Err = String;
+
+    /// Parse a comma separated list of rules
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|rule| !rule.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+/// Ports whose nodes are dropped, none by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortSet(Vec<PortRange>);
+
+impl PortSet {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.0.iter().any(|range| range.contains(port))
+    }
+
+    /// Drop the nodes on a port of the set
+    ///
+    /// # Returns
+    /// The number of removed nodes
+    pub fn drop_nodes(&self, nodes: &mut Vec<Proxy>) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let before = nodes.len();
+        nodes.retain(|node| !self.contains(node.port));
+        before - nodes.len()
+    }
+}
+
+impl FromIterator<PortRange> for PortSet {
+    fn from_iter<I: IntoIterator<Item = PortRange>>(iter: I) -> Self {
+        PortSet(iter.into_iter().collect())
+    }
+}
+
+impl FromStr for PortSet {
+    type Err = String;
+
+    /// Parse a comma separated list of ports and ranges
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|range| !range.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+/// Explain reason of a node dropped by its port
+pub fn dropped_port_reason(node: &Proxy) -> String {
+    format!("port {} is dropped", node.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(ports: &[u16]) -> Vec<Proxy> {
+        ports
+            .iter()
+            .map(|port| Proxy {
+                port: *port,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn ports(nodes: &[Proxy]) -> Vec<u16> {
+        nodes.iter().map(|node| node.port).collect()
+    }
+
+    #[test]
+    fn test_single_port_rewrite() {
+        let map: PortRewriteMap = "443 -> 8443".parse().unwrap();
+        let mut list = nodes(&[443, 80, 8443]);
+        assert_eq!(map.rewrite_nodes(&mut list), 1);
+        assert_eq!(ports(&list), vec![8443, 80, 8443]);
+
+        assert!("443".parse::<PortRewriteMap>().is_err());
+        assert!("0->80".parse::<PortRewriteMap>().is_err());
+        assert_eq!("".parse(), Ok(PortRewriteMap::default()));
+    }
+
+    #[test]
+    fn test_range_port_rewrite() {
+        // The first matching rule wins
+        let map: PortRewriteMap = "2000-2009->12000-12009, 2000-2999->443".parse().unwrap();
+        let mut list = nodes(&[2000, 2005, 2500, 3000]);
+        assert_eq!(map.rewrite_nodes(&mut list), 3);
+        assert_eq!(ports(&list), vec![12000, 12005, 443, 3000]);
+        assert_eq!(
+            map.0
+                .iter()
+                .map(|rule| rule.to_string())
+                .collect::<Vec<_>>(),
+            vec!["2000-2009->12000-12009", "2000-2999->443"]
+        );
+
+        let err = "2000-2999->12000-12009".parse::<PortRewrite>().unwrap_err();
+        assert!(err.contains("1000 ports onto 10"));
+        assert!("3000-2000->443".parse::<PortRewrite>().is_err());
+    }
+
+    #[test]
+    fn test_drop_ports() {
+        let set: PortSet = "25, 6000-6100".parse().unwrap();
+        let mut list = nodes(&[25, 443, 6050, 6101]);
+        assert_eq!(set.drop_nodes(&mut list), 2);
+        assert_eq!(ports(&list), vec![443, 6101]);
+        assert!("smtp".parse::<PortSet>().is_err());
+    }
+}
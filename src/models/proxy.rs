@@ -2,11 +2,14 @@
 //!
 //! Contains the core data structures for proxy configurations.
 
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
 use serde::{Deserialize, Serialize};
 
 use super::proxy_node::combined::CombinedProxy;
+use super::proxy_node::ip_version::IpVersion;
+use super::proxy_node::multiplex::Multiplex;
+use super::proxy_node::transport::GrpcMode;
 
 /// Represents the type of a proxy.
 /// This is the canonical enum used for proxy type identification across the
@@ -28,6 +31,7 @@ pub enum ProxyType {
     // new proxy types could be added as enum combined proxy types
     Vless,
     AnyTls,
+    Tuic,
 }
 
 /// Converts a `ProxyType` into a human-readable name.
@@ -47,14 +51,46 @@ impl ProxyType {
             ProxyType::Hysteria2 => "Hysteria2",
             ProxyType::Vless => "Vless",
             ProxyType::AnyTls => "AnyTLS",
+            ProxyType::Tuic => "TUIC",
             ProxyType::Unknown => "Unknown",
         }
     }
 }
 
+/// Parse a proxy type name, case-insensitively
+///
+/// Accepts the names used in links and configs, e.g. `ss`, `shadowsocksr`,
+/// `socks5`, `wg` or `hy2`.
+impl std::str::FromStr for ProxyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ss" | "shadowsocks" => Ok(ProxyType::Shadowsocks),
+            "ssr" | "shadowsocksr" => Ok(ProxyType::ShadowsocksR),
+            "vmess" => Ok(ProxyType::VMess),
+            "trojan" => Ok(ProxyType::Trojan),
+            "snell" => Ok(ProxyType::Snell),
+            "http" => Ok(ProxyType::HTTP),
+            "https" => Ok(ProxyType::HTTPS),
+            "socks" | "socks5" => Ok(ProxyType::Socks5),
+            "wg" | "wireguard" => Ok(ProxyType::WireGuard),
+            "hysteria" => Ok(ProxyType::Hysteria),
+            "hy2" | "hysteria2" => Ok(ProxyType::Hysteria2),
+            "vless" => Ok(ProxyType::Vless),
+            "anytls" => Ok(ProxyType::AnyTls),
+            "tuic" => Ok(ProxyType::Tuic),
+            _ => Err(format!("Unknown proxy type: {}", s)),
+        }
+    }
+}
+
 /// Represents a proxy configuration. Serialized for JavaScripts.
+///
+/// Also the node form of the [`interchange`](super::interchange) format,
+/// fields a document lacks take their default.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", default)]
 pub struct Proxy {
     pub proxy_type: ProxyType,
     #[serde(flatten)]
@@ -85,6 +121,8 @@ pub struct Proxy {
     pub host: Option<String>,
     pub path: Option<String>,
     pub edge: Option<String>,
+    /// gRPC stream mode, for the `grpc` transport
+    pub grpc_mode: Option<GrpcMode>,
 
     pub quic_secure: Option<String>,
     pub quic_secret: Option<String>,
@@ -93,7 +131,14 @@ pub struct Proxy {
     pub tcp_fast_open: Option<bool>,
     pub allow_insecure: Option<bool>,
     pub tls13: Option<bool>,
+    /// Address families the server is dialed with, see
+    /// [`ip_version`](super::proxy_node::ip_version)
+    pub ip_version: Option<IpVersion>,
+    /// sing-mux settings, see [`multiplex`](super::proxy_node::multiplex)
+    pub multiplex: Option<Multiplex>,
 
+    /// Name of the node or group this node dials through, see
+    /// [`relay`](super::relay)
     pub underlying_proxy: Option<String>,
 
     pub snell_version: u16,
@@ -104,7 +149,7 @@ pub struct Proxy {
     pub public_key: Option<String>,
     pub private_key: Option<String>,
     pub pre_shared_key: Option<String>,
-    pub dns_servers: HashSet<String>,
+    pub dns_servers: BTreeSet<String>,
     pub mtu: u16,
     pub allowed_ips: String,
     pub keep_alive: u16,
@@ -120,13 +165,16 @@ pub struct Proxy {
     pub auth_str: Option<String>,
     pub sni: Option<String>,
     pub fingerprint: Option<String>,
+    /// uTLS fingerprint of the TLS client hello, see
+    /// [`utls`](super::proxy_node::utls)
+    pub client_fingerprint: Option<String>,
     pub ca: Option<String>,
     pub ca_str: Option<String>,
     pub recv_window_conn: u32,
     pub recv_window: u32,
     pub disable_mtu_discovery: Option<bool>,
     pub hop_interval: u32,
-    pub alpn: HashSet<String>,
+    pub alpn: Vec<String>,
 
     pub cwnd: u32,
 }
@@ -160,12 +208,15 @@ impl Default for Proxy {
             host: None,
             path: None,
             edge: None,
+            grpc_mode: None,
             quic_secure: None,
             quic_secret: None,
             udp: None,
             tcp_fast_open: None,
             allow_insecure: None,
             tls13: None,
+            ip_version: None,
+            multiplex: None,
             underlying_proxy: None,
             snell_version: 0,
             server_name: None,
@@ -174,7 +225,7 @@ impl Default for Proxy {
             public_key: None,
             private_key: None,
             pre_shared_key: None,
-            dns_servers: HashSet::new(),
+            dns_servers: BTreeSet::new(),
             mtu: 0,
             allowed_ips: String::from("0.0.0.0/0, ::/0"),
             keep_alive: 0,
@@ -187,13 +238,14 @@ impl Default for Proxy {
             auth_str: None,
             sni: None,
             fingerprint: None,
+            client_fingerprint: None,
             ca: None,
             ca_str: None,
             recv_window_conn: 0,
             recv_window: 0,
             disable_mtu_discovery: None,
             hop_interval: 0,
-            alpn: HashSet::new(),
+            alpn: Vec::new(),
             cwnd: 0,
         }
     }
@@ -219,7 +271,7 @@ impl Proxy {
     pub fn is_combined_proxy(&self) -> bool {
         matches!(
             self.proxy_type,
-            ProxyType::Vless | ProxyType::Shadowsocks | ProxyType::AnyTls
+            ProxyType::Vless | ProxyType::Shadowsocks | ProxyType::AnyTls | ProxyType::Tuic
         )
     }
 
@@ -305,3 +357,5 @@ pub const SNELL_DEFAULT_GROUP: &str = "SnellProvider";
 pub const WG_DEFAULT_GROUP: &str = "WireGuardProvider";
 pub const HYSTERIA_DEFAULT_GROUP: &str = "HysteriaProvider";
 pub const HYSTERIA2_DEFAULT_GROUP: &str = "Hysteria2Provider";
+pub const TUIC_DEFAULT_GROUP: &str = "TUICProvider";
+pub const ANYTLS_DEFAULT_GROUP: &str = "AnyTLSProvider";
@@ -1,3 +1,6 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
 /// Type of proxy group
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ProxyGroupType {
@@ -25,6 +28,23 @@ impl ProxyGroupType {
     }
 }
 
+impl FromStr for ProxyGroupType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "select" => Ok(ProxyGroupType::Select),
+            "url-test" => Ok(ProxyGroupType::URLTest),
+            "fallback" => Ok(ProxyGroupType::Fallback),
+            "load-balance" => Ok(ProxyGroupType::LoadBalance),
+            "relay" => Ok(ProxyGroupType::Relay),
+            "ssid" => Ok(ProxyGroupType::SSID),
+            "smart" => Ok(ProxyGroupType::Smart),
+            other => Err(format!("Unknown proxy group type '{}'", other)),
+        }
+    }
+}
+
 /// Load balancing strategy
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BalanceStrategy {
@@ -53,6 +73,8 @@ pub struct ProxyGroupConfig {
     pub proxies: Vec<String>,
     /// List of provider names used by this group
     pub using_provider: Vec<String>,
+    /// Regex over node names selecting additional members
+    pub filter: Option<String>,
     /// URL for testing
     pub url: String,
     /// Interval in seconds between tests
@@ -63,14 +85,19 @@ pub struct ProxyGroupConfig {
     pub tolerance: u32,
     /// Strategy for load balancing
     pub strategy: BalanceStrategy,
-    /// Whether to use lazy loading
-    pub lazy: bool,
+    /// Whether to skip health checks while the group is not in use, `None`
+    /// leaves it to the client
+    pub lazy: Option<bool>,
     /// Whether to disable UDP support
     pub disable_udp: bool,
     /// Whether to persist connections
     pub persistent: bool,
     /// Whether to evaluate before use
     pub evaluate_before_use: bool,
+    /// Icon URL shown by Clash.Meta GUIs
+    pub icon: Option<String>,
+    /// Whether Clash.Meta GUIs hide the group
+    pub hidden: bool,
 }
 
 impl Default for ProxyGroupConfig {
@@ -80,15 +107,18 @@ impl Default for ProxyGroupConfig {
             group_type: ProxyGroupType::Select,
             proxies: Vec::new(),
             using_provider: Vec::new(),
+            filter: None,
             url: String::new(),
             interval: 0,
             timeout: 0,
             tolerance: 0,
             strategy: BalanceStrategy::ConsistentHashing,
-            lazy: false,
+            lazy: None,
             disable_udp: false,
             persistent: false,
             evaluate_before_use: false,
+            icon: None,
+            hidden: false,
         }
     }
 }
@@ -112,11 +142,195 @@ impl ProxyGroupConfig {
     pub fn strategy_str(&self) -> &'static str {
         self.strategy.as_str()
     }
+
+    /// Whether the group picks members by probing them
+    pub fn is_health_checked(&self) -> bool {
+        matches!(
+            self.group_type,
+            ProxyGroupType::URLTest
+                | ProxyGroupType::Fallback
+                | ProxyGroupType::LoadBalance
+                | ProxyGroupType::Smart
+        )
+    }
+
+    /// Fill the health check options the definition leaves unset
+    pub fn apply_health_check(&mut self, defaults: &GroupHealthCheck) {
+        if !self.is_health_checked() {
+            return;
+        }
+        if self.url.is_empty() {
+            self.url = defaults.url.clone();
+        }
+        if self.interval == 0 {
+            self.interval = defaults.interval;
+        }
+        if self.tolerance == 0 {
+            self.tolerance = defaults.tolerance;
+        }
+        if self.lazy.is_none() {
+            self.lazy = defaults.lazy;
+        }
+    }
+}
+
+/// Health check options of url-test, fallback and load-balance groups that
+/// do not set their own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupHealthCheck {
+    /// URL probed to test members
+    pub url: String,
+    /// Interval in seconds between tests
+    pub interval: u32,
+    /// Latency difference in milliseconds below which the current member is
+    /// kept
+    pub tolerance: u32,
+    /// Whether to skip tests while the group is not in use, `None` leaves it
+    /// to the client
+    pub lazy: Option<bool>,
+}
+
+impl Default for GroupHealthCheck {
+    fn default() -> Self {
+        Self {
+            url: "http://www.gstatic.com/generate_204".to_string(),
+            interval: 300,
+            tolerance: 0,
+            lazy: None,
+        }
+    }
+}
+
+/// Health check of the `proxy-providers` entry serving the node list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderHealthCheck {
+    /// Whether the block is emitted at all, a disabled check is left out
+    /// rather than written as `enable: false`
+    pub enable: bool,
+    /// URL probed to test the nodes
+    pub url: String,
+    /// Interval in seconds between tests
+    pub interval: u32,
+    /// Milliseconds a test may take, only written for Clash.Meta
+    pub timeout: Option<u32>,
+    /// Whether to skip tests while the provider is not in use, `None` leaves
+    /// it to the client
+    pub lazy: Option<bool>,
+}
+
+impl Default for ProviderHealthCheck {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            url: "http://www.gstatic.com/generate_204".to_string(),
+            interval: 300,
+            timeout: None,
+            lazy: None,
+        }
+    }
+}
+
+/// Rewrite of the type of the configured proxy groups
+///
+/// Spelled `from->to`, e.g. `select->url-test`, between the select,
+/// url-test, fallback and load-balance types. Groups kept from a Clash input
+/// are not rewritten, nor are the groups listed in `exclude`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupTypeOverride {
+    pub from: ProxyGroupType,
+    pub to: ProxyGroupType,
+    /// Names of the groups keeping their type
+    pub exclude: Vec<String>,
+}
+
+impl GroupTypeOverride {
+    /// Rewrite the type of the matching groups
+    ///
+    /// # Returns
+    /// The number of rewritten groups
+    pub fn apply(&self, groups: &mut [ProxyGroupConfig]) -> usize {
+        let mut rewritten = 0;
+        for group in groups
+            .iter_mut()
+            .filter(|group| group.group_type == self.from && !self.exclude.contains(&group.name))
+        {
+            group.group_type = self.to.clone();
+            rewritten += 1;
+        }
+        rewritten
+    }
+}
+
+impl FromStr for GroupTypeOverride {
+    type Err = String;
+
+    /// Parse `from->to`, without exclusions
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once("->")
+            .ok_or_else(|| format!("Invalid group type override '{}', expected from->to", s))?;
+        let member_type = |name: &str| {
+            let group_type: ProxyGroupType = name.parse()?;
+            match group_type {
+                ProxyGroupType::Select
+                | ProxyGroupType::URLTest
+                | ProxyGroupType::Fallback
+                | ProxyGroupType::LoadBalance => Ok(group_type),
+                _ => Err(format!("Group type '{}' cannot be overridden", name.trim())),
+            }
+        };
+        Ok(GroupTypeOverride {
+            from: member_type(from)?,
+            to: member_type(to)?,
+            exclude: Vec::new(),
+        })
+    }
 }
 
 /// A collection of proxy group configurations
 pub type ProxyGroupConfigs = Vec<ProxyGroupConfig>;
 
+/// Merge proxy groups sharing a name into the first of them
+///
+/// The merged group keeps the options of the first definition and takes the
+/// members and providers of the later ones after its own, each listed once.
+/// A later definition of another type is dropped with a warning, its members
+/// are not merged.
+pub fn collapse_duplicate_groups(groups: ProxyGroupConfigs) -> ProxyGroupConfigs {
+    let mut collapsed: ProxyGroupConfigs = Vec::with_capacity(groups.len());
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for group in groups {
+        let Some(&first) = index.get(&group.name) else {
+            index.insert(group.name.clone(), collapsed.len());
+            let mut group = group;
+            dedup_in_order(&mut group.proxies);
+            dedup_in_order(&mut group.using_provider);
+            collapsed.push(group);
+            continue;
+        };
+        let kept = &mut collapsed[first];
+        if kept.group_type != group.group_type {
+            log::warn!(
+                "Proxy group '{}' is defined as both {} and {}, keeping the first",
+                group.name,
+                kept.type_str(),
+                group.type_str()
+            );
+            continue;
+        }
+        kept.proxies.extend(group.proxies);
+        dedup_in_order(&mut kept.proxies);
+        kept.using_provider.extend(group.using_provider);
+        dedup_in_order(&mut kept.using_provider);
+    }
+    collapsed
+}
+
+fn dedup_in_order(items: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    items.retain(|item| seen.insert(item.clone()));
+}
+
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
 
@@ -132,13 +346,13 @@ impl Serialize for ProxyGroupConfig {
         match self.group_type {
             ProxyGroupType::LoadBalance => {
                 field_count += 4; // strategy, url, interval, tolerance
-                if !self.lazy {
+                if self.lazy.is_some() {
                     field_count += 1; // lazy
                 }
             }
             ProxyGroupType::URLTest | ProxyGroupType::Smart => {
                 field_count += 2; // url, interval
-                if !self.lazy {
+                if self.lazy.is_some() {
                     field_count += 1; // lazy
                 }
                 if self.tolerance > 0 {
@@ -192,8 +406,8 @@ impl Serialize for ProxyGroupConfig {
             ProxyGroupType::LoadBalance => {
                 // Load balancing specific fields
                 state.serialize_field("strategy", &self.strategy_str())?;
-                if !self.lazy {
-                    state.serialize_field("lazy", &self.lazy)?;
+                if let Some(lazy) = self.lazy {
+                    state.serialize_field("lazy", &lazy)?;
                 }
                 state.serialize_field("url", &self.url)?;
                 if self.interval > 0 {
@@ -205,8 +419,8 @@ impl Serialize for ProxyGroupConfig {
             }
             ProxyGroupType::URLTest | ProxyGroupType::Smart => {
                 // URL-test specific fields
-                if !self.lazy {
-                    state.serialize_field("lazy", &self.lazy)?;
+                if let Some(lazy) = self.lazy {
+                    state.serialize_field("lazy", &lazy)?;
                 }
                 state.serialize_field("url", &self.url)?;
                 if self.interval > 0 {
@@ -253,3 +467,69 @@ impl Serialize for ProxyGroupConfig {
         state.end()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(name: &str, group_type: ProxyGroupType, proxies: &[&str]) -> ProxyGroupConfig {
+        let mut group = ProxyGroupConfig::new(name.to_string(), group_type);
+        group.proxies = proxies.iter().map(|p| p.to_string()).collect();
+        group
+    }
+
+    #[test]
+    fn test_collapse_duplicate_groups() {
+        let mut first = group("Proxy", ProxyGroupType::Select, &["HK", "JP", "HK"]);
+        first.using_provider = vec!["sub1".to_string()];
+        let mut second = group("Proxy", ProxyGroupType::Select, &["JP", "US", "[]DIRECT"]);
+        second.using_provider = vec!["sub2".to_string(), "sub1".to_string()];
+        let groups = vec![
+            first,
+            group("Auto", ProxyGroupType::URLTest, &[".*"]),
+            second,
+        ];
+
+        let collapsed = collapse_duplicate_groups(groups);
+        let names: Vec<&str> = collapsed.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["Proxy", "Auto"]);
+        assert_eq!(collapsed[0].proxies, vec!["HK", "JP", "US", "[]DIRECT"]);
+        assert_eq!(collapsed[0].using_provider, vec!["sub1", "sub2"]);
+    }
+
+    #[test]
+    fn test_collapse_conflicting_groups() {
+        let mut first = group("Auto", ProxyGroupType::Select, &["HK"]);
+        first.interval = 120;
+        let groups = vec![
+            first,
+            group("Auto", ProxyGroupType::URLTest, &["JP"]),
+            group("Auto", ProxyGroupType::Select, &["US"]),
+        ];
+
+        let collapsed = collapse_duplicate_groups(groups);
+        assert_eq!(collapsed.len(), 1);
+        assert_eq!(collapsed[0].group_type, ProxyGroupType::Select);
+        assert_eq!(collapsed[0].interval, 120);
+        // The url-test definition is dropped, the later select one merged
+        assert_eq!(collapsed[0].proxies, vec!["HK", "US"]);
+    }
+
+    #[test]
+    fn test_group_type_override() {
+        let mut group_type: GroupTypeOverride = "select -> url-test".parse().unwrap();
+        group_type.exclude = vec!["Proxy".to_string()];
+        let mut groups = vec![
+            group("Proxy", ProxyGroupType::Select, &["Auto", "HK"]),
+            group("Streaming", ProxyGroupType::Select, &["HK"]),
+            group("Backup", ProxyGroupType::Fallback, &["HK"]),
+        ];
+        assert_eq!(group_type.apply(&mut groups), 1);
+        let types: Vec<&str> = groups.iter().map(|g| g.type_str()).collect();
+        assert_eq!(types, vec!["select", "url-test", "fallback"]);
+
+        assert!("select".parse::<GroupTypeOverride>().is_err());
+        assert!("select->relay".parse::<GroupTypeOverride>().is_err());
+        assert!("select->speed-test".parse::<GroupTypeOverride>().is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Credentials of HTTP and SOCKS5 nodes
+//!
+//! Both protocols take an optional username and password, kept in
+//! [`Proxy::username`] and [`Proxy::password`] with empty strings meaning
+//! unset. Clients differ in which partial credentials they send: SOCKS5
+//! username/password authentication (RFC 1929) cannot go without a username
+//! anywhere, and Clash only sends HTTP basic auth when both parts are set.
+//! A node whose credentials a target would silently drop is skipped instead
+//! of being emitted without authentication.
+
+use crate::models::{Proxy, ProxyType};
+
+/// Which parts of the credentials a client needs before it sends them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthRule {
+    /// Sent when the username is set, the password may be empty
+    UsernameRequired,
+    /// Sent only when both the username and the password are set
+    BothRequired,
+}
+
+impl Proxy {
+    /// Username of an HTTP or SOCKS5 node, `None` when empty
+    pub fn auth_username(&self) -> Option<&str> {
+        self.username
+            .as_deref()
+            .filter(|username| !username.is_empty())
+    }
+
+    /// Password of an HTTP or SOCKS5 node, `None` when empty
+    pub fn auth_password(&self) -> Option<&str> {
+        self.password
+            .as_deref()
+            .filter(|password| !password.is_empty())
+    }
+}
+
+/// Why a target following `rule` cannot carry the credentials of a node
+///
+/// `None` when the node has no credentials, complete ones, or is not an HTTP
+/// or SOCKS5 node.
+pub fn auth_skip_reason(node: &Proxy, rule: AuthRule) -> Option<String> {
+    if !matches!(
+        node.proxy_type,
+        ProxyType::HTTP | ProxyType::HTTPS | ProxyType::Socks5
+    ) {
+        return None;
+    }
+    match (node.auth_username(), node.auth_password(), rule) {
+        (None, Some(_), _) => Some(format!(
+            "{} credentials with a password but no username cannot be sent",
+            node.proxy_type.to_string()
+        )),
+        (Some(_), None, AuthRule::BothRequired) => Some(format!(
+            "{} credentials with a username but no password cannot be sent",
+            node.proxy_type.to_string()
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_skip_reason() {
+        let node = |proxy_type: ProxyType, username: &str, password: &str| Proxy {
+            proxy_type,
+            username: Some(username.to_string()),
+            password: Some(password.to_string()),
+            ..Default::default()
+        };
+
+        let full = node(ProxyType::HTTP, "user", "pass");
+        assert_eq!(full.auth_username(), Some("user"));
+        assert_eq!(auth_skip_reason(&full, AuthRule::BothRequired), None);
+        let none = node(ProxyType::Socks5, "", "");
+        assert_eq!(none.auth_password(), None);
+        assert_eq!(auth_skip_reason(&none, AuthRule::BothRequired), None);
+
+        let username_only = node(ProxyType::HTTPS, "user", "");
+        assert_eq!(
+            auth_skip_reason(&username_only, AuthRule::UsernameRequired),
+            None
+        );
+        assert!(auth_skip_reason(&username_only, AuthRule::BothRequired).is_some());
+        let password_only = node(ProxyType::Socks5, "", "pass");
+        assert!(auth_skip_reason(&password_only, AuthRule::UsernameRequired).is_some());
+
+        // Other protocols use the fields for other things
+        let trojan = node(ProxyType::Trojan, "", "pass");
+        assert_eq!(auth_skip_reason(&trojan, AuthRule::BothRequired), None);
+    }
+}
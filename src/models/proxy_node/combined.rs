@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 
 use super::anytls::AnyTlsProxy;
+use super::custom::CustomProxy;
 use super::shadowsocks::ShadowsocksProxy;
+use super::trojan_go::TrojanGoProxy;
+use super::tuic::TuicProxy;
 use super::vless::VlessProxy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,4 +13,7 @@ pub enum CombinedProxy {
     Vless(VlessProxy),
     Shadowsocks(ShadowsocksProxy),
     AnyTls(AnyTlsProxy),
+    Tuic(TuicProxy),
+    TrojanGo(TrojanGoProxy),
+    Custom(CustomProxy),
 }
@@ -0,0 +1,39 @@
+//! Nodes of protocols handled by a registered [`ProxyCodec`]
+//!
+//! [`ProxyCodec`]: crate::models::proxy_codec::ProxyCodec
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::combined::CombinedProxy;
+use crate::models::Proxy;
+
+/// Options of a node parsed by a registered codec
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CustomProxy {
+    /// Name of the codec that parsed the node and emits it
+    pub codec: String,
+    /// Whatever the codec keeps about the node
+    #[serde(default)]
+    pub data: JsonValue,
+}
+
+impl CustomProxy {
+    pub fn new(codec: impl Into<String>, data: JsonValue) -> Self {
+        CustomProxy {
+            codec: codec.into(),
+            data,
+        }
+    }
+}
+
+impl Proxy {
+    /// Codec options of the node, if a registered codec parsed it
+    pub fn custom_proxy(&self) -> Option<&CustomProxy> {
+        match &self.combined_proxy {
+            Some(CombinedProxy::Custom(custom)) => Some(custom),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Hysteria (v1) nodes
+//!
+//! Hysteria v1 and Hysteria2 are different protocols sharing a name. v1
+//! authenticates with an auth string, declares its bandwidth up front and
+//! can disguise its packets as another protocol; Hysteria2 authenticates
+//! with a password and negotiates bandwidth. They are kept apart as
+//! [`ProxyType::Hysteria`] and [`ProxyType::Hysteria2`]: `hysteria://` links
+//! and `type: hysteria` proxies only ever give the former.
+//!
+//! On a v1 node the shared [`Proxy`] fields mean:
+//!
+//! - `auth_str` is the auth string, `auth` the same given as base64;
+//! - `protocol` is the packet disguise, see [`HysteriaProtocol`];
+//! - `obfs_param` is the obfuscation password, `obfs` its mode (`xplus`);
+//! - `up_speed` and `down_speed` are in Mbps and never zero, nodes giving
+//!   none get [`HYSTERIA_DEFAULT_UP_MBPS`] and [`HYSTERIA_DEFAULT_DOWN_MBPS`];
+//! - `recv_window_conn` and `recv_window` are QUIC receive windows in bytes.
+//!
+//! [`ProxyType::Hysteria`]: crate::models::ProxyType::Hysteria
+//! [`ProxyType::Hysteria2`]: crate::models::ProxyType::Hysteria2
+//! [`Proxy`]: crate::models::Proxy
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Upload bandwidth of nodes that declare none, in Mbps
+pub const HYSTERIA_DEFAULT_UP_MBPS: u32 = 10;
+/// Download bandwidth of nodes that declare none, in Mbps
+pub const HYSTERIA_DEFAULT_DOWN_MBPS: u32 = 50;
+
+/// What Hysteria v1 packets are disguised as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HysteriaProtocol {
+    /// Plain QUIC over UDP
+    #[default]
+    Udp,
+    /// UDP packets looking like WeChat video calls
+    WechatVideo,
+    /// UDP packets with a fake TCP header, needs raw sockets
+    FakeTcp,
+}
+
+impl HysteriaProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HysteriaProtocol::Udp => "udp",
+            HysteriaProtocol::WechatVideo => "wechat-video",
+            HysteriaProtocol::FakeTcp => "faketcp",
+        }
+    }
+
+    /// Protocol of a link or config value, unknown values fall back to UDP
+    /// with a warning
+    pub fn parse_lossy(value: &str) -> Self {
+        value.parse().unwrap_or_else(|e| {
+            log::warn!("{}, using udp", e);
+            HysteriaProtocol::Udp
+        })
+    }
+}
+
+impl FromStr for HysteriaProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "udp" => Ok(HysteriaProtocol::Udp),
+            "wechat-video" => Ok(HysteriaProtocol::WechatVideo),
+            "faketcp" => Ok(HysteriaProtocol::FakeTcp),
+            other => Err(format!("Unknown Hysteria protocol '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for HysteriaProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Unit of a bandwidth value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BandwidthUnit {
+    BitsPerSecond,
+    Kbps,
+    Mbps,
+    Gbps,
+    Tbps,
+    /// Bytes per second, what Hysteria's own configs mean by a bare number
+    BytesPerSecond,
+}
+
+impl BandwidthUnit {
+    fn bits(&self) -> f64 {
+        match self {
+            BandwidthUnit::BitsPerSecond => 1.0,
+            BandwidthUnit::Kbps => 1e3,
+            BandwidthUnit::Mbps => 1e6,
+            BandwidthUnit::Gbps => 1e9,
+            BandwidthUnit::Tbps => 1e12,
+            BandwidthUnit::BytesPerSecond => 8.0,
+        }
+    }
+
+    /// Unit of a suffix such as `Mbps`, `mbit/s` or `g`, case-insensitively
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        let suffix = suffix.to_lowercase();
+        let suffix = suffix
+            .strip_suffix("/s")
+            .or_else(|| suffix.strip_suffix("ps"))
+            .unwrap_or(&suffix);
+        match suffix {
+            "b" | "bit" => Some(BandwidthUnit::BitsPerSecond),
+            "k" | "kb" | "kbit" => Some(BandwidthUnit::Kbps),
+            "m" | "mb" | "mbit" => Some(BandwidthUnit::Mbps),
+            "g" | "gb" | "gbit" => Some(BandwidthUnit::Gbps),
+            "t" | "tb" | "tbit" => Some(BandwidthUnit::Tbps),
+            _ => None,
+        }
+    }
+}
+
+/// A bandwidth, kept in bits per second
+///
+/// Values are a number with an optional unit: `100`, `100 Mbps`, `100mbps`,
+/// `1 gbps`, `1.5G` or `500 kbit/s`. Units are bits, `k`, `m`, `g` and `t`
+/// with or without `bps`, `bit` or `/s`. What a bare number means depends on
+/// where it comes from, Clash configs and links give Mbps while Hysteria's
+/// own configs give bytes per second, so [`Bandwidth::parse`] takes it.
+/// [`Proxy`] nodes store bandwidths as whole Mbps, see [`Bandwidth::mbps`].
+///
+/// [`Proxy`]: crate::models::Proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bandwidth {
+    bps: u64,
+}
+
+impl Bandwidth {
+    pub fn from_mbps(mbps: u32) -> Self {
+        Bandwidth {
+            bps: mbps as u64 * 1_000_000,
+        }
+    }
+
+    /// Parse a bandwidth, reading bare numbers in `bare_unit`
+    pub fn parse(value: &str, bare_unit: BandwidthUnit) -> Result<Self, String> {
+        let trimmed = value.trim();
+        let number_end = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(trimmed.len());
+        let (number, suffix) = trimmed.split_at(number_end);
+        let number: f64 = number
+            .parse()
+            .map_err(|_| format!("Invalid bandwidth '{}': no number", value))?;
+        let suffix = suffix.trim();
+        let unit = if suffix.is_empty() {
+            bare_unit
+        } else {
+            BandwidthUnit::from_suffix(suffix).ok_or_else(|| {
+                format!("Invalid bandwidth '{}': unknown unit '{}'", value, suffix)
+            })?
+        };
+        let bps = number * unit.bits();
+        if !bps.is_finite() || bps >= u64::MAX as f64 {
+            return Err(format!("Invalid bandwidth '{}': too large", value));
+        }
+        Ok(Bandwidth {
+            bps: bps.round() as u64,
+        })
+    }
+
+    pub fn bits_per_second(&self) -> u64 {
+        self.bps
+    }
+
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bps / 8
+    }
+
+    /// Whole Mbps, rounded and at least 1 for any non-zero bandwidth
+    pub fn mbps(&self) -> u32 {
+        if self.bps == 0 {
+            return 0;
+        }
+        let mbps = (self.bps as f64 / 1e6).round().max(1.0);
+        mbps.min(u32::MAX as f64) as u32
+    }
+
+    /// Clash.Meta form, e.g. `100 Mbps`
+    pub fn to_clash(&self) -> String {
+        format!("{} Mbps", self.mbps())
+    }
+}
+
+impl FromStr for Bandwidth {
+    type Err = String;
+
+    /// Parse a bandwidth, bare numbers are Mbps
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Bandwidth::parse(s, BandwidthUnit::Mbps)
+    }
+}
+
+/// Parse a bandwidth value such as `100`, `100 Mbps` or `1Gbps` into Mbps.
+/// Unparseable values yield `None` with a warning, so the node keeps its
+/// default bandwidth.
+pub fn parse_bandwidth(value: &str) -> Option<u32> {
+    match value.parse::<Bandwidth>() {
+        Ok(bandwidth) => Some(bandwidth.mbps()),
+        Err(e) => {
+            log::warn!("{}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bandwidth() {
+        for value in [
+            "100",
+            "100 Mbps",
+            "100mbps",
+            "100 Mbit/s",
+            "100M",
+            " 100 mb ",
+        ] {
+            assert_eq!(parse_bandwidth(value), Some(100), "{}", value);
+        }
+        assert_eq!(parse_bandwidth("1 gbps"), Some(1000));
+        assert_eq!(parse_bandwidth("1.5G"), Some(1500));
+        assert_eq!(parse_bandwidth("500 kbps"), Some(1));
+        assert_eq!(parse_bandwidth("0"), Some(0));
+
+        let bytes = Bandwidth::parse("10485760", BandwidthUnit::BytesPerSecond).unwrap();
+        assert_eq!(bytes.bits_per_second(), 83_886_080);
+        assert_eq!(bytes.bytes_per_second(), 10_485_760);
+        assert_eq!(bytes.mbps(), 84);
+        assert_eq!(Bandwidth::from_mbps(100).to_clash(), "100 Mbps");
+        assert_eq!("2 Tbps".parse::<Bandwidth>().unwrap().mbps(), 2_000_000);
+
+        assert_eq!(
+            "100 parsecs".parse::<Bandwidth>(),
+            Err("Invalid bandwidth '100 parsecs': unknown unit 'parsecs'".to_string())
+        );
+        assert!("fast".parse::<Bandwidth>().unwrap_err().contains("'fast'"));
+        assert_eq!(parse_bandwidth("1e9"), None);
+    }
+}
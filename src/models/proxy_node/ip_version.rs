@@ -0,0 +1,119 @@
+//! IP version preference of a node
+//!
+//! Clash.Meta (`ip-version`) and sing-box (`domain_strategy`) can restrict or
+//! order the address families a node's server name resolves to. Links give it
+//! as an `ip-version` query parameter, Clash configs as the `ip-version` key.
+//! Unlike client fingerprints the set is closed: a value outside
+//! [`IP_VERSIONS`] is an error, as a client would refuse the whole config.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Values accepted for `ip-version`, in the order error messages list them
+pub const IP_VERSIONS: &[&str] = &["dual", "ipv4", "ipv6", "ipv4-prefer", "ipv6-prefer"];
+
+/// Address families a node dials its server with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IpVersion {
+    /// Both families, the client's default
+    Dual,
+    /// IPv4 only
+    Ipv4,
+    /// IPv6 only
+    Ipv6,
+    /// Both families, IPv4 first
+    Ipv4Prefer,
+    /// Both families, IPv6 first
+    Ipv6Prefer,
+}
+
+impl IpVersion {
+    /// Name as used by Clash.Meta and in links
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IpVersion::Dual => "dual",
+            IpVersion::Ipv4 => "ipv4",
+            IpVersion::Ipv6 => "ipv6",
+            IpVersion::Ipv4Prefer => "ipv4-prefer",
+            IpVersion::Ipv6Prefer => "ipv6-prefer",
+        }
+    }
+
+    /// sing-box `domain_strategy`, `None` for dual stack which is its default
+    pub fn singbox_domain_strategy(&self) -> Option<&'static str> {
+        match self {
+            IpVersion::Dual => None,
+            IpVersion::Ipv4 => Some("ipv4_only"),
+            IpVersion::Ipv6 => Some("ipv6_only"),
+            IpVersion::Ipv4Prefer => Some("prefer_ipv4"),
+            IpVersion::Ipv6Prefer => Some("prefer_ipv6"),
+        }
+    }
+}
+
+impl FromStr for IpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dual" => Ok(IpVersion::Dual),
+            "ipv4" => Ok(IpVersion::Ipv4),
+            "ipv6" => Ok(IpVersion::Ipv6),
+            "ipv4-prefer" => Ok(IpVersion::Ipv4Prefer),
+            "ipv6-prefer" => Ok(IpVersion::Ipv6Prefer),
+            other => Err(format!(
+                "Unknown ip-version '{}', expected one of {}",
+                other,
+                IP_VERSIONS.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The `ip-version` query parameter of a link, if it has one
+pub fn link_ip_version(link: &str) -> Result<Option<IpVersion>, String> {
+    let link = link.split('#').next().unwrap_or(link);
+    let Some((_, query)) = link.split_once('?') else {
+        return Ok(None);
+    };
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| *key == "ip-version")
+        .map(|(_, value)| crate::utils::url_decode(value).parse())
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ip_version() {
+        assert_eq!("IPv4".parse::<IpVersion>(), Ok(IpVersion::Ipv4));
+        for name in IP_VERSIONS {
+            assert_eq!(name.parse::<IpVersion>().unwrap().as_str(), *name);
+        }
+        let err = "ipv5".parse::<IpVersion>().unwrap_err();
+        assert!(err.contains("'ipv5'") && err.contains("dual, ipv4, ipv6"));
+
+        assert_eq!(
+            link_ip_version("trojan://pw@example.com:443?sni=a&ip-version=ipv6-prefer#node"),
+            Ok(Some(IpVersion::Ipv6Prefer))
+        );
+        assert_eq!(
+            link_ip_version("trojan://pw@example.com:443#a?ip-version=x"),
+            Ok(None)
+        );
+        assert!(link_ip_version("vless://id@example.com:443?ip-version=both").is_err());
+    }
+}
@@ -1,4 +1,15 @@
 pub mod anytls;
+pub mod auth;
 pub mod combined;
+pub mod custom;
+pub mod hysteria;
+pub mod ip_version;
+pub mod multiplex;
 pub mod shadowsocks;
+pub mod shadowtls;
+pub mod ssr;
+pub mod transport;
+pub mod trojan_go;
+pub mod tuic;
+pub mod utls;
 pub mod vless;
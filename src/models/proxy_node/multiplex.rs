@@ -0,0 +1,392 @@
+//! Connection multiplexing of a node
+//!
+//! sing-box multiplexes the streams of Shadowsocks, VMess, Trojan and VLESS
+//! outbounds over a few connections with `smux`, `yamux` or `h2mux`,
+//! optionally padded and with TCP Brutal congestion control. Clash.Meta
+//! speaks the same sing-mux protocol under its `smux` key. The server must
+//! run sing-mux too, so multiplexing is only emitted when a node asks for it:
+//! links with a `multiplex` query parameter, Clash configs with an enabled
+//! `smux` mapping, interchange documents with a `Multiplex` field, or every
+//! node without one when the export sets
+//! [`ExtraSettings::multiplex`](crate::models::ExtraSettings::multiplex).
+//! That default becomes sing-box `multiplex` and Clash.Meta `smux` alike,
+//! and skips the nodes [`takes_default_multiplex`] rules out. Other targets,
+//! vanilla Clash and ClashR included, ignore the settings.
+//!
+//! The parameter is a comma separated spec, the protocol first:
+//! `h2mux,max_connections=4,padding=true,brutal=50:100`. The keys are the
+//! sing-box option names, `brutal` takes the upload and download rate in
+//! Mbps.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use serde_yaml::{Mapping, Value as YamlValue};
+
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::hysteria::Bandwidth;
+use crate::models::{Proxy, ProxyType};
+
+/// Stream multiplexer spoken over the connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiplexProtocol {
+    Smux,
+    Yamux,
+    /// sing-box's default
+    #[default]
+    H2mux,
+}
+
+impl MultiplexProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MultiplexProtocol::Smux => "smux",
+            MultiplexProtocol::Yamux => "yamux",
+            MultiplexProtocol::H2mux => "h2mux",
+        }
+    }
+}
+
+impl FromStr for MultiplexProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "smux" => Ok(MultiplexProtocol::Smux),
+            "yamux" => Ok(MultiplexProtocol::Yamux),
+            "h2mux" => Ok(MultiplexProtocol::H2mux),
+            other => Err(format!(
+                "Unknown multiplex protocol '{}', expected smux, yamux or h2mux",
+                other
+            )),
+        }
+    }
+}
+
+/// TCP Brutal rates in Mbps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Brutal {
+    pub up_mbps: u32,
+    pub down_mbps: u32,
+}
+
+/// Multiplexing settings of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Multiplex {
+    pub protocol: MultiplexProtocol,
+    /// Maximum connections, conflicts with `max_streams`
+    pub max_connections: Option<u32>,
+    /// Streams opened on a connection before another one is opened
+    pub min_streams: Option<u32>,
+    /// Streams a connection carries at most, conflicts with
+    /// `max_connections`
+    pub max_streams: Option<u32>,
+    pub padding: bool,
+    pub brutal: Option<Brutal>,
+}
+
+impl Multiplex {
+    /// The sing-box `multiplex` object
+    pub fn singbox_json(&self) -> JsonValue {
+        let mut multiplex = Map::new();
+        multiplex.insert("enabled".to_string(), JsonValue::Bool(true));
+        multiplex.insert("protocol".to_string(), json!(self.protocol.as_str()));
+        for (key, value) in [
+            ("max_connections", self.max_connections),
+            ("min_streams", self.min_streams),
+            ("max_streams", self.max_streams),
+        ] {
+            if let Some(value) = value {
+                multiplex.insert(key.to_string(), json!(value));
+            }
+        }
+        if self.padding {
+            multiplex.insert("padding".to_string(), JsonValue::Bool(true));
+        }
+        if let Some(brutal) = self.brutal {
+            multiplex.insert(
+                "brutal".to_string(),
+                json!({
+                    "enabled": true,
+                    "up_mbps": brutal.up_mbps,
+                    "down_mbps": brutal.down_mbps
+                }),
+            );
+        }
+        JsonValue::Object(multiplex)
+    }
+
+    /// The Clash.Meta `smux` mapping
+    ///
+    /// Clash.Meta spells the options in kebab case and puts TCP Brutal
+    /// under `brutal-opts` with `up` and `down` in Mbps.
+    pub fn clash_smux_yaml(&self) -> YamlValue {
+        let mut smux = Mapping::new();
+        smux.insert("enabled".into(), YamlValue::Bool(true));
+        smux.insert("protocol".into(), self.protocol.as_str().into());
+        for (key, value) in [
+            ("max-connections", self.max_connections),
+            ("min-streams", self.min_streams),
+            ("max-streams", self.max_streams),
+        ] {
+            if let Some(value) = value {
+                smux.insert(key.into(), value.into());
+            }
+        }
+        if self.padding {
+            smux.insert("padding".into(), YamlValue::Bool(true));
+        }
+        if let Some(brutal) = self.brutal {
+            let mut opts = Mapping::new();
+            opts.insert("enabled".into(), YamlValue::Bool(true));
+            opts.insert("up".into(), brutal.up_mbps.into());
+            opts.insert("down".into(), brutal.down_mbps.into());
+            smux.insert("brutal-opts".into(), YamlValue::Mapping(opts));
+        }
+        YamlValue::Mapping(smux)
+    }
+
+    /// Read a Clash.Meta `smux` mapping, `None` when it is not enabled
+    ///
+    /// Brutal rates are numbers of Mbps or bandwidths such as `50 Mbps`.
+    /// Options sing-box has no counterpart for, e.g. `statistic`, are
+    /// ignored.
+    pub fn from_clash_smux(smux: &YamlValue) -> Result<Option<Self>, String> {
+        if !smux.is_mapping() {
+            return Err("Clash smux is not a mapping".to_string());
+        }
+        if !smux
+            .get("enabled")
+            .and_then(YamlValue::as_bool)
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+        let count = |key: &str| {
+            smux.get(key)
+                .map(|value| {
+                    value
+                        .as_u64()
+                        .and_then(|count| u32::try_from(count).ok())
+                        .ok_or_else(|| format!("Invalid smux {} {:?}", key, value))
+                })
+                .transpose()
+        };
+        let mut multiplex = Multiplex {
+            protocol: match smux.get("protocol").and_then(YamlValue::as_str) {
+                Some(protocol) => protocol.parse()?,
+                None => MultiplexProtocol::default(),
+            },
+            max_connections: count("max-connections")?,
+            min_streams: count("min-streams")?,
+            max_streams: count("max-streams")?,
+            padding: smux.get("padding").and_then(YamlValue::as_bool) == Some(true),
+            brutal: None,
+        };
+        if let Some(opts) = smux
+            .get("brutal-opts")
+            .filter(|opts| opts.get("enabled").and_then(YamlValue::as_bool) == Some(true))
+        {
+            let rate = |key: &str| match opts.get(key) {
+                Some(YamlValue::Number(mbps)) => mbps
+                    .as_u64()
+                    .and_then(|mbps| u32::try_from(mbps).ok())
+                    .ok_or_else(|| format!("Invalid smux brutal {} {}", key, mbps)),
+                Some(YamlValue::String(rate)) => rate.parse::<Bandwidth>().map(|rate| rate.mbps()),
+                _ => Err(format!("Missing smux brutal {}", key)),
+            };
+            multiplex.brutal = Some(Brutal {
+                up_mbps: rate("up")?,
+                down_mbps: rate("down")?,
+            });
+        }
+        multiplex.check().map(Some)
+    }
+
+    fn check(self) -> Result<Self, String> {
+        if self.max_connections.is_some() && self.max_streams.is_some() {
+            return Err("Multiplex max_connections and max_streams conflict".to_string());
+        }
+        Ok(self)
+    }
+}
+
+impl FromStr for Multiplex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut multiplex = Multiplex::default();
+        let count = |key: &str, value: &str| {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid multiplex {} '{}'", key, value))
+        };
+        for (index, option) in s.split(',').map(str::trim).enumerate() {
+            let Some((key, value)) = option.split_once('=') else {
+                if index == 0 {
+                    multiplex.protocol = option.parse()?;
+                    continue;
+                }
+                return Err(format!("Invalid multiplex option '{}'", option));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "protocol" => multiplex.protocol = value.parse()?,
+                "max_connections" => multiplex.max_connections = Some(count(key, value)?),
+                "min_streams" => multiplex.min_streams = Some(count(key, value)?),
+                "max_streams" => multiplex.max_streams = Some(count(key, value)?),
+                "padding" => {
+                    multiplex.padding = matches!(value, "true" | "1");
+                }
+                "brutal" => {
+                    let (up, down) = value
+                        .split_once(':')
+                        .ok_or_else(|| format!("Invalid multiplex brutal '{}'", value))?;
+                    multiplex.brutal = Some(Brutal {
+                        up_mbps: count("brutal upload", up)?,
+                        down_mbps: count("brutal download", down)?,
+                    });
+                }
+                _ => return Err(format!("Unknown multiplex option '{}'", key)),
+            }
+        }
+        multiplex.check()
+    }
+}
+
+impl fmt::Display for Multiplex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.protocol.as_str())?;
+        for (key, value) in [
+            ("max_connections", self.max_connections),
+            ("min_streams", self.min_streams),
+            ("max_streams", self.max_streams),
+        ] {
+            if let Some(value) = value {
+                write!(f, ",{}={}", key, value)?;
+            }
+        }
+        if self.padding {
+            f.write_str(",padding=true")?;
+        }
+        if let Some(brutal) = self.brutal {
+            write!(f, ",brutal={}:{}", brutal.up_mbps, brutal.down_mbps)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether sing-box, and so Clash.Meta, multiplexes outbounds of
+/// `proxy_type`
+pub fn singbox_multiplexes(proxy_type: ProxyType) -> bool {
+    matches!(
+        proxy_type,
+        ProxyType::Shadowsocks | ProxyType::VMess | ProxyType::Trojan | ProxyType::Vless
+    )
+}
+
+/// Whether the export default multiplexing applies to `node`
+///
+/// sing-box refuses to multiplex Shadowsocks outbounds with UDP over TCP
+/// and VLESS outbounds with a `flow`, `udp_over_tcp` is whether the node is
+/// emitted with it.
+pub fn takes_default_multiplex(node: &Proxy, udp_over_tcp: bool) -> bool {
+    if !singbox_multiplexes(node.proxy_type) {
+        return false;
+    }
+    match &node.combined_proxy {
+        Some(CombinedProxy::Vless(vless)) => vless.flow.as_deref().is_none_or(str::is_empty),
+        _ => node.proxy_type != ProxyType::Shadowsocks || !udp_over_tcp,
+    }
+}
+
+/// The `multiplex` query parameter of a link, if it has one
+pub fn link_multiplex(link: &str) -> Result<Option<Multiplex>, String> {
+    let link = link.split('#').next().unwrap_or(link);
+    let Some((_, query)) = link.split_once('?') else {
+        return Ok(None);
+    };
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| *key == "multiplex")
+        .map(|(_, value)| crate::utils::url_decode(value).parse())
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multiplex() {
+        let multiplex: Multiplex = "smux, max_connections=4, padding=true, brutal=50:100"
+            .parse()
+            .unwrap();
+        assert_eq!(multiplex.protocol, MultiplexProtocol::Smux);
+        assert_eq!(
+            multiplex.to_string(),
+            "smux,max_connections=4,padding=true,brutal=50:100"
+        );
+        assert_eq!(
+            multiplex.singbox_json(),
+            json!({
+                "enabled": true,
+                "protocol": "smux",
+                "max_connections": 4,
+                "padding": true,
+                "brutal": { "enabled": true, "up_mbps": 50, "down_mbps": 100 }
+            })
+        );
+
+        let defaults: Multiplex = "max_streams=8".parse().unwrap();
+        assert_eq!(defaults.protocol, MultiplexProtocol::H2mux);
+        assert!("mplex".parse::<Multiplex>().is_err());
+        assert!("smux,brutal=50".parse::<Multiplex>().is_err());
+        assert!("smux,max_connections=4,max_streams=8"
+            .parse::<Multiplex>()
+            .is_err());
+
+        assert_eq!(
+            link_multiplex("trojan://pw@example.com:443?multiplex=yamux%2Cpadding%3D1#node"),
+            Ok(Some(Multiplex {
+                protocol: MultiplexProtocol::Yamux,
+                padding: true,
+                ..Default::default()
+            }))
+        );
+        assert_eq!(link_multiplex("trojan://pw@example.com:443"), Ok(None));
+    }
+
+    #[test]
+    fn test_clash_smux_round_trip() {
+        let multiplex: Multiplex = "h2mux,min_streams=2,padding=true,brutal=20:80"
+            .parse()
+            .unwrap();
+        let smux = multiplex.clash_smux_yaml();
+        assert_eq!(
+            smux,
+            serde_yaml::from_str::<YamlValue>(
+                "{ enabled: true, protocol: h2mux, min-streams: 2, padding: true, \
+                 brutal-opts: { enabled: true, up: 20, down: 80 } }"
+            )
+            .unwrap()
+        );
+        assert_eq!(Multiplex::from_clash_smux(&smux), Ok(Some(multiplex)));
+
+        let parse = |yaml: &str| Multiplex::from_clash_smux(&serde_yaml::from_str(yaml).unwrap());
+        assert_eq!(parse("{ protocol: smux }"), Ok(None));
+        assert_eq!(
+            parse("{ enabled: true, brutal-opts: { enabled: false, up: 1 } }"),
+            Ok(Some(Multiplex::default()))
+        );
+        assert!(parse("{ enabled: true, max-connections: -1 }").is_err());
+        assert!(parse("{ enabled: true, brutal-opts: { enabled: true, up: 10 } }").is_err());
+        assert!(parse("{ enabled: true, max-connections: 4, max-streams: 8 }").is_err());
+        assert!(parse("smux").is_err());
+    }
+}
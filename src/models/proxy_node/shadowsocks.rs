@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+/// UDP over TCP versions known to sing-box and Clash.Meta
+pub const UDP_OVER_TCP_VERSIONS: &[u8] = &[1, 2];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ShadowsocksProxy {
     pub server: String,
     pub port: u16,
@@ -13,6 +17,7 @@ pub struct ShadowsocksProxy {
     pub plugin: Option<String>,
     pub plugin_opts: Option<String>,
     pub udp_over_tcp: Option<bool>,
+    /// UDP over TCP protocol version, `None` leaves it to the client
     pub udp_over_tcp_version: Option<u8>,
     pub client_fingerprint: Option<String>,
 }
@@ -35,3 +40,67 @@ impl Default for ShadowsocksProxy {
         }
     }
 }
+
+impl ShadowsocksProxy {
+    /// Set the UDP over TCP options from a Clash proxy entry
+    ///
+    /// `udp-over-tcp` is either a flag, with the version in
+    /// `udp-over-tcp-version`, or a mapping with `enabled` and `version`.
+    /// Unknown versions are dropped with a warning so the client default
+    /// applies.
+    pub fn set_udp_over_tcp(
+        &mut self,
+        udp_over_tcp: Option<&serde_yaml::Value>,
+        version: Option<&serde_yaml::Value>,
+    ) {
+        let (enabled, nested_version) = match udp_over_tcp {
+            Some(serde_yaml::Value::Mapping(map)) => (
+                map.get("enabled").and_then(serde_yaml::Value::as_bool),
+                map.get("version"),
+            ),
+            Some(value) => (value.as_bool(), None),
+            None => (None, None),
+        };
+        self.udp_over_tcp = enabled;
+        self.udp_over_tcp_version = nested_version
+            .or(version)
+            .and_then(serde_yaml::Value::as_u64)
+            .and_then(|version| match u8::try_from(version) {
+                Ok(version) if UDP_OVER_TCP_VERSIONS.contains(&version) => Some(version),
+                _ => {
+                    log::warn!("Ignoring unknown UDP over TCP version {}", version);
+                    None
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_udp_over_tcp() {
+        let yaml = |s: &str| serde_yaml::from_str::<serde_yaml::Value>(s).unwrap();
+        let mut ss = ShadowsocksProxy::default();
+
+        ss.set_udp_over_tcp(Some(&yaml("true")), None);
+        assert_eq!(ss.udp_over_tcp, Some(true));
+        assert_eq!(ss.udp_over_tcp_version, None);
+
+        ss.set_udp_over_tcp(Some(&yaml("true")), Some(&yaml("2")));
+        assert_eq!(ss.udp_over_tcp_version, Some(2));
+
+        ss.set_udp_over_tcp(Some(&yaml("{enabled: true, version: 1}")), None);
+        assert_eq!(
+            (ss.udp_over_tcp, ss.udp_over_tcp_version),
+            (Some(true), Some(1))
+        );
+
+        ss.set_udp_over_tcp(Some(&yaml("true")), Some(&yaml("3")));
+        assert_eq!(ss.udp_over_tcp_version, None);
+
+        ss.set_udp_over_tcp(None, None);
+        assert_eq!(ss.udp_over_tcp, None);
+    }
+}
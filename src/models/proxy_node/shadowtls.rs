@@ -0,0 +1,94 @@
+//! ShadowTLS layer wrapping Shadowsocks nodes
+//!
+//! ShadowTLS is carried on a Shadowsocks [`Proxy`] as the `shadow-tls`
+//! plugin, with options in the usual `key=value;...` plugin string.
+
+use crate::models::Proxy;
+
+/// Plugin name used for the ShadowTLS layer
+pub const SHADOW_TLS_PLUGIN: &str = "shadow-tls";
+
+/// ShadowTLS settings of a Shadowsocks node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowTlsOptions {
+    /// Protocol version, 1 to 3
+    pub version: u8,
+    /// Handshake server name
+    pub host: String,
+    /// Password, not used by version 1
+    pub password: Option<String>,
+}
+
+impl ShadowTlsOptions {
+    /// Whether a plugin name refers to ShadowTLS
+    pub fn is_shadow_tls_plugin(plugin: &str) -> bool {
+        matches!(
+            plugin.to_lowercase().as_str(),
+            "shadow-tls" | "shadowtls" | "shadow_tls"
+        )
+    }
+
+    /// Parse a `host=...;password=...;version=3` plugin string
+    ///
+    /// The version defaults to 3. Returns `None` without a host.
+    pub fn from_plugin_opts(opts: &str) -> Option<Self> {
+        let mut options = ShadowTlsOptions {
+            version: 3,
+            host: String::new(),
+            password: None,
+        };
+        for opt in opts.split(';') {
+            let Some((key, value)) = opt.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "host" | "sni" => options.host = value.to_string(),
+                "password" if !value.is_empty() => options.password = Some(value.to_string()),
+                "version" => {
+                    options.version = value.parse().ok().filter(|v| (1..=3).contains(v))?
+                }
+                _ => {}
+            }
+        }
+        (!options.host.is_empty()).then_some(options)
+    }
+
+    /// Get the ShadowTLS layer of a node, if it has one
+    pub fn from_proxy(node: &Proxy) -> Option<Self> {
+        if !Self::is_shadow_tls_plugin(node.plugin.as_deref()?) {
+            return None;
+        }
+        Self::from_plugin_opts(node.plugin_option.as_deref().unwrap_or_default())
+    }
+
+    /// Format the options as a plugin string
+    pub fn to_plugin_opts(&self) -> String {
+        let mut opts = format!("host={}", self.host);
+        if let Some(password) = &self.password {
+            opts.push_str(&format!(";password={}", password));
+        }
+        opts.push_str(&format!(";version={}", self.version));
+        opts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_opts_round_trip() {
+        let opts =
+            ShadowTlsOptions::from_plugin_opts("password=secret;host=cloud.tencent.com").unwrap();
+        assert_eq!(opts.version, 3);
+        assert_eq!(opts.password.as_deref(), Some("secret"));
+        assert_eq!(
+            opts.to_plugin_opts(),
+            "host=cloud.tencent.com;password=secret;version=3"
+        );
+
+        assert!(ShadowTlsOptions::from_plugin_opts("password=secret").is_none());
+        assert!(ShadowTlsOptions::from_plugin_opts("host=a.com;version=9").is_none());
+    }
+}
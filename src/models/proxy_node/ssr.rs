@@ -0,0 +1,105 @@
+//! ShadowsocksR protocol and obfs parameters
+//!
+//! Clients reject SSR nodes whose protocol or obfs plugin they do not know, so
+//! parsers run both values through [`normalize_ssr_params`]. Common spellings
+//! such as `auth-aes128-md5` or `none` are rewritten to the canonical names,
+//! anything else is an error and the node is skipped.
+
+use thiserror::Error;
+
+/// Known SSR protocol plugins
+pub const SSR_PROTOCOLS: &[&str] = &[
+    "origin",
+    "verify_deflate",
+    "auth_sha1_v4",
+    "auth_aes128_md5",
+    "auth_aes128_sha1",
+    "auth_chain_a",
+    "auth_chain_b",
+    "auth_chain_c",
+    "auth_chain_d",
+    "auth_chain_e",
+    "auth_chain_f",
+];
+
+/// Known SSR obfs plugins
+pub const SSR_OBFS: &[&str] = &[
+    "plain",
+    "http_simple",
+    "http_post",
+    "random_head",
+    "tls1.2_ticket_auth",
+    "tls1.2_ticket_fastauth",
+];
+
+/// A protocol or obfs value outside the known SSR sets
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SsrParamError {
+    #[error("unknown SSR protocol '{0}'")]
+    UnknownProtocol(String),
+    #[error("unknown SSR obfs '{0}'")]
+    UnknownObfs(String),
+}
+
+/// Canonical spelling of a plugin name, or `None` if it is unknown
+///
+/// Values are matched case-insensitively with `-` read as `_`, and the
+/// `_compatible` suffix of plugins that also accept plain traffic is kept.
+fn canonical(value: &str, known: &[&str], none: &str) -> Option<String> {
+    let value = value.trim().to_lowercase().replace('-', "_");
+    let value = match value.as_str() {
+        "" => return Some(String::new()),
+        "none" => none.to_string(),
+        // Dotless spelling used by some panels
+        v if v.starts_with("tls12_") => v.replacen("tls12_", "tls1.2_", 1),
+        _ => value,
+    };
+    let base = value.strip_suffix("_compatible").unwrap_or(&value);
+    known.contains(&base).then_some(value)
+}
+
+/// Validate and normalize the protocol and obfs of an SSR node
+///
+/// Empty values are kept, clients treat them as `origin` and `plain`.
+///
+/// # Returns
+/// The canonical protocol and obfs names
+pub fn normalize_ssr_params(protocol: &str, obfs: &str) -> Result<(String, String), SsrParamError> {
+    let protocol = canonical(protocol, SSR_PROTOCOLS, "origin")
+        .ok_or_else(|| SsrParamError::UnknownProtocol(protocol.to_string()))?;
+    let obfs = canonical(obfs, SSR_OBFS, "plain")
+        .ok_or_else(|| SsrParamError::UnknownObfs(obfs.to_string()))?;
+    Ok((protocol, obfs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_ssr_params() {
+        assert_eq!(
+            normalize_ssr_params("Auth-AES128-MD5", "tls12_ticket_auth"),
+            Ok((
+                "auth_aes128_md5".to_string(),
+                "tls1.2_ticket_auth".to_string()
+            ))
+        );
+        assert_eq!(
+            normalize_ssr_params("none", "http_simple_compatible"),
+            Ok(("origin".to_string(), "http_simple_compatible".to_string()))
+        );
+        assert_eq!(
+            normalize_ssr_params("", ""),
+            Ok((String::new(), String::new()))
+        );
+        assert_eq!(
+            normalize_ssr_params("auth_chain_z", "plain"),
+            Err(SsrParamError::UnknownProtocol("auth_chain_z".to_string()))
+        );
+        assert_eq!(
+            normalize_ssr_params("origin", "obfs_tls"),
+            Err(SsrParamError::UnknownObfs("obfs_tls".to_string()))
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Transport layers of V2Ray based nodes
+//!
+//! A node uses exactly one transport, selected by its network name. VMess and
+//! Trojan nodes keep the transport options on [`Proxy`](crate::models::Proxy),
+//! with the gRPC service name in `path`; VLESS nodes keep them on
+//! [`VlessProxy`](super::vless::VlessProxy).
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Transport selected by a node's network name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Tcp,
+    Ws,
+    HttpUpgrade,
+    Http,
+    H2,
+    Grpc,
+    Kcp,
+    Quic,
+}
+
+impl Transport {
+    /// Network name as used in share links and Clash configurations
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::Ws => "ws",
+            Transport::HttpUpgrade => "httpupgrade",
+            Transport::Http => "http",
+            Transport::H2 => "h2",
+            Transport::Grpc => "grpc",
+            Transport::Kcp => "kcp",
+            Transport::Quic => "quic",
+        }
+    }
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    /// Parse a network name, an empty name meaning plain TCP
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "tcp" | "none" => Ok(Transport::Tcp),
+            "ws" | "websocket" => Ok(Transport::Ws),
+            "httpupgrade" => Ok(Transport::HttpUpgrade),
+            "http" => Ok(Transport::Http),
+            "h2" => Ok(Transport::H2),
+            "grpc" | "gun" => Ok(Transport::Grpc),
+            "kcp" | "mkcp" => Ok(Transport::Kcp),
+            "quic" => Ok(Transport::Quic),
+            other => Err(format!("Unknown transport type '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Transport {
+    /// Whether the transport reads the `path` and `host` options, ws and
+    /// HTTP based transports do
+    pub fn takes_path(&self) -> bool {
+        matches!(
+            self,
+            Transport::Ws | Transport::HttpUpgrade | Transport::Http | Transport::H2
+        )
+    }
+}
+
+/// Transport options as a share link gives them, before they are checked
+/// against its transport
+///
+/// Links carry `path`, `host` and `serviceName` side by side whatever their
+/// network, so a node taking the wrong one would dial a path the provider
+/// never meant. Empty values count as absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransportOptions {
+    pub path: Option<String>,
+    pub host: Option<String>,
+    pub service_name: Option<String>,
+}
+
+impl TransportOptions {
+    /// The path and Host header a node of `transport` is built with
+    ///
+    /// ws, httpupgrade, http and h2 read `path` (`/` when absent) and `host`,
+    /// gRPC reads `service_name` as its path and nothing else. A gRPC node
+    /// with a path or a path based node with a service name is rejected.
+    /// TCP, mKCP and QUIC keep path and host for their header obfuscation.
+    pub fn resolve(self, transport: Transport) -> Result<(String, String), String> {
+        let non_empty = |value: Option<String>| value.filter(|v| !v.is_empty());
+        let path = non_empty(self.path);
+        let host = non_empty(self.host);
+        let service_name = non_empty(self.service_name);
+
+        match transport {
+            Transport::Grpc => match path {
+                Some(path) => Err(format!(
+                    "grpc transport takes a serviceName, not path '{}'",
+                    path
+                )),
+                None => Ok((service_name.unwrap_or_default(), String::new())),
+            },
+            _ if service_name.is_some() => Err(format!(
+                "{} transport does not take a serviceName",
+                transport
+            )),
+            _ if transport.takes_path() => Ok((
+                path.unwrap_or_else(|| "/".to_string()),
+                host.unwrap_or_default(),
+            )),
+            _ => Ok((path.unwrap_or_default(), host.unwrap_or_default())),
+        }
+    }
+}
+
+/// gRPC stream mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrpcMode {
+    /// One stream per connection
+    Gun,
+    /// Several streams multiplexed over one connection
+    Multi,
+}
+
+impl GrpcMode {
+    /// Mode name as used in share links
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrpcMode::Gun => "gun",
+            GrpcMode::Multi => "multi",
+        }
+    }
+}
+
+impl FromStr for GrpcMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "gun" => Ok(GrpcMode::Gun),
+            "multi" => Ok(GrpcMode::Multi),
+            other => Err(format!("Unknown gRPC mode '{}'", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_transport() {
+        assert_eq!("".parse::<Transport>(), Ok(Transport::Tcp));
+        assert_eq!("gRPC".parse::<Transport>(), Ok(Transport::Grpc));
+        assert_eq!(
+            Transport::HttpUpgrade.as_str().parse::<Transport>(),
+            Ok(Transport::HttpUpgrade)
+        );
+        assert!("xhttp".parse::<Transport>().is_err());
+
+        assert_eq!("multi".parse::<GrpcMode>(), Ok(GrpcMode::Multi));
+        assert!("stream".parse::<GrpcMode>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_transport_options() {
+        let options = |path: &str, host: &str, service_name: &str| TransportOptions {
+            path: Some(path.to_string()),
+            host: Some(host.to_string()),
+            service_name: Some(service_name.to_string()),
+        };
+
+        assert_eq!(
+            options("/ws", "cdn.example.com", "").resolve(Transport::Ws),
+            Ok(("/ws".to_string(), "cdn.example.com".to_string()))
+        );
+        assert_eq!(
+            options("", "cdn.example.com", "").resolve(Transport::H2),
+            Ok(("/".to_string(), "cdn.example.com".to_string()))
+        );
+        assert!(options("/ws", "", "svc").resolve(Transport::Ws).is_err());
+        assert!(options("", "", "svc").resolve(Transport::H2).is_err());
+
+        // gRPC ignores the Host header and only takes a service name
+        assert_eq!(
+            options("", "cdn.example.com", "svc").resolve(Transport::Grpc),
+            Ok(("svc".to_string(), String::new()))
+        );
+        assert!(options("/grpc", "", "svc")
+            .resolve(Transport::Grpc)
+            .is_err());
+
+        assert_eq!(
+            TransportOptions::default().resolve(Transport::Tcp),
+            Ok((String::new(), String::new()))
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Trojan-Go specific options of Trojan nodes
+//!
+//! Trojan-Go extends Trojan with a websocket transport, its own multiplexing
+//! and an optional Shadowsocks AEAD layer inside TLS. Nodes are kept as
+//! [`ProxyType::Trojan`] with a [`CombinedProxy::TrojanGo`] carrying the
+//! extra options, and targets degrade them to plain Trojan where that is
+//! safe:
+//!
+//! - the websocket transport is kept by targets that emit Trojan over
+//!   websocket, other targets skip the node;
+//! - mux is dropped everywhere. Trojan-Go servers accept plain connections
+//!   from clients that do not multiplex, and no supported client speaks the
+//!   Trojan-Go mux protocol (`smux` of Clash.Meta and sing-box is sing-mux);
+//! - the Shadowsocks layer is only kept by Clash.Meta (`ss-opts`). The server
+//!   requires it, so every other target skips the node.
+//!
+//! [`ProxyType::Trojan`]: crate::models::ProxyType::Trojan
+//! [`CombinedProxy::TrojanGo`]: super::combined::CombinedProxy::TrojanGo
+
+use serde::{Deserialize, Serialize};
+
+use super::combined::CombinedProxy;
+use crate::models::Proxy;
+
+/// Ciphers Trojan-Go accepts for its Shadowsocks layer
+pub const TROJAN_GO_SS_CIPHERS: &[&str] = &["aes-128-gcm", "aes-256-gcm", "chacha20-ietf-poly1305"];
+
+/// Shadowsocks AEAD layer of a Trojan-Go node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrojanGoShadowsocks {
+    pub method: String,
+    pub password: String,
+}
+
+impl TrojanGoShadowsocks {
+    /// Parse the `encryption` parameter of a Trojan-Go link
+    ///
+    /// The value is `ss;method:password`, `none` or empty means no layer.
+    pub fn parse(encryption: &str) -> Result<Option<Self>, String> {
+        if encryption.is_empty() || encryption == "none" {
+            return Ok(None);
+        }
+        let Some(options) = encryption.strip_prefix("ss;") else {
+            return Err(format!("unsupported encryption '{}'", encryption));
+        };
+        let Some((method, password)) = options.split_once(':') else {
+            return Err(format!("invalid Shadowsocks encryption '{}'", options));
+        };
+        let method = method.to_lowercase();
+        if !TROJAN_GO_SS_CIPHERS.contains(&method.as_str()) {
+            return Err(format!("unsupported Shadowsocks cipher '{}'", method));
+        }
+        Ok(Some(TrojanGoShadowsocks {
+            method,
+            password: password.to_string(),
+        }))
+    }
+}
+
+/// Trojan-Go options of a Trojan node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct TrojanGoProxy {
+    /// Whether the client multiplexes connections
+    pub mux: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shadowsocks: Option<TrojanGoShadowsocks>,
+}
+
+/// Trojan-Go features a target can emit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrojanGoSupport {
+    /// Trojan over websocket
+    pub websocket: bool,
+    /// The Shadowsocks AEAD layer
+    pub shadowsocks: bool,
+}
+
+impl TrojanGoSupport {
+    /// Targets emitting Trojan over websocket but no Shadowsocks layer
+    pub const WEBSOCKET: TrojanGoSupport = TrojanGoSupport {
+        websocket: true,
+        shadowsocks: false,
+    };
+    /// Targets emitting plain Trojan only
+    pub const PLAIN: TrojanGoSupport = TrojanGoSupport {
+        websocket: false,
+        shadowsocks: false,
+    };
+}
+
+impl Proxy {
+    /// Trojan-Go options of the node, if it came from a Trojan-Go link
+    pub fn trojan_go(&self) -> Option<&TrojanGoProxy> {
+        match &self.combined_proxy {
+            Some(CombinedProxy::TrojanGo(trojan_go)) => Some(trojan_go),
+            _ => None,
+        }
+    }
+}
+
+/// Why a target with the given support cannot carry a Trojan-Go node
+///
+/// `None` when the node can be emitted, possibly degraded to plain Trojan.
+/// Nodes that are not Trojan-Go nodes are always accepted.
+pub fn trojan_go_skip_reason(node: &Proxy, support: TrojanGoSupport) -> Option<String> {
+    let trojan_go = node.trojan_go()?;
+    if let Some(ss) = &trojan_go.shadowsocks {
+        if !support.shadowsocks {
+            return Some(format!(
+                "Trojan-Go Shadowsocks encryption ({}) is not supported",
+                ss.method
+            ));
+        }
+    }
+    if !support.websocket && node.transfer_protocol.as_deref() == Some("ws") {
+        return Some("Trojan-Go websocket transport is not supported".to_string());
+    }
+    if trojan_go.mux {
+        log::debug!("Dropping Trojan-Go mux of node '{}'", node.remark);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trojan_go_skip_reason() {
+        assert_eq!(
+            TrojanGoShadowsocks::parse("ss;AES-128-GCM:secret").unwrap(),
+            Some(TrojanGoShadowsocks {
+                method: "aes-128-gcm".to_string(),
+                password: "secret".to_string(),
+            })
+        );
+        assert_eq!(TrojanGoShadowsocks::parse("none").unwrap(), None);
+        assert!(TrojanGoShadowsocks::parse("ss;rc4-md5:secret").is_err());
+        assert!(TrojanGoShadowsocks::parse("ss;aes-128-gcm").is_err());
+
+        let mut node = Proxy {
+            transfer_protocol: Some("ws".to_string()),
+            combined_proxy: Some(CombinedProxy::TrojanGo(TrojanGoProxy {
+                mux: true,
+                shadowsocks: None,
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            trojan_go_skip_reason(&node, TrojanGoSupport::WEBSOCKET),
+            None
+        );
+        assert!(trojan_go_skip_reason(&node, TrojanGoSupport::PLAIN).is_some());
+
+        node.combined_proxy = Some(CombinedProxy::TrojanGo(TrojanGoProxy {
+            mux: false,
+            shadowsocks: TrojanGoShadowsocks::parse("ss;aes-256-gcm:secret").unwrap(),
+        }));
+        assert!(trojan_go_skip_reason(&node, TrojanGoSupport::WEBSOCKET).is_some());
+        let full = TrojanGoSupport {
+            websocket: true,
+            shadowsocks: true,
+        };
+        assert_eq!(trojan_go_skip_reason(&node, full), None);
+
+        let plain = Proxy::default();
+        assert_eq!(trojan_go_skip_reason(&plain, TrojanGoSupport::PLAIN), None);
+    }
+}
@@ -0,0 +1,191 @@
+//! uTLS client fingerprints
+//!
+//! Clash.Meta (`client-fingerprint`) and sing-box (`utls.fingerprint`) can
+//! mimic the TLS client hello of a browser. Clients keep adding fingerprints,
+//! so values outside [`CLIENT_FINGERPRINTS`] are kept and only logged.
+//!
+//! [`assign_random_fingerprints`] gives every TLS node without a fingerprint
+//! one picked from a pool, so the nodes of a config do not all share the
+//! same client hello.
+
+use super::combined::CombinedProxy;
+use crate::models::{Proxy, ProxyType};
+
+/// Client fingerprints known to Clash.Meta and sing-box
+pub const CLIENT_FINGERPRINTS: &[&str] = &[
+    "chrome",
+    "firefox",
+    "safari",
+    "ios",
+    "android",
+    "edge",
+    "360",
+    "qq",
+    "random",
+    "randomized",
+];
+
+/// Normalize a client fingerprint read from a subscription
+///
+/// Values are trimmed and lowercased, empty values and `none` mean no
+/// fingerprint.
+pub fn parse_client_fingerprint(value: &str) -> Option<String> {
+    let value = value.trim().to_lowercase();
+    if value.is_empty() || value == "none" {
+        return None;
+    }
+    if !CLIENT_FINGERPRINTS.contains(&value.as_str()) {
+        log::warn!("Unknown client fingerprint '{}', passing it through", value);
+    }
+    Some(value)
+}
+
+/// Fingerprints picked from when no pool is configured
+pub const DEFAULT_FINGERPRINT_POOL: &[&str] =
+    &["chrome", "firefox", "safari", "ios", "android", "edge"];
+
+/// Whether the node speaks TLS with a client fingerprint targets can emit
+fn takes_client_fingerprint(node: &Proxy) -> bool {
+    match node.proxy_type {
+        ProxyType::Trojan => true,
+        ProxyType::VMess | ProxyType::Vless => node.tls_secure,
+        _ => false,
+    }
+}
+
+/// Whether the node already names a fingerprint, on itself or on its VLESS
+/// options
+fn has_client_fingerprint(node: &Proxy) -> bool {
+    let vless_fingerprint = match &node.combined_proxy {
+        Some(CombinedProxy::Vless(vless)) => vless.client_fingerprint.as_deref(),
+        _ => None,
+    };
+    node.client_fingerprint
+        .as_deref()
+        .or(vless_fingerprint)
+        .is_some_and(|fp| !fp.is_empty())
+}
+
+/// FNV-1a hash of the node's server, port and name, mixed with the seed
+///
+/// Picks depend on the node only, so reordering or filtering the list does
+/// not change the fingerprint of the remaining nodes.
+fn node_draw(node: &Proxy, seed: u64) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let port = node.port.to_le_bytes();
+    for byte in node.hostname.bytes().chain(port).chain(node.remark.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    // splitmix64 finalizer, so nearby seeds give unrelated picks
+    let mut z = hash ^ seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Give every TLS node without a fingerprint one picked from `pool`
+///
+/// Trojan nodes and TLS VMess and VLESS nodes are eligible, nodes that
+/// already name a fingerprint are left alone. The same seed always gives a
+/// node the same fingerprint. An empty pool falls back to
+/// [`DEFAULT_FINGERPRINT_POOL`].
+///
+/// # Returns
+/// The number of nodes given a fingerprint
+pub fn assign_random_fingerprints(nodes: &mut [Proxy], pool: &[String], seed: u64) -> usize {
+    let pool: Vec<String> = if pool.is_empty() {
+        DEFAULT_FINGERPRINT_POOL
+            .iter()
+            .map(|fp| fp.to_string())
+            .collect()
+    } else {
+        pool.iter()
+            .filter_map(|fp| parse_client_fingerprint(fp))
+            .collect()
+    };
+    if pool.is_empty() {
+        return 0;
+    }
+
+    let mut assigned = 0;
+    for node in nodes
+        .iter_mut()
+        .filter(|node| takes_client_fingerprint(node) && !has_client_fingerprint(node))
+    {
+        let index = (node_draw(node, seed) % pool.len() as u64) as usize;
+        node.client_fingerprint = Some(pool[index].clone());
+        assigned += 1;
+    }
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_fingerprint() {
+        assert_eq!(
+            parse_client_fingerprint(" Chrome "),
+            Some("chrome".to_string())
+        );
+        assert_eq!(parse_client_fingerprint(""), None);
+        assert_eq!(parse_client_fingerprint("none"), None);
+        // Fingerprints added by newer clients pass through
+        assert_eq!(
+            parse_client_fingerprint("chrome_pq"),
+            Some("chrome_pq".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assign_random_fingerprints() {
+        let node = |remark: &str, proxy_type: ProxyType, tls: bool| Proxy {
+            proxy_type,
+            remark: remark.to_string(),
+            hostname: format!("{}.example.com", remark),
+            port: 443,
+            tls_secure: tls,
+            ..Default::default()
+        };
+        let mut nodes: Vec<Proxy> = (0..16)
+            .map(|i| node(&format!("trojan{}", i), ProxyType::Trojan, false))
+            .collect();
+        nodes.push(node("plain", ProxyType::VMess, false));
+        let mut pinned = node("pinned", ProxyType::Vless, true);
+        pinned.client_fingerprint = Some("safari".to_string());
+        nodes.push(pinned);
+
+        let pool = vec!["Chrome".to_string(), "firefox".to_string()];
+        let mut first = nodes.clone();
+        assert_eq!(assign_random_fingerprints(&mut first, &pool, 7), 16);
+        assert_eq!(first[16].client_fingerprint, None);
+        assert_eq!(first[17].client_fingerprint.as_deref(), Some("safari"));
+        let picked: std::collections::HashSet<_> = first[..16]
+            .iter()
+            .map(|n| n.client_fingerprint.clone().unwrap())
+            .collect();
+        assert_eq!(picked.len(), 2);
+        assert!(picked.contains("chrome"));
+
+        // The same seed gives each node the same pick, whatever the order
+        let mut again = nodes.clone();
+        again.reverse();
+        assign_random_fingerprints(&mut again, &pool, 7);
+        again.reverse();
+        let fingerprints = |nodes: &[Proxy]| {
+            nodes
+                .iter()
+                .map(|n| n.client_fingerprint.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fingerprints(&first), fingerprints(&again));
+
+        let mut defaults = nodes.clone();
+        assign_random_fingerprints(&mut defaults, &[], 7);
+        assert!(
+            DEFAULT_FINGERPRINT_POOL.contains(&defaults[0].client_fingerprint.as_deref().unwrap())
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+
+use super::transport::GrpcMode;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VlessProxy {
     pub uuid: String,
     pub flow: Option<String>,
     pub tls: bool,
-    pub alpn: HashSet<String>,
+    pub alpn: Vec<String>,
     pub udp: bool,
     pub packet_addr: Option<bool>,
     pub xudp: Option<bool>,
@@ -20,6 +23,7 @@ pub struct VlessProxy {
     pub h2_host: Option<Vec<String>>,
     pub h2_path: Option<String>,
     pub grpc_service_name: Option<String>,
+    pub grpc_mode: Option<GrpcMode>,
     pub ws_path: Option<String>,
     pub ws_headers: Option<HashMap<String, String>>,
     pub skip_cert_verify: Option<bool>,
@@ -34,7 +38,7 @@ impl Default for VlessProxy {
             uuid: String::new(),
             flow: None,
             tls: false,
-            alpn: HashSet::new(),
+            alpn: Vec::new(),
             udp: true,
             packet_addr: None,
             xudp: None,
@@ -48,6 +52,7 @@ impl Default for VlessProxy {
             h2_host: None,
             h2_path: None,
             grpc_service_name: None,
+            grpc_mode: None,
             ws_path: None,
             ws_headers: None,
             skip_cert_verify: None,
@@ -57,3 +62,12 @@ impl Default for VlessProxy {
         }
     }
 }
+
+impl VlessProxy {
+    /// Whether the node uses Reality instead of plain TLS
+    pub fn is_reality(&self) -> bool {
+        self.reality_public_key
+            .as_deref()
+            .is_some_and(|key| !key.is_empty())
+    }
+}
@@ -15,6 +15,9 @@ pub struct RegexMatchConfig {
     pub _match: String,
     pub replace: String,
     pub script: String,
+    /// Replace `_match` as plain text instead of treating it as a regex
+    #[serde(default)]
+    pub literal: bool,
 
     // Non-serialized field to hold the compiled rule
     #[serde(skip)]
@@ -30,6 +33,7 @@ impl RegexMatchConfig {
             _match,
             replace,
             script,
+            literal: false,
             compiled_rule: None,
             compiled_regex_for_replace: None,
         };
@@ -38,9 +42,22 @@ impl RegexMatchConfig {
         config
     }
 
+    /// Create a rule replacing every occurrence of `_match` as plain text
+    pub fn new_literal(_match: String, replace: String) -> Self {
+        let mut config = Self::new(_match, replace, String::new());
+        config.literal = true;
+        config.compile();
+        config
+    }
+
     /// Compiles the regex pattern in the `_match` field.
     /// This should be called after deserialization or creation.
     pub fn compile(&mut self) {
+        if self.literal {
+            self.compiled_rule = None;
+            self.compiled_regex_for_replace = None;
+            return;
+        }
         self.compiled_rule = Some(compile_rule(&self._match));
         // Also pre-compile the regex specifically for the replacement logic
         // Use the same case-insensitivity as reg_find/compile_rule(Plain/Remarks)
@@ -48,6 +65,11 @@ impl RegexMatchConfig {
     }
 
     pub fn process(&self, remark: &mut String) {
+        if self.literal {
+            replace_literal(remark, &self._match, &self.replace);
+            return;
+        }
+
         let mut matched = false;
 
         // Use compiled rule for matching if available
@@ -72,5 +94,53 @@ impl RegexMatchConfig {
     }
 }
 
+/// Replace every occurrence of `pattern` in `remark` with `replace`
+///
+/// Occurrences are replaced left to right without overlap, and the
+/// replacement text is never matched again by the same rule.
+pub fn replace_literal(remark: &mut String, pattern: &str, replace: &str) {
+    if !pattern.is_empty() && remark.contains(pattern) {
+        *remark = remark.replace(pattern, replace);
+    }
+}
+
 /// Collection of regex match configurations
 pub type RegexMatchConfigs = Vec<RegexMatchConfig>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ini_bindings::FromIniWithDelimiter;
+
+    #[test]
+    fn test_literal_rules_apply_in_order() {
+        let rules = vec![
+            RegexMatchConfig::new_literal("HK".to_string(), "Hong Kong".to_string()),
+            RegexMatchConfig::new_literal("Hong".to_string(), "Xiang".to_string()),
+            RegexMatchConfig::new_literal("(IPLC)".to_string(), String::new()),
+        ];
+        let mut remark = "HK 01 (IPLC) HK".to_string();
+        for rule in &rules {
+            rule.process(&mut remark);
+        }
+        assert_eq!(remark, "Xiang Kong 01  Xiang Kong");
+
+        let parsed = RegexMatchConfigs::from_ini_with_delimiter(
+            &[
+                "!!literal:HK@Hong Kong".to_string(),
+                "HK@Hong Kong".to_string(),
+                "literal:HK@Hong Kong".to_string(),
+            ],
+            "@",
+        );
+        assert!(parsed[0].literal && parsed[0]._match == "HK");
+        assert!(!parsed[1].literal);
+        // Only the `!!` form is taken as a marker, the rest is a regex
+        assert!(!parsed[2].literal && parsed[2]._match == "literal:HK");
+
+        // Regex metacharacters are taken as they are
+        let mut remark = "a.b".to_string();
+        RegexMatchConfig::new_literal(".".to_string(), "-".to_string()).process(&mut remark);
+        assert_eq!(remark, "a-b");
+    }
+}
@@ -0,0 +1,136 @@
+//! Relay chains between nodes
+//!
+//! A node dials through an upstream when its `underlying_proxy` names another
+//! node, which is emitted as `dialer-proxy` for Clash.Meta, `detour` for
+//! sing-box and `underlying-proxy` for Surge. Names matching no node are kept
+//! as they are, they may refer to a proxy group of the target config.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::Proxy;
+
+/// A relay chain that cannot be emitted
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RelayChainError {
+    /// Following the upstreams leads back to a node of the chain
+    #[error("Relay chain loops back on itself: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+}
+
+/// Upstream name of a node, if it has one
+pub fn upstream_of(node: &Proxy) -> Option<&str> {
+    node.underlying_proxy
+        .as_deref()
+        .filter(|name| !name.is_empty())
+}
+
+/// Whether any node dials through an upstream
+pub fn has_relay_chains(nodes: &[Proxy]) -> bool {
+    nodes.iter().any(|node| upstream_of(node).is_some())
+}
+
+/// Point upstream names at the current names of renamed nodes
+///
+/// `old_names` holds the names of `nodes` before renaming, in the same order.
+/// When several nodes shared an old name the first one is used.
+pub fn relink_upstreams(nodes: &mut [Proxy], old_names: &[String]) {
+    let mut renamed: HashMap<&str, String> = HashMap::new();
+    for (old, node) in old_names.iter().zip(nodes.iter()) {
+        renamed
+            .entry(old.as_str())
+            .or_insert_with(|| node.remark.clone());
+    }
+    for node in nodes.iter_mut() {
+        if let Some(new_name) = upstream_of(node).and_then(|name| renamed.get(name)) {
+            node.underlying_proxy = Some(new_name.clone());
+        }
+    }
+}
+
+/// Check that no relay chain loops back on itself
+///
+/// # Returns
+/// The first cycle found, listed from the node it was entered at
+pub fn check_relay_chains(nodes: &[Proxy]) -> Result<(), RelayChainError> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        index.entry(node.remark.as_str()).or_insert(i);
+    }
+
+    // Nodes whose chain is known to end without a cycle
+    let mut checked = vec![false; nodes.len()];
+    for start in 0..nodes.len() {
+        let mut path: Vec<usize> = Vec::new();
+        let mut current = Some(start);
+        while let Some(i) = current {
+            if checked[i] {
+                break;
+            }
+            if let Some(pos) = path.iter().position(|&p| p == i) {
+                let mut cycle: Vec<String> = path[pos..]
+                    .iter()
+                    .map(|&p| nodes[p].remark.clone())
+                    .collect();
+                cycle.push(nodes[i].remark.clone());
+                return Err(RelayChainError::Cycle(cycle));
+            }
+            path.push(i);
+            current = upstream_of(&nodes[i]).and_then(|name| index.get(name).copied());
+        }
+        for i in path {
+            checked[i] = true;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(remark: &str, upstream: Option<&str>) -> Proxy {
+        Proxy {
+            remark: remark.to_string(),
+            underlying_proxy: upstream.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_relay_chains() {
+        let nodes = vec![
+            node("A", Some("B")),
+            node("B", Some("C")),
+            node("C", None),
+            node("D", Some("Relay group")),
+        ];
+        assert!(check_relay_chains(&nodes).is_ok());
+
+        let nodes = vec![
+            node("A", Some("B")),
+            node("B", Some("C")),
+            node("C", Some("B")),
+        ];
+        assert_eq!(
+            check_relay_chains(&nodes),
+            Err(RelayChainError::Cycle(vec![
+                "B".to_string(),
+                "C".to_string(),
+                "B".to_string()
+            ]))
+        );
+        assert!(check_relay_chains(&[node("A", Some("A"))]).is_err());
+    }
+
+    #[test]
+    fn test_relink_upstreams() {
+        let mut nodes = vec![node("HK 01", Some("Relay")), node("Relay", None)];
+        let old_names: Vec<String> = nodes.iter().map(|n| n.remark.clone()).collect();
+        nodes[1].remark = "🇯🇵 Relay".to_string();
+
+        relink_upstreams(&mut nodes, &old_names);
+        assert_eq!(nodes[0].underlying_proxy.as_deref(), Some("🇯🇵 Relay"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Built-in node sorting that does not need the JavaScript runtime
+
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use super::{Proxy, ProxyType};
+use crate::utils::string::natural_cmp;
+
+/// The node property used by [`SortKey`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortField {
+    /// Node remark, compared in natural order
+    Name,
+    /// Server hostname, compared in natural order
+    Server,
+    /// Server port
+    Port,
+    /// Proxy type name
+    Type,
+}
+
+/// A built-in sort order for nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SortKey {
+    /// The property to sort by
+    pub field: SortField,
+    /// Whether to reverse the order
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Create an ascending sort key for a field
+    pub fn new(field: SortField) -> Self {
+        SortKey {
+            field,
+            descending: false,
+        }
+    }
+
+    /// Reverse the sort direction
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+        self
+    }
+
+    /// Compare two nodes by this key
+    ///
+    /// Nodes of unknown type always sort last regardless of the direction,
+    /// matching the default remark sort.
+    pub fn compare(&self, a: &Proxy, b: &Proxy) -> Ordering {
+        match (
+            a.proxy_type == ProxyType::Unknown,
+            b.proxy_type == ProxyType::Unknown,
+        ) {
+            (true, false) => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            _ => {}
+        }
+
+        let ordering = match self.field {
+            SortField::Name => natural_cmp(&a.remark, &b.remark),
+            SortField::Server => natural_cmp(&a.hostname, &b.hostname),
+            SortField::Port => a.port.cmp(&b.port),
+            SortField::Type => a.proxy_type.to_string().cmp(b.proxy_type.to_string()),
+        };
+
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Sort nodes in place by this key, keeping the original order of equal
+    /// nodes
+    pub fn sort_nodes(&self, nodes: &mut [Proxy]) {
+        nodes.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+/// Parse a sort key such as `name`, `port:desc` or `-server`
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = s.trim().to_lowercase();
+        let (field, descending) = if let Some(field) = key.strip_prefix('-') {
+            (field.to_string(), true)
+        } else if let Some((field, direction)) = key.split_once(':') {
+            match direction {
+                "asc" => (field.to_string(), false),
+                "desc" => (field.to_string(), true),
+                _ => return Err(format!("Unknown sort key: {}", s)),
+            }
+        } else {
+            (key.clone(), false)
+        };
+
+        let field = match field.as_str() {
+            "name" | "remark" => SortField::Name,
+            "server" | "host" | "hostname" => SortField::Server,
+            "port" => SortField::Port,
+            "type" => SortField::Type,
+            _ => return Err(format!("Unknown sort key: {}", s)),
+        };
+        Ok(SortKey { field, descending })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(remark: &str, port: u16, proxy_type: ProxyType) -> Proxy {
+        Proxy {
+            remark: remark.to_string(),
+            hostname: format!("{}.example.com", remark.to_lowercase().replace(' ', "")),
+            port,
+            proxy_type,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sort_key_from_str() {
+        assert_eq!("name".parse(), Ok(SortKey::new(SortField::Name)));
+        assert_eq!(
+            "port:desc".parse(),
+            Ok(SortKey::new(SortField::Port).descending(true))
+        );
+        assert_eq!(
+            "-Server".parse(),
+            Ok(SortKey::new(SortField::Server).descending(true))
+        );
+        assert!("latency".parse::<SortKey>().is_err());
+        assert!("name:up".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_sort_nodes_natural() {
+        let mut nodes = vec![
+            node("Node 10", 443, ProxyType::VMess),
+            node("Broken", 1, ProxyType::Unknown),
+            node("Node 2", 8443, ProxyType::Shadowsocks),
+            node("Node 1", 80, ProxyType::Trojan),
+        ];
+
+        SortKey::new(SortField::Name).sort_nodes(&mut nodes);
+        let remarks: Vec<&str> = nodes.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["Node 1", "Node 2", "Node 10", "Broken"]);
+
+        SortKey::new(SortField::Port)
+            .descending(true)
+            .sort_nodes(&mut nodes);
+        let ports: Vec<u16> = nodes.iter().map(|n| n.port).collect();
+        assert_eq!(ports, vec![8443, 443, 80, 1]);
+    }
+}
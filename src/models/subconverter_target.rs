@@ -20,6 +20,10 @@ pub enum SubconverterTarget {
     Loon,
     SSD,
     SingBox,
+    /// Structured JSON node list
+    NodeJson,
+    /// JSON array of V2Ray outbounds
+    V2RayJson,
 }
 
 impl SubconverterTarget {
@@ -43,6 +47,8 @@ impl SubconverterTarget {
             "loon" => Some(SubconverterTarget::Loon),
             "ssd" => Some(SubconverterTarget::SSD),
             "singbox" => Some(SubconverterTarget::SingBox),
+            "json" | "nodejson" => Some(SubconverterTarget::NodeJson),
+            "v2rayjson" | "outbounds" => Some(SubconverterTarget::V2RayJson),
             // Map shadowrocket to Mixed
             "shadowrocket" => Some(SubconverterTarget::Mixed),
             // Map surfboardios to regular Surfboard
@@ -71,6 +77,8 @@ impl SubconverterTarget {
             SubconverterTarget::Loon => "loon".to_string(),
             SubconverterTarget::SSD => "ssd".to_string(),
             SubconverterTarget::SingBox => "singbox".to_string(),
+            SubconverterTarget::NodeJson => "json".to_string(),
+            SubconverterTarget::V2RayJson => "v2rayjson".to_string(),
         }
     }
 
@@ -78,7 +86,8 @@ impl SubconverterTarget {
         matches!(self, SubconverterTarget::Clash | SubconverterTarget::ClashR)
     }
 
-    /// Returns true if the target represents a simple type (e.g. ss, ssr, trojan)
+    /// Returns true if the target represents a simple type (e.g. ss, ssr,
+    /// trojan, or a JSON node list), which has no proxy groups or rules
     pub fn is_simple(&self) -> bool {
         matches!(
             self,
@@ -87,6 +96,8 @@ impl SubconverterTarget {
                 | SubconverterTarget::SSD
                 | SubconverterTarget::V2Ray
                 | SubconverterTarget::Trojan
+                | SubconverterTarget::NodeJson
+                | SubconverterTarget::V2RayJson
         )
     }
 
@@ -0,0 +1,144 @@
+//! What happens to nodes a target cannot emit
+//!
+//! Every generator reports such nodes through
+//! [`ExtraSettings::skip_node`](super::ExtraSettings::skip_node), which
+//! applies the [`UnsupportedPolicy`] of the export: the node is left out, the
+//! conversion fails, or the node is listed as a comment in its proxy section.
+//! Only line based formats (Surge, Loon, Quantumult, QuantumultX and Mellow)
+//! have comments, other targets skip the node under
+//! [`UnsupportedPolicy::Comment`]. Nodes with an enabled fallback are
+//! degraded instead of skipped, see [`degrade`](super::degrade).
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::utils::explain::SkippedNode;
+
+/// How nodes a target cannot emit are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UnsupportedPolicy {
+    /// Leave the node out with a warning
+    #[default]
+    Skip,
+    /// Fail the conversion, naming the nodes
+    Error,
+    /// Leave the node out and list it as a comment where the format allows
+    Comment,
+}
+
+impl UnsupportedPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnsupportedPolicy::Skip => "skip",
+            UnsupportedPolicy::Error => "error",
+            UnsupportedPolicy::Comment => "comment",
+        }
+    }
+}
+
+impl FromStr for UnsupportedPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "skip" => Ok(UnsupportedPolicy::Skip),
+            "error" => Ok(UnsupportedPolicy::Error),
+            "comment" => Ok(UnsupportedPolicy::Comment),
+            _ => Err(format!(
+                "Unknown unsupported node policy '{}', expected skip, error or comment",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UnsupportedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Nodes reported unsupported during an export, shared by clones like
+/// [`StatsRecorder`](crate::utils::StatsRecorder)
+#[derive(Debug, Clone, Default)]
+pub struct UnsupportedNodes(Arc<Mutex<Vec<SkippedNode>>>);
+
+impl UnsupportedNodes {
+    pub fn record(&self, target: &str, remark: &str, reason: String) {
+        if let Ok(mut nodes) = self.0.lock() {
+            nodes.push(SkippedNode {
+                remark: remark.to_string(),
+                target: target.to_string(),
+                reason,
+            });
+        }
+    }
+
+    /// Nodes recorded so far
+    pub fn snapshot(&self) -> Vec<SkippedNode> {
+        self.0.lock().map(|nodes| nodes.clone()).unwrap_or_default()
+    }
+
+    /// Remove the nodes recorded for `target`, as comment lines starting
+    /// with `prefix`
+    pub fn take_comments(&self, target: &str, prefix: &str) -> Vec<String> {
+        let Ok(mut nodes) = self.0.lock() else {
+            return Vec::new();
+        };
+        let (taken, kept) = std::mem::take(&mut *nodes)
+            .into_iter()
+            .partition(|node| node.target == target);
+        *nodes = kept;
+        taken
+            .into_iter()
+            .map(|node: SkippedNode| {
+                format!("{} {}: unsupported, {}", prefix, node.remark, node.reason)
+            })
+            .collect()
+    }
+
+    /// Error message naming the recorded nodes, `None` when there are none
+    pub fn error_message(&self) -> Option<String> {
+        let nodes = self.snapshot();
+        if nodes.is_empty() {
+            return None;
+        }
+        let names: Vec<String> = nodes
+            .iter()
+            .map(|node| format!("'{}' for {} ({})", node.remark, node.target, node.reason))
+            .collect();
+        Some(format!(
+            "{} node(s) cannot be emitted: {}",
+            nodes.len(),
+            names.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_nodes() {
+        assert_eq!("Comment".parse(), Ok(UnsupportedPolicy::Comment));
+        assert!("drop".parse::<UnsupportedPolicy>().is_err());
+
+        let nodes = UnsupportedNodes::default();
+        assert_eq!(nodes.error_message(), None);
+        nodes.record("Surge", "US 01", "Hysteria is not supported".to_string());
+        nodes.record("Loon", "JP 01", "SSR is not supported".to_string());
+        assert_eq!(
+            nodes.error_message().unwrap(),
+            "2 node(s) cannot be emitted: 'US 01' for Surge (Hysteria is not supported), \
+             'JP 01' for Loon (SSR is not supported)"
+        );
+        assert_eq!(
+            nodes.take_comments("Surge", "#"),
+            vec!["# US 01: unsupported, Hysteria is not supported"]
+        );
+        assert_eq!(nodes.snapshot().len(), 1);
+        assert!(nodes.take_comments("Surge", "#").is_empty());
+    }
+}
@@ -1,23 +1,59 @@
 use crate::{
     models::{
-        Proxy, HTTP_DEFAULT_GROUP, HYSTERIA2_DEFAULT_GROUP, HYSTERIA_DEFAULT_GROUP,
-        SNELL_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_DEFAULT_GROUP,
-        TROJAN_DEFAULT_GROUP, V2RAY_DEFAULT_GROUP, WG_DEFAULT_GROUP,
+        proxy_node::{
+            combined::CombinedProxy, hysteria::parse_bandwidth, ssr::normalize_ssr_params,
+        },
+        validate_ss2022_psk, Proxy, Transport, HTTP_DEFAULT_GROUP, HYSTERIA2_DEFAULT_GROUP,
+        HYSTERIA_DEFAULT_GROUP, SNELL_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP,
+        SS_DEFAULT_GROUP, TROJAN_DEFAULT_GROUP, V2RAY_DEFAULT_GROUP, WG_DEFAULT_GROUP,
     },
-    parser::yaml::clash::parse_clash_yaml,
+    parser::yaml::clash::{dialer_proxy_of, ip_version_of, parse_clash_yaml_entries, smux_of},
+    utils::split_alpn,
 };
 use serde_yaml::Value;
 
 /// Parse a Clash YAML configuration into a vector of Proxy objects
+///
+/// Entries rejected by the typed parser get a second chance with the more
+/// lenient legacy parser before they are skipped.
 pub fn explode_clash(content: &str, nodes: &mut Vec<Proxy>) -> bool {
     // 首先尝试使用新的YAML解析器
-    match parse_clash_yaml(content) {
-        Ok(mut proxies) => {
-            if !proxies.is_empty() {
-                nodes.append(&mut proxies);
+    match parse_clash_yaml_entries(content, false) {
+        Ok(report) if !report.proxies.is_empty() || !report.errors.is_empty() => {
+            let entries = serde_yaml::from_str::<Value>(content)
+                .ok()
+                .and_then(|yaml| yaml.get("proxies").and_then(|p| p.as_sequence()).cloned())
+                .unwrap_or_default();
+            let mut parsed = report.proxies;
+            for error in report.errors {
+                // A bad `ip-version` or `smux` fails the entry whichever
+                // parser reads it
+                let entry = &entries[error.index];
+                let node = ip_version_of(entry)
+                    .and_then(|ip_version| Ok((ip_version, smux_of(entry)?)))
+                    .ok()
+                    .and_then(|(ip_version, multiplex)| {
+                        parse_clash_proxy(entry).map(|node| Proxy {
+                            ip_version,
+                            multiplex,
+                            ..node
+                        })
+                    });
+                match node {
+                    Some(mut node) => {
+                        node.underlying_proxy = dialer_proxy_of(entry);
+                        parsed.push((error.index, node));
+                    }
+                    None => log::warn!("Skipping Clash proxy {}", error),
+                }
+            }
+            if !parsed.is_empty() {
+                parsed.sort_by_key(|(index, _)| *index);
+                nodes.extend(parsed.into_iter().map(|(_, node)| node));
                 return true;
             }
         }
+        Ok(_) => {}
         Err(e) => {
             // 失败时记录错误并尝试旧的解析方式
             eprintln!("新YAML解析器失败: {}", e);
@@ -44,7 +80,20 @@ pub fn explode_clash(content: &str, nodes: &mut Vec<Proxy>) -> bool {
 
     // Process each proxy in the sequence
     for proxy in proxies {
-        if let Some(node) = parse_clash_proxy(proxy) {
+        let options = ip_version_of(proxy).and_then(|ip_version| Ok((ip_version, smux_of(proxy)?)));
+        let (ip_version, multiplex) = match options {
+            Ok(options) => options,
+            Err(e) => {
+                log::warn!("Skipping Clash proxy: {}", e);
+                continue;
+            }
+        };
+        if let Some(mut node) = parse_clash_proxy(proxy) {
+            node.ip_version = ip_version;
+            node.multiplex = multiplex;
+            if let Some(dialer) = dialer_proxy_of(proxy) {
+                node.underlying_proxy = Some(dialer);
+            }
             nodes.push(node);
             success = true;
         }
@@ -53,6 +102,28 @@ pub fn explode_clash(content: &str, nodes: &mut Vec<Proxy>) -> bool {
     success
 }
 
+/// Read an `alpn` list given either as a sequence or a comma separated string
+fn yaml_alpn(proxy: &Value) -> Vec<String> {
+    match proxy.get("alpn") {
+        Some(Value::Sequence(seq)) => {
+            let joined: Vec<&str> = seq.iter().filter_map(|v| v.as_str()).collect();
+            split_alpn(&joined.join(","))
+        }
+        Some(Value::String(s)) => split_alpn(s),
+        _ => Vec::new(),
+    }
+}
+
+/// Read a `username` or `password`, which YAML turns into a number when
+/// it is all digits
+fn yaml_credential(proxy: &Value, key: &str) -> String {
+    match proxy.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
 /// Parse a single proxy from Clash YAML
 fn parse_clash_proxy(proxy: &Value) -> Option<Proxy> {
     // Extract the proxy type
@@ -85,13 +156,23 @@ fn parse_clash_proxy(proxy: &Value) -> Option<Proxy> {
         "ssr" | "shadowsocksr" => {
             parse_clash_ssr(proxy, name, server, port, udp, tfo, skip_cert_verify)
         }
-        "vmess" => parse_clash_vmess(proxy, name, server, port, udp, tfo, skip_cert_verify),
+        "vmess" => parse_clash_vmess(proxy, name, server, port, udp, tfo, skip_cert_verify).map(
+            |mut node| {
+                node.alpn = yaml_alpn(proxy);
+                node
+            },
+        ),
         "socks" | "socks5" => {
             parse_clash_socks(proxy, name, server, port, udp, tfo, skip_cert_verify)
         }
         "http" => parse_clash_http(proxy, name, server, port, false, tfo, skip_cert_verify),
         "https" => parse_clash_http(proxy, name, server, port, true, tfo, skip_cert_verify),
-        "trojan" => parse_clash_trojan(proxy, name, server, port, udp, tfo, skip_cert_verify),
+        "trojan" => parse_clash_trojan(proxy, name, server, port, udp, tfo, skip_cert_verify).map(
+            |mut node| {
+                node.alpn = yaml_alpn(proxy);
+                node
+            },
+        ),
         "snell" => parse_clash_snell(proxy, name, server, port, udp, tfo, skip_cert_verify),
         "wireguard" => parse_clash_wireguard(proxy, name, server, port, udp),
         "hysteria" => parse_clash_hysteria(proxy, name, server, port, tfo, skip_cert_verify),
@@ -230,10 +311,15 @@ fn parse_clash_ss(
         // Not implementing the full C++ transformation for now
     }
 
+    if let Err(e) = validate_ss2022_psk(cipher, password) {
+        log::warn!("Shadowsocks node '{}' skipped: {}", name, e);
+        return None;
+    }
+
     // Convert pluginopts String to &str
     let pluginopts_str = Box::leak(pluginopts.into_boxed_str());
 
-    Some(Proxy::ss_construct(
+    let mut node = Proxy::ss_construct(
         SS_DEFAULT_GROUP,
         name,
         server,
@@ -247,7 +333,11 @@ fn parse_clash_ss(
         skip_cert_verify,
         None,
         underlying_proxy,
-    ))
+    );
+    if let Some(CombinedProxy::Shadowsocks(ss)) = &mut node.combined_proxy {
+        ss.set_udp_over_tcp(proxy.get("udp-over-tcp"), proxy.get("udp-over-tcp-version"));
+    }
+    Some(node)
 }
 
 /// Parse a ShadowsocksR proxy from Clash YAML
@@ -283,15 +373,22 @@ fn parse_clash_ssr(
     if password.is_empty() || method.is_empty() || protocol.is_empty() || obfs.is_empty() {
         return None;
     }
+    let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+        Ok(params) => params,
+        Err(e) => {
+            log::warn!("ShadowsocksR node '{}' skipped: {}", name, e);
+            return None;
+        }
+    };
 
     Some(Proxy::ssr_construct(
         SSR_DEFAULT_GROUP,
         name,
         server,
         port,
-        protocol,
+        &protocol,
         method,
-        obfs,
+        &obfs,
         password,
         obfs_param,
         protocol_param,
@@ -348,83 +445,106 @@ fn parse_clash_vmess(
     let mut host = String::new();
     let mut path = String::new();
 
-    // Handle WebSocket options
-    if let Some(ws_opts) = proxy.get("ws-opts").and_then(|v| v.as_mapping()) {
-        if let Some(path_val) = ws_opts
-            .get(&Value::String("path".to_string()))
-            .and_then(|v| v.as_str())
-        {
-            path = path_val.to_string();
+    // Only the options of the selected transport are read
+    let transport = match network.parse::<Transport>() {
+        Ok(transport) => transport,
+        Err(e) => {
+            log::warn!("VMess node '{}' skipped: {}", name, e);
+            return None;
         }
+    };
+    match transport {
+        // WebSocket options
+        Transport::Ws | Transport::HttpUpgrade => {
+            if let Some(ws_opts) = proxy.get("ws-opts").and_then(|v| v.as_mapping()) {
+                if let Some(path_val) = ws_opts
+                    .get(&Value::String("path".to_string()))
+                    .and_then(|v| v.as_str())
+                {
+                    path = path_val.to_string();
+                }
 
-        if let Some(headers) = ws_opts
-            .get(&Value::String("headers".to_string()))
-            .and_then(|v| v.as_mapping())
-        {
-            if let Some(host_val) = headers
-                .get(&Value::String("Host".to_string()))
-                .and_then(|v| v.as_str())
-            {
-                host = host_val.to_string();
+                if let Some(headers) = ws_opts
+                    .get(&Value::String("headers".to_string()))
+                    .and_then(|v| v.as_mapping())
+                {
+                    if let Some(host_val) = headers
+                        .get(&Value::String("Host".to_string()))
+                        .and_then(|v| v.as_str())
+                    {
+                        host = host_val.to_string();
+                    }
+                }
             }
         }
-    }
-    // Handle HTTP/2 options
-    else if let Some(h2_opts) = proxy.get("h2-opts").and_then(|v| v.as_mapping()) {
-        if let Some(path_val) = h2_opts
-            .get(&Value::String("path".to_string()))
-            .and_then(|v| v.as_str())
-        {
-            path = path_val.to_string();
-        }
+        // HTTP/2 options
+        Transport::H2 => {
+            if let Some(h2_opts) = proxy.get("h2-opts").and_then(|v| v.as_mapping()) {
+                if let Some(path_val) = h2_opts
+                    .get(&Value::String("path".to_string()))
+                    .and_then(|v| v.as_str())
+                {
+                    path = path_val.to_string();
+                }
 
-        if let Some(hosts) = h2_opts
-            .get(&Value::String("host".to_string()))
-            .and_then(|v| v.as_sequence())
-        {
-            if !hosts.is_empty() {
-                if let Some(first_host) = hosts.get(0).and_then(|v| v.as_str()) {
-                    host = first_host.to_string();
+                if let Some(hosts) = h2_opts
+                    .get(&Value::String("host".to_string()))
+                    .and_then(|v| v.as_sequence())
+                {
+                    if !hosts.is_empty() {
+                        if let Some(first_host) = hosts.get(0).and_then(|v| v.as_str()) {
+                            host = first_host.to_string();
+                        }
+                    }
                 }
             }
         }
-    }
-    // Handle HTTP options
-    else if let Some(http_opts) = proxy.get("http-opts").and_then(|v| v.as_mapping()) {
-        if let Some(paths) = http_opts
-            .get(&Value::String("path".to_string()))
-            .and_then(|v| v.as_sequence())
-        {
-            if !paths.is_empty() {
-                if let Some(first_path) = paths.get(0).and_then(|v| v.as_str()) {
-                    path = first_path.to_string();
+        // HTTP options
+        Transport::Http => {
+            if let Some(http_opts) = proxy.get("http-opts").and_then(|v| v.as_mapping()) {
+                if let Some(paths) = http_opts
+                    .get(&Value::String("path".to_string()))
+                    .and_then(|v| v.as_sequence())
+                {
+                    if !paths.is_empty() {
+                        if let Some(first_path) = paths.get(0).and_then(|v| v.as_str()) {
+                            path = first_path.to_string();
+                        }
+                    }
                 }
-            }
-        }
 
-        if let Some(hosts) = http_opts
-            .get(&Value::String("host".to_string()))
-            .and_then(|v| v.as_sequence())
-        {
-            if !hosts.is_empty() {
-                if let Some(first_host) = hosts.get(0).and_then(|v| v.as_str()) {
-                    host = first_host.to_string();
+                if let Some(hosts) = http_opts
+                    .get(&Value::String("host".to_string()))
+                    .and_then(|v| v.as_sequence())
+                {
+                    if !hosts.is_empty() {
+                        if let Some(first_host) = hosts.get(0).and_then(|v| v.as_str()) {
+                            host = first_host.to_string();
+                        }
+                    }
                 }
             }
         }
-    }
-    // Handle gRPC options
-    else if let Some(grpc_opts) = proxy.get("grpc-opts").and_then(|v| v.as_mapping()) {
-        if let Some(service_name) = grpc_opts
-            .get(&Value::String("grpc-service-name".to_string()))
-            .and_then(|v| v.as_str())
-        {
-            path = service_name.to_string();
+        // gRPC options
+        Transport::Grpc => {
+            if let Some(grpc_opts) = proxy.get("grpc-opts").and_then(|v| v.as_mapping()) {
+                if let Some(service_name) = grpc_opts
+                    .get(&Value::String("grpc-service-name".to_string()))
+                    .and_then(|v| v.as_str())
+                {
+                    path = service_name.to_string();
+                }
+            }
         }
+        _ => {}
     }
 
     // Prepare path
-    let final_path = if path.is_empty() { "/" } else { &path };
+    let final_path = if path.is_empty() && transport != Transport::Grpc {
+        "/"
+    } else {
+        &path
+    };
 
     // Get edge value
     let edge = "";
@@ -437,7 +557,7 @@ fn parse_clash_vmess(
         "", // type
         uuid,
         alter_id,
-        network,
+        transport.as_str(),
         cipher,
         final_path,
         &host,
@@ -463,8 +583,8 @@ fn parse_clash_socks(
     skip_cert_verify: Option<bool>,
 ) -> Option<Proxy> {
     // Extract SOCKS-specific fields
-    let username = proxy.get("username").and_then(|v| v.as_str()).unwrap_or("");
-    let password = proxy.get("password").and_then(|v| v.as_str()).unwrap_or("");
+    let username = yaml_credential(proxy, "username");
+    let password = yaml_credential(proxy, "password");
 
     // Extract underlying proxy
     let underlying_proxy = proxy
@@ -477,8 +597,8 @@ fn parse_clash_socks(
         name,
         server,
         port,
-        username,
-        password,
+        &username,
+        &password,
         udp,
         tfo,
         skip_cert_verify,
@@ -497,8 +617,8 @@ fn parse_clash_http(
     skip_cert_verify: Option<bool>,
 ) -> Option<Proxy> {
     // Extract HTTP-specific fields
-    let username = proxy.get("username").and_then(|v| v.as_str()).unwrap_or("");
-    let password = proxy.get("password").and_then(|v| v.as_str()).unwrap_or("");
+    let username = yaml_credential(proxy, "username");
+    let password = yaml_credential(proxy, "password");
 
     // Extract underlying proxy
     let underlying_proxy = proxy
@@ -506,19 +626,27 @@ fn parse_clash_http(
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
-    Some(Proxy::http_construct(
+    // `type: http` with `tls: true` is an HTTPS proxy
+    let is_https = is_https || proxy.get("tls").and_then(|v| v.as_bool()) == Some(true);
+    let mut node = Proxy::http_construct(
         HTTP_DEFAULT_GROUP,
         name,
         server,
         port,
-        username,
-        password,
+        &username,
+        &password,
         is_https,
         tfo,
         skip_cert_verify,
         None,
         underlying_proxy,
-    ))
+    );
+    node.sni = proxy
+        .get("sni")
+        .and_then(|v| v.as_str())
+        .filter(|sni| !sni.is_empty())
+        .map(str::to_string);
+    Some(node)
 }
 
 /// Parse a Trojan proxy from Clash YAML
@@ -653,18 +781,25 @@ fn parse_clash_wireguard(
     // Extract WireGuard-specific fields
     let private_key = proxy
         .get("privateKey")
+        .or_else(|| proxy.get("private-key"))
         .and_then(|v| v.as_str())
         .unwrap_or("");
     let public_key = proxy
         .get("publicKey")
+        .or_else(|| proxy.get("public-key"))
         .and_then(|v| v.as_str())
         .unwrap_or("");
     let preshared_key = proxy
         .get("presharedKey")
+        .or_else(|| proxy.get("pre-shared-key"))
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
     if private_key.is_empty() || public_key.is_empty() {
+        log::warn!(
+            "Skipping WireGuard proxy '{}': missing private or public key",
+            name
+        );
         return None;
     }
 
@@ -736,12 +871,13 @@ fn parse_clash_hysteria(
     tfo: Option<bool>,
     skip_cert_verify: Option<bool>,
 ) -> Option<Proxy> {
-    // Extract Hysteria-specific fields
+    // Extract Hysteria-specific fields, `obfs` is the obfuscation password
     let auth = proxy.get("auth").and_then(|v| v.as_str()).unwrap_or("");
     let auth_str = proxy.get("auth-str").and_then(|v| v.as_str()).unwrap_or("");
-    let obfs = proxy.get("obfs").and_then(|v| v.as_str()).unwrap_or("");
+    let obfs_password = proxy.get("obfs").and_then(|v| v.as_str()).unwrap_or("");
     let protocol = proxy
         .get("protocol")
+        .or_else(|| proxy.get("obfs-protocol"))
         .and_then(|v| v.as_str())
         .unwrap_or("udp");
 
@@ -754,27 +890,22 @@ fn parse_clash_hysteria(
     // Get ports range if specified
     let ports = proxy.get("ports").and_then(|v| v.as_str()).unwrap_or("");
 
-    // Get up/down speeds
-    let up_mbps = proxy.get("up").and_then(|v| v.as_u64()).unwrap_or(0);
-    let down_mbps = proxy.get("down").and_then(|v| v.as_u64()).unwrap_or(0);
-    let up_speed = if up_mbps > 0 {
-        Some(up_mbps as u32)
-    } else {
-        None
-    };
-    let down_speed = if down_mbps > 0 {
-        Some(down_mbps as u32)
-    } else {
-        None
+    // Get up/down speeds, numbers or strings like `30 Mbps`
+    let bandwidth = |keys: [&str; 2]| {
+        keys.iter()
+            .filter_map(|key| proxy.get(*key))
+            .find_map(|v| match v {
+                Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+                Value::String(s) => parse_bandwidth(s),
+                _ => None,
+            })
     };
+    let up_speed = bandwidth(["up", "up-speed"]);
+    let down_speed = bandwidth(["down", "down-speed"]);
 
     // Get TLS settings
     let sni = proxy.get("sni").and_then(|v| v.as_str()).unwrap_or("");
-    let alpn_value = proxy.get("alpn").and_then(|v| v.as_str()).unwrap_or("");
-    let mut alpn = Vec::new();
-    if !alpn_value.is_empty() {
-        alpn.push(alpn_value.to_string());
-    }
+    let alpn = yaml_alpn(proxy);
 
     let fingerprint = proxy
         .get("fingerprint")
@@ -815,22 +946,23 @@ fn parse_clash_hysteria(
         None
     };
 
-    Some(Proxy::hysteria_construct(
+    let mut node = Proxy::hysteria_construct(
         HYSTERIA_DEFAULT_GROUP.to_string(),
         name.to_string(),
         server.to_string(),
         port,
         ports.to_string(),
         protocol.to_string(),
-        "".to_string(), // obfs_param
+        obfs_password.to_string(),
         up_speed,
         down_speed,
-        if !auth.is_empty() {
-            auth.to_string()
+        auth_str.to_string(),
+        if obfs_password.is_empty() {
+            ""
         } else {
-            auth_str.to_string()
-        },
-        obfs.to_string(),
+            "xplus"
+        }
+        .to_string(),
         sni.to_string(),
         fingerprint.to_string(),
         ca.to_string(),
@@ -843,7 +975,11 @@ fn parse_clash_hysteria(
         tfo,
         skip_cert_verify,
         Some(underlying_proxy.to_string()),
-    ))
+    );
+    if !auth.is_empty() {
+        node.auth = Some(auth.to_string());
+    }
+    Some(node)
 }
 
 /// Parse a Hysteria2 proxy from Clash YAML
@@ -898,15 +1034,7 @@ fn parse_clash_hysteria2(
         Some(v) => Some(v.to_owned()),
         None => None,
     };
-    let alpn = proxy
-        .get("alpn")
-        .and_then(|v| v.as_sequence())
-        .map(|v| {
-            v.iter()
-                .map(|v| v.as_str().unwrap_or("").to_owned())
-                .collect()
-        })
-        .unwrap_or_default();
+    let alpn = yaml_alpn(proxy);
 
     let fingerprint = match proxy.get("fingerprint").and_then(|v| v.as_str()) {
         Some(v) => Some(v.to_owned()),
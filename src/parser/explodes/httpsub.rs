@@ -31,10 +31,17 @@ pub fn explode_http_sub(link: &str, node: &mut Proxy) -> bool {
         }
     }
 
-    // Extract username and password
-    _username = url.username().to_string();
+    // Extract username and password, the URL keeps them percent-encoded
+    _username = url_decode(url.username());
     if let Some(pass) = url.password() {
-        password = pass.to_string();
+        password = url_decode(pass);
+    }
+
+    // Links without a remarks parameter may name the node in the fragment
+    if remarks.is_empty() {
+        if let Some(fragment) = url.fragment() {
+            remarks = url_decode(fragment);
+        }
     }
 
     // Extract hostname and port
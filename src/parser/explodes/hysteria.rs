@@ -1,8 +1,15 @@
-use crate::{models::HYSTERIA_DEFAULT_GROUP, utils::url_decode, Proxy};
+use crate::{
+    models::{proxy_node::hysteria::parse_bandwidth, HYSTERIA_DEFAULT_GROUP},
+    utils::{split_alpn, url_decode},
+    Proxy,
+};
 use std::collections::HashMap;
 use url::Url;
 
-/// Parse a Hysteria link into a Proxy object
+/// Parse a Hysteria v1 link into a Proxy object
+///
+/// `hysteria://host:port?auth=..&upmbps=..&downmbps=..&obfs=xplus&obfsParam=..#name`,
+/// nodes without bandwidth get the defaults.
 pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
     // Check if the link starts with hysteria://
     if !hysteria.starts_with("hysteria://") {
@@ -29,32 +36,39 @@ pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
     }
 
     // Extract auth string
-    let auth = params.get("auth").map(|s| s.as_str()).unwrap_or("");
+    let auth = params
+        .get("auth")
+        .or_else(|| params.get("auth_str"))
+        .map(|s| s.as_str())
+        .unwrap_or("");
 
     // Extract protocol
     let protocol = params.get("protocol").map(|s| s.as_str()).unwrap_or("udp");
 
-    // Extract up and down speeds
-    let up = params.get("upmbps").map(|s| s.as_str()).unwrap_or("10");
-    let down = params.get("downmbps").map(|s| s.as_str()).unwrap_or("50");
-    let up_speed = up.parse::<u32>().ok();
-    let down_speed = down.parse::<u32>().ok();
+    // Extract up and down speeds, unset ones default
+    let up_speed = params
+        .get("upmbps")
+        .or_else(|| params.get("up"))
+        .and_then(|s| parse_bandwidth(s));
+    let down_speed = params
+        .get("downmbps")
+        .or_else(|| params.get("down"))
+        .and_then(|s| parse_bandwidth(s));
 
     // Extract ALPN
     let alpn_str = params.get("alpn").map(|s| s.as_str()).unwrap_or("");
-    let mut alpn = Vec::new();
-    if !alpn_str.is_empty() {
-        for a in alpn_str.split(',') {
-            alpn.push(a.trim().to_string());
-        }
-    }
+    let alpn = split_alpn(alpn_str);
 
     // Extract obfs
     let obfs = params.get("obfs").map(|s| s.as_str()).unwrap_or("");
     let obfs_param = params.get("obfsParam").map(|s| s.as_str()).unwrap_or("");
 
     // Extract SNI
-    let sni = params.get("peer").map(|s| s.as_str()).unwrap_or(host);
+    let sni = params
+        .get("peer")
+        .or_else(|| params.get("sni"))
+        .map(|s| s.as_str())
+        .unwrap_or(host);
 
     // Extract insecure
     let insecure = params
@@ -63,11 +77,11 @@ pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
         .unwrap_or(false);
 
     // Extract remark from the fragment
-    let remark = url.fragment().unwrap_or("");
+    let remark = url_decode(url.fragment().unwrap_or(""));
     let formatted_remark = if remark.is_empty() {
         format!("{} ({})", host, port)
     } else {
-        remark.to_string()
+        remark
     };
 
     // Create the proxy object using the hysteria_construct method
@@ -87,10 +101,10 @@ pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
         "".to_string(), // fingerprint
         "".to_string(), // ca
         "".to_string(), // ca_str
-        None,           // recv_window_conn
-        None,           // recv_window
-        None,           // disable_mtu_discovery
-        None,           // hop_interval
+        params.get("recv_window_conn").and_then(|s| s.parse().ok()),
+        params.get("recv_window").and_then(|s| s.parse().ok()),
+        None, // disable_mtu_discovery
+        None, // hop_interval
         alpn,
         None, // tcp_fast_open
         Some(insecure),
@@ -99,3 +113,43 @@ pub fn explode_hysteria(hysteria: &str, node: &mut Proxy) -> bool {
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::proxy_node::hysteria::{
+        HYSTERIA_DEFAULT_DOWN_MBPS, HYSTERIA_DEFAULT_UP_MBPS,
+    };
+    use crate::models::ProxyType;
+
+    #[test]
+    fn test_explode_hysteria() {
+        let mut node = Proxy::default();
+        assert!(explode_hysteria(
+            "hysteria://example.com:8443?protocol=faketcp&auth=s3cret&peer=real.example.com\
+             &upmbps=20&downmbps=1Gbps&obfs=xplus&obfsParam=mask&recv_window=67108864#HK%20Hy",
+            &mut node
+        ));
+        assert_eq!(node.proxy_type, ProxyType::Hysteria);
+        assert_eq!(node.remark, "HK Hy");
+        assert_eq!(node.protocol.as_deref(), Some("faketcp"));
+        assert_eq!(node.auth_str.as_deref(), Some("s3cret"));
+        assert_eq!(node.obfs.as_deref(), Some("xplus"));
+        assert_eq!(node.obfs_param.as_deref(), Some("mask"));
+        assert_eq!((node.up_speed, node.down_speed), (20, 1000));
+        assert_eq!(node.recv_window, 67108864);
+        assert_eq!(node.sni.as_deref(), Some("real.example.com"));
+
+        // No bandwidth and an unknown protocol still give a node
+        let mut node = Proxy::default();
+        assert!(explode_hysteria(
+            "hysteria://example.com:443?protocol=tcp&auth=x",
+            &mut node
+        ));
+        assert_eq!(node.protocol.as_deref(), Some("udp"));
+        assert_eq!(
+            (node.up_speed, node.down_speed),
+            (HYSTERIA_DEFAULT_UP_MBPS, HYSTERIA_DEFAULT_DOWN_MBPS)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+mod anytls;
 mod common;
 mod explode_clash;
 mod http;
@@ -13,11 +14,13 @@ mod ssr;
 mod sstap;
 mod surge;
 mod trojan;
+mod tuic;
 mod vless;
 mod vmess;
 mod wireguard;
 
-pub use common::{explode, explode_conf_content, explode_sub};
+pub use anytls::explode_anytls;
+pub use common::{explode, explode_conf_content, explode_sub, explode_sub_reader};
 pub use explode_clash::explode_clash;
 pub use http::explode_http;
 pub use httpsub::explode_http_sub;
@@ -32,9 +35,12 @@ pub use ssr::{explode_ssr, explode_ssr_conf};
 pub use sstap::explode_sstap;
 pub use surge::explode_surge;
 pub use trojan::{explode_trojan, explode_trojan_go};
+pub use tuic::explode_tuic;
 pub use vless::explode_vless;
 pub use vmess::{
     explode_kitsunebi, explode_shadowrocket, explode_std_vmess, explode_std_vmess_new,
-    explode_vmess, explode_vmess_conf,
+    explode_vmess, explode_vmess_conf, explode_vmess_link,
+};
+pub use wireguard::{
+    explode_wireguard, explode_wireguard_conf, normalize_reserved, split_addresses,
 };
-pub use wireguard::explode_wireguard;
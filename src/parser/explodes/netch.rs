@@ -1,3 +1,4 @@
+use crate::models::proxy_node::ssr::normalize_ssr_params;
 use crate::models::{
     Proxy, HTTP_DEFAULT_GROUP, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_DEFAULT_GROUP,
     TROJAN_DEFAULT_GROUP, V2RAY_DEFAULT_GROUP,
@@ -120,6 +121,13 @@ pub fn explode_netch(netch: &str, node: &mut Proxy) -> bool {
             if method.is_empty() || password.is_empty() || protocol.is_empty() || obfs.is_empty() {
                 return false;
             }
+            let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+                Ok(params) => params,
+                Err(e) => {
+                    log::warn!("ShadowsocksR node '{}' skipped: {}", remark, e);
+                    return false;
+                }
+            };
 
             let group = if group.is_empty() {
                 SSR_DEFAULT_GROUP
@@ -132,9 +140,9 @@ pub fn explode_netch(netch: &str, node: &mut Proxy) -> bool {
                 &remark,
                 server,
                 port,
-                protocol,
+                &protocol,
                 method,
-                obfs,
+                &obfs,
                 password,
                 obfs_param,
                 protocol_param,
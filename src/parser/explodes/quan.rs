@@ -1,3 +1,4 @@
+use crate::models::proxy_node::ssr::normalize_ssr_params;
 use crate::models::{
     Proxy, HTTP_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_DEFAULT_GROUP, TROJAN_DEFAULT_GROUP,
     V2RAY_DEFAULT_GROUP,
@@ -150,6 +151,13 @@ fn parse_quan_ssr(name: &str, config_parts: Vec<&str>, node: &mut Proxy) -> bool
     let protocol_param = config_parts[6];
     let obfs = config_parts[7];
     let obfs_param = config_parts[8];
+    let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+        Ok(params) => params,
+        Err(e) => {
+            log::warn!("ShadowsocksR node '{}' skipped: {}", name, e);
+            return false;
+        }
+    };
 
     // Default values
     let mut udp = None;
@@ -172,9 +180,9 @@ fn parse_quan_ssr(name: &str, config_parts: Vec<&str>, node: &mut Proxy) -> bool
         name,
         server,
         port,
-        protocol,
+        &protocol,
         method,
-        obfs,
+        &obfs,
         password,
         obfs_param,
         protocol_param,
@@ -1,4 +1,6 @@
-use crate::models::{Proxy, SS_DEFAULT_GROUP};
+use crate::models::proxy_node::shadowtls::{ShadowTlsOptions, SHADOW_TLS_PLUGIN};
+use crate::models::{validate_ss2022_psk, Proxy, SS_CIPHERS, SS_DEFAULT_GROUP};
+use crate::utils::network::split_host_port;
 use crate::utils::url::url_decode;
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
@@ -47,6 +49,29 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
                 if !value.is_empty() {
                     group = crate::utils::base64::url_safe_base64_decode(&value);
                 }
+            } else if key == "shadow-tls" {
+                // Shadowrocket style: base64 JSON with version, host and password
+                match parse_shadow_tls_param(&value) {
+                    Some(shadow_tls) => {
+                        plugin = SHADOW_TLS_PLUGIN.to_string();
+                        plugin_opts = shadow_tls.to_plugin_opts();
+                    }
+                    None => log::warn!("Ignoring invalid shadow-tls parameter in SS link"),
+                }
+            }
+        }
+    }
+
+    if ShadowTlsOptions::is_shadow_tls_plugin(&plugin) {
+        match ShadowTlsOptions::from_plugin_opts(&plugin_opts) {
+            Some(shadow_tls) => {
+                plugin = SHADOW_TLS_PLUGIN.to_string();
+                plugin_opts = shadow_tls.to_plugin_opts();
+            }
+            None => {
+                log::warn!("Ignoring shadow-tls plugin without a valid host or version");
+                plugin.clear();
+                plugin_opts.clear();
             }
         }
     }
@@ -68,18 +93,22 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         let server_port = parts[1];
 
         // Parse server and port
-        let server_port_parts: Vec<&str> = server_port.split(':').collect();
-        if server_port_parts.len() < 2 {
+        let Some((host, port_str)) = split_host_port(server_port) else {
             return false;
-        }
-        server = server_port_parts[0].to_string();
-        port = match server_port_parts[1].parse::<u16>() {
+        };
+        server = host.to_string();
+        port = match port_str.parse::<u16>() {
             Ok(p) => p,
             Err(_) => return false,
         };
 
-        // Decode the secret part
-        let decoded_secret = crate::utils::base64::url_safe_base64_decode(secret);
+        // Decode the secret part, Shadowsocks 2022 links keep it
+        // percent-encoded instead of base64
+        let plain_secret = url_decode(secret);
+        let decoded_secret = match plain_secret.split_once(':') {
+            Some((method, _)) if SS_CIPHERS.contains(&method) => plain_secret,
+            _ => crate::utils::base64::url_safe_base64_decode(secret),
+        };
         let method_pass: Vec<&str> = decoded_secret.split(':').collect();
         if method_pass.len() < 2 {
             return false;
@@ -111,12 +140,11 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         password = method_pass_parts[1..].join(":"); // In case password contains colons
 
         // Parse server and port
-        let server_port_parts: Vec<&str> = server_port.split(':').collect();
-        if server_port_parts.len() < 2 {
+        let Some((host, port_str)) = split_host_port(server_port) else {
             return false;
-        }
-        server = server_port_parts[0].to_string();
-        port = match server_port_parts[1].parse::<u16>() {
+        };
+        server = host.to_string();
+        port = match port_str.parse::<u16>() {
             Ok(p) => p,
             Err(_) => return false,
         };
@@ -132,6 +160,11 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
         ps = format!("{} ({})", server, port);
     }
 
+    if let Err(e) = validate_ss2022_psk(&method, &password) {
+        log::warn!("Shadowsocks node '{}' skipped: {}", ps, e);
+        return false;
+    }
+
     // Create the proxy
     *node = Proxy::ss_construct(
         &group,
@@ -152,6 +185,25 @@ pub fn explode_ss(ss: &str, node: &mut Proxy) -> bool {
     true
 }
 
+/// Parse the base64 JSON `shadow-tls` parameter of a SS link
+fn parse_shadow_tls_param(value: &str) -> Option<ShadowTlsOptions> {
+    let json: Value =
+        serde_json::from_str(&crate::utils::base64::url_safe_base64_decode(value)).ok()?;
+    let field = |name: &str| match &json[name] {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+    let mut opts = format!("host={}", field("host")?);
+    if let Some(password) = field("password") {
+        opts.push_str(&format!(";password={}", password));
+    }
+    if let Some(version) = field("version") {
+        opts.push_str(&format!(";version={}", version));
+    }
+    ShadowTlsOptions::from_plugin_opts(&opts)
+}
+
 /// Parse a SSD (Shadowsocks subscription) link into a vector of Proxy objects
 pub fn explode_ssd(link: &str, nodes: &mut Vec<Proxy>) -> bool {
     // Check if the link starts with ssd://
@@ -421,3 +473,37 @@ pub fn explode_ss_conf(content: &str, nodes: &mut Vec<Proxy>) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explode_ss2022() {
+        let mut node = Proxy::default();
+        let link = "ss://2022-blake3-aes-128-gcm:ZGVmZ2hpamtsbW5vcHFycw%3D%3D%3AZGVmZ2hpamtsbW5vcHFycw%3D%3D@example.com:8388#SS2022";
+        assert!(explode_ss(link, &mut node));
+        assert_eq!(
+            node.encrypt_method.as_deref(),
+            Some("2022-blake3-aes-128-gcm")
+        );
+        assert_eq!(
+            node.password.as_deref(),
+            Some("ZGVmZ2hpamtsbW5vcHFycw==:ZGVmZ2hpamtsbW5vcHFycw==")
+        );
+        assert_eq!(node.hostname, "example.com");
+
+        // aes-256 needs a 32 byte key
+        let link =
+            "ss://2022-blake3-aes-256-gcm:ZGVmZ2hpamtsbW5vcHFycw%3D%3D@example.com:8388#short";
+        assert!(!explode_ss(link, &mut Proxy::default()));
+
+        // Base64 userinfo keeps working
+        let link = format!(
+            "ss://{}@example.com:8388#legacy",
+            STANDARD.encode("aes-128-gcm:pass:word")
+        );
+        assert!(explode_ss(&link, &mut node));
+        assert_eq!(node.password.as_deref(), Some("pass:word"));
+    }
+}
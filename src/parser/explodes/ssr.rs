@@ -1,3 +1,4 @@
+use crate::models::proxy_node::ssr::normalize_ssr_params;
 use crate::models::{Proxy, SSR_DEFAULT_GROUP, SS_CIPHERS};
 use crate::utils::base64::url_safe_base64_decode;
 use serde_json::Value;
@@ -63,6 +64,13 @@ pub fn explode_ssr(ssr: &str, node: &mut Proxy) -> bool {
     let method = parts[3];
     let obfs = parts[4];
     let password_encoded = parts[5];
+    let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+        Ok(params) => params,
+        Err(e) => {
+            log::warn!("ShadowsocksR node '{}' skipped: {}", server, e);
+            return false;
+        }
+    };
 
     // Decode password (base64 encoded)
     let password = url_safe_base64_decode(password_encoded);
@@ -102,9 +110,9 @@ pub fn explode_ssr(ssr: &str, node: &mut Proxy) -> bool {
             &remarks,
             server,
             port,
-            protocol,
+            &protocol,
             method,
-            obfs,
+            &obfs,
             &password,
             &obfsparam,
             &protoparam,
@@ -145,6 +153,13 @@ pub fn explode_ssr_conf(content: &str, nodes: &mut Vec<Proxy>) -> bool {
         let proto_param = config["protocolparam"].as_str().unwrap_or("");
         let remarks = config["remarks"].as_str().unwrap_or("");
         let group = config["group"].as_str().unwrap_or("");
+        let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+            Ok(params) => params,
+            Err(e) => {
+                log::warn!("ShadowsocksR node '{}' skipped: {}", server, e);
+                continue;
+            }
+        };
 
         // Create formatted remark and group
         let group_str = if group.is_empty() {
@@ -164,9 +179,9 @@ pub fn explode_ssr_conf(content: &str, nodes: &mut Vec<Proxy>) -> bool {
             &remark_str,
             server,
             port,
-            protocol,
+            &protocol,
             method,
-            obfs,
+            &obfs,
             password,
             obfs_param,
             proto_param,
@@ -271,6 +286,30 @@ mod tests {
         assert_eq!(node.remark, format!("{} ({})", server, port));
     }
 
+    #[test]
+    fn test_explode_ssr_validates_params() {
+        let link = |protocol: &str, obfs: &str| {
+            let plain = format!(
+                "example.com:8388:{}:aes-256-cfb:{}:{}",
+                protocol,
+                obfs,
+                STANDARD.encode("secret")
+            );
+            format!("ssr://{}", STANDARD.encode(plain))
+        };
+
+        let mut node = Proxy::default();
+        assert!(explode_ssr(
+            &link("auth-aes128-md5", "http-simple"),
+            &mut node
+        ));
+        assert_eq!(node.protocol.as_deref(), Some("auth_aes128_md5"));
+        assert_eq!(node.obfs.as_deref(), Some("http_simple"));
+
+        assert!(!explode_ssr(&link("auth_unknown", "plain"), &mut node));
+        assert!(!explode_ssr(&link("origin", "obfs_tls"), &mut node));
+    }
+
     #[test]
     fn test_explode_ssr_conf_valid() {
         let mut nodes = Vec::new();
@@ -1,6 +1,5 @@
-use crate::models::{
-    Proxy, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_CIPHERS, SS_DEFAULT_GROUP,
-};
+use crate::models::proxy_node::ssr::normalize_ssr_params;
+use crate::models::{Proxy, SOCKS_DEFAULT_GROUP, SSR_DEFAULT_GROUP, SS_CIPHERS, SS_DEFAULT_GROUP};
 use serde_json::{from_str, Value};
 
 /// Parse a SSTap JSON configuration into a vector of Proxy objects
@@ -83,6 +82,13 @@ pub fn explode_sstap(content: &str, nodes: &mut Vec<Proxy>) -> bool {
                 let protocol = config["protocol"].as_str().unwrap_or("");
                 let obfs = config["obfs"].as_str().unwrap_or("");
                 let method = config["method"].as_str().unwrap_or("");
+                let (protocol, obfs) = match normalize_ssr_params(protocol, obfs) {
+                    Ok(params) => params,
+                    Err(e) => {
+                        log::warn!("ShadowsocksR node '{}' skipped: {}", final_remarks, e);
+                        continue;
+                    }
+                };
 
                 // Check if it's SS or SSR
                 if SS_CIPHERS.iter().any(|c| *c == method)
@@ -128,9 +134,9 @@ pub fn explode_sstap(content: &str, nodes: &mut Vec<Proxy>) -> bool {
                         &final_remarks,
                         server,
                         port,
-                        protocol,
+                        &protocol,
                         method,
-                        obfs,
+                        &obfs,
                         password,
                         obfs_param,
                         proto_param,
@@ -1,4 +1,15 @@
-use crate::{models::TROJAN_DEFAULT_GROUP, utils::url_decode, Proxy};
+use crate::{
+    models::{
+        proxy_node::{
+            combined::CombinedProxy,
+            trojan_go::{TrojanGoProxy, TrojanGoShadowsocks},
+            utls::parse_client_fingerprint,
+        },
+        TROJAN_DEFAULT_GROUP,
+    },
+    utils::{split_alpn, url_decode},
+    Proxy,
+};
 use std::collections::HashMap;
 use url::Url;
 
@@ -106,6 +117,8 @@ pub fn explode_trojan(trojan: &str, node: &mut Proxy) -> bool {
         None,             // tls13
         None,             // underlying_proxy
     );
+    node.alpn = split_alpn(params.get("alpn").map_or("", |s| s.as_str()));
+    node.client_fingerprint = params.get("fp").and_then(|fp| parse_client_fingerprint(fp));
 
     true
 }
@@ -147,11 +160,37 @@ pub fn explode_trojan_go(trojan_go: &str, node: &mut Proxy) -> bool {
         params.insert(key.to_string(), url_decode(&value));
     }
 
-    // Extract network, host, path
-    let network = params.get("type").map(|s| s.to_string());
+    // Extract network, host, path; `type=original` is plain Trojan over TLS
+    let websocket = params.get("ws").is_some_and(|s| s == "1" || s == "true");
+    let network = if websocket {
+        Some("ws".to_string())
+    } else {
+        params
+            .get("type")
+            .filter(|s| !s.is_empty() && *s != "original")
+            .map(|s| s.to_string())
+    };
     let host_param = params.get("host").map(|s| s.to_string());
-    let path = params.get("path").map(|s| s.to_string());
+    let path = params
+        .get("path")
+        .or_else(|| params.get("wspath"))
+        .map(|s| s.to_string());
     let sni = params.get("sni").map(|s| s.to_string());
+
+    // Extract Trojan-Go options, links with an encryption we cannot carry
+    // are not Trojan-Go links we can convert
+    let shadowsocks =
+        match TrojanGoShadowsocks::parse(params.get("encryption").map_or("", |s| s.as_str())) {
+            Ok(shadowsocks) => shadowsocks,
+            Err(e) => {
+                log::warn!("Trojan-Go node '{}:{}' skipped: {}", host, port, e);
+                return false;
+            }
+        };
+    let trojan_go = TrojanGoProxy {
+        mux: params.get("mux").is_some_and(|s| s == "1" || s == "true"),
+        shadowsocks,
+    };
     // Extract TLS verification setting
     let skip_cert_verify = params
         .get("allowInsecure")
@@ -193,6 +232,56 @@ pub fn explode_trojan_go(trojan_go: &str, node: &mut Proxy) -> bool {
         None,             // tls13
         None,             // underlying_proxy
     );
+    node.alpn = split_alpn(params.get("alpn").map_or("", |s| s.as_str()));
+    node.client_fingerprint = params.get("fp").and_then(|fp| parse_client_fingerprint(fp));
+    node.combined_proxy = Some(CombinedProxy::TrojanGo(trojan_go));
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::explodes::explode;
+
+    #[test]
+    fn test_explode_trojan_go() {
+        let mut node = Proxy::default();
+        assert!(explode(
+            "trojan-go://secret@example.com:443/?sni=real.example.com&type=ws&host=cdn.example.com&path=%2Fws&encryption=ss%3Baes-128-gcm%3Ainner&mux=1#Go",
+            &mut node
+        ));
+        assert_eq!(node.remark, "Go");
+        assert_eq!(node.transfer_protocol.as_deref(), Some("ws"));
+        assert_eq!(node.path.as_deref(), Some("/ws"));
+        assert_eq!(node.host.as_deref(), Some("cdn.example.com"));
+        let trojan_go = node.trojan_go().unwrap();
+        assert!(trojan_go.mux);
+        let ss = trojan_go.shadowsocks.as_ref().unwrap();
+        assert_eq!(
+            (ss.method.as_str(), ss.password.as_str()),
+            ("aes-128-gcm", "inner")
+        );
+
+        let mut node = Proxy::default();
+        assert!(explode(
+            "trojan-go://secret@example.com:443/?ws=1&wspath=%2Flegacy&type=original#Legacy",
+            &mut node
+        ));
+        assert_eq!(node.transfer_protocol.as_deref(), Some("ws"));
+        assert_eq!(node.path.as_deref(), Some("/legacy"));
+        assert_eq!(node.trojan_go(), Some(&TrojanGoProxy::default()));
+
+        let mut node = Proxy::default();
+        assert!(explode(
+            "trojan-go://secret@example.com:443/?type=original",
+            &mut node
+        ));
+        assert_eq!(node.transfer_protocol, None);
+
+        assert!(!explode(
+            "trojan-go://secret@example.com:443/?encryption=ss%3Brc4-md5%3Ainner",
+            &mut Proxy::default()
+        ));
+    }
+}
@@ -1,8 +1,9 @@
 use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::utls::parse_client_fingerprint;
 use crate::models::proxy_node::vless::VlessProxy;
-use crate::models::{Proxy, ProxyType};
-use crate::utils::url_decode;
-use std::collections::{HashMap, HashSet};
+use crate::models::{Proxy, ProxyType, Transport};
+use crate::utils::{split_alpn, url_decode};
+use std::collections::HashMap;
 use url::Url;
 
 /// Parse a VLESS link into a Proxy object
@@ -38,24 +39,31 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
     let port = url.port().unwrap_or(443);
 
     // Extract optional fields
-    let tls = params
+    let security = params
         .get("security")
         .map(|s| s.to_lowercase())
-        .map(|s| s.ends_with("tls") || s == "reality")
-        .unwrap_or(false);
+        .unwrap_or_default();
+    let tls = security.ends_with("tls") || security == "reality";
+
+    // Reality cannot work without the server's public key
+    let public_key = params.get("pbk").filter(|s| !s.is_empty());
+    if security == "reality" && public_key.is_none() {
+        log::warn!(
+            "VLESS node {}:{} uses security=reality but has no public key (pbk), skipped",
+            host,
+            port
+        );
+        return false;
+    }
 
-    let fingerprint = params
-        .get("fp")
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| "chrome".to_string());
+    let fingerprint = match params.get("fp") {
+        Some(fp) => parse_client_fingerprint(fp),
+        None => Some("chrome".to_string()),
+    };
 
     let alpn = params
         .get("alpn")
-        .map(|s| {
-            s.split(',')
-                .map(|s| s.trim().to_string())
-                .collect::<HashSet<_>>()
-        })
+        .map(|s| split_alpn(s))
         .unwrap_or_default();
 
     let sni = params.get("sni").map(|s| s.to_string());
@@ -65,10 +73,13 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
     let packet_encoding = params.get("packetEncoding").map(|s| s.to_string());
     let packet_addr = packet_encoding.as_deref() == Some("packet");
 
-    let network = params
-        .get("type")
-        .map(|s| s.to_lowercase())
-        .unwrap_or_else(|| "tcp".to_string());
+    let network = match params.get("type").map_or(Ok(Transport::Tcp), |s| s.parse()) {
+        Ok(transport) => transport.as_str().to_string(),
+        Err(e) => {
+            log::warn!("VLESS node {}:{} skipped: {}", host, port, e);
+            return false;
+        }
+    };
 
     let fake_type = params
         .get("headerType")
@@ -87,11 +98,11 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
     }
     vless_proxy.network = Some(network.clone());
     vless_proxy.servername = sni;
-    vless_proxy.client_fingerprint = Some(fingerprint);
+    vless_proxy.client_fingerprint = fingerprint;
     vless_proxy.flow = flow;
 
     // Handle Reality options
-    if let Some(public_key) = params.get("pbk") {
+    if let Some(public_key) = public_key {
         vless_proxy.reality_public_key = Some(public_key.to_string());
         vless_proxy.reality_short_id = params.get("sid").map(|s| s.to_string());
     }
@@ -158,6 +169,7 @@ pub fn explode_vless(vless: &str, node: &mut Proxy) -> bool {
         }
         "grpc" => {
             vless_proxy.grpc_service_name = params.get("serviceName").map(|s| s.to_string());
+            vless_proxy.grpc_mode = params.get("mode").and_then(|s| s.parse().ok());
         }
         _ => {}
     }
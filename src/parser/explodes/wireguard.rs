@@ -1,6 +1,6 @@
-use crate::{utils::url_decode, Proxy};
+use crate::{models::WG_DEFAULT_GROUP, utils::url_decode, Proxy};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use url::Url;
 
 /// Parse a WireGuard link into a Proxy object
@@ -29,8 +29,14 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
     };
 
     let public_key = match params.get("publicKey") {
-        Some(key) => key,
-        None => return false,
+        Some(key) if !key.is_empty() => key,
+        _ => {
+            log::warn!(
+                "Rejecting WireGuard link without a public key: {}",
+                wireguard
+            );
+            return false;
+        }
     };
 
     // Extract host and port
@@ -42,11 +48,22 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
 
     // Extract optional fields
     let preshared_key = params.get("presharedKey").map(|s| s.as_str()).unwrap_or("");
+    let (address_ip, address_ipv6) = split_addresses(
+        params
+            .get("address")
+            .map(|s| s.as_str())
+            .unwrap_or_default(),
+    );
     let self_ip = params
         .get("selfIP")
-        .map(|s| s.as_str())
-        .unwrap_or("10.0.0.2");
-    let self_ipv6 = params.get("selfIPv6").map(|s| s.as_str()).unwrap_or("");
+        .cloned()
+        .or(address_ip)
+        .unwrap_or_else(|| "10.0.0.2".to_string());
+    let self_ipv6 = params
+        .get("selfIPv6")
+        .cloned()
+        .or(address_ipv6)
+        .unwrap_or_default();
     let mtu = params
         .get("mtu")
         .map(|s| s.parse::<u16>().unwrap_or(1420))
@@ -78,8 +95,8 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
         formatted_remark,
         host.to_string(),
         port,
-        self_ip.to_string(),
-        self_ipv6.to_string(),
+        self_ip,
+        self_ipv6,
         private_key.to_string(),
         public_key.to_string(),
         preshared_key.to_string(),
@@ -87,7 +104,10 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
         Some(mtu),
         Some(keep_alive),
         "https://www.gstatic.com/generate_204".to_string(),
-        "".to_string(),
+        params
+            .get("reserved")
+            .map(|s| normalize_reserved(s))
+            .unwrap_or_default(),
         None,
         None,
     );
@@ -96,6 +116,164 @@ pub fn explode_wireguard(wireguard: &str, node: &mut Proxy) -> bool {
     true
 }
 
+/// Split a WireGuard address list into its IPv4 and IPv6 addresses
+///
+/// Prefix lengths are dropped, e.g. `10.0.0.2/32, fd00::2/128` gives
+/// `(Some("10.0.0.2"), Some("fd00::2"))`. Only the first address of each
+/// family is kept.
+pub fn split_addresses(addresses: &str) -> (Option<String>, Option<String>) {
+    let mut ipv4 = None;
+    let mut ipv6 = None;
+    for address in addresses.split(',') {
+        let address = address.trim();
+        let address = address.split('/').next().unwrap_or(address);
+        if address.is_empty() {
+            continue;
+        }
+        if address.contains(':') {
+            ipv6.get_or_insert_with(|| address.to_string());
+        } else {
+            ipv4.get_or_insert_with(|| address.to_string());
+        }
+    }
+    (ipv4, ipv6)
+}
+
+/// Normalize reserved bytes to the comma separated form stored in
+/// `client_id`, accepting `1,2,3`, `1/2/3` and `[1, 2, 3]`
+pub fn normalize_reserved(reserved: &str) -> String {
+    reserved
+        .trim_matches(|c| c == '[' || c == ']')
+        .split([',', '/'])
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parse a standard WireGuard configuration file into a Proxy per peer
+///
+/// The `[Interface]` section provides the private key, addresses, DNS and
+/// MTU; every `[Peer]` section provides a public key and endpoint. Peers
+/// without a public key or endpoint are skipped with a warning.
+pub fn explode_wireguard_conf(content: &str, nodes: &mut Vec<Proxy>) -> bool {
+    #[derive(Default)]
+    struct Section {
+        name: String,
+        values: HashMap<String, String>,
+    }
+
+    let mut sections: Vec<Section> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push(Section {
+                name: line[1..line.len() - 1].trim().to_lowercase(),
+                ..Default::default()
+            });
+            continue;
+        }
+        if let (Some(section), Some((key, value))) = (sections.last_mut(), line.split_once('=')) {
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            // Repeated keys are appended, as wg-quick does for Address and DNS
+            section
+                .values
+                .entry(key)
+                .and_modify(|v| {
+                    v.push(',');
+                    v.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+    }
+
+    let Some(interface) = sections.iter().find(|s| s.name == "interface") else {
+        return false;
+    };
+    let private_key = match interface.values.get("privatekey") {
+        Some(key) if !key.is_empty() => key.clone(),
+        _ => {
+            log::warn!("WireGuard configuration has no PrivateKey in [Interface]");
+            return false;
+        }
+    };
+    let (self_ip, self_ipv6) =
+        split_addresses(interface.values.get("address").map_or("", |s| s.as_str()));
+    let dns_servers: BTreeSet<String> = interface
+        .values
+        .get("dns")
+        .map(|dns| {
+            dns.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mtu = interface
+        .values
+        .get("mtu")
+        .and_then(|s| s.parse::<u16>().ok())
+        .unwrap_or(0);
+
+    let orig_size = nodes.len();
+    for (index, peer) in sections.iter().filter(|s| s.name == "peer").enumerate() {
+        let public_key = match peer.values.get("publickey") {
+            Some(key) if !key.is_empty() => key.clone(),
+            _ => {
+                log::warn!("Skipping WireGuard peer #{}: missing PublicKey", index + 1);
+                continue;
+            }
+        };
+        let endpoint = peer.values.get("endpoint").map_or("", |s| s.as_str());
+        let Some((hostname, port)) = endpoint
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        else {
+            log::warn!(
+                "Skipping WireGuard peer #{}: invalid Endpoint '{}'",
+                index + 1,
+                endpoint
+            );
+            continue;
+        };
+        let hostname = hostname.trim_start_matches('[').trim_end_matches(']');
+
+        nodes.push(Proxy {
+            proxy_type: crate::models::ProxyType::WireGuard,
+            group: WG_DEFAULT_GROUP.to_string(),
+            remark: format!("{} ({})", hostname, port),
+            hostname: hostname.to_string(),
+            port,
+            self_ip: self_ip.clone(),
+            self_ipv6: self_ipv6.clone(),
+            private_key: Some(private_key.clone()),
+            public_key: Some(public_key),
+            pre_shared_key: peer.values.get("presharedkey").cloned(),
+            dns_servers: dns_servers.clone(),
+            mtu,
+            keep_alive: peer
+                .values
+                .get("persistentkeepalive")
+                .and_then(|s| s.parse::<u16>().ok())
+                .unwrap_or(0),
+            allowed_ips: peer
+                .values
+                .get("allowedips")
+                .cloned()
+                .unwrap_or_else(|| "0.0.0.0/0, ::/0".to_string()),
+            client_id: peer.values.get("reserved").map(|s| normalize_reserved(s)),
+            udp: Some(true),
+            ..Default::default()
+        });
+    }
+
+    nodes.len() > orig_size
+}
+
 /// Parse WireGuard peers from configuration text
 pub fn parse_peers(data: &str, node: &mut Proxy) -> bool {
     // Find peers enclosed in parentheses
@@ -156,3 +334,82 @@ pub fn parse_peers(data: &str, node: &mut Proxy) -> bool {
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::yaml::clash::clash_output::ClashProxyOutput;
+    use crate::parser::yaml::clash::parse_clash_yaml;
+
+    #[test]
+    fn test_explode_wireguard_conf() {
+        let conf = r#"
+[Interface]
+PrivateKey = cHJpdmF0ZQ==
+Address = 172.16.0.2/32, fd01:5ca1:ab1e::2/128
+DNS = 1.1.1.1
+MTU = 1280
+
+[Peer]
+PublicKey = cHVibGlj
+Endpoint = engage.example.com:2408
+AllowedIPs = 0.0.0.0/0, ::/0
+PersistentKeepalive = 25
+Reserved = 1, 2, 3
+
+[Peer]
+Endpoint = missing.example.com:51820
+"#;
+        let mut nodes = Vec::new();
+        assert!(explode_wireguard_conf(conf, &mut nodes));
+        assert_eq!(nodes.len(), 1);
+
+        let node = &nodes[0];
+        assert_eq!(node.hostname, "engage.example.com");
+        assert_eq!(node.port, 2408);
+        assert_eq!(node.self_ip.as_deref(), Some("172.16.0.2"));
+        assert_eq!(node.self_ipv6.as_deref(), Some("fd01:5ca1:ab1e::2"));
+        assert_eq!(node.public_key.as_deref(), Some("cHVibGlj"));
+        assert_eq!(node.client_id.as_deref(), Some("1,2,3"));
+        assert_eq!(node.mtu, 1280);
+        assert_eq!(node.keep_alive, 25);
+    }
+
+    #[test]
+    fn test_clash_meta_wireguard_round_trip() {
+        let yaml = r#"
+proxies:
+  - name: wg
+    type: wireguard
+    ip: 172.16.0.2/32
+    ipv6: fd01::2
+    private-key: cHJpdmF0ZQ==
+    peers:
+      - server: 162.159.192.1
+        port: 2480
+        public-key: cHVibGlj
+        pre-shared-key: c2hhcmVk
+        reserved: [209, 98, 59]
+  - name: no-key
+    type: wireguard
+    server: 1.2.3.4
+    port: 51820
+    private-key: cHJpdmF0ZQ==
+"#;
+        let nodes = parse_clash_yaml(yaml).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].hostname, "162.159.192.1");
+        assert_eq!(nodes[0].self_ip.as_deref(), Some("172.16.0.2"));
+
+        let output = serde_yaml::to_value(ClashProxyOutput::from(nodes[0].clone())).unwrap();
+        assert_eq!(output["type"], "wireguard");
+        assert_eq!(output["public-key"], "cHVibGlj");
+        assert_eq!(output["pre-shared-key"], "c2hhcmVk");
+        assert_eq!(output["ipv6"], "fd01::2");
+        assert_eq!(output["udp"], true);
+        assert_eq!(
+            output["reserved"],
+            serde_yaml::to_value(vec![209, 98, 59]).unwrap()
+        );
+    }
+}
@@ -1,7 +1,13 @@
 use std::collections::HashMap;
 
-use crate::models::RegexMatchConfigs;
+use crate::models::{ProxyGroupConfigs, RegexMatchConfigs};
+use crate::utils::explain::ExplainRecorder;
+use crate::utils::fetch::FetchOptions;
 use crate::utils::http::{parse_proxy, ProxyConfig};
+use crate::utils::limits::ParseLimits;
+use crate::utils::managed_config::ManagedConfig;
+use crate::utils::timing::StatsRecorder;
+use crate::utils::userinfo::SubscriptionUserInfo;
 use crate::Settings;
 use case_insensitive_string::CaseInsensitiveString;
 
@@ -27,12 +33,48 @@ pub struct ParseSettings {
     /// Subscription information
     pub sub_info: Option<String>,
 
+    /// Traffic and expiry reported by the `subscription-userinfo` header of
+    /// the first subscription that sent one
+    pub userinfo: Option<SubscriptionUserInfo>,
+
+    /// `#!MANAGED-CONFIG` header of the first Surge input that has one
+    pub managed_config: Option<ManagedConfig>,
+
     /// Whether operations requiring authorization are allowed
     pub authorized: bool,
 
+    /// Whether a malformed proxy entry of a Clash subscription fails the
+    /// subscription instead of being skipped
+    pub strict: bool,
+
+    /// Whether the proxy groups of Clash inputs are collected
+    pub keep_groups: bool,
+
+    /// Whether nodes of a `tag:LABEL,url` input are named `[LABEL] name`
+    pub prefix_labels: bool,
+
+    /// Proxy groups found in Clash inputs when `keep_groups` is set, members
+    /// are named as in the input
+    pub proxy_groups: ProxyGroupConfigs,
+
     /// HTTP request headers
     pub request_header: Option<HashMap<CaseInsensitiveString, String>>,
 
+    /// Timeout, retry and redirect options for downloading subscriptions,
+    /// `proxy` and `request_header` take precedence over the ones set here
+    pub fetch_options: FetchOptions,
+
+    /// Size and node count limits, the download limit is applied to
+    /// `fetch_options` by default
+    pub limits: ParseLimits,
+
+    /// Fetch and parse times of the subscriptions added with these settings
+    pub stats: StatsRecorder,
+
+    /// Where nodes dropped by the remark filters are recorded, in explain
+    /// mode
+    pub explain: Option<ExplainRecorder>,
+
     /// JavaScript runtime - optional depending on feature flags
     #[cfg(feature = "js_runtime")]
     pub js_runtime: Option<()>, // Placeholder for actual JS runtime type
@@ -45,7 +87,8 @@ pub struct ParseSettings {
 impl Default for ParseSettings {
     fn default() -> Self {
         // Get global settings
-        let settings = Settings::current();
+        let settings = Settings::snapshot();
+        let limits = ParseLimits::from_settings(&settings);
 
         ParseSettings {
             proxy: parse_proxy(&settings.proxy_subscription),
@@ -62,8 +105,18 @@ impl Default for ParseSettings {
             stream_rules: None, // TODO: Get from global settings
             time_rules: None,   // TODO: Get from global settings
             sub_info: None,
+            userinfo: None,
+            managed_config: None,
             authorized: !settings.api_access_token.is_empty(),
+            strict: false,
+            keep_groups: false,
+            prefix_labels: false,
+            proxy_groups: Vec::new(),
             request_header: None,
+            fetch_options: FetchOptions::default().max_size(limits.max_download_size),
+            limits,
+            stats: StatsRecorder::default(),
+            explain: None,
             #[cfg(feature = "js_runtime")]
             js_runtime: None,
             #[cfg(feature = "js_runtime")]
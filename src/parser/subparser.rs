@@ -2,11 +2,17 @@ use crate::models::Proxy;
 use crate::parser::explodes::*;
 use crate::parser::infoparser::{get_sub_info_from_nodes, get_sub_info_from_ssd};
 use crate::parser::parse_settings::ParseSettings;
-use crate::utils::http::get_sub_info_from_header;
-use crate::utils::matcher::{apply_matcher, reg_find};
+use crate::parser::yaml::clash::{parse_clash_proxy_groups, validate_clash_entries};
+use crate::utils::explain::{DropStage, ExplainRecorder};
+use crate::utils::file::{read_stdin, resolve_within_base};
+use crate::utils::managed_config::ManagedConfig;
 use crate::utils::network::is_link;
+use crate::utils::node_manip::RemarkFilter;
+use crate::utils::timing::ConvertStage;
 use crate::utils::url::url_decode;
-use crate::utils::{file_exists, file_get_async, web_get_async};
+use crate::utils::userinfo::SubscriptionUserInfo;
+use crate::utils::{decoded_body_size, fetch_subscription, file_exists, file_get_async};
+use crate::Settings;
 use log::warn;
 
 /// Equivalent to ConfType enum in C++
@@ -17,6 +23,7 @@ pub enum ConfType {
     SUB,
     Netch,
     Local,
+    Stdin,
     Unknown,
 }
 
@@ -40,12 +47,27 @@ pub async fn add_nodes(
 ) -> Result<(), String> {
     // Extract references to settings for easier access
     let proxy = &parse_settings.proxy;
-    let exclude_remarks = parse_settings.exclude_remarks.as_ref();
-    let include_remarks = parse_settings.include_remarks.as_ref();
+    // Compiled before anything is fetched, so an invalid pattern is
+    // reported once
+    let remark_filter = RemarkFilter::new(
+        parse_settings
+            .include_remarks
+            .as_deref()
+            .unwrap_or_default(),
+        parse_settings
+            .exclude_remarks
+            .as_deref()
+            .unwrap_or_default(),
+    )?;
     let stream_rules = parse_settings.stream_rules.as_ref();
     let time_rules = parse_settings.time_rules.as_ref();
     let request_header = parse_settings.request_header.as_ref();
     let authorized = parse_settings.authorized;
+    let strict = parse_settings.strict;
+    let prefix_labels = parse_settings.prefix_labels;
+    let limits = parse_settings.limits;
+    let explain = parse_settings.explain.clone();
+    let explain = explain.as_ref();
 
     // Variables to store data during processing
     let mut nodes: Vec<Proxy> = Vec::new();
@@ -62,7 +84,7 @@ pub async fn add_nodes(
         return Err("Script processing not implemented".to_string());
     }
 
-    // Handle tag: prefix for custom group
+    // Handle tag: prefix labelling the input, the label becomes the group
     if link.starts_with("tag:") {
         if let Some(pos) = link.find(',') {
             custom_group = link[4..pos].to_string();
@@ -78,8 +100,21 @@ pub async fn add_nodes(
         return Ok(());
     }
 
+    // `-` reads standard input, `file://` names a local file explicitly
+    let explicit_file = match link.strip_prefix("file://") {
+        Some(path) => {
+            link = url_decode(path);
+            true
+        }
+        None => false,
+    };
+
     // Determine link type
-    let link_type = if link.starts_with("https://t.me/socks") || link.starts_with("tg://socks") {
+    let link_type = if link == "-" {
+        ConfType::Stdin
+    } else if explicit_file {
+        ConfType::Local
+    } else if link.starts_with("https://t.me/socks") || link.starts_with("tg://socks") {
         ConfType::SOCKS
     } else if link.starts_with("https://t.me/http") || link.starts_with("tg://http") {
         ConfType::HTTP
@@ -105,21 +140,40 @@ pub async fn add_nodes(
             }
 
             // Download subscription content
-            let response = match web_get_async(&link, proxy, request_header).await {
+            let mut fetch_options = parse_settings.fetch_options.clone();
+            fetch_options.proxy = proxy.clone();
+            if let Some(request_header) = request_header {
+                fetch_options.headers.extend(request_header.clone());
+            }
+            let fetch_timer = parse_settings.stats.time(ConvertStage::Fetch);
+            let response = match fetch_subscription(&link, &fetch_options).await {
                 Ok(response) => response,
                 Err(e) => {
                     warn!("Failed to get subscription content from {}: {}", link, e);
                     return Err(format!("HTTP request failed: {}", e));
                 }
             };
+            drop(fetch_timer);
+            let _parse_timer = parse_settings.stats.time(ConvertStage::Parse);
 
             let sub_content = response.body;
             let headers = response.headers;
 
             if !sub_content.is_empty() {
+                if strict {
+                    validate_clash_entries(&sub_content)
+                        .map_err(|e| format!("Invalid Clash proxy {}", e))?;
+                }
                 // Parse the subscription content
                 let result = explode_conf_content(&sub_content, &mut nodes);
+                limits
+                    .check_decoded_size(decoded_body_size(&sub_content))
+                    .map_err(|e| e.to_string())?;
                 if result > 0 {
+                    limits
+                        .check_node_count(nodes.len())
+                        .map_err(|e| e.to_string())?;
+
                     // Get subscription info
                     if sub_content.starts_with("ssd://") {
                         // Extract info from SSD subscription
@@ -127,10 +181,11 @@ pub async fn add_nodes(
                             parse_settings.sub_info = Some(info);
                         }
                     } else {
-                        // Try to get info from header first
-                        let header_info = get_sub_info_from_header(&headers);
-                        if !header_info.is_empty() {
-                            parse_settings.sub_info = Some(header_info);
+                        // Try to get info from header first, a missing or
+                        // unreadable header is not an error
+                        if let Some(userinfo) = SubscriptionUserInfo::from_headers(&headers) {
+                            parse_settings.sub_info = Some(userinfo.to_string());
+                            parse_settings.userinfo.get_or_insert(userinfo);
                         } else {
                             // If no header info, try from nodes
                             if let (Some(stream_rules_unwrapped), Some(time_rules_unwrapped)) =
@@ -148,14 +203,18 @@ pub async fn add_nodes(
                     }
 
                     // Filter nodes and set group info
-                    filter_nodes(&mut nodes, exclude_remarks, include_remarks, group_id);
+                    filter_nodes(&mut nodes, &remark_filter, group_id, explain);
 
-                    // Set group_id and custom_group for all nodes
-                    for node in &mut nodes {
-                        node.group_id = group_id;
-                        if !custom_group.is_empty() {
-                            node.group = custom_group.clone();
-                        }
+                    // Set group_id and the label for all nodes
+                    label_nodes(&mut nodes, group_id, &custom_group, prefix_labels);
+
+                    if parse_settings.keep_groups {
+                        parse_settings
+                            .proxy_groups
+                            .extend(parse_clash_proxy_groups(&sub_content));
+                    }
+                    if parse_settings.managed_config.is_none() {
+                        parse_settings.managed_config = ManagedConfig::parse(&sub_content);
                     }
 
                     // Add nodes to result vector
@@ -168,19 +227,34 @@ pub async fn add_nodes(
                 Err("Cannot download subscription data".to_string())
             }
         }
-        ConfType::Local => {
+        ConfType::Local | ConfType::Stdin => {
             if !authorized {
                 return Err("Not authorized to access local files".to_string());
             }
 
             // Read and parse local file
-            let result = explode_conf(&link, &mut nodes).await;
+            let fetch_timer = parse_settings.stats.time(ConvertStage::Fetch);
+            let content = read_local_input(&link, link_type == ConfType::Stdin).await?;
+            drop(fetch_timer);
+            let _parse_timer = parse_settings.stats.time(ConvertStage::Parse);
+            if strict {
+                validate_clash_entries(&content)
+                    .map_err(|e| format!("Invalid Clash proxy {}", e))?;
+            }
+            let result = explode_conf_content(&content, &mut nodes);
+            limits
+                .check_decoded_size(decoded_body_size(&content))
+                .map_err(|e| e.to_string())?;
             if result > 0 {
+                limits
+                    .check_node_count(nodes.len())
+                    .map_err(|e| e.to_string())?;
+
                 // The rest is similar to SUB case
                 // Get subscription info
-                if link.starts_with("ssd://") {
+                if content.starts_with("ssd://") {
                     // Extract info from SSD subscription
-                    if let Some(info) = get_sub_info_from_ssd(&link) {
+                    if let Some(info) = get_sub_info_from_ssd(&content) {
                         parse_settings.sub_info = Some(info);
                     }
                 } else {
@@ -198,14 +272,18 @@ pub async fn add_nodes(
                     }
                 }
 
-                filter_nodes(&mut nodes, exclude_remarks, include_remarks, group_id);
+                filter_nodes(&mut nodes, &remark_filter, group_id, explain);
 
-                // Set group_id and custom_group for all nodes
-                for node in &mut nodes {
-                    node.group_id = group_id;
-                    if !custom_group.is_empty() {
-                        node.group = custom_group.clone();
-                    }
+                // Set group_id and the label for all nodes
+                label_nodes(&mut nodes, group_id, &custom_group, prefix_labels);
+
+                if parse_settings.keep_groups {
+                    parse_settings
+                        .proxy_groups
+                        .extend(parse_clash_proxy_groups(&content));
+                }
+                if parse_settings.managed_config.is_none() {
+                    parse_settings.managed_config = ManagedConfig::parse(&content);
                 }
 
                 all_nodes.append(&mut nodes);
@@ -216,14 +294,19 @@ pub async fn add_nodes(
         }
         _ => {
             // Handle direct link to a single proxy
+            let _parse_timer = parse_settings.stats.time(ConvertStage::Parse);
             if explode(&link, &mut node) {
-                if node.proxy_type == crate::models::ProxyType::Unknown {
+                if node.proxy_type == crate::models::ProxyType::Unknown
+                    && node.custom_proxy().is_none()
+                {
                     return Err("No valid link found".to_string());
                 }
-                node.group_id = group_id;
-                if !custom_group.is_empty() {
-                    node.group = custom_group;
-                }
+                label_nodes(
+                    std::slice::from_mut(&mut node),
+                    group_id,
+                    &custom_group,
+                    prefix_labels,
+                );
                 all_nodes.push(node);
                 Ok(())
             } else {
@@ -247,28 +330,53 @@ fn get_url_arg(url: &str, arg_name: &str) -> Option<String> {
     None
 }
 
-/// Parses a configuration file into a vector of Proxy objects
-/// Returns the number of proxies parsed
-async fn explode_conf(path: &str, nodes: &mut Vec<Proxy>) -> i32 {
-    // TODO: 安全问题，但是旧版subconverter也有……
-    match file_get_async(path, None).await {
-        Ok(content) => explode_conf_content(&content, nodes),
-        Err(_) => 0,
+/// Reads a local file, or standard input for `-`
+///
+/// In API mode standard input is unavailable and files must resolve inside
+/// the configured base path.
+async fn read_local_input(path: &str, stdin: bool) -> Result<String, String> {
+    let (api_mode, base_path) = {
+        let global = Settings::snapshot();
+        (global.api_mode, global.base_path.clone())
+    };
+
+    if stdin {
+        if api_mode {
+            return Err("Standard input is not available in API mode".to_string());
+        }
+        return read_stdin()
+            .await
+            .map_err(|e| format!("Failed to read standard input: {}", e));
     }
+
+    let resolved = if api_mode {
+        resolve_within_base(path, &base_path)
+            .map_err(|e| format!("Cannot read '{}': {}", path, e))?
+    } else {
+        path.into()
+    };
+    file_get_async(&resolved, None)
+        .await
+        .map_err(|e| format!("Failed to read '{}': {}", path, e))
 }
 
 /// Filters nodes based on include/exclude rules
+///
+/// Ignored nodes are recorded in `explain` when given.
 fn filter_nodes(
     nodes: &mut Vec<Proxy>,
-    exclude_remarks: Option<&Vec<String>>,
-    include_remarks: Option<&Vec<String>>,
+    remark_filter: &RemarkFilter,
     group_id: i32,
+    explain: Option<&ExplainRecorder>,
 ) {
     let mut node_index = 0;
     let mut i = 0;
 
     while i < nodes.len() {
-        if should_ignore(&nodes[i], exclude_remarks, include_remarks) {
+        if let Some(reason) = remark_filter.rejection(&nodes[i]) {
+            if let Some(explain) = explain {
+                explain.dropped(&nodes[i], DropStage::RemarkFilter, reason);
+            }
             // Log that node is ignored
             println!(
                 "Node {} - {} has been ignored and will not be added.",
@@ -289,49 +397,20 @@ fn filter_nodes(
     }
 }
 
-/// Determines if a node should be ignored based on its remarks and the filtering rules
-fn should_ignore(
-    node: &Proxy,
-    exclude_remarks: Option<&Vec<String>>,
-    include_remarks: Option<&Vec<String>>,
-) -> bool {
-    let mut excluded = false;
-    let mut included = true; // Default to true if no include rules
-
-    // Check exclude rules
-    if let Some(excludes) = exclude_remarks {
-        excluded = excludes.iter().any(|pattern| {
-            let mut real_rule = String::new();
-            if apply_matcher(pattern, &mut real_rule, node) {
-                if !real_rule.is_empty() {
-                    reg_find(&node.remark, &real_rule)
-                } else {
-                    pattern == &node.remark
-                }
-            } else {
-                false
-            }
-        });
-    }
-
-    // Check include rules if they exist
-    if let Some(includes) = include_remarks {
-        if !includes.is_empty() {
-            included = includes.iter().any(|pattern| {
-                let mut real_rule = String::new();
-                if apply_matcher(pattern, &mut real_rule, node) {
-                    if !real_rule.is_empty() {
-                        reg_find(&node.remark, &real_rule)
-                    } else {
-                        pattern == &node.remark
-                    }
-                } else {
-                    false
-                }
-            });
+/// Set the input of nodes and tag them with its label
+///
+/// A non-empty label becomes the group of the nodes and, with `prefix`, the
+/// start of their names as `[label] name`. Rename rules run later and see the
+/// prefixed names.
+fn label_nodes(nodes: &mut [Proxy], group_id: i32, label: &str, prefix: bool) {
+    for node in nodes {
+        node.group_id = group_id;
+        if label.is_empty() {
+            continue;
+        }
+        node.group = label.to_string();
+        if prefix {
+            node.remark = format!("[{}] {}", label, node.remark);
         }
     }
-
-    // A node is ignored if it's excluded OR not included
-    excluded || !included
 }
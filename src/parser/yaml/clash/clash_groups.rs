@@ -0,0 +1,150 @@
+//! Proxy groups of Clash inputs
+//!
+//! Groups keep the member names of the input, they are resolved against the
+//! final node list when the output is generated, see
+//! [`crate::interfaces::subconverter::resolve_input_groups`]. The
+//! `proxy-providers` of the input are not carried into the output, so the
+//! `use` lists naming them are dropped.
+
+use log::warn;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::models::{BalanceStrategy, ProxyGroupConfig, ProxyGroupConfigs, ProxyGroupType};
+
+/// Policies built into Clash that group members may name
+pub const CLASH_BUILTIN_POLICIES: &[&str] = &[
+    "DIRECT",
+    "REJECT",
+    "REJECT-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+
+/// A proxy group entry of a Clash config
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ClashInputGroup {
+    name: String,
+    #[serde(rename = "type")]
+    group_type: String,
+    #[serde(default)]
+    proxies: Vec<String>,
+    /// Providers of the input, dropped along with them
+    #[serde(default, rename = "use")]
+    using_provider: Vec<String>,
+    filter: Option<String>,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    interval: u32,
+    #[serde(default)]
+    tolerance: u32,
+    strategy: Option<String>,
+    lazy: Option<bool>,
+    #[serde(default)]
+    disable_udp: bool,
+    icon: Option<String>,
+    #[serde(default)]
+    hidden: bool,
+}
+
+impl From<ClashInputGroup> for ProxyGroupConfig {
+    fn from(group: ClashInputGroup) -> Self {
+        // `ssid` is a Surge group, not one Clash loads
+        let group_type = match group.group_type.parse() {
+            Ok(ProxyGroupType::SSID) | Err(_) => ProxyGroupType::Select,
+            Ok(group_type) => group_type,
+        };
+        let strategy = match group.strategy.as_deref() {
+            Some("round-robin") => BalanceStrategy::RoundRobin,
+            _ => BalanceStrategy::ConsistentHashing,
+        };
+        if !group.using_provider.is_empty() {
+            warn!(
+                "Dropping providers {:?} of Clash proxy group {}",
+                group.using_provider, group.name
+            );
+        }
+        ProxyGroupConfig {
+            name: group.name,
+            group_type,
+            proxies: group.proxies,
+            filter: group.filter.filter(|f| !f.is_empty()),
+            url: group.url,
+            interval: group.interval,
+            tolerance: group.tolerance,
+            strategy,
+            lazy: group.lazy,
+            disable_udp: group.disable_udp,
+            icon: group.icon.filter(|icon| !icon.is_empty()),
+            hidden: group.hidden,
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse the `proxy-groups` (or legacy `Proxy Group`) section of a Clash
+/// config
+///
+/// `proxies` of the returned groups hold the member names as written in the
+/// input. Entries that are not valid groups are skipped with a warning, and
+/// content that is not a Clash config yields no groups.
+pub fn parse_clash_proxy_groups(content: &str) -> ProxyGroupConfigs {
+    let Ok(yaml) = serde_yaml::from_str::<Value>(content) else {
+        return Vec::new();
+    };
+    let Some(Value::Sequence(entries)) =
+        yaml.get("proxy-groups").or_else(|| yaml.get("Proxy Group"))
+    else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            match serde_yaml::from_value::<ClashInputGroup>(entry.clone()) {
+                Ok(group) => Some(group.into()),
+                Err(e) => {
+                    warn!("Skipping Clash proxy group {}: {}", index, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clash_proxy_groups() {
+        let content = r#"
+proxies:
+  - {name: HK, type: socks5, server: 1.1.1.1, port: 1080}
+proxy-groups:
+  - name: Auto
+    type: url-test
+    proxies: [HK, DIRECT]
+    url: http://www.gstatic.com/generate_204
+    interval: 300
+  - name: Proxy
+    type: select
+    proxies: [Auto]
+    use: [provider]
+  - name: Broken
+"#;
+        let groups = parse_clash_proxy_groups(content);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "Auto");
+        assert_eq!(groups[0].group_type, ProxyGroupType::URLTest);
+        assert_eq!(groups[0].proxies, vec!["HK", "DIRECT"]);
+        assert_eq!(groups[0].interval, 300);
+        assert!(groups[1].using_provider.is_empty());
+
+        assert!(parse_clash_proxy_groups("ss://not-yaml").is_empty());
+    }
+}
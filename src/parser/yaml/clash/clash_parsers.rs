@@ -1,7 +1,56 @@
-use crate::models::Proxy;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::models::proxy_node::multiplex::Multiplex;
+use crate::models::{validate_ss2022_psk, IpVersion, Proxy};
 use crate::parser::yaml::clash::clash_proxy_types::ClashProxyYamlInput;
 
-use super::ClashYamlInput;
+/// A proxy entry of a Clash configuration that could not be converted
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct ClashEntryError {
+    /// Position of the entry in `proxies`
+    pub index: usize,
+    /// Name of the entry, if it has one
+    pub name: Option<String>,
+    /// 1-based line of the entry in the document, when it can be located
+    pub line: Option<usize>,
+    /// What is wrong with the entry
+    pub message: String,
+}
+
+impl fmt::Display for ClashEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "proxies[{}]", self.index)?;
+        if let Some(name) = &self.name {
+            write!(f, " '{}'", name)?;
+        }
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Error of [`parse_clash_yaml_entries`]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ClashParseError {
+    /// The content is not a YAML document with a `proxies` list
+    #[error("Failed to parse Clash YAML: {0}")]
+    Document(String),
+    /// A proxy entry is malformed and fail-fast parsing was requested
+    #[error("Invalid Clash proxy {0}")]
+    Entry(ClashEntryError),
+}
+
+/// Proxies of a Clash configuration along with the entries that were rejected
+#[derive(Debug, Clone, Default)]
+pub struct ClashParseReport {
+    /// Parsed proxies, with the position of their entry in `proxies`
+    pub proxies: Vec<(usize, Proxy)>,
+    /// Entries that could not be converted, in document order
+    pub errors: Vec<ClashEntryError>,
+}
 
 /// Parse Clash configuration from YAML string
 ///
@@ -11,57 +60,257 @@ use super::ClashYamlInput;
 /// 2. Proper error handling with Result type
 /// 3. Automatic deserialization using serde
 /// 4. Cleaner pattern matching compared to C++ if/else chains
+///
+/// Malformed entries are logged and skipped, see [`parse_clash_yaml_entries`]
+/// to get them back.
 pub fn parse_clash_yaml(content: &str) -> Result<Vec<Proxy>, String> {
-    let clash_input: ClashYamlInput = match serde_yaml::from_str(content) {
-        Ok(input) => input,
-        Err(e) => return Err(format!("Failed to parse Clash YAML: {}", e)),
-    };
+    let report = parse_clash_yaml_entries(content, false).map_err(|e| e.to_string())?;
+    for error in &report.errors {
+        log::warn!("Skipping Clash proxy {}", error);
+    }
+    Ok(report.proxies.into_iter().map(|(_, proxy)| proxy).collect())
+}
 
-    let mut proxies = Vec::new();
+/// Parse the proxies of a Clash configuration one entry at a time
+///
+/// Every entry of `proxies` is deserialized into its typed form on its own,
+/// so a malformed entry does not take the rest of the document down with it.
+/// Entries of unknown types are ignored without an error.
+///
+/// # Arguments
+/// * `content` - Clash configuration
+/// * `fail_fast` - Return the first malformed entry as an error instead of
+///   collecting it in the report
+pub fn parse_clash_yaml_entries(
+    content: &str,
+    fail_fast: bool,
+) -> Result<ClashParseReport, ClashParseError> {
+    let yaml: serde_yaml::Value =
+        serde_yaml::from_str(content).map_err(|e| ClashParseError::Document(e.to_string()))?;
+    let entries = match yaml.get("proxies") {
+        Some(serde_yaml::Value::Sequence(entries)) => entries.as_slice(),
+        Some(serde_yaml::Value::Null) | None => &[],
+        Some(_) => {
+            return Err(ClashParseError::Document(
+                "`proxies` is not a list".to_string(),
+            ))
+        }
+    };
 
-    for proxy in clash_input.extract_proxies() {
-        match proxy {
-            ClashProxyYamlInput::Shadowsocks(ss) => {
-                proxies.push(ss.into());
-            }
-            ClashProxyYamlInput::ShadowsocksR(ssr) => {
-                proxies.push(ssr.into());
-            }
-            ClashProxyYamlInput::VMess(vmess) => {
-                proxies.push(vmess.into());
-            }
-            ClashProxyYamlInput::Trojan(trojan) => {
-                proxies.push(trojan.into());
-            }
-            ClashProxyYamlInput::Http(http) => {
-                proxies.push(http.into());
+    let mut report = ClashParseReport::default();
+    for (index, entry) in entries.iter().enumerate() {
+        let converted = convert_entry(entry).and_then(|proxy| {
+            let ip_version = ip_version_of(entry)?;
+            let multiplex = smux_of(entry)?;
+            Ok(proxy.map(|proxy| Proxy {
+                ip_version,
+                multiplex,
+                ..proxy
+            }))
+        });
+        match converted {
+            Ok(Some(mut proxy)) => {
+                // `dialer-proxy` applies to every proxy type, so read it from the raw entry
+                if let Some(dialer) = dialer_proxy_of(entry) {
+                    proxy.underlying_proxy = Some(dialer);
+                }
+                report.proxies.push((index, proxy));
             }
-            ClashProxyYamlInput::Socks5(socks5) => {
-                proxies.push(socks5.into());
+            Ok(None) => {}
+            Err(message) => {
+                let name = entry
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                let error = ClashEntryError {
+                    index,
+                    line: name
+                        .as_deref()
+                        .and_then(|name| find_entry_line(content, name)),
+                    name,
+                    message,
+                };
+                if fail_fast {
+                    return Err(ClashParseError::Entry(error));
+                }
+                report.errors.push(error);
             }
-            ClashProxyYamlInput::Snell(snell) => {
-                proxies.push(snell.into());
-            }
-            ClashProxyYamlInput::WireGuard(wg) => {
-                proxies.push(wg.into());
-            }
-            ClashProxyYamlInput::Hysteria(hysteria) => {
-                proxies.push(hysteria.into());
-            }
-            ClashProxyYamlInput::Hysteria2(hysteria2) => {
-                proxies.push(hysteria2.into());
-            }
-            ClashProxyYamlInput::VLess(vless) => {
-                proxies.push(vless.into());
-            }
-            ClashProxyYamlInput::AnyTls(anytls) => {
-                proxies.push(anytls.into());
-            }
-            ClashProxyYamlInput::Unknown => {
-                // Skip unknown proxy types
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check that every proxy entry of a Clash configuration can be converted
+///
+/// Content that is not a Clash configuration passes, it is left to the other
+/// subscription parsers.
+pub fn validate_clash_entries(content: &str) -> Result<(), ClashEntryError> {
+    match parse_clash_yaml_entries(content, true) {
+        Err(ClashParseError::Entry(error)) => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Convert a single raw proxy entry, `None` for unknown proxy types
+fn convert_entry(entry: &serde_yaml::Value) -> Result<Option<Proxy>, String> {
+    let proxy: ClashProxyYamlInput =
+        serde_yaml::from_value(entry.clone()).map_err(|e| e.to_string())?;
+    let proxy = match proxy {
+        ClashProxyYamlInput::Shadowsocks(ss) => {
+            validate_ss2022_psk(ss.cipher(), ss.password()).map_err(|e| e.to_string())?;
+            ss.into()
+        }
+        ClashProxyYamlInput::ShadowsocksR(ssr) => ssr.into(),
+        ClashProxyYamlInput::VMess(vmess) => vmess.into(),
+        ClashProxyYamlInput::Trojan(trojan) => trojan.into(),
+        ClashProxyYamlInput::Http(http) => http.into(),
+        ClashProxyYamlInput::Socks5(socks5) => socks5.into(),
+        ClashProxyYamlInput::Snell(snell) => snell.into(),
+        ClashProxyYamlInput::WireGuard(wg) => {
+            if wg.public_key().is_none() {
+                return Err("missing public-key".to_string());
             }
+            wg.into()
         }
+        ClashProxyYamlInput::Hysteria(hysteria) => hysteria.into(),
+        ClashProxyYamlInput::Hysteria2(hysteria2) => hysteria2.into(),
+        ClashProxyYamlInput::VLess(vless) => vless.into(),
+        ClashProxyYamlInput::AnyTls(anytls) => anytls.into(),
+        ClashProxyYamlInput::Tuic(tuic) => tuic.into(),
+        // Skip unknown proxy types
+        ClashProxyYamlInput::Unknown => return Ok(None),
+    };
+    Ok(Some(proxy))
+}
+
+/// Line of the entry named `name`, found by looking for its `name:` key
+///
+/// Parsed YAML values carry no positions, so this works for both block and
+/// flow style entries as long as the name is unique in the document.
+fn find_entry_line(content: &str, name: &str) -> Option<usize> {
+    content
+        .lines()
+        .position(|line| {
+            line.match_indices("name:").any(|(pos, _)| {
+                let value = line[pos + "name:".len()..]
+                    .split([',', '}'])
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .trim_matches(|c| c == '"' || c == '\'');
+                value == name
+            })
+        })
+        .map(|line| line + 1)
+}
+
+/// Upstream of a raw proxy entry, from `dialer-proxy` or Surge style
+/// `underlying-proxy`
+pub(crate) fn dialer_proxy_of(entry: &serde_yaml::Value) -> Option<String> {
+    ["dialer-proxy", "underlying-proxy"]
+        .iter()
+        .find_map(|key| entry.get(*key).and_then(|v| v.as_str()))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// `ip-version` of a raw proxy entry, which every proxy type may carry
+pub(crate) fn ip_version_of(entry: &serde_yaml::Value) -> Result<Option<IpVersion>, String> {
+    entry
+        .get("ip-version")
+        .and_then(|v| v.as_str())
+        .map(str::parse)
+        .transpose()
+}
+
+/// Multiplexing of a raw proxy entry from its Clash.Meta `smux` mapping
+pub(crate) fn smux_of(entry: &serde_yaml::Value) -> Result<Option<Multiplex>, String> {
+    entry
+        .get("smux")
+        .map(Multiplex::from_clash_smux)
+        .transpose()
+        .map(Option::flatten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+proxies:
+  - name: good
+    type: trojan
+    server: a.example.com
+    port: 443
+    password: secret
+  - name: bad
+    type: trojan
+    server: b.example.com
+    port: not-a-port
+    password: secret
+  - { name: flow, type: socks5, server: c.example.com, port: 1080 }
+  - name: future
+    type: some-new-protocol
+    server: d.example.com
+"#;
+
+    #[test]
+    fn test_parse_clash_yaml_entries() {
+        let report = parse_clash_yaml_entries(YAML, false).unwrap();
+        let names: Vec<_> = report
+            .proxies
+            .iter()
+            .map(|(index, proxy)| (*index, proxy.remark.as_str()))
+            .collect();
+        assert_eq!(names, vec![(0, "good"), (2, "flow")]);
+
+        assert_eq!(report.errors.len(), 1);
+        let error = &report.errors[0];
+        assert_eq!(error.index, 1);
+        assert_eq!(error.name.as_deref(), Some("bad"));
+        assert_eq!(error.line, Some(8));
+        assert!(error.to_string().starts_with("proxies[1] 'bad' (line 8): "));
+
+        // The lenient entry point keeps the good entries
+        assert_eq!(parse_clash_yaml(YAML).unwrap().len(), 2);
     }
 
-    Ok(proxies)
+    #[test]
+    fn test_parse_clash_yaml_entries_fail_fast() {
+        let err = parse_clash_yaml_entries(YAML, true).unwrap_err();
+        assert!(matches!(
+            err,
+            ClashParseError::Entry(ClashEntryError { index: 1, .. })
+        ));
+        assert!(validate_clash_entries(YAML).is_err());
+
+        // Other subscription formats are not rejected
+        assert!(validate_clash_entries("trojan://secret@a.example.com:443#A").is_ok());
+        assert!(matches!(
+            parse_clash_yaml_entries("proxies: 1", true),
+            Err(ClashParseError::Document(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_clash_ip_version() {
+        let yaml = r#"
+proxies:
+  - { name: v6, type: trojan, server: a.example.com, port: 443, password: pw, ip-version: ipv6 }
+  - { name: typo, type: trojan, server: b.example.com, port: 443, password: pw, ip-version: v6 }
+"#;
+        let report = parse_clash_yaml_entries(yaml, false).unwrap();
+        assert_eq!(report.proxies.len(), 1);
+        assert_eq!(report.proxies[0].1.ip_version, Some(IpVersion::Ipv6));
+        assert_eq!(report.errors[0].name.as_deref(), Some("typo"));
+        assert!(report.errors[0]
+            .message
+            .contains("expected one of dual, ipv4"));
+
+        // The lenient fallback does not take the entry either
+        let mut nodes = Vec::new();
+        assert!(crate::parser::explodes::explode_clash(yaml, &mut nodes));
+        assert_eq!(nodes.len(), 1);
+    }
 }
@@ -4,6 +4,7 @@ use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
 use crate::models::proxy_node::anytls::AnyTlsProxy;
 use crate::models::proxy_node::combined::CombinedProxy;
+use crate::utils::deserialize::deserialize_alpn;
 
 /// Represents an AnyTLS proxy in Clash configuration (mihomo extension)
 #[derive(Debug, Clone, Deserialize)]
@@ -13,8 +14,8 @@ pub struct ClashInputAnyTLS {
     server: String,
     port: u16,
     password: String,
-    #[serde(default)]
-    alpn: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
     #[serde(default)]
     sni: Option<String>,
     #[serde(default)]
@@ -42,7 +43,7 @@ impl Into<Proxy> for ClashInputAnyTLS {
 
         let mut anytls_proxy = AnyTlsProxy::default();
         anytls_proxy.password = self.password;
-        anytls_proxy.alpn = self.alpn.map(|v| v.into_iter().collect()); // Convert Vec to HashSet if needed later, or keep as Vec
+        anytls_proxy.alpn = (!self.alpn.is_empty()).then_some(self.alpn);
         anytls_proxy.sni = self.sni;
         anytls_proxy.skip_cert_verify = self.skip_cert_verify;
         anytls_proxy.fingerprint = self.fingerprint;
@@ -1,8 +1,8 @@
-
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::utils::deserialize::deserialize_string_or_number;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents an HTTP/HTTPS proxy in Clash configuration
@@ -12,12 +12,14 @@ pub struct ClashInputHttp {
     name: String,
     server: String,
     port: u16,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_string_or_number")]
     username: Option<String>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_string_or_number")]
     password: Option<String>,
     #[serde(default)]
     tls: Option<bool>,
+    #[serde(default)]
+    sni: Option<String>,
     #[serde(alias = "skip-cert-verify", default)]
     skip_cert_verify: Option<bool>,
 }
@@ -65,6 +67,8 @@ impl Into<Proxy> for ClashInputHttp {
         proxy.port = self.port;
         proxy.username = self.username;
         proxy.password = self.password;
+        proxy.tls_secure = self.tls.unwrap_or(false);
+        proxy.sni = self.sni.filter(|sni| !sni.is_empty());
         proxy.allow_insecure.set_if_some(self.skip_cert_verify);
 
         proxy
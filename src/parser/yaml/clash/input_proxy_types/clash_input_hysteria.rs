@@ -2,6 +2,10 @@ use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::models::proxy_node::hysteria::{
+    parse_bandwidth, HysteriaProtocol, HYSTERIA_DEFAULT_DOWN_MBPS, HYSTERIA_DEFAULT_UP_MBPS,
+};
+use crate::utils::deserialize::deserialize_alpn;
 use crate::utils::deserialize::deserialize_string_or_number;
 use crate::utils::tribool::OptionSetExt;
 
@@ -36,8 +40,8 @@ pub struct ClashInputHysteria {
     sni: Option<String>,
     #[serde(default)]
     fingerprint: Option<String>,
-    #[serde(default)]
-    alpn: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
     #[serde(default)]
     ca: Option<String>,
     #[serde(alias = "ca-str", default)]
@@ -119,8 +123,8 @@ impl ClashInputHysteria {
         self.fingerprint.as_deref()
     }
 
-    pub fn alpn(&self) -> Option<&Vec<String>> {
-        self.alpn.as_ref()
+    pub fn alpn(&self) -> &[String] {
+        &self.alpn
     }
 
     pub fn ca(&self) -> Option<&str> {
@@ -168,39 +172,47 @@ impl Into<Proxy> for ClashInputHysteria {
         proxy.hostname = self.server;
         proxy.port = self.port;
         proxy.ports = self.ports;
-        proxy.protocol = self.protocol;
-        proxy.obfs = self.obfs_protocol;
-
-        // Handle upload/download speed
-        if let Some(up_value) = self.up {
-            proxy.up_speed = up_value.replace("Mbps", "").parse().unwrap_or(0);
-        } else if let Some(up_speed) = self.up_speed {
-            proxy.up_speed = up_speed;
-        }
-
-        if let Some(down_value) = self.down {
-            proxy.down_speed = down_value.replace("Mbps", "").parse().unwrap_or(0);
-        } else if let Some(down_speed) = self.down_speed {
-            proxy.down_speed = down_speed;
-        }
+        proxy.protocol = Some(
+            HysteriaProtocol::parse_lossy(
+                self.protocol
+                    .as_deref()
+                    .or(self.obfs_protocol.as_deref())
+                    .unwrap_or_default(),
+            )
+            .to_string(),
+        );
+
+        // Handle upload/download speed, unset or unreadable ones default
+        proxy.up_speed = self
+            .up
+            .as_deref()
+            .and_then(parse_bandwidth)
+            .or(self.up_speed)
+            .filter(|speed| *speed > 0)
+            .unwrap_or(HYSTERIA_DEFAULT_UP_MBPS);
+        proxy.down_speed = self
+            .down
+            .as_deref()
+            .and_then(parse_bandwidth)
+            .or(self.down_speed)
+            .filter(|speed| *speed > 0)
+            .unwrap_or(HYSTERIA_DEFAULT_DOWN_MBPS);
 
         // Set authentication
         proxy.auth = self.auth;
         proxy.auth_str = self.auth_str;
 
-        // Set obfuscation
-        proxy.obfs = self.obfs;
+        // Set obfuscation, `obfs` is the xplus password
+        if self.obfs.as_deref().is_some_and(|obfs| !obfs.is_empty()) {
+            proxy.obfs = Some("xplus".to_string());
+            proxy.obfs_param = self.obfs;
+        }
 
         // Set TLS related fields
         proxy.sni = self.sni;
         proxy.fingerprint = self.fingerprint;
 
-        // Handle alpn as a HashSet
-        if let Some(alpn_values) = self.alpn {
-            for value in alpn_values {
-                proxy.alpn.insert(value);
-            }
-        }
+        proxy.alpn = self.alpn;
 
         proxy.ca = self.ca;
         proxy.ca_str = self.ca_str;
@@ -1,9 +1,9 @@
-use std::collections::HashSet;
-
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::models::proxy_node::hysteria::parse_bandwidth;
+use crate::utils::deserialize::deserialize_alpn;
 use crate::utils::deserialize::deserialize_string_or_number;
 use crate::utils::tribool::OptionSetExt;
 
@@ -29,8 +29,8 @@ pub struct ClashInputHysteria2 {
     obfs_password: Option<String>,
     #[serde(default)]
     fingerprint: Option<String>,
-    #[serde(default)]
-    alpn: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
     #[serde(default)]
     ca: Option<String>,
     #[serde(alias = "ca-str", default)]
@@ -94,8 +94,8 @@ impl ClashInputHysteria2 {
         self.fingerprint.as_deref()
     }
 
-    pub fn alpn(&self) -> Option<&str> {
-        self.alpn.as_deref()
+    pub fn alpn(&self) -> &[String] {
+        &self.alpn
     }
 
     pub fn ca(&self) -> Option<&str> {
@@ -144,11 +144,11 @@ impl Into<Proxy> for ClashInputHysteria2 {
 
         // Handle upload/download speed
         if let Some(up_value) = self.up {
-            proxy.up_speed = up_value.replace("Mbps", "").parse().unwrap_or(0);
+            proxy.up_speed = parse_bandwidth(&up_value).unwrap_or(0);
         }
 
         if let Some(down_value) = self.down {
-            proxy.down_speed = down_value.replace("Mbps", "").parse().unwrap_or(0);
+            proxy.down_speed = parse_bandwidth(&down_value).unwrap_or(0);
         }
 
         // Set obfuscation options
@@ -158,16 +158,7 @@ impl Into<Proxy> for ClashInputHysteria2 {
         // Set TLS related fields
         proxy.fingerprint = self.fingerprint;
 
-        // Handle alpn as a comma-separated string to HashSet
-        if let Some(alpn_value) = self.alpn {
-            let mut alpn_set = HashSet::new();
-            for value in alpn_value.split(',').map(|s| s.trim().to_string()) {
-                if !value.is_empty() {
-                    alpn_set.insert(value);
-                }
-            }
-            proxy.alpn = alpn_set;
-        }
+        proxy.alpn = self.alpn;
 
         proxy.ca = self.ca;
         proxy.ca_str = self.ca_str;
@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 
 use serde::Deserialize;
+use serde_yaml::Value;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::shadowsocks::ShadowsocksProxy;
+use crate::models::proxy_node::shadowtls::{ShadowTlsOptions, SHADOW_TLS_PLUGIN};
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a Shadowsocks proxy in Clash configuration
@@ -24,7 +28,12 @@ pub struct ClashInputShadowsocks {
     #[serde(default)]
     plugin: Option<String>,
     #[serde(alias = "plugin-opts", default)]
-    plugin_opts: Option<HashMap<String, String>>,
+    plugin_opts: Option<HashMap<String, Value>>,
+    /// A flag, or a mapping with `enabled` and `version`
+    #[serde(default)]
+    udp_over_tcp: Option<Value>,
+    #[serde(default)]
+    udp_over_tcp_version: Option<Value>,
 }
 
 impl ClashInputShadowsocks {
@@ -64,7 +73,7 @@ impl ClashInputShadowsocks {
         self.plugin.as_deref()
     }
 
-    pub fn plugin_opts(&self) -> Option<&HashMap<String, String>> {
+    pub fn plugin_opts(&self) -> Option<&HashMap<String, Value>> {
         self.plugin_opts.as_ref()
     }
 }
@@ -87,6 +96,12 @@ impl Into<Proxy> for ClashInputShadowsocks {
             if let Some(opts) = self.plugin_opts {
                 let mut plugin_opts_str = String::new();
                 for (key, value) in opts {
+                    let value = match value {
+                        Value::String(s) => s,
+                        Value::Bool(b) => b.to_string(),
+                        Value::Number(n) => n.to_string(),
+                        _ => continue,
+                    };
                     if !plugin_opts_str.is_empty() {
                         plugin_opts_str.push(';');
                     }
@@ -96,6 +111,46 @@ impl Into<Proxy> for ClashInputShadowsocks {
             }
         }
 
+        if let Some(plugin) = proxy.plugin.as_deref() {
+            if ShadowTlsOptions::is_shadow_tls_plugin(plugin) {
+                let shadow_tls = proxy
+                    .plugin_option
+                    .as_deref()
+                    .and_then(ShadowTlsOptions::from_plugin_opts);
+                match shadow_tls {
+                    Some(shadow_tls) => {
+                        proxy.plugin = Some(SHADOW_TLS_PLUGIN.to_string());
+                        proxy.plugin_option = Some(shadow_tls.to_plugin_opts());
+                    }
+                    None => {
+                        log::warn!("Ignoring shadow-tls plugin without a valid host or version");
+                        proxy.plugin = None;
+                        proxy.plugin_option = None;
+                    }
+                }
+            }
+        }
+
+        if self.udp_over_tcp.is_some() {
+            let mut ss = ShadowsocksProxy {
+                server: proxy.hostname.clone(),
+                port: proxy.port,
+                password: proxy.password.clone().unwrap_or_default(),
+                cipher: proxy.encrypt_method.clone().unwrap_or_default(),
+                udp: proxy.udp,
+                tfo: proxy.tcp_fast_open,
+                skip_cert_verify: proxy.allow_insecure,
+                plugin: proxy.plugin.clone(),
+                plugin_opts: proxy.plugin_option.clone(),
+                ..Default::default()
+            };
+            ss.set_udp_over_tcp(
+                self.udp_over_tcp.as_ref(),
+                self.udp_over_tcp_version.as_ref(),
+            );
+            proxy.combined_proxy = Some(CombinedProxy::Shadowsocks(ss));
+        }
+
         proxy
     }
 }
@@ -1,8 +1,8 @@
-
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::utils::deserialize::deserialize_string_or_number;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a SOCKS5 proxy in Clash configuration
@@ -12,9 +12,9 @@ pub struct ClashInputSocks5 {
     name: String,
     server: String,
     port: u16,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_string_or_number")]
     username: Option<String>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_string_or_number")]
     password: Option<String>,
     #[serde(alias = "skip-cert-verify", default)]
     skip_cert_verify: Option<bool>,
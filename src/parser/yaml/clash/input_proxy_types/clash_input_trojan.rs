@@ -1,8 +1,9 @@
-
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::models::proxy_node::utls::parse_client_fingerprint;
+use crate::utils::deserialize::deserialize_alpn;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a Trojan proxy in Clash configuration
@@ -23,6 +24,10 @@ pub struct ClashInputTrojan {
     network: Option<String>,
     #[serde(default)]
     sni: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
+    #[serde(default)]
+    client_fingerprint: Option<String>,
 }
 
 impl ClashInputTrojan {
@@ -75,6 +80,11 @@ impl Into<Proxy> for ClashInputTrojan {
         proxy.tcp_fast_open.set_if_some(self.tfo);
         proxy.allow_insecure.set_if_some(self.skip_cert_verify);
         proxy.sni = self.sni;
+        proxy.alpn = self.alpn;
+        proxy.client_fingerprint = self
+            .client_fingerprint
+            .as_deref()
+            .and_then(parse_client_fingerprint);
 
         if let Some(net) = self.network {
             proxy.transfer_protocol = Some(net);
@@ -0,0 +1,75 @@
+use serde::Deserialize;
+
+use crate::models::proxy::Proxy;
+use crate::models::proxy::ProxyType;
+use crate::models::proxy_node::combined::CombinedProxy;
+use crate::models::proxy_node::tuic::TuicProxy;
+use crate::models::TUIC_DEFAULT_GROUP;
+use crate::utils::deserialize::deserialize_alpn;
+
+/// Represents a TUIC v5 proxy in Clash configuration (mihomo extension)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClashInputTuic {
+    name: String,
+    server: String,
+    port: u16,
+    #[serde(default)]
+    uuid: String,
+    #[serde(default)]
+    password: String,
+    #[serde(alias = "congestion-controller", default)]
+    congestion_controller: Option<String>,
+    #[serde(alias = "udp-relay-mode", default)]
+    udp_relay_mode: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
+    #[serde(default)]
+    sni: Option<String>,
+    #[serde(alias = "disable-sni", default)]
+    disable_sni: Option<bool>,
+    #[serde(alias = "reduce-rtt", default)]
+    reduce_rtt: Option<bool>,
+    #[serde(alias = "heartbeat-interval", default)]
+    heartbeat_interval: Option<u32>,
+    #[serde(alias = "skip-cert-verify", default)]
+    skip_cert_verify: Option<bool>,
+    #[serde(default)]
+    udp: Option<bool>,
+    #[serde(default)]
+    tfo: Option<bool>,
+}
+
+impl From<ClashInputTuic> for Proxy {
+    fn from(input: ClashInputTuic) -> Self {
+        let tuic_proxy = TuicProxy {
+            uuid: input.uuid,
+            password: input.password,
+            congestion_control: input.congestion_controller,
+            udp_relay_mode: input.udp_relay_mode,
+            alpn: (!input.alpn.is_empty()).then(|| input.alpn.clone()),
+            sni: input.sni.clone(),
+            disable_sni: input.disable_sni,
+            reduce_rtt: input.reduce_rtt,
+            heartbeat_interval: input.heartbeat_interval,
+            skip_cert_verify: input.skip_cert_verify,
+            udp: input.udp,
+            tfo: input.tfo,
+        };
+
+        Proxy {
+            proxy_type: ProxyType::Tuic,
+            combined_proxy: Some(CombinedProxy::Tuic(tuic_proxy)),
+            group: TUIC_DEFAULT_GROUP.to_string(),
+            remark: input.name,
+            hostname: input.server,
+            port: input.port,
+            sni: input.sni,
+            alpn: input.alpn,
+            tcp_fast_open: input.tfo,
+            udp: input.udp,
+            allow_insecure: input.skip_cert_verify,
+            ..Default::default()
+        }
+    }
+}
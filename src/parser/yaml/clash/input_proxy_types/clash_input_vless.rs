@@ -6,6 +6,7 @@ use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
 use crate::models::proxy_node::combined::CombinedProxy;
 use crate::models::proxy_node::vless::VlessProxy;
+use crate::utils::deserialize::deserialize_alpn;
 
 /// Represents a VLESS proxy in Clash configuration
 #[derive(Debug, Clone, Deserialize)]
@@ -19,8 +20,8 @@ pub struct ClashInputVLess {
     flow: Option<String>,
     #[serde(default)]
     tls: Option<bool>,
-    #[serde(default)]
-    alpn: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
     #[serde(default)]
     udp: Option<bool>,
     #[serde(alias = "packet-addr", default)]
@@ -80,8 +81,8 @@ impl ClashInputVLess {
         self.tls
     }
 
-    pub fn alpn(&self) -> Option<&Vec<String>> {
-        self.alpn.as_ref()
+    pub fn alpn(&self) -> &[String] {
+        &self.alpn
     }
 
     pub fn udp(&self) -> Option<bool> {
@@ -155,7 +156,7 @@ impl ClashInputVLess {
 pub struct RealityOptions {
     #[serde(rename = "public-key")]
     pub public_key: String,
-    #[serde(rename = "short-id")]
+    #[serde(rename = "short-id", default)]
     pub short_id: String,
 }
 
@@ -217,6 +218,7 @@ impl Into<Proxy> for ClashInputVLess {
     fn into(self) -> Proxy {
         let mut proxy = Proxy::default();
         proxy.proxy_type = ProxyType::Vless;
+        proxy.udp = self.udp;
 
         let mut vless_proxy = VlessProxy::default();
         vless_proxy.uuid = self.uuid;
@@ -233,11 +235,7 @@ impl Into<Proxy> for ClashInputVLess {
         vless_proxy.client_fingerprint = self.client_fingerprint;
 
         // Handle ALPN
-        if let Some(alpn_values) = self.alpn {
-            for value in alpn_values {
-                vless_proxy.alpn.insert(value);
-            }
-        }
+        vless_proxy.alpn = self.alpn;
 
         // Handle network-specific options
         if let Some(net) = self.network.as_deref() {
@@ -280,7 +278,7 @@ impl Into<Proxy> for ClashInputVLess {
         // Handle Reality options
         if let Some(reality) = self.reality_opts {
             vless_proxy.reality_public_key = Some(reality.public_key);
-            vless_proxy.reality_short_id = Some(reality.short_id);
+            vless_proxy.reality_short_id = Some(reality.short_id).filter(|id| !id.is_empty());
         }
 
         proxy.combined_proxy = Some(CombinedProxy::Vless(vless_proxy));
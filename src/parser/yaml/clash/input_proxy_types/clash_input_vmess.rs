@@ -4,6 +4,8 @@ use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::models::proxy_node::utls::parse_client_fingerprint;
+use crate::utils::deserialize::deserialize_alpn;
 use crate::utils::tribool::OptionSetExt;
 
 /// Represents a VMess proxy in Clash configuration
@@ -33,6 +35,10 @@ pub struct ClashInputVMess {
     tls: Option<bool>,
     #[serde(alias = "servername", default)]
     servername: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_alpn")]
+    alpn: Vec<String>,
+    #[serde(default)]
+    client_fingerprint: Option<String>,
 }
 
 impl ClashInputVMess {
@@ -108,6 +114,11 @@ impl Into<Proxy> for ClashInputVMess {
         proxy.allow_insecure.set_if_some(self.skip_cert_verify);
         proxy.tls_secure = self.tls.unwrap_or(false);
         proxy.server_name = self.servername;
+        proxy.alpn = self.alpn;
+        proxy.client_fingerprint = self
+            .client_fingerprint
+            .as_deref()
+            .and_then(parse_client_fingerprint);
 
         // Network protocol handling
         if let Some(net) = self.network {
@@ -1,28 +1,65 @@
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
 use serde::Deserialize;
 
 use crate::models::proxy::Proxy;
 use crate::models::proxy::ProxyType;
+use crate::parser::explodes::{normalize_reserved, split_addresses};
 use crate::utils::tribool::OptionSetExt;
 
+/// A peer of a Clash.Meta WireGuard proxy
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClashInputWireGuardPeer {
+    #[serde(default)]
+    server: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    public_key: Option<String>,
+    #[serde(alias = "preshared-key", default)]
+    pre_shared_key: Option<String>,
+    #[serde(default)]
+    reserved: Option<serde_yaml::Value>,
+    #[serde(default)]
+    allowed_ips: Option<Vec<String>>,
+}
+
+/// Convert `reserved` given as a list of bytes or a string to `a,b,c`
+fn reserved_to_string(reserved: &serde_yaml::Value) -> String {
+    match reserved {
+        serde_yaml::Value::Sequence(bytes) => bytes
+            .iter()
+            .filter_map(|b| b.as_u64().map(|b| b.to_string()))
+            .collect::<Vec<_>>()
+            .join(","),
+        serde_yaml::Value::String(s) => normalize_reserved(s),
+        _ => String::new(),
+    }
+}
+
 /// Represents a WireGuard proxy in Clash configuration
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "type", rename_all = "kebab-case")]
 pub struct ClashInputWireGuard {
     name: String,
+    #[serde(default)]
     server: String,
+    #[serde(default)]
     port: u16,
-    #[serde(alias = "private-key")]
     private_key: String,
-    #[serde(alias = "public-key")]
-    public_key: String,
+    #[serde(default)]
+    public_key: Option<String>,
     #[serde(default)]
     ip: Option<String>,
     #[serde(default)]
     ipv6: Option<String>,
     #[serde(alias = "preshared-key", default)]
-    preshared_key: Option<String>,
+    pre_shared_key: Option<String>,
+    #[serde(default)]
+    reserved: Option<serde_yaml::Value>,
+    #[serde(default)]
+    peers: Vec<ClashInputWireGuardPeer>,
     #[serde(default)]
     dns: Option<Vec<String>>,
     #[serde(default)]
@@ -52,8 +89,12 @@ impl ClashInputWireGuard {
         &self.private_key
     }
 
-    pub fn public_key(&self) -> &str {
-        &self.public_key
+    /// The public key of the proxy, or of its first peer
+    pub fn public_key(&self) -> Option<&str> {
+        self.public_key
+            .as_deref()
+            .or_else(|| self.peers.first().and_then(|p| p.public_key.as_deref()))
+            .filter(|key| !key.is_empty())
     }
 
     pub fn ip(&self) -> Option<&str> {
@@ -65,7 +106,7 @@ impl ClashInputWireGuard {
     }
 
     pub fn preshared_key(&self) -> Option<&str> {
-        self.preshared_key.as_deref()
+        self.pre_shared_key.as_deref()
     }
 
     pub fn dns(&self) -> Option<&Vec<String>> {
@@ -93,17 +134,42 @@ impl Into<Proxy> for ClashInputWireGuard {
     fn into(self) -> Proxy {
         let mut proxy = Proxy::default();
         proxy.proxy_type = ProxyType::WireGuard;
+        proxy.public_key = self.public_key().map(|s| s.to_string());
+
+        // Clash.Meta allows the peer to be given in `peers` instead
+        let peer = self.peers.into_iter().next();
+        let mut server = self.server;
+        let mut port = self.port;
+        let mut pre_shared_key = self.pre_shared_key;
+        let mut reserved = self.reserved;
+        let mut allowed_ips = self.allowed_ips;
+        if let Some(peer) = peer {
+            if server.is_empty() {
+                server = peer.server.unwrap_or_default();
+            }
+            if port == 0 {
+                port = peer.port.unwrap_or_default();
+            }
+            pre_shared_key = pre_shared_key.or(peer.pre_shared_key);
+            reserved = reserved.or(peer.reserved);
+            allowed_ips = allowed_ips.or(peer.allowed_ips);
+        }
+
         proxy.remark = self.name;
-        proxy.hostname = self.server;
-        proxy.port = self.port;
+        proxy.hostname = server;
+        proxy.port = port;
         proxy.private_key = Some(self.private_key);
-        proxy.public_key = Some(self.public_key);
-        proxy.self_ip = self.ip;
-        proxy.self_ipv6 = self.ipv6;
-        proxy.pre_shared_key = self.preshared_key;
-
-        // Convert Vec<String> to HashSet<String> for dns_servers
-        let mut dns_set = HashSet::new();
+        // Addresses may carry a prefix length
+        proxy.self_ip = self.ip.and_then(|ip| split_addresses(&ip).0);
+        proxy.self_ipv6 = self.ipv6.and_then(|ip| split_addresses(&ip).1);
+        proxy.pre_shared_key = pre_shared_key;
+        proxy.client_id = reserved
+            .as_ref()
+            .map(reserved_to_string)
+            .filter(|s| !s.is_empty());
+
+        // Convert Vec<String> to BTreeSet<String> for dns_servers
+        let mut dns_set = BTreeSet::new();
         if let Some(dns_servers) = self.dns {
             for dns_server in dns_servers {
                 dns_set.insert(dns_server);
@@ -112,7 +178,9 @@ impl Into<Proxy> for ClashInputWireGuard {
         proxy.dns_servers = dns_set;
 
         proxy.mtu = self.mtu.unwrap_or(0) as u16;
-        proxy.allowed_ips = self.allowed_ips.unwrap_or_default().join(",");
+        if let Some(allowed_ips) = allowed_ips {
+            proxy.allowed_ips = allowed_ips.join(",");
+        }
         proxy.keep_alive = self.keepalive.unwrap_or(0) as u16;
         proxy.udp.set_if_some(self.udp);
 
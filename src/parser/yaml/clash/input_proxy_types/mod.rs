@@ -7,6 +7,7 @@ pub mod clash_input_shadowsocksr;
 pub mod clash_input_snell;
 pub mod clash_input_socks5;
 pub mod clash_input_trojan;
+pub mod clash_input_tuic;
 pub mod clash_input_vless;
 pub mod clash_input_vmess;
 pub mod clash_input_wireguard;
@@ -1,8 +1,14 @@
+mod clash_groups;
 mod clash_input;
 mod clash_parsers;
 mod clash_proxy_types;
 mod input_proxy_types;
 
+pub use clash_groups::{parse_clash_proxy_groups, CLASH_BUILTIN_POLICIES};
 pub use clash_input::ClashYamlInput;
-pub use clash_parsers::parse_clash_yaml;
+pub(crate) use clash_parsers::{dialer_proxy_of, ip_version_of, smux_of};
+pub use clash_parsers::{
+    parse_clash_yaml, parse_clash_yaml_entries, validate_clash_entries, ClashEntryError,
+    ClashParseError, ClashParseReport,
+};
 pub use clash_proxy_types::ClashProxyYamlInput;
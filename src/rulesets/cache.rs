@@ -0,0 +1,170 @@
+//! Caching of fetched rulesets
+//!
+//! Remote rulesets are kept in the process wide
+//! [`memory_cache`](crate::utils::memory_cache) for
+//! [`cache_ruleset`](crate::Settings::cache_ruleset) seconds, so a
+//! deployment converting the same profiles over and over downloads each of
+//! them once per period instead of once per conversion. With
+//! [`ruleset_cache_dir`](crate::Settings::ruleset_cache_dir) set they are
+//! also written to that directory, which outlives restarts. A fetch is
+//! retried [`retries`](RulesetCachePolicy::retries) times before it counts
+//! as failed, and with
+//! [`serve_stale_ruleset`](crate::Settings::serve_stale_ruleset) a ruleset
+//! whose fetch fails is served from the last copy of either cache, however
+//! old, rather than left out of the generated rules.
+
+use std::fs;
+use std::path::PathBuf;
+
+use log::debug;
+
+use crate::utils::memory_cache;
+use crate::utils::string::hash_const;
+use crate::utils::system::safe_system_time;
+use crate::Settings;
+
+/// How fetched rulesets are cached
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RulesetCachePolicy {
+    /// Seconds a copy is served without fetching again, 0 for none
+    pub ttl: u32,
+    /// Whether the last copy is served when fetching fails
+    pub serve_stale: bool,
+    /// Directory of the disk cache, `None` keeps copies in memory only
+    pub dir: Option<PathBuf>,
+    /// Number of retries after the first failed fetch
+    pub retries: u32,
+}
+
+impl RulesetCachePolicy {
+    /// Retries of a failed fetch unless told otherwise
+    pub const DEFAULT_RETRIES: u32 = 2;
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            ttl: settings.cache_ruleset,
+            serve_stale: settings.serve_stale_ruleset,
+            dir: (!settings.ruleset_cache_dir.is_empty())
+                .then(|| PathBuf::from(&settings.ruleset_cache_dir)),
+            retries: Self::DEFAULT_RETRIES,
+        }
+    }
+
+    /// Whether fetched rulesets are kept at all
+    pub fn keeps_copies(&self) -> bool {
+        self.ttl > 0 || self.serve_stale
+    }
+
+    /// File of the disk copy of `url`
+    fn disk_path(&self, url: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.rules", hash_const(url))))
+    }
+
+    /// A copy of `url` younger than the TTL
+    pub fn fresh(&self, url: &str) -> Option<String> {
+        if self.ttl == 0 {
+            return None;
+        }
+        if let Some(content) = memory_cache::get_if_valid(url, self.ttl) {
+            return Some(content);
+        }
+        let path = self.disk_path(url)?;
+        let age = fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| safe_system_time().duration_since(modified).ok())?;
+        if age.as_secs() >= u64::from(self.ttl) {
+            return None;
+        }
+        fs::read_to_string(&path).ok()
+    }
+
+    /// The last copy of `url`, whatever its age
+    pub fn last(&self, url: &str) -> Option<String> {
+        memory_cache::get(url).or_else(|| {
+            self.disk_path(url)
+                .and_then(|path| fs::read_to_string(path).ok())
+        })
+    }
+
+    /// Keep a freshly fetched copy of `url`
+    ///
+    /// The disk copy is written to a temporary file first and renamed over
+    /// the old one, so concurrent readers never see a partial ruleset.
+    pub fn store(&self, url: &str, content: &str) {
+        if let Err(e) = memory_cache::store(url, content) {
+            debug!("Failed to store ruleset {} in memory: {}", url, e);
+        }
+        if let Some(path) = self.disk_path(url) {
+            let tmp = path.with_extension(format!("rules.{}.tmp", std::process::id()));
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&tmp, content))
+                .and_then(|_| fs::rename(&tmp, &path));
+            if let Err(e) = written {
+                let _ = fs::remove_file(&tmp);
+                debug!("Failed to store ruleset {} on disk: {}", url, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rulesets::ruleset::fetch_ruleset_with;
+    use crate::utils::http::parse_proxy;
+
+    #[tokio::test]
+    async fn test_serve_stale_ruleset() {
+        // Nothing listens on the discard port, fetching fails at once
+        let url = "http://127.0.0.1:9/stale_ruleset.list";
+        let proxy = parse_proxy("NONE");
+        let mut policy = RulesetCachePolicy {
+            ttl: 0,
+            serve_stale: true,
+            dir: None,
+            retries: 0,
+        };
+        policy.store(url, "DOMAIN-SUFFIX,example.com");
+
+        assert_eq!(
+            fetch_ruleset_with(url, &proxy, &policy).await,
+            Ok("DOMAIN-SUFFIX,example.com".to_string())
+        );
+        policy.serve_stale = false;
+        assert!(fetch_ruleset_with(url, &proxy, &policy).await.is_err());
+        memory_cache::remove(url);
+    }
+
+    #[test]
+    fn test_disk_ruleset_cache() {
+        let url = "https://example.com/disk_ruleset.list";
+        let dir = tempfile::tempdir().unwrap();
+        let policy = RulesetCachePolicy {
+            ttl: 3600,
+            serve_stale: false,
+            dir: Some(dir.path().join("rulesets")),
+            retries: 0,
+        };
+        policy.store(url, "IP-CIDR,192.0.2.0/24");
+        // Only the renamed copy is left behind
+        let files: Vec<_> = fs::read_dir(dir.path().join("rulesets"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].to_string_lossy().ends_with(".rules"));
+
+        // A restart empties the memory, the disk copy remains
+        memory_cache::remove(url);
+        assert_eq!(policy.fresh(url).as_deref(), Some("IP-CIDR,192.0.2.0/24"));
+        assert_eq!(policy.last(url).as_deref(), Some("IP-CIDR,192.0.2.0/24"));
+        let no_ttl = RulesetCachePolicy { ttl: 0, ..policy };
+        assert_eq!(no_ttl.fresh(url), None);
+        assert!(no_ttl.last("https://example.com/other.list").is_none());
+    }
+}
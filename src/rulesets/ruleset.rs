@@ -7,10 +7,12 @@ use log::{debug, info, warn}; // Import for parallel execution
 
 use crate::models::ruleset::{get_ruleset_type_from_url, RulesetContent, RulesetType};
 use crate::models::RulesetConfig;
+use crate::rulesets::cache::RulesetCachePolicy;
 use crate::utils::file::read_file_async;
 use crate::utils::file_exists;
-use crate::utils::http::{parse_proxy, web_get_async, ProxyConfig};
+use crate::utils::http::{parse_proxy, ProxyConfig};
 use crate::utils::memory_cache;
+use crate::utils::{fetch_subscription, FetchOptions, ParseLimits};
 use crate::Settings;
 
 /// Fetch ruleset content from file or URL with async operations
@@ -19,16 +21,29 @@ pub async fn fetch_ruleset(
     proxy: &ProxyConfig,
     cache_timeout: u32,
     _async_fetch: bool,
+) -> Result<String, String> {
+    let policy = RulesetCachePolicy {
+        ttl: cache_timeout,
+        retries: RulesetCachePolicy::DEFAULT_RETRIES,
+        ..Default::default()
+    };
+    fetch_ruleset_with(url, proxy, &policy).await
+}
+
+/// Fetch ruleset content from file or URL, cached as `policy` says
+///
+/// Files are cached in memory only and never served stale.
+pub async fn fetch_ruleset_with(
+    url: &str,
+    proxy: &ProxyConfig,
+    policy: &RulesetCachePolicy,
 ) -> Result<String, String> {
     debug!("Requesting ruleset from: {}", url);
 
-    // Check memory cache first if caching is enabled
-    if cache_timeout > 0 {
-        let cache_key = url;
-        if let Some(content) = memory_cache::get_if_valid(cache_key, cache_timeout) {
-            debug!("Using cached ruleset for URL: {}", url);
-            return Ok(content);
-        }
+    // Check the cache first if caching is enabled
+    if let Some(content) = policy.fresh(url) {
+        debug!("Using cached ruleset for URL: {}", url);
+        return Ok(content);
     }
 
     // If it's a file on disk, read it directly using async file read
@@ -43,7 +58,7 @@ pub async fn fetch_ruleset(
                 info!("Loaded ruleset from file: {}", url);
 
                 // Store in memory cache if caching is enabled
-                if cache_timeout > 0 {
+                if policy.ttl > 0 {
                     if let Err(e) = memory_cache::store(url, &content) {
                         warn!("Failed to store ruleset in cache: {}", e);
                     }
@@ -56,27 +71,40 @@ pub async fn fetch_ruleset(
     }
 
     // For URLs, fetch content and cache
-    match fetch_from_url(url, proxy).await {
+    match fetch_from_url(url, proxy, policy.retries).await {
         Ok(content) => {
-            // Store in memory cache if caching is enabled
-            if cache_timeout > 0 {
-                if let Err(e) = memory_cache::store(url, &content) {
-                    warn!("Failed to store ruleset in cache: {}", e);
-                }
+            if policy.keeps_copies() {
+                policy.store(url, &content);
             }
             Ok(content)
         }
-        Err(e) => Err(e),
+        Err(e) => match policy.last(url).filter(|_| policy.serve_stale) {
+            Some(content) => {
+                warn!(
+                    "Failed to fetch ruleset {}, serving the cached copy: {}",
+                    url, e
+                );
+                Ok(content)
+            }
+            None => Err(e),
+        },
     }
 }
 
-/// Helper function to fetch content from URL asynchronously
-async fn fetch_from_url(url: &str, proxy: &ProxyConfig) -> Result<String, String> {
+/// Helper function to fetch content from URL asynchronously, retrying
+/// transient failures `retries` times
+async fn fetch_from_url(url: &str, proxy: &ProxyConfig, retries: u32) -> Result<String, String> {
     debug!("Fetching ruleset from URL: {}", url);
-    match web_get_async(url, proxy, None).await {
-        Ok(response) => Ok(response.body),
-        Err(e) => Err(e.message),
-    }
+    let limits = ParseLimits::from_settings(&Settings::snapshot());
+    let defaults = FetchOptions::default();
+    let opts = FetchOptions::default()
+        .proxy(proxy.clone())
+        .retries(retries, defaults.backoff)
+        .max_size(limits.max_download_size);
+    fetch_subscription(url, &opts)
+        .await
+        .map(|response| response.body)
+        .map_err(|e| e.to_string())
 }
 
 /// Refresh rulesets based on configuration (Parallel version for non-WASM)
@@ -89,8 +117,9 @@ pub async fn refresh_rulesets(
     ruleset_content_array.clear();
 
     // Get global settings
-    let settings = Settings::current();
+    let settings = Settings::snapshot();
     let proxy = parse_proxy(&settings.proxy_ruleset);
+    let policy = RulesetCachePolicy::from_settings(&settings);
 
     // Create a vector of boxed futures for parallel ruleset fetching
     let mut fetch_futures: Vec<Pin<Box<dyn Future<Output = FetchResult> + 'static>>> = Vec::new();
@@ -128,16 +157,14 @@ pub async fn refresh_rulesets(
 
                     // Clone needed values for the future closure
                     let proxy_clone = proxy.clone();
-                    let cache_ruleset = settings.cache_ruleset;
-                    let async_fetch = settings.async_fetch_ruleset; // Note: async_fetch flag from settings might not be relevant anymore with parallel fetching
+                    let policy_clone = policy.clone();
                     let fetch_url = rule_url_without_prefix.clone();
                     let original_url_clone = rule_url.clone(); // Clone original URL
 
                     // Create the future and box it
                     let future = async move {
                         let content =
-                            fetch_ruleset(&fetch_url, &proxy_clone, cache_ruleset, async_fetch)
-                                .await;
+                            fetch_ruleset_with(&fetch_url, &proxy_clone, &policy_clone).await;
 
                         FetchResult {
                             url: fetch_url,
@@ -162,15 +189,13 @@ pub async fn refresh_rulesets(
 
             // Clone needed values for the future closure
             let proxy_clone = proxy.clone();
-            let cache_ruleset = settings.cache_ruleset;
-            let async_fetch = settings.async_fetch_ruleset;
+            let policy_clone = policy.clone();
             let fetch_url = rule_url.clone();
             let original_url_clone = rule_url.clone();
 
             // Create the future and box it
             let future = async move {
-                let content =
-                    fetch_ruleset(&fetch_url, &proxy_clone, cache_ruleset, async_fetch).await;
+                let content = fetch_ruleset_with(&fetch_url, &proxy_clone, &policy_clone).await;
 
                 FetchResult {
                     url: fetch_url, // Use fetch_url (which is same as original_url here)
@@ -221,8 +246,9 @@ pub async fn refresh_rulesets(
     ruleset_content_array.clear();
 
     // Get global settings
-    let settings = Settings::current();
+    let settings = Settings::snapshot();
     let proxy = parse_proxy(&settings.proxy_ruleset);
+    let policy = RulesetCachePolicy::from_settings(&settings);
 
     // Process rulesets sequentially
     for ruleset_config in ruleset_list {
@@ -269,12 +295,7 @@ pub async fn refresh_rulesets(
         }
 
         // Fetch the ruleset content sequentially
-        let proxy_clone = proxy.clone();
-        let cache_ruleset = settings.cache_ruleset;
-        let async_fetch = settings.async_fetch_ruleset; // This flag might be less relevant now, but kept for consistency
-
-        let content_result =
-            fetch_ruleset(&fetch_url, &proxy_clone, cache_ruleset, async_fetch).await;
+        let content_result = fetch_ruleset_with(&fetch_url, &proxy, &policy).await;
 
         // Process the result
         match content_result {
@@ -108,7 +108,7 @@ impl ExternalSettings {
 
     /// Validate rulesets count
     pub fn validate_rulesets(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let settings = Settings::current();
+        let settings = Settings::snapshot();
         if settings.max_allowed_rulesets > 0
             && self.custom_rulesets.len() > settings.max_allowed_rulesets
         {
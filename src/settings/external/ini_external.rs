@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use super::super::ini_bindings::{FromIni, FromIniWithDelimiter};
 use crate::models::ruleset::RulesetConfigs;
 use crate::models::{ProxyGroupConfigs, RegexMatchConfig, RegexMatchConfigs, RulesetConfig};
-use crate::settings::{import_items, Settings};
+use crate::settings::{import_items, import_match_rules, RuleImportOptions, Settings};
 use crate::utils::http::parse_proxy;
 
 /// INI external settings structure
@@ -147,20 +147,15 @@ impl IniExternalSettings {
     }
 
     pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+        let global = Settings::snapshot();
         let proxy_config = parse_proxy(&global.proxy_config);
         // Process rename nodes
-        import_items(
-            &mut self.rename_nodes,
-            false,
-            &proxy_config,
-            &global.base_path,
-        )
-        .await?;
+        let rule_import = RuleImportOptions::new(&proxy_config, global.cache_config);
+        import_match_rules(&mut self.rename_nodes, "@", &rule_import).await;
         self.parsed_rename = RegexMatchConfigs::from_ini_with_delimiter(&self.rename_nodes, "@");
 
         // Process emoji rules
-        import_items(&mut self.emojis, false, &proxy_config, &global.base_path).await?;
+        import_match_rules(&mut self.emojis, ",", &rule_import).await;
         self.parsed_emojis = RegexMatchConfigs::from_ini_with_delimiter(&self.emojis, ",");
 
         // Process imports for rulesets
@@ -5,6 +5,7 @@ use crate::models::{ProxyGroupConfigs, RegexMatchConfig, RulesetConfig};
 use crate::settings::import_toml::import_toml_items;
 use crate::settings::toml_deserializer::*;
 use crate::settings::Settings;
+use crate::settings::{import_toml_match_rules, RuleImportOptions};
 use crate::utils::http::parse_proxy;
 
 // Default value functions
@@ -102,17 +103,11 @@ pub struct TomlExternalSettings {
 
 impl TomlExternalSettings {
     pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+        let global = Settings::snapshot();
         let proxy_config = parse_proxy(&global.proxy_config);
 
-        import_toml_items(
-            &mut self.custom.rename_node,
-            false,
-            "rename_node",
-            &proxy_config,
-            &global.base_path,
-        )
-        .await?;
+        let rule_import = RuleImportOptions::new(&proxy_config, global.cache_config);
+        import_toml_match_rules(&mut self.custom.rename_node, "rename_node", &rule_import).await;
         self.parsed_rename = self
             .custom
             .rename_node
@@ -121,14 +116,7 @@ impl TomlExternalSettings {
             .collect();
 
         // Process emoji rules
-        import_toml_items(
-            &mut self.custom.emoji_settings.emoji,
-            false,
-            "emoji",
-            &proxy_config,
-            &global.base_path,
-        )
-        .await?;
+        import_toml_match_rules(&mut self.custom.emoji_settings.emoji, "emoji", &rule_import).await;
         self.parsed_emojis = self
             .custom
             .emoji_settings
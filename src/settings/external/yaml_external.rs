@@ -5,7 +5,8 @@ use super::super::ini_bindings::{FromIni, FromIniWithDelimiter};
 use crate::models::ruleset::RulesetConfigs;
 use crate::models::{ProxyGroupConfigs, RegexMatchConfig, RegexMatchConfigs};
 use crate::settings::yaml_deserializer::deserialize_template_args_as_hash_map;
-use crate::{settings::import_items, utils::http::parse_proxy, Settings};
+use crate::settings::{import_items, import_match_rules, RuleImportOptions};
+use crate::{utils::http::parse_proxy, Settings};
 
 // Default value functions
 fn default_true() -> bool {
@@ -106,28 +107,17 @@ pub struct YamlExternalSettings {
 
 impl YamlExternalSettings {
     pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+        let global = Settings::snapshot();
         let proxy_config = parse_proxy(&global.proxy_config);
 
         // Process rename nodes
-        import_items(
-            &mut self.custom.rename_nodes,
-            false,
-            &proxy_config,
-            &global.base_path,
-        )
-        .await?;
+        let rule_import = RuleImportOptions::new(&proxy_config, global.cache_config);
+        import_match_rules(&mut self.custom.rename_nodes, "@", &rule_import).await;
         self.parsed_rename =
             RegexMatchConfigs::from_ini_with_delimiter(&self.custom.rename_nodes, "@");
 
         // Process emoji rules
-        import_items(
-            &mut self.custom.emojis,
-            false,
-            &proxy_config,
-            &global.base_path,
-        )
-        .await?;
+        import_match_rules(&mut self.custom.emojis, ",", &rule_import).await;
         self.parsed_emojis = RegexMatchConfigs::from_ini_with_delimiter(&self.custom.emojis, ",");
 
         // Process imports for rulesets
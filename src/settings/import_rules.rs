@@ -0,0 +1,222 @@
+//! Emoji and rename rules imported from files or URLs
+//!
+//! Rule lists are shared between many configs, so they are usually
+//! referenced with `!!import:` instead of being copied. Unlike the other
+//! imports, a rule list that cannot be fetched or read only costs its own
+//! rules: the import is dropped with a warning and loading goes on. URLs are
+//! fetched like subscriptions, with timeout and retries, and kept in the
+//! memory cache for `cache_config` seconds.
+
+use crate::utils::http::ProxyConfig;
+use crate::utils::{
+    fetch_subscription, file_exists, file_get_async, memory_cache, FetchOptions, ParseLimits,
+};
+use crate::Settings;
+
+use super::toml_deserializer::ImportableInToml;
+
+/// How imported rule lists are fetched
+#[derive(Debug, Clone)]
+pub struct RuleImportOptions {
+    /// Timeout, retry and proxy options for URLs
+    pub fetch: FetchOptions,
+    /// Seconds fetched content stays cached, 0 to fetch every time
+    pub cache_timeout: u32,
+}
+
+impl RuleImportOptions {
+    /// Options fetching through `proxy` with the download limit of the
+    /// current settings
+    pub fn new(proxy: &ProxyConfig, cache_timeout: u32) -> Self {
+        let limits = ParseLimits::from_settings(&Settings::snapshot());
+        RuleImportOptions {
+            fetch: FetchOptions::default()
+                .proxy(proxy.clone())
+                .max_size(limits.max_download_size),
+            cache_timeout,
+        }
+    }
+}
+
+/// Read an import source, from the cache when it is still fresh
+async fn load_source(path: &str, options: &RuleImportOptions) -> Result<String, String> {
+    if !path.starts_with("http://") && !path.starts_with("https://") {
+        if !file_exists(path).await {
+            return Err("file not found".to_string());
+        }
+        return file_get_async(path, None).await.map_err(|e| e.to_string());
+    }
+
+    if options.cache_timeout > 0 {
+        if let Some(content) = memory_cache::get_if_valid(path, options.cache_timeout) {
+            log::debug!("Using cached rules for {}", path);
+            return Ok(content);
+        }
+    }
+    let content = fetch_subscription(path, &options.fetch)
+        .await
+        .map_err(|e| e.to_string())?
+        .body;
+    if options.cache_timeout > 0 && !content.is_empty() {
+        if let Err(e) = memory_cache::store(path, &content) {
+            log::warn!("Failed to cache rules of {}: {}", path, e);
+        }
+    }
+    Ok(content)
+}
+
+/// Rule lines of an imported list, or why the list is malformed
+fn parse_rule_lines(content: &str, delimiter: &str) -> Result<Vec<String>, String> {
+    let mut rules = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with(';')
+            || line.starts_with('#')
+            || line.starts_with("//")
+        {
+            continue;
+        }
+        if !line.starts_with("script:") && !line.contains(delimiter) {
+            return Err(format!(
+                "line {} is not a rule: '{}'",
+                index + 1,
+                line.chars().take(64).collect::<String>()
+            ));
+        }
+        rules.push(line.to_string());
+    }
+    if rules.is_empty() {
+        return Err("no rules found".to_string());
+    }
+    Ok(rules)
+}
+
+/// Replace `!!import:` entries of emoji or rename rules with the rules they
+/// point to
+///
+/// `delimiter` separates the match from the replacement, `,` for emoji and
+/// `@` for rename rules. Imports that fail or hold malformed rules are
+/// dropped with a warning.
+pub async fn import_match_rules(
+    target: &mut Vec<String>,
+    delimiter: &str,
+    options: &RuleImportOptions,
+) {
+    let mut result = Vec::new();
+    for item in target.iter() {
+        let Some(path) = item.strip_prefix("!!import:") else {
+            result.push(item.clone());
+            continue;
+        };
+        log::info!("Trying to import rules from {}", path);
+
+        let rules = load_source(path, options)
+            .await
+            .and_then(|content| parse_rule_lines(&content, delimiter));
+        match rules {
+            Ok(rules) => {
+                log::info!("Imported {} rule(s) from {}", rules.len(), path);
+                result.extend(rules);
+            }
+            Err(e) => log::warn!("Ignoring rules imported from {}: {}", path, e),
+        }
+    }
+    *target = result;
+}
+
+/// Parse the `import_key` array of an imported TOML rule list
+fn parse_toml_rules<T: ImportableInToml>(
+    content: &str,
+    import_key: &str,
+) -> Result<Vec<T>, String> {
+    let root = toml::from_str::<toml::Value>(content).map_err(|e| e.to_string())?;
+    let array = root
+        .get(import_key)
+        .and_then(toml::Value::as_array)
+        .ok_or_else(|| format!("no '{}' array found", import_key))?;
+    array
+        .iter()
+        .map(|value| T::try_from_toml_value(value).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// TOML counterpart of [`import_match_rules`], reading the `import_key`
+/// array of the imported files
+pub async fn import_toml_match_rules<T: ImportableInToml>(
+    target: &mut Vec<T>,
+    import_key: &str,
+    options: &RuleImportOptions,
+) {
+    let mut result = Vec::new();
+    for item in target.iter() {
+        let Some(path) = item.get_import_path().filter(|_| item.is_import_node()) else {
+            result.push(item.clone());
+            continue;
+        };
+        log::info!("Trying to import rules from {}", path);
+
+        let rules = load_source(&path, options)
+            .await
+            .and_then(|content| parse_toml_rules::<T>(&content, import_key));
+        match rules {
+            Ok(rules) => {
+                log::info!("Imported {} rule(s) from {}", rules.len(), path);
+                result.extend(rules);
+            }
+            Err(e) => log::warn!("Ignoring rules imported from {}: {}", path, e),
+        }
+    }
+    *target = result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::toml_deserializer::RegexMatchRuleInToml;
+
+    fn options() -> RuleImportOptions {
+        RuleImportOptions::new(&ProxyConfig::default(), 0)
+    }
+
+    #[tokio::test]
+    async fn test_import_match_rules() {
+        let mut rules = vec![
+            "(?i)hk,🇭🇰".to_string(),
+            "!!import:base/snippets/emoji.txt".to_string(),
+            "!!import:base/snippets/missing.txt".to_string(),
+        ];
+        import_match_rules(&mut rules, ",", &options()).await;
+        assert_eq!(rules[0], "(?i)hk,🇭🇰");
+        assert!(rules.len() > 10);
+        assert!(rules.iter().all(|rule| !rule.starts_with("!!import:")));
+
+        let mut rules = vec!["!!import:base/snippets/emoji.toml".to_string()];
+        import_match_rules(&mut rules, ",", &options()).await;
+        assert!(rules.is_empty());
+
+        let mut rules: Vec<RegexMatchRuleInToml> = vec![RegexMatchRuleInToml {
+            import: Some("base/snippets/emoji.toml".to_string()),
+            ..Default::default()
+        }];
+        import_toml_match_rules(&mut rules, "emoji", &options()).await;
+        assert!(rules.len() > 10);
+
+        let mut rules: Vec<RegexMatchRuleInToml> = vec![RegexMatchRuleInToml {
+            import: Some("base/snippets/emoji.txt".to_string()),
+            ..Default::default()
+        }];
+        import_toml_match_rules(&mut rules, "emoji", &options()).await;
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_rule_list() {
+        assert_eq!(
+            parse_rule_lines("# emoji\nHK,🇭🇰\nscript:path.js\n", ",").unwrap(),
+            vec!["HK,🇭🇰", "script:path.js"]
+        );
+        assert!(parse_rule_lines("<!DOCTYPE html>\n<html></html>", "@").is_err());
+        assert!(parse_rule_lines("", "@").is_err());
+    }
+}
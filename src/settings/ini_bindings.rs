@@ -55,16 +55,7 @@ impl FromIni<ProxyGroupConfigs> for ProxyGroupConfigs {
             let type_str = v_array[1];
 
             rules_upper_bound = v_array.len();
-            conf.group_type = match type_str {
-                "select" => ProxyGroupType::Select,
-                "relay" => ProxyGroupType::Relay,
-                "url-test" => ProxyGroupType::URLTest,
-                "fallback" => ProxyGroupType::Fallback,
-                "load-balance" => ProxyGroupType::LoadBalance,
-                "ssid" => ProxyGroupType::SSID,
-                "smart" => ProxyGroupType::Smart,
-                _ => ProxyGroupType::Select,
-            };
+            conf.group_type = type_str.parse().unwrap_or(ProxyGroupType::Select);
 
             if conf.group_type == ProxyGroupType::URLTest
                 || conf.group_type == ProxyGroupType::LoadBalance
@@ -96,6 +87,12 @@ impl FromIni<ProxyGroupConfigs> for ProxyGroupConfigs {
                     for provider in provider_list {
                         conf.using_provider.push(provider.to_string());
                     }
+                } else if let Some(filter) = v_array[i].strip_prefix("!!FILTER=") {
+                    conf.filter = Some(filter.to_string());
+                } else if let Some(icon) = v_array[i].strip_prefix("!!ICON=") {
+                    conf.icon = Some(icon.to_string()).filter(|icon| !icon.is_empty());
+                } else if v_array[i] == "!!HIDDEN" {
+                    conf.hidden = true;
                 } else {
                     conf.proxies.push(v_array[i].to_string());
                 }
@@ -166,7 +163,12 @@ impl FromIniWithDelimiter<RegexMatchConfigs> for RegexMatchConfigs {
                 continue;
             }
 
-            // Handle match/replace case
+            // Handle match/replace case, `!!literal:` marks a plain text match
+            let (x, literal) = match x.strip_prefix("!!literal:") {
+                Some(rest) => (rest, true),
+                None => (x.as_str(), false),
+            };
+            conf.literal = literal;
             let pos = x.rfind(delimiter);
             conf._match = x[..pos.unwrap_or(x.len())].to_string();
 
@@ -3,6 +3,7 @@
 //! This module contains all the configuration settings and utilities
 pub mod external;
 pub mod import;
+pub mod import_rules;
 pub mod import_toml;
 pub mod ini_bindings;
 pub mod settings;
@@ -13,5 +14,6 @@ pub mod yaml_deserializer;
 // Re-export settings struct and functions
 pub use external::ExternalSettings;
 pub use import::*;
+pub use import_rules::{import_match_rules, import_toml_match_rules, RuleImportOptions};
 pub use ini_bindings::*;
 pub use settings::settings_struct::{refresh_configuration, update_settings_from_file, Settings};
@@ -10,6 +10,61 @@ use super::yaml_settings::YamlSettings;
 use crate::constants::log_level::{
     LOG_LEVEL_DEBUG, LOG_LEVEL_ERROR, LOG_LEVEL_INFO, LOG_LEVEL_VERBOSE, LOG_LEVEL_WARNING,
 };
+use crate::models::{GroupHealthCheck, ProviderHealthCheck, SkipCertVerifyPolicy};
+
+/// Parse `skip_cert_verify_policy`, falling back to applying the flag to
+/// every node
+fn parse_scv_policy(value: &str) -> SkipCertVerifyPolicy {
+    if value.is_empty() {
+        return SkipCertVerifyPolicy::default();
+    }
+    value.parse().unwrap_or_else(|e| {
+        log::warn!("{}", e);
+        SkipCertVerifyPolicy::default()
+    })
+}
+
+/// Group health check defaults, unset options keep the built-in ones
+fn group_health_check(
+    url: &str,
+    interval: u32,
+    tolerance: u32,
+    lazy: Option<bool>,
+) -> GroupHealthCheck {
+    let mut check = GroupHealthCheck::default();
+    if !url.is_empty() {
+        check.url = url.to_string();
+    }
+    if interval > 0 {
+        check.interval = interval;
+    }
+    check.tolerance = tolerance;
+    check.lazy = lazy;
+    check
+}
+
+/// Provider health check, unset options keep the built-in ones
+fn provider_health_check(
+    enable: Option<bool>,
+    url: &str,
+    interval: u32,
+    timeout: Option<u32>,
+    lazy: Option<bool>,
+) -> ProviderHealthCheck {
+    let mut check = ProviderHealthCheck::default();
+    if let Some(enable) = enable {
+        check.enable = enable;
+    }
+    if !url.is_empty() {
+        check.url = url.to_string();
+    }
+    if interval > 0 {
+        check.interval = interval;
+    }
+    check.timeout = timeout.filter(|timeout| *timeout > 0);
+    check.lazy = lazy;
+    check
+}
 
 // Conversion from YamlSettings to Settings
 impl From<YamlSettings> for Settings {
@@ -26,6 +81,7 @@ impl From<YamlSettings> for Settings {
         settings.api_access_token = yaml_settings.common.api_access_token;
         settings.base_path = yaml_settings.common.base_path;
         settings.clash_base = yaml_settings.common.clash_rule_base;
+        settings.clash_dns = yaml_settings.common.clash_dns;
         settings.surge_base = yaml_settings.common.surge_rule_base;
         settings.surfboard_base = yaml_settings.common.surfboard_rule_base;
         settings.mellow_base = yaml_settings.common.mellow_rule_base;
@@ -38,6 +94,7 @@ impl From<YamlSettings> for Settings {
         settings.proxy_ruleset = yaml_settings.common.proxy_ruleset;
         settings.proxy_subscription = yaml_settings.common.proxy_subscription;
         settings.append_type = yaml_settings.common.append_proxy_type;
+        settings.normalize_names = yaml_settings.common.normalize_names;
         settings.reload_conf_on_request = yaml_settings.common.reload_conf_on_request;
 
         // Process default_url and insert_url
@@ -55,6 +112,8 @@ impl From<YamlSettings> for Settings {
         settings.udp_flag = yaml_settings.node_pref.udp_flag;
         settings.tfo_flag = yaml_settings.node_pref.tcp_fast_open_flag;
         settings.skip_cert_verify = yaml_settings.node_pref.skip_cert_verify_flag;
+        settings.skip_cert_verify_policy =
+            parse_scv_policy(&yaml_settings.node_pref.skip_cert_verify_policy);
         settings.tls13_flag = yaml_settings.node_pref.tls13_flag;
         settings.enable_sort = yaml_settings.node_pref.sort_flag;
         settings.sort_script = yaml_settings.node_pref.sort_script;
@@ -64,6 +123,19 @@ impl From<YamlSettings> for Settings {
         settings.clash_proxies_style = yaml_settings.node_pref.clash_proxies_style;
         settings.clash_proxy_groups_style = yaml_settings.node_pref.clash_proxy_groups_style;
         settings.singbox_add_clash_modes = yaml_settings.node_pref.singbox_add_clash_modes;
+        settings.group_health_check = group_health_check(
+            &yaml_settings.node_pref.group_test_url,
+            yaml_settings.node_pref.group_test_interval,
+            yaml_settings.node_pref.group_test_tolerance,
+            yaml_settings.node_pref.group_lazy,
+        );
+        settings.provider_health_check = provider_health_check(
+            yaml_settings.node_pref.provider_test_enable,
+            &yaml_settings.node_pref.provider_test_url,
+            yaml_settings.node_pref.provider_test_interval,
+            yaml_settings.node_pref.provider_test_timeout,
+            yaml_settings.node_pref.provider_lazy,
+        );
         // Managed config
         settings.write_managed_config = yaml_settings.managed_config.write_managed_config;
         settings.managed_config_prefix = yaml_settings.managed_config.managed_config_prefix;
@@ -100,10 +172,20 @@ impl From<YamlSettings> for Settings {
         settings.max_allowed_rulesets = yaml_settings.advanced.max_allowed_rulesets;
         settings.max_allowed_rules = yaml_settings.advanced.max_allowed_rules;
         settings.max_allowed_download_size = yaml_settings.advanced.max_allowed_download_size;
+        settings.max_allowed_decoded_size = yaml_settings.advanced.max_allowed_decoded_size;
+        settings.max_allowed_nodes = yaml_settings.advanced.max_allowed_nodes;
+        settings.max_concurrent_conversions = yaml_settings.advanced.max_concurrent_conversions;
+        settings.max_queued_conversions = yaml_settings.advanced.max_queued_conversions;
         settings.cache_subscription = yaml_settings.advanced.cache_subscription;
         settings.cache_config = yaml_settings.advanced.cache_config;
         settings.cache_ruleset = yaml_settings.advanced.cache_ruleset;
+        settings.ruleset_cache_dir = yaml_settings.advanced.ruleset_cache_dir.clone();
+        settings.serve_stale_ruleset = yaml_settings.advanced.serve_stale_ruleset;
         settings.script_clean_context = yaml_settings.advanced.script_clean_context;
+        settings.script_memory_limit = yaml_settings.advanced.script_memory_limit;
+        settings.script_timeout = yaml_settings.advanced.script_timeout;
+        settings.max_probe_concurrency = yaml_settings.advanced.max_probe_concurrency;
+        settings.max_probe_timeout = yaml_settings.advanced.max_probe_timeout;
         settings.async_fetch_ruleset = yaml_settings.advanced.async_fetch_ruleset;
         settings.skip_failed_links = yaml_settings.advanced.skip_failed_links;
 
@@ -116,6 +198,10 @@ impl From<YamlSettings> for Settings {
         settings.enable_rule_gen = yaml_settings.rulesets.enabled;
         settings.overwrite_original_rules = yaml_settings.rulesets.overwrite_original_rules;
         settings.update_ruleset_on_request = yaml_settings.rulesets.update_ruleset_on_request;
+        // Like the INI rulesets, the rule section only applies with rule generation
+        if settings.enable_rule_gen {
+            settings.surge_rule_section = yaml_settings.rulesets.surge_rule_section.clone();
+        }
 
         // update fields processed
         settings.renames = yaml_settings.parsed_rename;
@@ -144,6 +230,7 @@ impl From<TomlSettings> for Settings {
         settings.api_access_token = common.api_access_token;
         settings.base_path = common.base_path;
         settings.clash_base = common.clash_rule_base;
+        settings.clash_dns = common.clash_dns;
         settings.surge_base = common.surge_rule_base;
         settings.surfboard_base = common.surfboard_rule_base;
         settings.mellow_base = common.mellow_rule_base;
@@ -156,6 +243,7 @@ impl From<TomlSettings> for Settings {
         settings.proxy_ruleset = common.proxy_ruleset;
         settings.proxy_subscription = common.proxy_subscription;
         settings.append_type = common.append_proxy_type;
+        settings.normalize_names = common.normalize_names;
         settings.reload_conf_on_request = common.reload_conf_on_request;
 
         settings.enable_filter = common.enable_filter;
@@ -177,6 +265,7 @@ impl From<TomlSettings> for Settings {
         settings.udp_flag = node_pref.udp_flag;
         settings.tfo_flag = node_pref.tcp_fast_open_flag;
         settings.skip_cert_verify = node_pref.skip_cert_verify_flag;
+        settings.skip_cert_verify_policy = parse_scv_policy(&node_pref.skip_cert_verify_policy);
         settings.tls13_flag = node_pref.tls13_flag;
         settings.enable_sort = node_pref.sort_flag;
         settings.sort_script = node_pref.sort_script.clone();
@@ -186,6 +275,19 @@ impl From<TomlSettings> for Settings {
         settings.clash_proxies_style = node_pref.clash_proxies_style.clone();
         settings.clash_proxy_groups_style = node_pref.clash_proxy_groups_style.clone();
         settings.singbox_add_clash_modes = node_pref.singbox_add_clash_modes;
+        settings.group_health_check = group_health_check(
+            &node_pref.group_test_url,
+            node_pref.group_test_interval,
+            node_pref.group_test_tolerance,
+            node_pref.group_lazy,
+        );
+        settings.provider_health_check = provider_health_check(
+            node_pref.provider_test_enable,
+            &node_pref.provider_test_url,
+            node_pref.provider_test_interval,
+            node_pref.provider_test_timeout,
+            node_pref.provider_lazy,
+        );
 
         // Managed config
         settings.write_managed_config = toml_settings.managed_config.write_managed_config;
@@ -224,10 +326,20 @@ impl From<TomlSettings> for Settings {
         settings.max_allowed_rulesets = toml_settings.advanced.max_allowed_rulesets;
         settings.max_allowed_rules = toml_settings.advanced.max_allowed_rules;
         settings.max_allowed_download_size = toml_settings.advanced.max_allowed_download_size;
+        settings.max_allowed_decoded_size = toml_settings.advanced.max_allowed_decoded_size;
+        settings.max_allowed_nodes = toml_settings.advanced.max_allowed_nodes;
+        settings.max_concurrent_conversions = toml_settings.advanced.max_concurrent_conversions;
+        settings.max_queued_conversions = toml_settings.advanced.max_queued_conversions;
         settings.cache_subscription = toml_settings.advanced.cache_subscription;
         settings.cache_config = toml_settings.advanced.cache_config;
         settings.cache_ruleset = toml_settings.advanced.cache_ruleset;
+        settings.ruleset_cache_dir = toml_settings.advanced.ruleset_cache_dir.clone();
+        settings.serve_stale_ruleset = toml_settings.advanced.serve_stale_ruleset;
         settings.script_clean_context = toml_settings.advanced.script_clean_context;
+        settings.script_memory_limit = toml_settings.advanced.script_memory_limit;
+        settings.script_timeout = toml_settings.advanced.script_timeout;
+        settings.max_probe_concurrency = toml_settings.advanced.max_probe_concurrency;
+        settings.max_probe_timeout = toml_settings.advanced.max_probe_timeout;
         settings.async_fetch_ruleset = toml_settings.advanced.async_fetch_ruleset;
         settings.skip_failed_links = toml_settings.advanced.skip_failed_links;
 
@@ -241,6 +353,9 @@ impl From<TomlSettings> for Settings {
             settings.overwrite_original_rules = toml_settings.ruleset.overwrite_original_rules;
             settings.update_ruleset_on_request = toml_settings.ruleset.update_ruleset_on_request;
         }
+        if settings.enable_rule_gen {
+            settings.surge_rule_section = toml_settings.ruleset.surge_rule_section.clone();
+        }
 
         // Ensure listen_address is not empty
         if settings.listen_address.trim().is_empty() {
@@ -295,6 +410,7 @@ impl From<IniSettings> for Settings {
         settings.enable_filter = ini_settings.enable_filter;
         settings.base_path = ini_settings.base_path.clone();
         settings.clash_base = ini_settings.clash_base.clone();
+        settings.clash_dns = ini_settings.clash_dns.clone();
         settings.surge_base = ini_settings.surge_base.clone();
         settings.surfboard_base = ini_settings.surfboard_base.clone();
         settings.mellow_base = ini_settings.mellow_base.clone();
@@ -305,6 +421,7 @@ impl From<IniSettings> for Settings {
         settings.singbox_base = ini_settings.singbox_base.clone();
         settings.default_ext_config = ini_settings.default_ext_config.clone();
         settings.append_type = ini_settings.append_type;
+        settings.normalize_names = ini_settings.normalize_names;
         settings.proxy_config = ini_settings.proxy_config.clone();
         settings.proxy_ruleset = ini_settings.proxy_ruleset.clone();
         settings.proxy_subscription = ini_settings.proxy_subscription.clone();
@@ -318,6 +435,7 @@ impl From<IniSettings> for Settings {
         settings.udp_flag = ini_settings.udp_flag;
         settings.tfo_flag = ini_settings.tfo_flag;
         settings.skip_cert_verify = ini_settings.skip_cert_verify;
+        settings.skip_cert_verify_policy = parse_scv_policy(&ini_settings.skip_cert_verify_policy);
         settings.tls13_flag = ini_settings.tls13_flag;
         settings.enable_sort = ini_settings.enable_sort;
         settings.sort_script = ini_settings.sort_script.clone();
@@ -327,6 +445,19 @@ impl From<IniSettings> for Settings {
         settings.clash_proxies_style = ini_settings.clash_proxies_style.clone();
         settings.clash_proxy_groups_style = ini_settings.clash_proxy_groups_style.clone();
         settings.singbox_add_clash_modes = ini_settings.singbox_add_clash_modes;
+        settings.group_health_check = group_health_check(
+            &ini_settings.group_test_url,
+            ini_settings.group_test_interval,
+            ini_settings.group_test_tolerance,
+            ini_settings.group_lazy,
+        );
+        settings.provider_health_check = provider_health_check(
+            ini_settings.provider_test_enable,
+            &ini_settings.provider_test_url,
+            ini_settings.provider_test_interval,
+            ini_settings.provider_test_timeout,
+            ini_settings.provider_lazy,
+        );
         // Set rename_node from parsed_rename
         settings.renames = ini_settings.parsed_rename;
 
@@ -347,6 +478,7 @@ impl From<IniSettings> for Settings {
         if ini_settings.enable_rule_gen {
             settings.overwrite_original_rules = ini_settings.overwrite_original_rules;
             settings.update_ruleset_on_request = ini_settings.update_ruleset_on_request;
+            settings.surge_rule_section = ini_settings.surge_rule_section.clone();
             // Convert string rulesets to RulesetConfig
             settings.custom_rulesets = ini_settings.parsed_ruleset;
         } else {
@@ -383,18 +515,29 @@ impl From<IniSettings> for Settings {
         settings.max_allowed_rulesets = ini_settings.max_allowed_rulesets;
         settings.max_allowed_rules = ini_settings.max_allowed_rules;
         settings.max_allowed_download_size = ini_settings.max_allowed_download_size;
+        settings.max_allowed_decoded_size = ini_settings.max_allowed_decoded_size;
+        settings.max_allowed_nodes = ini_settings.max_allowed_nodes;
+        settings.max_concurrent_conversions = ini_settings.max_concurrent_conversions;
+        settings.max_queued_conversions = ini_settings.max_queued_conversions;
         if ini_settings.enable_cache {
             settings.cache_subscription = ini_settings.cache_subscription;
             settings.cache_config = ini_settings.cache_config;
             settings.cache_ruleset = ini_settings.cache_ruleset;
             settings.serve_cache_on_fetch_fail = ini_settings.serve_cache_on_fetch_fail;
+            settings.ruleset_cache_dir = ini_settings.ruleset_cache_dir.clone();
+            settings.serve_stale_ruleset = ini_settings.serve_stale_ruleset;
         } else {
             settings.cache_subscription = 0;
             settings.cache_config = 0;
             settings.cache_ruleset = 0;
             settings.serve_cache_on_fetch_fail = false;
+            settings.serve_stale_ruleset = false;
         }
         settings.script_clean_context = ini_settings.script_clean_context;
+        settings.script_memory_limit = ini_settings.script_memory_limit;
+        settings.script_timeout = ini_settings.script_timeout;
+        settings.max_probe_concurrency = ini_settings.max_probe_concurrency;
+        settings.max_probe_timeout = ini_settings.max_probe_timeout;
         settings.async_fetch_ruleset = ini_settings.async_fetch_ruleset;
         settings.skip_failed_links = ini_settings.skip_failed_links;
 
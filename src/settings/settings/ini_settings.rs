@@ -8,7 +8,7 @@ use crate::{
     models::{
         cron::CronTaskConfigs, ruleset::RulesetConfigs, ProxyGroupConfigs, RegexMatchConfigs,
     },
-    settings::import_items,
+    settings::{import_items, import_match_rules, RuleImportOptions},
 };
 
 /// Settings structure to hold global configuration
@@ -60,6 +60,8 @@ pub struct IniSettings {
     #[serde(default)]
     pub overwrite_original_rules: bool,
     #[serde(default)]
+    pub surge_rule_section: String,
+    #[serde(default)]
     pub print_dbg_info: bool,
     #[serde(default = "default_true")]
     pub append_sub_userinfo: bool,
@@ -73,6 +75,12 @@ pub struct IniSettings {
     pub log_level: u32,
     #[serde(default = "default_max_download_size")]
     pub max_allowed_download_size: i64,
+    #[serde(default = "default_max_decoded_size")]
+    pub max_allowed_decoded_size: i64,
+    #[serde(default = "default_max_nodes")]
+    pub max_allowed_nodes: usize,
+    pub max_concurrent_conversions: usize,
+    pub max_queued_conversions: usize,
     pub template_path: String,
     #[serde(default)]
     pub template_vars: HashMap<String, String>,
@@ -94,11 +102,14 @@ pub struct IniSettings {
 
     #[serde(default)]
     pub append_type: bool,
+    #[serde(default)]
+    pub normalize_names: bool,
     #[serde(default = "default_true")]
     pub filter_deprecated: bool,
     pub udp_flag: Option<bool>,
     pub tfo_flag: Option<bool>,
     pub skip_cert_verify: Option<bool>,
+    pub skip_cert_verify_policy: String,
     pub tls13_flag: Option<bool>,
     #[serde(default)]
     pub enable_sort: bool,
@@ -109,6 +120,20 @@ pub struct IniSettings {
     #[serde(default)]
     pub singbox_add_clash_modes: bool,
     #[serde(default)]
+    pub group_test_url: String,
+    #[serde(default)]
+    pub group_test_interval: u32,
+    #[serde(default)]
+    pub group_test_tolerance: u32,
+    pub group_lazy: Option<bool>,
+    pub provider_test_enable: Option<bool>,
+    #[serde(default)]
+    pub provider_test_url: String,
+    #[serde(default)]
+    pub provider_test_interval: u32,
+    pub provider_test_timeout: Option<u32>,
+    pub provider_lazy: Option<bool>,
+    #[serde(default)]
     pub rename_node: Vec<String>,
     #[serde(default)]
     pub stream_rule: Vec<String>,
@@ -129,6 +154,7 @@ pub struct IniSettings {
 
     // Base configs
     pub clash_base: String,
+    pub clash_dns: String,
     pub surge_base: String,
     pub surfboard_base: String,
     pub mellow_base: String,
@@ -151,14 +177,24 @@ pub struct IniSettings {
     pub cache_config: u32,
     #[serde(default = "default_cache_ruleset")]
     pub cache_ruleset: u32,
+    pub ruleset_cache_dir: String,
+    pub serve_stale_ruleset: bool,
 
     // Limits
     #[serde(default = "default_max_rulesets")]
     pub max_allowed_rulesets: usize,
     #[serde(default = "default_max_rules")]
     pub max_allowed_rules: usize,
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub script_clean_context: bool,
+    #[serde(default = "default_script_memory_limit")]
+    pub script_memory_limit: usize,
+    #[serde(default = "default_script_timeout")]
+    pub script_timeout: u32,
+    #[serde(default = "default_max_probe_concurrency")]
+    pub max_probe_concurrency: usize,
+    #[serde(default = "default_max_probe_timeout")]
+    pub max_probe_timeout: u32,
 
     // Cron system
     #[serde(default)]
@@ -226,6 +262,14 @@ fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
 
+fn default_max_decoded_size() -> i64 {
+    32 * 1024 * 1024 // 32MB
+}
+
+fn default_max_nodes() -> usize {
+    50000
+}
+
 fn default_cache_subscription() -> u32 {
     60
 }
@@ -246,6 +290,22 @@ fn default_max_rules() -> usize {
     32768
 }
 
+fn default_script_memory_limit() -> usize {
+    64 * 1024 * 1024 // 64MB
+}
+
+fn default_script_timeout() -> u32 {
+    5000
+}
+
+fn default_max_probe_concurrency() -> usize {
+    32
+}
+
+fn default_max_probe_timeout() -> u32 {
+    10000
+}
+
 impl IniSettings {
     /// Create a new settings instance with default values
     pub fn new() -> Self {
@@ -257,7 +317,8 @@ impl IniSettings {
         let proxy_config = parse_proxy(&self.proxy_config);
 
         // Process rule rename_node
-        import_items(&mut self.rename_node, false, &proxy_config, &self.base_path).await?;
+        let rule_import = RuleImportOptions::new(&proxy_config, self.cache_config);
+        import_match_rules(&mut self.rename_node, "@", &rule_import).await;
         self.parsed_rename = RegexMatchConfigs::from_ini_with_delimiter(&self.rename_node, "@");
 
         // Process stream rules
@@ -270,7 +331,7 @@ impl IniSettings {
         self.parsed_time_rule = RegexMatchConfigs::from_ini_with_delimiter(&self.time_rule, "|");
 
         // Process emoji rules
-        import_items(&mut self.emoji_rules, false, &proxy_config, &self.base_path).await?;
+        import_match_rules(&mut self.emoji_rules, ",", &rule_import).await;
         self.parsed_emoji_rules =
             RegexMatchConfigs::from_ini_with_delimiter(&self.emoji_rules, ",");
 
@@ -373,6 +434,7 @@ impl IniSettings {
             "filter_script" => self.filter_script = value.to_string(),
             "base_path" => self.base_path = value.to_string(),
             "clash_rule_base" => self.clash_base = value.to_string(),
+            "clash_dns" => self.clash_dns = value.to_string(),
             "surge_rule_base" => self.surge_base = value.to_string(),
             "surfboard_rule_base" => self.surfboard_base = value.to_string(),
             "mellow_rule_base" => self.mellow_base = value.to_string(),
@@ -383,6 +445,7 @@ impl IniSettings {
             "singbox_rule_base" => self.singbox_base = value.to_string(),
             "default_external_config" => self.default_ext_config = value.to_string(),
             "append_proxy_type" => self.append_type = parse_bool(value),
+            "normalize_names" => self.normalize_names = parse_bool(value),
             "proxy_config" => self.proxy_config = value.to_string(),
             "proxy_ruleset" => self.proxy_ruleset = value.to_string(),
             "proxy_subscription" => self.proxy_subscription = value.to_string(),
@@ -404,6 +467,7 @@ impl IniSettings {
             "udp_flag" => self.udp_flag = Some(parse_bool(value)),
             "tcp_fast_open_flag" => self.tfo_flag = Some(parse_bool(value)),
             "skip_cert_verify_flag" => self.skip_cert_verify = Some(parse_bool(value)),
+            "skip_cert_verify_policy" => self.skip_cert_verify_policy = value.to_string(),
             "tls13_flag" => self.tls13_flag = Some(parse_bool(value)),
             "sort_flag" => self.enable_sort = parse_bool(value),
             "sort_script" => self.sort_script = value.to_string(),
@@ -413,6 +477,15 @@ impl IniSettings {
             "clash_proxies_style" => self.clash_proxies_style = value.to_string(),
             "clash_proxy_groups_style" => self.clash_proxy_groups_style = value.to_string(),
             "singbox_add_clash_modes" => self.singbox_add_clash_modes = parse_bool(value),
+            "group_test_url" => self.group_test_url = value.to_string(),
+            "group_test_interval" => self.group_test_interval = value.parse().unwrap_or(0),
+            "group_test_tolerance" => self.group_test_tolerance = value.parse().unwrap_or(0),
+            "group_lazy" => self.group_lazy = Some(parse_bool(value)),
+            "provider_test_enable" => self.provider_test_enable = Some(parse_bool(value)),
+            "provider_test_url" => self.provider_test_url = value.to_string(),
+            "provider_test_interval" => self.provider_test_interval = value.parse().unwrap_or(0),
+            "provider_test_timeout" => self.provider_test_timeout = value.parse().ok(),
+            "provider_lazy" => self.provider_lazy = Some(parse_bool(value)),
             "rename_node" => self.rename_node.push(value.to_string()),
             _ => {}
         }
@@ -455,6 +528,7 @@ impl IniSettings {
             "enabled" => self.enable_rule_gen = parse_bool(value),
             "overwrite_original_rules" => self.overwrite_original_rules = parse_bool(value),
             "update_ruleset_on_request" => self.update_ruleset_on_request = parse_bool(value),
+            "surge_rule_section" => self.surge_rule_section = value.to_string(),
             "ruleset" | "surge_ruleset" => {
                 self.rulesets.push(value.to_string());
             }
@@ -553,6 +627,26 @@ impl IniSettings {
                     self.max_allowed_download_size = val
                 }
             }
+            "max_allowed_decoded_size" => {
+                if let Ok(val) = value.parse() {
+                    self.max_allowed_decoded_size = val
+                }
+            }
+            "max_allowed_nodes" => {
+                if let Ok(val) = value.parse() {
+                    self.max_allowed_nodes = val
+                }
+            }
+            "max_concurrent_conversions" => {
+                if let Ok(val) = value.parse() {
+                    self.max_concurrent_conversions = val
+                }
+            }
+            "max_queued_conversions" => {
+                if let Ok(val) = value.parse() {
+                    self.max_queued_conversions = val
+                }
+            }
             "enable_cache" => {
                 self.enable_cache = parse_bool(value);
             }
@@ -572,7 +666,29 @@ impl IniSettings {
                 }
             }
             "serve_cache_on_fetch_fail" => self.serve_cache_on_fetch_fail = parse_bool(value),
+            "ruleset_cache_dir" => self.ruleset_cache_dir = value.to_string(),
+            "serve_stale_ruleset" => self.serve_stale_ruleset = parse_bool(value),
             "script_clean_context" => self.script_clean_context = parse_bool(value),
+            "script_memory_limit" => {
+                if let Ok(val) = value.parse() {
+                    self.script_memory_limit = val
+                }
+            }
+            "script_timeout" => {
+                if let Ok(val) = value.parse() {
+                    self.script_timeout = val
+                }
+            }
+            "max_probe_concurrency" => {
+                if let Ok(val) = value.parse() {
+                    self.max_probe_concurrency = val
+                }
+            }
+            "max_probe_timeout" => {
+                if let Ok(val) = value.parse() {
+                    self.max_probe_timeout = val
+                }
+            }
             "async_fetch_ruleset" => self.async_fetch_ruleset = parse_bool(value),
             "skip_failed_links" => self.skip_failed_links = parse_bool(value),
             _ => {}
@@ -11,15 +11,18 @@ use std::sync::RwLock;
 
 use log::debug;
 use log::info;
+use log::warn;
 use serde_yaml;
 use toml;
 
 use crate::models::cron::CronTaskConfigs;
-use crate::models::proxy_group_config::ProxyGroupConfig;
+use crate::models::proxy_group_config::{GroupHealthCheck, ProviderHealthCheck, ProxyGroupConfig};
 use crate::models::ruleset::RulesetContent;
 use crate::models::RegexMatchConfig;
 use crate::models::RegexMatchConfigs;
 use crate::models::RulesetConfig;
+use crate::models::SkipCertVerifyPolicy;
+use crate::settings::settings::toml_settings::TomlSettings;
 use crate::utils::file::copy_file;
 use crate::utils::file_exists;
 use crate::utils::file_get_async;
@@ -145,6 +148,8 @@ pub struct Settings {
     pub enable_rule_gen: bool,
     pub update_ruleset_on_request: bool,
     pub overwrite_original_rules: bool,
+    /// Surge `[Rule]` section added to the rulesets, a local file or URL
+    pub surge_rule_section: String,
     pub append_userinfo: bool,
     pub async_fetch_ruleset: bool,
     pub surge_resolve_hostname: bool,
@@ -153,6 +158,15 @@ pub struct Settings {
     pub custom_group: String,
     pub log_level: u32,
     pub max_allowed_download_size: i64,
+    /// Maximum size of a decoded subscription in bytes, 0 for no limit
+    pub max_allowed_decoded_size: i64,
+    /// Maximum number of nodes in one conversion, 0 for no limit
+    pub max_allowed_nodes: usize,
+    /// Maximum number of conversions running at once, 0 for no limit
+    pub max_concurrent_conversions: usize,
+    /// Maximum number of conversions waiting for a free slot, further
+    /// requests are turned away
+    pub max_queued_conversions: usize,
     pub template_path: String,
     /// Template variables used for template rendering
     pub template_vars: HashMap<String, String>,
@@ -169,10 +183,14 @@ pub struct Settings {
     pub add_emoji: bool,
     pub remove_emoji: bool,
     pub append_type: bool,
+    /// Name unnamed nodes and number duplicate names right after parsing
+    pub normalize_names: bool,
     pub filter_deprecated: bool,
     pub udp_flag: Option<bool>,
     pub tfo_flag: Option<bool>,
     pub skip_cert_verify: Option<bool>,
+    /// Which nodes `skip_cert_verify` applies to
+    pub skip_cert_verify_policy: SkipCertVerifyPolicy,
     pub tls13_flag: Option<bool>,
     pub enable_insert: bool,
     pub enable_sort: bool,
@@ -181,6 +199,12 @@ pub struct Settings {
     pub singbox_add_clash_modes: bool,
     pub clash_proxies_style: String,
     pub clash_proxy_groups_style: String,
+    /// Health check of url-test, fallback and load-balance groups that set
+    /// none
+    pub group_health_check: GroupHealthCheck,
+    /// Health check of the provider of Clash output with
+    /// `clash_use_providers`
+    pub provider_health_check: ProviderHealthCheck,
     pub proxy_config: String,
     pub proxy_ruleset: String,
     pub proxy_subscription: String,
@@ -191,6 +215,8 @@ pub struct Settings {
 
     // Base configs
     pub clash_base: String,
+    /// File or URL with the `dns` section injected into Clash configs
+    pub clash_dns: String,
     pub surge_base: String,
     pub surfboard_base: String,
     pub mellow_base: String,
@@ -207,6 +233,10 @@ pub struct Settings {
     pub cache_subscription: u32,
     pub cache_config: u32,
     pub cache_ruleset: u32,
+    /// Directory fetched rulesets are also cached in, empty for memory only
+    pub ruleset_cache_dir: String,
+    /// Whether the last copy of a ruleset is served when fetching it fails
+    pub serve_stale_ruleset: bool,
 
     // Server
     pub listen_address: String,
@@ -218,6 +248,14 @@ pub struct Settings {
     pub max_allowed_rulesets: usize,
     pub max_allowed_rules: usize,
     pub script_clean_context: bool,
+    /// Memory a filter script may allocate in bytes, 0 for no limit
+    pub script_memory_limit: usize,
+    /// Milliseconds a filter script may run for, 0 for no limit
+    pub script_timeout: u32,
+    /// Most liveness probes a request may run at once
+    pub max_probe_concurrency: usize,
+    /// Longest liveness probe timeout in milliseconds a request may ask for
+    pub max_probe_timeout: u32,
 
     // Cron system
     pub enable_cron: bool,
@@ -253,6 +291,14 @@ pub fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
 
+pub fn default_max_decoded_size() -> i64 {
+    32 * 1024 * 1024 // 32MB
+}
+
+pub fn default_max_nodes() -> usize {
+    50000
+}
+
 pub fn default_cache_subscription() -> u32 {
     60
 }
@@ -273,6 +319,22 @@ pub fn default_max_rules() -> usize {
     32768
 }
 
+pub fn default_script_memory_limit() -> usize {
+    64 * 1024 * 1024 // 64MB
+}
+
+pub fn default_script_timeout() -> u32 {
+    5000
+}
+
+pub fn default_max_probe_concurrency() -> usize {
+    32
+}
+
+pub fn default_max_probe_timeout() -> u32 {
+    10000
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
@@ -300,6 +362,7 @@ impl Default for Settings {
             enable_rule_gen: default_true(),
             update_ruleset_on_request: false,
             overwrite_original_rules: false,
+            surge_rule_section: String::new(),
             append_userinfo: default_true(),
             async_fetch_ruleset: false,
             surge_resolve_hostname: false,
@@ -308,6 +371,10 @@ impl Default for Settings {
             custom_group: String::new(),
             log_level: default_log_level(),
             max_allowed_download_size: default_max_download_size(),
+            max_allowed_decoded_size: default_max_decoded_size(),
+            max_allowed_nodes: default_max_nodes(),
+            max_concurrent_conversions: 0,
+            max_queued_conversions: 0,
             template_path: String::new(),
             template_vars: HashMap::new(),
 
@@ -321,10 +388,12 @@ impl Default for Settings {
             add_emoji: false,
             remove_emoji: false,
             append_type: false,
+            normalize_names: false,
             filter_deprecated: default_true(),
             udp_flag: None,
             tfo_flag: None,
             skip_cert_verify: None,
+            skip_cert_verify_policy: SkipCertVerifyPolicy::default(),
             tls13_flag: None,
             enable_insert: false,
             enable_sort: false,
@@ -333,6 +402,8 @@ impl Default for Settings {
             singbox_add_clash_modes: false,
             clash_proxies_style: String::new(),
             clash_proxy_groups_style: String::new(),
+            group_health_check: GroupHealthCheck::default(),
+            provider_health_check: ProviderHealthCheck::default(),
             proxy_config: String::new(),
             proxy_ruleset: String::new(),
             proxy_subscription: String::new(),
@@ -342,6 +413,7 @@ impl Default for Settings {
             enable_filter: false,
             // Base configs
             clash_base: String::new(),
+            clash_dns: String::new(),
             surge_base: String::new(),
             surfboard_base: String::new(),
             mellow_base: String::new(),
@@ -358,6 +430,8 @@ impl Default for Settings {
             cache_subscription: default_cache_subscription(),
             cache_config: default_cache_config(),
             cache_ruleset: default_cache_ruleset(),
+            ruleset_cache_dir: String::new(),
+            serve_stale_ruleset: false,
 
             // Server
             listen_address: default_listen_address(),
@@ -368,7 +442,11 @@ impl Default for Settings {
             // Limits
             max_allowed_rulesets: default_max_rulesets(),
             max_allowed_rules: default_max_rules(),
-            script_clean_context: false,
+            script_clean_context: true,
+            script_memory_limit: default_script_memory_limit(),
+            script_timeout: default_script_timeout(),
+            max_probe_concurrency: default_max_probe_concurrency(),
+            max_probe_timeout: default_max_probe_timeout(),
 
             // Cron system
             enable_cron: false,
@@ -396,6 +474,10 @@ impl Settings {
     }
 
     /// Get a read-only reference to the current settings
+    ///
+    /// The guard holds the lock and so blocks [`Settings::reload`] until it
+    /// is dropped; code awaiting while it reads the settings takes a
+    /// [`Settings::snapshot`] instead.
     pub fn current() -> GlobalLockReadGuard<'static, Arc<Settings>> {
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -407,6 +489,29 @@ impl Settings {
         }
     }
 
+    /// Get the current settings without holding the lock
+    ///
+    /// The snapshot may be kept across awaits and does not change when the
+    /// settings are reloaded meanwhile.
+    pub fn snapshot() -> Arc<Settings> {
+        Arc::clone(&Self::current())
+    }
+
+    /// Replace the global settings as a whole
+    ///
+    /// Readers see either the old or the new settings, never a mix of both.
+    /// A conversion copies what it needs from the global settings when its
+    /// [`ExtraSettings`](crate::models::ExtraSettings) is created with
+    /// `ExtraSettings::default()` and keeps those values; only settings it
+    /// reads directly later on, such as the ruleset cache, come from the
+    /// reloaded ones.
+    ///
+    /// # Returns
+    /// The settings replaced
+    pub fn reload(settings: Settings) -> Arc<Settings> {
+        std::mem::replace(&mut *Self::current_mut(), Arc::new(settings))
+    }
+
     pub async fn load_from_content(
         content: &str,
         file_path: &str,
@@ -431,19 +536,7 @@ impl Settings {
 
         // Try to parse as TOML
         if toml::from_str::<toml::Value>(&content).is_ok() {
-            let mut toml_settings: crate::settings::settings::toml_settings::TomlSettings =
-                toml::from_str(&content)?;
-
-            toml_settings.process_imports().await?;
-
-            let mut settings = Settings::from(toml_settings);
-
-            // Ensure listen_address is not empty
-            if settings.listen_address.trim().is_empty() {
-                settings.listen_address = default_listen_address();
-            }
-
-            return Ok(settings);
+            return Settings::from_toml(content).await;
         }
 
         if file_path.ends_with(".toml") {
@@ -469,6 +562,45 @@ impl Settings {
         Ok(settings)
     }
 
+    /// Load settings from TOML content
+    ///
+    /// This is the TOML branch of [`Settings::load_from_content`], so both
+    /// give identical settings for the same document. Unknown keys are
+    /// logged and skipped.
+    pub async fn from_toml(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let value: toml::Value = toml::from_str(content)?;
+        for key in TomlSettings::unknown_keys(&value) {
+            warn!("Unknown TOML setting '{}', ignored", key);
+        }
+
+        let mut toml_settings: TomlSettings = value.try_into()?;
+        toml_settings.process_imports().await?;
+
+        let mut settings = Settings::from(toml_settings);
+
+        // Ensure listen_address is not empty
+        if settings.listen_address.trim().is_empty() {
+            settings.listen_address = default_listen_address();
+        }
+
+        Ok(settings)
+    }
+
+    /// Load settings from a TOML file or URL
+    pub async fn from_toml_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = if path.starts_with("http://") || path.starts_with("https://") {
+            web_get_async(path, &ProxyConfig::default(), None)
+                .await?
+                .body
+        } else {
+            file_get_async(path, None).await?
+        };
+
+        let mut settings = Settings::from_toml(&content).await?;
+        settings.pref_path = path.to_string();
+        Ok(settings)
+    }
+
     /// Load settings from file or URL asynchronously
     pub async fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let mut _content = String::new();
@@ -503,24 +635,12 @@ fn get_global() -> &'static GlobalLock<Arc<Settings>> {
 
 /// Refresh the configuration asynchronously
 pub async fn refresh_configuration() {
-    #[cfg(not(target_arch = "wasm32"))]
-    let settings = GLOBAL.read().unwrap();
-    #[cfg(target_arch = "wasm32")]
-    let settings = get_global().read().unwrap();
-
-    let path = settings.pref_path.clone();
-    drop(settings); // Release the lock before potential long operation
+    // Release the lock before potential long operation
+    let path = Settings::snapshot().pref_path.clone();
 
     match Settings::load_from_file(&path).await {
         Ok(new_settings) => {
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                *GLOBAL.write().unwrap() = Arc::new(new_settings);
-            }
-            #[cfg(target_arch = "wasm32")]
-            {
-                *get_global().write().unwrap() = Arc::new(new_settings);
-            }
+            Settings::reload(new_settings);
         }
         Err(err) => {
             eprintln!("Failed to refresh configuration from '{}': {}", path, err);
@@ -535,14 +655,7 @@ pub async fn update_settings_from_file(path: &str) -> Result<(), Box<dyn std::er
     match Settings::load_from_file(&path).await {
         Ok(new_settings) => {
             debug!("Updating settings from file: {}", path);
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                *GLOBAL.write().unwrap() = Arc::new(new_settings);
-            }
-            #[cfg(target_arch = "wasm32")]
-            {
-                *get_global().write().unwrap() = Arc::new(new_settings);
-            }
+            Settings::reload(new_settings);
             Ok(())
         }
         Err(err) => {
@@ -599,14 +712,7 @@ pub async fn update_settings_from_content(content: &str) -> Result<(), Box<dyn s
 
     match Settings::load_from_content(&content, "").await {
         Ok(settings) => {
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                *GLOBAL.write().unwrap() = Arc::new(settings);
-            }
-            #[cfg(target_arch = "wasm32")]
-            {
-                *get_global().write().unwrap() = Arc::new(settings);
-            }
+            Settings::reload(settings);
             Ok(())
         }
         Err(err) => {
@@ -615,3 +721,49 @@ pub async fn update_settings_from_content(content: &str) -> Result<(), Box<dyn s
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn test_reload_is_atomic() {
+        // Only the listen fields change, other tests reading the global
+        // settings meanwhile see the same values
+        let original = Settings::snapshot();
+        let generation = |n: u32| Settings {
+            listen_address: format!("reload-{}", n),
+            listen_port: n,
+            ..(*original).clone()
+        };
+        let consistent = |settings: &Settings| {
+            settings.listen_address == format!("reload-{}", settings.listen_port)
+        };
+        Settings::reload(generation(0));
+        let kept = Settings::snapshot();
+
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        assert!(consistent(&Settings::snapshot()));
+                        assert!(consistent(&Settings::current()));
+                    }
+                });
+            }
+            for n in 1..=500 {
+                Settings::reload(generation(n));
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+
+        // A snapshot outlives reloads unchanged
+        assert_eq!(kept.listen_port, 0);
+        assert_eq!(Settings::snapshot().listen_port, 500);
+        let replaced = Settings::reload((*original).clone());
+        assert_eq!(replaced.listen_address, "reload-500");
+    }
+}
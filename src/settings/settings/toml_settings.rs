@@ -10,10 +10,12 @@ use crate::{
     },
     settings::{
         import_toml::import_toml_items,
+        import_toml_match_rules,
         toml_deserializer::{
             deserialize_template_as_template_settings, ProxyGroupConfigInToml,
             RegexMatchRuleInToml, RulesetConfigInToml, TaskConfigInToml,
         },
+        RuleImportOptions,
     },
     utils::http::parse_proxy,
 };
@@ -80,9 +82,33 @@ fn default_max_rules() -> usize {
     32768
 }
 
+fn default_script_memory_limit() -> usize {
+    64 * 1024 * 1024 // 64MB
+}
+
+fn default_script_timeout() -> u32 {
+    5000
+}
+
+fn default_max_probe_concurrency() -> usize {
+    32
+}
+
+fn default_max_probe_timeout() -> u32 {
+    10000
+}
+
 fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
+
+fn default_max_decoded_size() -> i64 {
+    32 * 1024 * 1024 // 32MB
+}
+
+fn default_max_nodes() -> usize {
+    50000
+}
 /// User info settings
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
@@ -113,6 +139,7 @@ pub struct CommonSettings {
     #[serde(default = "default_empty_string")]
     pub base_path: String,
     pub clash_rule_base: String,
+    pub clash_dns: String,
     pub surge_rule_base: String,
     pub surfboard_rule_base: String,
     pub mellow_rule_base: String,
@@ -128,6 +155,7 @@ pub struct CommonSettings {
     #[serde(default = "default_none")]
     pub proxy_subscription: String,
     pub append_proxy_type: bool,
+    pub normalize_names: bool,
     pub reload_conf_on_request: bool,
 }
 
@@ -138,6 +166,7 @@ pub struct NodePreferences {
     pub udp_flag: Option<bool>,
     pub tcp_fast_open_flag: Option<bool>,
     pub skip_cert_verify_flag: Option<bool>,
+    pub skip_cert_verify_policy: String,
     pub tls13_flag: Option<bool>,
     pub sort_flag: bool,
     pub sort_script: String,
@@ -150,6 +179,15 @@ pub struct NodePreferences {
     pub clash_proxies_style: String,
     #[serde(default = "default_empty_string")]
     pub clash_proxy_groups_style: String,
+    pub group_test_url: String,
+    pub group_test_interval: u32,
+    pub group_test_tolerance: u32,
+    pub group_lazy: Option<bool>,
+    pub provider_test_enable: Option<bool>,
+    pub provider_test_url: String,
+    pub provider_test_interval: u32,
+    pub provider_test_timeout: Option<u32>,
+    pub provider_lazy: Option<bool>,
     pub singbox_add_clash_modes: bool,
     pub rename_node: Vec<RegexMatchRuleInToml>,
 }
@@ -198,6 +236,7 @@ pub struct RulesetSettings {
     pub enabled: bool,
     pub overwrite_original_rules: bool,
     pub update_ruleset_on_request: bool,
+    pub surge_rule_section: String,
 }
 
 /// Template variable
@@ -261,6 +300,12 @@ pub struct AdvancedSettings {
     pub max_allowed_rules: usize,
     #[serde(default = "default_max_download_size")]
     pub max_allowed_download_size: i64,
+    #[serde(default = "default_max_decoded_size")]
+    pub max_allowed_decoded_size: i64,
+    #[serde(default = "default_max_nodes")]
+    pub max_allowed_nodes: usize,
+    pub max_concurrent_conversions: usize,
+    pub max_queued_conversions: usize,
     pub enable_cache: bool,
     #[serde(default = "default_cache_subscription")]
     pub cache_subscription: u32,
@@ -268,7 +313,18 @@ pub struct AdvancedSettings {
     pub cache_config: u32,
     #[serde(default = "default_cache_ruleset")]
     pub cache_ruleset: u32,
+    pub ruleset_cache_dir: String,
+    pub serve_stale_ruleset: bool,
+    #[serde(default = "default_true")]
     pub script_clean_context: bool,
+    #[serde(default = "default_script_memory_limit")]
+    pub script_memory_limit: usize,
+    #[serde(default = "default_script_timeout")]
+    pub script_timeout: u32,
+    #[serde(default = "default_max_probe_concurrency")]
+    pub max_probe_concurrency: usize,
+    #[serde(default = "default_max_probe_timeout")]
+    pub max_probe_timeout: u32,
     pub async_fetch_ruleset: bool,
     pub skip_failed_links: bool,
 }
@@ -319,18 +375,12 @@ pub struct TomlSettings {
 
 impl TomlSettings {
     pub async fn process_imports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let global = Settings::current();
+        let global = Settings::snapshot();
         let proxy_config = parse_proxy(&self.common.proxy_config);
 
         // Process rename nodes
-        import_toml_items(
-            &mut self.node_pref.rename_node,
-            false,
-            "rename_node",
-            &proxy_config,
-            &self.common.base_path,
-        )
-        .await?;
+        let rule_import = RuleImportOptions::new(&proxy_config, self.advanced.cache_config);
+        import_toml_match_rules(&mut self.node_pref.rename_node, "rename_node", &rule_import).await;
         self.parsed_rename = self
             .node_pref
             .rename_node
@@ -371,14 +421,7 @@ impl TomlSettings {
             .collect();
 
         // Process emoji rules
-        import_toml_items(
-            &mut self.emojis.emoji,
-            false,
-            "emoji",
-            &proxy_config,
-            &self.common.base_path,
-        )
-        .await?;
+        import_toml_match_rules(&mut self.emojis.emoji, "emoji", &rule_import).await;
         self.parsed_emoji_rules = self.emojis.emoji.iter().map(|r| r.clone().into()).collect();
 
         // Process rulesets
@@ -430,4 +473,188 @@ impl TomlSettings {
 
         Ok(())
     }
+
+    /// List the keys of a TOML document that no setting reads
+    ///
+    /// Keys are reported as dotted paths, e.g. `node_pref.udp`. The items of
+    /// arrays such as `rulesets` are not checked.
+    pub fn unknown_keys(value: &toml::Value) -> Vec<String> {
+        // The keys of each section are the field names and aliases serde
+        // reads, so they cannot drift from the structs
+        let sections: [(&str, &[&str]); 10] = [
+            ("common", serde_fields::<CommonSettings>()),
+            ("userinfo", serde_fields::<UserInfoSettings>()),
+            ("node_pref", serde_fields::<NodePreferences>()),
+            ("managed_config", serde_fields::<ManagedConfigSettings>()),
+            (
+                "surge_external_proxy",
+                serde_fields::<SurgeExternalProxySettings>(),
+            ),
+            ("emojis", serde_fields::<EmojiSettings>()),
+            ("ruleset", serde_fields::<RulesetSettings>()),
+            ("template", serde_fields::<TemplateSettings>()),
+            ("server", serde_fields::<ServerSettings>()),
+            ("advanced", serde_fields::<AdvancedSettings>()),
+        ];
+        let top = serde_fields::<TomlSettings>();
+
+        let mut unknown = Vec::new();
+        for (key, value) in value.as_table().into_iter().flatten() {
+            // `template.globals` is declared with a dotted name, so its
+            // first segment counts as known
+            if !top
+                .iter()
+                .any(|name| name.split('.').next() == Some(key.as_str()))
+            {
+                unknown.push(key.clone());
+                continue;
+            }
+            let fields = sections
+                .iter()
+                .find(|(section, _)| section == key)
+                .map(|(_, fields)| *fields);
+            if let (Some(table), Some(fields)) = (value.as_table(), fields) {
+                unknown.extend(
+                    table
+                        .keys()
+                        .filter(|name| !fields.contains(&name.as_str()))
+                        .map(|name| format!("{}.{}", key, name)),
+                );
+            }
+        }
+        unknown
+    }
+}
+
+/// The field names and aliases `T` is deserialized from
+fn serde_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    use serde::de::{self, Visitor};
+
+    /// Records the fields of the struct asked for, then gives up
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML_PREF: &str = r#"
+[common]
+api_mode = false
+
+[node_pref]
+udp_flag = true
+clash_proxies_style = "block"
+unknown_flag = true
+
+[surge_external_proxy]
+surge_ssr_path = "/usr/bin/ssr-local"
+
+[ruleset]
+enabled = true
+overwrite_original_rules = true
+surge_rule_section = "rules/surge.conf"
+
+[[rulesets]]
+group = "DIRECT"
+ruleset = "[]GEOIP,CN"
+
+[template]
+template_path = "./templates"
+
+[extras]
+foo = 1
+"#;
+
+    #[test]
+    fn test_unknown_keys() {
+        let value: toml::Value = toml::from_str(TOML_PREF).unwrap();
+        assert_eq!(
+            TomlSettings::unknown_keys(&value),
+            vec!["extras", "node_pref.unknown_flag"]
+        );
+
+        // Renamed fields are known by their TOML name, map sections are not
+        // checked
+        let value: toml::Value = toml::from_str(
+            "[common]\ndefault_url = []\ndefault_urls = []\n\
+             [template.globals]\nclash.port = \"7890\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            TomlSettings::unknown_keys(&value),
+            vec!["common.default_urls"]
+        );
+    }
+
+    #[test]
+    fn test_from_toml_matches_load_from_content() {
+        let settings = futures::executor::block_on(Settings::from_toml(TOML_PREF)).unwrap();
+        assert!(settings.enable_rule_gen);
+        assert!(settings.overwrite_original_rules);
+        assert_eq!(settings.surge_rule_section, "rules/surge.conf");
+        assert_eq!(settings.surge_ssr_path, "/usr/bin/ssr-local");
+        assert_eq!(settings.clash_proxies_style, "block");
+        assert_eq!(settings.udp_flag, Some(true));
+
+        let loaded =
+            futures::executor::block_on(Settings::load_from_content(TOML_PREF, "pref.toml"))
+                .unwrap();
+        assert_eq!(format!("{:?}", loaded), format!("{:?}", settings));
+
+        // The rule section goes with rule generation, as in the INI file
+        let pref = TOML_PREF.replace("enabled = true", "enabled = false");
+        let settings = futures::executor::block_on(Settings::from_toml(&pref)).unwrap();
+        assert!(!settings.enable_rule_gen);
+        assert!(settings.surge_rule_section.is_empty());
+    }
+
+    #[test]
+    fn test_provider_health_check() {
+        let pref = "[node_pref]\nprovider_test_enable = true\nprovider_test_interval = 600\n\
+                    provider_test_timeout = 2000\nprovider_lazy = true\n";
+        let value: toml::Value = toml::from_str(pref).unwrap();
+        assert!(TomlSettings::unknown_keys(&value).is_empty());
+
+        let settings = futures::executor::block_on(Settings::from_toml(pref)).unwrap();
+        let check = settings.provider_health_check;
+        assert!(check.enable);
+        assert_eq!(check.url, "http://www.gstatic.com/generate_204");
+        assert_eq!((check.interval, check.timeout), (600, Some(2000)));
+        assert_eq!(check.lazy, Some(true));
+    }
+
+    #[test]
+    fn test_group_lazy_is_known() {
+        let value: toml::Value = toml::from_str("[node_pref]\ngroup_lazy = false\n").unwrap();
+        assert!(TomlSettings::unknown_keys(&value).is_empty());
+    }
 }
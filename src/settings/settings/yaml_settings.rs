@@ -7,7 +7,7 @@ use crate::{
     models::{
         cron::CronTaskConfigs, ruleset::RulesetConfigs, ProxyGroupConfigs, RegexMatchConfigs,
     },
-    settings::{import_items, yaml_deserializer::*},
+    settings::{import_items, import_match_rules, yaml_deserializer::*, RuleImportOptions},
     utils::http::parse_proxy,
 };
 
@@ -73,10 +73,34 @@ fn default_max_rules() -> usize {
     32768
 }
 
+fn default_script_memory_limit() -> usize {
+    64 * 1024 * 1024 // 64MB
+}
+
+fn default_script_timeout() -> u32 {
+    5000
+}
+
+fn default_max_probe_concurrency() -> usize {
+    32
+}
+
+fn default_max_probe_timeout() -> u32 {
+    10000
+}
+
 fn default_max_download_size() -> i64 {
     32 * 1024 * 1024 // 32MB
 }
 
+fn default_max_decoded_size() -> i64 {
+    32 * 1024 * 1024 // 32MB
+}
+
+fn default_max_nodes() -> usize {
+    50000
+}
+
 /// User info settings
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
@@ -105,6 +129,7 @@ pub struct CommonSettings {
     #[serde(default = "default_empty_string")]
     pub base_path: String,
     pub clash_rule_base: String,
+    pub clash_dns: String,
     pub surge_rule_base: String,
     pub surfboard_rule_base: String,
     pub mellow_rule_base: String,
@@ -120,6 +145,7 @@ pub struct CommonSettings {
     #[serde(default = "default_none")]
     pub proxy_subscription: String,
     pub append_proxy_type: bool,
+    pub normalize_names: bool,
     pub reload_conf_on_request: bool,
 }
 
@@ -130,6 +156,7 @@ pub struct NodePreferences {
     pub udp_flag: Option<bool>,
     pub tcp_fast_open_flag: Option<bool>,
     pub skip_cert_verify_flag: Option<bool>,
+    pub skip_cert_verify_policy: String,
     pub tls13_flag: Option<bool>,
     pub sort_flag: bool,
     pub sort_script: String,
@@ -140,6 +167,15 @@ pub struct NodePreferences {
     pub clash_use_new_field_name: bool,
     pub clash_proxies_style: String,
     pub clash_proxy_groups_style: String,
+    pub group_test_url: String,
+    pub group_test_interval: u32,
+    pub group_test_tolerance: u32,
+    pub group_lazy: Option<bool>,
+    pub provider_test_enable: Option<bool>,
+    pub provider_test_url: String,
+    pub provider_test_interval: u32,
+    pub provider_test_timeout: Option<u32>,
+    pub provider_lazy: Option<bool>,
     pub singbox_add_clash_modes: bool,
     pub rename_node: Vec<RegexMatchRuleInYaml>,
 }
@@ -189,6 +225,7 @@ pub struct RulesetSettings {
     pub enabled: bool,
     pub overwrite_original_rules: bool,
     pub update_ruleset_on_request: bool,
+    pub surge_rule_section: String,
     #[serde(alias = "surge_ruleset")]
     pub rulesets: Vec<RulesetConfigInYaml>,
 }
@@ -261,6 +298,12 @@ pub struct AdvancedSettings {
     pub max_allowed_rules: usize,
     #[serde(default = "default_max_download_size")]
     pub max_allowed_download_size: i64,
+    #[serde(default = "default_max_decoded_size")]
+    pub max_allowed_decoded_size: i64,
+    #[serde(default = "default_max_nodes")]
+    pub max_allowed_nodes: usize,
+    pub max_concurrent_conversions: usize,
+    pub max_queued_conversions: usize,
     pub enable_cache: bool,
     #[serde(default = "default_cache_subscription")]
     pub cache_subscription: u32,
@@ -268,8 +311,18 @@ pub struct AdvancedSettings {
     pub cache_config: u32,
     #[serde(default = "default_cache_ruleset")]
     pub cache_ruleset: u32,
+    pub ruleset_cache_dir: String,
+    pub serve_stale_ruleset: bool,
     #[serde(default = "default_true")]
     pub script_clean_context: bool,
+    #[serde(default = "default_script_memory_limit")]
+    pub script_memory_limit: usize,
+    #[serde(default = "default_script_timeout")]
+    pub script_timeout: u32,
+    #[serde(default = "default_max_probe_concurrency")]
+    pub max_probe_concurrency: usize,
+    #[serde(default = "default_max_probe_timeout")]
+    pub max_probe_timeout: u32,
     pub async_fetch_ruleset: bool,
     pub skip_failed_links: bool,
 }
@@ -329,13 +382,8 @@ impl YamlSettings {
             .map(|rule| rule.to_ini_with_delimiter("@"))
             .collect::<Vec<String>>();
 
-        import_items(
-            &mut rename_nodes,
-            false,
-            proxy_config,
-            &self.common.base_path,
-        )
-        .await?;
+        let rule_import = RuleImportOptions::new(proxy_config, self.advanced.cache_config);
+        import_match_rules(&mut rename_nodes, "@", &rule_import).await;
         self.parsed_rename = RegexMatchConfigs::from_ini_with_delimiter(&rename_nodes, "@");
 
         // read streamrule
@@ -372,13 +420,7 @@ impl YamlSettings {
             .iter()
             .map(|rule| rule.to_ini_with_delimiter(","))
             .collect::<Vec<String>>();
-        import_items(
-            &mut emoji_rules,
-            false,
-            proxy_config,
-            &self.common.base_path,
-        )
-        .await?;
+        import_match_rules(&mut emoji_rules, ",", &rule_import).await;
         self.parsed_emoji_rules = RegexMatchConfigs::from_ini_with_delimiter(&emoji_rules, ",");
 
         // read rulesets
@@ -28,6 +28,7 @@ pub struct RegexMatchRuleInToml {
     pub replace: Option<String>,
     pub script: Option<String>,
     pub import: Option<String>,
+    pub literal: Option<bool>,
 }
 
 impl Into<RegexMatchConfig> for RegexMatchRuleInToml {
@@ -37,6 +38,7 @@ impl Into<RegexMatchConfig> for RegexMatchRuleInToml {
             self.replace.unwrap_or_default(),
             self.script.unwrap_or_default(),
         );
+        config.literal = self.literal.unwrap_or(false);
         config.compile();
         config
     }
@@ -84,14 +86,6 @@ impl Into<RulesetConfig> for RulesetConfigInToml {
     }
 }
 
-fn default_test_url() -> Option<String> {
-    Some("http://www.gstatic.com/generate_204".to_string())
-}
-
-fn default_interval() -> Option<u32> {
-    Some(300)
-}
-
 /// Proxy group configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
@@ -101,14 +95,19 @@ pub struct ProxyGroupConfigInToml {
     pub group_type: String,
     pub strategy: Option<String>,
     pub rule: Vec<String>,
-    #[serde(default = "default_test_url")]
+    /// Regex over node names selecting additional members
+    pub filter: Option<String>,
+    /// Health check options, unset ones fall back to the `group_test_*`
+    /// settings
     pub url: Option<String>,
-    #[serde(default = "default_interval")]
     pub interval: Option<u32>,
     pub lazy: Option<bool>,
     pub tolerance: Option<u32>,
     pub timeout: Option<u32>,
     pub disable_udp: Option<bool>,
+    /// Clash.Meta display options
+    pub icon: Option<String>,
+    pub hidden: Option<bool>,
     pub import: Option<String>,
 }
 
@@ -124,16 +123,7 @@ impl ImportableInToml for ProxyGroupConfigInToml {
 
 impl Into<ProxyGroupConfig> for ProxyGroupConfigInToml {
     fn into(self) -> ProxyGroupConfig {
-        let group_type = match self.group_type.as_str() {
-            "select" => ProxyGroupType::Select,
-            "url-test" => ProxyGroupType::URLTest,
-            "load-balance" => ProxyGroupType::LoadBalance,
-            "fallback" => ProxyGroupType::Fallback,
-            "relay" => ProxyGroupType::Relay,
-            "ssid" => ProxyGroupType::SSID,
-            "smart" => ProxyGroupType::Smart,
-            _ => ProxyGroupType::Select, // 默认为 Select
-        };
+        let group_type = self.group_type.parse().unwrap_or(ProxyGroupType::Select); // 默认为 Select
 
         // 处理 strategy 字段
         let strategy = match self.strategy.as_deref() {
@@ -147,17 +137,20 @@ impl Into<ProxyGroupConfig> for ProxyGroupConfigInToml {
             name: self.name,
             group_type,
             proxies: self.rule,
+            filter: self.filter.filter(|f| !f.is_empty()),
             url: self.url.unwrap_or_default(),
-            interval: self.interval.unwrap_or(300),
+            interval: self.interval.unwrap_or(0),
             tolerance: self.tolerance.unwrap_or(0),
             timeout: self.timeout.unwrap_or(5),
-            lazy: self.lazy.unwrap_or(false),
+            lazy: self.lazy,
             disable_udp: self.disable_udp.unwrap_or(false),
             strategy,
             // 添加缺失的字段
             persistent: false,
             evaluate_before_use: false,
             using_provider: Vec::new(),
+            icon: self.icon.filter(|icon| !icon.is_empty()),
+            hidden: self.hidden.unwrap_or(false),
         };
 
         // 根据不同的代理组类型设置特定属性
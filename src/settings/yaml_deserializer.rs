@@ -16,6 +16,7 @@ pub struct RegexMatchRuleInYaml {
     pub replace: Option<String>,
     pub script: Option<String>,
     pub import: Option<String>,
+    pub literal: Option<bool>,
 }
 
 /// Trait for converting to INI format with a specified delimiter
@@ -42,7 +43,12 @@ impl ToIniWithDelimiter for RegexMatchRuleInYaml {
         // Finally check for match and replace
         if let (Some(match_str), Some(replace)) = (&self.match_str, &self.replace) {
             if !match_str.is_empty() && !replace.is_empty() {
-                return format!("{}{}{}", match_str, delimiter, replace);
+                let prefix = if self.literal.unwrap_or(false) {
+                    "!!literal:"
+                } else {
+                    ""
+                };
+                return format!("{}{}{}{}", prefix, match_str, delimiter, replace);
             }
         }
 
@@ -113,12 +119,17 @@ pub struct ProxyGroupConfigInYaml {
     #[serde(rename = "type")]
     pub group_type: String,
     pub rule: Vec<String>,
-    #[serde(default = "default_test_url")]
+    /// Regex over node names selecting additional members
+    pub filter: Option<String>,
+    /// Health check options, unset ones fall back to the `group_test_*`
+    /// settings
     pub url: Option<String>,
-    #[serde(default = "default_interval")]
     pub interval: Option<u32>,
     pub tolerance: Option<u32>,
     pub timeout: Option<u32>,
+    /// Clash.Meta display options
+    pub icon: Option<String>,
+    pub hidden: Option<bool>,
     pub import: Option<String>,
 }
 
@@ -138,6 +149,15 @@ impl ToIni for ProxyGroupConfigInYaml {
         for rule in &self.rule {
             temp_array.push(rule.clone());
         }
+        if let Some(filter) = self.filter.as_deref().filter(|f| !f.is_empty()) {
+            temp_array.push(format!("!!FILTER={}", filter));
+        }
+        if let Some(icon) = self.icon.as_deref().filter(|icon| !icon.is_empty()) {
+            temp_array.push(format!("!!ICON={}", icon));
+        }
+        if self.hidden == Some(true) {
+            temp_array.push("!!HIDDEN".to_string());
+        }
 
         // Check if we have enough elements based on group type
         match self.group_type.as_str() {
@@ -156,15 +176,14 @@ impl ToIni for ProxyGroupConfigInYaml {
                     return String::new();
                 }
 
-                // Add url
-                temp_array.push(
-                    self.url
-                        .clone()
-                        .unwrap_or_else(|| "http://www.gstatic.com/generate_204".to_string()),
-                );
+                // Add url, empty when unset
+                temp_array.push(self.url.clone().unwrap_or_default());
 
                 // Add interval, timeout, tolerance as a combined string
-                let interval = self.interval.unwrap_or(300).to_string();
+                let interval = match self.interval {
+                    Some(i) => i.to_string(),
+                    None => String::new(),
+                };
                 let timeout = match self.timeout {
                     Some(t) => t.to_string(),
                     None => String::new(),
@@ -183,14 +202,6 @@ impl ToIni for ProxyGroupConfigInYaml {
     }
 }
 
-fn default_test_url() -> Option<String> {
-    Some("http://www.gstatic.com/generate_204".to_string())
-}
-
-fn default_interval() -> Option<u32> {
-    Some(300)
-}
-
 /// Task configuration
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
@@ -214,7 +214,7 @@ fn filter_find(value: Value, pattern: Value) -> Result<bool, JinjaError> {
 
 fn fn_get_link(path: Value) -> Result<String, JinjaError> {
     let path_str = path.to_string();
-    let settings = Settings::current();
+    let settings = Settings::snapshot();
     Ok(format!("{}{}", settings.managed_config_prefix, path_str))
 }
 
@@ -91,7 +91,7 @@ pub async fn upload_gist(
         }
     }
 
-    let proxy_config = parse_proxy(&Settings::current().proxy_config);
+    let proxy_config = parse_proxy(&Settings::snapshot().proxy_config);
     let mut headers = HashMap::new();
     headers.insert(
         CaseInsensitiveString::new("Authorization"),
@@ -58,3 +58,463 @@ pub fn url_safe_base64_decode(input: &str) -> String {
 pub fn url_safe_base64_encode(input: &str) -> String {
     url_safe_base64_apply(&base64_encode(input))
 }
+
+/// Decodes a subscription body that may be base64 or a plain node list.
+///
+/// Base64 bodies are accepted with either alphabet, with or without padding
+/// and with line breaks or trailing whitespace. The decoded text is only
+/// used when it looks like a node list; anything else, including bodies that
+/// are not base64 at all, is returned as trimmed plain text.
+pub fn decode_subscription_body(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    let text = text.trim_start_matches('\u{feff}').trim();
+    if looks_like_node_list(text) {
+        return text.to_string();
+    }
+
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let compact = compact.trim_end_matches('=');
+    if compact.is_empty()
+        || !compact
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '-' | '_'))
+    {
+        return text.to_string();
+    }
+
+    let config = general_purpose::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone)
+        .with_decode_allow_trailing_bits(true);
+    for alphabet in [&base64::alphabet::URL_SAFE, &base64::alphabet::STANDARD] {
+        let engine = general_purpose::GeneralPurpose::new(alphabet, config);
+        let Ok(decoded) = engine.decode(compact) else {
+            continue;
+        };
+        let decoded = String::from_utf8_lossy(&decoded);
+        let decoded = decoded.trim_start_matches('\u{feff}').trim();
+        if looks_like_node_list(decoded) {
+            return decoded.to_string();
+        }
+    }
+
+    text.to_string()
+}
+
+/// Size in bytes of a subscription body once decoded
+///
+/// Follows [`decode_subscription_body`] without decoding: a base64 body
+/// counts the bytes it decodes to, any other body its own length.
+pub fn decoded_body_size(body: &str) -> usize {
+    let text = body.trim_start_matches('\u{feff}').trim();
+    if looks_like_node_list(text) {
+        return text.len();
+    }
+    let mut digits = 0;
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '-' | '_') {
+            digits += 1;
+        } else if !c.is_whitespace() && c != '=' {
+            return text.len();
+        }
+    }
+    digits * 3 / 4
+}
+
+/// Whether text looks like a list of proxy links or a proxy config
+fn looks_like_node_list(text: &str) -> bool {
+    text.lines().map(str::trim).any(|line| {
+        let is_link = line.split_once("://").is_some_and(|(scheme, rest)| {
+            !scheme.is_empty()
+                && !rest.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        });
+        is_link
+            || line.starts_with("proxies:")
+            || line.starts_with("Proxy:")
+            || line.starts_with("[Proxy]")
+            || ["vmess=", "shadowsocks=", "trojan=", "http="]
+                .iter()
+                .any(|key| line.starts_with(key))
+            || line.split_once('=').is_some_and(|(_, value)| {
+                let value = value.trim_start();
+                [
+                    "ss,",
+                    "vmess,",
+                    "trojan,",
+                    "http,",
+                    "socks5,",
+                    "shadowsocks,",
+                ]
+                .iter()
+                .any(|kind| value.starts_with(kind))
+            })
+    })
+}
+
+/// Significant characters looked at before a body without any character
+/// outside the base64 alphabets is taken for base64
+const SNIFF_LEN: usize = 64;
+
+/// UTF-8 byte order mark
+const BOM: &[u8] = "\u{feff}".as_bytes();
+
+/// Engine decoding the normalized groups of a streamed body, which only
+/// the last group may leave short
+const STREAM_ENGINE: general_purpose::GeneralPurpose = general_purpose::GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    general_purpose::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone)
+        .with_decode_allow_trailing_bits(true),
+);
+
+/// Why a streamed base64 body cannot be decoded
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Base64StreamError {
+    /// A character outside both alphabets, after the body was taken for base64
+    #[error("Invalid base64 byte 0x{byte:02x} at offset {offset}")]
+    InvalidByte { offset: usize, byte: u8 },
+    /// A group ending after its first character, which holds no whole byte
+    #[error("Truncated base64 group at offset {offset}")]
+    TruncatedGroup { offset: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyKind {
+    Undecided,
+    Base64,
+    Plain,
+}
+
+/// Incremental form of [`decode_subscription_body`] yielding lines
+///
+/// Chunks of the body are pushed as they arrive and complete lines come back
+/// as soon as their end has been decoded, so neither the body nor the
+/// decoded text is held in memory at once. Only the characters of a split
+/// 4-character group, the bytes of the current line and the first
+/// [`SNIFF_LEN`] characters before the body kind is known are buffered.
+///
+/// The body is taken for base64 when its first significant characters are
+/// all in the standard or URL-safe alphabet, both of which may be mixed.
+/// Padding, line breaks and other whitespace are skipped anywhere, a byte
+/// order mark is dropped before and after decoding. Any other body is
+/// passed through as plain lines. Unlike [`decode_subscription_body`],
+/// decoded text is not checked to look like a node list.
+///
+/// Lines come back with their line break and a trailing `\r` removed, blank
+/// lines are skipped.
+#[derive(Debug)]
+pub struct SubscriptionLineDecoder {
+    kind: BodyKind,
+    /// Raw bytes seen while the kind is undecided
+    sniffed: Vec<u8>,
+    /// Normalized characters of the group split by the last chunk
+    group: Vec<u8>,
+    /// Bytes of the line not terminated yet
+    line: Vec<u8>,
+    /// Offset in the body of the next byte pushed
+    offset: usize,
+    /// Whether a line was yielded or dropped as blank
+    started: bool,
+}
+
+impl Default for SubscriptionLineDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscriptionLineDecoder {
+    pub fn new() -> Self {
+        SubscriptionLineDecoder {
+            kind: BodyKind::Undecided,
+            sniffed: Vec::new(),
+            group: Vec::with_capacity(4),
+            line: Vec::new(),
+            offset: 0,
+            started: false,
+        }
+    }
+
+    /// Whether the body was taken for base64, `None` until that is known
+    pub fn is_base64(&self) -> Option<bool> {
+        match self.kind {
+            BodyKind::Undecided => None,
+            kind => Some(kind == BodyKind::Base64),
+        }
+    }
+
+    /// Decode the next chunk of the body, returning the lines it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<String>, Base64StreamError> {
+        let mut lines = Vec::new();
+        if self.kind == BodyKind::Undecided {
+            self.sniffed.extend_from_slice(chunk);
+            self.offset += chunk.len();
+            let Some(kind) = sniff_body(&self.sniffed, false) else {
+                return Ok(lines);
+            };
+            self.kind = kind;
+            self.feed_sniffed(&mut lines)?;
+            return Ok(lines);
+        }
+        let offset = self.offset;
+        self.offset += chunk.len();
+        self.feed(chunk, offset, &mut lines)?;
+        Ok(lines)
+    }
+
+    /// Decode what is left once the body has ended
+    pub fn finish(mut self) -> Result<Vec<String>, Base64StreamError> {
+        let mut lines = Vec::new();
+        if self.kind == BodyKind::Undecided {
+            self.kind = sniff_body(&self.sniffed, true).unwrap_or(BodyKind::Plain);
+            self.feed_sniffed(&mut lines)?;
+        }
+        if self.kind == BodyKind::Base64 {
+            self.flush_group(self.offset, &mut lines)?;
+        }
+        let line = std::mem::take(&mut self.line);
+        self.emit_line(&line, &mut lines);
+        Ok(lines)
+    }
+
+    /// Decode the bytes buffered while the kind was undecided
+    fn feed_sniffed(&mut self, lines: &mut Vec<String>) -> Result<(), Base64StreamError> {
+        let sniffed = std::mem::take(&mut self.sniffed);
+        // Plain lines drop the byte order mark themselves
+        let bom = if self.kind == BodyKind::Base64 && sniffed.starts_with(BOM) {
+            BOM.len()
+        } else {
+            0
+        };
+        self.feed(&sniffed[bom..], bom, lines)
+    }
+
+    fn feed(
+        &mut self,
+        bytes: &[u8],
+        offset: usize,
+        lines: &mut Vec<String>,
+    ) -> Result<(), Base64StreamError> {
+        if self.kind == BodyKind::Plain {
+            self.push_decoded(bytes, lines);
+            return Ok(());
+        }
+        for (i, &byte) in bytes.iter().enumerate() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' => self.group.push(byte),
+                b'-' => self.group.push(b'+'),
+                b'_' => self.group.push(b'/'),
+                // Padding ends a group, another block may follow
+                b'=' => self.flush_group(offset + i, lines)?,
+                byte if byte.is_ascii_whitespace() => {}
+                byte => {
+                    return Err(Base64StreamError::InvalidByte {
+                        offset: offset + i,
+                        byte,
+                    })
+                }
+            }
+            if self.group.len() == 4 {
+                self.flush_group(offset + i, lines)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the characters of the current group, which may be short
+    fn flush_group(
+        &mut self,
+        offset: usize,
+        lines: &mut Vec<String>,
+    ) -> Result<(), Base64StreamError> {
+        if self.group.is_empty() {
+            return Ok(());
+        }
+        if self.group.len() == 1 {
+            return Err(Base64StreamError::TruncatedGroup { offset });
+        }
+        let mut decoded = [0u8; 3];
+        // Every character is in the alphabet and the group has 2 to 4 of them
+        let len = STREAM_ENGINE
+            .decode_slice(&self.group, &mut decoded)
+            .expect("a normalized base64 group decodes");
+        self.group.clear();
+        self.push_decoded(&decoded[..len], lines);
+        Ok(())
+    }
+
+    fn push_decoded(&mut self, bytes: &[u8], lines: &mut Vec<String>) {
+        let mut rest = bytes;
+        while let Some(end) = rest.iter().position(|&b| b == b'\n') {
+            if self.line.is_empty() {
+                self.emit_line(&rest[..end], lines);
+            } else {
+                self.line.extend_from_slice(&rest[..end]);
+                let line = std::mem::take(&mut self.line);
+                self.emit_line(&line, lines);
+            }
+            rest = &rest[end + 1..];
+        }
+        self.line.extend_from_slice(rest);
+    }
+
+    fn emit_line(&mut self, line: &[u8], lines: &mut Vec<String>) {
+        let mut line = String::from_utf8_lossy(line);
+        if !self.started {
+            self.started = true;
+            if let Some(stripped) = line.strip_prefix('\u{feff}') {
+                line = stripped.to_string().into();
+            }
+        }
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if !line.trim().is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+}
+
+/// The kind of a body from its first bytes, `None` when more are needed
+fn sniff_body(bytes: &[u8], complete: bool) -> Option<BodyKind> {
+    // A chunk may end inside the byte order mark
+    if !complete && BOM.starts_with(bytes) {
+        return None;
+    }
+    let text = bytes.strip_prefix(BOM).unwrap_or(bytes);
+    let mut significant = 0;
+    for &byte in text {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' | b'-' | b'_' | b'=' => {
+                significant += 1;
+                if significant >= SNIFF_LEN {
+                    return Some(BodyKind::Base64);
+                }
+            }
+            byte if byte.is_ascii_whitespace() => {}
+            _ => return Some(BodyKind::Plain),
+        }
+    }
+    match (complete, significant) {
+        (false, _) => None,
+        (true, 0) => Some(BodyKind::Plain),
+        (true, _) => Some(BodyKind::Base64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINKS: &str =
+        "trojan://secret@a.example.com:443#A\nss://YWVzLTEyOC1nY206cGFzcw@b.example.com:8388#B";
+
+    #[test]
+    fn test_decode_subscription_body() {
+        // Plain lists are kept, including a trailing newline
+        assert_eq!(
+            decode_subscription_body(format!("{}\n\n", LINKS).as_bytes()),
+            LINKS
+        );
+
+        // Standard, URL-safe, unpadded and wrapped base64 all decode
+        let standard = general_purpose::STANDARD.encode(LINKS);
+        let url_safe = general_purpose::URL_SAFE_NO_PAD.encode(LINKS);
+        let wrapped = standard
+            .as_bytes()
+            .chunks(16)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        for body in [
+            standard.clone(),
+            format!("{}\n  ", standard.trim_end_matches('=')),
+            url_safe,
+            wrapped,
+        ] {
+            assert_eq!(decode_subscription_body(body.as_bytes()), LINKS);
+        }
+
+        // Base64 that does not decode to nodes is not used
+        let other = general_purpose::STANDARD.encode("hello world");
+        assert_eq!(decode_subscription_body(other.as_bytes()), other);
+    }
+
+    fn decode_in_chunks(body: &[u8], size: usize) -> Result<Vec<String>, Base64StreamError> {
+        let mut decoder = SubscriptionLineDecoder::new();
+        let mut lines = Vec::new();
+        for chunk in body.chunks(size) {
+            lines.extend(decoder.push(chunk)?);
+        }
+        lines.extend(decoder.finish()?);
+        Ok(lines)
+    }
+
+    #[test]
+    fn test_decoded_body_size() {
+        let encoded = general_purpose::STANDARD.encode(LINKS);
+        assert_eq!(decoded_body_size(&encoded), LINKS.len());
+        // Line breaks and padding do not count
+        let wrapped = format!("{}\r\n{}\n", &encoded[..8], &encoded[8..]);
+        assert_eq!(decoded_body_size(&wrapped), LINKS.len());
+        assert_eq!(decoded_body_size(LINKS), LINKS.trim().len());
+        assert_eq!(decoded_body_size("proxies: []"), 11);
+    }
+
+    #[test]
+    fn test_subscription_line_decoder() {
+        let expected: Vec<&str> = LINKS.lines().collect();
+        let standard = general_purpose::STANDARD.encode(format!("\u{feff}{}\r\n", LINKS));
+        let wrapped = standard
+            .as_bytes()
+            .chunks(76)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        let bodies = [
+            standard.clone(),
+            format!("\u{feff}{}\n", wrapped),
+            general_purpose::URL_SAFE_NO_PAD.encode(LINKS),
+            format!("{}\n\n", LINKS),
+        ];
+        // Every chunk size splits groups, lines and the byte order mark
+        // somewhere
+        for body in &bodies {
+            for size in 1..=9 {
+                assert_eq!(
+                    decode_in_chunks(body.as_bytes(), size).unwrap(),
+                    expected,
+                    "{:?} in chunks of {}",
+                    body,
+                    size
+                );
+            }
+        }
+
+        // Padded blocks may follow each other
+        let blocks = format!(
+            "{}{}",
+            general_purpose::STANDARD.encode("trojan://a@a.example.com:443#A\n"),
+            general_purpose::STANDARD.encode("trojan://b@b.example.com:443#B")
+        );
+        assert_eq!(decode_in_chunks(blocks.as_bytes(), 5).unwrap().len(), 2);
+
+        let mut decoder = SubscriptionLineDecoder::new();
+        assert!(decoder.push(b"dHJv").unwrap().is_empty());
+        assert_eq!(decoder.is_base64(), None);
+
+        let mut broken = standard[..SNIFF_LEN].to_string();
+        broken.push_str("*A");
+        assert_eq!(
+            decode_in_chunks(broken.as_bytes(), 7),
+            Err(Base64StreamError::InvalidByte {
+                offset: SNIFF_LEN,
+                byte: b'*'
+            })
+        );
+        assert!(matches!(
+            decode_in_chunks(b"dHJvamFu\nd", 3),
+            Err(Base64StreamError::TruncatedGroup { .. })
+        ));
+    }
+}
@@ -1,6 +1,9 @@
 use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
 use std::fmt;
 
+use crate::utils::split_alpn;
+
 /// Helper function to deserialize fields that can be either string or number
 pub fn deserialize_string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
 where
@@ -67,3 +70,23 @@ where
 
     deserializer.deserialize_any(StringOrNumberVisitor)
 }
+
+/// Deserialize an ALPN list written either as a sequence or as a comma
+/// separated string, missing and null values give an empty list
+pub fn deserialize_alpn<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Alpn {
+        List(Vec<String>),
+        Joined(String),
+    }
+
+    Ok(match Option::<Alpn>::deserialize(deserializer)? {
+        Some(Alpn::List(list)) => split_alpn(&list.join(",")),
+        Some(Alpn::Joined(joined)) => split_alpn(&joined),
+        None => Vec::new(),
+    })
+}
@@ -0,0 +1,241 @@
+//! Explain mode: what the pipeline did to each node
+//!
+//! With [`explain`](crate::models::ExtraSettings::explain) set, stages record
+//! into an [`ExplainRecorder`] which nodes they dropped and why, which rename
+//! rules and emoji changed a name, and which nodes a target could not emit.
+//! The [`ExplainReport`] is returned with the result. Without the flag
+//! nothing is recorded and no node is copied.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::models::{Proxy, ProxyType};
+
+/// Stages that drop nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropStage {
+    /// `exclude_types`
+    ExcludeType,
+    /// `drop_ports`
+    Port,
+    /// Deduplication
+    Dedup,
+    /// `include_remarks` and `exclude_remarks`
+    RemarkFilter,
+    /// Liveness probing
+    Probe,
+    /// The filter script
+    FilterScript,
+}
+
+impl DropStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DropStage::ExcludeType => "exclude_type",
+            DropStage::Port => "port",
+            DropStage::Dedup => "dedup",
+            DropStage::RemarkFilter => "remark_filter",
+            DropStage::Probe => "probe",
+            DropStage::FilterScript => "filter_script",
+        }
+    }
+}
+
+impl fmt::Display for DropStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A node removed before emission
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DroppedNode {
+    pub remark: String,
+    /// `host:port` of the node
+    pub server: String,
+    pub stage: DropStage,
+    pub reason: String,
+}
+
+/// A node whose name was changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenamedNode {
+    pub original: String,
+    pub remark: String,
+    /// The rules that changed the name, in the order they applied
+    pub rules: Vec<String>,
+}
+
+/// An emoji prefixed to a node name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmojiAdded {
+    /// Name of the node before the emoji
+    pub remark: String,
+    pub emoji: String,
+}
+
+/// A node a target could not emit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedNode {
+    pub remark: String,
+    pub target: String,
+    pub reason: String,
+}
+
+/// Everything recorded by a conversion in explain mode
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExplainReport {
+    pub dropped: Vec<DroppedNode>,
+    pub renamed: Vec<RenamedNode>,
+    pub emoji: Vec<EmojiAdded>,
+    pub skipped: Vec<SkippedNode>,
+}
+
+/// Shared accumulator of an [`ExplainReport`]
+///
+/// Clones record into the same report, like
+/// [`StatsRecorder`](super::StatsRecorder).
+#[derive(Debug, Clone, Default)]
+pub struct ExplainRecorder(Arc<Mutex<ExplainReport>>);
+
+impl ExplainRecorder {
+    fn with(&self, f: impl FnOnce(&mut ExplainReport)) {
+        if let Ok(mut report) = self.0.lock() {
+            f(&mut report);
+        }
+    }
+
+    /// Record a node removed by a stage
+    pub fn dropped(&self, node: &Proxy, stage: DropStage, reason: String) {
+        self.with(|report| {
+            report.dropped.push(DroppedNode {
+                remark: node.remark.clone(),
+                server: format!("{}:{}", node.hostname, node.port),
+                stage,
+                reason,
+            })
+        });
+    }
+
+    /// Record a name change, nothing when no rule changed the name
+    pub fn renamed(&self, original: &str, remark: &str, rules: Vec<String>) {
+        if rules.is_empty() {
+            return;
+        }
+        self.with(|report| {
+            report.renamed.push(RenamedNode {
+                original: original.to_string(),
+                remark: remark.to_string(),
+                rules,
+            })
+        });
+    }
+
+    /// Record an emoji prefixed to `remark`
+    pub fn emoji(&self, remark: &str, emoji: &str) {
+        self.with(|report| {
+            report.emoji.push(EmojiAdded {
+                remark: remark.to_string(),
+                emoji: emoji.to_string(),
+            })
+        });
+    }
+
+    /// Record a node a target could not emit
+    pub fn skipped(&self, target: &str, remark: &str, reason: String) {
+        self.with(|report| {
+            report.skipped.push(SkippedNode {
+                remark: remark.to_string(),
+                target: target.to_string(),
+                reason,
+            })
+        });
+    }
+
+    /// Everything recorded so far
+    pub fn snapshot(&self) -> ExplainReport {
+        self.0
+            .lock()
+            .map(|report| report.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn node_key(node: &Proxy) -> (&str, &str, u16, ProxyType) {
+    (&node.remark, &node.hostname, node.port, node.proxy_type)
+}
+
+/// The nodes going into a filtering stage, to record the ones it drops
+///
+/// Nodes are only copied when explaining.
+#[derive(Debug)]
+pub struct DropTracker {
+    watched: Option<(ExplainRecorder, Vec<Proxy>)>,
+}
+
+impl DropTracker {
+    pub fn start(explain: Option<&ExplainRecorder>, nodes: &[Proxy]) -> Self {
+        DropTracker {
+            watched: explain.map(|explain| (explain.clone(), nodes.to_vec())),
+        }
+    }
+
+    /// Whether the nodes are tracked, i.e. explain mode is on
+    pub fn is_tracking(&self) -> bool {
+        self.watched.is_some()
+    }
+
+    /// Record the nodes missing from `nodes`, which the stage dropped
+    pub fn finish(self, stage: DropStage, nodes: &[Proxy], reason: impl Fn(&Proxy) -> String) {
+        let Some((explain, before)) = self.watched else {
+            return;
+        };
+        let mut left: HashMap<_, usize> = HashMap::new();
+        for node in nodes {
+            *left.entry(node_key(node)).or_default() += 1;
+        }
+        for node in &before {
+            match left.get_mut(&node_key(node)) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => explain.dropped(node, stage, reason(node)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_tracker() {
+        let node = |remark: &str| Proxy {
+            remark: remark.to_string(),
+            hostname: "example.com".to_string(),
+            port: 443,
+            ..Default::default()
+        };
+        let mut nodes = vec![node("A"), node("B"), node("A")];
+        let explain = ExplainRecorder::default();
+
+        // Untracked runs record nothing
+        DropTracker::start(None, &nodes).finish(DropStage::Dedup, &[], |_| String::new());
+        assert_eq!(explain.snapshot(), ExplainReport::default());
+
+        let tracker = DropTracker::start(Some(&explain), &nodes);
+        nodes.remove(2);
+        nodes.remove(1);
+        tracker.finish(DropStage::Dedup, &nodes, |node| {
+            format!("{} is a duplicate", node.remark)
+        });
+        let dropped = explain.snapshot().dropped;
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(dropped[0].remark, "B");
+        assert_eq!(dropped[0].server, "example.com:443");
+        assert_eq!(dropped[1].reason, "A is a duplicate");
+    }
+}
@@ -0,0 +1,351 @@
+//! Subscription fetching with timeouts, retries and bounded redirects
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use case_insensitive_string::CaseInsensitiveString;
+use thiserror::Error;
+
+use crate::utils::http::{web_get_once, GetOnceError, HttpResponse, ProxyConfig};
+
+/// Default timeout for a single request
+const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// Upper bound for the delay between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Options controlling how a subscription is fetched
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// Timeout for each request, including every redirect hop
+    pub timeout: Duration,
+    /// Number of retries after the first failed attempt
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every following retry
+    pub backoff: Duration,
+    /// Maximum number of 3xx redirects to follow
+    pub max_redirects: u32,
+    /// User-Agent header, overrides the default one
+    pub user_agent: Option<String>,
+    /// Extra request headers, dropped once a redirect leaves the origin
+    pub headers: HashMap<CaseInsensitiveString, String>,
+    /// Proxy used for requests
+    pub proxy: ProxyConfig,
+    /// Maximum response body size in bytes, 0 for no limit
+    pub max_size: usize,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            timeout: DEFAULT_FETCH_TIMEOUT,
+            retries: 2,
+            backoff: Duration::from_millis(500),
+            max_redirects: 10,
+            user_agent: None,
+            headers: HashMap::new(),
+            proxy: ProxyConfig::default(),
+            max_size: 0,
+        }
+    }
+}
+
+impl FetchOptions {
+    /// Set the timeout for each request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the number of retries and the initial backoff delay
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the maximum number of redirects to follow
+    pub fn max_redirects(mut self, max_redirects: u32) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Set the User-Agent header
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Add a request header
+    pub fn header(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.headers
+            .insert(CaseInsensitiveString::new(key), value.into());
+        self
+    }
+
+    /// Set the proxy used for requests
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Set the maximum response body size, 0 for no limit
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Delay before the given retry, starting at 1
+    fn backoff_for(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+/// Errors returned by [`fetch_subscription`]
+#[derive(Debug, Clone, Error)]
+pub enum FetchError {
+    /// The request could not be completed
+    #[error("Failed to fetch {url} after {attempts} attempt(s): {message}")]
+    Request {
+        url: String,
+        attempts: u32,
+        message: String,
+    },
+    /// The server answered with a non-success status
+    #[error("Failed to fetch {url} after {attempts} attempt(s): HTTP status {status}")]
+    Status {
+        url: String,
+        attempts: u32,
+        status: u16,
+    },
+    /// The redirect chain was longer than allowed
+    #[error("Failed to fetch {url}: more than {limit} redirect(s)")]
+    TooManyRedirects { url: String, limit: u32 },
+    /// A redirect did not point to a valid location
+    #[error("Failed to fetch {url}: invalid redirect location '{location}'")]
+    InvalidRedirect { url: String, location: String },
+    /// The response body was larger than allowed, it was not read further
+    #[error("Failed to fetch {url}: response is larger than {limit} bytes")]
+    TooLarge { url: String, limit: usize },
+}
+
+impl FetchError {
+    /// The URL that failed to be fetched
+    pub fn url(&self) -> &str {
+        match self {
+            FetchError::Request { url, .. }
+            | FetchError::Status { url, .. }
+            | FetchError::TooManyRedirects { url, .. }
+            | FetchError::InvalidRedirect { url, .. }
+            | FetchError::TooLarge { url, .. } => url,
+        }
+    }
+}
+
+/// Outcome of a single attempt
+enum Attempt {
+    Done(HttpResponse),
+    Retry(FetchError),
+    Fail(FetchError),
+}
+
+/// Whether a status code is worth retrying
+fn is_retryable_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+/// Resolve a `Location` header against the URL it was returned for
+fn resolve_location(base: &str, location: &str) -> Option<String> {
+    let base = url::Url::parse(base).ok()?;
+    base.join(location).ok().map(|u| u.to_string())
+}
+
+/// Whether two URLs share scheme, host and port
+fn same_origin(a: &str, b: &str) -> bool {
+    match (url::Url::parse(a), url::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    // There is no timer available in every WASM host, retry right away
+    #[cfg(target_arch = "wasm32")]
+    let _ = duration;
+}
+
+/// Fetch a URL once, following redirects up to the configured limit
+async fn fetch_once(url: &str, opts: &FetchOptions, attempts: u32) -> Attempt {
+    let mut headers = opts.headers.clone();
+    if let Some(user_agent) = &opts.user_agent {
+        headers.insert(CaseInsensitiveString::new("User-Agent"), user_agent.clone());
+    }
+
+    let mut current = url.to_string();
+    let mut redirects = 0;
+    loop {
+        let response = match web_get_once(
+            &current,
+            &opts.proxy,
+            Some(&headers),
+            opts.timeout,
+            opts.max_size,
+        )
+        .await
+        {
+            Ok(response) => response,
+            // Retrying would download the same oversized body again
+            Err(GetOnceError::BodyTooLarge { limit }) => {
+                return Attempt::Fail(FetchError::TooLarge {
+                    url: url.to_string(),
+                    limit,
+                })
+            }
+            Err(GetOnceError::Request(e)) => {
+                return Attempt::Retry(FetchError::Request {
+                    url: url.to_string(),
+                    attempts,
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        match response.status {
+            200..=299 => return Attempt::Done(response),
+            300..=399 => {
+                let location = response
+                    .headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("location"))
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default();
+                let Some(next) = resolve_location(&current, &location) else {
+                    return Attempt::Fail(FetchError::InvalidRedirect {
+                        url: url.to_string(),
+                        location,
+                    });
+                };
+                redirects += 1;
+                if redirects > opts.max_redirects {
+                    return Attempt::Fail(FetchError::TooManyRedirects {
+                        url: url.to_string(),
+                        limit: opts.max_redirects,
+                    });
+                }
+                // Custom headers often carry credentials meant for the
+                // original host only, keep just the User-Agent from here on
+                if !same_origin(&current, &next) {
+                    headers.retain(|key, _| key == &CaseInsensitiveString::new("User-Agent"));
+                }
+                log::debug!("Following redirect from {} to {}", current, next);
+                current = next;
+            }
+            status => {
+                let error = FetchError::Status {
+                    url: url.to_string(),
+                    attempts,
+                    status,
+                };
+                return if is_retryable_status(status) {
+                    Attempt::Retry(error)
+                } else {
+                    Attempt::Fail(error)
+                };
+            }
+        }
+    }
+}
+
+/// Fetch a subscription with timeout, retries and bounded redirects
+///
+/// Transport errors, timeouts, 408/429 and 5xx responses are retried with
+/// exponential backoff. Other 4xx responses and redirect errors fail right
+/// away.
+///
+/// # Arguments
+/// * `url` - The subscription URL
+/// * `opts` - Fetch options
+///
+/// # Returns
+/// * `Ok(HttpResponse)` - The final 2xx response
+/// * `Err(FetchError)` - The last error, naming the URL
+pub async fn fetch_subscription(
+    url: &str,
+    opts: &FetchOptions,
+) -> Result<HttpResponse, FetchError> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match fetch_once(url, opts, attempts).await {
+            Attempt::Done(response) => return Ok(response),
+            Attempt::Fail(error) => return Err(error),
+            Attempt::Retry(error) => {
+                if attempts > opts.retries {
+                    return Err(error);
+                }
+                let delay = opts.backoff_for(attempts);
+                log::warn!("{}, retrying in {:?}", error, delay);
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let opts = FetchOptions::default().retries(5, Duration::from_secs(1));
+        assert_eq!(opts.backoff_for(1), Duration::from_secs(1));
+        assert_eq!(opts.backoff_for(2), Duration::from_secs(2));
+        assert_eq!(opts.backoff_for(3), Duration::from_secs(4));
+        assert_eq!(opts.backoff_for(10), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        assert_eq!(
+            resolve_location("https://example.com/a/b?x=1", "/sub?token=1").as_deref(),
+            Some("https://example.com/sub?token=1")
+        );
+        assert_eq!(
+            resolve_location("https://example.com/a/b", "https://cdn.example.com/c").as_deref(),
+            Some("https://cdn.example.com/c")
+        );
+    }
+
+    #[test]
+    fn test_same_origin() {
+        assert!(same_origin(
+            "https://example.com/a",
+            "https://example.com/b?x=1"
+        ));
+        assert!(!same_origin(
+            "https://example.com/a",
+            "https://cdn.example.com/a"
+        ));
+        assert!(!same_origin(
+            "https://example.com/a",
+            "http://example.com/a"
+        ));
+        assert!(!same_origin(
+            "https://example.com/a",
+            "https://example.com:8443/a"
+        ));
+    }
+
+    #[test]
+    fn test_fetch_error_names_url() {
+        let error = FetchError::Status {
+            url: "https://example.com/sub".to_string(),
+            attempts: 3,
+            status: 503,
+        };
+        assert_eq!(error.url(), "https://example.com/sub");
+        assert!(error.to_string().contains("https://example.com/sub"));
+    }
+}
@@ -4,12 +4,16 @@ use crate::utils::http::{parse_proxy, web_get_async};
 // Import platform-specific implementations
 #[cfg(not(target_arch = "wasm32"))]
 mod platform {
-    pub use crate::utils::file_std::{copy_file, file_exists, file_get_async, read_file_async};
+    pub use crate::utils::file_std::{
+        copy_file, file_exists, file_get_async, read_file_async, read_stdin, resolve_within_base,
+    };
 }
 
 #[cfg(target_arch = "wasm32")]
 mod platform {
-    pub use crate::utils::file_wasm::{copy_file, file_exists, file_get_async, read_file_async};
+    pub use crate::utils::file_wasm::{
+        copy_file, file_exists, file_get_async, read_file_async, read_stdin, resolve_within_base,
+    };
 }
 
 // Re-export platform-specific implementations
@@ -28,7 +32,7 @@ pub use platform::*;
 pub async fn load_content_async(path: &str) -> Result<String, String> {
     if path.starts_with("http://") || path.starts_with("https://") {
         // It's a URL, use HTTP client
-        match web_get_async(path, &parse_proxy(&Settings::current().proxy_config), None).await {
+        match web_get_async(path, &parse_proxy(&Settings::snapshot().proxy_config), None).await {
             Ok(response) => Ok(response.body),
             Err(e) => Err(format!("Failed to read file from URL: {}", e)),
         }
@@ -1,6 +1,8 @@
 use std::fs;
 use std::io::{self, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncReadExt;
 
 /// Read a file into a string
 pub fn read_file(path: &str) -> Result<String, io::Error> {
@@ -22,6 +24,35 @@ pub async fn read_file_async(path: &str) -> Result<String, io::Error> {
     tokio::fs::read_to_string(path).await
 }
 
+/// Read standard input to the end
+pub async fn read_stdin() -> io::Result<String> {
+    let mut content = String::new();
+    tokio::io::stdin().read_to_string(&mut content).await?;
+    Ok(content)
+}
+
+/// Resolve a local path, rejecting it when it leaves `base_dir`
+///
+/// A relative `path` is taken relative to `base_dir`, then both paths are
+/// canonicalized, so neither `..` components nor symlinks can point outside
+/// the base directory. An empty `base_dir` means the working directory.
+pub fn resolve_within_base(path: &str, base_dir: &str) -> io::Result<PathBuf> {
+    let base = fs::canonicalize(if base_dir.is_empty() { "." } else { base_dir })?;
+    let path = Path::new(path);
+    let resolved = if path.is_absolute() {
+        fs::canonicalize(path)?
+    } else {
+        fs::canonicalize(base.join(path))?
+    };
+    if !resolved.starts_with(&base) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "File path is outside the base directory",
+        ));
+    }
+    Ok(resolved)
+}
+
 /// Check if a file exists
 pub async fn file_exists(path: &str) -> bool {
     tokio::fs::metadata(path).await.is_ok()
@@ -111,3 +142,32 @@ pub async fn file_get_async<P: AsRef<Path>>(
     }
     tokio::fs::read_to_string(path).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_within_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        fs::create_dir(&base).unwrap();
+        fs::write(base.join("sub.txt"), "").unwrap();
+        fs::write(dir.path().join("secret.txt"), "").unwrap();
+        let base_str = base.to_str().unwrap();
+
+        let inside = base.join("sub.txt");
+        assert!(resolve_within_base(inside.to_str().unwrap(), base_str).is_ok());
+
+        assert_eq!(
+            resolve_within_base("sub.txt", base_str).unwrap(),
+            fs::canonicalize(&inside).unwrap()
+        );
+        let err = resolve_within_base("../secret.txt", base_str).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let escaped = base.join("../secret.txt");
+        let err = resolve_within_base(escaped.to_str().unwrap(), base_str).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
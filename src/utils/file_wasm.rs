@@ -79,6 +79,28 @@ pub async fn read_file_async(path: &str) -> Result<String, io::Error> {
     read_file(path).await // Just delegate
 }
 
+/// Standard input is not available in the browser
+pub async fn read_stdin() -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Standard input is not available on this target",
+    ))
+}
+
+/// Resolve a local path inside the VFS
+///
+/// The VFS has no notion of a base directory, so only `..` components are
+/// rejected.
+pub fn resolve_within_base(path: &str, _base_dir: &str) -> io::Result<std::path::PathBuf> {
+    if path.split(['/', '\\']).any(|part| part == "..") {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "File path is outside the base directory",
+        ));
+    }
+    Ok(std::path::PathBuf::from(path))
+}
+
 /// Check if a file exists (async)
 pub async fn file_exists(path: &str) -> bool {
     match get_vfs().await {
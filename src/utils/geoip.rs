@@ -0,0 +1,258 @@
+//! GeoIP lookup used to pick flag emoji for nodes
+//!
+//! No database is bundled. Install one with [`set_geoip_resolver`], either a
+//! custom [`GeoIpResolver`] or a [`GeoIpDatabase`] loaded from a CSV file.
+//! Server names are resolved through [`tokio::net::lookup_host`], so no
+//! lookup blocks the runtime, and each name is resolved again only once its
+//! address is [`HOST_CACHE_TTL`] old.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use lazy_static::lazy_static;
+
+use crate::utils::system::safe_system_time;
+
+#[cfg(target_arch = "wasm32")]
+use crate::utils::network::hostname_to_ip_addr;
+
+/// Server names whose address is remembered, the cache starts over when full
+const HOST_CACHE_CAPACITY: usize = 4096;
+
+/// How long the address of a server name is remembered, so nodes whose
+/// servers move are located again
+pub const HOST_CACHE_TTL: Duration = Duration::from_secs(600);
+
+/// Resolves an IP address to an ISO 3166-1 alpha-2 country code
+pub trait GeoIpResolver: Send + Sync {
+    /// Look up the country code for an address, e.g. `"US"`
+    fn country_code(&self, ip: IpAddr) -> Option<String>;
+}
+
+lazy_static! {
+    static ref GEOIP_RESOLVER: RwLock<Option<Arc<dyn GeoIpResolver>>> = RwLock::new(None);
+    static ref HOST_CACHE: Mutex<HashMap<String, (Option<IpAddr>, SystemTime)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Install the resolver used for GeoIP emoji, `None` removes it
+pub fn set_geoip_resolver(resolver: Option<Arc<dyn GeoIpResolver>>) {
+    if let Ok(mut current) = GEOIP_RESOLVER.write() {
+        *current = resolver;
+    }
+}
+
+/// Get the installed resolver
+pub fn geoip_resolver() -> Option<Arc<dyn GeoIpResolver>> {
+    GEOIP_RESOLVER.read().ok().and_then(|r| r.clone())
+}
+
+/// In-memory database of address ranges
+///
+/// Each line of the CSV source is either `start,end,CC` (the layout of the
+/// common "IP to country lite" databases) or `cidr,CC`. Quotes around fields
+/// are ignored, as are lines that cannot be parsed.
+#[derive(Debug, Clone, Default)]
+pub struct GeoIpDatabase {
+    ranges: Vec<(u128, u128, String)>,
+}
+
+impl GeoIpDatabase {
+    /// Parse a database from CSV content
+    pub fn from_csv(content: &str) -> Self {
+        let mut ranges: Vec<(u128, u128, String)> = content
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line
+                    .split(',')
+                    .map(|f| f.trim().trim_matches('"'))
+                    .collect();
+                match fields.as_slice() {
+                    [cidr, code] => {
+                        let (start, end) = parse_cidr(cidr)?;
+                        Some((start, end, normalize_code(code)?))
+                    }
+                    [start, end, code, ..] => {
+                        let start = ip_to_u128(start.parse().ok()?);
+                        let end = ip_to_u128(end.parse().ok()?);
+                        Some((start, end, normalize_code(code)?))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        ranges.sort_by_key(|(start, _, _)| *start);
+        GeoIpDatabase { ranges }
+    }
+
+    /// Load a database from a CSV file
+    pub fn from_file(path: &str) -> std::io::Result<Self> {
+        Ok(Self::from_csv(&std::fs::read_to_string(path)?))
+    }
+
+    /// Number of ranges in the database
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Whether the database has no ranges
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl GeoIpResolver for GeoIpDatabase {
+    fn country_code(&self, ip: IpAddr) -> Option<String> {
+        let ip = ip_to_u128(ip);
+        let idx = self.ranges.partition_point(|(start, _, _)| *start <= ip);
+        let (_, end, code) = self.ranges.get(idx.checked_sub(1)?)?;
+        (ip <= *end).then(|| code.clone())
+    }
+}
+
+/// Map IPv4 addresses into the IPv4-mapped IPv6 space so both share one key
+fn ip_to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+fn parse_cidr(cidr: &str) -> Option<(u128, u128)> {
+    let (addr, prefix) = cidr.split_once('/')?;
+    let addr: IpAddr = addr.parse().ok()?;
+    let prefix: u32 = prefix.parse().ok()?;
+    let prefix = match addr {
+        IpAddr::V4(_) if prefix <= 32 => prefix + 96,
+        IpAddr::V6(_) if prefix <= 128 => prefix,
+        _ => return None,
+    };
+    let host_mask = u128::MAX.checked_shr(prefix).unwrap_or(0);
+    let start = ip_to_u128(addr) & !host_mask;
+    Some((start, start | host_mask))
+}
+
+fn normalize_code(code: &str) -> Option<String> {
+    (code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()))
+        .then(|| code.to_ascii_uppercase())
+}
+
+/// Convert a country code to its flag emoji, e.g. `"JP"` to 🇯🇵
+pub fn country_flag(code: &str) -> Option<String> {
+    normalize_code(code)?
+        .chars()
+        .map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// Whether a remark already starts with a flag emoji
+pub fn starts_with_flag(remark: &str) -> bool {
+    let mut chars = remark.trim_start().chars();
+    let is_indicator =
+        |c: Option<char>| c.is_some_and(|c| ('\u{1F1E6}'..='\u{1F1FF}').contains(&c));
+    is_indicator(chars.next()) && is_indicator(chars.next())
+}
+
+/// Address of a server name, failed lookups are remembered too
+async fn resolve_host(host: &str) -> Option<IpAddr> {
+    let now = safe_system_time();
+    if let Some((ip, resolved)) = HOST_CACHE.lock().ok()?.get(host) {
+        if now.duration_since(*resolved).unwrap_or_default() < HOST_CACHE_TTL {
+            return *ip;
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let ip = tokio::net::lookup_host((host, 0))
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .map(|addr| addr.ip());
+    #[cfg(target_arch = "wasm32")]
+    let ip = hostname_to_ip_addr(host).and_then(|ip| ip.parse().ok());
+    let mut cache = HOST_CACHE.lock().ok()?;
+    if cache.len() >= HOST_CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(host.to_string(), (ip, now));
+    ip
+}
+
+/// Resolve a server address to a country code with the installed resolver
+///
+/// Hostnames are resolved to an address first. Returns `None` when no
+/// resolver is installed or the lookup fails.
+pub async fn geoip_country(server: &str) -> Option<String> {
+    let resolver = geoip_resolver()?;
+    let host = server.trim_start_matches('[').trim_end_matches(']');
+    let ip: IpAddr = match host.parse() {
+        Ok(ip) => ip,
+        Err(_) => resolve_host(host).await?,
+    };
+    normalize_code(&resolver.country_code(ip)?)
+}
+
+/// Resolve a server address to a flag emoji, see [`geoip_country`]
+pub async fn geoip_flag(server: &str) -> Option<String> {
+    country_flag(&geoip_country(server).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_lookup() {
+        let db = GeoIpDatabase::from_csv(
+            "\"1.0.0.0\",\"1.0.0.255\",\"AU\"\n8.8.8.0/24,us\n2001:db8::/32,JP\ninvalid line",
+        );
+        assert_eq!(db.len(), 3);
+        assert_eq!(
+            db.country_code("1.0.0.1".parse().unwrap()).as_deref(),
+            Some("AU")
+        );
+        assert_eq!(
+            db.country_code("8.8.8.8".parse().unwrap()).as_deref(),
+            Some("US")
+        );
+        assert_eq!(
+            db.country_code("2001:db8::1".parse().unwrap()).as_deref(),
+            Some("JP")
+        );
+        assert_eq!(db.country_code("8.8.9.1".parse().unwrap()), None);
+        assert_eq!(db.country_code("0.0.0.1".parse().unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn test_geoip_country() {
+        struct Loopback;
+        impl GeoIpResolver for Loopback {
+            fn country_code(&self, ip: IpAddr) -> Option<String> {
+                ip.is_loopback().then(|| "zz".to_string())
+            }
+        }
+        assert_eq!(geoip_country("127.0.0.1").await, None);
+        set_geoip_resolver(Some(Arc::new(Loopback)));
+        assert_eq!(geoip_country("[::1]").await.as_deref(), Some("ZZ"));
+        assert_eq!(geoip_country("localhost").await.as_deref(), Some("ZZ"));
+        assert!(HOST_CACHE.lock().unwrap().contains_key("localhost"));
+
+        // An expired address is resolved again
+        let stale = safe_system_time() - HOST_CACHE_TTL;
+        HOST_CACHE
+            .lock()
+            .unwrap()
+            .insert("localhost".to_string(), (None, stale));
+        assert_eq!(geoip_country("localhost").await.as_deref(), Some("ZZ"));
+        set_geoip_resolver(None);
+    }
+
+    #[test]
+    fn test_country_flag() {
+        assert_eq!(country_flag("jp").as_deref(), Some("🇯🇵"));
+        assert_eq!(country_flag("USA"), None);
+        assert!(starts_with_flag("🇺🇸 US Node"));
+        assert!(!starts_with_flag("US Node 🇺🇸"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Comment block put ahead of generated configurations
+//!
+//! With [`output_header`](crate::models::ExtraSettings::output_header) set,
+//! the template is rendered into comment lines and prepended to outputs that
+//! have line comments: Clash, Surge, Surfboard, Loon, QuantumultX and Mellow.
+//! JSON and base64 outputs have no comments and are left alone.
+//!
+//! Templates use `{name}` placeholders:
+//!
+//! | Placeholder | Value |
+//! |---|---|
+//! | `{time}` | generation time, UTC |
+//! | `{subscriptions}` | number of subscriptions fetched |
+//! | `{nodes}` | number of nodes |
+//! | `{upload}`, `{download}`, `{total}`, `{remaining}` | traffic reported by the provider |
+//! | `{expire}` | expiry date reported by the provider |
+//!
+//! A line whose placeholder has no value, e.g. traffic of a provider that
+//! sent none or the time of a deterministic conversion, is left out.
+//! Unknown placeholders are kept as written.
+
+use crate::models::SubconverterTarget;
+
+use super::userinfo::SubscriptionUserInfo;
+
+/// Template used when a header is asked for without one
+pub const DEFAULT_OUTPUT_HEADER: &str = "Generated by subconverter at {time}\n\
+     Subscriptions: {subscriptions}, nodes: {nodes}\n\
+     Traffic: {upload} up, {download} down, {remaining} left of {total}\n\
+     Expires: {expire}";
+
+/// What a header can tell about a conversion
+#[derive(Debug, Clone, Default)]
+pub struct HeaderInfo {
+    /// Unix time of the conversion, `None` to leave it out
+    pub time: Option<u64>,
+    pub subscriptions: usize,
+    pub nodes: usize,
+    pub userinfo: Option<SubscriptionUserInfo>,
+}
+
+impl HeaderInfo {
+    fn value(&self, name: &str) -> Option<Option<String>> {
+        let userinfo = self.userinfo.unwrap_or_default();
+        let value = match name {
+            "time" => self.time.map(format_utc_time),
+            "subscriptions" => Some(self.subscriptions.to_string()),
+            "nodes" => Some(self.nodes.to_string()),
+            "upload" => userinfo.upload.map(format_bytes),
+            "download" => userinfo.download.map(format_bytes),
+            "total" => userinfo.total.map(format_bytes),
+            "remaining" => userinfo.remaining().map(format_bytes),
+            "expire" => userinfo
+                .expire
+                .map(|expire| format_utc_time(expire)[..10].to_string()),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// Comment marker of the output of `target`, `None` when it has none
+pub fn header_comment_prefix(target: &SubconverterTarget) -> Option<&'static str> {
+    match target {
+        SubconverterTarget::Clash
+        | SubconverterTarget::ClashR
+        | SubconverterTarget::Surge(_)
+        | SubconverterTarget::Surfboard
+        | SubconverterTarget::Loon
+        | SubconverterTarget::QuantumultX
+        | SubconverterTarget::Mellow => Some("#"),
+        _ => None,
+    }
+}
+
+/// Render `template` into comment lines starting with `prefix`
+pub fn render_output_header(template: &str, info: &HeaderInfo, prefix: &str) -> String {
+    let mut header = String::new();
+    'lines: for line in template.lines() {
+        let mut rendered = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let placeholder = &rest[start..];
+            let Some(end) = placeholder.find('}') else {
+                rest = placeholder;
+                break;
+            };
+            match info.value(&placeholder[1..end]) {
+                Some(Some(value)) => rendered.push_str(&value),
+                Some(None) => continue 'lines,
+                None => rendered.push_str(&placeholder[..=end]),
+            }
+            rest = &placeholder[end + 1..];
+        }
+        rendered.push_str(rest);
+
+        let rendered = rendered.trim_end();
+        if rendered.is_empty() {
+            header.push_str(prefix);
+        } else {
+            header.push_str(&format!("{} {}", prefix, rendered));
+        }
+        header.push('\n');
+    }
+    header
+}
+
+/// Put `header` ahead of `output`, after a leading `#!` directive such as
+/// Surge's `#!MANAGED-CONFIG`, which must stay on the first line
+pub fn prepend_output_header(output: &str, header: &str) -> String {
+    if header.is_empty() {
+        return output.to_string();
+    }
+    if output.starts_with("#!") {
+        let (first, rest) = output.split_once('\n').unwrap_or((output, ""));
+        return format!("{}\n{}{}", first, header, rest);
+    }
+    format!("{}{}", header, output)
+}
+
+/// Byte count in binary units, e.g. `1.50 GiB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, UNITS[unit])
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` of a Unix time
+fn format_utc_time(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // Days to civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_output_header() {
+        let mut info = HeaderInfo {
+            time: Some(1_700_000_000),
+            subscriptions: 2,
+            nodes: 31,
+            userinfo: SubscriptionUserInfo::parse(
+                "upload=1073741824; download=536870912; total=10737418240; expire=1735689600",
+            ),
+        };
+        assert_eq!(
+            render_output_header(DEFAULT_OUTPUT_HEADER, &info, "#"),
+            "# Generated by subconverter at 2023-11-14 22:13:20 UTC\n\
+             # Subscriptions: 2, nodes: 31\n\
+             # Traffic: 1.00 GiB up, 512.00 MiB down, 8.50 GiB left of 10.00 GiB\n\
+             # Expires: 2025-01-01\n"
+        );
+
+        // Lines without a value are dropped, unknown placeholders kept
+        info.time = None;
+        info.userinfo = None;
+        assert_eq!(
+            render_output_header(DEFAULT_OUTPUT_HEADER, &info, "#"),
+            "# Subscriptions: 2, nodes: 31\n"
+        );
+        assert_eq!(
+            render_output_header("{nodes} {other}\n\nkey: {value", &info, "#"),
+            "# 31 {other}\n#\n# key: {value\n"
+        );
+
+        let header = "# header\n";
+        assert_eq!(
+            prepend_output_header("#!MANAGED-CONFIG url\n[Proxy]\n", header),
+            "#!MANAGED-CONFIG url\n# header\n[Proxy]\n"
+        );
+        assert_eq!(
+            prepend_output_header("proxies: []\n", header),
+            "# header\nproxies: []\n"
+        );
+    }
+}
@@ -6,7 +6,7 @@ use std::collections::HashMap;
 mod platform {
     pub use crate::utils::http_std::{
         get_sub_info_from_header, get_sub_info_from_response, parse_proxy, web_get, web_get_async,
-        web_patch_async, web_post_async, HttpError, HttpResponse, ProxyConfig,
+        web_get_once, web_patch_async, web_post_async, HttpError, HttpResponse, ProxyConfig,
     };
 }
 
@@ -14,13 +14,33 @@ mod platform {
 mod platform {
     pub use crate::utils::http_wasm::{
         get_sub_info_from_header, get_sub_info_from_response, parse_proxy, web_get, web_get_async,
-        web_patch_async, web_post_async, HttpError, HttpResponse, ProxyConfig,
+        web_get_once, web_patch_async, web_post_async, HttpError, HttpResponse, ProxyConfig,
     };
 }
 
 // Re-export platform-specific implementations
 pub use platform::*;
 
+/// Error of [`web_get_once`]
+#[derive(Debug, Clone)]
+pub enum GetOnceError {
+    /// The request failed
+    Request(HttpError),
+    /// The response body is larger than the allowed size
+    BodyTooLarge { limit: usize },
+}
+
+impl std::fmt::Display for GetOnceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GetOnceError::Request(e) => write!(f, "{}", e),
+            GetOnceError::BodyTooLarge { limit } => {
+                write!(f, "Response body is larger than {} bytes", limit)
+            }
+        }
+    }
+}
+
 /// Asynchronous function that returns only the body content if status is 2xx,
 /// otherwise treats as error
 /// This provides backward compatibility with code expecting only successful
@@ -1,3 +1,4 @@
+use crate::utils::http::GetOnceError;
 use crate::utils::system::get_system_proxy;
 use awc::Client;
 use case_insensitive_string::CaseInsensitiveString;
@@ -84,9 +85,65 @@ pub async fn web_get_async(
     proxy_config: &ProxyConfig,
     headers: Option<&HashMap<CaseInsensitiveString, String>>,
 ) -> Result<HttpResponse, HttpError> {
+    send_get(
+        url,
+        proxy_config,
+        headers,
+        Duration::from_secs(DEFAULT_TIMEOUT),
+        true,
+        None,
+    )
+    .await
+    .map_err(|e| match e {
+        GetOnceError::Request(e) => e,
+        e => HttpError {
+            message: e.to_string(),
+            status: None,
+        },
+    })
+}
+
+/// Makes a single HTTP GET request without following redirects
+///
+/// 3xx responses are returned as-is so the caller can decide how to follow
+/// the `Location` header.
+///
+/// # Arguments
+/// * `url` - The URL to request
+/// * `proxy_config` - Proxy configuration
+/// * `headers` - Optional custom headers
+/// * `timeout` - Timeout for the whole request
+/// * `max_size` - Maximum body size in bytes, 0 for no limit
+///
+/// # Returns
+/// * `Ok(HttpResponse)` - The response with status, body, and headers
+/// * `Err(GetOnceError)` - Error details if the request failed or the body
+///   was too large; the body is never read past the limit
+pub async fn web_get_once(
+    url: &str,
+    proxy_config: &ProxyConfig,
+    headers: Option<&HashMap<CaseInsensitiveString, String>>,
+    timeout: Duration,
+    max_size: usize,
+) -> Result<HttpResponse, GetOnceError> {
+    let limit = if max_size == 0 { usize::MAX } else { max_size };
+    send_get(url, proxy_config, headers, timeout, false, Some(limit)).await
+}
+
+async fn send_get(
+    url: &str,
+    _proxy_config: &ProxyConfig,
+    headers: Option<&HashMap<CaseInsensitiveString, String>>,
+    timeout: Duration,
+    follow_redirects: bool,
+    body_limit: Option<usize>,
+) -> Result<HttpResponse, GetOnceError> {
     // Build client with proxy if specified
 
-    let mut client_builder = Client::builder().timeout(Duration::from_secs(DEFAULT_TIMEOUT));
+    let mut client_builder = Client::builder().timeout(timeout);
+    if !follow_redirects {
+        client_builder = client_builder.disable_redirects();
+    }
 
     // if let Some(proxy) = &proxy_config.proxy {
     //     if !proxy.is_empty() {
@@ -120,10 +177,10 @@ pub async fn web_get_async(
     let mut response = match client_request.send().await {
         Ok(resp) => resp,
         Err(e) => {
-            return Err(HttpError {
+            return Err(GetOnceError::Request(HttpError {
                 message: format!("Failed to send request: {}", e),
                 status: None,
-            });
+            }));
         }
     };
 
@@ -138,17 +195,35 @@ pub async fn web_get_async(
         }
     }
 
+    // Refuse announced oversized bodies before reading anything
+    if let Some(limit) = body_limit {
+        let length = resp_headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok());
+        if length.is_some_and(|length| length > limit) {
+            return Err(GetOnceError::BodyTooLarge { limit });
+        }
+    }
+
     // Get response body, even for error responses
-    match response.body().await {
+    let body = match body_limit {
+        Some(limit) => response.body().limit(limit).await,
+        None => response.body().await,
+    };
+    match body {
         Ok(body) => Ok(HttpResponse {
             status,
             body: String::from_utf8(body.to_vec()).unwrap(),
             headers: resp_headers,
         }),
-        Err(e) => Err(HttpError {
+        Err(awc::error::PayloadError::Overflow) => Err(GetOnceError::BodyTooLarge {
+            limit: body_limit.unwrap_or_default(),
+        }),
+        Err(e) => Err(GetOnceError::Request(HttpError {
             message: format!("Failed to read response body: {}", e),
             status: Some(status),
-        }),
+        })),
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::utils::http::GetOnceError;
 use crate::utils::system::get_system_proxy;
 use case_insensitive_string::CaseInsensitiveString;
 use std::collections::HashMap;
@@ -347,6 +348,27 @@ pub async fn web_get_async(
     }
 }
 
+/// Makes a single HTTP GET request
+///
+/// The fetch API follows redirects by itself and has no per-request timeout,
+/// so this is equivalent to `web_get_async` in WASM environments. The body
+/// size limit is checked once the body has been read.
+pub async fn web_get_once(
+    url: &str,
+    proxy_config: &ProxyConfig,
+    headers: Option<&HashMap<CaseInsensitiveString, String>>,
+    _timeout: std::time::Duration,
+    max_size: usize,
+) -> Result<HttpResponse, GetOnceError> {
+    let response = web_get_async(url, proxy_config, headers)
+        .await
+        .map_err(GetOnceError::Request)?;
+    if max_size > 0 && response.body.len() > max_size {
+        return Err(GetOnceError::BodyTooLarge { limit: max_size });
+    }
+    Ok(response)
+}
+
 /// Synchronous version of web_get_async that uses tokio runtime to run the
 /// async function
 ///
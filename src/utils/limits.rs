@@ -0,0 +1,98 @@
+//! Size limits for fetched and parsed subscriptions
+//!
+//! Limits keep a hosted instance from running out of memory on a hostile
+//! subscription. They are read from [`Settings`], where 0 disables a limit.
+
+use thiserror::Error;
+
+use crate::Settings;
+
+/// Limits applied while fetching and parsing subscriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseLimits {
+    /// Maximum downloaded body size in bytes
+    pub max_download_size: usize,
+    /// Maximum decoded subscription size in bytes
+    pub max_decoded_size: usize,
+    /// Maximum number of nodes
+    pub max_nodes: usize,
+}
+
+/// A limit of [`ParseLimits`] was exceeded
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LimitError {
+    /// A download was larger than allowed
+    #[error("Download exceeds the limit of {limit} bytes")]
+    DownloadSize { limit: usize },
+    /// A decoded subscription was larger than allowed
+    #[error("Decoded subscription of {size} bytes exceeds the limit of {limit} bytes")]
+    DecodedSize { size: usize, limit: usize },
+    /// More nodes than allowed were parsed
+    #[error("{count} nodes exceed the limit of {limit} nodes")]
+    NodeCount { count: usize, limit: usize },
+}
+
+impl ParseLimits {
+    /// Limits configured in the given settings
+    pub fn from_settings(settings: &Settings) -> Self {
+        ParseLimits {
+            max_download_size: settings.max_allowed_download_size.max(0) as usize,
+            max_decoded_size: settings.max_allowed_decoded_size.max(0) as usize,
+            max_nodes: settings.max_allowed_nodes,
+        }
+    }
+
+    /// Limits of the current global settings
+    pub fn current() -> Self {
+        Self::from_settings(&Settings::snapshot())
+    }
+
+    /// Check the size of a decoded subscription
+    pub fn check_decoded_size(&self, size: usize) -> Result<(), LimitError> {
+        if self.max_decoded_size > 0 && size > self.max_decoded_size {
+            return Err(LimitError::DecodedSize {
+                size,
+                limit: self.max_decoded_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check a number of parsed nodes
+    pub fn check_node_count(&self, count: usize) -> Result<(), LimitError> {
+        if self.max_nodes > 0 && count > self.max_nodes {
+            return Err(LimitError::NodeCount {
+                count,
+                limit: self.max_nodes,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let limits = ParseLimits {
+            max_download_size: 0,
+            max_decoded_size: 10,
+            max_nodes: 2,
+        };
+        assert!(limits.check_decoded_size(10).is_ok());
+        assert_eq!(
+            limits.check_decoded_size(11),
+            Err(LimitError::DecodedSize {
+                size: 11,
+                limit: 10
+            })
+        );
+        assert!(limits.check_node_count(2).is_ok());
+        assert!(limits.check_node_count(3).is_err());
+
+        // Zero disables a limit
+        assert!(ParseLimits::default().check_node_count(usize::MAX).is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+//! Surge's managed configuration header
+//!
+//! A managed Surge or Surfboard profile starts with a line such as
+//! `#!MANAGED-CONFIG https://example.com/sub interval=86400 strict=false`,
+//! telling the client where to download updates from and how often. The
+//! header of an input profile is read so it can be written back when the
+//! profile is converted to Surge again; see
+//! [`keep_managed_config`](crate::SubconverterConfig::keep_managed_config).
+
+use std::fmt;
+
+use log::warn;
+
+/// Directive starting the header line
+pub const MANAGED_CONFIG_DIRECTIVE: &str = "#!MANAGED-CONFIG";
+
+/// The fields of a `#!MANAGED-CONFIG` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedConfig {
+    /// URL the client downloads updates from
+    pub url: String,
+    /// Seconds between updates
+    pub interval: Option<u32>,
+    /// Whether the client refuses to use the profile when an update fails
+    pub strict: Option<bool>,
+}
+
+impl ManagedConfig {
+    /// Read the header on the first line of `content`
+    ///
+    /// Returns `None` without a header. A malformed header is ignored with a
+    /// warning: one without a URL or with an `interval` or `strict` value
+    /// that does not parse. Other parameters are ignored.
+    pub fn parse(content: &str) -> Option<Self> {
+        let line = content
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .next()?
+            .trim();
+        let rest = line.strip_prefix(MANAGED_CONFIG_DIRECTIVE)?;
+        match Self::parse_params(rest) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                warn!("Ignoring malformed managed config header '{}': {}", line, e);
+                None
+            }
+        }
+    }
+
+    fn parse_params(params: &str) -> Result<Self, String> {
+        let mut params = params.split_whitespace();
+        let url = params
+            .next()
+            .filter(|url| url.contains("://"))
+            .ok_or("no URL")?;
+        let mut config = ManagedConfig {
+            url: url.to_string(),
+            interval: None,
+            strict: None,
+        };
+        for param in params {
+            let Some((key, value)) = param.split_once('=') else {
+                continue;
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "interval" => {
+                    config.interval = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid interval '{}'", value))?,
+                    )
+                }
+                "strict" => {
+                    config.strict = Some(match value.to_ascii_lowercase().as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(format!("invalid strict '{}'", value)),
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Put `config` on the first line of `output`, in place of the header
+/// already there if any
+pub fn set_managed_config(output: &str, config: &ManagedConfig) -> String {
+    let body = match output.strip_prefix(MANAGED_CONFIG_DIRECTIVE) {
+        Some(rest) => rest.split_once('\n').map_or("", |(_, body)| body),
+        None => output,
+    };
+    format!("{}\n\n{}", config, body.trim_start_matches('\n'))
+}
+
+/// The header line, without a line break
+impl fmt::Display for ManagedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", MANAGED_CONFIG_DIRECTIVE, self.url)?;
+        if let Some(interval) = self.interval {
+            write!(f, " interval={}", interval)?;
+        }
+        if let Some(strict) = self.strict {
+            write!(f, " strict={}", strict)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_managed_config() {
+        let content = "#!MANAGED-CONFIG https://example.com/sub?id=1 interval=43200 strict=true\n\n[General]\n";
+        let config = ManagedConfig::parse(content).unwrap();
+        assert_eq!(
+            config,
+            ManagedConfig {
+                url: "https://example.com/sub?id=1".to_string(),
+                interval: Some(43200),
+                strict: Some(true),
+            }
+        );
+        assert_eq!(config.to_string(), content.lines().next().unwrap());
+
+        let bare = ManagedConfig::parse("#!MANAGED-CONFIG https://example.com/sub").unwrap();
+        assert_eq!(bare.to_string(), "#!MANAGED-CONFIG https://example.com/sub");
+
+        // Malformed headers and profiles without one
+        assert_eq!(ManagedConfig::parse("#!MANAGED-CONFIG interval=60"), None);
+        assert_eq!(
+            ManagedConfig::parse("#!MANAGED-CONFIG https://example.com interval=daily"),
+            None
+        );
+        assert_eq!(
+            ManagedConfig::parse("[General]\n#!MANAGED-CONFIG https://a"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_set_managed_config() {
+        let config = ManagedConfig::parse("#!MANAGED-CONFIG https://example.com/sub").unwrap();
+        let expected = "#!MANAGED-CONFIG https://example.com/sub\n\n[General]\n";
+        assert_eq!(set_managed_config("[General]\n", &config), expected);
+        assert_eq!(
+            set_managed_config(
+                "#!MANAGED-CONFIG https://sub.example.org/sub?target=surge interval=60\n\n[General]\n",
+                &config
+            ),
+            expected
+        );
+    }
+}
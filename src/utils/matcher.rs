@@ -29,6 +29,7 @@ lazy_static! {
         m.insert(ProxyType::WireGuard, "WIREGUARD");
         m.insert(ProxyType::Hysteria, "HYSTERIA");
         m.insert(ProxyType::Hysteria2, "HYSTERIA2");
+        m.insert(ProxyType::Tuic, "TUIC");
         m.insert(ProxyType::Unknown, "UNKNOWN");
         m
     };
@@ -1,46 +1,71 @@
 pub mod base64;
 pub mod deserialize;
+pub mod explain;
+pub mod fetch;
 pub mod file;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod file_std;
 #[cfg(target_arch = "wasm32")]
 pub mod file_wasm;
+pub mod geoip;
+pub mod header;
 pub mod http;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod http_std;
 #[cfg(target_arch = "wasm32")]
 pub mod http_wasm;
 pub mod ini_reader;
+pub mod limits;
+pub mod managed_config;
 pub mod matcher;
 pub mod memory_cache;
 pub mod network;
 pub mod node_manip;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod probe;
 pub mod regexp;
 pub mod serialize;
 pub mod string;
 pub mod system;
+pub mod throttle;
+pub mod timing;
+pub mod transliterate;
 pub mod tribool;
 pub mod url;
 pub mod useragent;
+pub mod userinfo;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
 // Re-export common utilities
+pub use base64::{
+    decode_subscription_body, decoded_body_size, Base64StreamError, SubscriptionLineDecoder,
+};
+pub use explain::{ExplainRecorder, ExplainReport};
+pub use fetch::{fetch_subscription, FetchError, FetchOptions};
 pub use file::{file_exists, file_get_async};
 pub use http::{get_sub_info_from_header, web_get_async};
 pub use ini_reader::IniReader;
+pub use limits::{LimitError, ParseLimits};
+pub use managed_config::ManagedConfig;
 pub use network::{is_ipv4, is_ipv6, is_link};
-pub use node_manip::{append_type_to_remark, preprocess_nodes};
+pub use node_manip::{
+    append_duplicate_index, append_type_to_remark, excluded_type_reason, filter_nodes_by_remark,
+    filter_nodes_by_type, normalize_node_names, preprocess_node, preprocess_nodes, RemarkFilter,
+};
 pub use regexp::{
     reg_find, reg_get_all_match, reg_get_match, reg_match, reg_replace, reg_trim, reg_valid,
 };
 pub use serialize::{is_empty_option_string, is_u32_option_zero};
 pub use string::{
-    ends_with, md5, remove_emoji, replace_all_distinct, starts_with, to_lower, trim,
+    ends_with, md5, remove_emoji, replace_all_distinct, split_alpn, starts_with, to_lower, trim,
     trim_whitespace,
 };
 pub use system::{get_env, get_system_proxy, sleep_ms};
+pub use throttle::{acquire_conversion_slot, ConversionBusy};
+pub use timing::{ConvertStage, ConvertStats, StatsRecorder, Stopwatch};
 pub use url::{url_decode, url_encode};
 pub use useragent::{match_user_agent, ver_greater_equal};
+pub use userinfo::SubscriptionUserInfo;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::{init_panic_hook, set_panic_hook};
@@ -1,6 +1,6 @@
 //! Network utilities for IP address handling and validation
 
-use std::net::{IpAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv6Addr, ToSocketAddrs};
 
 /// Resolves a hostname to its IP address
 ///
@@ -67,9 +67,53 @@ pub fn is_ipv4(s: &str) -> bool {
 ///
 /// True if the string is a valid IPv6 address, false otherwise
 pub fn is_ipv6(s: &str) -> bool {
-    // Basic implementation - placeholder
-    // In a real implementation, we'd do proper IPv6 validation
-    s.contains(':')
+    s.parse::<Ipv6Addr>().is_ok()
+}
+
+/// Removes the brackets around an IPv6 literal
+///
+/// Nodes store bare addresses; other hosts are returned unchanged.
+pub fn strip_ipv6_brackets(host: &str) -> &str {
+    match host
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        Some(inner) if is_ipv6(inner) => inner,
+        _ => host,
+    }
+}
+
+/// Wraps an IPv6 literal in brackets, for positions followed by `:port`
+pub fn bracket_ipv6(host: &str) -> String {
+    if is_ipv6(host) {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+/// Joins a host and a port, bracketing IPv6 literals
+pub fn join_host_port(host: &str, port: u16) -> String {
+    format!("{}:{}", bracket_ipv6(host), port)
+}
+
+/// Splits `host:port`, also accepting a bracketed IPv6 host
+///
+/// # Returns
+///
+/// The host without brackets and the port, or `None` if either is missing
+pub fn split_host_port(s: &str) -> Option<(&str, &str)> {
+    let (host, port) = s.trim().rsplit_once(':')?;
+    let host = match host.strip_prefix('[') {
+        Some(rest) => rest.strip_suffix(']')?,
+        // An unbracketed IPv6 literal has no separate port
+        None if host.contains(':') => return None,
+        None => host,
+    };
+    if host.is_empty() || port.is_empty() {
+        return None;
+    }
+    Some((host, port))
 }
 
 /// Checks if a string is a valid URL
@@ -114,6 +158,25 @@ mod tests {
         assert!(!is_ipv6("not an ip"));
     }
 
+    #[test]
+    fn test_ipv6_brackets() {
+        assert_eq!(strip_ipv6_brackets("[2001:db8::1]"), "2001:db8::1");
+        assert_eq!(strip_ipv6_brackets("example.com"), "example.com");
+        assert_eq!(join_host_port("2001:db8::1", 443), "[2001:db8::1]:443");
+        assert_eq!(join_host_port("1.2.3.4", 443), "1.2.3.4:443");
+
+        assert_eq!(
+            split_host_port("[2001:db8::1]:443"),
+            Some(("2001:db8::1", "443"))
+        );
+        assert_eq!(
+            split_host_port("example.com:80"),
+            Some(("example.com", "80"))
+        );
+        assert_eq!(split_host_port("2001:db8::1"), None);
+        assert_eq!(split_host_port("example.com"), None);
+    }
+
     #[test]
     fn test_hostname_to_ip_addr() {
         // This test might be flaky depending on network conditions
@@ -1,44 +1,64 @@
 use log::{debug, info};
+use regex::{Regex, RegexBuilder};
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 
 use crate::models::{
     extra_settings::ExtraSettings,
+    port_rewrite::dropped_port_reason,
     proxy::{Proxy, ProxyType},
-    regex_match_config::RegexMatchConfigs,
+    regex_match_config::replace_literal,
+    relay::{check_relay_chains, has_relay_chains, relink_upstreams},
 };
 use crate::utils::{
+    explain::{DropStage, DropTracker, ExplainRecorder},
+    geoip::{geoip_flag, starts_with_flag},
     matcher::{apply_matcher, reg_find},
     reg_replace,
     string::{remove_emoji, trim},
+    timing::ConvertStage,
+    transliterate::{normalize_punctuation, transliterate_regions},
 };
 
 use super::matcher::apply_compiled_rule;
 
 /// Applies a rename configuration to a node
 /// Similar to the C++ nodeRename function
-async fn node_rename(node: &mut Proxy, extra: &mut ExtraSettings) {
+///
+/// Rules that changed the name are added to `fired` in explain mode.
+async fn node_rename(node: &mut Proxy, extra: &mut ExtraSettings, fired: &mut Vec<String>) {
     extra.init_js_context();
-    let rename_array = &extra.rename_array;
     let original_remark = node.remark.clone();
-    for pattern in rename_array {
+    for index in 0..extra.rename_array.len() {
+        let pattern = &extra.rename_array[index];
+        let before = extra.explain.is_some().then(|| node.remark.clone());
         if !pattern.script.is_empty() {
-            match extra
-                .eval_get_rename_node_remark(node, pattern.script.clone())
-                .await
-            {
+            let script = pattern.script.clone();
+            match extra.eval_get_rename_node_remark(node, script).await {
                 Ok(new_remark) => {
                     node.remark = new_remark;
                 }
                 Err(e) => {
                     log::error!("Error renaming node: {}", e);
+                    extra.reset_js_context_after(e.as_ref());
                 }
             }
+        } else if pattern.literal {
+            replace_literal(&mut node.remark, &pattern._match, &pattern.replace);
         } else if !pattern._match.is_empty() {
             let mut real_rule = String::new();
             if apply_matcher(&pattern._match, &mut real_rule, node) && !real_rule.is_empty() {
                 node.remark = reg_replace(&node.remark, &real_rule, &pattern.replace, true, false);
             }
         }
+        let pattern = &extra.rename_array[index];
+        if before.is_some_and(|before| before != node.remark) {
+            fired.push(if pattern.script.is_empty() {
+                format!("rename '{}' -> '{}'", pattern._match, pattern.replace)
+            } else {
+                "rename script".to_string()
+            });
+        }
     }
 
     // If the remark is empty after processing, restore the original
@@ -47,19 +67,34 @@ async fn node_rename(node: &mut Proxy, extra: &mut ExtraSettings) {
     }
 }
 
-/// Adds emoji to node remark based on regex matching
-async fn add_emoji(node: &Proxy, emoji_array: &RegexMatchConfigs, extra: &ExtraSettings) -> String {
-    for pattern in emoji_array {
+/// Adds emoji to node remark based on GeoIP lookup or regex matching
+async fn add_emoji(node: &Proxy, extra: &mut ExtraSettings) -> String {
+    // Never prefix a second flag
+    if starts_with_flag(&node.remark) {
+        return node.remark.clone();
+    }
+
+    if extra.emoji_by_geoip {
+        if let Some(flag) = geoip_flag(&node.hostname).await {
+            return format!("{} {}", flag, node.remark);
+        }
+        debug!(
+            "GeoIP lookup failed for {}, using emoji rules",
+            node.hostname
+        );
+    }
+
+    for index in 0..extra.emoji_array.len() {
+        let pattern = &extra.emoji_array[index];
         if !pattern.script.is_empty() {
-            match extra
-                .eval_get_emoji_node_remark(node, pattern.script.clone())
-                .await
-            {
+            let script = pattern.script.clone();
+            match extra.eval_get_emoji_node_remark(node, script).await {
                 Ok(emoji) => {
                     return format!("{} {}", emoji, node.remark);
                 }
                 Err(e) => {
                     log::error!("Error adding emoji: {}", e);
+                    extra.reset_js_context_after(e.as_ref());
                 }
             }
             continue;
@@ -81,39 +116,399 @@ async fn add_emoji(node: &Proxy, emoji_array: &RegexMatchConfigs, extra: &ExtraS
     node.remark.clone()
 }
 
+/// An include or exclude remark pattern
+#[derive(Debug, Clone)]
+struct RemarkPattern {
+    /// The pattern as written
+    source: String,
+    /// Whether the pattern starts with a node matcher such as `!!GROUP=`
+    matcher: bool,
+    /// The remark regex, `None` when the matcher alone decides
+    regex: Option<Regex>,
+}
+
+impl RemarkPattern {
+    /// Compile a pattern, `(?i)` prefixes are honored like in `reg_find`
+    fn new(source: &str, kind: &str) -> Result<Self, String> {
+        let mut real_rule = String::new();
+        apply_matcher(source, &mut real_rule, &Proxy::default());
+        let regex = if real_rule.is_empty() {
+            None
+        } else {
+            let (body, case_insensitive) = match real_rule.strip_prefix("(?i)") {
+                Some(body) => (body, true),
+                None => (real_rule.as_str(), false),
+            };
+            let regex = RegexBuilder::new(body)
+                .case_insensitive(case_insensitive)
+                .multi_line(true)
+                .build()
+                .map_err(|e| format!("Invalid {} pattern '{}': {}", kind, source, e))?;
+            Some(regex)
+        };
+        Ok(RemarkPattern {
+            source: source.to_string(),
+            matcher: source.starts_with("!!"),
+            regex,
+        })
+    }
+
+    fn matches(&self, node: &Proxy) -> bool {
+        if self.matcher && !apply_matcher(&self.source, &mut String::new(), node) {
+            return false;
+        }
+        self.regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&node.remark))
+    }
+}
+
+fn compile_remark_patterns(patterns: &[String], kind: &str) -> Result<Vec<RemarkPattern>, String> {
+    patterns
+        .iter()
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| RemarkPattern::new(pattern, kind))
+        .collect()
+}
+
+/// Compiled include and exclude remark patterns
+///
+/// A node passes if it matches at least one include pattern (or there are
+/// none) and matches no exclude pattern. Patterns are regexes on the remark
+/// and may start with a node matcher such as `!!GROUP=`, see
+/// [`apply_matcher`]. This is the filter subscriptions are parsed with, it is
+/// compiled once so an invalid pattern is reported before any node is read.
+#[derive(Debug, Clone, Default)]
+pub struct RemarkFilter {
+    includes: Vec<RemarkPattern>,
+    excludes: Vec<RemarkPattern>,
+}
+
+impl RemarkFilter {
+    /// Compile the patterns, failing on the first invalid one
+    pub fn new(include_remarks: &[String], exclude_remarks: &[String]) -> Result<Self, String> {
+        Ok(RemarkFilter {
+            includes: compile_remark_patterns(include_remarks, "include_remarks")?,
+            excludes: compile_remark_patterns(exclude_remarks, "exclude_remarks")?,
+        })
+    }
+
+    /// Whether the filter has no patterns and keeps every node
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty() && self.excludes.is_empty()
+    }
+
+    /// Whether the node is kept
+    pub fn matches(&self, node: &Proxy) -> bool {
+        self.rejection(node).is_none()
+    }
+
+    /// Why the node is dropped, `None` when it is kept
+    pub fn rejection(&self, node: &Proxy) -> Option<String> {
+        if let Some(pattern) = self.excludes.iter().find(|pattern| pattern.matches(node)) {
+            return Some(format!("matches exclude_remarks '{}'", pattern.source));
+        }
+        (!self.includes.is_empty() && !self.includes.iter().any(|pattern| pattern.matches(node)))
+            .then(|| "matches no include_remarks pattern".to_string())
+    }
+
+    /// Drop the nodes the filter rejects, recording them in `explain`
+    ///
+    /// # Returns
+    /// The number of removed nodes
+    pub fn apply(&self, nodes: &mut Vec<Proxy>, explain: Option<&ExplainRecorder>) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let before = nodes.len();
+        nodes.retain(|node| match self.rejection(node) {
+            Some(reason) => {
+                if let Some(explain) = explain {
+                    explain.dropped(node, DropStage::RemarkFilter, reason);
+                }
+                false
+            }
+            None => true,
+        });
+        before - nodes.len()
+    }
+}
+
+/// Filters nodes by remark with include and exclude patterns
+///
+/// See [`RemarkFilter`]. All patterns are compiled before any node is
+/// touched, so an invalid pattern fails the whole call.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of removed nodes
+/// * `Err(String)` - The first invalid pattern
+pub fn filter_nodes_by_remark(
+    nodes: &mut Vec<Proxy>,
+    include_remarks: &[String],
+    exclude_remarks: &[String],
+    explain: Option<&ExplainRecorder>,
+) -> Result<usize, String> {
+    Ok(RemarkFilter::new(include_remarks, exclude_remarks)?.apply(nodes, explain))
+}
+
+/// Explain mode reason of a node dropped by `exclude_types`
+pub fn excluded_type_reason(node: &Proxy) -> String {
+    format!("type {} is excluded", node.proxy_type.to_string())
+}
+
+/// Drops every node whose proxy type is in `types`
+///
+/// # Returns
+/// The number of removed nodes
+pub fn filter_nodes_by_type(nodes: &mut Vec<Proxy>, types: &[ProxyType]) -> usize {
+    if types.is_empty() {
+        return 0;
+    }
+    let before = nodes.len();
+    nodes.retain(|node| !types.contains(&node.proxy_type));
+    before - nodes.len()
+}
+
+/// Name of a node under the name template, `None` without a template or
+/// when the template gives an empty name
+async fn templated_remark(node: &Proxy, extra: &ExtraSettings) -> Option<String> {
+    let template = extra.name_template.as_ref()?;
+    Some(template.render(node, &extra.region_names).await).filter(|remark| !remark.is_empty())
+}
+
+/// Give a node the remark of the stage `name`, which is added to `fired`
+/// when it changed the remark
+fn step(node: &mut Proxy, fired: &mut Vec<String>, name: &str, remark: String) {
+    if remark != node.remark {
+        node.remark = remark;
+        fired.push(name.to_string());
+    }
+}
+
+/// Applies the per-node stages to one node: name normalization, emoji
+/// removal, the name template, rename, then emoji. With
+/// [`name_template_after_rename`](ExtraSettings::name_template_after_rename)
+/// the template runs right after rename instead.
+pub async fn preprocess_node(node: &mut Proxy, extra: &mut ExtraSettings) {
+    let original = node.remark.clone();
+    let mut fired = Vec::new();
+    if extra.normalize_punctuation {
+        let remark = normalize_punctuation(&node.remark);
+        step(node, &mut fired, "normalize_punctuation", remark);
+    }
+    if extra.transliterate_regions {
+        let remark = transliterate_regions(&node.remark, &extra.region_names);
+        step(node, &mut fired, "transliterate_regions", remark);
+    }
+
+    // Remove emoji if needed
+    if extra.remove_emoji {
+        let remark = trim(&remove_emoji(&node.remark)).to_string();
+        step(node, &mut fired, "remove_emoji", remark);
+    }
+
+    if !extra.name_template_after_rename {
+        if let Some(remark) = templated_remark(node, extra).await {
+            step(node, &mut fired, "name_template", remark);
+        }
+    }
+
+    // Apply rename patterns
+    node_rename(node, extra, &mut fired).await;
+    if extra.name_template_after_rename {
+        if let Some(remark) = templated_remark(node, extra).await {
+            step(node, &mut fired, "name_template", remark);
+        }
+    }
+    if let Some(explain) = &extra.explain {
+        explain.renamed(&original, &node.remark, fired);
+    }
+
+    // Add emoji if needed
+    if extra.add_emoji {
+        if extra
+            .emoji_array
+            .iter()
+            .any(|pattern| !pattern.script.is_empty())
+        {
+            extra.init_js_context();
+        }
+        let remark = add_emoji(node, extra).await;
+        if let Some(explain) = &extra.explain {
+            if let Some(emoji) = remark.strip_suffix(node.remark.as_str()) {
+                if !emoji.is_empty() {
+                    explain.emoji(&node.remark, emoji.trim_end());
+                }
+            }
+        }
+        node.remark = remark;
+    }
+}
+
 /// Preprocesses nodes before conversion
 /// Based on the C++ preprocessNodes function
+///
+/// Stages run in order: type exclusion, deduplication, remark filters,
+/// liveness probing, per-node name normalization, emoji removal, the name
+/// template, rename and emoji, sorting, duplicate name numbering, then the
+/// [`node_transform`](ExtraSettings::node_transform) hook. Relay chains follow
+/// the renames of their upstream nodes and are rejected when they loop.
+/// Filtering, renaming and sorting are timed into
+/// [`stats`](ExtraSettings::stats), dropped and renamed nodes recorded into
+/// [`explain`](ExtraSettings::explain) when it is set.
 pub async fn preprocess_nodes(
     nodes: &mut Vec<Proxy>,
     extra: &mut ExtraSettings,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    preprocess(nodes, extra, true).await
+}
+
+/// [`preprocess_nodes`] without the remark filters, for nodes they were
+/// already applied to when the subscriptions were parsed
+pub(crate) async fn preprocess_parsed_nodes(
+    nodes: &mut Vec<Proxy>,
+    extra: &mut ExtraSettings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    preprocess(nodes, extra, false).await
+}
+
+async fn preprocess(
+    nodes: &mut Vec<Proxy>,
+    extra: &mut ExtraSettings,
+    filter_remarks: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let filter_timer = extra.stats.time(ConvertStage::Filter);
+    // Excluded protocols never reach any later stage
+    let tracker = DropTracker::start(extra.explain.as_ref(), nodes);
+    let excluded = filter_nodes_by_type(nodes, &extra.exclude_types);
+    tracker.finish(DropStage::ExcludeType, nodes, excluded_type_reason);
+    if excluded > 0 {
+        info!(
+            "Removed {} node(s) of excluded types, {} left",
+            excluded,
+            nodes.len()
+        );
+    }
+
+    // Ports are dropped by their original value, then rewritten
+    let tracker = DropTracker::start(extra.explain.as_ref(), nodes);
+    let dropped = extra.drop_ports.drop_nodes(nodes);
+    tracker.finish(DropStage::Port, nodes, dropped_port_reason);
+    if dropped > 0 {
+        info!(
+            "Removed {} node(s) on dropped ports, {} left",
+            dropped,
+            nodes.len()
+        );
+    }
+    let rewritten = extra.port_rewrite.rewrite_nodes(nodes);
+    if rewritten > 0 {
+        info!("Rewrote the port of {} node(s)", rewritten);
+    }
+
+    // Remove duplicates first so renaming cannot hide them
+    if extra.dedup {
+        let tracker = DropTracker::start(extra.explain.as_ref(), nodes);
+        let removed = extra.dedup_key.dedup_nodes(nodes);
+        tracker.finish(DropStage::Dedup, nodes, |_| {
+            format!("duplicate of an earlier node by {:?}", extra.dedup_key)
+        });
+        info!(
+            "Removed {} duplicate node(s), {} left",
+            removed,
+            nodes.len()
+        );
+    }
+
+    if filter_remarks {
+        let filtered = filter_nodes_by_remark(
+            nodes,
+            &extra.include_remarks,
+            &extra.exclude_remarks,
+            extra.explain.as_ref(),
+        )?;
+        if filtered > 0 {
+            info!(
+                "Filtered out {} node(s) by remark, {} left",
+                filtered,
+                nodes.len()
+            );
+        }
+    }
+
+    // Probe the remaining nodes before spending time on their remarks
+    if extra.probe_liveness {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let tracker = DropTracker::start(extra.explain.as_ref(), nodes);
+            let removed = crate::utils::probe::probe_nodes(
+                nodes,
+                extra.probe_concurrency,
+                extra.probe_timeout,
+            )
+            .await;
+            tracker.finish(DropStage::Probe, nodes, |_| {
+                format!("unreachable within {:?}", extra.probe_timeout)
+            });
+            info!(
+                "Removed {} unreachable node(s), {} left",
+                removed,
+                nodes.len()
+            );
+        }
+        #[cfg(target_arch = "wasm32")]
+        log::warn!("Liveness probing is not available on this target");
+    }
+    drop(filter_timer);
+
+    // Relay chains name their upstream, which must follow its renames
+    let chained = has_relay_chains(nodes);
+    let remarks = |nodes: &[Proxy]| nodes.iter().map(|n| n.remark.clone()).collect::<Vec<_>>();
+
     // Process each node
+    let old_names = chained.then(|| remarks(nodes));
+    let rename_timer = extra.stats.time(ConvertStage::Rename);
     for node in nodes.iter_mut() {
-        // Remove emoji if needed
-        if extra.remove_emoji {
-            node.remark = trim(&remove_emoji(&node.remark)).to_string();
-        }
-
-        // Apply rename patterns
-        node_rename(node, extra).await;
-
-        // Add emoji if needed
-        if extra.add_emoji {
-            if extra
-                .emoji_array
-                .iter()
-                .any(|pattern| !pattern.script.is_empty())
-            {
-                extra.init_js_context();
-            }
-            node.remark = add_emoji(node, &extra.emoji_array, extra).await;
-        }
+        preprocess_node(node, extra).await;
+    }
+    drop(rename_timer);
+    if let Some(old_names) = old_names {
+        relink_upstreams(nodes, &old_names);
     }
 
     // Sort nodes if needed
     if extra.sort_flag && extra.authorized {
         info!("Sorting {} nodes", nodes.len());
-        extra.eval_sort_nodes(nodes).await?;
+        let _timer = extra.stats.time(ConvertStage::Sort);
+        match extra.sort_key {
+            Some(sort_key) => sort_key.sort_nodes(nodes),
+            None => extra.eval_sort_nodes(nodes).await?,
+        }
+    }
+
+    // Numbered last so it sees the final names in output order
+    if extra.append_index {
+        let old_names = chained.then(|| remarks(nodes));
+        let renamed = append_duplicate_index(
+            nodes,
+            extra.append_index_digits,
+            &extra.append_index_separator,
+        );
+        if renamed > 0 {
+            info!("Appended an index to {} node(s) with shared names", renamed);
+        }
+        if let Some(old_names) = old_names {
+            relink_upstreams(nodes, &old_names);
+        }
+    }
+
+    if chained {
+        check_relay_chains(nodes)?;
+    }
+
+    if let Some(transform) = extra.node_transform.as_mut() {
+        transform(nodes);
+        info!("Node transform hook left {} node(s)", nodes.len());
     }
 
     debug!("Node preprocessing completed for {} nodes", nodes.len());
@@ -132,3 +527,380 @@ pub fn append_type_to_remark(nodes: &mut Vec<Proxy>) {
         }
     }
 }
+
+/// Appends an index to names shared by several nodes
+///
+/// Nodes with a unique name are left alone, the others are numbered from 1 in
+/// list order, e.g. `HK 01`, `HK 02`. Indices giving a name another node
+/// already has are skipped.
+///
+/// # Returns
+/// The number of renamed nodes
+pub fn append_duplicate_index(nodes: &mut [Proxy], digits: usize, separator: &str) -> usize {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for node in nodes.iter() {
+        *counts.entry(node.remark.clone()).or_default() += 1;
+    }
+    if counts.values().all(|count| *count == 1) {
+        return 0;
+    }
+
+    let mut taken: HashSet<String> = counts.keys().cloned().collect();
+    let mut next_index: HashMap<String, usize> = HashMap::new();
+    let mut renamed = 0;
+    for node in nodes.iter_mut() {
+        if counts[&node.remark] < 2 {
+            continue;
+        }
+        let index = next_index.entry(node.remark.clone()).or_default();
+        let name = loop {
+            *index += 1;
+            let candidate = format!(
+                "{}{}{:0width$}",
+                node.remark,
+                separator,
+                index,
+                width = digits
+            );
+            if !taken.contains(&candidate) {
+                break candidate;
+            }
+        };
+        taken.insert(name.clone());
+        node.remark = name;
+        renamed += 1;
+    }
+    renamed
+}
+
+/// Names unnamed nodes and makes duplicate names unique
+///
+/// Nodes with an empty or blank name are named `{type}_{server}_{port}`.
+/// The first node keeps a name shared by several nodes, later ones get a
+/// number from 2, e.g. `HK`, `HK 2`, `HK 3`, so upstream names keep pointing
+/// at the node they meant.
+///
+/// # Returns
+/// The number of renamed nodes
+pub fn normalize_node_names(nodes: &mut [Proxy]) -> usize {
+    let mut renamed = 0;
+    for node in nodes.iter_mut() {
+        if node.remark.trim().is_empty() {
+            node.remark = format!(
+                "{}_{}_{}",
+                node.proxy_type.to_string(),
+                node.hostname,
+                node.port
+            );
+            renamed += 1;
+        }
+    }
+
+    let mut taken: HashSet<String> = nodes.iter().map(|node| node.remark.clone()).collect();
+    let mut seen: HashSet<String> = HashSet::new();
+    for node in nodes.iter_mut() {
+        if seen.insert(node.remark.clone()) {
+            continue;
+        }
+        let mut index = 2;
+        let name = loop {
+            let candidate = format!("{} {}", node.remark, index);
+            if !taken.contains(&candidate) {
+                break candidate;
+            }
+            index += 1;
+        };
+        taken.insert(name.clone());
+        seen.insert(name.clone());
+        node.remark = name;
+        renamed += 1;
+    }
+    renamed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(remarks: &[&str]) -> Vec<Proxy> {
+        remarks
+            .iter()
+            .map(|remark| Proxy {
+                remark: remark.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_nodes_by_remark() {
+        let mut list = nodes(&["HK 01", "hk 02 Expire", "JP 01", "US 01"]);
+        let removed = filter_nodes_by_remark(
+            &mut list,
+            &["(?i)hk".to_string(), "JP".to_string()],
+            &["Expire".to_string()],
+            None,
+        )
+        .unwrap();
+        assert_eq!(removed, 2);
+        let remarks: Vec<&str> = list.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["HK 01", "JP 01"]);
+
+        // Node matchers narrow the pattern, or decide alone
+        list[1].group = "Old".to_string();
+        let filter = RemarkFilter::new(&[], &["!!GROUP=Old!!HK".to_string()]).unwrap();
+        assert!(filter.matches(&list[0]) && filter.matches(&list[1]));
+        let filter = RemarkFilter::new(&[], &["!!GROUP=Old".to_string()]).unwrap();
+        assert!(filter.matches(&list[0]));
+        assert_eq!(
+            filter.rejection(&list[1]).as_deref(),
+            Some("matches exclude_remarks '!!GROUP=Old'")
+        );
+    }
+
+    #[test]
+    fn test_node_transform_sees_renamed_nodes() {
+        let mut list = nodes(&["HK 01", "JP 01"]);
+        let mut extra = ExtraSettings::builder()
+            .exclude_remarks(vec!["JP".to_string()])
+            .node_transform(|nodes| {
+                assert_eq!(nodes.len(), 1);
+                assert_eq!(nodes[0].remark, "Hong Kong 01");
+                nodes.push(Proxy {
+                    remark: "Injected".to_string(),
+                    ..Default::default()
+                });
+            })
+            .build();
+        extra.rename_array = vec![crate::models::RegexMatchConfig::new(
+            "HK".to_string(),
+            "Hong Kong".to_string(),
+            String::new(),
+        )];
+
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+        let remarks: Vec<&str> = list.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["Hong Kong 01", "Injected"]);
+    }
+
+    #[test]
+    fn test_append_duplicate_index() {
+        let mut list = nodes(&[
+            "Hong Kong",
+            "Japan",
+            "Hong Kong",
+            "Hong Kong 01",
+            "Hong Kong",
+        ]);
+        assert_eq!(append_duplicate_index(&mut list, 2, " "), 3);
+        let remarks: Vec<&str> = list.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(
+            remarks,
+            vec![
+                "Hong Kong 02",
+                "Japan",
+                "Hong Kong 03",
+                "Hong Kong 01",
+                "Hong Kong 04"
+            ]
+        );
+
+        let mut unique = nodes(&["HK", "JP"]);
+        assert_eq!(append_duplicate_index(&mut unique, 2, " "), 0);
+        assert_eq!(unique[0].remark, "HK");
+
+        let mut list = nodes(&["HK", "HK"]);
+        append_duplicate_index(&mut list, 0, "-");
+        assert_eq!(list[1].remark, "HK-2");
+    }
+
+    #[test]
+    fn test_normalize_node_names() {
+        let mut list = nodes(&["HK", "", "HK", "HK 2", " ", "HK"]);
+        for node in list.iter_mut() {
+            node.proxy_type = ProxyType::Trojan;
+            node.hostname = "example.com".to_string();
+            node.port = 443;
+        }
+        assert_eq!(normalize_node_names(&mut list), 5);
+        let remarks: Vec<&str> = list.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(
+            remarks,
+            vec![
+                "HK",
+                "Trojan_example.com_443",
+                "HK 3",
+                "HK 2",
+                "Trojan_example.com_443 2",
+                "HK 4"
+            ]
+        );
+
+        let mut unique = nodes(&["HK", "JP"]);
+        assert_eq!(normalize_node_names(&mut unique), 0);
+    }
+
+    #[test]
+    fn test_relay_chains_follow_renames() {
+        let mut list = nodes(&["HK 01", "Relay"]);
+        list[0].underlying_proxy = Some("Relay".to_string());
+        let mut extra = ExtraSettings::default();
+        extra.rename_array = vec![crate::models::RegexMatchConfig::new(
+            "Relay".to_string(),
+            "JP Relay".to_string(),
+            String::new(),
+        )];
+
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+        assert_eq!(list[0].underlying_proxy.as_deref(), Some("JP Relay"));
+
+        list[1].underlying_proxy = Some("HK 01".to_string());
+        let err = futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap_err();
+        assert!(err.to_string().contains("loops back"));
+    }
+
+    #[test]
+    fn test_remove_emoji_runs_before_add_emoji() {
+        let mut list = nodes(&["🇺🇸 HK 01", "🏳️‍🌈 JP 01"]);
+        let mut extra = ExtraSettings::builder()
+            .remove_emoji(true)
+            .add_emoji(true)
+            .build();
+        extra.emoji_array = vec![crate::models::RegexMatchConfig::new(
+            "HK".to_string(),
+            "🇭🇰".to_string(),
+            String::new(),
+        )];
+
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+        let remarks: Vec<&str> = list.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["🇭🇰 HK 01", "JP 01"]);
+    }
+
+    #[cfg(feature = "js-runtime")]
+    #[test]
+    fn test_rename_script_timeout_resets_context() {
+        let mut list = nodes(&["HK 01"]);
+        let mut extra = ExtraSettings::builder()
+            .script_timeout(100)
+            .script_clean_context(false)
+            .build();
+        let script = |source: &str| {
+            crate::models::RegexMatchConfig::new(String::new(), String::new(), source.to_string())
+        };
+        extra.rename_array = vec![
+            script("var leaked = 1; function rename(node) { for (;;) {} }"),
+            script("function rename(node) { return typeof leaked; }"),
+        ];
+
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+        assert_eq!(list[0].remark, "undefined");
+    }
+
+    #[test]
+    fn test_append_index_runs_after_rename() {
+        let mut list = nodes(&["HK 01", "HK 02"]);
+        let mut extra = ExtraSettings::builder().append_index(true).build();
+        extra.rename_array = vec![crate::models::RegexMatchConfig::new(
+            "HK \\d+".to_string(),
+            "Hong Kong".to_string(),
+            String::new(),
+        )];
+
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+        let remarks: Vec<&str> = list.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["Hong Kong 01", "Hong Kong 02"]);
+    }
+
+    #[test]
+    fn test_name_template_stage() {
+        let rename = vec![crate::models::RegexMatchConfig::new(
+            "Hong Kong".to_string(),
+            "HKG".to_string(),
+            String::new(),
+        )];
+        let run = |after: bool| {
+            let mut list = nodes(&["HK 01"]);
+            let mut extra = ExtraSettings::builder()
+                .region_name("HK", "Hong Kong")
+                .name_template("{region} {name}")
+                .name_template_after_rename(after)
+                .build();
+            extra.rename_array = rename.clone();
+            futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+            list[0].remark.clone()
+        };
+        assert_eq!(run(false), "HKG HK 01");
+        assert_eq!(run(true), "Hong Kong HK 01");
+    }
+
+    #[test]
+    fn test_exclude_types_removes_nodes_from_groups() {
+        use crate::generator::config::group::group_generate_members;
+        use crate::models::ProxyGroupConfig;
+
+        let mut list: Vec<Proxy> = [
+            ("HK SS", ProxyType::Shadowsocks),
+            ("HK SSR", ProxyType::ShadowsocksR),
+            ("HK HTTP", ProxyType::HTTP),
+            ("JP Trojan", ProxyType::Trojan),
+        ]
+        .iter()
+        .map(|(remark, proxy_type)| Proxy {
+            proxy_type: *proxy_type,
+            remark: remark.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        let mut extra = ExtraSettings {
+            exclude_types: vec![ProxyType::ShadowsocksR, ProxyType::HTTP],
+            ..Default::default()
+        };
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+
+        let group = ProxyGroupConfig {
+            proxies: vec![".*".to_string()],
+            filter: Some("^HK".to_string()),
+            ..Default::default()
+        };
+        let members = group_generate_members(&group, &list, true, "DIRECT", &extra);
+        assert_eq!(members, vec!["HK SS", "JP Trojan"]);
+
+        let group = ProxyGroupConfig {
+            proxies: vec!["!!TYPE=SSR|HTTP".to_string()],
+            ..Default::default()
+        };
+        let members = group_generate_members(&group, &list, true, "DIRECT", &extra);
+        assert_eq!(members, vec!["DIRECT"]);
+    }
+
+    #[test]
+    fn test_filter_nodes_by_remark_invalid_pattern() {
+        let mut list = nodes(&["HK 01"]);
+        let err = filter_nodes_by_remark(&mut list, &[], &["HK(".to_string()], None).unwrap_err();
+        assert!(err.contains("exclude_remarks"));
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn test_preprocess_nodes_records_stage_times() {
+        let mut list = nodes(&["JP 01", "HK 01"]);
+        let recorder = crate::utils::StatsRecorder::default();
+        let mut extra = ExtraSettings::builder()
+            .sort(true)
+            .authorized(true)
+            .sort_key(Some(crate::models::SortKey::new(
+                crate::models::SortField::Name,
+            )))
+            .stats(recorder.clone())
+            .build();
+
+        futures::executor::block_on(preprocess_nodes(&mut list, &mut extra)).unwrap();
+        assert_eq!(list[0].remark, "HK 01");
+        let stats = recorder.snapshot();
+        assert!(stats.filter + stats.rename + stats.sort > std::time::Duration::ZERO);
+        assert_eq!(stats.fetch, std::time::Duration::ZERO);
+        assert_eq!(stats.emit, std::time::Duration::ZERO);
+        assert_eq!(stats, extra.stats.snapshot());
+    }
+}
@@ -0,0 +1,129 @@
+//! Liveness probing of nodes before export
+//!
+//! Each node's server is resolved and TCP-connected with a timeout. DNS goes
+//! through [`tokio::net::lookup_host`], so no lookup blocks the runtime.
+
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+
+use crate::models::{Proxy, ProxyType};
+
+/// Probe a single server, returning the connect latency
+///
+/// The timeout covers both the lookup and the connection attempts.
+pub async fn probe_server(host: &str, port: u16, limit: Duration) -> Result<Duration, String> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let start = Instant::now();
+    let attempt = async {
+        let addrs = lookup_host((host, port))
+            .await
+            .map_err(|e| format!("lookup failed: {}", e))?;
+        let mut last_error = "no address".to_string();
+        for addr in addrs {
+            match TcpStream::connect(addr).await {
+                Ok(_) => return Ok(start.elapsed()),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(last_error)
+    };
+    timeout(limit, attempt)
+        .await
+        .map_err(|_| format!("timed out after {} ms", limit.as_millis()))?
+}
+
+/// Drop nodes whose server does not accept a TCP connection
+///
+/// At most `concurrency` probes run at once and the order of the kept nodes
+/// is preserved. Nodes of UDP based protocols (WireGuard, Hysteria, TUIC)
+/// cannot be probed over TCP and are always kept.
+///
+/// # Returns
+/// The number of removed nodes
+pub async fn probe_nodes(nodes: &mut Vec<Proxy>, concurrency: usize, timeout_ms: u32) -> usize {
+    let limit = Duration::from_millis(timeout_ms.max(1) as u64);
+    let results: Vec<bool> = stream::iter(nodes.iter())
+        .map(|node| async move {
+            if !is_tcp_probeable(node) {
+                return true;
+            }
+            match probe_server(&node.hostname, node.port, limit).await {
+                Ok(latency) => {
+                    info!(
+                        "Probe '{}' {}:{} alive in {} ms",
+                        node.remark,
+                        node.hostname,
+                        node.port,
+                        latency.as_millis()
+                    );
+                    true
+                }
+                Err(e) => {
+                    warn!(
+                        "Probe '{}' {}:{} failed: {}",
+                        node.remark, node.hostname, node.port, e
+                    );
+                    false
+                }
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let before = nodes.len();
+    let mut alive = results.into_iter();
+    nodes.retain(|_| alive.next().unwrap_or(true));
+    before - nodes.len()
+}
+
+/// Whether a node's server is expected to accept TCP connections
+fn is_tcp_probeable(node: &Proxy) -> bool {
+    !node.hostname.is_empty()
+        && !matches!(
+            node.proxy_type,
+            ProxyType::Unknown
+                | ProxyType::WireGuard
+                | ProxyType::Hysteria
+                | ProxyType::Hysteria2
+                | ProxyType::Tuic
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(remark: &str, port: u16) -> Proxy {
+        Proxy {
+            proxy_type: ProxyType::Socks5,
+            remark: remark.to_string(),
+            hostname: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_drops_dead_nodes() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let alive_port = listener.local_addr().unwrap().port();
+        let dead_port = {
+            let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            closed.local_addr().unwrap().port()
+        };
+
+        let mut nodes = vec![
+            node("alive", alive_port),
+            node("dead", dead_port),
+            node("alive too", alive_port),
+        ];
+        assert_eq!(probe_nodes(&mut nodes, 2, 1000).await, 1);
+        let remarks: Vec<&str> = nodes.iter().map(|n| n.remark.as_str()).collect();
+        assert_eq!(remarks, vec!["alive", "alive too"]);
+    }
+}
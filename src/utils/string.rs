@@ -226,17 +226,35 @@ pub fn join<T: AsRef<str>>(parts: &[T], separator: &str) -> String {
         .join(separator)
 }
 
+/// Split a comma separated ALPN value into its protocols
+///
+/// Blank entries and repeats are dropped, the order is kept since it is the
+/// client's preference.
+pub fn split_alpn(value: &str) -> Vec<String> {
+    let mut alpn: Vec<String> = Vec::new();
+    for proto in value.split(',').map(str::trim) {
+        if !proto.is_empty() && !alpn.iter().any(|p| p == proto) {
+            alpn.push(proto.to_string());
+        }
+    }
+    alpn
+}
+
 lazy_static! {
-    // This regex targets characters with the Unicode Emoji property.
-    // Combining Presentation and Extended_Pictographic covers standard emojis, components, and sequences.
-    static ref EMOJI_REGEX: Regex = Regex::new(r"\p{Emoji_Presentation}|\p{Extended_Pictographic}").unwrap();
+    // Leading emoji sequences and the whitespace between them: keycaps, flags
+    // (regional indicator pairs and tag sequences), pictographs with their
+    // skin tone modifiers and variation selectors, joined by ZWJ.
+    static ref LEADING_EMOJI_REGEX: Regex = Regex::new(
+        r"^(?:[0-9#*]\x{FE0F}?\x{20E3}|[\s\p{Emoji_Presentation}\p{Extended_Pictographic}\p{Regional_Indicator}\p{Emoji_Modifier}\x{200D}\x{FE0E}\x{FE0F}\x{20E3}\x{E0020}-\x{E007F}])+"
+    )
+    .unwrap();
 }
 
-/// Removes emoji characters from a string using a regular expression.
+/// Removes the emoji a node name starts with, like the C++ `removeEmoji`
 ///
-/// This implementation uses the `regex` crate with Unicode property support.
-/// It targets characters with the `Emoji_Presentation` or `Extended_Pictographic`
-/// properties, which cover most standard emojis.
+/// Flags such as 🇭🇰, keycaps and ZWJ sequences such as 🏳️‍🌈 are removed
+/// as a whole together with the whitespace around them, so no stray
+/// variation selector or joiner is left at the start of the name.
 ///
 /// # Arguments
 ///
@@ -244,10 +262,71 @@ lazy_static! {
 ///
 /// # Returns
 ///
-/// A new string with emoji characters removed.
+/// The string without its leading emoji, or the original string if it only
+/// consists of emoji.
 pub fn remove_emoji(s: &str) -> String {
-    // Replace all matches with an empty string.
-    EMOJI_REGEX.replace_all(s, "").into_owned()
+    let rest = LEADING_EMOJI_REGEX.replace(s, "");
+    if rest.is_empty() {
+        s.to_string()
+    } else {
+        rest.into_owned()
+    }
+}
+
+/// Compare two strings in natural order, so digit runs compare by value
+///
+/// Letters are compared case-insensitively, falling back to a plain
+/// comparison when two strings only differ in case or leading zeros.
+///
+/// # Arguments
+///
+/// * `a` - The first string
+/// * `b` - The second string
+///
+/// # Returns
+///
+/// The ordering of `a` relative to `b`, e.g. "Node 2" is less than "Node 10".
+pub fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let mut a_num = String::new();
+                while let Some(c) = a_chars.next_if(|c| c.is_ascii_digit()) {
+                    a_num.push(c);
+                }
+                let mut b_num = String::new();
+                while let Some(c) = b_chars.next_if(|c| c.is_ascii_digit()) {
+                    b_num.push(c);
+                }
+
+                let a_trimmed = a_num.trim_start_matches('0');
+                let b_trimmed = b_num.trim_start_matches('0');
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                let ordering = ca.to_lowercase().cmp(cb.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
 }
 
 /// Calculate MD5 hash for a string
@@ -402,6 +481,15 @@ mod tests {
         assert_eq!(join(&empty, ","), "");
     }
 
+    #[test]
+    fn test_split_alpn() {
+        assert_eq!(
+            split_alpn(" h3, h2 ,,h3,http/1.1"),
+            vec!["h3", "h2", "http/1.1"]
+        );
+        assert!(split_alpn("").is_empty());
+    }
+
     #[test]
     fn test_remove_emoji() {
         // Test with emoji at the beginning
@@ -414,6 +502,34 @@ mod tests {
         assert_eq!(remove_emoji("😀"), "😀"); // Preserves the original if all emoji
     }
 
+    #[test]
+    fn test_remove_emoji_sequences() {
+        // Flags and the whitespace after them
+        assert_eq!(remove_emoji("🇭🇰 Hong Kong 01"), "Hong Kong 01");
+        assert_eq!(remove_emoji("🇺🇸🇭🇰  US-HK"), "US-HK");
+        // ZWJ sequences, variation selectors, skin tones, keycaps and tag flags
+        assert_eq!(remove_emoji("🏳️‍🌈 Node"), "Node");
+        assert_eq!(remove_emoji("👨🏽‍💻 Dev"), "Dev");
+        assert_eq!(remove_emoji("☁️ ✈️Cloud"), "Cloud");
+        assert_eq!(remove_emoji("1️⃣ First"), "First");
+        assert_eq!(remove_emoji("🏴󠁧󠁢󠁳󠁣󠁴󠁿 Scotland"), "Scotland");
+        // Only leading emoji are removed
+        assert_eq!(remove_emoji("HK 🚀 01"), "HK 🚀 01");
+        assert_eq!(remove_emoji("10 Gbps"), "10 Gbps");
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        use std::cmp::Ordering;
+
+        assert_eq!(natural_cmp("Node 2", "Node 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Node 10", "Node 9"), Ordering::Greater);
+        assert_eq!(natural_cmp("hk 01", "HK 1"), "hk 01".cmp("HK 1"));
+        assert_eq!(natural_cmp("HK", "HK 1"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "B"), Ordering::Less);
+        assert_eq!(natural_cmp("Node 3", "Node 3"), Ordering::Equal);
+    }
+
     #[test]
     fn test_md5() {
         // Test cases with known MD5 hashes
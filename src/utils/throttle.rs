@@ -0,0 +1,167 @@
+//! Bound on simultaneous conversions
+//!
+//! Conversions of a hosted instance share its CPU, and the QuickJS runtimes
+//! of filter scripts are the heaviest part of them. A [`ConversionLimiter`]
+//! lets a fixed number of conversions run at once and queues a bounded number
+//! more; anything beyond that is turned away with [`ConversionBusy`] so the
+//! web handlers can answer 503. The global limiter follows the
+//! `max_concurrent_conversions` and `max_queued_conversions` [`Settings`],
+//! where a limit of 0 lets every conversion through.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::Settings;
+
+lazy_static::lazy_static! {
+    static ref GLOBAL_LIMITER: Mutex<Option<Arc<ConversionLimiter>>> = Mutex::new(None);
+}
+
+/// A conversion was turned away because all slots and the queue are taken
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Too many conversions in progress ({running} running, {queued} queued)")]
+pub struct ConversionBusy {
+    pub running: usize,
+    pub queued: usize,
+}
+
+/// Held while a conversion runs, the slot is freed when it is dropped
+#[derive(Debug)]
+pub struct ConversionSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Counts a waiting conversion until it gets a slot or gives up
+struct QueueEntry<'a>(&'a AtomicUsize);
+
+impl Drop for QueueEntry<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Semaphore bounding how many conversions run and wait at once
+#[derive(Debug)]
+pub struct ConversionLimiter {
+    limit: usize,
+    max_queued: usize,
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+impl ConversionLimiter {
+    /// Create a limiter running `limit` conversions at once, 0 for no limit,
+    /// with up to `max_queued` more waiting for a slot
+    pub fn new(limit: usize, max_queued: usize) -> Self {
+        ConversionLimiter {
+            limit,
+            max_queued,
+            semaphore: Arc::new(Semaphore::new(limit)),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for a free slot, or fail at once if the queue is full
+    pub async fn acquire(&self) -> Result<ConversionSlot, ConversionBusy> {
+        if self.limit == 0 {
+            return Ok(ConversionSlot { _permit: None });
+        }
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(ConversionSlot {
+                _permit: Some(permit),
+            });
+        }
+
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst);
+        let _entry = QueueEntry(&self.queued);
+        if queued >= self.max_queued {
+            return Err(ConversionBusy {
+                running: self.limit,
+                queued,
+            });
+        }
+
+        // The semaphore is never closed
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("conversion semaphore closed");
+        Ok(ConversionSlot {
+            _permit: Some(permit),
+        })
+    }
+
+    /// Number of conversions waiting for a slot
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+}
+
+/// The limiter of the current global settings
+///
+/// It is rebuilt when the limits change on a settings reload; conversions
+/// holding a slot of the old limiter run to completion.
+pub fn conversion_limiter() -> Arc<ConversionLimiter> {
+    let settings = Settings::snapshot();
+    let mut global = GLOBAL_LIMITER.lock().unwrap_or_else(|e| e.into_inner());
+    match global.as_ref() {
+        Some(limiter)
+            if limiter.limit == settings.max_concurrent_conversions
+                && limiter.max_queued == settings.max_queued_conversions =>
+        {
+            limiter.clone()
+        }
+        _ => {
+            let limiter = Arc::new(ConversionLimiter::new(
+                settings.max_concurrent_conversions,
+                settings.max_queued_conversions,
+            ));
+            *global = Some(limiter.clone());
+            limiter
+        }
+    }
+}
+
+/// Take a slot of the global limiter for one conversion
+pub async fn acquire_conversion_slot() -> Result<ConversionSlot, ConversionBusy> {
+    conversion_limiter().acquire().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conversion_limiter() {
+        let limiter = Arc::new(ConversionLimiter::new(1, 1));
+        let running = limiter.acquire().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        while limiter.queued() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            limiter.acquire().await.unwrap_err(),
+            ConversionBusy {
+                running: 1,
+                queued: 1
+            }
+        );
+
+        drop(running);
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+        assert_eq!(limiter.queued(), 0);
+
+        let unlimited = ConversionLimiter::new(0, 0);
+        let _a = unlimited.acquire().await.unwrap();
+        let _b = unlimited.acquire().await.unwrap();
+    }
+}
@@ -0,0 +1,237 @@
+//! Per-stage timing of conversions
+//!
+//! Each stage of a conversion is timed with a [`StageTimer`], which logs the
+//! duration at debug level and adds it to a [`StatsRecorder`] when dropped.
+//! The totals end up in the [`ConvertStats`] returned with the result, so a
+//! hosted deployment can report them per request.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Stages of a conversion that are timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConvertStage {
+    /// Downloading subscriptions and reading local inputs
+    Fetch,
+    /// Parsing the fetched content into nodes
+    Parse,
+    /// Type exclusion, deduplication, remark filters, liveness probing and
+    /// the filter script
+    Filter,
+    /// Per-node emoji removal, rename and emoji
+    Rename,
+    /// Sorting nodes
+    Sort,
+    /// Generating the output config
+    Emit,
+    /// Evaluating QuickJS scripts, whatever stage runs them
+    Script,
+}
+
+impl ConvertStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConvertStage::Fetch => "fetch",
+            ConvertStage::Parse => "parse",
+            ConvertStage::Filter => "filter",
+            ConvertStage::Rename => "rename",
+            ConvertStage::Sort => "sort",
+            ConvertStage::Emit => "emit",
+            ConvertStage::Script => "script",
+        }
+    }
+}
+
+impl fmt::Display for ConvertStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Time spent in each stage of a conversion
+///
+/// Fetch and parse times are summed over subscriptions, which may overlap
+/// when they are fetched concurrently. `script` is the QuickJS share of the
+/// other stages: the filter script counts in `filter` as well, rename and
+/// emoji scripts in `rename` and the sort script in `sort`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvertStats {
+    pub fetch: Duration,
+    pub parse: Duration,
+    pub filter: Duration,
+    pub rename: Duration,
+    pub sort: Duration,
+    pub emit: Duration,
+    pub script: Duration,
+    /// Wall time of the whole conversion
+    pub total: Duration,
+}
+
+impl ConvertStats {
+    /// Time spent in a stage
+    pub fn get(&self, stage: ConvertStage) -> Duration {
+        match stage {
+            ConvertStage::Fetch => self.fetch,
+            ConvertStage::Parse => self.parse,
+            ConvertStage::Filter => self.filter,
+            ConvertStage::Rename => self.rename,
+            ConvertStage::Sort => self.sort,
+            ConvertStage::Emit => self.emit,
+            ConvertStage::Script => self.script,
+        }
+    }
+
+    /// Add time spent in a stage
+    pub fn add(&mut self, stage: ConvertStage, elapsed: Duration) {
+        let slot = match stage {
+            ConvertStage::Fetch => &mut self.fetch,
+            ConvertStage::Parse => &mut self.parse,
+            ConvertStage::Filter => &mut self.filter,
+            ConvertStage::Rename => &mut self.rename,
+            ConvertStage::Sort => &mut self.sort,
+            ConvertStage::Emit => &mut self.emit,
+            ConvertStage::Script => &mut self.script,
+        };
+        *slot += elapsed;
+    }
+
+    /// Add every stage of `other`, the total included
+    pub fn merge(&mut self, other: &ConvertStats) {
+        self.fetch += other.fetch;
+        self.parse += other.parse;
+        self.filter += other.filter;
+        self.rename += other.rename;
+        self.sort += other.sort;
+        self.emit += other.emit;
+        self.script += other.script;
+        self.total += other.total;
+    }
+}
+
+/// Shared accumulator of stage times
+///
+/// Clones record into the same [`ConvertStats`], so timers can outlive the
+/// borrow of whatever owns the recorder.
+#[derive(Debug, Clone, Default)]
+pub struct StatsRecorder(Arc<Mutex<ConvertStats>>);
+
+impl StatsRecorder {
+    /// Start timing a stage, the time is recorded when the timer is dropped
+    pub fn time(&self, stage: ConvertStage) -> StageTimer {
+        StageTimer {
+            stage,
+            start: Stopwatch::start(),
+            recorder: self.clone(),
+        }
+    }
+
+    /// Add time spent in a stage
+    pub fn record(&self, stage: ConvertStage, elapsed: Duration) {
+        if let Ok(mut stats) = self.0.lock() {
+            stats.add(stage, elapsed);
+        }
+    }
+
+    /// Add every stage of `other`
+    pub fn merge(&self, other: &ConvertStats) {
+        if let Ok(mut stats) = self.0.lock() {
+            stats.merge(other);
+        }
+    }
+
+    /// Stage times recorded so far
+    pub fn snapshot(&self) -> ConvertStats {
+        self.0.lock().map(|stats| *stats).unwrap_or_default()
+    }
+}
+
+/// Running timer of one stage, see [`StatsRecorder::time`]
+#[derive(Debug)]
+pub struct StageTimer {
+    stage: ConvertStage,
+    start: Stopwatch,
+    recorder: StatsRecorder,
+}
+
+impl StageTimer {
+    /// Time since the timer started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        let elapsed = self.elapsed();
+        // Scripts run once per node, their total is logged with the stats
+        if self.stage == ConvertStage::Script {
+            log::trace!("script evaluation took {:?}", elapsed);
+        } else {
+            log::debug!("{} stage took {:?}", self.stage, elapsed);
+        }
+        self.recorder.record(self.stage, elapsed);
+    }
+}
+
+/// Monotonic stopwatch, `Instant` panics on wasm so the JavaScript clock is
+/// used there
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch(
+    #[cfg(not(target_arch = "wasm32"))] std::time::Instant,
+    #[cfg(target_arch = "wasm32")] f64,
+);
+
+impl Stopwatch {
+    /// Start measuring from now
+    pub fn start() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            Stopwatch(std::time::Instant::now())
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Stopwatch(js_sys::Date::now())
+        }
+    }
+
+    /// Time since the stopwatch started
+    pub fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.0.elapsed()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Duration::from_secs_f64((js_sys::Date::now() - self.0).max(0.0) / 1000.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_recorder() {
+        let recorder = StatsRecorder::default();
+        {
+            let _timer = recorder.time(ConvertStage::Sort);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        recorder.record(ConvertStage::Script, Duration::from_millis(5));
+        recorder
+            .clone()
+            .record(ConvertStage::Script, Duration::from_millis(5));
+
+        let mut other = ConvertStats::default();
+        other.add(ConvertStage::Fetch, Duration::from_millis(7));
+        recorder.merge(&other);
+
+        let stats = recorder.snapshot();
+        assert!(stats.sort >= Duration::from_millis(2));
+        assert_eq!(stats.get(ConvertStage::Script), Duration::from_millis(10));
+        assert_eq!(stats.fetch, Duration::from_millis(7));
+        assert_eq!(stats.emit, Duration::ZERO);
+    }
+}
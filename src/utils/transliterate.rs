@@ -0,0 +1,275 @@
+//! Canonical forms of node names
+//!
+//! Providers mix full-width punctuation and Chinese region names into node
+//! names, which some clients display or sort badly. Two opt-in steps run
+//! before rename rules:
+//!
+//! - [`normalize_punctuation`] maps full-width forms (`（Ｈ Ｋ）０１`) and
+//!   common CJK brackets and separators to ASCII;
+//! - [`transliterate_regions`] replaces known region names with their
+//!   English name, from [`REGION_NAMES`] and any entries a user adds.
+//!
+//! [`region_of_code`] gives the same English names for the country codes
+//! GeoIP lookups return.
+//!
+//! Both work on whole characters, so emoji, flags and their joiners pass
+//! through untouched.
+
+/// Region names replaced by [`transliterate_regions`]
+pub const REGION_NAMES: &[(&str, &str)] = &[
+    ("香港", "Hong Kong"),
+    ("澳门", "Macau"),
+    ("澳門", "Macau"),
+    ("台湾", "Taiwan"),
+    ("台灣", "Taiwan"),
+    ("日本", "Japan"),
+    ("东京", "Tokyo"),
+    ("東京", "Tokyo"),
+    ("大阪", "Osaka"),
+    ("韩国", "Korea"),
+    ("韓國", "Korea"),
+    ("首尔", "Seoul"),
+    ("新加坡", "Singapore"),
+    ("狮城", "Singapore"),
+    ("美国", "United States"),
+    ("美國", "United States"),
+    ("洛杉矶", "Los Angeles"),
+    ("圣何塞", "San Jose"),
+    ("英国", "United Kingdom"),
+    ("英國", "United Kingdom"),
+    ("伦敦", "London"),
+    ("德国", "Germany"),
+    ("德國", "Germany"),
+    ("法国", "France"),
+    ("法國", "France"),
+    ("荷兰", "Netherlands"),
+    ("荷蘭", "Netherlands"),
+    ("俄罗斯", "Russia"),
+    ("俄羅斯", "Russia"),
+    ("加拿大", "Canada"),
+    ("澳大利亚", "Australia"),
+    ("澳洲", "Australia"),
+    // Ahead of "印度", which would otherwise match its first two characters
+    ("印度尼西亚", "Indonesia"),
+    ("印度", "India"),
+    ("土耳其", "Turkey"),
+    ("马来西亚", "Malaysia"),
+    ("泰国", "Thailand"),
+    ("越南", "Vietnam"),
+    ("菲律宾", "Philippines"),
+    ("印尼", "Indonesia"),
+    ("阿根廷", "Argentina"),
+    ("巴西", "Brazil"),
+];
+
+/// English names of the country codes of the regions in [`REGION_NAMES`],
+/// as GeoIP databases give them
+pub const REGION_CODES: &[(&str, &str)] = &[
+    ("HK", "Hong Kong"),
+    ("MO", "Macau"),
+    ("TW", "Taiwan"),
+    ("JP", "Japan"),
+    ("KR", "Korea"),
+    ("SG", "Singapore"),
+    ("US", "United States"),
+    ("GB", "United Kingdom"),
+    ("DE", "Germany"),
+    ("FR", "France"),
+    ("NL", "Netherlands"),
+    ("RU", "Russia"),
+    ("CA", "Canada"),
+    ("AU", "Australia"),
+    ("IN", "India"),
+    ("ID", "Indonesia"),
+    ("TR", "Turkey"),
+    ("MY", "Malaysia"),
+    ("TH", "Thailand"),
+    ("VN", "Vietnam"),
+    ("PH", "Philippines"),
+    ("AR", "Argentina"),
+    ("BR", "Brazil"),
+];
+
+/// ASCII form of a full-width or CJK punctuation character
+fn ascii_form(c: char) -> Option<char> {
+    match c {
+        // Full-width forms of the printable ASCII range
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21),
+        '\u{3000}' => Some(' '),
+        '【' | '〔' | '〖' | '「' | '『' => Some('['),
+        '】' | '〕' | '〗' | '」' | '』' => Some(']'),
+        '《' | '〈' => Some('<'),
+        '》' | '〉' => Some('>'),
+        '、' => Some(','),
+        '。' => Some('.'),
+        '—' | '–' => Some('-'),
+        '‘' | '’' => Some('\''),
+        '“' | '”' => Some('"'),
+        _ => None,
+    }
+}
+
+/// Replace full-width and CJK punctuation with its ASCII form
+pub fn normalize_punctuation(name: &str) -> String {
+    name.chars().map(|c| ascii_form(c).unwrap_or(c)).collect()
+}
+
+/// Replace known region names with their canonical English name
+///
+/// `extra` entries are tried before the built-in [`REGION_NAMES`], so they
+/// can override them; longer names win over shorter ones. ASCII names only
+/// match whole words, `HK` does not touch `HKBN`. The replacement is spaced
+/// from letters and digits around it: `香港01` becomes `Hong Kong 01`.
+pub fn transliterate_regions(name: &str, extra: &[(String, String)]) -> String {
+    let table = region_table(extra, false);
+
+    let mut result = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(c) = rest.chars().next() {
+        let Some((from, to)) = region_at(&table, result.chars().next_back(), rest) else {
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        if result
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            result.push(' ');
+        }
+        result.push_str(to);
+        rest = &rest[from.len()..];
+        if rest.chars().next().is_some_and(char::is_alphanumeric) {
+            result.push(' ');
+        }
+    }
+    result
+}
+
+/// English name of the first known region named in `name`
+///
+/// Names are matched like [`transliterate_regions`] matches them, and the
+/// English names themselves are recognized too: both `香港 01` and
+/// `Hong Kong 01` give `Hong Kong`.
+pub fn find_region(name: &str, extra: &[(String, String)]) -> Option<String> {
+    let table = region_table(extra, true);
+    let mut before = None;
+    for (index, c) in name.char_indices() {
+        if let Some((_, to)) = region_at(&table, before, &name[index..]) {
+            return Some(to.to_string());
+        }
+        before = Some(c);
+    }
+    None
+}
+
+/// English name of the region of a country code such as `HK`
+///
+/// `extra` entries naming the code are tried before [`REGION_CODES`]. A code
+/// neither knows is returned as is.
+pub fn region_of_code(code: &str, extra: &[(String, String)]) -> String {
+    extra
+        .iter()
+        .map(|(from, to)| (from.as_str(), to.as_str()))
+        .chain(REGION_CODES.iter().copied())
+        .find(|(from, to)| from.eq_ignore_ascii_case(code) && !to.is_empty())
+        .map_or_else(|| code.to_string(), |(_, to)| to.to_string())
+}
+
+/// Region names with their English name, longest first, optionally with
+/// each English name mapping to itself
+fn region_table(extra: &[(String, String)], canonical: bool) -> Vec<(&str, &str)> {
+    let mut table: Vec<(&str, &str)> = extra
+        .iter()
+        .filter(|(from, _)| !from.is_empty())
+        .map(|(from, to)| (from.as_str(), to.as_str()))
+        .chain(REGION_NAMES.iter().copied())
+        .collect();
+    if canonical {
+        let names: Vec<(&str, &str)> = table
+            .iter()
+            .filter(|(_, to)| !to.is_empty())
+            .map(|(_, to)| (*to, *to))
+            .collect();
+        table.extend(names);
+    }
+    // Stable, so user entries stay ahead of built-in ones of the same length
+    table.sort_by_key(|(from, _)| std::cmp::Reverse(from.chars().count()));
+    table
+}
+
+/// The entry of `table` that `rest` starts with, ASCII names only matching
+/// whole words given the character `before` it
+fn region_at<'a>(
+    table: &[(&'a str, &'a str)],
+    before: Option<char>,
+    rest: &str,
+) -> Option<(&'a str, &'a str)> {
+    table
+        .iter()
+        .find(|(from, _)| {
+            rest.starts_with(from)
+                && (!from.is_ascii()
+                    || !(before.is_some_and(is_ascii_word)
+                        || rest[from.len()..].chars().next().is_some_and(is_ascii_word)))
+        })
+        .copied()
+}
+
+fn is_ascii_word(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_transliterate() {
+        assert_eq!(
+            normalize_punctuation("🇭🇰【香港】ＩＥＰＬ　０１｜x2"),
+            "🇭🇰[香港]IEPL 01|x2"
+        );
+        // Joined emoji keep their joiner and variation selectors
+        assert_eq!(normalize_punctuation("🏳️‍🌈（Ｔｅｓｔ）"), "🏳️‍🌈(Test)");
+
+        assert_eq!(transliterate_regions("🇭🇰 香港01", &[]), "🇭🇰 Hong Kong 01");
+        assert_eq!(
+            transliterate_regions("[台灣] 新加坡-狮城", &[]),
+            "[Taiwan] Singapore-Singapore"
+        );
+        assert_eq!(transliterate_regions("印度尼西亚 01", &[]), "Indonesia 01");
+        assert_eq!(transliterate_regions("印度 01", &[]), "India 01");
+
+        let extra = vec![
+            ("HK".to_string(), "Hong Kong".to_string()),
+            ("香港".to_string(), "HK".to_string()),
+        ];
+        assert_eq!(transliterate_regions("HK 01", &extra), "Hong Kong 01");
+        assert_eq!(transliterate_regions("HKBN 01", &extra), "HKBN 01");
+        assert_eq!(transliterate_regions("香港 01", &extra), "HK 01");
+    }
+
+    #[test]
+    fn test_find_region() {
+        assert_eq!(find_region("🇭🇰 香港01", &[]).as_deref(), Some("Hong Kong"));
+        assert_eq!(
+            find_region("IEPL United States 02", &[]).as_deref(),
+            Some("United States")
+        );
+        assert_eq!(find_region("Premium 01", &[]), None);
+
+        let extra = vec![("HK".to_string(), "Hong Kong".to_string())];
+        assert_eq!(find_region("HK-01", &extra).as_deref(), Some("Hong Kong"));
+        assert_eq!(find_region("HKBN 01", &extra), None);
+    }
+
+    #[test]
+    fn test_region_of_code() {
+        assert_eq!(region_of_code("JP", &[]), "Japan");
+        assert_eq!(region_of_code("ZZ", &[]), "ZZ");
+        let extra = vec![("jp".to_string(), "Nippon".to_string())];
+        assert_eq!(region_of_code("JP", &extra), "Nippon");
+    }
+}
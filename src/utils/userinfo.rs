@@ -0,0 +1,149 @@
+//! Traffic and expiry reported by subscription providers
+//!
+//! Providers send them in a `subscription-userinfo` response header, e.g.
+//! `upload=455727941; download=6174315083; total=1073741824000; expire=1671815872`.
+//! Fields may be missing or come in any order, some providers send the expiry
+//! as a float. Whatever parses is kept, the rest is ignored.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Name of the response header carrying the info
+pub const USERINFO_HEADER: &str = "subscription-userinfo";
+
+/// Traffic in bytes and expiry as a Unix timestamp of a subscription
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubscriptionUserInfo {
+    pub upload: Option<u64>,
+    pub download: Option<u64>,
+    pub total: Option<u64>,
+    pub expire: Option<u64>,
+}
+
+impl SubscriptionUserInfo {
+    /// Parse a header value, `None` when no field could be read
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut info = SubscriptionUserInfo::default();
+        for item in value.split([';', ',']) {
+            let Some((key, value)) = item.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let number = value.parse::<u64>().ok().or_else(|| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v >= 0.0)
+                    .map(|v| v as u64)
+            });
+            let slot = match key.trim().to_ascii_lowercase().as_str() {
+                "upload" => &mut info.upload,
+                "download" => &mut info.download,
+                "total" => &mut info.total,
+                "expire" => &mut info.expire,
+                _ => continue,
+            };
+            match number {
+                Some(number) => *slot = Some(number),
+                None => log::debug!("Ignoring invalid subscription userinfo field '{}'", item),
+            }
+        }
+        (!info.is_empty()).then_some(info)
+    }
+
+    /// Info of a response, `None` when the header is absent or unreadable
+    pub fn from_headers(headers: &HashMap<String, String>) -> Option<Self> {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(USERINFO_HEADER))
+            .and_then(|(_, value)| Self::parse(value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upload.is_none()
+            && self.download.is_none()
+            && self.total.is_none()
+            && self.expire.is_none()
+    }
+
+    /// Traffic left, when the total is known
+    pub fn remaining(&self) -> Option<u64> {
+        let used = self
+            .upload
+            .unwrap_or(0)
+            .saturating_add(self.download.unwrap_or(0));
+        self.total.map(|total| total.saturating_sub(used))
+    }
+}
+
+/// Header value with the known fields, e.g. `upload=1; download=2; total=3`
+impl fmt::Display for SubscriptionUserInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            ("upload", self.upload),
+            ("download", self.download),
+            ("total", self.total),
+            ("expire", self.expire),
+        ];
+        let mut first = true;
+        for (key, value) in fields {
+            let Some(value) = value else {
+                continue;
+            };
+            if !first {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}={}", key, value)?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subscription_userinfo() {
+        let info = SubscriptionUserInfo::parse(
+            "upload=455727941; download=6174315083; total=1073741824000; expire=1671815872",
+        )
+        .unwrap();
+        assert_eq!(info.upload, Some(455727941));
+        assert_eq!(
+            info.remaining(),
+            Some(1073741824000 - 455727941 - 6174315083)
+        );
+        assert_eq!(
+            info.to_string(),
+            "upload=455727941; download=6174315083; total=1073741824000; expire=1671815872"
+        );
+
+        // Partial and sloppy values keep what parses
+        let info =
+            SubscriptionUserInfo::parse("Total=1024;expire=1671815872.5; upload=n/a").unwrap();
+        assert_eq!(
+            info,
+            SubscriptionUserInfo {
+                total: Some(1024),
+                expire: Some(1671815872),
+                ..Default::default()
+            }
+        );
+        assert_eq!(info.to_string(), "total=1024; expire=1671815872");
+
+        assert_eq!(SubscriptionUserInfo::parse(""), None);
+        assert_eq!(SubscriptionUserInfo::parse("upload=; plan=pro"), None);
+
+        let headers = HashMap::from([(
+            "Subscription-Userinfo".to_string(),
+            "download=1".to_string(),
+        )]);
+        assert_eq!(
+            SubscriptionUserInfo::from_headers(&headers).and_then(|info| info.download),
+            Some(1)
+        );
+        assert_eq!(SubscriptionUserInfo::from_headers(&HashMap::new()), None);
+    }
+}
@@ -60,7 +60,7 @@ pub async fn simple_handler(
     // Set appropriate target based on path
     match target_type.as_str() {
         "clash" | "clashr" | "surge" | "quan" | "quanx" | "loon" | "ss" | "ssr" | "ssd"
-        | "v2ray" | "trojan" | "mixed" | "singbox" => {
+        | "v2ray" | "trojan" | "mixed" | "singbox" | "json" => {
             // Create a modified query with the target set
             let mut modified_query = query.into_inner();
             modified_query.target = Some(target_type.clone());